@@ -15,7 +15,7 @@
 
 use std::{
 	path::{Path, PathBuf},
-	sync::Arc,
+	sync::{Arc, LazyLock},
 };
 
 use anyhow::{Context, Result, anyhow};
@@ -23,10 +23,16 @@ use filen_cli::{ClientConfigArgs, deserialize_auth_config, serialize_auth_config
 use filen_sdk_rs::{
 	ErrorKind,
 	auth::{Client, unauth::UnauthClient},
+	cache::{CacheMessage, ResyncProgress},
 };
 
 use crate::{CliConfig, ui::UI, util::LongKeyringEntry};
 
+/// Progress of the cache's resyncs, for commands that wait until the cache holds a complete listing
+pub(crate) static CACHE_RESYNC_PROGRESS_TX: LazyLock<
+	tokio::sync::broadcast::Sender<ResyncProgress>,
+> = LazyLock::new(|| tokio::sync::broadcast::channel(64).0);
+
 /// A lazily authenticated client.
 /// Since some commands (e. g. logout) don't need the user to be authenticated, we only authenticate when necessary.
 pub(crate) enum LazyClient {
@@ -84,7 +90,14 @@ impl LazyClient {
 				)
 				.await?;
 				client
-					.configure_cache(config.config_dir.join("filen-sdk-rs-cache"), |_| {})
+					.configure_cache(config.config_dir.join("filen-sdk-rs-cache"), |messages| {
+						for message in messages {
+							if let CacheMessage::ResyncProgress(progress) = message {
+								// fails when nobody is waiting, which is fine
+								let _ = CACHE_RESYNC_PROGRESS_TX.send(progress);
+							}
+						}
+					})
 					.await
 					.context("Failed to configure cache")?;
 				*self = Self::Authenticated {
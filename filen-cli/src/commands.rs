@@ -1,3 +1,5 @@
+use std::{path::PathBuf, sync::Arc};
+
 use anyhow::{Context, Result};
 use clap::Subcommand;
 use console::style;
@@ -9,6 +11,10 @@ use filen_sdk_rs::{
 		categories::{DirType, NonRootFileType, Normal},
 		file::traits::HasFileInfo as _,
 	},
+	http_provider::{
+		s3::{S3Credentials, S3Options},
+		webdav::{WebDavCredentials, WebDavOptions},
+	},
 	io::{RemoteDirectory, RemoteFile, client_impl::IoSharedClientExt},
 };
 use serde_json::json;
@@ -18,8 +24,14 @@ use crate::{
 	auth::{self, LazyClient, export_auth_config},
 	completion::FilenCompleter,
 	docs::{print_in_app_docs, serve_markdown_docs_as_html},
+	notes_cmd::{self, NotesCommand},
+	sharing_cmd::{self, LinkCommand, ShareCommand},
+	storage_cmd::{self, DuSort},
+	sync_cmd::{self, SyncDirection, SyncOptions},
+	transfer_cmd::{self, ExistingFiles},
 	ui::{self, UI},
 	util::RemotePath,
+	versions_cmd::{self, VersionsCommand},
 };
 
 #[derive(Debug, Subcommand)]
@@ -108,8 +120,78 @@ pub(crate) enum Commands {
 		#[arg(add = FilenCompleter::directory())]
 		destination: String,
 	},
+	/// Upload a local file or directory (recursively)
+	Upload {
+		/// Local file or directory to upload
+		local: PathBuf,
+		/// Destination parent directory (default: the current working directory)
+		#[arg(add = FilenCompleter::directory())]
+		remote: Option<String>,
+		/// What to do with files that already exist in the destination
+		#[arg(long, value_enum, default_value_t = ExistingFiles::Overwrite)]
+		existing: ExistingFiles,
+	},
+	/// Download a file or directory (recursively)
+	Download {
+		/// File or directory to download ("/" for the entire Filen drive)
+		#[arg(add = FilenCompleter::file_or_directory())]
+		remote: String,
+		/// Local destination directory (default: the current directory)
+		local: Option<PathBuf>,
+		/// What to do with files that already exist in the destination
+		#[arg(long, value_enum, default_value_t = ExistingFiles::Overwrite)]
+		existing: ExistingFiles,
+	},
+	/// Sync a local directory with a directory in the drive
+	Sync {
+		/// Local directory to sync
+		local: PathBuf,
+		/// Directory in the drive to sync with
+		#[arg(add = FilenCompleter::directory())]
+		remote: String,
+		/// Which way to sync
+		#[arg(long, value_enum, default_value_t = SyncDirection::TwoWay)]
+		mode: SyncDirection,
+		/// Keep running and sync whenever either side changes
+		#[arg(long)]
+		watch: bool,
+		/// Only print the planned operations, without changing anything
+		#[arg(long, conflicts_with = "watch")]
+		dry_run: bool,
+	},
 	/// Search for a file or directory interactively
 	Search,
+	/// Find files and directories by name, type, size or modification time
+	Find {
+		/// Directory to search in (default: the working directory)
+		#[arg(add = FilenCompleter::directory())]
+		directory: Option<String>,
+		#[command(flatten)]
+		options: crate::search_cmd::FindOptions,
+	},
+	/// Show the size of a directory and of the directories below it
+	Du {
+		/// Directory to sum up (default: the working directory)
+		#[arg(add = FilenCompleter::directory())]
+		directory: Option<String>,
+		/// Only show directories up to this many levels below it
+		#[arg(short = 'd', long)]
+		depth: Option<usize>,
+		/// Order of the directories
+		#[arg(long, value_enum, default_value_t = DuSort::Name)]
+		sort: DuSort,
+	},
+	/// Print the files and directories below a directory as a tree
+	Tree {
+		/// Directory to print (default: the working directory)
+		#[arg(add = FilenCompleter::directory())]
+		directory: Option<String>,
+		/// Only descend this many levels deep
+		#[arg(short = 'L', long)]
+		level: Option<usize>,
+	},
+	/// Show a breakdown of the used storage
+	Quota,
 	/// Favorite a file or directory
 	Favorite {
 		/// File or directory to favorite
@@ -122,6 +204,37 @@ pub(crate) enum Commands {
 		#[arg(add = FilenCompleter::file_or_directory())]
 		file_or_directory: String,
 	},
+	/// List the versions of a file, or manage file versions
+	#[command(
+		disable_help_subcommand = true,
+		args_conflicts_with_subcommands = true,
+		subcommand_negates_reqs = true
+	)]
+	Versions {
+		/// File to list the versions of
+		#[arg(required = true, add = FilenCompleter::file())]
+		file: Option<String>,
+		#[command(subcommand)]
+		command: Option<VersionsCommand>,
+	},
+	/// Manage the public link of a file or directory
+	#[command(disable_help_subcommand = true)]
+	Link {
+		#[command(subcommand)]
+		command: LinkCommand,
+	},
+	/// Share files and directories with your contacts
+	#[command(disable_help_subcommand = true)]
+	Share {
+		#[command(subcommand)]
+		command: ShareCommand,
+	},
+	/// Read, write and organize your notes
+	#[command(disable_help_subcommand = true)]
+	Notes {
+		#[command(subcommand)]
+		command: NotesCommand,
+	},
 	/// List trashed items with option to restore or permanently delete them
 	ListTrash,
 	/// Permanently delete all trashed items
@@ -147,9 +260,9 @@ pub(crate) enum Commands {
 		/// Additional arguments to Rclone
 		rclone_args: Vec<String>,
 	},
-	/// Runs a WebDAV, FTP, SFTP or HTTP server exposing your Filen drive
+	/// Runs a WebDAV, FTP, SFTP, HTTP or S3 server exposing your Filen drive
 	Serve {
-		/// The type of server to run: webdav, ftp, sftp, http
+		/// The type of server to run: webdav, ftp, sftp, http, s3
 		server: String,
 		/// IP and port for the server (`<ip>:<port>` or `:<port>`)
 		#[arg(long = "addr", default_value = ":80")]
@@ -158,13 +271,16 @@ pub(crate) enum Commands {
 		#[arg(long, add = FilenCompleter::directory())]
 		root: Option<String>,
 		/// Username for authentication to the server (default: no authentication).
-		/// On S3 servers, this is the Access Key ID.
+		/// On S3 servers, this is the Access Key ID (default: "user").
 		#[arg(long)]
 		user: Option<String>,
 		/// Password for authentication to the server (default: no authentication).
-		/// On S3 servers, this is the Secret Access Key.
+		/// On S3 servers, this is the Secret Access Key (default: "password").
 		#[arg(long)]
 		password: Option<String>,
+		/// Serve S3 without authentication, so anyone who can reach the server has access to the drive
+		#[arg(long, conflicts_with_all = ["user", "password"])]
+		no_auth: bool,
 		/// The server is read-only
 		#[arg(long)]
 		read_only: bool,
@@ -177,7 +293,6 @@ pub(crate) enum Commands {
 		/// Additional arguments to Rclone
 		rclone_args: Vec<String>,
 	},
-	// todo: s3 server
 	/// Exports your user API key (for use with non-managed Rclone)
 	ExportApiKey,
 	/// View the documentation (same as --help) locally in a browser rendered as HTML
@@ -273,7 +388,66 @@ pub(crate) async fn execute_command(
 			.await?;
 			None
 		}
+		Commands::Upload {
+			local,
+			remote,
+			existing,
+		} => {
+			transfer_cmd::upload(ui, client, working_path, &local, remote, existing).await?;
+			None
+		}
+		Commands::Download {
+			remote,
+			local,
+			existing,
+		} => {
+			transfer_cmd::download(ui, client, working_path, &remote, local, existing).await?;
+			None
+		}
+		Commands::Sync {
+			local,
+			remote,
+			mode,
+			watch,
+			dry_run,
+		} => {
+			sync_cmd::sync(
+				config,
+				ui,
+				client,
+				working_path,
+				local,
+				&remote,
+				SyncOptions {
+					mode,
+					watch,
+					dry_run,
+				},
+			)
+			.await?;
+			None
+		}
 		Commands::Search => crate::search_cmd::search_cmd(ui, client, working_path).await?,
+		Commands::Find { directory, options } => {
+			crate::search_cmd::find(ui, client, working_path, directory, options).await?;
+			None
+		}
+		Commands::Du {
+			directory,
+			depth,
+			sort,
+		} => {
+			storage_cmd::du(ui, client, working_path, directory, depth, sort).await?;
+			None
+		}
+		Commands::Tree { directory, level } => {
+			storage_cmd::tree(ui, client, working_path, directory, level).await?;
+			None
+		}
+		Commands::Quota => {
+			storage_cmd::quota(ui, client).await?;
+			None
+		}
 		Commands::Favorite { file_or_directory } => {
 			set_file_or_directory_favorite(ui, client, working_path, &file_or_directory, true)
 				.await?;
@@ -284,6 +458,22 @@ pub(crate) async fn execute_command(
 				.await?;
 			None
 		}
+		Commands::Versions { file, command } => {
+			versions_cmd::versions(ui, client, working_path, file, command).await?;
+			None
+		}
+		Commands::Link { command } => {
+			sharing_cmd::link(ui, client, working_path, command).await?;
+			None
+		}
+		Commands::Share { command } => {
+			sharing_cmd::share(ui, client, working_path, command).await?;
+			None
+		}
+		Commands::Notes { command } => {
+			notes_cmd::notes(ui, client, command).await?;
+			None
+		}
 		Commands::ListTrash => {
 			list_trash(ui, client).await?;
 			None
@@ -332,6 +522,7 @@ pub(crate) async fn execute_command(
 			root,
 			user,
 			password,
+			no_auth,
 			read_only,
 			cache_size,
 			transfers,
@@ -350,24 +541,46 @@ pub(crate) async fn execute_command(
 					)));
 				}
 			};
-			rclone::start_server(
-				config,
-				ui,
-				client,
-				&server,
-				display_server_type,
-				BasicServerOptions {
-					address,
+			if server == "webdav" || server == "s3" {
+				if cache_size.is_some() || transfers.is_some() || !rclone_args.is_empty() {
+					ui.print_warning(&format!(
+						"--cache-size, --transfers and Rclone arguments are ignored by the built-in {} server",
+						display_server_type
+					));
+				}
+				serve_natively(
+					ui,
+					client,
+					&server,
+					display_server_type,
+					&address,
 					root,
 					user,
 					password,
+					no_auth,
 					read_only,
-					cache_size,
-					transfers,
-				},
-				rclone_args,
-			)
-			.await?;
+				)
+				.await?;
+			} else {
+				rclone::start_server(
+					config,
+					ui,
+					client,
+					&server,
+					display_server_type,
+					BasicServerOptions {
+						address,
+						root,
+						user,
+						password,
+						read_only,
+						cache_size,
+						transfers,
+					},
+					rclone_args,
+				)
+				.await?;
+			}
 			None
 		}
 		Commands::ExportApiKey => {
@@ -868,10 +1081,21 @@ async fn move_or_copy_file_or_directory(
 		},
 		MoveOrCopy::Copy => match source_file_or_directory {
 			NonRootFileType::File(file) => {
-				copy_file(client, file.as_ref(), &destination_dir).await?;
+				let bar = ui.progress_bar(file.name().unwrap_or_default(), file.size());
+				let bar_callback = bar.clone();
+				let result = client
+					.copy_file(
+						&file,
+						&destination_dir,
+						None,
+						Some(Arc::new(move |bytes| bar_callback.inc(bytes))),
+					)
+					.await;
+				bar.finish();
+				result.context("Failed to copy file")?;
 			}
 			NonRootFileType::Dir(dir) => {
-				copy_dir_recursive(client, dir.as_ref(), &destination_dir).await?;
+				transfer_cmd::copy_dir(ui, client, &dir, &destination_dir).await?;
 			}
 			NonRootFileType::Root(_) => {
 				return Err(UI::failure("Cannot copy root directory"));
@@ -890,73 +1114,6 @@ async fn move_or_copy_file_or_directory(
 	Ok(())
 }
 
-async fn copy_file(
-	client: &Client,
-	file: &RemoteFile,
-	destination_dir: &DirType<'_, Normal>,
-) -> Result<RemoteFile> {
-	let name = file.name().context("Failed to decrypt file name")?;
-	let mut builder = client
-		.make_file_builder(name, destination_dir.uuid())
-		.context("Failed to prepare file copy")?;
-	if let Some(mime) = file.mime() {
-		builder = builder.mime(mime.to_string());
-	}
-	if let Some(created) = file.created() {
-		builder = builder.created(created);
-	}
-	if let Some(modified) = file.last_modified() {
-		builder = builder.modified(modified);
-	}
-	let data = client
-		.download_file(file)
-		.await
-		.context("Failed to download file for copying")?;
-	// todo: does this consume too much memory for large files? maybe we should stream the data
-	client
-		.upload_file(builder, &data)
-		.await
-		.context("Failed to upload copied file")
-}
-
-async fn copy_dir_recursive(
-	client: &Client,
-	source_dir: &RemoteDirectory,
-	destination_parent: &DirType<'_, Normal>,
-) -> Result<RemoteDirectory> {
-	let name = source_dir
-		.name()
-		.context("Failed to decrypt directory name")?;
-	let new_dir = client
-		.create_dir(destination_parent, name)
-		.await
-		.context("Failed to create destination directory for copying")?;
-	let (subdirs, files) = client
-		.list_dir::<_, Normal>(
-			&DirType::Dir(std::borrow::Cow::Borrowed(source_dir)),
-			None::<&fn(u64, Option<u64>)>,
-		)
-		.await
-		.context("Failed to list source directory for copying")?;
-	for file in &files {
-		copy_file(
-			client,
-			file,
-			&DirType::Dir(std::borrow::Cow::Borrowed(&new_dir)),
-		)
-		.await?;
-	}
-	for subdir in &subdirs {
-		Box::pin(copy_dir_recursive(
-			client,
-			subdir,
-			&DirType::Dir(std::borrow::Cow::Borrowed(&new_dir)),
-		))
-		.await?;
-	}
-	Ok(new_dir)
-}
-
 async fn set_file_or_directory_favorite(
 	ui: &mut UI,
 	client: &mut LazyClient,
@@ -1024,6 +1181,97 @@ async fn empty_trash(ui: &mut UI, client: &mut LazyClient) -> Result<()> {
 	Ok(())
 }
 
+/// Runs the SDK's WebDAV or S3 server until the CLI is killed.
+#[allow(clippy::too_many_arguments)]
+async fn serve_natively(
+	ui: &mut UI,
+	client: &mut LazyClient,
+	server_type: &str,
+	display_server_type: &str,
+	address: &str,
+	root: Option<String>,
+	user: Option<String>,
+	password: Option<String>,
+	no_auth: bool,
+	read_only: bool,
+) -> Result<()> {
+	let client = client.get(ui).await?;
+	// like Rclone, `:<port>` listens on all interfaces
+	let (bind_address, display_address) = match address.strip_prefix(':') {
+		Some(port) => (format!("0.0.0.0:{port}"), format!("127.0.0.1:{port}")),
+		None => (address.to_string(), address.to_string()),
+	};
+	let bind_address = bind_address
+		.parse()
+		.with_context(|| format!("Invalid server address: {}", address))?;
+	// like the Rclone setup, S3 servers always require authentication unless opted out of
+	let credentials = (!no_auth && (server_type == "s3" || user.is_some() || password.is_some()))
+		.then(|| {
+			(
+				user.unwrap_or("user".to_string()),
+				password.unwrap_or("password".to_string()),
+			)
+		});
+	let server = if server_type == "s3" {
+		client
+			.clone()
+			.start_s3_server(
+				bind_address,
+				S3Options {
+					root,
+					credentials: credentials
+						.clone()
+						.map(|(access_key_id, secret_access_key)| S3Credentials {
+							access_key_id,
+							secret_access_key,
+						}),
+					read_only,
+				},
+			)
+			.await
+	} else {
+		client
+			.clone()
+			.start_webdav_server(
+				bind_address,
+				WebDavOptions {
+					root,
+					credentials: credentials
+						.clone()
+						.map(|(user, password)| WebDavCredentials { user, password }),
+					read_only,
+				},
+			)
+			.await
+	}
+	.with_context(|| format!("Failed to start {} server", display_server_type))?;
+	ui.print_success(&format!(
+		"Started {} server on http://{} {} (kill the CLI to stop)",
+		display_server_type,
+		display_address,
+		if let Some((user, password)) = &credentials {
+			if server_type == "s3" {
+				format!(
+					"with Access Key ID \"{}\" and Secret Access Key \"{}\"",
+					user, password
+				)
+			} else {
+				format!("with username \"{}\" and password \"{}\"", user, password)
+			}
+		} else {
+			"without authentication".to_string()
+		}
+	));
+	let mut stop_rx = crate::CTRLC_TX.subscribe();
+	let _ = stop_rx.recv().await;
+	ui.print_muted(&format!("Stopping {} server...", display_server_type));
+	server
+		.stop()
+		.await
+		.with_context(|| format!("Failed to stop {} server", display_server_type))?;
+	Ok(())
+}
+
 mod rclone {
 	//! [cli-doc] managed-rclone
 	//! The Filen CLI includes a managed installation of Rclone, which can be used to [access Filen](https://rclone.org/filen).
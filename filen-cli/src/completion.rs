@@ -80,8 +80,16 @@ impl FilenCompleter {
 			working_path: working_path.clone(),
 			await_result,
 		};
+		Self::initialize_completers_in_subcommands(command, &context)
+	}
+
+	/// Recurses into nested subcommands, like the ones of `link`
+	fn initialize_completers_in_subcommands(
+		command: clap::Command,
+		context: &CompleterContext,
+	) -> clap::Command {
 		command.mut_subcommands(|subcommand| {
-			subcommand.mut_args(|arg| {
+			let subcommand = subcommand.mut_args(|arg| {
 				if let Some(completer) = arg.get::<ArgValueCompleter>()
 					&& let Some(Some(completion)) = completer
 						.complete(&OsStr::default())
@@ -96,7 +104,8 @@ impl FilenCompleter {
 				} else {
 					arg
 				}
-			})
+			});
+			Self::initialize_completers_in_subcommands(subcommand, context)
 		})
 	}
 }
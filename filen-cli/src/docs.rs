@@ -13,7 +13,10 @@ use clap::{CommandFactory, builder::Styles};
 use filen_macros::extract_cli_doc_fragments;
 use tiny_http::{Header, Response};
 
-use crate::{CliArgs, ui::UI};
+use crate::{
+	CliArgs,
+	ui::{self, UI},
+};
 
 extract_cli_doc_fragments!();
 
@@ -85,16 +88,62 @@ static PARSED_DOC_OUTLINE: LazyLock<Result<Vec<ParsedDocSection>>> = LazyLock::n
 				DocElement::CommandHelp("tail"),
 				DocElement::CommandHelp("stat"),
 				DocElement::CommandHelp("search"),
+				DocElement::CommandHelp("find"),
 				DocElement::CommandHelp("mkdir"),
 				DocElement::CommandHelp("rm"),
 				DocElement::CommandHelp("mv"),
 				DocElement::CommandHelp("cp"),
+				DocElement::CommandHelp("upload"),
+				DocElement::CommandHelp("download"),
 				DocElement::CommandHelp("favorite"),
 				DocElement::CommandHelp("unfavorite"),
 				DocElement::CommandHelp("list-trash"),
 				DocElement::CommandHelp("empty-trash"),
 			],
 		},
+		DocSection {
+			id: "sync",
+			title: "Syncing",
+			elements: vec![
+				DocElement::DocFragment("sync"),
+				DocElement::CommandHelp("sync"),
+			],
+		},
+		DocSection {
+			id: "versions",
+			title: "File Versions",
+			elements: vec![
+				DocElement::DocFragment("versions"),
+				DocElement::CommandHelp("versions"),
+			],
+		},
+		DocSection {
+			id: "storage",
+			title: "Storage Usage",
+			elements: vec![
+				DocElement::DocFragment("storage"),
+				DocElement::CommandHelp("du"),
+				DocElement::CommandHelp("tree"),
+				DocElement::CommandHelp("quota"),
+			],
+		},
+		DocSection {
+			id: "sharing",
+			title: "Links and Sharing",
+			elements: vec![
+				DocElement::DocFragment("sharing"),
+				DocElement::CommandHelp("link"),
+				DocElement::CommandHelp("share"),
+			],
+		},
+		DocSection {
+			id: "notes",
+			title: "Notes",
+			elements: vec![
+				DocElement::DocFragment("notes"),
+				DocElement::CommandHelp("notes"),
+			],
+		},
 		DocSection {
 			id: "managed-rclone",
 			title: "Managed Rclone",
@@ -318,12 +367,18 @@ fn format_markdown_command_help(cmd: &mut clap::Command) -> String {
 			}
 		})
 		.collect::<Vec<_>>();
-	format!(
+	std::iter::once(format!(
 		"> `{}`  \n> {}{}",
 		usage.trim(),
 		about.trim(),
 		args.join("")
+	))
+	.chain(
+		ui::subcommands_with_full_names(cmd)
+			.map(|mut subcommand| format_markdown_command_help(&mut subcommand)),
 	)
+	.collect::<Vec<_>>()
+	.join("\n\n")
 }
 
 // in-app docs
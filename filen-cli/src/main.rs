@@ -26,10 +26,16 @@ mod auth;
 mod commands;
 mod completion;
 mod docs;
+mod notes_cmd;
 mod search_cmd;
+mod sharing_cmd;
+mod storage_cmd;
+mod sync_cmd;
+mod transfer_cmd;
 mod ui;
 mod updater;
 mod util;
+mod versions_cmd;
 
 #[derive(Debug, Parser)]
 #[clap(
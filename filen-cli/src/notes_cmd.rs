@@ -0,0 +1,591 @@
+//! [cli-doc] notes
+//! Notes are identified by their title or, if several notes share a title, by their UUID (see `notes list`).
+//! Use `notes show` to print a note and `notes edit` to open it in your editor (`$VISUAL` or `$EDITOR`, default: `vi` or `notepad`);
+//! the note is only saved if you changed it. `notes new` creates a note, `--type` sets the type of a new or edited note.
+//!
+//! Every edit is kept in the note's history: `notes history` lists the versions and `notes restore --version` brings one back.
+//! `notes trash` moves a note to the trash and `notes restore` moves it out again.
+
+use std::str::FromStr as _;
+
+use anyhow::{Context, Result};
+use filen_sdk_rs::{auth::Client, notes::Note};
+use filen_types::{api::v3::notes::NoteType, fs::Uuid};
+use serde_json::json;
+
+use crate::{
+	auth::LazyClient,
+	ui::{self, UI},
+};
+
+/// How many characters of a note's content the preview shown in note lists holds
+const PREVIEW_LENGTH: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum NoteTypeArg {
+	Text,
+	Md,
+	Code,
+	Rich,
+	Checklist,
+}
+
+impl From<NoteTypeArg> for NoteType {
+	fn from(note_type: NoteTypeArg) -> Self {
+		match note_type {
+			NoteTypeArg::Text => NoteType::Text,
+			NoteTypeArg::Md => NoteType::Md,
+			NoteTypeArg::Code => NoteType::Code,
+			NoteTypeArg::Rich => NoteType::Rich,
+			NoteTypeArg::Checklist => NoteType::Checklist,
+		}
+	}
+}
+
+fn note_type_label(note_type: NoteType) -> &'static str {
+	match note_type {
+		NoteType::Text => "text",
+		NoteType::Md => "md",
+		NoteType::Code => "code",
+		NoteType::Rich => "rich",
+		NoteType::Checklist => "checklist",
+	}
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub(crate) enum NotesCommand {
+	/// List your notes (default: the ones that are neither trashed nor archived)
+	List {
+		/// Only list trashed notes
+		#[arg(long, conflicts_with = "archived")]
+		trashed: bool,
+		/// Only list archived notes
+		#[arg(long)]
+		archived: bool,
+		/// Only list notes with this tag
+		#[arg(long)]
+		tag: Option<String>,
+	},
+	/// Print the content of a note
+	Show {
+		/// Title or UUID of the note
+		note: String,
+	},
+	/// Edit a note in your editor
+	Edit {
+		/// Title or UUID of the note
+		note: String,
+		/// Change the note's type
+		#[arg(long = "type", value_enum)]
+		note_type: Option<NoteTypeArg>,
+	},
+	/// Create a new note
+	New {
+		/// Title of the note (default: the current date and time)
+		title: Option<String>,
+		/// Type of the note
+		#[arg(long = "type", value_enum, default_value_t = NoteTypeArg::Text)]
+		note_type: NoteTypeArg,
+		/// Open the new note in your editor
+		#[arg(long)]
+		edit: bool,
+	},
+	/// Add tags to a note, creating tags that don't exist yet
+	Tag {
+		/// Title or UUID of the note
+		note: String,
+		/// Names of the tags
+		#[arg(required = true)]
+		tags: Vec<String>,
+		/// Remove the tags from the note instead
+		#[arg(long)]
+		remove: bool,
+	},
+	/// List the versions in a note's history
+	History {
+		/// Title or UUID of the note
+		note: String,
+	},
+	/// Restore a note from the trash or archive, or an earlier version of it
+	Restore {
+		/// Title or UUID of the note
+		note: String,
+		/// ID of the version to restore (see `notes history`)
+		#[arg(long)]
+		version: Option<u64>,
+	},
+	/// Move a note to the trash
+	Trash {
+		/// Title or UUID of the note
+		note: String,
+		/// Permanently delete the note (default: move to trash)
+		#[arg(short, long)]
+		permanent: bool,
+	},
+}
+
+pub(crate) async fn notes(
+	ui: &mut UI,
+	client: &mut LazyClient,
+	command: NotesCommand,
+) -> Result<()> {
+	let client = client.get(ui).await?;
+	match command {
+		NotesCommand::List {
+			trashed,
+			archived,
+			tag,
+		} => {
+			let mut notes = client
+				.list_notes()
+				.await
+				.context("Failed to list notes")?
+				.into_iter()
+				.filter(|note| note.trashed() == trashed && note.archived() == archived)
+				.filter(|note| {
+					tag.as_ref().is_none_or(|tag| {
+						note.tags().iter().any(|t| t.name() == Some(tag.as_str()))
+					})
+				})
+				.collect::<Vec<_>>();
+			notes.sort_by(|a, b| {
+				b.pinned()
+					.cmp(&a.pinned())
+					.then_with(|| b.edited().cmp(&a.edited()))
+			});
+			print_notes(ui, &notes)?;
+		}
+		NotesCommand::Show { note } => {
+			let mut note = find_note(client, &note).await?;
+			let content = get_content(client, &mut note).await?;
+			if ui.json {
+				let mut json = note_json(&note);
+				json["content"] = json!(content);
+				ui.print_json(json)?;
+			} else {
+				ui.print(&content);
+			}
+		}
+		NotesCommand::Edit { note, note_type } => {
+			let mut note = find_note(client, &note).await?;
+			let content = get_content(client, &mut note).await?;
+			edit_note(
+				ui,
+				client,
+				&mut note,
+				&content,
+				note_type.map(NoteType::from),
+			)
+			.await?;
+		}
+		NotesCommand::New {
+			title,
+			note_type,
+			edit,
+		} => {
+			let mut note = client
+				.create_note(title)
+				.await
+				.context("Failed to create note")?;
+			let note_type = NoteType::from(note_type);
+			if edit {
+				edit_note(ui, client, &mut note, "", Some(note_type)).await?;
+			} else {
+				if note_type != note.note_type() {
+					client
+						.set_note_type(&mut note, note_type, Some(""))
+						.await
+						.context("Failed to set note type")?;
+				}
+				if ui.json {
+					ui.print_json(note_json(&note))?;
+				} else {
+					ui.print_success(&format!("Created note: {}", note_label(&note)));
+				}
+			}
+		}
+		NotesCommand::Tag { note, tags, remove } => {
+			let mut note = find_note(client, &note).await?;
+			for name in &tags {
+				if remove {
+					let Some(tag) = note
+						.tags()
+						.iter()
+						.find(|tag| tag.name() == Some(name.as_str()))
+						.cloned()
+					else {
+						return Err(UI::failure(&format!(
+							"Note {} is not tagged #{}",
+							note_label(&note),
+							name
+						)));
+					};
+					client
+						.remove_tag_from_note(&mut note, &tag)
+						.await
+						.context("Failed to remove tag from note")?;
+				} else {
+					let mut tag = client
+						.create_note_tag(name.clone())
+						.await
+						.context("Failed to create note tag")?;
+					client
+						.add_tag_to_note(&mut note, &mut tag)
+						.await
+						.context("Failed to tag note")?;
+				}
+			}
+			if ui.json {
+				ui.print_json(note_json(&note))?;
+			} else {
+				ui.print_success(&format!(
+					"{} note {}: {}",
+					if remove { "Untagged" } else { "Tagged" },
+					note_label(&note),
+					tags.iter()
+						.map(|tag| format!("#{}", tag))
+						.collect::<Vec<_>>()
+						.join(", ")
+				));
+			}
+		}
+		NotesCommand::History { note } => {
+			let note = find_note(client, &note).await?;
+			let history = client
+				.get_note_history(&note)
+				.await
+				.context("Failed to get note history")?;
+			if ui.json {
+				ui.print_json(json!(
+					history
+						.iter()
+						.map(|version| json!({
+							"id": version.id(),
+							"edited": version.edited(),
+							"type": note_type_label(version.note_type()),
+							"preview": version.preview(),
+						}))
+						.collect::<Vec<_>>()
+				))?;
+			} else if history.is_empty() {
+				ui.print_muted(&format!("Note {} has no history", note_label(&note)));
+			} else {
+				for version in &history {
+					ui.print(&format!(
+						"{}  {}  {}",
+						console::style(version.id()).bold(),
+						console::style(format!(
+							"{} · {}",
+							ui::format_date(&version.edited()),
+							note_type_label(version.note_type())
+						))
+						.dim(),
+						version
+							.preview()
+							.unwrap_or("(preview could not be decrypted)")
+					));
+				}
+			}
+		}
+		NotesCommand::Restore { note, version } => {
+			let mut note = find_note(client, &note).await?;
+			if let Some(version) = version {
+				let version = client
+					.get_note_history(&note)
+					.await
+					.context("Failed to get note history")?
+					.into_iter()
+					.find(|v| v.id() == version)
+					.ok_or_else(|| {
+						UI::failure(&format!(
+							"No version {} in the history of note {} (see `notes history`)",
+							version,
+							note_label(&note)
+						))
+					})?;
+				client
+					.restore_note_from_history(&mut note, version)
+					.await
+					.context("Failed to restore note version")?;
+			} else {
+				if !note.trashed() && !note.archived() {
+					return Err(UI::failure(&format!(
+						"Note {} is neither trashed nor archived",
+						note_label(&note)
+					)));
+				}
+				client
+					.restore_note(&mut note)
+					.await
+					.context("Failed to restore note")?;
+			}
+			if ui.json {
+				ui.print_json(note_json(&note))?;
+			} else {
+				ui.print_success(&format!("Restored note: {}", note_label(&note)));
+			}
+		}
+		NotesCommand::Trash { note, permanent } => {
+			let mut note = find_note(client, &note).await?;
+			let label = note_label(&note);
+			if permanent {
+				if !ui.prompt_confirm(&format!("Permanently delete note {}?", label), false)? {
+					return Ok(());
+				}
+				client
+					.delete_note(note)
+					.await
+					.context("Failed to permanently delete note")?;
+				ui.print_success(&format!("Permanently deleted note: {}", label));
+			} else {
+				client
+					.trash_note(&mut note)
+					.await
+					.context("Failed to trash note")?;
+				ui.print_success(&format!("Trashed note: {}", label));
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Finds a note by its UUID or, failing that, by its title, which has to be unambiguous
+async fn find_note(client: &Client, note: &str) -> Result<Note> {
+	let notes = client.list_notes().await.context("Failed to list notes")?;
+	if let Ok(uuid) = Uuid::from_str(note)
+		&& let Some(found) = notes.iter().find(|n| *n.uuid() == uuid)
+	{
+		return Ok(found.clone());
+	}
+	let mut matches = notes
+		.into_iter()
+		.filter(|n| n.title() == Some(note))
+		.collect::<Vec<_>>();
+	match matches.len() {
+		0 => Err(UI::failure(&format!("No such note: {}", note))),
+		1 => Ok(matches.remove(0)),
+		_ => Err(UI::failure(&format!(
+			"Several notes are titled \"{}\", use one of their UUIDs instead: {}",
+			note,
+			matches
+				.iter()
+				.map(|n| n.uuid().to_string())
+				.collect::<Vec<_>>()
+				.join(", ")
+		))),
+	}
+}
+
+async fn get_content(client: &Client, note: &mut Note) -> Result<String> {
+	client
+		.get_note_content(note)
+		.await
+		.context("Failed to get note content")?
+		.ok_or_else(|| UI::failure("The note's content could not be decrypted"))
+}
+
+/// Opens `content` in the editor and saves the note if the content or `note_type` changed
+async fn edit_note(
+	ui: &mut UI,
+	client: &Client,
+	note: &mut Note,
+	content: &str,
+	note_type: Option<NoteType>,
+) -> Result<()> {
+	let note_type = note_type.unwrap_or(note.note_type());
+	let edited = open_in_editor(content, note_type).await?;
+	let content_changed = edited != content;
+	let type_changed = note_type != note.note_type();
+	if content_changed {
+		client
+			.set_note_content(note, &edited, note_preview(&edited, note_type))
+			.await
+			.context("Failed to save note")?;
+	}
+	if type_changed {
+		client
+			.set_note_type(note, note_type, Some(&edited))
+			.await
+			.context("Failed to set note type")?;
+	}
+	if ui.json {
+		let mut json = note_json(note);
+		json["saved"] = json!(content_changed || type_changed);
+		ui.print_json(json)?;
+	} else if content_changed || type_changed {
+		ui.print_success(&format!("Saved note: {}", note_label(note)));
+	} else {
+		ui.print_muted(&format!("Note {} is unchanged", note_label(note)));
+	}
+	Ok(())
+}
+
+/// Lets the user edit `content` in `$VISUAL` or `$EDITOR` and returns the result
+async fn open_in_editor(content: &str, note_type: NoteType) -> Result<String> {
+	let editor = std::env::var("VISUAL")
+		.ok()
+		.filter(|editor| !editor.trim().is_empty())
+		.or_else(|| {
+			std::env::var("EDITOR")
+				.ok()
+				.filter(|editor| !editor.trim().is_empty())
+		})
+		.unwrap_or_else(|| {
+			if cfg!(windows) {
+				"notepad".to_string()
+			} else {
+				"vi".to_string()
+			}
+		});
+	// the editor may be a command with arguments, like "code --wait"
+	let Some((program, args)) = shlex::split(&editor)
+		.as_deref()
+		.and_then(|editor| editor.split_first())
+		.map(|(program, args)| (program.clone(), args.to_vec()))
+	else {
+		return Err(UI::failure(&format!("Invalid editor command: {}", editor)));
+	};
+
+	// a file in a directory of its own, so that editors that replace the file when saving work too
+	let dir = tempfile::tempdir().context("Failed to create temporary directory")?;
+	let path = dir.path().join(format!(
+		"note.{}",
+		match note_type {
+			NoteType::Text | NoteType::Code => "txt",
+			NoteType::Md => "md",
+			NoteType::Rich | NoteType::Checklist => "html",
+		}
+	));
+	tokio::fs::write(&path, content)
+		.await
+		.context("Failed to write temporary note file")?;
+	let status = tokio::process::Command::new(&program)
+		.args(args)
+		.arg(&path)
+		.status()
+		.await
+		.with_context(|| format!("Failed to start editor: {}", program))?;
+	if !status.success() {
+		return Err(UI::failure(&format!(
+			"Editor exited with {}, the note was not saved",
+			status
+		)));
+	}
+	tokio::fs::read_to_string(&path)
+		.await
+		.context("Failed to read edited note")
+}
+
+/// The preview shown in note lists: the start of the first non-empty line, without markup
+fn note_preview(content: &str, note_type: NoteType) -> String {
+	let text = match note_type {
+		NoteType::Rich | NoteType::Checklist => strip_html_tags(content),
+		NoteType::Text | NoteType::Md | NoteType::Code => content.to_string(),
+	};
+	text.lines()
+		.map(str::trim)
+		.find(|line| !line.is_empty())
+		.unwrap_or_default()
+		.chars()
+		.take(PREVIEW_LENGTH)
+		.collect()
+}
+
+/// Replaces HTML tags with line breaks, so the text of different elements ends up on different lines
+fn strip_html_tags(html: &str) -> String {
+	let mut text = String::with_capacity(html.len());
+	let mut in_tag = false;
+	for c in html.chars() {
+		match c {
+			'<' => in_tag = true,
+			'>' if in_tag => {
+				in_tag = false;
+				text.push('\n');
+			}
+			_ if !in_tag => text.push(c),
+			_ => {}
+		}
+	}
+	text.replace("&nbsp;", " ")
+		.replace("&lt;", "<")
+		.replace("&gt;", ">")
+		.replace("&amp;", "&")
+}
+
+fn note_label(note: &Note) -> String {
+	note.title()
+		.map(str::to_string)
+		.unwrap_or_else(|| note.uuid().to_string())
+}
+
+fn note_json(note: &Note) -> serde_json::Value {
+	json!({
+		"uuid": note.uuid(),
+		"title": note.title(),
+		"type": note_type_label(note.note_type()),
+		"tags": note.tags().iter().filter_map(|tag| tag.name()).collect::<Vec<_>>(),
+		"preview": note.preview(),
+		"pinned": note.pinned(),
+		"favorited": note.favorited(),
+		"trashed": note.trashed(),
+		"archived": note.archived(),
+		"created": note.created(),
+		"edited": note.edited(),
+	})
+}
+
+fn print_notes(ui: &mut UI, notes: &[Note]) -> Result<()> {
+	if ui.json {
+		ui.print_json(json!(notes.iter().map(note_json).collect::<Vec<_>>()))?;
+	} else if notes.is_empty() {
+		ui.print_muted("No notes");
+	} else {
+		for note in notes {
+			let tags = note
+				.tags()
+				.iter()
+				.filter_map(|tag| tag.name())
+				.map(|name| format!("#{}", name))
+				.collect::<Vec<_>>()
+				.join(" ");
+			ui.print(&format!(
+				"{}{}  {}  {}",
+				if note.pinned() { "* " } else { "" },
+				console::style(note_label(note)).bold(),
+				console::style(format!(
+					"{} · {}",
+					note_type_label(note.note_type()),
+					ui::format_date(&note.edited())
+				))
+				.dim(),
+				console::style(tags).cyan()
+			));
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn preview_is_the_first_line_without_markup() {
+		assert_eq!(
+			note_preview("\n  Groceries  \n- milk\n", NoteType::Md),
+			"Groceries"
+		);
+		assert_eq!(
+			note_preview(
+				"<ul data-checked=\"false\"><li>milk &amp; eggs</li><li>bread</li></ul>",
+				NoteType::Checklist
+			),
+			"milk & eggs"
+		);
+		assert_eq!(note_preview("", NoteType::Text), "");
+		assert_eq!(
+			note_preview(&"x".repeat(200), NoteType::Code).len(),
+			PREVIEW_LENGTH
+		);
+	}
+}
@@ -1,13 +1,21 @@
-use std::sync::{Arc, Mutex};
+use std::{
+	sync::{Arc, Mutex},
+	time::Duration,
+};
 
-use crate::{CommandResult, auth::LazyClient, ui::UI, util::RemotePath};
+use crate::{
+	CommandResult,
+	auth::{CACHE_RESYNC_PROGRESS_TX, LazyClient},
+	ui::UI,
+	util::RemotePath,
+};
 use anyhow::{Context, Result, anyhow};
 use filen_sdk_rs::{
-	cache::{SearchConfig, SearchSnapshot},
-	fs::HasUUID as _,
+	cache::{ResyncProgress, Search, SearchConfig, SearchItemType, SearchResult, SearchSnapshot},
+	fs::{HasUUID as _, categories::NonRootFileType},
 };
-
-// todo: allow non-interactive search
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
 
 pub(crate) async fn search_cmd(
 	ui: &mut UI,
@@ -123,6 +131,332 @@ pub(crate) async fn search_cmd(
 	}))
 }
 
+/// How long `find` waits for the cache to start listing the searched directory,
+/// which it doesn't if the directory is already cached under another search
+const RESYNC_START_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many results `find` reads at once
+const FIND_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum FindItemType {
+	/// Files
+	#[value(name = "f")]
+	File,
+	/// Directories
+	#[value(name = "d")]
+	Dir,
+}
+
+impl From<FindItemType> for SearchItemType {
+	fn from(item_type: FindItemType) -> Self {
+		match item_type {
+			FindItemType::File => SearchItemType::File,
+			FindItemType::Dir => SearchItemType::Dir,
+		}
+	}
+}
+
+/// A `+N` (more than), `-N` (less than) or `N` (exactly) filter argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Bound<T> {
+	MoreThan(T),
+	LessThan(T),
+	Exactly(T),
+}
+
+/// A number of minutes, hours, days or weeks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Age {
+	count: i32,
+	unit: chrono::TimeDelta,
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct FindOptions {
+	/// Only items whose name contains this text or matches this glob pattern (with "*" and "?"), ignoring case
+	#[arg(long)]
+	name: Option<String>,
+	/// Only files (f) or only directories (d)
+	#[arg(long = "type", value_enum)]
+	item_type: Option<FindItemType>,
+	/// Only files larger (+) or smaller (-) than this size, or of exactly this size (e.g. "+10M", units: k, M, G, T)
+	#[arg(long, value_parser = parse_size_bound, allow_hyphen_values = true)]
+	size: Option<Bound<u64>>,
+	/// Only files modified less (-) or more (+) than this long ago, or exactly this long ago (e.g. "-7d", units: m, h, d, w)
+	#[arg(long, value_parser = parse_age_bound, allow_hyphen_values = true)]
+	mtime: Option<Bound<Age>>,
+	/// Print the results as JSON, one object per line
+	#[arg(long)]
+	json: bool,
+}
+
+/// Prints the items in a directory matching `options`, once the cache holds a complete listing of it
+pub(crate) async fn find(
+	ui: &mut UI,
+	client: &mut LazyClient,
+	working_path: &RemotePath,
+	directory: Option<String>,
+	options: FindOptions,
+) -> Result<()> {
+	let client = client.get(ui).await?;
+	let directory = working_path.navigate(directory.as_deref().unwrap_or(""));
+	let directory_uuid = match client
+		.find_item_at_path(&directory.0)
+		.await
+		.context("Failed to find directory")?
+	{
+		Some(NonRootFileType::Dir(dir)) => dir.uuid(),
+		Some(NonRootFileType::Root(root)) => root.uuid(),
+		Some(NonRootFileType::File(_)) => {
+			return Err(UI::failure(&format!("Not a directory: {}", directory)));
+		}
+		None => return Err(UI::failure(&format!("No such directory: {}", directory))),
+	};
+	let (config, glob) = find_config(&options)?;
+
+	// subscribe before creating the search, which starts the resync
+	let mut progress = CACHE_RESYNC_PROGRESS_TX.subscribe();
+	let search = client
+		.clone()
+		.create_search(directory_uuid, config)
+		.await
+		.context("Failed to create search")?;
+	wait_until_converged(&search, &mut progress).await?;
+
+	let json = options.json || ui.json;
+	let mut offset = 0;
+	loop {
+		let (snapshot, _window_handle) = search
+			.get_range(offset..offset + FIND_PAGE_SIZE, Box::new(|_| {}))
+			.await
+			.context("Failed to get search results")?;
+		for hit in &snapshot.results {
+			if glob
+				.as_deref()
+				.is_some_and(|glob| !glob_matches(glob, hit.result.name()))
+			{
+				continue;
+			}
+			let path = directory.navigate(&hit.full_path());
+			if json {
+				ui.print(
+					&serde_json::to_string(&search_result_json(&path, &hit.result))
+						.context("Failed to serialize JSON")?,
+				);
+			} else {
+				ui.print(&path.0);
+			}
+		}
+		offset += snapshot.results.len();
+		if snapshot.results.len() < FIND_PAGE_SIZE || offset >= snapshot.total {
+			break;
+		}
+	}
+	search.close().await;
+	Ok(())
+}
+
+/// The search config for `options`, and the glob pattern the results still have to be filtered by
+fn find_config(options: &FindOptions) -> Result<(SearchConfig, Option<String>)> {
+	let mut config = SearchConfig::new();
+	let mut glob = None;
+	if let Some(name) = &options.name {
+		if name.contains(['*', '?']) {
+			// let the search narrow the results down to names containing the pattern's longest literal part
+			if let Some(literal) = name
+				.split(['*', '?'])
+				.max_by_key(|part| part.len())
+				.filter(|part| !part.is_empty())
+			{
+				config = config.with_name(literal);
+			}
+			glob = Some(name.clone());
+		} else {
+			config = config.with_name(name.clone());
+		}
+	}
+	if let Some(item_type) = options.item_type {
+		config = config.with_item_type(item_type.into());
+	}
+	if let Some(size) = options.size {
+		config = match size {
+			Bound::MoreThan(size) => config.with_min_size(size.saturating_add(1)),
+			Bound::LessThan(size) => config.with_max_size(size.saturating_sub(1)),
+			Bound::Exactly(size) => config.with_min_size(size).with_max_size(size),
+		};
+	}
+	if let Some(mtime) = options.mtime {
+		let now = chrono::Utc::now();
+		let (Bound::MoreThan(age) | Bound::LessThan(age) | Bound::Exactly(age)) = mtime;
+		let ago = |count: i32| {
+			age.unit
+				.checked_mul(count)
+				.and_then(|age| now.checked_sub_signed(age))
+				.ok_or_else(|| UI::failure("--mtime is too far in the past"))
+		};
+		config = match mtime {
+			Bound::MoreThan(_) => config.with_modified_before(ago(age.count)?),
+			Bound::LessThan(_) => config.with_modified_after(ago(age.count)?),
+			Bound::Exactly(_) => config
+				.with_modified_after(ago(age.count.saturating_add(1))?)
+				.with_modified_before(ago(age.count)?),
+		};
+	}
+	Ok((config, glob))
+}
+
+/// Waits until the cache holds a complete listing of the searched directory
+async fn wait_until_converged(
+	search: &Search,
+	progress: &mut tokio::sync::broadcast::Receiver<ResyncProgress>,
+) -> Result<()> {
+	let root = search.root_uuid();
+	let mut stop_rx = crate::CTRLC_TX.subscribe();
+	// whether a resync that lists the searched directory is running
+	let mut listing = false;
+	while search.is_live() {
+		let next_progress = async {
+			if listing {
+				Ok(progress.recv().await)
+			} else {
+				tokio::time::timeout(RESYNC_START_TIMEOUT, progress.recv()).await
+			}
+		};
+		tokio::select! {
+			_ = stop_rx.recv() => return Err(UI::failure("Canceled")),
+			next_progress = next_progress => match next_progress {
+				// no resync was started, because the directory was already cached
+				Err(_) => return Ok(()),
+				Ok(Ok(ResyncProgress::Started { roots })) => {
+					listing = listing || roots.contains(&root);
+				}
+				Ok(Ok(ResyncProgress::Finished { converged })) if listing => {
+					if converged {
+						return Ok(());
+					}
+					// the cache retries by itself, starting another resync
+					log::warn!("Listing the searched directory failed, waiting for a retry");
+				}
+				Ok(Ok(_)) | Ok(Err(RecvError::Lagged(_))) => {}
+				Ok(Err(RecvError::Closed)) => return Ok(()),
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Whether `name` matches a glob `pattern` with "*" and "?" wildcards, ignoring case
+fn glob_matches(pattern: &str, name: &str) -> bool {
+	let pattern = pattern.to_lowercase().chars().collect::<Vec<_>>();
+	let name = name.to_lowercase().chars().collect::<Vec<_>>();
+	let (mut p, mut n) = (0, 0);
+	// where the last "*" is in the pattern, and where in the name the text it matches ends
+	let mut last_star = None;
+	while n < name.len() {
+		match pattern.get(p) {
+			Some('*') => {
+				last_star = Some((p, n));
+				p += 1;
+			}
+			Some(c) if *c == '?' || *c == name[n] => {
+				p += 1;
+				n += 1;
+			}
+			_ => match last_star {
+				Some((star_p, star_n)) => {
+					// let the "*" match one more character
+					last_star = Some((star_p, star_n + 1));
+					p = star_p + 1;
+					n = star_n + 1;
+				}
+				None => return false,
+			},
+		}
+	}
+	pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn parse_bound<T>(
+	value: &str,
+	parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<Bound<T>, String> {
+	if let Some(value) = value.strip_prefix('+') {
+		parse(value).map(Bound::MoreThan)
+	} else if let Some(value) = value.strip_prefix('-') {
+		parse(value).map(Bound::LessThan)
+	} else {
+		parse(value).map(Bound::Exactly)
+	}
+}
+
+fn parse_size_bound(value: &str) -> Result<Bound<u64>, String> {
+	parse_bound(value, |size| {
+		let (number, unit) = split_unit(size);
+		let multiplier: u64 = match unit {
+			"" | "B" => 1,
+			"k" | "K" => 1 << 10,
+			"M" => 1 << 20,
+			"G" => 1 << 30,
+			"T" => 1 << 40,
+			_ => return Err(format!("Unknown size unit: {} (use k, M, G or T)", unit)),
+		};
+		number
+			.parse::<u64>()
+			.ok()
+			.and_then(|number| number.checked_mul(multiplier))
+			.ok_or_else(|| format!("Invalid size: {}", size))
+	})
+}
+
+fn parse_age_bound(value: &str) -> Result<Bound<Age>, String> {
+	parse_bound(value, |age| {
+		let (number, unit) = split_unit(age);
+		let unit = match unit {
+			"m" => chrono::TimeDelta::minutes(1),
+			"h" => chrono::TimeDelta::hours(1),
+			"" | "d" => chrono::TimeDelta::days(1),
+			"w" => chrono::TimeDelta::weeks(1),
+			_ => return Err(format!("Unknown time unit: {} (use m, h, d or w)", unit)),
+		};
+		let count = number
+			.parse::<i32>()
+			.map_err(|_| format!("Invalid time: {}", age))?;
+		Ok(Age { count, unit })
+	})
+}
+
+/// Splits e.g. "10M" into "10" and "M"
+fn split_unit(value: &str) -> (&str, &str) {
+	value.split_at(
+		value
+			.find(|c: char| !c.is_ascii_digit())
+			.unwrap_or(value.len()),
+	)
+}
+
+fn search_result_json(path: &RemotePath, result: &SearchResult) -> serde_json::Value {
+	match result {
+		SearchResult::Dir(dir) => json!({
+			"path": path.0,
+			"type": "directory",
+			"uuid": dir.uuid,
+			"created": dir.created,
+			"favorited": dir.favorited,
+		}),
+		SearchResult::File(file) => json!({
+			"path": path.0,
+			"type": "file",
+			"uuid": file.uuid,
+			"size": file.size,
+			"mime": file.mime,
+			"modified": file.last_modified,
+			"created": file.created,
+			"favorited": file.favorited,
+		}),
+	}
+}
+
 #[derive(Clone)]
 struct SearchAutocomplete {
 	set_search_query_tx: tokio::sync::mpsc::UnboundedSender<String>,
@@ -168,3 +502,43 @@ impl inquire::Autocomplete for SearchAutocomplete {
 			.unwrap_or(false)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn glob_patterns() {
+		assert!(glob_matches("*.txt", "Notes.TXT"));
+		assert!(glob_matches("report-????.pdf", "report-2024.pdf"));
+		assert!(glob_matches("a*b*c", "aXXbYYbZc"));
+		assert!(glob_matches("*", ""));
+		assert!(!glob_matches("*.txt", "notes.txt.bak"));
+		assert!(!glob_matches("report-????.pdf", "report-24.pdf"));
+		assert!(!glob_matches("a*b*c", "aXXbYY"));
+	}
+
+	#[test]
+	fn size_and_age_bounds() {
+		assert_eq!(parse_size_bound("+10M"), Ok(Bound::MoreThan(10 << 20)));
+		assert_eq!(parse_size_bound("-1k"), Ok(Bound::LessThan(1024)));
+		assert_eq!(parse_size_bound("512"), Ok(Bound::Exactly(512)));
+		assert!(parse_size_bound("+10X").is_err());
+		assert!(parse_size_bound("+").is_err());
+		assert_eq!(
+			parse_age_bound("-7d"),
+			Ok(Bound::LessThan(Age {
+				count: 7,
+				unit: chrono::TimeDelta::days(1)
+			}))
+		);
+		assert_eq!(
+			parse_age_bound("+2h"),
+			Ok(Bound::MoreThan(Age {
+				count: 2,
+				unit: chrono::TimeDelta::hours(1)
+			}))
+		);
+		assert!(parse_age_bound("-7y").is_err());
+	}
+}
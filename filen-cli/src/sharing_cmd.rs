@@ -0,0 +1,599 @@
+//! [cli-doc] sharing
+//! Public links let anyone with the URL view (and, unless disabled, download) a file or directory,
+//! optionally protected by a password and expiring after a set time.
+//! Use `link create` to make one, `link show` to print it again, `link update` to change it and `link remove` to disable it.
+//!
+//! Files and directories can also be shared with your Filen contacts, who then see them in their "Shared with me" section.
+//! Use `share add` to share an item with a contact, `share list` to see what you share (or, with `--incoming`, what is shared with you)
+//! and `share remove` to stop sharing.
+
+use std::borrow::Cow;
+
+use anyhow::{Context, Result};
+use filen_sdk_rs::{
+	auth::Client,
+	connect::{
+		DirPublicLinkRW, FilePublicLink, PasswordState,
+		fs::{SharedRootDirectory, SharedRootFile},
+	},
+	fs::{
+		HasName as _, HasUUID as _,
+		categories::{NonRootFileType, Normal, RootItemType, Shared},
+		file::traits::HasFileInfo as _,
+	},
+	io::{RemoteDirectory, RemoteFile},
+};
+use filen_types::{api::v3::dir::link::PublicLinkExpiration, fs::Uuid};
+use serde_json::json;
+
+use crate::{auth::LazyClient, completion::FilenCompleter, ui::UI, util::RemotePath};
+
+/// The web app's public link pages, which take the link's uuid and its key after an encoded "#"
+const PUBLIC_LINK_BASE_URL: &str = "https://app.filen.io/#";
+
+/// How long a public link stays valid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum LinkExpiration {
+	Never,
+	#[value(name = "1h")]
+	OneHour,
+	#[value(name = "6h")]
+	SixHours,
+	#[value(name = "1d")]
+	OneDay,
+	#[value(name = "3d")]
+	ThreeDays,
+	#[value(name = "7d")]
+	OneWeek,
+	#[value(name = "14d")]
+	TwoWeeks,
+	#[value(name = "30d")]
+	ThirtyDays,
+}
+
+impl From<LinkExpiration> for PublicLinkExpiration {
+	fn from(expiration: LinkExpiration) -> Self {
+		match expiration {
+			LinkExpiration::Never => PublicLinkExpiration::Never,
+			LinkExpiration::OneHour => PublicLinkExpiration::OneHour,
+			LinkExpiration::SixHours => PublicLinkExpiration::SixHours,
+			LinkExpiration::OneDay => PublicLinkExpiration::OneDay,
+			LinkExpiration::ThreeDays => PublicLinkExpiration::ThreeDays,
+			LinkExpiration::OneWeek => PublicLinkExpiration::OneWeek,
+			LinkExpiration::TwoWeeks => PublicLinkExpiration::TwoWeeks,
+			LinkExpiration::ThirtyDays => PublicLinkExpiration::ThirtyDays,
+		}
+	}
+}
+
+fn expiration_label(expiration: PublicLinkExpiration) -> &'static str {
+	match expiration {
+		PublicLinkExpiration::Never => "never",
+		PublicLinkExpiration::OneHour => "1h",
+		PublicLinkExpiration::SixHours => "6h",
+		PublicLinkExpiration::OneDay => "1d",
+		PublicLinkExpiration::ThreeDays => "3d",
+		PublicLinkExpiration::OneWeek => "7d",
+		PublicLinkExpiration::TwoWeeks => "14d",
+		PublicLinkExpiration::ThirtyDays => "30d",
+	}
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub(crate) enum LinkCommand {
+	/// Create a public link to a file or directory
+	Create {
+		/// File or directory to link
+		#[arg(add = FilenCompleter::file_or_directory())]
+		file_or_directory: String,
+		#[command(flatten)]
+		options: LinkOptions,
+	},
+	/// Print the public link of a file or directory
+	Show {
+		/// Linked file or directory
+		#[arg(add = FilenCompleter::file_or_directory())]
+		file_or_directory: String,
+	},
+	/// Change the password, expiration or download permission of a public link
+	Update {
+		/// Linked file or directory
+		#[arg(add = FilenCompleter::file_or_directory())]
+		file_or_directory: String,
+		#[command(flatten)]
+		options: LinkOptions,
+		/// Remove the link's password
+		#[arg(long, conflicts_with = "password")]
+		no_password: bool,
+		/// Allow downloads again
+		#[arg(long, conflicts_with = "no_download")]
+		download: bool,
+	},
+	/// Remove the public link of a file or directory
+	Remove {
+		/// Linked file or directory
+		#[arg(add = FilenCompleter::file_or_directory())]
+		file_or_directory: String,
+	},
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct LinkOptions {
+	/// Protect the link with a password
+	#[arg(long)]
+	password: Option<String>,
+	/// When the link expires, counted from now
+	#[arg(long, value_enum)]
+	expires: Option<LinkExpiration>,
+	/// Only allow viewing, not downloading
+	#[arg(long)]
+	no_download: bool,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub(crate) enum ShareCommand {
+	/// Share a file or directory with a contact
+	Add {
+		/// File or directory to share
+		#[arg(add = FilenCompleter::file_or_directory())]
+		file_or_directory: String,
+		/// Email address of the contact to share with
+		email: String,
+	},
+	/// List the items you share with others
+	List {
+		/// List the items others share with you instead
+		#[arg(long)]
+		incoming: bool,
+	},
+	/// Stop sharing a file or directory
+	Remove {
+		/// Shared file or directory
+		#[arg(add = FilenCompleter::file_or_directory())]
+		file_or_directory: String,
+		/// Only stop sharing with this contact (default: everyone)
+		#[arg(long)]
+		email: Option<String>,
+	},
+}
+
+/// What a link is to, and the link itself
+enum PublicLink {
+	File(RemoteFile, FilePublicLink),
+	Dir(RemoteDirectory, DirPublicLinkRW),
+}
+
+impl PublicLink {
+	/// The link's URL, `None` if the directory link's key failed to decrypt
+	fn url(&self) -> Option<String> {
+		let (kind, uuid, key) = match self {
+			PublicLink::File(file, link) => {
+				("d", link.uuid(), file.key()?.to_str().as_ref().to_string())
+			}
+			PublicLink::Dir(_, link) => ("f", link.uuid(), link.key_string()?),
+		};
+		let key = key.bytes().map(|b| format!("{b:02x}")).collect::<String>();
+		Some(format!("{PUBLIC_LINK_BASE_URL}/{kind}/{uuid}%23{key}"))
+	}
+
+	fn uuid(&self) -> Uuid {
+		match self {
+			PublicLink::File(_, link) => link.uuid(),
+			PublicLink::Dir(_, link) => link.uuid(),
+		}
+	}
+
+	fn password_protected(&self) -> bool {
+		let password = match self {
+			PublicLink::File(_, link) => link.password(),
+			PublicLink::Dir(_, link) => link.password(),
+		};
+		!matches!(password, PasswordState::None)
+	}
+
+	fn expiration(&self) -> PublicLinkExpiration {
+		match self {
+			PublicLink::File(_, link) => link.expiration(),
+			PublicLink::Dir(_, link) => link.expiration(),
+		}
+	}
+
+	fn downloadable(&self) -> bool {
+		match self {
+			PublicLink::File(_, link) => link.downloadable(),
+			PublicLink::Dir(_, link) => link.enable_download(),
+		}
+	}
+
+	fn set_password(&mut self, password: Option<String>) {
+		match (self, password) {
+			(PublicLink::File(_, link), Some(password)) => link.set_password(password),
+			(PublicLink::File(_, link), None) => link.clear_password(),
+			(PublicLink::Dir(_, link), Some(password)) => link.set_password(password),
+			(PublicLink::Dir(_, link), None) => link.clear_password(),
+		}
+	}
+
+	fn set_expiration(&mut self, expiration: PublicLinkExpiration) {
+		match self {
+			PublicLink::File(_, link) => link.set_expiration(expiration),
+			PublicLink::Dir(_, link) => link.set_expiration(expiration),
+		}
+	}
+
+	fn set_downloadable(&mut self, downloadable: bool) {
+		match self {
+			PublicLink::File(_, link) => link.set_downloadable(downloadable),
+			PublicLink::Dir(_, link) => link.set_enable_download(downloadable),
+		}
+	}
+
+	async fn save(&self, client: &Client) -> Result<()> {
+		match self {
+			PublicLink::File(file, link) => client.update_file_link(file, link).await,
+			PublicLink::Dir(dir, link) => client.update_dir_link(dir, link).await,
+		}
+		.context("Failed to update public link")
+	}
+
+	async fn remove(self, client: &Client) -> Result<()> {
+		match self {
+			PublicLink::File(file, link) => client.remove_file_link(&file, link).await,
+			PublicLink::Dir(dir, _) => client.remove_dir_link(&dir).await,
+		}
+		.context("Failed to remove public link")
+	}
+}
+
+pub(crate) async fn link(
+	ui: &mut UI,
+	client: &mut LazyClient,
+	working_path: &RemotePath,
+	command: LinkCommand,
+) -> Result<()> {
+	let client = client.get(ui).await?;
+	match command {
+		LinkCommand::Create {
+			file_or_directory,
+			options,
+		} => {
+			let path = working_path.navigate(&file_or_directory);
+			let mut link = match find_item(client, &path).await? {
+				NonRootFileType::File(file) => {
+					if client
+						.get_file_link_status(&file)
+						.await
+						.context("Failed to get public link status")?
+						.is_some()
+					{
+						return Err(already_linked(&path));
+					}
+					let link = client
+						.public_link_file(&file)
+						.await
+						.context("Failed to create public link")?;
+					PublicLink::File(file.into_owned(), link)
+				}
+				NonRootFileType::Dir(dir) => {
+					if client
+						.get_dir_link_rw(&dir)
+						.await
+						.context("Failed to get public link status")?
+						.is_some()
+					{
+						return Err(already_linked(&path));
+					}
+					let link = client
+						.public_link_dir(&dir, None::<&fn(u64, Option<u64>)>)
+						.await
+						.context("Failed to create public link")?;
+					PublicLink::Dir(dir.into_owned(), link)
+				}
+				NonRootFileType::Root(_) => unreachable!("find_item rejects the root directory"),
+			};
+			if options.password.is_some() || options.expires.is_some() || options.no_download {
+				if let Some(password) = options.password {
+					link.set_password(Some(password));
+				}
+				if let Some(expires) = options.expires {
+					link.set_expiration(expires.into());
+				}
+				link.set_downloadable(!options.no_download);
+				if let Err(e) = link.save(client).await {
+					// the link was created public, so it mustn't stay without the requested options
+					link.remove(client)
+						.await
+						.with_context(|| format!("{e:#}, and the new link is still public"))?;
+					return Err(e);
+				}
+			}
+			print_link(ui, &path, &link, "Created public link")?;
+		}
+		LinkCommand::Show { file_or_directory } => {
+			let path = working_path.navigate(&file_or_directory);
+			let link = find_link(client, &path).await?;
+			print_link(ui, &path, &link, "Public link")?;
+		}
+		LinkCommand::Update {
+			file_or_directory,
+			options,
+			no_password,
+			download,
+		} => {
+			let path = working_path.navigate(&file_or_directory);
+			let mut link = find_link(client, &path).await?;
+			if let Some(password) = options.password {
+				link.set_password(Some(password));
+			} else if no_password {
+				link.set_password(None);
+			}
+			if let Some(expires) = options.expires {
+				link.set_expiration(expires.into());
+			}
+			if options.no_download || download {
+				link.set_downloadable(download);
+			}
+			link.save(client).await?;
+			print_link(ui, &path, &link, "Updated public link")?;
+		}
+		LinkCommand::Remove { file_or_directory } => {
+			let path = working_path.navigate(&file_or_directory);
+			find_link(client, &path).await?.remove(client).await?;
+			ui.print_success(&format!("Removed public link: {}", path));
+		}
+	}
+	Ok(())
+}
+
+fn already_linked(path: &RemotePath) -> anyhow::Error {
+	UI::failure(&format!(
+		"Already has a public link (see `link show`): {}",
+		path
+	))
+}
+
+/// Finds a file or directory other than the root, which can be neither linked nor shared
+async fn find_item<'a>(
+	client: &'a Client,
+	path: &RemotePath,
+) -> Result<NonRootFileType<'a, Normal>> {
+	match client
+		.find_item_at_path(&path.0)
+		.await
+		.context("Failed to find file or directory")?
+	{
+		Some(NonRootFileType::Root(_)) => {
+			Err(UI::failure("Cannot link or share the root directory"))
+		}
+		Some(item) => Ok(item),
+		None => Err(UI::failure(&format!("No such file or directory: {}", path))),
+	}
+}
+
+async fn find_link(client: &Client, path: &RemotePath) -> Result<PublicLink> {
+	let link = match find_item(client, path).await? {
+		NonRootFileType::File(file) => client
+			.get_file_link_status(&file)
+			.await
+			.context("Failed to get public link status")?
+			.map(|link| PublicLink::File(file.into_owned(), link)),
+		NonRootFileType::Dir(dir) => client
+			.get_dir_link_rw(&dir)
+			.await
+			.context("Failed to get public link status")?
+			.map(|link| PublicLink::Dir(dir.into_owned(), link)),
+		NonRootFileType::Root(_) => unreachable!("find_item rejects the root directory"),
+	};
+	link.ok_or_else(|| UI::failure(&format!("No public link: {}", path)))
+}
+
+fn print_link(ui: &mut UI, path: &RemotePath, link: &PublicLink, message: &str) -> Result<()> {
+	let url = link.url();
+	if ui.json {
+		ui.print_json(json!({
+			"path": path.0,
+			"type": match link {
+				PublicLink::File(..) => "file",
+				PublicLink::Dir(..) => "directory",
+			},
+			"url": url,
+			"uuid": link.uuid(),
+			"passwordProtected": link.password_protected(),
+			"expiration": expiration_label(link.expiration()),
+			"downloadable": link.downloadable(),
+		}))?;
+	} else {
+		ui.print_success(&format!("{}: {}", message, path));
+		ui.print_key_value_table(&[
+			(
+				"URL",
+				url.as_deref()
+					.unwrap_or("(unavailable, the link key failed to decrypt)"),
+			),
+			(
+				"Password",
+				if link.password_protected() {
+					"yes"
+				} else {
+					"no"
+				},
+			),
+			("Expires", expiration_label(link.expiration())),
+			("Download", if link.downloadable() { "yes" } else { "no" }),
+		]);
+	}
+	Ok(())
+}
+
+pub(crate) async fn share(
+	ui: &mut UI,
+	client: &mut LazyClient,
+	working_path: &RemotePath,
+	command: ShareCommand,
+) -> Result<()> {
+	let client = client.get(ui).await?;
+	match command {
+		ShareCommand::Add {
+			file_or_directory,
+			email,
+		} => {
+			let path = working_path.navigate(&file_or_directory);
+			let item = find_item(client, &path).await?;
+			let contact = client
+				.get_contacts()
+				.await
+				.context("Failed to list contacts")?
+				.into_iter()
+				.find(|contact| contact.email.eq_ignore_ascii_case(&email))
+				.ok_or_else(|| {
+					UI::failure(&format!(
+						"Not one of your contacts: {} (items can only be shared with contacts)",
+						email
+					))
+				})?;
+			match item {
+				NonRootFileType::File(file) => client.share_file(&file, &contact).await,
+				NonRootFileType::Dir(dir) => {
+					client
+						.share_dir(&dir, &contact, None::<&fn(u64, Option<u64>)>)
+						.await
+				}
+				NonRootFileType::Root(_) => unreachable!("find_item rejects the root directory"),
+			}
+			.context("Failed to share")?;
+			ui.print_success(&format!("Shared {} with {}", path, contact.email));
+		}
+		ShareCommand::List { incoming } => {
+			let (dirs, files) = if incoming {
+				client
+					.list_in_shared_root(None::<&fn(u64, Option<u64>)>)
+					.await
+			} else {
+				client
+					.list_out_shared(None, None::<&fn(u64, Option<u64>)>)
+					.await
+			}
+			.context("Failed to list shared items")?;
+			print_shared_items(ui, &dirs, &files, incoming)?;
+		}
+		ShareCommand::Remove {
+			file_or_directory,
+			email,
+		} => {
+			let path = working_path.navigate(&file_or_directory);
+			let uuid = find_item(client, &path).await?.uuid();
+			let (dirs, files) = client
+				.list_out_shared(None, None::<&fn(u64, Option<u64>)>)
+				.await
+				.context("Failed to list shared items")?;
+			let is_match = |item_uuid: Uuid, receiver: &str| {
+				item_uuid == uuid
+					&& email
+						.as_ref()
+						.is_none_or(|email| email.eq_ignore_ascii_case(receiver))
+			};
+			let shares = dirs
+				.into_iter()
+				.filter(|dir| is_match(dir.uuid(), dir.sharing_role().email()))
+				.map(|dir| RootItemType::<Shared>::Dir(Cow::Owned(dir)))
+				.chain(
+					files
+						.into_iter()
+						.filter(|file| is_match(file.uuid(), file.sharing_role().email()))
+						.map(|file| RootItemType::<Shared>::File(Cow::Owned(file))),
+				)
+				.collect::<Vec<_>>();
+			if shares.is_empty() {
+				return Err(UI::failure(&match email {
+					Some(email) => format!("Not shared with {}: {}", email, path),
+					None => format!("Not shared: {}", path),
+				}));
+			}
+			for share in &shares {
+				client
+					.remove_shared_item(share)
+					.await
+					.context("Failed to stop sharing")?;
+			}
+			ui.print_success(&format!(
+				"Stopped sharing {} with {}",
+				path,
+				shares
+					.iter()
+					.map(|share| match share {
+						RootItemType::Dir(dir) => dir.sharing_role().email(),
+						RootItemType::File(file) => file.sharing_role().email(),
+					})
+					.collect::<Vec<_>>()
+					.join(", ")
+			));
+		}
+	}
+	Ok(())
+}
+
+fn print_shared_items(
+	ui: &mut UI,
+	dirs: &[SharedRootDirectory],
+	files: &[SharedRootFile],
+	incoming: bool,
+) -> Result<()> {
+	let mut items = dirs
+		.iter()
+		.map(|dir| {
+			(
+				dir.name()
+					.map(str::to_string)
+					.unwrap_or_else(|| dir.uuid().to_string()),
+				"directory",
+				dir.sharing_role().email(),
+				dir.uuid(),
+			)
+		})
+		.chain(files.iter().map(|file| {
+			(
+				file.name()
+					.map(str::to_string)
+					.unwrap_or_else(|| file.uuid().to_string()),
+				"file",
+				file.sharing_role().email(),
+				file.uuid(),
+			)
+		}))
+		.collect::<Vec<_>>();
+	items.sort_by(|a, b| a.0.cmp(&b.0));
+	if ui.json {
+		let email_key = if incoming { "sharedBy" } else { "sharedWith" };
+		ui.print_json(json!(
+			items
+				.iter()
+				.map(|(name, kind, email, uuid)| json!({
+					"name": name,
+					"type": kind,
+					email_key: email,
+					"uuid": uuid,
+				}))
+				.collect::<Vec<_>>()
+		))?;
+	} else if items.is_empty() {
+		ui.print_muted(if incoming {
+			"Nothing is shared with you"
+		} else {
+			"You don't share anything"
+		});
+	} else {
+		for (name, kind, email, _) in &items {
+			ui.print(&format!(
+				"{}  {} {}",
+				if *kind == "directory" {
+					console::style(name).blue().to_string()
+				} else {
+					name.clone()
+				},
+				console::style(if incoming { "from" } else { "with" }).dim(),
+				email
+			));
+		}
+	}
+	Ok(())
+}
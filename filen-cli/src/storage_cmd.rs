@@ -0,0 +1,554 @@
+//! [cli-doc] storage
+//! `du` sums up the size of a directory and of each directory below it, and `tree` prints its structure.
+//! Both list the directory recursively, which can take a while for large directories.
+//!
+//! `quota` breaks down your used storage by top-level directory, by file type, the trash and older file versions.
+//! The older versions' share is what's left of the used storage after the files in the drive and the trash.
+
+use std::{
+	borrow::Cow,
+	collections::{BTreeMap, HashMap},
+	sync::Arc,
+};
+
+use anyhow::{Context, Result};
+use console::style;
+use filen_sdk_rs::{
+	auth::Client,
+	fs::{
+		HasName as _, HasUUID as _,
+		categories::{
+			DirType, NonRootFileType, Normal,
+			fs::{CategoryFS as _, CategoryFSExt as _},
+		},
+		file::traits::HasFileInfo as _,
+	},
+	io::{RemoteDirectory, RemoteFile},
+};
+use serde_json::json;
+
+use crate::{
+	auth::LazyClient,
+	ui::{self, UI},
+	util::RemotePath,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum DuSort {
+	/// By path
+	Name,
+	/// Largest first
+	Size,
+}
+
+/// The combined size and number of files
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Usage {
+	size: u64,
+	files: u64,
+}
+
+impl Usage {
+	fn add(&mut self, size: u64) {
+		self.size += size;
+		self.files += 1;
+	}
+}
+
+pub(crate) async fn du(
+	ui: &mut UI,
+	client: &mut LazyClient,
+	working_path: &RemotePath,
+	directory: Option<String>,
+	depth: Option<usize>,
+	sort: DuSort,
+) -> Result<()> {
+	let client = client.get(ui).await?;
+	let path = working_path.navigate(directory.as_deref().unwrap_or(""));
+	let (dirs, files) = list_recursive(client, &path).await?;
+	let usage = directory_usage(
+		dirs.iter().map(|(_, dir_path)| dir_path.as_str()),
+		files
+			.iter()
+			.map(|(file, file_path)| (file_path.as_str(), file.size())),
+	);
+	let mut rows = usage
+		.into_iter()
+		.filter(|(dir_path, _)| depth.is_none_or(|depth| path_depth(dir_path) <= depth))
+		.collect::<Vec<_>>();
+	match sort {
+		// like `du`, the directory itself comes last
+		DuSort::Name => rows.sort_by_key(|(dir_path, _)| (dir_path.is_empty(), *dir_path)),
+		DuSort::Size => rows.sort_by_key(|(_, usage)| std::cmp::Reverse(usage.size)),
+	}
+	if ui.json {
+		ui.print_json(json!(
+			rows.iter()
+				.map(|(dir_path, usage)| json!({
+					"path": path.navigate(dir_path).0,
+					"size": usage.size,
+					"files": usage.files,
+				}))
+				.collect::<Vec<_>>()
+		))?;
+		return Ok(());
+	}
+	let sizes = rows
+		.iter()
+		.map(|(_, usage)| ui::format_size(usage.size))
+		.collect::<Vec<_>>();
+	let width = sizes.iter().map(String::len).max().unwrap_or(0);
+	for ((dir_path, _), size) in rows.iter().zip(sizes) {
+		ui.print(&format!("{:>width$}  {}", size, path.navigate(dir_path)));
+	}
+	Ok(())
+}
+
+pub(crate) async fn tree(
+	ui: &mut UI,
+	client: &mut LazyClient,
+	working_path: &RemotePath,
+	directory: Option<String>,
+	level: Option<usize>,
+) -> Result<()> {
+	let client = client.get(ui).await?;
+	let path = working_path.navigate(directory.as_deref().unwrap_or(""));
+	let (dirs, files) = list_recursive(client, &path).await?;
+	let entries = dirs
+		.iter()
+		.map(|(_, dir_path)| (dir_path.as_str(), None))
+		.chain(
+			files
+				.iter()
+				.map(|(file, file_path)| (file_path.as_str(), Some(file.size()))),
+		);
+	let tree = TreeEntries::new(entries);
+	if ui.json {
+		ui.print_json(json!({
+			"path": path.0,
+			"children": tree.json("", level),
+		}))?;
+		return Ok(());
+	}
+	let mut lines = Vec::new();
+	let (dir_count, file_count) = tree.lines("", "", level, &mut lines);
+	ui.print(&style(&path.0).blue().to_string());
+	for line in lines {
+		ui.print(&line);
+	}
+	ui.print_muted(&format!(
+		"{} {}, {} {}",
+		dir_count,
+		if dir_count == 1 {
+			"directory"
+		} else {
+			"directories"
+		},
+		file_count,
+		if file_count == 1 { "file" } else { "files" },
+	));
+	Ok(())
+}
+
+pub(crate) async fn quota(ui: &mut UI, client: &mut LazyClient) -> Result<()> {
+	let client = client.get(ui).await?;
+	let user_info = client
+		.get_user_info()
+		.await
+		.context("Failed to get user info")?;
+	let root = DirType::Root(Cow::Borrowed(client.root()));
+
+	// by top-level directory
+	let (top_level_dirs, root_files) = client
+		.list_dir::<_, Normal>(&root, None::<&fn(u64, Option<u64>)>)
+		.await
+		.context("Failed to list root directory")?;
+	let mut folders = Vec::with_capacity(top_level_dirs.len());
+	for dir in &top_level_dirs {
+		let size = Normal::dir_size(client, &DirType::Dir(Cow::Borrowed(dir)), ())
+			.await
+			.context("Failed to get directory size")?;
+		folders.push((dir, size));
+	}
+	folders.sort_by_key(|(_, size)| std::cmp::Reverse(size.size));
+	let mut root_usage = Usage::default();
+	for file in &root_files {
+		root_usage.add(file.size());
+	}
+
+	// by file type
+	let (_, all_files) = client
+		.list_dir_recursive::<Normal, _>(&root, None::<&fn(u64, Option<u64>)>, ())
+		.await
+		.context("Failed to list drive")?;
+	let mut drive_usage = Usage::default();
+	let mut categories = BTreeMap::<&str, Usage>::new();
+	for file in &all_files {
+		drive_usage.add(file.size());
+		categories
+			.entry(file_category(file.mime().unwrap_or_default()))
+			.or_default()
+			.add(file.size());
+	}
+	let mut categories = categories.into_iter().collect::<Vec<_>>();
+	categories.sort_by_key(|(_, usage)| std::cmp::Reverse(usage.size));
+
+	// trash and older versions
+	let (trash_dirs, trash_files) = client
+		.list_trash(None::<&fn(u64, Option<u64>)>)
+		.await
+		.context("Failed to list trash")?;
+	let mut trash_size = trash_files.iter().map(|file| file.size()).sum::<u64>();
+	for dir in &trash_dirs {
+		trash_size += Normal::dir_size(client, &DirType::Dir(Cow::Borrowed(dir)), ())
+			.await
+			.context("Failed to get trashed directory size")?
+			.size;
+	}
+	let versions_size = user_info
+		.storage_used
+		.saturating_sub(drive_usage.size + trash_size);
+
+	if ui.json {
+		ui.print_json(json!({
+			"usedStorage": user_info.storage_used,
+			"totalStorage": user_info.max_storage,
+			"folders": folders
+				.iter()
+				.map(|(dir, size)| json!({
+					"name": dir_name(dir),
+					"uuid": dir.uuid(),
+					"size": size.size,
+					"files": size.files,
+					"directories": size.dirs,
+				}))
+				.collect::<Vec<_>>(),
+			"rootFiles": {
+				"size": root_usage.size,
+				"files": root_usage.files,
+			},
+			"categories": categories
+				.iter()
+				.map(|(category, usage)| json!({
+					"category": category,
+					"size": usage.size,
+					"files": usage.files,
+				}))
+				.collect::<Vec<_>>(),
+			"trash": trash_size,
+			"versions": versions_size,
+		}))?;
+		return Ok(());
+	}
+
+	let percent = |size: u64| {
+		if user_info.storage_used == 0 {
+			0.0
+		} else {
+			size as f64 / user_info.storage_used as f64 * 100.0
+		}
+	};
+	let format_share = |usage: Usage| {
+		format!(
+			"{:>10}  {:>5.1}%  {} {}",
+			ui::format_size(usage.size),
+			percent(usage.size),
+			usage.files,
+			if usage.files == 1 { "file" } else { "files" },
+		)
+	};
+	ui.print_key_value_table(&[(
+		"Used",
+		&format!(
+			"{} of {} ({:.1}%)",
+			ui::format_size(user_info.storage_used),
+			ui::format_size(user_info.max_storage),
+			if user_info.max_storage == 0 {
+				0.0
+			} else {
+				user_info.storage_used as f64 / user_info.max_storage as f64 * 100.0
+			}
+		),
+	)]);
+
+	ui.print("");
+	ui.print(&UI::format_text_heading("By directory"));
+	let mut rows = folders
+		.iter()
+		.map(|(dir, size)| {
+			(
+				format!("{}/", dir_name(dir)),
+				format_share(Usage {
+					size: size.size,
+					files: size.files,
+				}),
+			)
+		})
+		.collect::<Vec<_>>();
+	if root_usage.files > 0 {
+		rows.push((String::from("(files in /)"), format_share(root_usage)));
+	}
+	print_table(ui, &rows);
+
+	ui.print("");
+	ui.print(&UI::format_text_heading("By file type"));
+	let rows = categories
+		.iter()
+		.map(|(category, usage)| (category.to_string(), format_share(*usage)))
+		.collect::<Vec<_>>();
+	print_table(ui, &rows);
+
+	ui.print("");
+	ui.print(&UI::format_text_heading("Overhead"));
+	let format_overhead =
+		|size: u64| format!("{:>10}  {:>5.1}%", ui::format_size(size), percent(size));
+	print_table(
+		ui,
+		&[
+			(String::from("Trash"), format_overhead(trash_size)),
+			(
+				String::from("Older versions"),
+				format_overhead(versions_size),
+			),
+		],
+	);
+	Ok(())
+}
+
+/// Lists all directories and files below a directory, with their paths relative to it
+async fn list_recursive(
+	client: &Arc<Client>,
+	path: &RemotePath,
+) -> Result<(Vec<(RemoteDirectory, String)>, Vec<(RemoteFile, String)>)> {
+	let dir = match client
+		.find_item_at_path(&path.0)
+		.await
+		.context("Failed to find directory")?
+	{
+		Some(NonRootFileType::Dir(dir)) => DirType::Dir(dir),
+		Some(NonRootFileType::Root(root)) => DirType::Root(root),
+		Some(NonRootFileType::File(_)) => {
+			return Err(UI::failure(&format!("Not a directory: {}", path)));
+		}
+		None => return Err(UI::failure(&format!("No such directory: {}", path))),
+	};
+	Normal::list_dir_recursive_with_paths(
+		client.clone(),
+		dir,
+		None::<&fn(u64, Option<u64>)>,
+		&mut |_| {},
+		(),
+	)
+	.await
+	.context("Failed to list directory")
+}
+
+/// Sums up the files below each directory, by the directory's relative path ("" for the listed directory)
+fn directory_usage<'a>(
+	dirs: impl IntoIterator<Item = &'a str>,
+	files: impl IntoIterator<Item = (&'a str, u64)>,
+) -> BTreeMap<&'a str, Usage> {
+	let mut usage = dirs
+		.into_iter()
+		.chain([""])
+		.map(|dir_path| (dir_path, Usage::default()))
+		.collect::<BTreeMap<_, _>>();
+	for (file_path, size) in files {
+		let mut parent = file_path;
+		loop {
+			parent = parent.rsplit_once('/').map_or("", |(parent, _)| parent);
+			usage.entry(parent).or_default().add(size);
+			if parent.is_empty() {
+				break;
+			}
+		}
+	}
+	usage
+}
+
+/// How many directories deep a relative path is ("" being 0)
+fn path_depth(path: &str) -> usize {
+	if path.is_empty() {
+		0
+	} else {
+		path.matches('/').count() + 1
+	}
+}
+
+/// The entries of a recursive listing by parent path, directories first
+struct TreeEntries<'a> {
+	/// (name, relative path, file size or `None` for directories)
+	children: HashMap<&'a str, Vec<(&'a str, &'a str, Option<u64>)>>,
+}
+
+impl<'a> TreeEntries<'a> {
+	fn new(entries: impl IntoIterator<Item = (&'a str, Option<u64>)>) -> Self {
+		let mut children = HashMap::<_, Vec<_>>::new();
+		for (path, size) in entries {
+			let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+			children.entry(parent).or_default().push((name, path, size));
+		}
+		for entries in children.values_mut() {
+			entries.sort_by_key(|(name, _, size)| (size.is_some(), *name));
+		}
+		Self { children }
+	}
+
+	/// Appends the lines of the tree below `parent` and returns how many directories and files they show
+	fn lines(
+		&self,
+		parent: &str,
+		prefix: &str,
+		level: Option<usize>,
+		lines: &mut Vec<String>,
+	) -> (usize, usize) {
+		let (mut dir_count, mut file_count) = (0, 0);
+		if level == Some(0) {
+			return (dir_count, file_count);
+		}
+		let entries = self.children.get(parent).map(Vec::as_slice).unwrap_or(&[]);
+		for (index, (name, path, size)) in entries.iter().enumerate() {
+			let last = index == entries.len() - 1;
+			let branch = if last { "└── " } else { "├── " };
+			match size {
+				None => {
+					dir_count += 1;
+					lines.push(format!("{}{}{}", prefix, branch, style(name).blue()));
+					let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+					let (dirs, files) =
+						self.lines(path, &prefix, level.map(|level| level - 1), lines);
+					dir_count += dirs;
+					file_count += files;
+				}
+				Some(_) => {
+					file_count += 1;
+					lines.push(format!("{}{}{}", prefix, branch, name));
+				}
+			}
+		}
+		(dir_count, file_count)
+	}
+
+	fn json(&self, parent: &str, level: Option<usize>) -> Vec<serde_json::Value> {
+		if level == Some(0) {
+			return Vec::new();
+		}
+		self.children
+			.get(parent)
+			.map(Vec::as_slice)
+			.unwrap_or(&[])
+			.iter()
+			.map(|(name, path, size)| match size {
+				None => json!({
+					"name": name,
+					"type": "directory",
+					"children": self.json(path, level.map(|level| level - 1)),
+				}),
+				Some(size) => json!({
+					"name": name,
+					"type": "file",
+					"size": size,
+				}),
+			})
+			.collect()
+	}
+}
+
+/// The category a file belongs to in the storage report, by its MIME type
+fn file_category(mime: &str) -> &'static str {
+	let mime = mime.split(';').next().unwrap_or_default().trim();
+	match mime.split_once('/').map_or(mime, |(kind, _)| kind) {
+		"image" => "Images",
+		"video" => "Videos",
+		"audio" => "Audio",
+		"text" => "Documents",
+		_ => match mime {
+			"application/pdf"
+			| "application/rtf"
+			| "application/msword"
+			| "application/vnd.ms-excel"
+			| "application/vnd.ms-powerpoint" => "Documents",
+			_ if mime.starts_with("application/vnd.openxmlformats-officedocument.")
+				|| mime.starts_with("application/vnd.oasis.opendocument.") =>
+			{
+				"Documents"
+			}
+			"application/zip"
+			| "application/gzip"
+			| "application/x-tar"
+			| "application/x-bzip2"
+			| "application/x-xz"
+			| "application/x-7z-compressed"
+			| "application/x-rar-compressed"
+			| "application/vnd.rar" => "Archives",
+			_ => "Other",
+		},
+	}
+}
+
+fn dir_name(dir: &RemoteDirectory) -> String {
+	dir.name()
+		.map(str::to_string)
+		.unwrap_or_else(|| dir.uuid().to_string())
+}
+
+/// Prints rows of a label and a value, with the values aligned
+fn print_table(ui: &mut UI, rows: &[(String, String)]) {
+	let width = rows
+		.iter()
+		.map(|(label, _)| label.chars().count())
+		.max()
+		.unwrap_or(0);
+	for (label, value) in rows {
+		ui.print(&format!("{:<width$}  {}", label, value));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sums_up_directories() {
+		let usage = directory_usage(
+			["a", "a/b", "c"],
+			[("a/b/file1", 10), ("a/file2", 5), ("file3", 1)],
+		);
+		assert_eq!(usage[""], Usage { size: 16, files: 3 });
+		assert_eq!(usage["a"], Usage { size: 15, files: 2 });
+		assert_eq!(usage["a/b"], Usage { size: 10, files: 1 });
+		assert_eq!(usage["c"], Usage::default());
+		assert_eq!(path_depth(""), 0);
+		assert_eq!(path_depth("a/b"), 2);
+	}
+
+	#[test]
+	fn prints_tree() {
+		console::set_colors_enabled(false);
+		let tree = TreeEntries::new([
+			("b", None),
+			("a.txt", Some(1)),
+			("b/c.txt", Some(2)),
+			("b/d", None),
+		]);
+		let mut lines = Vec::new();
+		assert_eq!(tree.lines("", "", None, &mut lines), (2, 2));
+		assert_eq!(lines, ["├── b", "│   ├── d", "│   └── c.txt", "└── a.txt"]);
+		let mut lines = Vec::new();
+		assert_eq!(tree.lines("", "", Some(1), &mut lines), (1, 1));
+	}
+
+	#[test]
+	fn file_categories() {
+		assert_eq!(file_category("image/png"), "Images");
+		assert_eq!(file_category("text/plain; charset=utf-8"), "Documents");
+		assert_eq!(
+			file_category(
+				"application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+			),
+			"Documents"
+		);
+		assert_eq!(file_category("application/zip"), "Archives");
+		assert_eq!(file_category(""), "Other");
+	}
+}
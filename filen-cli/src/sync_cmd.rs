@@ -0,0 +1,375 @@
+//! [cli-doc] sync
+//! The `sync` command keeps a local directory and a directory in your Filen drive in sync.
+//! The state of the last sync is kept in the config directory, so later runs only transfer what changed since.
+//! Use `--watch` to keep syncing whenever either side changes, or `--dry-run` to only see what would be done.
+//!
+//! Files and directories matching the rules in `.filenignore` files are not synced.
+//! These files use the `.gitignore` syntax and can be placed anywhere inside the synced directory.
+//! A `.filenignore` file in the config directory applies to all syncs.
+
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{Context, Result};
+use filen_sdk_rs::{
+	auth::Client,
+	fs::categories::{DirType, NonRootFileType},
+	io::RemoteDirectory,
+};
+use filen_sync_core::{
+	conflict::{Conflict, ConflictPolicy, Resolution},
+	engine::{SyncEngine, SyncPairConfig, SyncReport, prepare_missing_remote},
+	ignore::{FILENIGNORE, IgnoreStackBuilder},
+	plan::{PlanOptions, SyncMode, SyncOp},
+	remote_watch::RemoteChangeSet,
+	watch::LocalChangeSet,
+};
+use serde_json::json;
+use tokio::{select, sync::mpsc, task::JoinSet};
+
+use crate::{CliConfig, auth::LazyClient, ui::UI, util::RemotePath};
+
+const SYNC_STATE_DB_FILENAME: &str = "sync-state.db";
+const WATCH_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Which way `sync` propagates changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum SyncDirection {
+	/// Propagate changes on either side to the other
+	TwoWay,
+	/// Make the remote directory mirror the local one
+	Up,
+	/// Make the local directory mirror the remote one
+	Down,
+}
+
+impl From<SyncDirection> for SyncMode {
+	fn from(direction: SyncDirection) -> Self {
+		match direction {
+			SyncDirection::TwoWay => SyncMode::TwoWay,
+			SyncDirection::Up => SyncMode::MirrorUp,
+			SyncDirection::Down => SyncMode::MirrorDown,
+		}
+	}
+}
+
+pub(crate) struct SyncOptions {
+	pub(crate) mode: SyncDirection,
+	pub(crate) watch: bool,
+	pub(crate) dry_run: bool,
+}
+
+pub(crate) async fn sync(
+	config: &CliConfig,
+	ui: &mut UI,
+	client: &mut LazyClient,
+	working_path: &RemotePath,
+	local: PathBuf,
+	remote: &str,
+	options: SyncOptions,
+) -> Result<()> {
+	let remote_str = working_path.navigate(remote);
+	if !local.exists() && options.mode != SyncDirection::Up && !options.dry_run {
+		std::fs::create_dir_all(&local).context("Failed to create local directory")?;
+	}
+	let local_root = match std::fs::canonicalize(&local) {
+		Ok(path) if path.is_dir() => path,
+		// dry runs don't create the local directory, it's planned as empty instead
+		Err(e)
+			if e.kind() == std::io::ErrorKind::NotFound
+				&& options.mode != SyncDirection::Up
+				&& options.dry_run =>
+		{
+			std::path::absolute(&local).context("Failed to resolve local directory")?
+		}
+		_ => {
+			return Err(UI::failure(&format!(
+				"No such local directory: {}",
+				local.display()
+			)));
+		}
+	};
+	let client = client.get(ui).await?;
+	let global_ignore_file = config.config_dir.join(FILENIGNORE);
+	let global_ignore_file = global_ignore_file.exists().then_some(global_ignore_file);
+	let Some(remote_root) =
+		find_remote_root(client, &remote_str, options.mode, options.dry_run).await?
+	else {
+		// the sync would create the remote directory, so plan against an empty one
+		let mut ignore_builder = IgnoreStackBuilder::new(&local_root);
+		if let Some(path) = &global_ignore_file {
+			ignore_builder.set_global_user_file(path);
+		}
+		let plan_options = PlanOptions {
+			mode: options.mode.into(),
+			conflict_policy: ConflictPolicy::KeepBoth,
+			device_name: device_name(),
+			now: chrono::Utc::now(),
+		};
+		let (plan, _) = prepare_missing_remote(local_root, ignore_builder, plan_options)
+			.await
+			.context("Failed to plan sync")?;
+		return print_plan(ui, &plan.ops, &plan.conflicts);
+	};
+
+	let pair_config = SyncPairConfig {
+		local_root,
+		remote_root,
+		state_db: config.config_dir.join(SYNC_STATE_DB_FILENAME),
+		global_ignore_file,
+		sync_ignore_file: None,
+		mode: options.mode.into(),
+		conflict_policy: ConflictPolicy::KeepBoth,
+		device_name: device_name(),
+	};
+	if options.dry_run {
+		// only reads the sync state, so the dry run doesn't change anything
+		let mut engine = SyncEngine::new_dry_run(client.clone(), pair_config)
+			.context("Failed to read sync state")?;
+		let prepared = engine.prepare().await.context("Failed to plan sync")?;
+		return print_plan(ui, &prepared.plan.ops, &prepared.plan.conflicts);
+	}
+	let mut engine =
+		SyncEngine::new(client.clone(), pair_config).context("Failed to open sync state")?;
+
+	if !options.watch {
+		let report = engine.sync_once().await.context("Failed to sync")?;
+		return print_report(ui, &report, &remote_str);
+	}
+
+	// Watchers are started before the first run, so nothing that changes during it is missed.
+	// They run in their own tasks, since waiting for a change set isn't cancel-safe.
+	let (changes_tx, mut changes_rx) = mpsc::unbounded_channel();
+	let mut watchers = JoinSet::new();
+	let mut local_watcher = engine
+		.watch(WATCH_DEBOUNCE)
+		.context("Failed to watch local directory")?;
+	let local_tx = changes_tx.clone();
+	watchers.spawn(async move {
+		while let Some(changes) = local_watcher.next().await {
+			if local_tx.send(Changes::Local(changes)).is_err() {
+				break;
+			}
+		}
+	});
+	let mut remote_watcher = engine
+		.watch_remote(WATCH_DEBOUNCE)
+		.await
+		.context("Failed to watch remote directory")?;
+	watchers.spawn(async move {
+		while let Some(changes) = remote_watcher.next().await {
+			if changes_tx.send(Changes::Remote(changes)).is_err() {
+				break;
+			}
+		}
+	});
+
+	let report = engine.sync_once().await.context("Failed to sync")?;
+	if let Err(e) = print_report(ui, &report, &remote_str) {
+		ui.print_failure_or_error(&e);
+	}
+	ui.print_muted("Watching for changes (press Ctrl-C to stop)...");
+
+	let mut stop_rx = crate::CTRLC_TX.subscribe();
+	loop {
+		let prepared = select! {
+			_ = stop_rx.recv() => break,
+			changes = changes_rx.recv() => match changes {
+				Some(Changes::Local(changes)) => engine.prepare_changed(changes).await,
+				Some(Changes::Remote(changes)) => engine.prepare_remote_changed(changes).await,
+				None => break,
+			},
+		};
+		let result = match prepared {
+			Ok(prepared) if prepared.plan.is_empty() && prepared.scan_errors.is_empty() => continue,
+			Ok(prepared) => engine.execute(prepared).await,
+			Err(e) => Err(e),
+		};
+		let result = result
+			.context("Failed to sync")
+			.and_then(|report| print_report(ui, &report, &remote_str));
+		if let Err(e) = result {
+			ui.print_failure_or_error(&e);
+		}
+	}
+	ui.print_muted("Stopped watching for changes");
+	Ok(())
+}
+
+enum Changes {
+	Local(LocalChangeSet),
+	Remote(RemoteChangeSet),
+}
+
+/// Finds the remote directory to sync with, creating it if the sync could upload into it.
+/// Dry runs don't create it and get `None` instead.
+async fn find_remote_root(
+	client: &Client,
+	directory: &RemotePath,
+	mode: SyncDirection,
+	dry_run: bool,
+) -> Result<Option<RemoteDirectory>> {
+	let dir = if mode == SyncDirection::Down || dry_run {
+		match client
+			.find_item_at_path(&directory.0)
+			.await
+			.context("Failed to find remote directory")?
+		{
+			Some(NonRootFileType::Dir(dir)) => DirType::Dir(dir),
+			Some(NonRootFileType::Root(root)) => DirType::Root(root),
+			Some(NonRootFileType::File(_)) => {
+				return Err(UI::failure(&format!("Not a directory: {}", directory.0)));
+			}
+			None if mode != SyncDirection::Down => return Ok(None),
+			None => {
+				return Err(UI::failure(&format!(
+					"No such remote directory: {}",
+					directory.0
+				)));
+			}
+		}
+	} else {
+		client
+			.find_or_create_dir(&directory.0)
+			.await
+			.context("Failed to find or create remote directory")?
+	};
+	match dir {
+		DirType::Dir(dir) => Ok(Some(dir.into_owned())),
+		DirType::Root(_) => Err(UI::failure(
+			"Can't sync with the root directory, choose a directory inside it",
+		)),
+	}
+}
+
+/// Names this device in conflicted copies
+fn device_name() -> String {
+	let name = std::env::var("COMPUTERNAME")
+		.or_else(|_| std::env::var("HOSTNAME"))
+		.or_else(|_| std::fs::read_to_string("/etc/hostname"))
+		.unwrap_or_default();
+	let name = name
+		.trim()
+		.chars()
+		.filter(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+		.collect::<String>();
+	if name.is_empty() {
+		String::from("filen-cli")
+	} else {
+		name
+	}
+}
+
+/// Returns a short name for the operation and the path(s) it affects
+fn describe_op(op: &SyncOp) -> (&'static str, String) {
+	match op {
+		SyncOp::CreateRemoteDir { path } => ("create remote dir", path.clone()),
+		SyncOp::CreateLocalDir { path } => ("create local dir", path.clone()),
+		SyncOp::Upload { path } => ("upload", path.clone()),
+		SyncOp::Download { path } => ("download", path.clone()),
+		SyncOp::UploadDir { path } => ("upload dir", path.clone()),
+		SyncOp::DownloadDir { path } => ("download dir", path.clone()),
+		SyncOp::MoveRemote { from, to } => ("move remote", format!("{} → {}", from, to)),
+		SyncOp::MoveLocal { from, to } => ("move local", format!("{} → {}", from, to)),
+		SyncOp::DeleteRemote { path } => ("delete remote", path.clone()),
+		SyncOp::DeleteLocal { path } => ("delete local", path.clone()),
+		SyncOp::ConflictCopy { path, copy } => ("conflicted copy", format!("{} → {}", path, copy)),
+	}
+}
+
+fn describe_conflict(conflict: &Conflict) -> String {
+	let resolution = match &conflict.resolution {
+		Resolution::KeptLocal => "keeping the local version".to_string(),
+		Resolution::KeptRemote => "keeping the remote version".to_string(),
+		Resolution::KeptBoth { copy } => format!("keeping both, the local version as {}", copy),
+		Resolution::Unresolved { reason } => format!("unresolved ({})", reason),
+	};
+	format!("Conflict at {}: {}", conflict.path, resolution)
+}
+
+fn print_plan(ui: &mut UI, ops: &[SyncOp], conflicts: &[Conflict]) -> Result<()> {
+	if ui.json {
+		return ui.print_json(json!({
+			"operations": ops,
+			"conflicts": conflicts,
+		}));
+	}
+	if ops.is_empty() {
+		ui.print_success("Already in sync, nothing to do");
+		return Ok(());
+	}
+	let rows = ops.iter().map(describe_op).collect::<Vec<_>>();
+	ui.print_key_value_table(
+		&rows
+			.iter()
+			.map(|(op, path)| (*op, path.as_str()))
+			.collect::<Vec<_>>(),
+	);
+	for conflict in conflicts {
+		ui.print_warning(&describe_conflict(conflict));
+	}
+	ui.print_muted(&format!(
+		"Dry run: {} {} planned, nothing was changed",
+		ops.len(),
+		if ops.len() == 1 {
+			"operation"
+		} else {
+			"operations"
+		}
+	));
+	Ok(())
+}
+
+fn print_report(ui: &mut UI, report: &SyncReport, remote: &RemotePath) -> Result<()> {
+	if ui.json {
+		ui.print_json(json!({
+			"applied": report.applied,
+			"failed": report
+				.failed
+				.iter()
+				.map(|(op, e)| json!({ "operation": op, "error": e.to_string() }))
+				.collect::<Vec<_>>(),
+			"scanErrors": report
+				.scan_errors
+				.iter()
+				.map(|e| e.to_string())
+				.collect::<Vec<_>>(),
+			"conflicts": report.conflicts,
+		}))?;
+		if !report.failed.is_empty() {
+			return Err(crate::construct_exit_code_error(1));
+		}
+		return Ok(());
+	}
+	for e in &report.scan_errors {
+		ui.print_warning(&format!("Skipped while scanning: {}", e));
+	}
+	for conflict in &report.conflicts {
+		ui.print_warning(&describe_conflict(conflict));
+	}
+	for (op, e) in &report.failed {
+		let (op, path) = describe_op(op);
+		ui.print_failure(&format!("Failed to {} {}: {}", op, path, e));
+	}
+	ui.print_success(&format!(
+		"Synced with {} ({} {} applied)",
+		remote.0,
+		report.applied.len(),
+		if report.applied.len() == 1 {
+			"operation"
+		} else {
+			"operations"
+		}
+	));
+	if !report.failed.is_empty() {
+		return Err(UI::failure(&format!(
+			"{} {} failed and will be retried on the next sync",
+			report.failed.len(),
+			if report.failed.len() == 1 {
+				"operation"
+			} else {
+				"operations"
+			}
+		)));
+	}
+	Ok(())
+}
@@ -0,0 +1,537 @@
+use std::{
+	borrow::Cow,
+	collections::HashSet,
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Result};
+use filen_sdk_rs::{
+	Error,
+	auth::Client,
+	fs::{
+		HasName as _,
+		categories::{DirType, NonRootFileType, NonRootItemType, Normal, fs::CategoryFSExt},
+		file::traits::HasFileInfo as _,
+	},
+	io::{
+		CategoryDirDownloadExtPub, DirCopyCallback, DirDownloadCallback, DirUploadCallback,
+		RemoteDirectory, RemoteFile, client_impl::IoSharedClientExt,
+	},
+};
+use serde_json::json;
+
+use crate::{
+	auth::LazyClient,
+	ui::{self, ProgressBar, UI},
+	util::RemotePath,
+};
+
+/// What to do with files that already exist at the destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum ExistingFiles {
+	/// Replace them (uploads keep the replaced file as a previous version)
+	Overwrite,
+	/// Leave them as they are
+	Skip,
+}
+
+pub(crate) async fn upload(
+	ui: &mut UI,
+	client: &mut LazyClient,
+	working_path: &RemotePath,
+	local: &Path,
+	remote: Option<String>,
+	existing: ExistingFiles,
+) -> Result<()> {
+	let remote_str = working_path.navigate(remote.as_deref().unwrap_or(""));
+	let client = client.get(ui).await?;
+	let destination = find_remote_directory(client, &remote_str).await?;
+	let local = std::fs::canonicalize(local).map_err(|_| {
+		UI::failure(&format!(
+			"No such local file or directory: {}",
+			local.display()
+		))
+	})?;
+	let name = local
+		.file_name()
+		.and_then(|name| name.to_str())
+		.ok_or_else(|| UI::failure(&format!("Invalid file name: {}", local.display())))?
+		.to_string();
+	let target_str = remote_str.navigate(&name);
+
+	let summary = if local.is_dir() {
+		let existed = client
+			.find_item_at_path(&target_str.0)
+			.await
+			.context("Failed to find destination directory")?;
+		if let Some(NonRootFileType::File(_)) = existed {
+			return Err(UI::failure(&format!(
+				"A file with the same name exists: {}",
+				target_str.0
+			)));
+		}
+		let target = client
+			.create_dir(&destination, &name)
+			.await
+			.context("Failed to create destination directory")?;
+		let existing_files = match (existing, existed) {
+			(ExistingFiles::Skip, Some(_)) => list_remote_files(client, &target).await?,
+			_ => HashSet::new(),
+		};
+		let progress = TransferProgress::new(ui.progress_bar(&name, 0));
+		let mut skipped = 0;
+		client
+			.clone()
+			.upload_dir_recursively_filtered(local.clone(), &progress, &target, |path, is_dir| {
+				let skip = !is_dir
+					&& path
+						.strip_prefix(&local)
+						.is_ok_and(|path| existing_files.contains(&to_remote_relative(path)));
+				if skip {
+					skipped += 1;
+				}
+				!skip
+			})
+			.await
+			.context("Failed to upload directory")?;
+		progress.finish(skipped)
+	} else {
+		match client
+			.find_item_at_path(&target_str.0)
+			.await
+			.context("Failed to find destination file")?
+		{
+			Some(NonRootFileType::File(_)) if existing == ExistingFiles::Skip => TransferSummary {
+				skipped: 1,
+				..Default::default()
+			},
+			Some(NonRootFileType::Dir(_)) => {
+				return Err(UI::failure(&format!(
+					"A directory with the same name exists: {}",
+					target_str.0
+				)));
+			}
+			_ => {
+				let size = std::fs::metadata(&local)
+					.context("Failed to read local file")?
+					.len();
+				let bar = ui.progress_bar(&name, size);
+				let bar_callback = bar.clone();
+				let result = client
+					.upload_file_from_path(
+						&destination,
+						local.clone(),
+						Some(Arc::new(move |bytes| bar_callback.inc(bytes))),
+					)
+					.await;
+				bar.finish();
+				result.context("Failed to upload file")?;
+				TransferSummary {
+					files: 1,
+					bytes: size,
+					..Default::default()
+				}
+			}
+		}
+	};
+	print_summary(ui, &summary, "Uploaded", &target_str.0)
+}
+
+pub(crate) async fn download(
+	ui: &mut UI,
+	client: &mut LazyClient,
+	working_path: &RemotePath,
+	remote: &str,
+	local: Option<PathBuf>,
+	existing: ExistingFiles,
+) -> Result<()> {
+	let remote_str = working_path.navigate(remote);
+	let local = local.unwrap_or_else(|| PathBuf::from("."));
+	if !local.is_dir() {
+		return Err(UI::failure(&format!(
+			"No such local directory: {}",
+			local.display()
+		)));
+	}
+	let client = client.get(ui).await?;
+	let Some(item) = client
+		.find_item_at_path(&remote_str.0)
+		.await
+		.context("Failed to find file or directory")?
+	else {
+		return Err(UI::failure(&format!(
+			"No such file or directory: {}",
+			remote_str.0
+		)));
+	};
+
+	let (summary, target) = match item {
+		NonRootFileType::File(file) => {
+			let target = local.join(safe_local_name(file.name())?);
+			if existing == ExistingFiles::Skip && target.exists() {
+				let summary = TransferSummary {
+					skipped: 1,
+					..Default::default()
+				};
+				(summary, target)
+			} else {
+				let bar = ui.progress_bar(file.name().unwrap_or_default(), file.size());
+				let bar_callback = bar.clone();
+				let result = client
+					.download_file_to_path(
+						file.as_ref(),
+						&target,
+						Some(Arc::new(move |bytes| bar_callback.inc(bytes))),
+					)
+					.await;
+				bar.finish();
+				result.context("Failed to download file")?;
+				let summary = TransferSummary {
+					files: 1,
+					bytes: file.size(),
+					..Default::default()
+				};
+				(summary, target)
+			}
+		}
+		NonRootFileType::Dir(dir) => {
+			let target = local.join(safe_local_name(dir.name())?);
+			let label = dir.name().unwrap_or_default().to_string();
+			let summary = download_dir(
+				ui,
+				client,
+				DirType::Dir(Cow::Owned(dir.into_owned())),
+				&label,
+				&target,
+				existing,
+			)
+			.await?;
+			(summary, target)
+		}
+		NonRootFileType::Root(root) => {
+			let dir = DirType::Root(Cow::Owned(root.into_owned()));
+			let summary = download_dir(ui, client, dir, "Filen", &local, existing).await?;
+			(summary, local)
+		}
+	};
+	print_summary(ui, &summary, "Downloaded", &target.display().to_string())
+}
+
+async fn download_dir(
+	ui: &mut UI,
+	client: &Arc<Client>,
+	dir: DirType<'static, Normal>,
+	label: &str,
+	target: &Path,
+	existing: ExistingFiles,
+) -> Result<TransferSummary> {
+	let target_str = target
+		.to_str()
+		.ok_or_else(|| UI::failure(&format!("Invalid local path: {}", target.display())))?
+		.to_string();
+	let progress = TransferProgress::new(ui.progress_bar(label, 0));
+	let skipped = Arc::new(Mutex::new(0));
+	let skipped_filter = skipped.clone();
+	Normal::download_dir_recursively_filtered(
+		client.clone(),
+		target_str,
+		&progress,
+		dir,
+		(),
+		move |path: &Path, is_dir| {
+			let skip = existing == ExistingFiles::Skip && !is_dir && path.exists();
+			if skip {
+				*skipped_filter.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+			}
+			!skip
+		},
+	)
+	.await
+	.context("Failed to download directory")?;
+	let skipped = *skipped.lock().unwrap_or_else(|e| e.into_inner());
+	Ok(progress.finish(skipped))
+}
+
+/// Copies a drive directory into `destination`, streaming file contents instead of going
+/// through the local disk
+pub(crate) async fn copy_dir(
+	ui: &mut UI,
+	client: &Client,
+	dir: &RemoteDirectory,
+	destination: &DirType<'_, Normal>,
+) -> Result<()> {
+	let progress = TransferProgress::new(ui.progress_bar(dir.name().unwrap_or_default(), 0));
+	let result = client
+		.copy_dir_recursive(dir, destination, None, &progress)
+		.await;
+	let summary = progress.finish(0);
+	result.context("Failed to copy directory")?;
+	for (path, error) in &summary.errors {
+		match path {
+			Some(path) => ui.print_failure(&format!("{}: {}", path, error)),
+			None => ui.print_failure(error),
+		}
+	}
+	if !summary.errors.is_empty() {
+		return Err(UI::failure(&format!(
+			"{} {} failed to copy",
+			summary.errors.len(),
+			if summary.errors.len() == 1 {
+				"item"
+			} else {
+				"items"
+			}
+		)));
+	}
+	Ok(())
+}
+
+async fn find_remote_directory(
+	client: &Client,
+	directory: &RemotePath,
+) -> Result<DirType<'static, Normal>> {
+	match client
+		.find_item_at_path(&directory.0)
+		.await
+		.context("Failed to find destination directory")?
+	{
+		Some(NonRootFileType::Dir(dir)) => Ok(DirType::Dir(Cow::Owned(dir.into_owned()))),
+		Some(NonRootFileType::Root(root)) => Ok(DirType::Root(Cow::Owned(root.into_owned()))),
+		Some(NonRootFileType::File(_)) => {
+			Err(UI::failure(&format!("Not a directory: {}", directory.0)))
+		}
+		None => Err(UI::failure(&format!(
+			"No such destination directory: {}",
+			directory.0
+		))),
+	}
+}
+
+/// Lists the paths of all files below `dir`, relative to it
+async fn list_remote_files(client: &Arc<Client>, dir: &RemoteDirectory) -> Result<HashSet<String>> {
+	let (_, files) = Normal::list_dir_recursive_with_paths(
+		client.clone(),
+		DirType::Dir(Cow::Borrowed(dir)),
+		None::<&fn(u64, Option<u64>)>,
+		&mut |_| {},
+		(),
+	)
+	.await
+	.context("Failed to list destination directory")?;
+	Ok(files.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Formats a relative local path the way remote paths are written
+fn to_remote_relative(path: &Path) -> String {
+	path.components()
+		.map(|component| component.as_os_str().to_string_lossy())
+		.collect::<Vec<_>>()
+		.join("/")
+}
+
+/// Returns a remote item's name if it can be used as a local file name
+fn safe_local_name(name: Option<&str>) -> Result<&str> {
+	match name {
+		Some(name)
+			if !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\']) =>
+		{
+			Ok(name)
+		}
+		_ => Err(UI::failure(&format!(
+			"Name can't be used as a local file name: {}",
+			name.unwrap_or("(undecryptable)")
+		))),
+	}
+}
+
+#[derive(Default)]
+struct TransferSummary {
+	files: u64,
+	bytes: u64,
+	skipped: u64,
+	/// Failed items as (path, error message). Errors that don't belong to an item have no path.
+	errors: Vec<(Option<String>, String)>,
+}
+
+fn print_summary(ui: &mut UI, summary: &TransferSummary, verb: &str, target: &str) -> Result<()> {
+	if ui.json {
+		ui.print_json(json!({
+			"target": target,
+			"files": summary.files,
+			"bytes": summary.bytes,
+			"skipped": summary.skipped,
+			"errors": summary
+				.errors
+				.iter()
+				.map(|(path, error)| json!({ "path": path, "error": error }))
+				.collect::<Vec<_>>(),
+		}))?;
+		if !summary.errors.is_empty() {
+			return Err(crate::construct_exit_code_error(1));
+		}
+		return Ok(());
+	}
+	for (path, error) in &summary.errors {
+		match path {
+			Some(path) => ui.print_failure(&format!("{}: {}", path, error)),
+			None => ui.print_failure(error),
+		}
+	}
+	ui.print_success(&format!(
+		"{} {} {} ({}) to {}",
+		verb,
+		summary.files,
+		if summary.files == 1 { "file" } else { "files" },
+		ui::format_size(summary.bytes),
+		target
+	));
+	if summary.skipped > 0 {
+		ui.print_muted(&format!(
+			"Skipped {} existing {}",
+			summary.skipped,
+			if summary.skipped == 1 {
+				"file"
+			} else {
+				"files"
+			}
+		));
+	}
+	if !summary.errors.is_empty() {
+		return Err(UI::failure(&format!(
+			"{} {} failed",
+			summary.errors.len(),
+			if summary.errors.len() == 1 {
+				"item"
+			} else {
+				"items"
+			}
+		)));
+	}
+	Ok(())
+}
+
+/// Feeds the progress of a directory transfer into a progress bar and collects its results
+struct TransferProgress {
+	bar: ProgressBar,
+	summary: Mutex<TransferSummary>,
+}
+
+impl TransferProgress {
+	fn new(bar: ProgressBar) -> Self {
+		TransferProgress {
+			bar,
+			summary: Mutex::new(TransferSummary::default()),
+		}
+	}
+
+	fn update(&self, f: impl FnOnce(&mut TransferSummary)) {
+		f(&mut self.summary.lock().unwrap_or_else(|e| e.into_inner()));
+	}
+
+	fn add_errors(&self, errors: impl Iterator<Item = (Option<String>, Error)>) {
+		self.update(|summary| {
+			summary
+				.errors
+				.extend(errors.map(|(path, e)| (path, e.to_string())))
+		});
+	}
+
+	fn finish(self, skipped: u64) -> TransferSummary {
+		self.bar.finish();
+		let mut summary = self.summary.into_inner().unwrap_or_else(|e| e.into_inner());
+		summary.skipped = skipped;
+		summary
+	}
+}
+
+impl DirUploadCallback for TransferProgress {
+	fn on_scan_progress(&self, _known_dirs: u64, _known_files: u64, _known_bytes: u64) {}
+
+	fn on_scan_errors(&self, errors: Vec<Error>) {
+		self.add_errors(errors.into_iter().map(|e| (None, e)));
+	}
+
+	fn on_scan_complete(&self, _total_dirs: u64, _total_files: u64, total_bytes: u64) {
+		self.bar.set_total(total_bytes);
+	}
+
+	fn on_upload_update(
+		&self,
+		_uploaded_dirs: Vec<RemoteDirectory>,
+		uploaded_files: Vec<RemoteFile>,
+		uploaded_bytes: u64,
+	) {
+		self.bar.inc(uploaded_bytes);
+		self.update(|summary| {
+			summary.files += uploaded_files.len() as u64;
+			summary.bytes += uploaded_bytes;
+		});
+	}
+
+	fn on_upload_errors(&self, errors: Vec<(PathBuf, Error)>) {
+		self.add_errors(
+			errors
+				.into_iter()
+				.map(|(path, e)| (Some(path.display().to_string()), e)),
+		);
+	}
+}
+
+impl DirCopyCallback for TransferProgress {
+	fn on_scan_complete(&self, _total_dirs: u64, _total_files: u64, total_bytes: u64) {
+		self.bar.set_total(total_bytes);
+	}
+
+	fn on_copy_update(
+		&self,
+		_copied_dirs: Vec<RemoteDirectory>,
+		copied_files: Vec<RemoteFile>,
+		copied_bytes: u64,
+	) {
+		self.bar.inc(copied_bytes);
+		self.update(|summary| {
+			summary.files += copied_files.len() as u64;
+			summary.bytes += copied_bytes;
+		});
+	}
+
+	fn on_copy_errors(&self, errors: Vec<(Error, NonRootItemType<'static, Normal>)>) {
+		self.add_errors(
+			errors
+				.into_iter()
+				.map(|(e, item)| (item.name().map(str::to_owned), e)),
+		);
+	}
+}
+
+impl DirDownloadCallback<Normal> for TransferProgress {
+	fn on_query_download_progress(&self, _known_bytes: u64, _total_bytes: Option<u64>) {}
+
+	fn on_scan_progress(&self, _known_dirs: u64, _known_files: u64, _known_bytes: u64) {}
+
+	fn on_scan_errors(&self, errors: Vec<Error>) {
+		self.add_errors(errors.into_iter().map(|e| (None, e)));
+	}
+
+	fn on_scan_complete(&self, _total_dirs: u64, _total_files: u64, total_bytes: u64) {
+		self.bar.set_total(total_bytes);
+	}
+
+	fn on_download_update(
+		&self,
+		_downloaded_dirs: Vec<(RemoteDirectory, String)>,
+		downloaded_files: Vec<(RemoteFile, String)>,
+		downloaded_bytes: u64,
+	) {
+		self.bar.inc(downloaded_bytes);
+		self.update(|summary| {
+			summary.files += downloaded_files.len() as u64;
+			summary.bytes += downloaded_bytes;
+		});
+	}
+
+	fn on_download_errors(&self, errors: Vec<(Error, String, NonRootItemType<'static, Normal>)>) {
+		self.add_errors(errors.into_iter().map(|(e, path, _)| (Some(path), e)));
+	}
+}
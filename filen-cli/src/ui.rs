@@ -1,4 +1,10 @@
-use std::sync::Arc;
+use std::{
+	sync::{
+		Arc, Mutex,
+		atomic::{AtomicU64, Ordering},
+	},
+	time::{Duration, Instant},
+};
 
 use anyhow::{Context, Result};
 use clap::{CommandFactory, builder::Styles};
@@ -259,6 +265,23 @@ impl UI {
 		Ok(())
 	}
 
+	/// Create a progress bar for transferring `total` bytes.
+	/// It stays hidden in quiet or JSON mode and when stderr is not a terminal.
+	pub(crate) fn progress_bar(&self, label: &str, total: u64) -> ProgressBar {
+		if self.quiet || self.json || !console::Term::stderr().is_term() {
+			return ProgressBar(None);
+		}
+		let state = Arc::new(ProgressBarState {
+			label: label.to_string(),
+			terminal_width: self.get_terminal_width().unwrap_or(80),
+			total: AtomicU64::new(total),
+			done: AtomicU64::new(0),
+			last_drawn: Mutex::new(None),
+		});
+		state.draw(true);
+		ProgressBar(Some(state))
+	}
+
 	// prompt
 
 	/// Prompt the user for input in the REPL (contains some special formatting)
@@ -425,7 +448,7 @@ impl UI {
 			})
 			.help_template("{usage}\n{about}")
 			.render_help();
-		format!(
+		let help = format!(
 			"{}{}",
 			//style("◊").green().bold().bright(),
 			formatted_usage.ansi(),
@@ -437,7 +460,15 @@ impl UI {
 				.map(|l| format!("{} {}", style("→").dim(), l.trim()))
 				.collect::<Vec<_>>()
 				.join("\n")
-		)
+		);
+		// commands like `link` only group subcommands, so their help is that of the subcommands
+		std::iter::once(help)
+			.chain(
+				subcommands_with_full_names(cmd)
+					.map(|mut subcommand| Self::format_command_help(&mut subcommand)),
+			)
+			.collect::<Vec<_>>()
+			.join("\n\n")
 	}
 
 	pub(crate) fn format_global_options_help() -> String {
@@ -482,11 +513,100 @@ impl UI {
 	}
 }
 
+const PROGRESS_BAR_REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A progress bar on a single line of stderr, which can be updated from any thread.
+/// Created with [`UI::progress_bar`].
+#[derive(Clone)]
+pub(crate) struct ProgressBar(Option<Arc<ProgressBarState>>);
+
+impl ProgressBar {
+	pub(crate) fn set_total(&self, total: u64) {
+		if let Some(state) = &self.0 {
+			state.total.store(total, Ordering::Relaxed);
+			state.draw(false);
+		}
+	}
+
+	pub(crate) fn inc(&self, bytes: u64) {
+		if let Some(state) = &self.0 {
+			state.done.fetch_add(bytes, Ordering::Relaxed);
+			state.draw(false);
+		}
+	}
+
+	/// Remove the progress bar from the terminal
+	pub(crate) fn finish(&self) {
+		if let Some(state) = &self.0 {
+			let _last_drawn = state.last_drawn.lock().unwrap_or_else(|e| e.into_inner());
+			let _ = console::Term::stderr().clear_line();
+		}
+	}
+}
+
+struct ProgressBarState {
+	label: String,
+	terminal_width: usize,
+	total: AtomicU64,
+	done: AtomicU64,
+	last_drawn: Mutex<Option<Instant>>,
+}
+
+impl ProgressBarState {
+	fn draw(&self, force: bool) {
+		let mut last_drawn = self.last_drawn.lock().unwrap_or_else(|e| e.into_inner());
+		if !force && last_drawn.is_some_and(|t| t.elapsed() < PROGRESS_BAR_REDRAW_INTERVAL) {
+			return;
+		}
+		*last_drawn = Some(Instant::now());
+		let total = self.total.load(Ordering::Relaxed);
+		let done = self.done.load(Ordering::Relaxed).min(total);
+		let fraction = if total == 0 {
+			0.0
+		} else {
+			done as f64 / total as f64
+		};
+		let stats = format!(
+			"{} / {} ({:.0}%)",
+			format_size(done),
+			format_size(total),
+			fraction * 100.0
+		);
+		let bar_width = self
+			.terminal_width
+			.saturating_sub(self.label.width() + stats.width() + 4)
+			.min(40);
+		let filled = (fraction * bar_width as f64) as usize;
+		let line = format!(
+			"{} {}{} {}",
+			self.label,
+			style("━".repeat(filled)).green(),
+			style("━".repeat(bar_width - filled)).dim(),
+			stats
+		);
+		let term = console::Term::stderr();
+		let _ = term.clear_line();
+		let _ = term.write_str(&line);
+	}
+}
+
 pub(crate) struct ReplPromptResult {
 	pub(crate) input: Option<String>,
 	pub(crate) exit: bool,
 }
 
+/// The subcommands of `cmd`, named for their usage like "link create"
+pub(crate) fn subcommands_with_full_names(
+	cmd: &clap::Command,
+) -> impl Iterator<Item = clap::Command> + '_ {
+	cmd.get_subcommands().map(|subcommand| {
+		let name = cmd.get_bin_name().unwrap_or(cmd.get_name());
+		subcommand
+			.clone()
+			.bin_name(format!("{} {}", name, subcommand.get_name()))
+	})
+}
+
 pub(crate) fn format_date(date: &chrono::DateTime<chrono::Utc>) -> String {
 	date.format("%Y-%m-%d %H:%M:%S (UTC)").to_string()
 }
@@ -0,0 +1,383 @@
+//! [cli-doc] versions
+//! When versioning is enabled, overwriting a file keeps its previous contents as an older version.
+//! `versions <file>` lists the versions of a file, newest first: version 1 is the current one.
+//! Versions can be referred to by their number in this list or by their UUID,
+//! to `versions restore` one, `versions rm` one or `versions diff` two of them (only for text files).
+//!
+//! Versioning can be turned on and off for your account with `versions enable` and `versions disable`,
+//! and `versions delete-all` permanently deletes the older versions of all files.
+
+use std::str::FromStr as _;
+
+use anyhow::{Context, Result};
+use filen_sdk_rs::{
+	auth::Client,
+	fs::{
+		categories::NonRootFileType,
+		file::{FileVersion, traits::HasFileInfo as _},
+	},
+	io::{RemoteFile, client_impl::IoSharedClientExt as _},
+};
+use filen_types::fs::Uuid;
+use serde_json::json;
+
+use crate::{
+	auth::LazyClient,
+	completion::FilenCompleter,
+	ui::{self, UI},
+	util::RemotePath,
+};
+
+#[derive(Debug, clap::Subcommand)]
+pub(crate) enum VersionsCommand {
+	/// Replace a file's contents with an older version
+	Restore {
+		/// Versioned file
+		#[arg(add = FilenCompleter::file())]
+		file: String,
+		/// Number or UUID of the version to restore
+		version: String,
+	},
+	/// Permanently delete an older version of a file
+	Rm {
+		/// Versioned file
+		#[arg(add = FilenCompleter::file())]
+		file: String,
+		/// Number or UUID of the version to delete
+		version: String,
+	},
+	/// Print the differences between two versions of a text file
+	Diff {
+		/// Versioned file
+		#[arg(add = FilenCompleter::file())]
+		file: String,
+		/// Number or UUID of the older version
+		a: String,
+		/// Number or UUID of the newer version (default: the current version)
+		b: Option<String>,
+	},
+	/// Keep older versions of files when they are overwritten
+	Enable,
+	/// Stop keeping older versions of files when they are overwritten
+	Disable,
+	/// Permanently delete the older versions of all files
+	DeleteAll,
+}
+
+pub(crate) async fn versions(
+	ui: &mut UI,
+	client: &mut LazyClient,
+	working_path: &RemotePath,
+	file: Option<String>,
+	command: Option<VersionsCommand>,
+) -> Result<()> {
+	let client = client.get(ui).await?;
+	let Some(command) = command else {
+		let file = file.ok_or_else(|| UI::failure("No file given"))?;
+		let path = working_path.navigate(&file);
+		let file = find_file(client, &path).await?;
+		let versions = list_versions(client, &file).await?;
+		print_versions(ui, &versions)?;
+		return Ok(());
+	};
+	match command {
+		VersionsCommand::Restore { file, version } => {
+			let path = working_path.navigate(&file);
+			let mut file = find_file(client, &path).await?;
+			let versions = list_versions(client, &file).await?;
+			let (number, version) = find_version(&versions, &version)?;
+			if number == 1 {
+				return Err(UI::failure(&format!(
+					"Version 1 is the current version of {}",
+					path
+				)));
+			}
+			client
+				.restore_file_version(&mut file, version.clone())
+				.await
+				.context("Failed to restore file version")?;
+			ui.print_success(&format!("Restored version {} of {}", number, path));
+		}
+		VersionsCommand::Rm { file, version } => {
+			let path = working_path.navigate(&file);
+			let file = find_file(client, &path).await?;
+			let versions = list_versions(client, &file).await?;
+			let (number, version) = find_version(&versions, &version)?;
+			if number == 1 {
+				return Err(UI::failure(
+					"Cannot delete the current version (use `rm` to delete the file)",
+				));
+			}
+			if !ui.prompt_confirm(
+				&format!("Permanently delete version {} of {}?", number, path),
+				false,
+			)? {
+				return Ok(());
+			}
+			client
+				.delete_file_version(version.clone())
+				.await
+				.context("Failed to delete file version")?;
+			ui.print_success(&format!("Deleted version {} of {}", number, path));
+		}
+		VersionsCommand::Diff { file, a, b } => {
+			let path = working_path.navigate(&file);
+			let file = find_file(client, &path).await?;
+			let mime = file.mime().unwrap_or_default();
+			if !is_text_mime(mime) {
+				return Err(UI::failure(&format!(
+					"Not a text file ({}): {}",
+					if mime.is_empty() {
+						"unknown type"
+					} else {
+						mime
+					},
+					path
+				)));
+			}
+			let versions = list_versions(client, &file).await?;
+			let (a_number, a) = find_version(&versions, &a)?;
+			let (b_number, b) = find_version(&versions, b.as_deref().unwrap_or("1"))?;
+			let a_content = download_version(client, &file, a).await?;
+			let b_content = download_version(client, &file, b).await?;
+			let diff = similar::TextDiff::from_lines(&a_content, &b_content);
+			let a_label = format!("{} (version {})", path, a_number);
+			let b_label = format!("{} (version {})", path, b_number);
+			if ui.json {
+				ui.print_json(json!({
+					"a": a_label,
+					"b": b_label,
+					"diff": diff.unified_diff().header(&a_label, &b_label).to_string(),
+				}))?;
+			} else if a_content == b_content {
+				ui.print_muted(&format!(
+					"Versions {} and {} are identical",
+					a_number, b_number
+				));
+			} else {
+				let diff = diff
+					.unified_diff()
+					.header(&a_label, &b_label)
+					.to_string()
+					.lines()
+					.map(|line| {
+						if line.starts_with("+++") || line.starts_with("---") {
+							console::style(line).bold().to_string()
+						} else if line.starts_with('+') {
+							console::style(line).green().to_string()
+						} else if line.starts_with('-') {
+							console::style(line).red().to_string()
+						} else if line.starts_with("@@") {
+							console::style(line).cyan().to_string()
+						} else {
+							line.to_string()
+						}
+					})
+					.collect::<Vec<_>>()
+					.join("\n");
+				ui.print(&diff);
+			}
+		}
+		VersionsCommand::Enable => set_versioning_enabled(ui, client, true).await?,
+		VersionsCommand::Disable => set_versioning_enabled(ui, client, false).await?,
+		VersionsCommand::DeleteAll => {
+			if !ui.prompt_confirm(
+				"Permanently delete the older versions of all files? This cannot be undone.",
+				false,
+			)? {
+				return Ok(());
+			}
+			client
+				.delete_all_versions()
+				.await
+				.context("Failed to delete all versions")?;
+			ui.print_success("Deleted the older versions of all files");
+		}
+	}
+	Ok(())
+}
+
+async fn set_versioning_enabled(ui: &mut UI, client: &Client, enabled: bool) -> Result<()> {
+	if !ui.prompt_confirm(
+		if enabled {
+			"Keep older versions of files when they are overwritten? They count towards your storage."
+		} else {
+			"Stop keeping older versions of files? Overwritten contents can't be restored anymore."
+		},
+		false,
+	)? {
+		return Ok(());
+	}
+	client
+		.set_versioning_enabled(enabled)
+		.await
+		.context("Failed to change versioning")?;
+	ui.print_success(if enabled {
+		"Enabled file versioning"
+	} else {
+		"Disabled file versioning"
+	});
+	Ok(())
+}
+
+async fn find_file(client: &Client, path: &RemotePath) -> Result<RemoteFile> {
+	match client
+		.find_item_at_path(&path.0)
+		.await
+		.context("Failed to find file")?
+	{
+		Some(NonRootFileType::File(file)) => Ok(file.into_owned()),
+		Some(_) => Err(UI::failure(&format!("Not a file: {}", path))),
+		None => Err(UI::failure(&format!("No such file: {}", path))),
+	}
+}
+
+/// Lists a file's versions, newest first
+async fn list_versions(client: &Client, file: &RemoteFile) -> Result<Vec<FileVersion>> {
+	client
+		.list_file_versions(file)
+		.await
+		.context("Failed to list file versions")
+}
+
+/// Finds a version by its number (starting at 1 for the newest) or its UUID
+fn find_version<'a>(
+	versions: &'a [FileVersion],
+	version: &str,
+) -> Result<(usize, &'a FileVersion)> {
+	let index = if let Ok(number) = version.parse::<usize>() {
+		number
+			.checked_sub(1)
+			.filter(|index| *index < versions.len())
+	} else if let Ok(uuid) = Uuid::from_str(version) {
+		versions.iter().position(|v| v.uuid() == uuid)
+	} else {
+		None
+	};
+	index
+		.map(|index| (index + 1, &versions[index]))
+		.ok_or_else(|| {
+			UI::failure(&format!(
+				"No such version: {} (see `versions <file>`)",
+				version
+			))
+		})
+}
+
+async fn download_version(
+	client: &Client,
+	file: &RemoteFile,
+	version: &FileVersion,
+) -> Result<String> {
+	let content = client
+		.download_file(&version.to_remote_file(file))
+		.await
+		.context("Failed to download file version")?;
+	Ok(String::from_utf8_lossy(&content).into_owned())
+}
+
+fn is_text_mime(mime: &str) -> bool {
+	let mime = mime.split(';').next().unwrap_or_default().trim();
+	mime.starts_with("text/")
+		|| mime.ends_with("+json")
+		|| mime.ends_with("+xml")
+		|| matches!(
+			mime,
+			"application/json"
+				| "application/xml"
+				| "application/javascript"
+				| "application/x-javascript"
+				| "application/typescript"
+				| "application/x-sh"
+				| "application/x-yaml"
+				| "application/yaml"
+				| "application/toml"
+				| "application/sql"
+		)
+}
+
+fn print_versions(ui: &mut UI, versions: &[FileVersion]) -> Result<()> {
+	if ui.json {
+		ui.print_json(json!(
+			versions
+				.iter()
+				.enumerate()
+				.map(|(index, version)| json!({
+					"number": index + 1,
+					"uuid": version.uuid(),
+					"timestamp": version.timestamp(),
+					"size": version.size(),
+					"current": index == 0,
+				}))
+				.collect::<Vec<_>>()
+		))?;
+		return Ok(());
+	}
+	let rows = versions
+		.iter()
+		.enumerate()
+		.map(|(index, version)| {
+			[
+				(index + 1).to_string(),
+				ui::format_date(&version.timestamp()),
+				ui::format_size(version.size()),
+				version.uuid().to_string(),
+			]
+		})
+		.collect::<Vec<_>>();
+	let header = ["#", "Timestamp", "Size", "UUID"];
+	let widths = (0..header.len())
+		.map(|column| {
+			rows.iter()
+				.map(|row| row[column].len())
+				.chain(std::iter::once(header[column].len()))
+				.max()
+				.unwrap_or(0)
+		})
+		.collect::<Vec<_>>();
+	let format_row = |row: &[&str]| {
+		row.iter()
+			.zip(&widths)
+			.map(|(cell, width)| format!("{:<width$}", cell))
+			.collect::<Vec<_>>()
+			.join("  ")
+			.trim_end()
+			.to_string()
+	};
+	ui.print(&console::style(format_row(&header)).dim().to_string());
+	for (index, row) in rows.iter().enumerate() {
+		let row = format_row(&row.each_ref().map(String::as_str));
+		ui.print(&if index == 0 {
+			format!("{}  {}", row, console::style("(current)").dim())
+		} else {
+			row
+		});
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn text_mimes() {
+		for mime in [
+			"text/plain",
+			"text/markdown; charset=utf-8",
+			"application/json",
+			"application/ld+json",
+			"image/svg+xml",
+		] {
+			assert!(is_text_mime(mime), "{mime}");
+		}
+		for mime in [
+			"",
+			"image/png",
+			"application/octet-stream",
+			"application/pdf",
+		] {
+			assert!(!is_text_mime(mime), "{mime}");
+		}
+	}
+}
@@ -314,6 +314,120 @@ async fn cmd_cp() {
 	);
 }
 
+#[shared_test_runtime]
+async fn cmd_upload_download() {
+	let resources = test_utils::RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let test_dir = &resources.dir;
+	let dir_path = format!("/{}", test_dir.name().unwrap());
+
+	let local_dir = assert_fs::TempDir::new().unwrap();
+	let source = local_dir.path().join("upload_src");
+	std::fs::create_dir_all(source.join("sub")).unwrap();
+	std::fs::write(source.join("a.txt"), "aaa").unwrap();
+	std::fs::write(source.join("sub/b.txt"), "bbbb").unwrap();
+
+	// upload a directory
+	authenticated_cli_with_args!("--json", "upload", source.to_str().unwrap(), &dir_path)
+		.success()
+		.stdout(
+			predicates::str::contains("\"files\": 2")
+				.and(predicates::str::contains("\"bytes\": 7"))
+				.and(predicates::str::contains("\"skipped\": 0")),
+		);
+	assert!(
+		client
+			.find_item_at_path(&format!("{}/upload_src/sub/b.txt", dir_path))
+			.await
+			.unwrap()
+			.is_some()
+	);
+
+	// upload a single file, then again without replacing it
+	let file = source.join("a.txt");
+	authenticated_cli_with_args!("--json", "upload", file.to_str().unwrap(), &dir_path)
+		.success()
+		.stdout(predicates::str::contains("\"files\": 1"));
+	authenticated_cli_with_args!(
+		"--json",
+		"upload",
+		file.to_str().unwrap(),
+		&dir_path,
+		"--existing",
+		"skip"
+	)
+	.success()
+	.stdout(
+		predicates::str::contains("\"files\": 0").and(predicates::str::contains("\"skipped\": 1")),
+	);
+	authenticated_cli_with_args!(
+		"--json",
+		"upload",
+		source.to_str().unwrap(),
+		&dir_path,
+		"--existing",
+		"skip"
+	)
+	.success()
+	.stdout(
+		predicates::str::contains("\"files\": 0").and(predicates::str::contains("\"skipped\": 2")),
+	);
+
+	// download a directory
+	let download_dir = local_dir.path().join("download");
+	std::fs::create_dir(&download_dir).unwrap();
+	let remote_source = format!("{}/upload_src", dir_path);
+	authenticated_cli_with_args!(
+		"--json",
+		"download",
+		&remote_source,
+		download_dir.to_str().unwrap()
+	)
+	.success()
+	.stdout(
+		predicates::str::contains("\"files\": 2").and(predicates::str::contains("\"bytes\": 7")),
+	);
+	let downloaded = download_dir.join("upload_src/a.txt");
+	assert_eq!(std::fs::read_to_string(&downloaded).unwrap(), "aaa");
+	assert_eq!(
+		std::fs::read_to_string(download_dir.join("upload_src/sub/b.txt")).unwrap(),
+		"bbbb"
+	);
+
+	// existing local files are kept with skip and replaced with overwrite
+	std::fs::write(&downloaded, "changed").unwrap();
+	authenticated_cli_with_args!(
+		"--json",
+		"download",
+		&remote_source,
+		download_dir.to_str().unwrap(),
+		"--existing",
+		"skip"
+	)
+	.success()
+	.stdout(predicates::str::contains("\"skipped\": 2"));
+	assert_eq!(std::fs::read_to_string(&downloaded).unwrap(), "changed");
+	authenticated_cli_with_args!(
+		"--json",
+		"download",
+		&format!("{}/a.txt", remote_source),
+		download_dir.join("upload_src").to_str().unwrap(),
+		"--existing",
+		"overwrite"
+	)
+	.success()
+	.stdout(predicates::str::contains("\"files\": 1"));
+	assert_eq!(std::fs::read_to_string(&downloaded).unwrap(), "aaa");
+
+	// a missing source fails
+	authenticated_cli_with_args!(
+		"download",
+		&format!("{}/does_not_exist", dir_path),
+		download_dir.to_str().unwrap()
+	)
+	.failure();
+}
+
 #[shared_test_runtime]
 async fn cmd_favorite_unfavorite() {
 	let resources = test_utils::RESOURCES.get_resources().await;
@@ -352,6 +466,268 @@ async fn cmd_favorite_unfavorite() {
 	}
 }
 
+#[shared_test_runtime]
+async fn cmd_find() {
+	let resources = test_utils::RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let test_dir = &resources.dir;
+
+	let file = client
+		.make_file_builder("findable_report.txt", test_dir.uuid)
+		.unwrap();
+	client
+		.upload_file(file, "some content".as_bytes())
+		.await
+		.unwrap();
+	let dir_path = format!("/{}", test_dir.name().unwrap());
+
+	// by glob and type
+	authenticated_cli_with_args!("find", &dir_path, "--name", "findable_*.txt", "--type", "f")
+		.success()
+		.stdout(predicates::str::contains(format!(
+			"{}/findable_report.txt",
+			dir_path
+		)));
+
+	// excluded by size and type
+	authenticated_cli_with_args!("find", &dir_path, "--name", "findable", "--size", "+1M")
+		.success()
+		.stdout(predicates::str::contains("findable_report.txt").not());
+	authenticated_cli_with_args!("find", &dir_path, "--name", "findable", "--type", "d")
+		.success()
+		.stdout(predicates::str::contains("findable_report.txt").not());
+
+	// as JSON lines
+	authenticated_cli_with_args!(
+		"find", &dir_path, "--name", "findable", "--mtime", "-1d", "--json"
+	)
+	.success()
+	.stdout(
+		predicates::str::contains("\"type\":\"file\"")
+			.and(predicates::str::contains("\"size\":12")),
+	);
+
+	// invalid filters fail
+	authenticated_cli_with_args!("find", &dir_path, "--size", "+10X").failure();
+}
+
+#[shared_test_runtime]
+async fn cmd_du_tree_quota() {
+	let resources = test_utils::RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let test_dir = &resources.dir;
+
+	let usage_dir = client
+		.create_dir(&test_dir.into(), "usage_dir")
+		.await
+		.unwrap();
+	let file = client
+		.make_file_builder("usage_file.txt", usage_dir.uuid)
+		.unwrap();
+	client.upload_file(file, b"0123456789").await.unwrap();
+	let dir_path = test_dir.name().unwrap().to_string();
+
+	// du
+	authenticated_cli_with_args!("--json", "du", &dir_path, "-d", "1", "--sort", "size")
+		.success()
+		.stdout(
+			predicates::str::contains(format!("/{}/usage_dir\"", dir_path))
+				.and(predicates::str::contains("\"size\": 10")),
+		);
+
+	// tree
+	authenticated_cli_with_args!("tree", &dir_path)
+		.success()
+		.stdout(
+			predicates::str::contains("usage_dir")
+				.and(predicates::str::contains("└── usage_file.txt")),
+		);
+	authenticated_cli_with_args!("tree", &dir_path, "-L", "1")
+		.success()
+		.stdout(predicates::str::contains("usage_file.txt").not());
+
+	// quota
+	authenticated_cli_with_args!("--json", "quota")
+		.success()
+		.stdout(
+			predicates::str::contains("\"usedStorage\"")
+				.and(predicates::str::contains("\"categories\""))
+				.and(predicates::str::contains("\"versions\"")),
+		);
+}
+
+#[shared_test_runtime]
+async fn cmd_versions() {
+	let resources = test_utils::RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let test_dir = &resources.dir;
+
+	// upload a file twice to create a version
+	for content in ["first line\nsecond line\n", "first line\nchanged line\n"] {
+		let file = client
+			.make_file_builder("versioned.txt", test_dir.uuid)
+			.unwrap()
+			.mime("text/plain".to_string());
+		client.upload_file(file, content.as_bytes()).await.unwrap();
+	}
+	let file_path = format!("{}/versioned.txt", test_dir.name().unwrap());
+
+	// list
+	authenticated_cli_with_args!("--json", "versions", &file_path)
+		.success()
+		.stdout(
+			predicates::str::contains("\"number\": 2")
+				.and(predicates::str::contains("\"current\": true")),
+		);
+
+	// diff
+	authenticated_cli_with_args!("versions", "diff", &file_path, "2", "1")
+		.success()
+		.stdout(
+			predicates::str::contains("-second line")
+				.and(predicates::str::contains("+changed line")),
+		);
+
+	// restore
+	authenticated_cli_with_args!("versions", "restore", &file_path, "2")
+		.success()
+		.stdout(predicates::str::contains("Restored version 2"));
+	authenticated_cli_with_args!("cat", &file_path)
+		.success()
+		.stdout(predicates::str::contains("second line"));
+
+	// unknown versions fail
+	authenticated_cli_with_args!("versions", "restore", &file_path, "99")
+		.failure()
+		.stdout(predicates::str::contains("No such version"));
+}
+
+#[shared_test_runtime]
+async fn cmd_link() {
+	let resources = test_utils::RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let test_dir = &resources.dir;
+
+	// create test file to link
+	let file = client
+		.make_file_builder("testfile.txt", test_dir.uuid)
+		.unwrap();
+	let content = "Hello, Filen!";
+	let file = client.upload_file(file, content.as_bytes()).await.unwrap();
+
+	let file_path = format!("{}/testfile.txt", test_dir.name().unwrap());
+
+	// create a password-protected link
+	authenticated_cli_with_args!(
+		"--json",
+		"link",
+		"create",
+		&file_path,
+		"--password",
+		"secret",
+		"--expires",
+		"1d"
+	)
+	.success()
+	.stdout(
+		predicates::str::contains("https://app.filen.io/#/d/")
+			.and(predicates::str::contains("\"passwordProtected\": true"))
+			.and(predicates::str::contains("\"expiration\": \"1d\"")),
+	);
+	assert!(client.get_file_link_status(&file).await.unwrap().is_some());
+
+	// creating it again fails
+	authenticated_cli_with_args!("link", "create", &file_path)
+		.failure()
+		.stdout(predicates::str::contains("Already has a public link"));
+
+	// update and show
+	authenticated_cli_with_args!(
+		"link",
+		"update",
+		&file_path,
+		"--no-password",
+		"--no-download"
+	)
+	.success()
+	.stdout(predicates::str::contains("Updated public link"));
+	authenticated_cli_with_args!("--json", "link", "show", &file_path)
+		.success()
+		.stdout(
+			predicates::str::contains("\"passwordProtected\": false")
+				.and(predicates::str::contains("\"downloadable\": false")),
+		);
+
+	// remove
+	authenticated_cli_with_args!("link", "remove", &file_path)
+		.success()
+		.stdout(predicates::str::contains("Removed public link"));
+	assert!(client.get_file_link_status(&file).await.unwrap().is_none());
+	authenticated_cli_with_args!("link", "show", &file_path)
+		.failure()
+		.stdout(predicates::str::contains("No public link"));
+}
+
+#[shared_test_runtime]
+async fn cmd_notes() {
+	let resources = test_utils::RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let _lock = client
+		.acquire_lock_with_default("test:notes")
+		.await
+		.unwrap();
+
+	// create a note with content to work on
+	let title = format!("cli test note {}", rand::rng().try_next_u64().unwrap());
+	let mut note = client.create_note(Some(title.clone())).await.unwrap();
+	client
+		.set_note_content(&mut note, "Hello, Filen!", "Hello, Filen!".to_string())
+		.await
+		.unwrap();
+
+	// list and show
+	authenticated_cli_with_args!("--json", "notes", "list")
+		.success()
+		.stdout(predicates::str::contains(title.as_str()));
+	authenticated_cli_with_args!("notes", "show", &title)
+		.success()
+		.stdout(predicates::str::contains("Hello, Filen!"));
+
+	// tag
+	authenticated_cli_with_args!("--json", "notes", "tag", &title, "cli-test")
+		.success()
+		.stdout(predicates::str::contains("\"cli-test\""));
+	authenticated_cli_with_args!("notes", "list", "--tag", "cli-test")
+		.success()
+		.stdout(predicates::str::contains(title.as_str()));
+	authenticated_cli_with_args!("notes", "tag", &title, "cli-test", "--remove")
+		.success()
+		.stdout(predicates::str::contains("Untagged note"));
+
+	// history
+	authenticated_cli_with_args!("--json", "notes", "history", &title)
+		.success()
+		.stdout(predicates::str::contains("\"id\""));
+
+	// trash and restore
+	authenticated_cli_with_args!("notes", "trash", &title)
+		.success()
+		.stdout(predicates::str::contains("Trashed note"));
+	authenticated_cli_with_args!("notes", "list", "--trashed")
+		.success()
+		.stdout(predicates::str::contains(title.as_str()));
+	authenticated_cli_with_args!("notes", "restore", &title)
+		.success()
+		.stdout(predicates::str::contains("Restored note"));
+
+	// unknown notes fail
+	authenticated_cli_with_args!("notes", "show", "no such note title")
+		.failure()
+		.stdout(predicates::str::contains("No such note"));
+
+	client.delete_note(note).await.unwrap();
+}
+
 #[shared_test_runtime]
 async fn cmd_rclone() {
 	let resources = test_utils::RESOURCES.get_resources().await;
@@ -419,3 +795,173 @@ async fn cmd_list_trash_empty_trash() {
 		tokio::time::sleep(std::time::Duration::from_secs(5)).await;
 	}
 }
+
+/// Kills the server process when the test ends, even if it fails
+struct KillOnDrop(std::process::Child);
+
+impl Drop for KillOnDrop {
+	fn drop(&mut self) {
+		let _ = self.0.kill();
+		let _ = self.0.wait();
+	}
+}
+
+#[shared_test_runtime]
+async fn cmd_serve_s3_requires_auth_by_default() {
+	let port = std::net::TcpListener::bind("127.0.0.1:0")
+		.unwrap()
+		.local_addr()
+		.unwrap()
+		.port();
+	let (_temp_dir, auth_config_file) = test_utils::cli::prepare_cli_auth_config().await;
+	let _server = KillOnDrop(
+		std::process::Command::new(env!("CARGO_BIN_EXE_filen-cli"))
+			.args([
+				"--auth-config-path",
+				auth_config_file.to_str().unwrap(),
+				"serve",
+				"s3",
+				"--addr",
+				&format!("127.0.0.1:{port}"),
+			])
+			.stdout(std::process::Stdio::null())
+			.spawn()
+			.unwrap(),
+	);
+
+	// an unsigned request is rejected, although no credentials were given
+	let url = format!("http://127.0.0.1:{port}/");
+	let deadline = std::time::Instant::now() + std::time::Duration::from_secs(60);
+	let response = loop {
+		match reqwest::get(&url).await {
+			Ok(response) => break response,
+			Err(e) if std::time::Instant::now() >= deadline => {
+				panic!("S3 server didn't start within 60s: {e}")
+			}
+			Err(_) => tokio::time::sleep(std::time::Duration::from_millis(500)).await,
+		}
+	};
+	assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+}
+
+#[shared_test_runtime]
+async fn cmd_sync_dry_run() {
+	let resources = test_utils::RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let test_dir = &resources.dir;
+
+	let remote_dir = client
+		.create_dir(&test_dir.into(), "sync_dry_run_remote")
+		.await
+		.unwrap();
+	let file = client
+		.make_file_builder("remote.txt", remote_dir.uuid)
+		.unwrap();
+	client.upload_file(file, b"remote content").await.unwrap();
+	let dir_path = format!("/{}", test_dir.name().unwrap());
+	let config_dir = assert_fs::TempDir::new().unwrap();
+	let local_dir = assert_fs::TempDir::new().unwrap();
+	std::fs::write(local_dir.path().join("local.txt"), "local content").unwrap();
+	let missing_local_dir = local_dir.path().join("missing");
+
+	// up into a remote directory that doesn't exist yet
+	authenticated_cli_with_args!(
+		"--config-dir",
+		config_dir.path().to_str().unwrap(),
+		"sync",
+		local_dir.path().to_str().unwrap(),
+		&format!("{}/sync_dry_run_missing", dir_path),
+		"--mode",
+		"up",
+		"--dry-run"
+	)
+	.success()
+	.stdout(
+		predicates::str::contains("local.txt")
+			.and(predicates::str::contains("nothing was changed")),
+	);
+
+	// down and two-way into a local directory that doesn't exist yet
+	for mode in ["down", "two-way"] {
+		authenticated_cli_with_args!(
+			"--config-dir",
+			config_dir.path().to_str().unwrap(),
+			"sync",
+			missing_local_dir.to_str().unwrap(),
+			&format!("{}/sync_dry_run_remote", dir_path),
+			"--mode",
+			mode,
+			"--dry-run"
+		)
+		.success()
+		.stdout(predicates::str::contains("remote.txt"));
+	}
+
+	// nothing was created on either side, and no sync state was written
+	assert!(
+		client
+			.find_item_at_path(&format!("{}/sync_dry_run_missing", dir_path))
+			.await
+			.unwrap()
+			.is_none()
+	);
+	assert!(!missing_local_dir.exists());
+	assert!(!config_dir.path().join("sync-state.db").exists());
+}
+
+#[shared_test_runtime]
+async fn cmd_sync_up_down() {
+	let resources = test_utils::RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let test_dir = &resources.dir;
+	let dir_path = format!("/{}", test_dir.name().unwrap());
+	let config_dir = assert_fs::TempDir::new().unwrap();
+
+	// up
+	let local_dir = assert_fs::TempDir::new().unwrap();
+	std::fs::create_dir(local_dir.path().join("sub")).unwrap();
+	std::fs::write(local_dir.path().join("sub/local.txt"), "local content").unwrap();
+	authenticated_cli_with_args!(
+		"--config-dir",
+		config_dir.path().to_str().unwrap(),
+		"sync",
+		local_dir.path().to_str().unwrap(),
+		&format!("{}/sync_up", dir_path),
+		"--mode",
+		"up"
+	)
+	.success();
+	assert!(matches!(
+		client
+			.find_item_at_path(&format!("{}/sync_up/sub/local.txt", dir_path))
+			.await
+			.unwrap(),
+		Some(NonRootFileType::File(_))
+	));
+	assert!(config_dir.path().join("sync-state.db").exists());
+
+	// down
+	let remote_dir = client
+		.create_dir(&test_dir.into(), "sync_down")
+		.await
+		.unwrap();
+	let file = client
+		.make_file_builder("remote.txt", remote_dir.uuid)
+		.unwrap();
+	client.upload_file(file, b"remote content").await.unwrap();
+	let download_dir = local_dir.path().join("download");
+	authenticated_cli_with_args!(
+		"--config-dir",
+		config_dir.path().to_str().unwrap(),
+		"sync",
+		download_dir.to_str().unwrap(),
+		&format!("{}/sync_down", dir_path),
+		"--mode",
+		"down"
+	)
+	.success();
+	assert_eq!(
+		std::fs::read_to_string(download_dir.join("remote.txt")).unwrap(),
+		"remote content"
+	);
+}
@@ -0,0 +1,82 @@
+//! Chunk ingest and egest. The real backend serves these from their own hosts, the fake server
+//! serves them next to the gateway.
+
+use std::{borrow::Cow, sync::Arc};
+
+use axum::{
+	Router,
+	body::Bytes,
+	extract::{Path, Query, State},
+	http::{HeaderMap, StatusCode},
+	response::{IntoResponse, Response},
+	routing::{get, post},
+};
+use filen_types::{api::v3::upload, fs::Uuid};
+use serde::Deserialize;
+use sha2::{Digest, Sha512};
+
+use crate::{
+	ServerState,
+	response::{ApiError, ApiResult, ok},
+	route,
+	store::{BUCKET, REGION},
+	user::Auth,
+};
+
+pub(crate) fn routes() -> Router<Arc<ServerState>> {
+	Router::new()
+		.route(&route(upload::ENDPOINT), post(ingest))
+		.route(
+			&format!("/{REGION}/{{bucket}}/{{uuid}}/{{index}}"),
+			get(egest),
+		)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IngestQuery {
+	uuid: Uuid,
+	index: u64,
+	upload_key: String,
+	hash: String,
+}
+
+async fn ingest(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	Query(query): Query<IngestQuery>,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	if !query
+		.hash
+		.eq_ignore_ascii_case(&hex::encode(Sha512::digest(&body)))
+	{
+		return Err(ApiError::new(
+			"invalid_chunk_hash",
+			"The chunk doesn't match its hash",
+		));
+	}
+	state
+		.store()
+		.store_chunk(user, query.uuid, query.index, &query.upload_key, body)?;
+	ok(
+		&headers,
+		upload::Response {
+			bucket: Cow::Borrowed(BUCKET),
+			region: Cow::Borrowed(REGION),
+		},
+	)
+}
+
+/// Serves a chunk without authentication, like the real egest hosts do. The chunks are
+/// encrypted, so only holders of the file key can read them.
+async fn egest(
+	State(state): State<Arc<ServerState>>,
+	Path((bucket, uuid, index)): Path<(String, Uuid, u64)>,
+) -> Response {
+	match state.store().chunk(uuid, index) {
+		Some(chunk) if bucket == BUCKET => chunk.into_response(),
+		_ => StatusCode::NOT_FOUND.into_response(),
+	}
+}
@@ -0,0 +1,1017 @@
+//! The drive endpoints: directories, files, uploads, the trash and favorites.
+//!
+//! Every change is also sent as a socket event, shaped like the ones the real backend sends.
+
+use std::{borrow::Cow, sync::Arc};
+
+use axum::{Router, body::Bytes, extract::State, http::HeaderMap, routing::post};
+use filen_types::{
+	api::v3::{
+		dir::{self, color::DirColor},
+		file, item,
+		socket::{
+			FileArchiveRestored, FileArchived, FileDeletedPermanent, FileMetadataChanged, FileMove,
+			FileNew, FileRestore, FileTrash, FolderColorChanged, FolderDeletedPermanent,
+			FolderMove, FolderRename, FolderRestore, FolderSubCreated, FolderTrash, ItemFavorite,
+		},
+		trash, upload,
+	},
+	crypto::EncryptedString,
+	fs::{ObjectType, ParentUuid, StableUuid, Uuid},
+	traits::CowHelpers,
+};
+
+use crate::{
+	ServerState,
+	response::{ApiError, ApiResult, empty, ok, parse},
+	route,
+	store::{BUCKET, Dir, File, REGION, Store, now},
+	user::Auth,
+};
+
+pub(crate) fn routes() -> Router<Arc<ServerState>> {
+	Router::new()
+		.route(&route(dir::ENDPOINT), post(get_dir))
+		.route(&route(dir::create::ENDPOINT), post(create_dir))
+		.route(&route(dir::content::ENDPOINT), post(dir_content))
+		.route(&route(dir::download::ENDPOINT), post(dir_download))
+		.route(&route(dir::exists::ENDPOINT), post(dir_exists))
+		.route(&route(dir::size::ENDPOINT), post(dir_size))
+		.route(&route(dir::metadata::ENDPOINT), post(dir_metadata))
+		.route(&route(dir::color::ENDPOINT), post(dir_color))
+		.route(&route(dir::r#move::ENDPOINT), post(move_dir))
+		.route(&route(dir::trash::ENDPOINT), post(trash_dir))
+		.route(&route(dir::restore::ENDPOINT), post(restore_dir))
+		.route(&route(dir::delete::permanent::ENDPOINT), post(delete_dir))
+		.route(&route(file::ENDPOINT), post(get_file))
+		.route(&route(file::stable::ENDPOINT), post(get_stable_file))
+		.route(&route(file::exists::ENDPOINT), post(file_exists))
+		.route(&route(file::metadata::ENDPOINT), post(file_metadata))
+		.route(&route(file::r#move::ENDPOINT), post(move_file))
+		.route(&route(file::trash::ENDPOINT), post(trash_file))
+		.route(&route(file::restore::ENDPOINT), post(restore_file))
+		.route(&route(file::delete::permanent::ENDPOINT), post(delete_file))
+		.route(&route(file::versions::ENDPOINT), post(file_versions))
+		.route(
+			&route(file::version::restore::ENDPOINT),
+			post(restore_version),
+		)
+		.route(&route(upload::empty::ENDPOINT), post(upload_empty))
+		.route(&route(upload::done::ENDPOINT), post(upload_done))
+		.route(&route(item::favorite::ENDPOINT), post(favorite))
+		.route(&route(trash::empty::ENDPOINT), post(empty_trash))
+}
+
+fn encrypted(s: &str) -> EncryptedString<'_> {
+	EncryptedString(Cow::Borrowed(s))
+}
+
+/// The parent of a directory below the root
+fn parent_of(dir: &Dir) -> Uuid {
+	dir.parent.unwrap_or_default()
+}
+
+/// Like [`Store::dir_mut`], but refuses the root directory, which can't be changed
+fn non_root_dir_mut(store: &mut Store, user: u64, uuid: Uuid) -> Result<&mut Dir, ApiError> {
+	store.dir_mut(user, uuid)?.parent.ok_or_else(|| {
+		ApiError::new(
+			"cannot_modify_base_folder",
+			"The root folder can't be changed",
+		)
+	})?;
+	store.dir_mut(user, uuid)
+}
+
+/// The current version of a file, refusing archived versions
+fn current_file_mut(store: &mut Store, user: u64, uuid: Uuid) -> Result<&mut File, ApiError> {
+	let file = store.file_mut(user, uuid)?;
+	if file.archived_by.is_some() {
+		return Err(ApiError::new(
+			"file_is_archived",
+			"This is an old version of the file",
+		));
+	}
+	Ok(file)
+}
+
+fn content_dir(dir: &Dir) -> dir::content::Directory<'_> {
+	dir::content::Directory {
+		uuid: dir.uuid,
+		meta: encrypted(&dir.meta),
+		parent: ParentUuid::Uuid(parent_of(dir)),
+		color: dir.color.clone(),
+		timestamp: dir.timestamp,
+		favorited: Some(dir.favorited),
+		is_sync: None,
+		is_default: None,
+	}
+}
+
+fn content_file(file: &File) -> dir::content::File<'_> {
+	dir::content::File {
+		uuid: file.uuid,
+		stable_uuid: file.stable_uuid,
+		metadata: encrypted(&file.metadata),
+		rm: Cow::Borrowed(&file.rm),
+		timestamp: file.timestamp,
+		chunks: file.chunks,
+		size: file.size,
+		bucket: Cow::Borrowed(BUCKET),
+		region: Cow::Borrowed(REGION),
+		parent: ParentUuid::Uuid(file.parent),
+		version: file.version,
+		favorited: file.favorited,
+	}
+}
+
+fn file_response(file: &File) -> file::Response<'_> {
+	file::Response {
+		uuid: file.uuid,
+		stable_uuid: file.stable_uuid,
+		region: Cow::Borrowed(REGION),
+		bucket: Cow::Borrowed(BUCKET),
+		name_encrypted: encrypted(&file.name),
+		name_hashed: Cow::Borrowed(&file.name_hashed),
+		size_encrypted: encrypted(&file.size_encrypted),
+		mime_encrypted: encrypted(&file.mime),
+		metadata: encrypted(&file.metadata),
+		timestamp: file.timestamp,
+		size: file.size,
+		parent: ParentUuid::Uuid(file.parent),
+		versioned: file.archived_by.is_some(),
+		trash: file.trashed,
+		version: file.version,
+		favorited: file.favorited,
+	}
+}
+
+/// Builds one of the socket events that describe a whole file: `FileNew`, `FileMove` or
+/// `FileRestore`
+macro_rules! file_event {
+	($event:ident, $file:expr) => {{
+		let file: &File = $file;
+		$event {
+			parent: ParentUuid::Uuid(file.parent),
+			uuid: file.uuid,
+			stable_uuid: file.stable_uuid,
+			metadata: EncryptedString(Cow::Owned(file.metadata.clone())),
+			timestamp: file.timestamp,
+			chunks: file.chunks,
+			size: file.size,
+			bucket: Cow::Borrowed(BUCKET),
+			region: Cow::Borrowed(REGION),
+			version: file.version,
+			favorited: file.favorited,
+		}
+	}};
+}
+
+async fn get_dir(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: dir::Request = parse(&body)?;
+	let store = state.store();
+	let dir = store.dir(user, request.uuid)?;
+	ok(
+		&headers,
+		dir::Response {
+			uuid: dir.uuid,
+			metadata: encrypted(&dir.meta),
+			name_hashed: Cow::Borrowed(&dir.name_hashed),
+			parent: ParentUuid::Uuid(parent_of(dir)),
+			trash: dir.trashed,
+			favorited: dir.favorited,
+			color: dir.color.clone(),
+			timestamp: dir.timestamp,
+		},
+	)
+}
+
+/// Creating a directory whose name is taken answers with the existing directory, like the real
+/// backend does.
+async fn create_dir(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: dir::create::Request = parse(&body)?;
+	let mut store = state.store();
+	store.live_dir(user, request.parent)?;
+	if let Some(existing) = store.live_dir_named(request.parent, &request.name_hashed) {
+		return ok(
+			&headers,
+			dir::create::Response {
+				uuid: existing.uuid,
+				timestamp: existing.timestamp,
+			},
+		);
+	}
+	if store.dirs.contains_key(&request.uuid) || store.files.contains_key(&request.uuid) {
+		return Err(ApiError::new(
+			"uuid_already_exists",
+			"An item with this uuid already exists",
+		));
+	}
+	let timestamp = now();
+	store.dirs.insert(
+		request.uuid,
+		Dir {
+			uuid: request.uuid,
+			owner: user,
+			parent: Some(request.parent),
+			meta: request.meta.0.to_string(),
+			name_hashed: request.name_hashed.into_owned(),
+			color: DirColor::Default,
+			timestamp,
+			favorited: false,
+			trashed: false,
+		},
+	);
+	store.emit(
+		user,
+		"folder-sub-created",
+		FolderSubCreated {
+			name: request.meta,
+			uuid: request.uuid,
+			parent: ParentUuid::Uuid(request.parent),
+			timestamp,
+			favorited: false,
+		},
+	);
+	ok(
+		&headers,
+		dir::create::Response {
+			uuid: request.uuid,
+			timestamp,
+		},
+	)
+}
+
+/// Lists a directory, or one of the `trash`, `recents`, `favorites` and `links` pseudo
+/// directories. Nothing is ever shared on the fake server, so `links` is always empty.
+async fn dir_content(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: dir::content::Request = parse(&body)?;
+	let store = state.store();
+	let owned_files = || {
+		store
+			.files
+			.values()
+			.filter(move |f| f.owner == user && f.archived_by.is_none())
+	};
+	let owned_dirs = || {
+		store
+			.dirs
+			.values()
+			.filter(move |d| d.owner == user && d.parent.is_some())
+	};
+	let (files, dirs): (Vec<&File>, Vec<&Dir>) = match request.uuid {
+		ParentUuid::Uuid(uuid) => {
+			store.dir(user, uuid)?;
+			(
+				store.files_in(uuid).filter(|f| !f.trashed).collect(),
+				store.dirs_in(uuid).filter(|d| !d.trashed).collect(),
+			)
+		}
+		ParentUuid::Trash(_) => (
+			owned_files().filter(|f| f.trashed).collect(),
+			owned_dirs().filter(|d| d.trashed).collect(),
+		),
+		ParentUuid::Recents => {
+			let mut files = owned_files().filter(|f| !f.trashed).collect::<Vec<_>>();
+			files.sort_by_key(|f| std::cmp::Reverse(f.timestamp));
+			(files, Vec::new())
+		}
+		ParentUuid::Favorites => (
+			owned_files()
+				.filter(|f| f.favorited && !f.trashed)
+				.collect(),
+			owned_dirs().filter(|d| d.favorited && !d.trashed).collect(),
+		),
+		ParentUuid::Links => (Vec::new(), Vec::new()),
+	};
+	ok(
+		&headers,
+		dir::content::Response {
+			files: files.into_iter().map(content_file).collect(),
+			dirs: dirs.into_iter().map(content_dir).collect(),
+		},
+	)
+}
+
+/// Lists a directory recursively. The requested directory is part of the answer, without a
+/// parent.
+async fn dir_download(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: dir::download::Request = parse(&body)?;
+	let ParentUuid::Uuid(uuid) = request.uuid else {
+		return Err(ApiError::new(
+			"invalid_params",
+			"Only folders can be listed recursively",
+		));
+	};
+	let store = state.store();
+	store.dir(user, uuid)?;
+	let tree = store.dir_tree(uuid, false);
+	let dirs = tree
+		.iter()
+		.map(|d| &store.dirs[d])
+		.map(|d| dir::download::Directory {
+			uuid: d.uuid,
+			meta: encrypted(&d.meta),
+			parent: (d.uuid != uuid).then(|| ParentUuid::Uuid(parent_of(d))),
+			color: d.color.clone(),
+			timestamp: d.timestamp,
+			favorited: d.favorited,
+		})
+		.collect();
+	let files = tree
+		.iter()
+		.flat_map(|d| store.files_in(*d).filter(|f| !f.trashed))
+		.map(|f| dir::download::File {
+			uuid: f.uuid,
+			stable_uuid: f.stable_uuid,
+			metadata: encrypted(&f.metadata),
+			timestamp: f.timestamp,
+			chunks: f.chunks,
+			size: encrypted(&f.size_encrypted),
+			chunks_size: f.size,
+			bucket: Cow::Borrowed(BUCKET),
+			region: Cow::Borrowed(REGION),
+			parent: ParentUuid::Uuid(f.parent),
+			version: f.version,
+			favorited: f.favorited,
+		})
+		.collect();
+	ok(&headers, dir::download::Response { files, dirs })
+}
+
+async fn dir_exists(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: dir::exists::Request = parse(&body)?;
+	let store = state.store();
+	store.dir(user, request.parent)?;
+	let existing = store
+		.live_dir_named(request.parent, &request.name_hashed)
+		.map(|d| d.uuid);
+	ok(&headers, dir::exists::Response(existing))
+}
+
+async fn dir_size(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: dir::size::Request = parse(&body)?;
+	let store = state.store();
+	store.dir(user, request.uuid)?;
+	let tree = store.dir_tree(request.uuid, false);
+	let (size, files) = tree
+		.iter()
+		.flat_map(|d| store.files_in(*d).filter(|f| !f.trashed))
+		.fold((0, 0), |(size, files), f| (size + f.size, files + 1));
+	ok(
+		&headers,
+		dir::size::Response {
+			size,
+			files,
+			dirs: tree.len() as u64 - 1,
+		},
+	)
+}
+
+async fn dir_metadata(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: dir::metadata::Request = parse(&body)?;
+	let mut store = state.store();
+	let dir = non_root_dir_mut(&mut store, user, request.uuid)?;
+	dir.meta = request.metadata.0.to_string();
+	dir.name_hashed = request.name_hashed.into_owned();
+	store.emit(
+		user,
+		"folder-rename",
+		FolderRename {
+			name: request.metadata,
+			uuid: request.uuid,
+		},
+	);
+	empty(&headers)
+}
+
+async fn dir_color(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: dir::color::Request = parse(&body)?;
+	let mut store = state.store();
+	non_root_dir_mut(&mut store, user, request.uuid)?.color =
+		request.color.clone().into_owned_cow();
+	store.emit(
+		user,
+		"folder-color-changed",
+		FolderColorChanged {
+			uuid: request.uuid,
+			color: request.color,
+		},
+	);
+	empty(&headers)
+}
+
+async fn move_dir(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: dir::r#move::Request = parse(&body)?;
+	let mut store = state.store();
+	store.live_dir(user, request.to)?;
+	non_root_dir_mut(&mut store, user, request.uuid)?;
+	if store.dir_tree(request.uuid, true).contains(&request.to) {
+		return Err(ApiError::new(
+			"cannot_move_folder_into_itself",
+			"A folder can't be moved into itself",
+		));
+	}
+	let name_hashed = store.dirs[&request.uuid].name_hashed.clone();
+	if store
+		.live_dir_named(request.to, &name_hashed)
+		.is_some_and(|d| d.uuid != request.uuid)
+	{
+		return Err(ApiError::new(
+			"folder_exists",
+			"A folder with this name already exists in the destination",
+		));
+	}
+	let dir = store.dir_mut(user, request.uuid)?;
+	dir.parent = Some(request.to);
+	let event = FolderMove {
+		name: EncryptedString(Cow::Owned(dir.meta.clone())),
+		uuid: dir.uuid,
+		parent: ParentUuid::Uuid(request.to),
+		timestamp: dir.timestamp,
+		favorited: dir.favorited,
+	};
+	store.emit(user, "folder-move", event);
+	empty(&headers)
+}
+
+async fn trash_dir(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: dir::trash::Request = parse(&body)?;
+	let mut store = state.store();
+	let dir = non_root_dir_mut(&mut store, user, request.uuid)?;
+	dir.trashed = true;
+	let event = FolderTrash {
+		parent: parent_of(dir),
+		uuid: dir.uuid,
+	};
+	store.emit(user, "folder-trash", event);
+	empty(&headers)
+}
+
+/// Restores a directory to its old parent, or to the root if the old parent is gone
+async fn restore_dir(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: dir::restore::Request = parse(&body)?;
+	let mut store = state.store();
+	let parent = parent_of(non_root_dir_mut(&mut store, user, request.uuid)?);
+	let parent = match store.live_dir(user, parent) {
+		Ok(_) => parent,
+		Err(_) => store.user(user).root,
+	};
+	let dir = store.dir_mut(user, request.uuid)?;
+	dir.trashed = false;
+	dir.parent = Some(parent);
+	let event = FolderRestore {
+		name: EncryptedString(Cow::Owned(dir.meta.clone())),
+		uuid: dir.uuid,
+		parent: ParentUuid::Uuid(parent),
+		timestamp: dir.timestamp,
+		favorited: dir.favorited,
+	};
+	store.emit(user, "folder-restore", event);
+	empty(&headers)
+}
+
+async fn delete_dir(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: dir::delete::permanent::Request = parse(&body)?;
+	let mut store = state.store();
+	non_root_dir_mut(&mut store, user, request.uuid)?;
+	store.delete_dir(request.uuid);
+	store.emit(
+		user,
+		"folder-deleted-permanent",
+		FolderDeletedPermanent { uuid: request.uuid },
+	);
+	empty(&headers)
+}
+
+async fn get_file(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: file::Request = parse(&body)?;
+	let store = state.store();
+	ok(&headers, file_response(store.file(user, request.uuid)?))
+}
+
+async fn get_stable_file(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: file::stable::Request = parse(&body)?;
+	let store = state.store();
+	let file = store
+		.files
+		.values()
+		.find(|f| {
+			f.owner == user && f.stable_uuid == request.stable_uuid && f.archived_by.is_none()
+		})
+		.ok_or_else(|| ApiError::not_found("File"))?;
+	ok(&headers, file_response(file))
+}
+
+async fn file_exists(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: file::exists::Request = parse(&body)?;
+	let ParentUuid::Uuid(parent) = request.parent else {
+		return ok(&headers, file::exists::Response(None));
+	};
+	let store = state.store();
+	store.dir(user, parent)?;
+	let existing = store
+		.live_file_named(parent, &request.name_hashed)
+		.map(|f| f.uuid);
+	ok(&headers, file::exists::Response(existing))
+}
+
+async fn file_metadata(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: file::metadata::Request = parse(&body)?;
+	let mut store = state.store();
+	let file = current_file_mut(&mut store, user, request.uuid)?;
+	let old_metadata = std::mem::replace(&mut file.metadata, request.metadata.0.to_string());
+	file.name = request.name.0.to_string();
+	file.name_hashed = request.name_hashed.into_owned();
+	let stable_uuid = file.stable_uuid;
+	store.emit(
+		user,
+		"file-metadata-changed",
+		FileMetadataChanged {
+			uuid: request.uuid,
+			stable_uuid,
+			name: request.name,
+			metadata: request.metadata,
+			old_metadata: EncryptedString(Cow::Owned(old_metadata)),
+		},
+	);
+	empty(&headers)
+}
+
+async fn move_file(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: file::r#move::Request = parse(&body)?;
+	let mut store = state.store();
+	store.live_dir(user, request.new_parent)?;
+	let name_hashed = current_file_mut(&mut store, user, request.uuid)?
+		.name_hashed
+		.clone();
+	if store
+		.live_file_named(request.new_parent, &name_hashed)
+		.is_some_and(|f| f.uuid != request.uuid)
+	{
+		return Err(ApiError::new(
+			"file_exists",
+			"A file with this name already exists in the destination",
+		));
+	}
+	let file = store.file_mut(user, request.uuid)?;
+	file.parent = request.new_parent;
+	let event = file_event!(FileMove, file);
+	store.emit(user, "file-move", event);
+	empty(&headers)
+}
+
+async fn trash_file(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: file::trash::Request = parse(&body)?;
+	let mut store = state.store();
+	let file = current_file_mut(&mut store, user, request.uuid)?;
+	file.trashed = true;
+	let event = FileTrash {
+		uuid: file.uuid,
+		stable_uuid: file.stable_uuid,
+		new_uuid: None,
+	};
+	store.emit(user, "file-trash", event);
+	empty(&headers)
+}
+
+/// Restores a file to its old parent, or to the root if the old parent is gone
+async fn restore_file(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: file::restore::Request = parse(&body)?;
+	let mut store = state.store();
+	let parent = current_file_mut(&mut store, user, request.uuid)?.parent;
+	let parent = match store.live_dir(user, parent) {
+		Ok(_) => parent,
+		Err(_) => store.user(user).root,
+	};
+	let file = store.file_mut(user, request.uuid)?;
+	file.trashed = false;
+	file.parent = parent;
+	let event = file_event!(FileRestore, file);
+	store.emit(user, "file-restore", event);
+	empty(&headers)
+}
+
+/// Deletes a file with all its versions, or a single old version of a file
+async fn delete_file(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: file::delete::permanent::Request = parse(&body)?;
+	let mut store = state.store();
+	let file = store.file(user, request.uuid)?;
+	let stable_uuid = file.stable_uuid;
+	let whole_file = file.archived_by.is_none();
+	if whole_file {
+		let versions = store
+			.versions(stable_uuid)
+			.into_iter()
+			.map(|f| f.uuid)
+			.collect::<Vec<_>>();
+		for version in versions {
+			store.delete_file(version);
+		}
+	} else {
+		store.delete_file(request.uuid);
+	}
+	store.emit(
+		user,
+		"file-deleted-permanent",
+		FileDeletedPermanent {
+			uuid: request.uuid,
+			stable_uuid: whole_file.then_some(stable_uuid),
+		},
+	);
+	empty(&headers)
+}
+
+async fn file_versions(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: file::versions::Request = parse(&body)?;
+	let store = state.store();
+	let stable_uuid = store.file(user, request.uuid)?.stable_uuid;
+	let versions = store
+		.versions(stable_uuid)
+		.into_iter()
+		.map(|f| file::versions::FileVersion {
+			bucket: Cow::Borrowed(BUCKET),
+			chunks: f.chunks,
+			metadata: encrypted(&f.metadata),
+			region: Cow::Borrowed(REGION),
+			timestamp: f.timestamp,
+			uuid: f.uuid,
+			stable_uuid: f.stable_uuid,
+			version: f.version,
+			size: f.size,
+		})
+		.collect();
+	ok(&headers, file::versions::Response { versions })
+}
+
+/// Makes an old version the current one. It takes the place of the current version, which is
+/// archived in turn.
+async fn restore_version(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: file::version::restore::Request = parse(&body)?;
+	let mut store = state.store();
+	let current = current_file_mut(&mut store, user, request.current)?;
+	let (stable_uuid, parent, name, name_hashed, favorited) = (
+		current.stable_uuid,
+		current.parent,
+		current.name.clone(),
+		current.name_hashed.clone(),
+		current.favorited,
+	);
+	if store.file(user, request.uuid)?.stable_uuid != stable_uuid {
+		return Err(ApiError::new(
+			"invalid_params",
+			"The version doesn't belong to this file",
+		));
+	}
+	for file in store.files.values_mut() {
+		if file.stable_uuid == stable_uuid {
+			file.archived_by = (file.uuid != request.uuid).then_some(request.uuid);
+		}
+	}
+	let restored = store.file_mut(user, request.uuid)?;
+	restored.parent = parent;
+	restored.name = name;
+	restored.name_hashed = name_hashed;
+	restored.favorited = favorited;
+	restored.trashed = false;
+	let event = FileArchiveRestored {
+		current_uuid: request.current,
+		stable_uuid,
+		parent: ParentUuid::Uuid(parent),
+		uuid: restored.uuid,
+		metadata: EncryptedString(Cow::Owned(restored.metadata.clone())),
+		timestamp: restored.timestamp,
+		chunks: restored.chunks,
+		size: restored.size,
+		bucket: Cow::Borrowed(BUCKET),
+		region: Cow::Borrowed(REGION),
+		version: restored.version,
+		favorited,
+	};
+	store.emit(user, "file-archive-restored", event);
+	let restored = store.file(user, request.uuid)?;
+	ok(
+		&headers,
+		file::version::restore::Response {
+			uuid: restored.uuid,
+			current_uuid: request.current,
+			stable_uuid,
+			metadata: encrypted(&restored.metadata),
+			bucket: Cow::Borrowed(BUCKET),
+			region: Cow::Borrowed(REGION),
+			chunks: restored.chunks,
+			parent: ParentUuid::Uuid(parent),
+			timestamp: restored.timestamp,
+			version: restored.version,
+			favorited,
+		},
+	)
+}
+
+async fn upload_empty(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: upload::empty::Request = parse(&body)?;
+	let mut store = state.store();
+	let response = add_file(&mut store, user, request, 0, 0, String::new())?;
+	ok(&headers, response)
+}
+
+async fn upload_done(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: upload::done::Request = parse(&body)?;
+	let mut store = state.store();
+	store.live_dir(user, request.empty_request.parent)?;
+	let size = store.finish_upload(
+		user,
+		request.empty_request.uuid,
+		&request.upload_key,
+		request.chunks,
+	)?;
+	let response = add_file(
+		&mut store,
+		user,
+		request.empty_request,
+		request.chunks,
+		size,
+		request.rm.into_owned(),
+	)?;
+	ok(&headers, response)
+}
+
+/// Adds an uploaded file. A file with the same name in the same directory becomes an old
+/// version of the new one.
+fn add_file(
+	store: &mut Store,
+	user: u64,
+	request: upload::empty::Request<'_>,
+	chunks: u64,
+	size: u64,
+	rm: String,
+) -> Result<upload::empty::Response, ApiError> {
+	store.live_dir(user, request.parent)?;
+	if store.files.contains_key(&request.uuid) || store.dirs.contains_key(&request.uuid) {
+		return Err(ApiError::new(
+			"uuid_already_exists",
+			"An item with this uuid already exists",
+		));
+	}
+	let replaced = store
+		.live_file_named(request.parent, &request.name_hashed)
+		.map(|f| (f.uuid, f.stable_uuid, f.favorited));
+	let stable_uuid = match replaced {
+		Some((_, stable_uuid, _)) => stable_uuid,
+		None => StableUuid::new_for_test(request.uuid),
+	};
+	for file in store.files.values_mut() {
+		if file.stable_uuid == stable_uuid {
+			file.archived_by = Some(request.uuid);
+		}
+	}
+	let file = File {
+		uuid: request.uuid,
+		stable_uuid,
+		owner: user,
+		parent: request.parent,
+		name: request.name.0.into_owned(),
+		name_hashed: request.name_hashed.into_owned(),
+		size_encrypted: request.size.0.into_owned(),
+		mime: request.mime.0.into_owned(),
+		metadata: request.metadata.0.into_owned(),
+		rm,
+		version: request.version,
+		chunks,
+		size,
+		timestamp: now(),
+		favorited: replaced.is_some_and(|(_, _, favorited)| favorited),
+		trashed: false,
+		archived_by: None,
+	};
+	let response = upload::empty::Response {
+		uuid: file.uuid,
+		stable_uuid,
+		chunks,
+		size,
+		timestamp: file.timestamp,
+	};
+	let event = file_event!(FileNew, &file);
+	store.files.insert(file.uuid, file);
+	if let Some((old_uuid, _, _)) = replaced {
+		store.emit(
+			user,
+			"file-versioned",
+			FileArchived {
+				uuid: old_uuid,
+				stable_uuid,
+				new_uuid: Some(request.uuid),
+			},
+		);
+	}
+	store.emit(user, "file-new", event);
+	Ok(response)
+}
+
+async fn favorite(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: item::favorite::Request = parse(&body)?;
+	let mut store = state.store();
+	let event = match request.r#type {
+		ObjectType::File => {
+			let file = current_file_mut(&mut store, user, request.uuid)?;
+			file.favorited = request.value;
+			ItemFavorite {
+				uuid: file.uuid,
+				stable_uuid: Some(file.stable_uuid),
+				item_type: ObjectType::File,
+				value: request.value,
+				parent: ParentUuid::Uuid(file.parent),
+				metadata: Some(EncryptedString(Cow::Owned(file.metadata.clone()))),
+				name_encrypted: None,
+				region: Some(Cow::Borrowed(REGION)),
+				bucket: Some(Cow::Borrowed(BUCKET)),
+				size: Some(file.size),
+				chunks: Some(file.chunks),
+				timestamp: file.timestamp,
+				color: DirColor::Default,
+				version: Some(file.version),
+			}
+		}
+		ObjectType::Dir => {
+			let dir = non_root_dir_mut(&mut store, user, request.uuid)?;
+			dir.favorited = request.value;
+			ItemFavorite {
+				uuid: dir.uuid,
+				stable_uuid: None,
+				item_type: ObjectType::Dir,
+				value: request.value,
+				parent: ParentUuid::Uuid(parent_of(dir)),
+				metadata: None,
+				name_encrypted: Some(EncryptedString(Cow::Owned(dir.meta.clone()))),
+				region: None,
+				bucket: None,
+				size: None,
+				chunks: None,
+				timestamp: dir.timestamp,
+				color: dir.color.clone(),
+				version: None,
+			}
+		}
+	};
+	store.emit(user, "item-favorite", event);
+	ok(&headers, request)
+}
+
+async fn empty_trash(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+) -> ApiResult {
+	let mut store = state.store();
+	let trashed_dirs = store
+		.dirs
+		.values()
+		.filter(|d| d.owner == user && d.trashed)
+		.map(|d| d.uuid)
+		.collect::<Vec<_>>();
+	for dir in trashed_dirs {
+		store.delete_dir(dir);
+	}
+	let trashed_files = store
+		.files
+		.values()
+		.filter(|f| f.owner == user && f.trashed && f.archived_by.is_none())
+		.map(|f| f.stable_uuid)
+		.collect::<Vec<_>>();
+	for stable_uuid in trashed_files {
+		let versions = store
+			.versions(stable_uuid)
+			.into_iter()
+			.map(|f| f.uuid)
+			.collect::<Vec<_>>();
+		for version in versions {
+			store.delete_file(version);
+		}
+	}
+	store.emit(user, "trash-empty", serde_json::json!({}));
+	empty(&headers)
+}
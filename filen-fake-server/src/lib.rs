@@ -0,0 +1,92 @@
+//! An in-process stand-in for the Filen backend, so SDK tests can run without an account or
+//! network access.
+//!
+//! [`FakeServer`] serves the v3 gateway endpoints the drive needs, chunk ingest and egest, and the
+//! socket.io event stream, all from one local HTTP server backed by memory. Point a client at it
+//! with `ApiEndpoints::single(server.url())`.
+//!
+//! Accounts work like on the real backend: register once, then log in with the same password.
+//! New accounts are activated right away. Endpoints that aren't implemented answer with an
+//! `endpoint_not_implemented` API error.
+//!
+//! The server never sees plaintext, just like the real one. It stores whatever encrypted metadata
+//! and chunks the client sends, so it can't check them beyond their shape.
+
+use std::{
+	io,
+	net::SocketAddr,
+	sync::{Arc, Mutex},
+};
+
+use tokio::{net::TcpListener, task::JoinHandle};
+
+mod chunks;
+mod drive;
+mod response;
+mod socket;
+mod store;
+mod user;
+
+use store::Store;
+
+pub(crate) struct ServerState {
+	store: Mutex<Store>,
+}
+
+impl ServerState {
+	/// Locks the store. A handler that panicked while holding the lock leaves the store as it was
+	/// at the panic, which is good enough for tests.
+	pub(crate) fn store(&self) -> std::sync::MutexGuard<'_, Store> {
+		self.store.lock().unwrap_or_else(|e| e.into_inner())
+	}
+}
+
+/// A fake Filen backend listening on a local port. It stops when dropped.
+pub struct FakeServer {
+	addr: SocketAddr,
+	task: JoinHandle<()>,
+}
+
+/// The router path of a gateway endpoint from `filen_types::api::v3`
+pub(crate) fn route(endpoint: &str) -> String {
+	format!("/{endpoint}")
+}
+
+impl FakeServer {
+	/// Starts a server on a random free port of `127.0.0.1`, on the current Tokio runtime.
+	pub async fn start() -> io::Result<Self> {
+		let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+		let addr = listener.local_addr()?;
+		let state = Arc::new(ServerState {
+			store: Mutex::new(Store::default()),
+		});
+		let router = axum::Router::new()
+			.merge(user::routes())
+			.merge(drive::routes())
+			.merge(chunks::routes())
+			.merge(socket::routes())
+			.fallback(response::not_implemented)
+			.with_state(state);
+		let task = tokio::spawn(async move {
+			if let Err(e) = axum::serve(listener, router).await {
+				tracing::error!("fake server stopped: {e}");
+			}
+		});
+		Ok(Self { addr, task })
+	}
+
+	/// The base URL of the server, e.g. `http://127.0.0.1:43567`.
+	pub fn url(&self) -> String {
+		format!("http://{}", self.addr)
+	}
+
+	pub fn addr(&self) -> SocketAddr {
+		self.addr
+	}
+}
+
+impl Drop for FakeServer {
+	fn drop(&mut self) {
+		self.task.abort();
+	}
+}
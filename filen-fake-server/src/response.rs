@@ -0,0 +1,103 @@
+//! The response envelope every gateway endpoint answers with.
+
+use axum::{
+	body::Bytes,
+	http::{HeaderMap, StatusCode, header::CONTENT_TYPE},
+	response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+/// An API error, sent as `{"status":false,"code":..,"message":..}`
+#[derive(Debug)]
+pub(crate) struct ApiError {
+	status: StatusCode,
+	code: &'static str,
+	message: String,
+}
+
+impl ApiError {
+	pub(crate) fn new(code: &'static str, message: impl Into<String>) -> Self {
+		Self {
+			status: StatusCode::OK,
+			code,
+			message: message.into(),
+		}
+	}
+
+	pub(crate) fn with_status(mut self, status: StatusCode) -> Self {
+		self.status = status;
+		self
+	}
+
+	pub(crate) fn unauthorized() -> Self {
+		Self::new("api_key_not_found", "Invalid API key").with_status(StatusCode::UNAUTHORIZED)
+	}
+
+	pub(crate) fn not_found(what: &str) -> Self {
+		Self::new(
+			"not_found",
+			format!("{what} not found or not owned by this account"),
+		)
+	}
+}
+
+impl IntoResponse for ApiError {
+	fn into_response(self) -> Response {
+		let body = serde_json::json!({
+			"status": false,
+			"message": self.message,
+			"code": self.code,
+		});
+		(
+			self.status,
+			[(CONTENT_TYPE, "application/json")],
+			body.to_string(),
+		)
+			.into_response()
+	}
+}
+
+pub(crate) type ApiResult = Result<Response, ApiError>;
+
+/// Parses a JSON request body into one of the request types from `filen_types::api::v3`
+pub(crate) fn parse<'a, T: Deserialize<'a>>(body: &'a Bytes) -> Result<T, ApiError> {
+	serde_json::from_slice(body).map_err(|e| ApiError::new("invalid_params", e.to_string()))
+}
+
+/// Wraps `data` in a successful response. Clients that ask for it with a `msgpack` header get the
+/// same structure msgpack-encoded, like the real gateway does for large listings.
+pub(crate) fn ok(headers: &HeaderMap, data: impl Serialize) -> ApiResult {
+	let data =
+		serde_json::to_value(data).map_err(|e| ApiError::new("internal_error", e.to_string()))?;
+	envelope(headers, Some(data))
+}
+
+/// A successful response without data, for endpoints the SDK reads as `()`
+pub(crate) fn empty(headers: &HeaderMap) -> ApiResult {
+	envelope(headers, None)
+}
+
+fn envelope(headers: &HeaderMap, data: Option<serde_json::Value>) -> ApiResult {
+	let mut body = serde_json::json!({
+		"status": true,
+		"message": "OK",
+		"code": "ok",
+	});
+	if let Some(data) = data {
+		body["data"] = data;
+	}
+	if headers.get("msgpack").is_some_and(|v| v == "1") {
+		let bytes =
+			rmp_serde::to_vec(&body).map_err(|e| ApiError::new("internal_error", e.to_string()))?;
+		return Ok(([(CONTENT_TYPE, "application/msgpack")], bytes).into_response());
+	}
+	Ok(([(CONTENT_TYPE, "application/json")], body.to_string()).into_response())
+}
+
+pub(crate) async fn not_implemented(uri: axum::http::Uri) -> ApiError {
+	ApiError::new(
+		"endpoint_not_implemented",
+		format!("{} is not implemented by the fake server", uri.path()),
+	)
+	.with_status(StatusCode::NOT_FOUND)
+}
@@ -0,0 +1,119 @@
+//! The socket.io (Engine.IO v3) event stream, over a plain websocket.
+//!
+//! Only what the SDK uses is implemented: the handshake, authentication with an API key, pings,
+//! and drive events for the authenticated user.
+
+use std::sync::Arc;
+
+use axum::{
+	Router,
+	extract::{
+		State,
+		ws::{Message, WebSocket, WebSocketUpgrade},
+	},
+	response::Response,
+	routing::get,
+};
+use futures::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use tokio::{select, sync::broadcast::error::RecvError};
+
+use crate::ServerState;
+
+const PING_INTERVAL_MS: u64 = 25_000;
+const PING_TIMEOUT_MS: u64 = 20_000;
+
+/// A drive event for one user's socket connections
+#[derive(Clone, Debug)]
+pub(crate) struct Event {
+	pub(crate) user: u64,
+	pub(crate) name: &'static str,
+	pub(crate) payload: Value,
+}
+
+pub(crate) fn routes() -> Router<Arc<ServerState>> {
+	Router::new().route("/socket.io/", get(upgrade))
+}
+
+async fn upgrade(State(state): State<Arc<ServerState>>, ws: WebSocketUpgrade) -> Response {
+	ws.on_upgrade(move |socket| async move {
+		if let Err(e) = serve(state, socket).await {
+			tracing::debug!("socket connection ended: {e}");
+		}
+	})
+}
+
+/// Builds a socket.io event message, `42["name",data]`
+fn event_message(name: &str, data: Option<Value>) -> Message {
+	let event = match data {
+		Some(data) => json!([name, data]),
+		None => json!([name]),
+	};
+	Message::text(format!("42{event}"))
+}
+
+async fn serve(state: Arc<ServerState>, socket: WebSocket) -> Result<(), axum::Error> {
+	let (mut write, mut read) = socket.split();
+	// Subscribe before anything else, so no event is missed between authenticating and listening.
+	let mut events = state.store().subscribe();
+	let handshake = json!({
+		"sid": hex::encode(rand::random::<[u8; 10]>()),
+		"upgrades": [],
+		"pingInterval": PING_INTERVAL_MS,
+		"pingTimeout": PING_TIMEOUT_MS,
+	});
+	write.send(Message::text(format!("0{handshake}"))).await?;
+	write.send(Message::text("40")).await?;
+
+	let mut user = None;
+	loop {
+		select! {
+			message = read.next() => {
+				let text = match message {
+					Some(Ok(Message::Text(text))) => text,
+					Some(Ok(Message::Close(_))) | None => return Ok(()),
+					Some(Ok(_)) => continue,
+					Some(Err(e)) => return Err(e),
+				};
+				if text.as_str() == "2" {
+					write.send(Message::text("3")).await?;
+					continue;
+				}
+				let Some(Ok(Value::Array(event))) =
+					text.as_str().strip_prefix("42").map(serde_json::from_str::<Value>)
+				else {
+					continue;
+				};
+				match event.first().and_then(Value::as_str) {
+					Some("authed") => {
+						let data = Value::Bool(user.is_some());
+						write.send(event_message("authed", Some(data))).await?;
+					}
+					Some("auth") => {
+						user = event
+							.get(1)
+							.and_then(|data| data.get("apiKey"))
+							.and_then(Value::as_str)
+							.and_then(|api_key| state.store().user_by_api_key(api_key));
+						let reply = if user.is_some() { "authSuccess" } else { "authFailed" };
+						write.send(event_message(reply, None)).await?;
+					}
+					_ => {}
+				}
+			}
+			event = events.recv() => {
+				let event = match event {
+					Ok(event) => event,
+					Err(RecvError::Lagged(missed)) => {
+						tracing::warn!("socket connection fell behind and missed {missed} events");
+						continue;
+					}
+					Err(RecvError::Closed) => return Ok(()),
+				};
+				if user == Some(event.user) {
+					write.send(event_message(event.name, Some(event.payload))).await?;
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,435 @@
+//! The in-memory state behind the fake server.
+
+use std::{
+	collections::HashMap,
+	time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use filen_types::{
+	api::v3::dir::color::DirColor,
+	auth::{AuthVersion, FileEncryptionVersion},
+	fs::{StableUuid, Uuid},
+	serde::rsa::RsaDerPublicKey,
+};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::{response::ApiError, socket::Event};
+
+/// The only bucket and region chunks are stored in
+pub(crate) const BUCKET: &str = "fake-bucket";
+pub(crate) const REGION: &str = "fake-region";
+
+/// How long a lock is held without a refresh, same as on the real backend
+const LOCK_TTL: Duration = Duration::from_secs(30);
+
+/// How many unsent socket events a slow socket connection may fall behind before it misses some
+const EVENT_BUFFER: usize = 1024;
+
+/// The current time, truncated to the millisecond precision timestamps have on the wire
+pub(crate) fn now() -> DateTime<Utc> {
+	let now = Utc::now();
+	DateTime::from_timestamp_millis(now.timestamp_millis()).unwrap_or(now)
+}
+
+pub(crate) struct User {
+	pub(crate) id: u64,
+	pub(crate) email: String,
+	pub(crate) salt: String,
+	pub(crate) auth_version: AuthVersion,
+	pub(crate) password: String,
+	pub(crate) api_key: String,
+	pub(crate) master_keys: Option<String>,
+	pub(crate) public_key: Option<RsaDerPublicKey<'static>>,
+	pub(crate) private_key: Option<String>,
+	pub(crate) dek: Option<String>,
+	pub(crate) root: Uuid,
+	last_drive_message_id: u64,
+}
+
+pub(crate) struct Dir {
+	pub(crate) uuid: Uuid,
+	pub(crate) owner: u64,
+	/// `None` for an account's root directory
+	pub(crate) parent: Option<Uuid>,
+	pub(crate) meta: String,
+	pub(crate) name_hashed: String,
+	pub(crate) color: DirColor<'static>,
+	pub(crate) timestamp: DateTime<Utc>,
+	pub(crate) favorited: bool,
+	pub(crate) trashed: bool,
+}
+
+pub(crate) struct File {
+	pub(crate) uuid: Uuid,
+	pub(crate) stable_uuid: StableUuid,
+	pub(crate) owner: u64,
+	pub(crate) parent: Uuid,
+	pub(crate) name: String,
+	pub(crate) name_hashed: String,
+	pub(crate) size_encrypted: String,
+	pub(crate) mime: String,
+	pub(crate) metadata: String,
+	pub(crate) rm: String,
+	pub(crate) version: FileEncryptionVersion,
+	pub(crate) chunks: u64,
+	/// The number of stored bytes, summed over all chunks
+	pub(crate) size: u64,
+	pub(crate) timestamp: DateTime<Utc>,
+	pub(crate) favorited: bool,
+	pub(crate) trashed: bool,
+	/// Set for old versions of a file, to the uuid of the version that replaced it
+	pub(crate) archived_by: Option<Uuid>,
+}
+
+/// Chunks uploaded for a file that wasn't finished with `upload/done` yet
+struct PendingUpload {
+	owner: u64,
+	upload_key: String,
+}
+
+struct Lock {
+	uuid: Uuid,
+	expires: Instant,
+}
+
+pub(crate) struct Store {
+	users: HashMap<u64, User>,
+	emails: HashMap<String, u64>,
+	api_keys: HashMap<String, u64>,
+	pub(crate) dirs: HashMap<Uuid, Dir>,
+	pub(crate) files: HashMap<Uuid, File>,
+	chunks: HashMap<(Uuid, u64), Bytes>,
+	uploads: HashMap<Uuid, PendingUpload>,
+	locks: HashMap<(u64, String), Lock>,
+	events: broadcast::Sender<Event>,
+}
+
+impl Default for Store {
+	fn default() -> Self {
+		Self {
+			users: HashMap::new(),
+			emails: HashMap::new(),
+			api_keys: HashMap::new(),
+			dirs: HashMap::new(),
+			files: HashMap::new(),
+			chunks: HashMap::new(),
+			uploads: HashMap::new(),
+			locks: HashMap::new(),
+			events: broadcast::channel(EVENT_BUFFER).0,
+		}
+	}
+}
+
+impl Store {
+	pub(crate) fn register(
+		&mut self,
+		email: &str,
+		salt: String,
+		password: String,
+		auth_version: AuthVersion,
+	) -> Result<(), ApiError> {
+		if self.emails.contains_key(email) {
+			return Err(ApiError::new(
+				"email_address_already_registered",
+				"This email address is already registered",
+			));
+		}
+		let id = self.users.len() as u64 + 1;
+		let root = Uuid::new_v4();
+		let api_key = hex::encode(rand::random::<[u8; 32]>());
+		self.dirs.insert(
+			root,
+			Dir {
+				uuid: root,
+				owner: id,
+				parent: None,
+				meta: String::new(),
+				name_hashed: String::new(),
+				color: DirColor::Default,
+				timestamp: now(),
+				favorited: false,
+				trashed: false,
+			},
+		);
+		self.emails.insert(email.to_owned(), id);
+		self.api_keys.insert(api_key.clone(), id);
+		self.users.insert(
+			id,
+			User {
+				id,
+				email: email.to_owned(),
+				salt,
+				auth_version,
+				password,
+				api_key,
+				master_keys: None,
+				public_key: None,
+				private_key: None,
+				dek: None,
+				root,
+				last_drive_message_id: 0,
+			},
+		);
+		Ok(())
+	}
+
+	pub(crate) fn user_by_email(&self, email: &str) -> Result<&User, ApiError> {
+		self.emails
+			.get(email)
+			.and_then(|id| self.users.get(id))
+			.ok_or_else(|| ApiError::new("email_not_found", "No account with this email address"))
+	}
+
+	pub(crate) fn user_by_api_key(&self, api_key: &str) -> Option<u64> {
+		self.api_keys.get(api_key).copied()
+	}
+
+	pub(crate) fn user(&self, id: u64) -> &User {
+		&self.users[&id]
+	}
+
+	pub(crate) fn user_mut(&mut self, id: u64) -> &mut User {
+		self.users.get_mut(&id).expect("authenticated users exist")
+	}
+
+	pub(crate) fn storage_used(&self, user: u64) -> u64 {
+		self.files
+			.values()
+			.filter(|f| f.owner == user)
+			.map(|f| f.size)
+			.sum()
+	}
+
+	pub(crate) fn dir(&self, user: u64, uuid: Uuid) -> Result<&Dir, ApiError> {
+		self.dirs
+			.get(&uuid)
+			.filter(|d| d.owner == user)
+			.ok_or_else(|| ApiError::not_found("Folder"))
+	}
+
+	pub(crate) fn dir_mut(&mut self, user: u64, uuid: Uuid) -> Result<&mut Dir, ApiError> {
+		self.dirs
+			.get_mut(&uuid)
+			.filter(|d| d.owner == user)
+			.ok_or_else(|| ApiError::not_found("Folder"))
+	}
+
+	/// Like [`Store::dir`], but only for directories that can hold new items
+	pub(crate) fn live_dir(&self, user: u64, uuid: Uuid) -> Result<&Dir, ApiError> {
+		self.dir(user, uuid)
+			.ok()
+			.filter(|d| !d.trashed)
+			.ok_or_else(|| ApiError::not_found("Parent folder"))
+	}
+
+	/// Any version of a file, including archived ones
+	pub(crate) fn file(&self, user: u64, uuid: Uuid) -> Result<&File, ApiError> {
+		self.files
+			.get(&uuid)
+			.filter(|f| f.owner == user)
+			.ok_or_else(|| ApiError::not_found("File"))
+	}
+
+	pub(crate) fn file_mut(&mut self, user: u64, uuid: Uuid) -> Result<&mut File, ApiError> {
+		self.files
+			.get_mut(&uuid)
+			.filter(|f| f.owner == user)
+			.ok_or_else(|| ApiError::not_found("File"))
+	}
+
+	/// The current versions of the files in a directory
+	pub(crate) fn files_in(&self, parent: Uuid) -> impl Iterator<Item = &File> {
+		self.files
+			.values()
+			.filter(move |f| f.parent == parent && f.archived_by.is_none())
+	}
+
+	pub(crate) fn dirs_in(&self, parent: Uuid) -> impl Iterator<Item = &Dir> {
+		self.dirs.values().filter(move |d| d.parent == Some(parent))
+	}
+
+	pub(crate) fn live_file_named(&self, parent: Uuid, name_hashed: &str) -> Option<&File> {
+		self.files_in(parent)
+			.find(|f| !f.trashed && f.name_hashed == name_hashed)
+	}
+
+	pub(crate) fn live_dir_named(&self, parent: Uuid, name_hashed: &str) -> Option<&Dir> {
+		self.dirs_in(parent)
+			.find(|d| !d.trashed && d.name_hashed == name_hashed)
+	}
+
+	/// The uuids of `root` and every directory below it, parents before children. Trashed
+	/// directories and everything in them are left out unless `with_trashed` is set.
+	pub(crate) fn dir_tree(&self, root: Uuid, with_trashed: bool) -> Vec<Uuid> {
+		let mut tree = vec![root];
+		let mut i = 0;
+		while let Some(&dir) = tree.get(i) {
+			tree.extend(
+				self.dirs_in(dir)
+					.filter(|d| with_trashed || !d.trashed)
+					.map(|d| d.uuid),
+			);
+			i += 1;
+		}
+		tree
+	}
+
+	/// All versions of the file with this stable uuid, newest first
+	pub(crate) fn versions(&self, stable_uuid: StableUuid) -> Vec<&File> {
+		let mut versions = self
+			.files
+			.values()
+			.filter(|f| f.stable_uuid == stable_uuid)
+			.collect::<Vec<_>>();
+		versions.sort_by_key(|f| std::cmp::Reverse((f.timestamp, f.archived_by.is_none())));
+		versions
+	}
+
+	pub(crate) fn store_chunk(
+		&mut self,
+		user: u64,
+		uuid: Uuid,
+		index: u64,
+		upload_key: &str,
+		data: Bytes,
+	) -> Result<(), ApiError> {
+		if self.files.contains_key(&uuid) {
+			return Err(ApiError::new(
+				"file_already_exists",
+				"A file with this uuid was already uploaded",
+			));
+		}
+		let upload = self.uploads.entry(uuid).or_insert_with(|| PendingUpload {
+			owner: user,
+			upload_key: upload_key.to_owned(),
+		});
+		if upload.owner != user || upload.upload_key != upload_key {
+			return Err(ApiError::new(
+				"invalid_upload_key",
+				"The upload key doesn't match the upload",
+			));
+		}
+		self.chunks.insert((uuid, index), data);
+		Ok(())
+	}
+
+	/// Ends an upload, returning the number of stored bytes
+	pub(crate) fn finish_upload(
+		&mut self,
+		user: u64,
+		uuid: Uuid,
+		upload_key: &str,
+		chunks: u64,
+	) -> Result<u64, ApiError> {
+		match self.uploads.get(&uuid) {
+			Some(upload) if upload.owner == user && upload.upload_key == upload_key => {}
+			_ => {
+				return Err(ApiError::new(
+					"upload_not_found",
+					"No chunks were uploaded with this uuid and upload key",
+				));
+			}
+		}
+		let mut size = 0;
+		for index in 0..chunks {
+			let chunk = self.chunks.get(&(uuid, index)).ok_or_else(|| {
+				ApiError::new("chunk_missing", format!("Chunk {index} was never uploaded"))
+			})?;
+			size += chunk.len() as u64;
+		}
+		self.uploads.remove(&uuid);
+		Ok(size)
+	}
+
+	pub(crate) fn chunk(&self, uuid: Uuid, index: u64) -> Option<Bytes> {
+		self.chunks.get(&(uuid, index)).cloned()
+	}
+
+	/// Removes a file version and its chunks for good
+	pub(crate) fn delete_file(&mut self, uuid: Uuid) -> Option<File> {
+		let file = self.files.remove(&uuid)?;
+		for index in 0..file.chunks {
+			self.chunks.remove(&(uuid, index));
+		}
+		Some(file)
+	}
+
+	/// Removes a directory and everything in it for good
+	pub(crate) fn delete_dir(&mut self, uuid: Uuid) {
+		for dir in self.dir_tree(uuid, true) {
+			let files = self
+				.files
+				.values()
+				.filter(|f| f.parent == dir)
+				.map(|f| f.uuid)
+				.collect::<Vec<_>>();
+			for file in files {
+				self.delete_file(file);
+			}
+			self.dirs.remove(&dir);
+		}
+	}
+
+	/// Takes, refreshes or releases `resource` for `uuid`, returning whether it succeeded
+	pub(crate) fn lock(&mut self, user: u64, resource: &str, uuid: Uuid, take: bool) -> bool {
+		let key = (user, resource.to_owned());
+		let now = Instant::now();
+		match self.locks.get(&key) {
+			Some(lock) if lock.expires > now && lock.uuid != uuid => false,
+			_ if take => {
+				self.locks.insert(
+					key,
+					Lock {
+						uuid,
+						expires: now + LOCK_TTL,
+					},
+				);
+				true
+			}
+			_ => self.locks.remove(&key).is_some(),
+		}
+	}
+
+	pub(crate) fn is_locked(&self, user: u64, resource: &str) -> bool {
+		self.locks
+			.get(&(user, resource.to_owned()))
+			.is_some_and(|lock| lock.expires > Instant::now())
+	}
+
+	pub(crate) fn subscribe(&self) -> broadcast::Receiver<Event> {
+		self.events.subscribe()
+	}
+
+	/// Sends a drive event to the user's socket connections.
+	///
+	/// Events are numbered while the store is locked, so they're sent in the order their
+	/// `driveMessageId`s say.
+	pub(crate) fn emit(&mut self, user: u64, name: &'static str, payload: impl Serialize) {
+		let mut payload = match serde_json::to_value(payload) {
+			Ok(serde_json::Value::Object(payload)) => payload,
+			Ok(other) => {
+				tracing::error!("socket event {name} isn't an object: {other}");
+				return;
+			}
+			Err(e) => {
+				tracing::error!("failed to serialize socket event {name}: {e}");
+				return;
+			}
+		};
+		let user_state = self.user_mut(user);
+		user_state.last_drive_message_id += 1;
+		payload.insert(
+			"driveMessageId".to_owned(),
+			user_state.last_drive_message_id.into(),
+		);
+		// Nobody listening is fine, events are only kept for connected sockets.
+		let _ = self.events.send(Event {
+			user,
+			name,
+			payload: payload.into(),
+		});
+	}
+}
@@ -0,0 +1,276 @@
+//! Accounts: registration, login and the per-user endpoints the SDK calls while logging in.
+
+use std::{borrow::Cow, sync::Arc};
+
+use axum::{
+	Router,
+	body::Bytes,
+	extract::{FromRequestParts, State},
+	http::{HeaderMap, header::AUTHORIZATION, request::Parts},
+	routing::{get, post},
+};
+use filen_types::{
+	api::v3::{auth, login, register, user},
+	auth::APIKey,
+	crypto::{EncryptedDEK, EncryptedMasterKeys, EncryptedString, rsa::EncryptedPrivateKey},
+	serde::rsa::RsaDerPublicKey,
+};
+
+use crate::{
+	ServerState,
+	response::{ApiError, ApiResult, empty, ok, parse},
+	route,
+};
+
+/// The storage quota every fake account gets
+const MAX_STORAGE: u64 = 10 * 1024 * 1024 * 1024;
+
+pub(crate) fn routes() -> Router<Arc<ServerState>> {
+	Router::new()
+		.route(&route(register::ENDPOINT), post(register))
+		.route(&route(auth::info::ENDPOINT), post(auth_info))
+		.route(&route(login::ENDPOINT), post(login))
+		.route(&route(user::master_keys::ENDPOINT), post(master_keys))
+		.route(&route(user::key_pair::info::ENDPOINT), get(key_pair_info))
+		.route(&route(user::key_pair::set::ENDPOINT), post(set_key_pair))
+		.route(&route(user::key_pair::update::ENDPOINT), post(set_key_pair))
+		.route(&route(user::dek::ENDPOINT), get(dek))
+		.route(&route(user::base_folder::ENDPOINT), get(base_folder))
+		.route(&route(user::info::ENDPOINT), get(user_info))
+		.route(&route(user::lock::ENDPOINT), post(lock))
+}
+
+/// The id of the user a request's API key belongs to
+pub(crate) struct Auth(pub(crate) u64);
+
+impl FromRequestParts<Arc<ServerState>> for Auth {
+	type Rejection = ApiError;
+
+	async fn from_request_parts(
+		parts: &mut Parts,
+		state: &Arc<ServerState>,
+	) -> Result<Self, Self::Rejection> {
+		parts
+			.headers
+			.get(AUTHORIZATION)
+			.and_then(|value| value.to_str().ok())
+			.and_then(|value| value.strip_prefix("Bearer "))
+			.and_then(|api_key| state.store().user_by_api_key(api_key))
+			.map(Auth)
+			.ok_or_else(ApiError::unauthorized)
+	}
+}
+
+async fn register(
+	State(state): State<Arc<ServerState>>,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: register::Request = parse(&body)?;
+	state.store().register(
+		&request.email,
+		request.salt.into_owned(),
+		request.password.0.into_owned(),
+		request.auth_version,
+	)?;
+	ok(&headers, register::Response {})
+}
+
+async fn auth_info(
+	State(state): State<Arc<ServerState>>,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: auth::info::Request = parse(&body)?;
+	let store = state.store();
+	let user = store.user_by_email(&request.email)?;
+	ok(
+		&headers,
+		auth::info::Response {
+			email: Cow::Borrowed(&user.email),
+			auth_version: user.auth_version,
+			salt: Cow::Borrowed(&user.salt),
+			user_id: user.id,
+		},
+	)
+}
+
+/// Accounts on the fake server have no two-factor authentication, so the code is ignored.
+async fn login(
+	State(state): State<Arc<ServerState>>,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: login::Request = parse(&body)?;
+	let store = state.store();
+	let user = store
+		.user_by_email(&request.email)
+		.ok()
+		.filter(|user| user.password == request.password.0)
+		.ok_or_else(|| {
+			ApiError::new(
+				"email_or_password_wrong",
+				"Email address or password is wrong",
+			)
+		})?;
+	ok(
+		&headers,
+		login::Response {
+			api_key: APIKey(Cow::Borrowed(&user.api_key)),
+			master_keys: user
+				.master_keys
+				.as_deref()
+				.map(|keys| EncryptedMasterKeys(EncryptedString(Cow::Borrowed(keys)))),
+			public_key: user.public_key.clone(),
+			private_key: user
+				.private_key
+				.as_deref()
+				.map(|key| EncryptedPrivateKey(EncryptedString(Cow::Borrowed(key)))),
+			dek: user
+				.dek
+				.as_deref()
+				.map(|dek| EncryptedDEK(EncryptedString(Cow::Borrowed(dek)))),
+		},
+	)
+}
+
+/// Stores the first master keys an account sends and answers every later call with them. The
+/// real backend merges key chains, which only matters after a password change.
+async fn master_keys(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: user::master_keys::Request = parse(&body)?;
+	let mut store = state.store();
+	let user = store.user_mut(user);
+	let keys = user
+		.master_keys
+		.get_or_insert_with(|| request.master_keys.0.0.into_owned());
+	ok(
+		&headers,
+		user::master_keys::Response {
+			keys: EncryptedMasterKeys(EncryptedString(Cow::Borrowed(keys))),
+		},
+	)
+}
+
+async fn key_pair_info(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+) -> ApiResult {
+	let store = state.store();
+	let user = store.user(user);
+	ok(
+		&headers,
+		user::key_pair::info::Response {
+			public_key: user.public_key.clone(),
+			private_key: user
+				.private_key
+				.as_deref()
+				.map(|key| EncryptedPrivateKey(EncryptedString(Cow::Borrowed(key)))),
+		},
+	)
+}
+
+async fn set_key_pair(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	let request: user::key_pair::set::Request = parse(&body)?;
+	let mut store = state.store();
+	let user = store.user_mut(user);
+	user.public_key = Some(RsaDerPublicKey(Cow::Owned(
+		request.public_key.0.into_owned(),
+	)));
+	user.private_key = Some(request.private_key.0.0.into_owned());
+	empty(&headers)
+}
+
+async fn dek(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+) -> ApiResult {
+	let store = state.store();
+	ok(
+		&headers,
+		user::dek::Response {
+			dek: store
+				.user(user)
+				.dek
+				.as_deref()
+				.map(|dek| EncryptedDEK(EncryptedString(Cow::Borrowed(dek)))),
+		},
+	)
+}
+
+async fn base_folder(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+) -> ApiResult {
+	let uuid = state.store().user(user).root;
+	ok(&headers, user::base_folder::Response { uuid })
+}
+
+async fn user_info(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+) -> ApiResult {
+	let store = state.store();
+	let storage_used = store.storage_used(user);
+	let user = store.user(user);
+	ok(
+		&headers,
+		user::info::Response {
+			id: user.id,
+			email: Cow::Borrowed(&user.email),
+			is_premium: false,
+			max_storage: MAX_STORAGE,
+			storage_used,
+			avatar_url: None,
+			root_dir_uuid: user.root,
+		},
+	)
+}
+
+async fn lock(
+	State(state): State<Arc<ServerState>>,
+	Auth(user): Auth,
+	headers: HeaderMap,
+	body: Bytes,
+) -> ApiResult {
+	use user::lock::{LockStatus, LockType, Response};
+
+	let request: user::lock::Request = parse(&body)?;
+	let mut store = state.store();
+	let mut response = Response {
+		acquired: false,
+		released: false,
+		refreshed: false,
+		resource: request.resource.clone(),
+		status: None,
+	};
+	match request.r#type {
+		LockType::Acquire => {
+			response.acquired = store.lock(user, &request.resource, request.uuid, true);
+		}
+		LockType::Refresh => {
+			response.refreshed = store.lock(user, &request.resource, request.uuid, true);
+		}
+		LockType::Release => {
+			response.released = store.lock(user, &request.resource, request.uuid, false);
+		}
+		LockType::Status => {}
+	}
+	response.status = store
+		.is_locked(user, &request.resource)
+		.then_some(LockStatus::Locked);
+	ok(&headers, response)
+}
@@ -0,0 +1,19 @@
+use std::path::PathBuf;
+
+use filen_sdk_rs::fs::cache::CacheableConversionError;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error("SDK error: {0}")]
+	Sdk(#[from] filen_sdk_rs::Error),
+	#[error("Failed to mount at {path}: {source}")]
+	Mount {
+		path: PathBuf,
+		#[source]
+		source: std::io::Error,
+	},
+	#[error("Mount root {0} is not a directory")]
+	NotADirectory(String),
+	#[error("Mount root can't be cached: {0}")]
+	Uncacheable(#[from] CacheableConversionError),
+}
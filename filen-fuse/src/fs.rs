@@ -0,0 +1,913 @@
+use std::{
+	borrow::Cow,
+	collections::HashMap,
+	ffi::OsStr,
+	future::Future,
+	path::PathBuf,
+	sync::{
+		Arc, Mutex, MutexGuard,
+		atomic::{AtomicU64, Ordering},
+	},
+	time::{Duration, SystemTime},
+};
+
+use chrono::Utc;
+use filen_sdk_rs::{
+	Error, ErrorKind,
+	auth::{Client, unauth::UnauthClient},
+	fs::{
+		HasName,
+		categories::{DirType, Normal},
+		dir::{RemoteDirectory, cache::CacheableDir, meta::DirectoryMetaChanges},
+		file::{RemoteFile, cache::CacheableFile, meta::FileMetaChanges},
+	},
+};
+use fuser::{
+	FileAttr, FileType, Filesystem, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory, ReplyEmpty,
+	ReplyEntry, ReplyOpen, ReplyStatfs, ReplyWrite, Request, TimeOrNow,
+};
+use libc::c_int;
+use tokio::runtime::Handle;
+
+use crate::{
+	handle::{OpenFile, WriteBack},
+	tree::{Item, Node, Tree},
+};
+
+/// How long the kernel may cache attributes and entries before asking again; changes from
+/// elsewhere are also pushed as invalidations, so this only bounds how stale a missed one gets
+const TTL: Duration = Duration::from_secs(1);
+const BLOCK_SIZE: u32 = 4096;
+const MAX_NAME_LENGTH: u32 = 255;
+
+/// State shared by the filesystem callbacks, the tasks they spawn, and the snapshot task.
+pub(crate) struct Shared {
+	pub(crate) client: Arc<Client>,
+	unauth_client: UnauthClient,
+	pub(crate) tree: Mutex<Tree>,
+	handles: Mutex<HashMap<u64, Arc<tokio::sync::Mutex<OpenFile>>>>,
+	next_fh: AtomicU64,
+	uid: u32,
+	gid: u32,
+	read_only: bool,
+	write_back_dir: PathBuf,
+}
+
+impl Shared {
+	pub(crate) fn new(
+		client: Arc<Client>,
+		tree: Tree,
+		read_only: bool,
+		write_back_dir: PathBuf,
+	) -> Self {
+		// SAFETY: getuid and getgid can't fail and have no preconditions
+		let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+		Self {
+			unauth_client: client.get_unauthed(),
+			client,
+			tree: Mutex::new(tree),
+			handles: Mutex::new(HashMap::new()),
+			next_fh: AtomicU64::new(1),
+			uid,
+			gid,
+			read_only,
+			write_back_dir,
+		}
+	}
+
+	pub(crate) fn tree(&self) -> MutexGuard<'_, Tree> {
+		self.tree.lock().unwrap_or_else(|e| e.into_inner())
+	}
+
+	fn handles(&self) -> MutexGuard<'_, HashMap<u64, Arc<tokio::sync::Mutex<OpenFile>>>> {
+		self.handles.lock().unwrap_or_else(|e| e.into_inner())
+	}
+
+	fn handle(&self, fh: u64) -> Option<Arc<tokio::sync::Mutex<OpenFile>>> {
+		self.handles().get(&fh).cloned()
+	}
+
+	fn open_handle(&self, file: OpenFile) -> u64 {
+		let fh = self.next_fh.fetch_add(1, Ordering::Relaxed);
+		self.handles()
+			.insert(fh, Arc::new(tokio::sync::Mutex::new(file)));
+		fh
+	}
+
+	fn attr(&self, ino: u64, node: &Node) -> FileAttr {
+		let (kind, perm, nlink) = if node.is_dir() {
+			(FileType::Directory, 0o755, 2)
+		} else {
+			(FileType::RegularFile, 0o644, 1)
+		};
+		let perm = if self.read_only { perm & !0o222 } else { perm };
+		let size = node.size();
+		let modified = SystemTime::from(node.modified());
+		FileAttr {
+			ino,
+			size,
+			blocks: size.div_ceil(512),
+			atime: modified,
+			mtime: modified,
+			ctime: modified,
+			crtime: node.created().map_or(modified, SystemTime::from),
+			kind,
+			perm,
+			nlink,
+			uid: self.uid,
+			gid: self.gid,
+			rdev: 0,
+			blksize: BLOCK_SIZE,
+			flags: 0,
+		}
+	}
+
+	fn node_attr(&self, ino: u64) -> Option<FileAttr> {
+		self.tree().node(ino).map(|node| self.attr(ino, node))
+	}
+
+	/// The directory `ino` as the SDK's move and create calls take it
+	fn dir_type(&self, ino: u64) -> Option<DirType<'static, Normal>> {
+		match &self.tree().node(ino)?.item {
+			Item::Root(None) => Some(DirType::Root(Cow::Owned(self.client.root().clone()))),
+			Item::Root(Some(dir)) | Item::Dir(dir) => {
+				Some(DirType::Dir(Cow::Owned(RemoteDirectory::from(dir.clone()))))
+			}
+			Item::File(_) | Item::NewFile { .. } => None,
+		}
+	}
+
+	fn remote_file(&self, ino: u64) -> Option<RemoteFile> {
+		match &self.tree().node(ino)?.item {
+			Item::File(file) => Some(RemoteFile::from(file.clone())),
+			_ => None,
+		}
+	}
+
+	/// Uploads `write_back` as a new version of `ino`, or as its first one if it's new.
+	async fn upload(&self, ino: u64, write_back: &mut WriteBack) -> Result<(), Error> {
+		let (name, parent, created) = {
+			let tree = self.tree();
+			// unlinked while it was open
+			let Some(node) = tree.node(ino) else {
+				return Ok(());
+			};
+			let Some(parent) = tree.dir_uuid(node.parent) else {
+				return Ok(());
+			};
+			(node.name.clone(), parent, node.created())
+		};
+		let mut builder = self
+			.client
+			.make_file_builder(&name, parent)?
+			.modified(Utc::now());
+		if let Some(created) = created {
+			builder = builder.created(created);
+		}
+		let file = write_back.upload(&self.client, builder).await?;
+		let file = CacheableFile::try_from(file).map_err(|(_, e)| e)?;
+		self.tree().set_item(ino, Item::File(file));
+		Ok(())
+	}
+}
+
+/// What `readdir` lists for directory `ino`: `.` and `..`, then its children by name
+fn dir_entries(
+	tree: &Tree,
+	ino: u64,
+) -> Result<impl Iterator<Item = (u64, FileType, &str)>, c_int> {
+	let node = match tree.node(ino) {
+		Some(node) if node.is_dir() => node,
+		Some(_) => return Err(libc::ENOTDIR),
+		None => return Err(libc::ENOENT),
+	};
+	Ok([
+		(ino, FileType::Directory, "."),
+		(node.parent, FileType::Directory, ".."),
+	]
+	.into_iter()
+	.chain(tree.children(ino).map(|(name, child)| {
+		let kind = match tree.node(child) {
+			Some(node) if node.is_dir() => FileType::Directory,
+			_ => FileType::RegularFile,
+		};
+		(child, kind, name)
+	})))
+}
+
+/// The errno reported for a failed SDK call
+fn errno(e: &Error) -> c_int {
+	match e.kind() {
+		ErrorKind::FileNotFound | ErrorKind::FolderNotFound => libc::ENOENT,
+		ErrorKind::InvalidName => libc::EINVAL,
+		ErrorKind::MaxStorageReached => libc::ENOSPC,
+		_ => {
+			tracing::error!("FUSE operation failed: {e}");
+			libc::EIO
+		}
+	}
+}
+
+/// The kernel-facing side of the mount.
+///
+/// Lookups, attributes and listings are answered from the [`Tree`] without blocking; anything
+/// that needs the network is spawned on the runtime the mount was started from and replies
+/// from there, so one slow request doesn't hold up the others.
+pub(crate) struct FilenFs {
+	shared: Arc<Shared>,
+	runtime: Handle,
+}
+
+impl FilenFs {
+	pub(crate) fn new(shared: Arc<Shared>, runtime: Handle) -> Self {
+		Self { shared, runtime }
+	}
+
+	fn spawn<F, Fut>(&self, f: F)
+	where
+		F: FnOnce(Arc<Shared>) -> Fut,
+		Fut: Future<Output = ()> + Send + 'static,
+	{
+		self.runtime.spawn(f(self.shared.clone()));
+	}
+
+	/// Uploads the write-back copy behind `fh` if it changed since the last upload.
+	fn sync_handle(&self, fh: u64, reply: ReplyEmpty) {
+		let Some(handle) = self.shared.handle(fh) else {
+			reply.error(libc::EBADF);
+			return;
+		};
+		self.spawn(move |shared| async move {
+			let mut handle = handle.lock().await;
+			let ino = handle.ino;
+			let result = match &mut handle.write_back {
+				Some(write_back) if write_back.is_dirty() => shared.upload(ino, write_back).await,
+				_ => Ok(()),
+			};
+			match result {
+				Ok(()) => reply.ok(),
+				Err(e) => reply.error(errno(&e)),
+			}
+		});
+	}
+
+	/// The inode of `name` in `parent`, or the errno to reply with
+	fn child(&self, parent: u64, name: &OsStr) -> Result<u64, c_int> {
+		let name = name.to_str().ok_or(libc::ENOENT)?;
+		self.shared.tree().lookup(parent, name).ok_or(libc::ENOENT)
+	}
+
+	/// `name` as an item name, checking that `parent` is a directory
+	fn new_name(&self, parent: u64, name: &OsStr) -> Result<String, c_int> {
+		if self.shared.read_only {
+			return Err(libc::EROFS);
+		}
+		let name = name.to_str().ok_or(libc::EINVAL)?;
+		match self.shared.tree().node(parent) {
+			Some(node) if node.is_dir() => Ok(name.to_owned()),
+			Some(_) => Err(libc::ENOTDIR),
+			None => Err(libc::ENOENT),
+		}
+	}
+}
+
+impl Filesystem for FilenFs {
+	fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+		match self
+			.child(parent, name)
+			.and_then(|ino| self.shared.node_attr(ino).ok_or(libc::ENOENT))
+		{
+			Ok(attr) => reply.entry(&TTL, &attr, 0),
+			Err(errno) => reply.error(errno),
+		}
+	}
+
+	fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+		match self.shared.node_attr(ino) {
+			Some(attr) => reply.attr(&TTL, &attr),
+			None => reply.error(libc::ENOENT),
+		}
+	}
+
+	fn setattr(
+		&mut self,
+		_req: &Request<'_>,
+		ino: u64,
+		_mode: Option<u32>,
+		_uid: Option<u32>,
+		_gid: Option<u32>,
+		size: Option<u64>,
+		_atime: Option<TimeOrNow>,
+		_mtime: Option<TimeOrNow>,
+		_ctime: Option<SystemTime>,
+		fh: Option<u64>,
+		_crtime: Option<SystemTime>,
+		_chgtime: Option<SystemTime>,
+		_bkuptime: Option<SystemTime>,
+		_flags: Option<u32>,
+		reply: ReplyAttr,
+	) {
+		// permissions, owners and times aren't stored, so only truncation changes anything
+		let Some(size) = size else {
+			match self.shared.node_attr(ino) {
+				Some(attr) => reply.attr(&TTL, &attr),
+				None => reply.error(libc::ENOENT),
+			}
+			return;
+		};
+		if self.shared.read_only {
+			reply.error(libc::EROFS);
+			return;
+		}
+		let handle = fh.and_then(|fh| self.shared.handle(fh));
+		self.spawn(move |shared| async move {
+			let result = async {
+				if let Some(handle) = handle {
+					let mut handle = handle.lock().await;
+					if let Some(write_back) = &mut handle.write_back {
+						write_back.set_len(size)?;
+						shared.tree().set_local_size(ino, Some(size));
+						return Ok(());
+					}
+				}
+				let mut write_back = match shared.remote_file(ino) {
+					Some(file) if size > 0 => {
+						WriteBack::load(&shared.unauth_client, &file, &shared.write_back_dir)
+							.await?
+					}
+					_ => WriteBack::empty(&shared.write_back_dir)?,
+				};
+				write_back.set_len(size)?;
+				shared.upload(ino, &mut write_back).await
+			}
+			.await;
+			match result.map(|()| shared.node_attr(ino)) {
+				Ok(Some(attr)) => reply.attr(&TTL, &attr),
+				Ok(None) => reply.error(libc::ENOENT),
+				Err(e) => reply.error(errno(&e)),
+			}
+		});
+	}
+
+	fn mkdir(
+		&mut self,
+		_req: &Request<'_>,
+		parent: u64,
+		name: &OsStr,
+		_mode: u32,
+		_umask: u32,
+		reply: ReplyEntry,
+	) {
+		let name = match self.new_name(parent, name) {
+			Ok(name) => name,
+			Err(errno) => {
+				reply.error(errno);
+				return;
+			}
+		};
+		self.spawn(move |shared| async move {
+			let result = async {
+				let Some(parent_dir) = shared.dir_type(parent) else {
+					return Err(Error::custom(
+						ErrorKind::FolderNotFound,
+						"parent was removed",
+					));
+				};
+				let dir = shared.client.create_dir(&parent_dir, &name).await?;
+				let dir = CacheableDir::try_from(dir).map_err(|(_, e)| e)?;
+				Ok(shared.tree().insert(parent, name, Item::Dir(dir)))
+			}
+			.await;
+			match result.map(|ino| shared.node_attr(ino)) {
+				Ok(Some(attr)) => reply.entry(&TTL, &attr, 0),
+				Ok(None) => reply.error(libc::ENOENT),
+				Err(e) => reply.error(errno(&e)),
+			}
+		});
+	}
+
+	fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+		if self.shared.read_only {
+			reply.error(libc::EROFS);
+			return;
+		}
+		let ino = match self.child(parent, name) {
+			Ok(ino) => ino,
+			Err(errno) => {
+				reply.error(errno);
+				return;
+			}
+		};
+		let file = {
+			let mut tree = self.shared.tree();
+			match tree.node(ino).map(|node| &node.item) {
+				Some(Item::File(file)) => Ok(Some(RemoteFile::from(file.clone()))),
+				// never uploaded, so there is nothing to trash
+				Some(Item::NewFile { .. }) => {
+					tree.remove(ino);
+					Ok(None)
+				}
+				Some(_) => Err(libc::EISDIR),
+				None => Err(libc::ENOENT),
+			}
+		};
+		let mut file = match file {
+			Ok(Some(file)) => file,
+			Ok(None) => {
+				reply.ok();
+				return;
+			}
+			Err(errno) => {
+				reply.error(errno);
+				return;
+			}
+		};
+		self.spawn(move |shared| async move {
+			match shared.client.trash_file(&mut file).await {
+				Ok(()) => {
+					shared.tree().remove(ino);
+					reply.ok();
+				}
+				Err(e) => reply.error(errno(&e)),
+			}
+		});
+	}
+
+	fn rmdir(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+		if self.shared.read_only {
+			reply.error(libc::EROFS);
+			return;
+		}
+		let ino = match self.child(parent, name) {
+			Ok(ino) => ino,
+			Err(errno) => {
+				reply.error(errno);
+				return;
+			}
+		};
+		let dir = {
+			let tree = self.shared.tree();
+			match tree.node(ino).map(|node| &node.item) {
+				Some(Item::Dir(_)) if tree.children(ino).next().is_some() => Err(libc::ENOTEMPTY),
+				Some(Item::Dir(dir)) => Ok(RemoteDirectory::from(dir.clone())),
+				Some(_) => Err(libc::ENOTDIR),
+				None => Err(libc::ENOENT),
+			}
+		};
+		let mut dir = match dir {
+			Ok(dir) => dir,
+			Err(errno) => {
+				reply.error(errno);
+				return;
+			}
+		};
+		self.spawn(move |shared| async move {
+			match shared.client.trash_dir(&mut dir).await {
+				Ok(()) => {
+					shared.tree().remove(ino);
+					reply.ok();
+				}
+				Err(e) => reply.error(errno(&e)),
+			}
+		});
+	}
+
+	fn rename(
+		&mut self,
+		_req: &Request<'_>,
+		parent: u64,
+		name: &OsStr,
+		newparent: u64,
+		newname: &OsStr,
+		flags: u32,
+		reply: ReplyEmpty,
+	) {
+		if flags & !libc::RENAME_NOREPLACE != 0 {
+			reply.error(libc::EINVAL);
+			return;
+		}
+		let checked = self.child(parent, name).and_then(|ino| {
+			let new_name = self.new_name(newparent, newname)?;
+			let tree = self.shared.tree();
+			let is_dir = tree.node(ino).ok_or(libc::ENOENT)?.is_dir();
+			let target = match tree.lookup(newparent, &new_name) {
+				Some(target) if target == ino => return Ok(None),
+				Some(_) if flags & libc::RENAME_NOREPLACE != 0 => return Err(libc::EEXIST),
+				Some(target) => {
+					let target_node = tree.node(target).ok_or(libc::ENOENT)?;
+					match (is_dir, target_node.is_dir()) {
+						(true, false) => return Err(libc::ENOTDIR),
+						(false, true) => return Err(libc::EISDIR),
+						(true, true) if tree.children(target).next().is_some() => {
+							return Err(libc::ENOTEMPTY);
+						}
+						_ => Some((target, target_node.item.clone())),
+					}
+				}
+				None => None,
+			};
+			Ok(Some((ino, new_name, target)))
+		});
+		let (ino, new_name, target) = match checked {
+			Ok(Some(rename)) => rename,
+			Ok(None) => {
+				reply.ok();
+				return;
+			}
+			Err(errno) => {
+				reply.error(errno);
+				return;
+			}
+		};
+		self.spawn(move |shared| async move {
+			let result = async {
+				// the drive doesn't replace items, so the one in the way goes to the trash first
+				if let Some((target, item)) = target {
+					match item {
+						Item::File(file) => {
+							shared
+								.client
+								.trash_file(&mut RemoteFile::from(file))
+								.await?
+						}
+						Item::Dir(dir) => {
+							shared
+								.client
+								.trash_dir(&mut RemoteDirectory::from(dir))
+								.await?
+						}
+						Item::Root(_) | Item::NewFile { .. } => {}
+					}
+					shared.tree().remove(target);
+				}
+
+				let item = shared.tree().node(ino).map(|node| node.item.clone());
+				let new_parent_dir = || {
+					shared.dir_type(newparent).ok_or_else(|| {
+						Error::custom(ErrorKind::FolderNotFound, "parent was removed")
+					})
+				};
+				match item {
+					Some(Item::File(file)) => {
+						let mut file = RemoteFile::from(file);
+						if file.name() != Some(new_name.as_str()) {
+							shared
+								.client
+								.update_file_metadata(
+									&mut file,
+									FileMetaChanges::default().name(&new_name)?,
+								)
+								.await?;
+						}
+						if parent != newparent {
+							shared
+								.client
+								.move_file(&mut file, &new_parent_dir()?)
+								.await?;
+						}
+						let file = CacheableFile::try_from(file).map_err(|(_, e)| e)?;
+						shared.tree().set_item(ino, Item::File(file));
+					}
+					Some(Item::Dir(dir)) => {
+						let mut dir = RemoteDirectory::from(dir);
+						if dir.name() != Some(new_name.as_str()) {
+							shared
+								.client
+								.update_dir_metadata(
+									&mut dir,
+									DirectoryMetaChanges::default().name(&new_name)?,
+								)
+								.await?;
+						}
+						if parent != newparent {
+							shared.client.move_dir(&mut dir, &new_parent_dir()?).await?;
+						}
+						let dir = CacheableDir::try_from(dir).map_err(|(_, e)| e)?;
+						shared.tree().set_item(ino, Item::Dir(dir));
+					}
+					// not uploaded yet, so it's uploaded under its new name
+					Some(Item::NewFile { .. }) => {}
+					Some(Item::Root(_)) | None => {
+						return Err(Error::custom(ErrorKind::FileNotFound, "item was removed"));
+					}
+				}
+				shared.tree().rename(ino, newparent, new_name);
+				Ok(())
+			}
+			.await;
+			match result {
+				Ok(()) => reply.ok(),
+				Err(e) => reply.error(errno(&e)),
+			}
+		});
+	}
+
+	fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+		let item = match self.shared.tree().node(ino) {
+			Some(node) if node.is_dir() => {
+				reply.error(libc::EISDIR);
+				return;
+			}
+			Some(node) => node.item.clone(),
+			None => {
+				reply.error(libc::ENOENT);
+				return;
+			}
+		};
+		if flags & libc::O_ACCMODE == libc::O_RDONLY {
+			let fh = self.shared.open_handle(OpenFile::new(ino, None));
+			reply.opened(fh, 0);
+			return;
+		}
+		if self.shared.read_only {
+			reply.error(libc::EROFS);
+			return;
+		}
+		self.spawn(move |shared| async move {
+			let write_back = match item {
+				Item::File(file) if flags & libc::O_TRUNC == 0 => {
+					WriteBack::load(
+						&shared.unauth_client,
+						&RemoteFile::from(file),
+						&shared.write_back_dir,
+					)
+					.await
+				}
+				_ => WriteBack::empty(&shared.write_back_dir).map_err(Error::from),
+			};
+			match write_back.and_then(|write_back| Ok((write_back.len()?, write_back))) {
+				Ok((size, write_back)) => {
+					shared.tree().set_local_size(ino, Some(size));
+					let fh = shared.open_handle(OpenFile::new(ino, Some(write_back)));
+					reply.opened(fh, 0);
+				}
+				Err(e) => reply.error(errno(&e)),
+			}
+		});
+	}
+
+	fn read(
+		&mut self,
+		_req: &Request<'_>,
+		_ino: u64,
+		fh: u64,
+		offset: i64,
+		size: u32,
+		_flags: i32,
+		_lock_owner: Option<u64>,
+		reply: ReplyData,
+	) {
+		let Some(handle) = self.shared.handle(fh) else {
+			reply.error(libc::EBADF);
+			return;
+		};
+		self.spawn(move |shared| async move {
+			let mut handle = handle.lock().await;
+			let file = shared.remote_file(handle.ino);
+			match handle
+				.read(
+					&shared.unauth_client,
+					file.as_ref(),
+					offset as u64,
+					u64::from(size),
+				)
+				.await
+			{
+				Ok(data) => reply.data(&data),
+				Err(e) => reply.error(errno(&e)),
+			}
+		});
+	}
+
+	fn write(
+		&mut self,
+		_req: &Request<'_>,
+		_ino: u64,
+		fh: u64,
+		offset: i64,
+		data: &[u8],
+		_write_flags: u32,
+		_flags: i32,
+		_lock_owner: Option<u64>,
+		reply: ReplyWrite,
+	) {
+		let Some(handle) = self.shared.handle(fh) else {
+			reply.error(libc::EBADF);
+			return;
+		};
+		let data = data.to_vec();
+		self.spawn(move |shared| async move {
+			let mut handle = handle.lock().await;
+			let ino = handle.ino;
+			let Some(write_back) = &mut handle.write_back else {
+				reply.error(libc::EBADF);
+				return;
+			};
+			match write_back.write(offset as u64, &data) {
+				Ok(size) => {
+					shared.tree().set_local_size(ino, Some(size));
+					reply.written(data.len() as u32);
+				}
+				Err(e) => reply.error(errno(&Error::from(e))),
+			}
+		});
+	}
+
+	fn flush(
+		&mut self,
+		_req: &Request<'_>,
+		_ino: u64,
+		fh: u64,
+		_lock_owner: u64,
+		reply: ReplyEmpty,
+	) {
+		self.sync_handle(fh, reply);
+	}
+
+	fn fsync(
+		&mut self,
+		_req: &Request<'_>,
+		_ino: u64,
+		fh: u64,
+		_datasync: bool,
+		reply: ReplyEmpty,
+	) {
+		self.sync_handle(fh, reply);
+	}
+
+	fn release(
+		&mut self,
+		_req: &Request<'_>,
+		_ino: u64,
+		fh: u64,
+		_flags: i32,
+		_lock_owner: Option<u64>,
+		_flush: bool,
+		reply: ReplyEmpty,
+	) {
+		let Some(handle) = self.shared.handles().remove(&fh) else {
+			reply.ok();
+			return;
+		};
+		reply.ok();
+		// normally flushed already, this only catches writes after the last flush
+		self.spawn(move |shared| async move {
+			let mut handle = handle.lock().await;
+			let ino = handle.ino;
+			if let Some(write_back) = &mut handle.write_back {
+				if write_back.is_dirty()
+					&& let Err(e) = shared.upload(ino, write_back).await
+				{
+					tracing::error!("Failed to upload {ino} after it was closed: {e}");
+				}
+				shared.tree().set_local_size(ino, None);
+			}
+		});
+	}
+
+	fn readdir(
+		&mut self,
+		_req: &Request<'_>,
+		ino: u64,
+		_fh: u64,
+		offset: i64,
+		mut reply: ReplyDirectory,
+	) {
+		let tree = self.shared.tree();
+		let entries = match dir_entries(&tree, ino) {
+			Ok(entries) => entries,
+			Err(e) => {
+				reply.error(e);
+				return;
+			}
+		};
+		for (i, (ino, kind, name)) in entries.enumerate().skip(offset as usize) {
+			if reply.add(ino, i as i64 + 1, kind, name) {
+				break;
+			}
+		}
+		reply.ok();
+	}
+
+	fn statfs(&mut self, _req: &Request<'_>, _ino: u64, reply: ReplyStatfs) {
+		self.spawn(move |shared| async move {
+			match shared.client.get_user_info().await {
+				Ok(info) => {
+					let block_size = u64::from(BLOCK_SIZE);
+					let free = info.max_storage.saturating_sub(info.storage_used) / block_size;
+					reply.statfs(
+						info.max_storage / block_size,
+						free,
+						free,
+						shared.tree().len() as u64,
+						u64::from(u32::MAX),
+						BLOCK_SIZE,
+						MAX_NAME_LENGTH,
+						BLOCK_SIZE,
+					);
+				}
+				Err(e) => reply.error(errno(&e)),
+			}
+		});
+	}
+
+	fn create(
+		&mut self,
+		_req: &Request<'_>,
+		parent: u64,
+		name: &OsStr,
+		_mode: u32,
+		_umask: u32,
+		_flags: i32,
+		reply: ReplyCreate,
+	) {
+		let name = match self.new_name(parent, name) {
+			Ok(name) => name,
+			Err(errno) => {
+				reply.error(errno);
+				return;
+			}
+		};
+		let write_back = match WriteBack::empty(&self.shared.write_back_dir) {
+			Ok(write_back) => write_back,
+			Err(e) => {
+				reply.error(errno(&Error::from(e)));
+				return;
+			}
+		};
+		let ino = {
+			let mut tree = self.shared.tree();
+			let ino = tree.insert(
+				parent,
+				name,
+				Item::NewFile {
+					created: Utc::now(),
+				},
+			);
+			tree.set_local_size(ino, Some(0));
+			ino
+		};
+		let fh = self
+			.shared
+			.open_handle(OpenFile::new(ino, Some(write_back)));
+		match self.shared.node_attr(ino) {
+			Some(attr) => reply.created(&TTL, &attr, 0, fh, 0),
+			None => reply.error(libc::ENOENT),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use uuid::Uuid;
+
+	use super::*;
+	use crate::tree::ROOT_INO;
+
+	#[test]
+	fn dir_entries_list_dot_entries_then_children() {
+		let mut tree = Tree::new(Uuid::new_v4(), None);
+		let b = tree.insert(
+			ROOT_INO,
+			"b.txt".to_owned(),
+			Item::NewFile {
+				created: Utc::now(),
+			},
+		);
+		let a = tree.insert(
+			ROOT_INO,
+			"a".to_owned(),
+			Item::Dir(CacheableDir {
+				uuid: Uuid::new_v4(),
+				parent: Uuid::new_v4(),
+				color: Default::default(),
+				favorited: false,
+				timestamp: Utc::now(),
+				name: "a".into(),
+				created: None,
+			}),
+		);
+
+		let entries = dir_entries(&tree, ROOT_INO).unwrap().collect::<Vec<_>>();
+		assert_eq!(
+			entries,
+			[
+				(ROOT_INO, FileType::Directory, "."),
+				(ROOT_INO, FileType::Directory, ".."),
+				(a, FileType::Directory, "a"),
+				(b, FileType::RegularFile, "b.txt"),
+			]
+		);
+		let entries = dir_entries(&tree, a).unwrap().collect::<Vec<_>>();
+		assert_eq!(
+			entries,
+			[
+				(a, FileType::Directory, "."),
+				(ROOT_INO, FileType::Directory, ".."),
+			]
+		);
+
+		assert_eq!(dir_entries(&tree, b).err(), Some(libc::ENOTDIR));
+		assert_eq!(dir_entries(&tree, b + 1).err(), Some(libc::ENOENT));
+	}
+}
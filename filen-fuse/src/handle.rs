@@ -0,0 +1,200 @@
+use std::{io::SeekFrom, os::unix::fs::FileExt, path::Path};
+
+use bytes::Bytes;
+use filen_sdk_rs::{
+	Error,
+	auth::{Client, unauth::UnauthClient},
+	consts::CHUNK_SIZE_U64,
+	fs::file::{FileBuilder, RemoteFile, read::FileReaderBuilder},
+	io::HasFileInfo,
+};
+use futures::AsyncReadExt;
+use tokio::io::AsyncSeekExt;
+use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
+
+/// How much is downloaded at once for reads without a write-back copy, so sequential reads
+/// don't issue a request per kernel read
+const READ_WINDOW: u64 = 4 * CHUNK_SIZE_U64;
+
+/// The state behind one kernel file handle.
+pub(crate) struct OpenFile {
+	pub(crate) ino: u64,
+	/// The local copy writes go to, set for handles opened for writing
+	pub(crate) write_back: Option<WriteBack>,
+	/// The last range downloaded for reads, by its offset
+	window: Option<(u64, Bytes)>,
+}
+
+impl OpenFile {
+	pub(crate) fn new(ino: u64, write_back: Option<WriteBack>) -> Self {
+		Self {
+			ino,
+			write_back,
+			window: None,
+		}
+	}
+
+	/// Reads up to `size` bytes at `offset`, from the write-back copy if there is one.
+	pub(crate) async fn read(
+		&mut self,
+		client: &UnauthClient,
+		file: Option<&RemoteFile>,
+		offset: u64,
+		size: u64,
+	) -> Result<Bytes, Error> {
+		if let Some(write_back) = &self.write_back {
+			return write_back.read(offset, size).map(Bytes::from);
+		}
+		let Some(file) = file else {
+			return Ok(Bytes::new());
+		};
+		let end = (offset + size).min(file.size());
+		if offset >= end {
+			return Ok(Bytes::new());
+		}
+		if let Some((start, window)) = &self.window
+			&& *start <= offset
+			&& start + window.len() as u64 >= end
+		{
+			let from = (offset - start) as usize;
+			return Ok(window.slice(from..from + (end - offset) as usize));
+		}
+
+		// windows start on chunk boundaries, so no chunk is downloaded for only part of it
+		let start = offset - offset % CHUNK_SIZE_U64;
+		let window_end = (start + READ_WINDOW).max(end).min(file.size());
+		let mut reader = FileReaderBuilder::new(client, file)
+			.with_start(start)
+			.with_end(window_end)
+			.build();
+		let mut data = Vec::with_capacity((window_end - start) as usize);
+		reader.read_to_end(&mut data).await?;
+		let window = Bytes::from(data);
+		let from = ((offset - start) as usize).min(window.len());
+		let to = ((end - start) as usize).min(window.len());
+		let read = window.slice(from..to);
+		self.window = Some((start, window));
+		Ok(read)
+	}
+}
+
+/// A local copy of a file that is uploaded as a new version once it was written to.
+pub(crate) struct WriteBack {
+	file: std::fs::File,
+	dirty: bool,
+}
+
+impl WriteBack {
+	/// An empty copy, which is uploaded even if it's never written to.
+	pub(crate) fn empty(dir: &Path) -> std::io::Result<Self> {
+		Ok(Self {
+			file: tempfile::tempfile_in(dir)?,
+			dirty: true,
+		})
+	}
+
+	/// A copy of `file`'s current contents.
+	pub(crate) async fn load(
+		client: &UnauthClient,
+		file: &RemoteFile,
+		dir: &Path,
+	) -> Result<Self, Error> {
+		let local = tempfile::tempfile_in(dir)?;
+		let mut writer = tokio::fs::File::from_std(local.try_clone()?).compat_write();
+		let mut reader = FileReaderBuilder::new(client, file).build();
+		futures::io::copy(&mut reader, &mut writer).await?;
+		Ok(Self {
+			file: local,
+			dirty: false,
+		})
+	}
+
+	pub(crate) fn is_dirty(&self) -> bool {
+		self.dirty
+	}
+
+	pub(crate) fn len(&self) -> std::io::Result<u64> {
+		Ok(self.file.metadata()?.len())
+	}
+
+	fn read(&self, offset: u64, size: u64) -> Result<Vec<u8>, Error> {
+		let size = size.min(self.len()?.saturating_sub(offset));
+		let mut data = vec![0; size as usize];
+		self.file.read_exact_at(&mut data, offset)?;
+		Ok(data)
+	}
+
+	/// Writes `data` at `offset`, returning the copy's new length.
+	pub(crate) fn write(&mut self, offset: u64, data: &[u8]) -> std::io::Result<u64> {
+		self.file.write_all_at(data, offset)?;
+		self.dirty = true;
+		self.len()
+	}
+
+	pub(crate) fn set_len(&mut self, size: u64) -> std::io::Result<()> {
+		self.file.set_len(size)?;
+		self.dirty = true;
+		Ok(())
+	}
+
+	/// Uploads the copy as `builder`, after which it's clean until written to again.
+	pub(crate) async fn upload(
+		&mut self,
+		client: &Client,
+		builder: FileBuilder,
+	) -> Result<RemoteFile, Error> {
+		let size = self.len()?;
+		let mut local = tokio::fs::File::from_std(self.file.try_clone()?);
+		local.seek(SeekFrom::Start(0)).await?;
+		let mut reader = local.compat();
+		let file = client
+			.upload_file_from_reader(builder, &mut reader, None, Some(size))
+			.await?;
+		self.dirty = false;
+		Ok(file)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn write_back_reads_what_was_written() {
+		let dir = tempfile::tempdir().unwrap();
+		let mut write_back = WriteBack::empty(dir.path()).unwrap();
+		assert!(
+			write_back.is_dirty(),
+			"an empty copy is uploaded even if unwritten"
+		);
+		assert_eq!(write_back.len().unwrap(), 0);
+
+		assert_eq!(write_back.write(0, b"hello").unwrap(), 5);
+		// writing past the end leaves a hole of zeros
+		assert_eq!(write_back.write(7, b"world").unwrap(), 12);
+		assert_eq!(write_back.read(0, 100).unwrap(), b"hello\0\0world");
+		assert_eq!(write_back.read(3, 4).unwrap(), b"lo\0\0");
+		assert!(write_back.read(20, 4).unwrap().is_empty());
+
+		write_back.set_len(4).unwrap();
+		assert_eq!(write_back.read(0, 100).unwrap(), b"hell");
+	}
+
+	#[test]
+	fn write_back_is_dirty_once_changed() {
+		let dir = tempfile::tempdir().unwrap();
+		let clean = || WriteBack {
+			file: tempfile::tempfile_in(dir.path()).unwrap(),
+			dirty: false,
+		};
+
+		let mut write_back = clean();
+		write_back.write(0, b"a").unwrap();
+		assert!(write_back.is_dirty());
+
+		let mut write_back = clean();
+		write_back.set_len(10).unwrap();
+		assert!(write_back.is_dirty());
+		assert_eq!(write_back.len().unwrap(), 10);
+	}
+}
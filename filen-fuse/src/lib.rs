@@ -0,0 +1,163 @@
+//! A FUSE filesystem over a Filen drive, without an external binary like rclone.
+//!
+//! Metadata comes from the SDK's SQLite cache through one live, recursive search over the
+//! mounted directory, so lookups and listings never touch the network and the drive events the
+//! cache receives over the socket turn into kernel invalidations. Reads are downloaded in
+//! windows, and writes go to a local copy that is uploaded as a new version on flush or close.
+//! Deleted items are moved to the trash.
+#![cfg(target_os = "linux")]
+
+mod error;
+mod fs;
+mod handle;
+mod tree;
+
+use std::{
+	ffi::OsStr,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+use filen_sdk_rs::{
+	auth::Client,
+	cache::{Search, SearchConfig, SearchWindowHandle},
+	fs::{HasUUID, categories::NonRootFileType, dir::cache::CacheableDir},
+};
+use fuser::{BackgroundSession, MountOption, Notifier};
+use tokio::task::JoinHandle;
+
+pub use error::Error;
+
+use fs::{FilenFs, Shared};
+use tree::{Invalidation, Tree};
+
+#[derive(Debug, Clone, Default)]
+pub struct MountOptions {
+	/// Drive directory to mount, e.g. `Documents/Shared`; the whole drive when `None`.
+	pub root: Option<String>,
+	/// Mounts the filesystem read-only.
+	pub read_only: bool,
+	/// Lets other users access the mount, which needs `user_allow_other` in `/etc/fuse.conf`.
+	pub allow_other: bool,
+	/// Where local copies of files open for writing are kept; the system temp directory when
+	/// `None`.
+	pub write_back_dir: Option<PathBuf>,
+}
+
+/// A mounted drive, unmounted by [`unmount`](Self::unmount) or when dropped.
+pub struct Mount {
+	session: BackgroundSession,
+	search: Search,
+	_window: SearchWindowHandle,
+	updates: JoinHandle<()>,
+}
+
+impl Mount {
+	/// Unmounts the filesystem, waiting for the kernel to release it.
+	///
+	/// Uploads of files that were still open are abandoned.
+	pub async fn unmount(self) {
+		self.updates.abort();
+		let session = self.session;
+		let _ = tokio::task::spawn_blocking(move || session.umount_and_join()).await;
+		self.search.close().await;
+	}
+}
+
+/// Mounts `client`'s drive at `mount_point`.
+///
+/// The client's cache has to be configured with
+/// [`configure_cache`](Client::configure_cache) first. Must be called within a multi-threaded
+/// Tokio runtime, which serves the filesystem's requests until it's unmounted.
+pub async fn mount(
+	client: Arc<Client>,
+	mount_point: impl AsRef<Path>,
+	options: MountOptions,
+) -> Result<Mount, Error> {
+	let mount_point = mount_point.as_ref();
+	let root_path = options.root.as_deref().unwrap_or_default();
+	let (root_uuid, root_dir) = match client.find_item_at_path(root_path).await? {
+		Some(NonRootFileType::Root(root)) => (root.uuid(), None),
+		Some(NonRootFileType::Dir(dir)) => {
+			let dir = CacheableDir::try_from(dir.into_owned()).map_err(|(_, e)| e)?;
+			(dir.uuid, Some(dir))
+		}
+		_ => return Err(Error::NotADirectory(root_path.to_owned())),
+	};
+
+	let search = client
+		.clone()
+		.create_search(root_uuid, SearchConfig::new())
+		.await?;
+	let (sender, mut snapshots) = tokio::sync::mpsc::unbounded_channel();
+	let (snapshot, window) = search
+		.get_range(
+			0..usize::MAX,
+			Box::new(move |snapshot| {
+				let _ = sender.send(snapshot);
+			}),
+		)
+		.await?;
+	let mut tree = Tree::new(root_uuid, root_dir);
+	tree.apply_snapshot(&snapshot.results);
+
+	let write_back_dir = options.write_back_dir.unwrap_or_else(std::env::temp_dir);
+	let shared = Arc::new(Shared::new(client, tree, options.read_only, write_back_dir));
+	let mut mount_options = vec![
+		MountOption::FSName("filen".to_owned()),
+		MountOption::Subtype("filen".to_owned()),
+		MountOption::DefaultPermissions,
+		MountOption::NoAtime,
+	];
+	if options.read_only {
+		mount_options.push(MountOption::RO);
+	}
+	if options.allow_other {
+		mount_options.push(MountOption::AllowOther);
+	}
+	let session = fuser::spawn_mount2(
+		FilenFs::new(shared.clone(), tokio::runtime::Handle::current()),
+		mount_point,
+		&mount_options,
+	)
+	.map_err(|source| Error::Mount {
+		path: mount_point.to_owned(),
+		source,
+	})?;
+
+	let notifier = Arc::new(session.notifier());
+	let updates = tokio::spawn(async move {
+		while let Some(snapshot) = snapshots.recv().await {
+			let invalidations = shared.tree().apply_snapshot(&snapshot.results);
+			if !invalidations.is_empty() {
+				let notifier = notifier.clone();
+				let _ =
+					tokio::task::spawn_blocking(move || invalidate(&notifier, invalidations)).await;
+			}
+			if !snapshot.live {
+				tracing::warn!(
+					"Mount root was deleted or the cache stopped, the mount is now stale"
+				);
+				break;
+			}
+		}
+	});
+
+	Ok(Mount {
+		session,
+		search,
+		_window: window,
+		updates,
+	})
+}
+
+/// Tells the kernel to forget what changed on the drive. Errors are ignored: the kernel
+/// answers `ENOENT` for anything it doesn't have cached.
+fn invalidate(notifier: &Notifier, invalidations: Vec<Invalidation>) {
+	for invalidation in invalidations {
+		let _ = match invalidation {
+			Invalidation::Entry { parent, name } => notifier.inval_entry(parent, OsStr::new(&name)),
+			Invalidation::Inode(ino) => notifier.inval_inode(ino, 0, 0),
+		};
+	}
+}
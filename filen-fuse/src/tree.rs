@@ -0,0 +1,541 @@
+use std::{
+	collections::{BTreeMap, HashMap},
+	time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use filen_sdk_rs::{
+	cache::{SearchHit, SearchResult},
+	fs::{dir::cache::CacheableDir, file::cache::CacheableFile},
+};
+use uuid::Uuid;
+
+/// The inode FUSE reserves for the root of the filesystem (`FUSE_ROOT_ID`)
+pub(crate) const ROOT_INO: u64 = 1;
+/// How long a local change wins over cache snapshots, which only see it once its drive event
+/// came back over the socket
+const LOCAL_CHANGE_GRACE: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub(crate) enum Item {
+	/// The mounted directory; `None` when it's the drive root
+	Root(Option<CacheableDir<'static>>),
+	Dir(CacheableDir<'static>),
+	File(CacheableFile<'static>),
+	/// Created locally and not uploaded yet, which happens when its first handle is flushed
+	NewFile {
+		created: DateTime<Utc>,
+	},
+}
+
+impl Item {
+	fn from_result(result: &SearchResult) -> Self {
+		match result {
+			SearchResult::Dir(dir) => Self::Dir(dir.clone()),
+			SearchResult::File(file) => Self::File(file.clone()),
+		}
+	}
+
+	/// What identifies the item across snapshots: directories by uuid, files by stable uuid,
+	/// since an upload mints a new uuid
+	fn key(&self) -> Option<Uuid> {
+		match self {
+			Self::Dir(dir) => Some(dir.uuid),
+			Self::File(file) => Some(Uuid::from(file.stable_uuid)),
+			Self::Root(_) | Self::NewFile { .. } => None,
+		}
+	}
+
+	/// Changes whenever the file's contents do
+	fn version(&self) -> Option<Uuid> {
+		match self {
+			Self::File(file) => Some(file.uuid),
+			_ => None,
+		}
+	}
+}
+
+fn result_key(result: &SearchResult) -> Uuid {
+	match result {
+		SearchResult::Dir(dir) => dir.uuid,
+		SearchResult::File(file) => Uuid::from(file.stable_uuid),
+	}
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Node {
+	pub(crate) parent: u64,
+	pub(crate) name: String,
+	pub(crate) item: Item,
+	/// The size of the local write-back copy, while one is open
+	pub(crate) local_size: Option<u64>,
+}
+
+impl Node {
+	pub(crate) fn is_dir(&self) -> bool {
+		matches!(self.item, Item::Root(_) | Item::Dir(_))
+	}
+
+	pub(crate) fn size(&self) -> u64 {
+		self.local_size.unwrap_or(match &self.item {
+			Item::File(file) => file.size,
+			_ => 0,
+		})
+	}
+
+	pub(crate) fn modified(&self) -> DateTime<Utc> {
+		match &self.item {
+			Item::File(file) => file.last_modified,
+			Item::Dir(dir) | Item::Root(Some(dir)) => dir.timestamp,
+			Item::NewFile { created } => *created,
+			Item::Root(None) => DateTime::UNIX_EPOCH,
+		}
+	}
+
+	pub(crate) fn created(&self) -> Option<DateTime<Utc>> {
+		match &self.item {
+			Item::File(file) => file.created,
+			Item::Dir(dir) | Item::Root(Some(dir)) => dir.created,
+			Item::NewFile { created } => Some(*created),
+			Item::Root(None) => None,
+		}
+	}
+}
+
+/// What the kernel has to forget after a snapshot changed the tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Invalidation {
+	/// The name `name` in directory `parent`, which now points elsewhere or nowhere
+	Entry { parent: u64, name: String },
+	/// The cached attributes and pages of a file whose contents changed
+	Inode(u64),
+}
+
+/// The mounted subtree by inode, mirrored from the cache's snapshots and patched by local
+/// changes until the cache catches up with them.
+pub(crate) struct Tree {
+	root_uuid: Uuid,
+	nodes: HashMap<u64, Node>,
+	children: HashMap<u64, BTreeMap<String, u64>>,
+	/// Inodes by [`Item::key`], so items keep theirs across snapshots
+	inos: HashMap<Uuid, u64>,
+	next_ino: u64,
+	/// Inodes changed locally, and until when they win over snapshots
+	recent: HashMap<u64, Instant>,
+	/// Keys of items removed locally, kept out of snapshots until then
+	removed: HashMap<Uuid, Instant>,
+}
+
+impl Tree {
+	pub(crate) fn new(root_uuid: Uuid, root: Option<CacheableDir<'static>>) -> Self {
+		let root = Node {
+			parent: ROOT_INO,
+			name: String::new(),
+			item: Item::Root(root),
+			local_size: None,
+		};
+		Self {
+			root_uuid,
+			nodes: HashMap::from([(ROOT_INO, root)]),
+			children: HashMap::new(),
+			inos: HashMap::from([(root_uuid, ROOT_INO)]),
+			next_ino: ROOT_INO,
+			recent: HashMap::new(),
+			removed: HashMap::new(),
+		}
+	}
+
+	pub(crate) fn len(&self) -> usize {
+		self.nodes.len()
+	}
+
+	pub(crate) fn node(&self, ino: u64) -> Option<&Node> {
+		self.nodes.get(&ino)
+	}
+
+	pub(crate) fn lookup(&self, parent: u64, name: &str) -> Option<u64> {
+		self.children.get(&parent)?.get(name).copied()
+	}
+
+	/// The entries of directory `ino`, sorted by name
+	pub(crate) fn children(&self, ino: u64) -> impl Iterator<Item = (&str, u64)> {
+		self.children
+			.get(&ino)
+			.into_iter()
+			.flatten()
+			.map(|(name, ino)| (name.as_str(), *ino))
+	}
+
+	/// The uuid of directory `ino`, which new children are created in
+	pub(crate) fn dir_uuid(&self, ino: u64) -> Option<Uuid> {
+		match &self.nodes.get(&ino)?.item {
+			Item::Root(_) => Some(self.root_uuid),
+			Item::Dir(dir) => Some(dir.uuid),
+			Item::File(_) | Item::NewFile { .. } => None,
+		}
+	}
+
+	fn allocate(&mut self) -> u64 {
+		self.next_ino += 1;
+		self.next_ino
+	}
+
+	fn touch(&mut self, ino: u64) {
+		self.recent.insert(ino, Instant::now() + LOCAL_CHANGE_GRACE);
+	}
+
+	fn link(&mut self, ino: u64) {
+		if let Some(node) = self.nodes.get(&ino) {
+			self.children
+				.entry(node.parent)
+				.or_default()
+				.insert(node.name.clone(), ino);
+		}
+	}
+
+	fn unlink(&mut self, ino: u64) {
+		if let Some(node) = self.nodes.get(&ino)
+			&& let Some(siblings) = self.children.get_mut(&node.parent)
+			&& siblings.get(&node.name) == Some(&ino)
+		{
+			siblings.remove(&node.name);
+		}
+	}
+
+	/// Adds an item created locally, replacing whatever `name` pointed to in `parent`.
+	pub(crate) fn insert(&mut self, parent: u64, name: String, item: Item) -> u64 {
+		let ino = self.allocate();
+		if let Some(key) = item.key() {
+			self.inos.insert(key, ino);
+		}
+		self.nodes.insert(
+			ino,
+			Node {
+				parent,
+				name,
+				item,
+				local_size: None,
+			},
+		);
+		self.link(ino);
+		self.touch(ino);
+		ino
+	}
+
+	/// Replaces the item at `ino` with its state after a local change, like a move.
+	pub(crate) fn set_item(&mut self, ino: u64, item: Item) {
+		if let Some(key) = item.key() {
+			// a snapshot may have seen the change first, under an inode of its own
+			if let Some(other) = self.inos.insert(key, ino).filter(|other| *other != ino) {
+				self.unlink(other);
+				self.nodes.remove(&other);
+			}
+		}
+		if let Some(node) = self.nodes.get_mut(&ino) {
+			node.item = item;
+		}
+		self.link(ino);
+		self.touch(ino);
+	}
+
+	pub(crate) fn set_local_size(&mut self, ino: u64, size: Option<u64>) {
+		if let Some(node) = self.nodes.get_mut(&ino) {
+			node.local_size = size;
+		}
+	}
+
+	pub(crate) fn rename(&mut self, ino: u64, parent: u64, name: String) {
+		self.unlink(ino);
+		if let Some(node) = self.nodes.get_mut(&ino) {
+			node.parent = parent;
+			node.name = name;
+		}
+		self.link(ino);
+		self.touch(ino);
+	}
+
+	pub(crate) fn remove(&mut self, ino: u64) {
+		self.unlink(ino);
+		if let Some(key) = self.nodes.remove(&ino).and_then(|node| node.item.key()) {
+			self.inos.remove(&key);
+			self.removed
+				.insert(key, Instant::now() + LOCAL_CHANGE_GRACE);
+		}
+		self.recent.remove(&ino);
+	}
+
+	/// Replaces the tree with the cache's current view of the subtree, returning what the
+	/// kernel has to forget.
+	///
+	/// Items keep their inodes, so open handles and the kernel's caches stay valid across
+	/// snapshots. Local changes the cache hasn't caught up with yet, and files that were never
+	/// uploaded, survive.
+	pub(crate) fn apply_snapshot(&mut self, hits: &[SearchHit]) -> Vec<Invalidation> {
+		let now = Instant::now();
+		self.recent.retain(|_, until| *until > now);
+		self.removed.retain(|_, until| *until > now);
+		let hits = hits
+			.iter()
+			.filter(|hit| !self.removed.contains_key(&result_key(&hit.result)))
+			.collect::<Vec<_>>();
+
+		// hits aren't ordered parents first, so every inode is known before nodes are built
+		let mut inos = HashMap::with_capacity(hits.len() + 1);
+		inos.insert(self.root_uuid, ROOT_INO);
+		for hit in &hits {
+			let key = result_key(&hit.result);
+			let ino = match self.inos.get(&key) {
+				Some(ino) => *ino,
+				None => self.allocate(),
+			};
+			inos.insert(key, ino);
+		}
+
+		let mut old_nodes = std::mem::take(&mut self.nodes);
+		let root = old_nodes
+			.remove(&ROOT_INO)
+			.expect("the root node is never removed");
+		self.nodes.insert(ROOT_INO, root);
+		let mut invalidations = Vec::new();
+		for hit in &hits {
+			let ino = inos[&result_key(&hit.result)];
+			let Some(&parent) = inos.get(&hit.result.parent()) else {
+				continue;
+			};
+			let mut node = Node {
+				parent,
+				name: hit.result.name().to_owned(),
+				item: Item::from_result(&hit.result),
+				local_size: None,
+			};
+			match old_nodes.remove(&ino) {
+				Some(old) if self.recent.contains_key(&ino) => node = old,
+				Some(old) => {
+					if old.parent != node.parent || old.name != node.name {
+						invalidations.push(Invalidation::Entry {
+							parent: old.parent,
+							name: old.name,
+						});
+						invalidations.push(Invalidation::Entry {
+							parent,
+							name: node.name.clone(),
+						});
+					}
+					if old.item.version() != node.item.version() {
+						invalidations.push(Invalidation::Inode(ino));
+					}
+					node.local_size = old.local_size;
+				}
+				// also drops a cached negative lookup of the name
+				None => invalidations.push(Invalidation::Entry {
+					parent,
+					name: node.name.clone(),
+				}),
+			}
+			self.nodes.insert(ino, node);
+		}
+
+		for (ino, old) in old_nodes {
+			if matches!(old.item, Item::NewFile { .. }) || self.recent.contains_key(&ino) {
+				if let Some(key) = old.item.key() {
+					inos.insert(key, ino);
+				}
+				self.nodes.insert(ino, old);
+			} else {
+				invalidations.push(Invalidation::Entry {
+					parent: old.parent,
+					name: old.name,
+				});
+			}
+		}
+		self.inos = inos;
+		self.rebuild_children();
+		invalidations
+	}
+
+	fn rebuild_children(&mut self) {
+		self.children.clear();
+		// files that weren't uploaded yet hide what they are going to replace
+		let (new_files, others): (Vec<_>, Vec<_>) = self
+			.nodes
+			.iter()
+			.filter(|(ino, _)| **ino != ROOT_INO)
+			.partition(|(_, node)| matches!(node.item, Item::NewFile { .. }));
+		for (ino, node) in others.into_iter().chain(new_files) {
+			self.children
+				.entry(node.parent)
+				.or_default()
+				.insert(node.name.clone(), *ino);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn dir(uuid: Uuid, parent: Uuid, name: &str) -> SearchHit {
+		SearchHit {
+			result: SearchResult::Dir(CacheableDir {
+				uuid,
+				parent,
+				color: Default::default(),
+				favorited: false,
+				timestamp: Utc::now(),
+				name: name.to_owned().into(),
+				created: None,
+			}),
+			parent_path: "".into(),
+			highlights: Vec::new(),
+		}
+	}
+
+	#[test]
+	fn items_keep_their_inodes() {
+		let (root, a, b) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+		let mut tree = Tree::new(root, None);
+		tree.apply_snapshot(&[dir(b, a, "b"), dir(a, root, "a")]);
+		let a_ino = tree.lookup(ROOT_INO, "a").unwrap();
+		let b_ino = tree.lookup(a_ino, "b").unwrap();
+
+		let invalidations = tree.apply_snapshot(&[dir(a, root, "c"), dir(b, a, "b")]);
+		assert_eq!(tree.lookup(ROOT_INO, "c"), Some(a_ino));
+		assert_eq!(tree.lookup(ROOT_INO, "a"), None);
+		assert_eq!(tree.lookup(a_ino, "b"), Some(b_ino));
+		assert!(invalidations.contains(&Invalidation::Entry {
+			parent: ROOT_INO,
+			name: "a".to_owned(),
+		}));
+
+		let invalidations = tree.apply_snapshot(&[dir(a, root, "c")]);
+		assert_eq!(
+			invalidations,
+			[Invalidation::Entry {
+				parent: a_ino,
+				name: "b".to_owned(),
+			}]
+		);
+		assert!(tree.node(b_ino).is_none());
+	}
+
+	#[test]
+	fn local_changes_survive_stale_snapshots() {
+		let (root, a) = (Uuid::new_v4(), Uuid::new_v4());
+		let mut tree = Tree::new(root, None);
+		tree.apply_snapshot(&[dir(a, root, "a")]);
+		let a_ino = tree.lookup(ROOT_INO, "a").unwrap();
+		let new_file = tree.insert(
+			ROOT_INO,
+			"new.txt".to_owned(),
+			Item::NewFile {
+				created: Utc::now(),
+			},
+		);
+
+		tree.remove(a_ino);
+		tree.apply_snapshot(&[dir(a, root, "a")]);
+		assert_eq!(tree.lookup(ROOT_INO, "a"), None);
+		assert_eq!(tree.lookup(ROOT_INO, "new.txt"), Some(new_file));
+
+		let b = Uuid::new_v4();
+		let b_ino = tree.insert(
+			ROOT_INO,
+			"b".to_owned(),
+			Item::Dir(match dir(b, root, "b").result {
+				SearchResult::Dir(dir) => dir,
+				SearchResult::File(_) => unreachable!(),
+			}),
+		);
+		tree.rename(b_ino, ROOT_INO, "c".to_owned());
+		tree.apply_snapshot(&[dir(b, root, "b")]);
+		assert_eq!(tree.lookup(ROOT_INO, "c"), Some(b_ino));
+		assert_eq!(tree.lookup(ROOT_INO, "b"), None);
+	}
+
+	fn new_file() -> Item {
+		Item::NewFile {
+			created: Utc::now(),
+		}
+	}
+
+	#[test]
+	fn lookup_and_children() {
+		let (root, a, b) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+		let mut tree = Tree::new(root, None);
+		tree.apply_snapshot(&[dir(b, root, "b"), dir(a, root, "a")]);
+		let a_ino = tree.lookup(ROOT_INO, "a").unwrap();
+		let b_ino = tree.lookup(ROOT_INO, "b").unwrap();
+		let file_ino = tree.insert(a_ino, "file.txt".to_owned(), new_file());
+
+		assert_eq!(
+			tree.children(ROOT_INO).collect::<Vec<_>>(),
+			[("a", a_ino), ("b", b_ino)]
+		);
+		assert_eq!(
+			tree.children(a_ino).collect::<Vec<_>>(),
+			[("file.txt", file_ino)]
+		);
+		assert_eq!(tree.children(b_ino).count(), 0);
+		assert_eq!(tree.children(file_ino).count(), 0);
+		assert_eq!(tree.lookup(a_ino, "file.txt"), Some(file_ino));
+		assert_eq!(tree.lookup(ROOT_INO, "file.txt"), None);
+		assert_eq!(tree.lookup(file_ino, "a"), None);
+		assert_eq!(tree.len(), 4);
+
+		// new children go into the directory's uuid, files have none
+		assert_eq!(tree.dir_uuid(ROOT_INO), Some(root));
+		assert_eq!(tree.dir_uuid(a_ino), Some(a));
+		assert_eq!(tree.dir_uuid(file_ino), None);
+	}
+
+	#[test]
+	fn rename_moves_entries() {
+		let (root, a, b) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+		let mut tree = Tree::new(root, None);
+		tree.apply_snapshot(&[dir(a, root, "a"), dir(b, root, "b")]);
+		let a_ino = tree.lookup(ROOT_INO, "a").unwrap();
+		let b_ino = tree.lookup(ROOT_INO, "b").unwrap();
+		let file_ino = tree.insert(a_ino, "x.txt".to_owned(), new_file());
+
+		// within a directory
+		tree.rename(file_ino, a_ino, "y.txt".to_owned());
+		assert_eq!(tree.lookup(a_ino, "x.txt"), None);
+		assert_eq!(tree.lookup(a_ino, "y.txt"), Some(file_ino));
+
+		// into another one, keeping the inode
+		tree.rename(file_ino, b_ino, "z.txt".to_owned());
+		assert_eq!(tree.children(a_ino).count(), 0);
+		assert_eq!(tree.lookup(b_ino, "z.txt"), Some(file_ino));
+		let node = tree.node(file_ino).unwrap();
+		assert_eq!((node.parent, node.name.as_str()), (b_ino, "z.txt"));
+
+		// a directory takes its children along
+		tree.rename(b_ino, a_ino, "b".to_owned());
+		assert_eq!(tree.lookup(ROOT_INO, "b"), None);
+		assert_eq!(tree.lookup(a_ino, "b"), Some(b_ino));
+		assert_eq!(tree.lookup(b_ino, "z.txt"), Some(file_ino));
+	}
+
+	#[test]
+	fn replaced_names_stay_with_their_new_inode() {
+		let mut tree = Tree::new(Uuid::new_v4(), None);
+		let old = tree.insert(ROOT_INO, "a.txt".to_owned(), new_file());
+		let new = tree.insert(ROOT_INO, "a.txt".to_owned(), new_file());
+		assert_eq!(tree.lookup(ROOT_INO, "a.txt"), Some(new));
+
+		// dropping the replaced inode leaves the name to the new one
+		tree.remove(old);
+		assert!(tree.node(old).is_none());
+		assert_eq!(tree.lookup(ROOT_INO, "a.txt"), Some(new));
+
+		// renaming onto an existing name points it at the renamed inode
+		let other = tree.insert(ROOT_INO, "b.txt".to_owned(), new_file());
+		tree.rename(other, ROOT_INO, "a.txt".to_owned());
+		assert_eq!(tree.lookup(ROOT_INO, "a.txt"), Some(other));
+		assert_eq!(tree.lookup(ROOT_INO, "b.txt"), None);
+
+		tree.remove(other);
+		assert_eq!(tree.lookup(ROOT_INO, "a.txt"), None);
+		assert_eq!(tree.children(ROOT_INO).count(), 0);
+	}
+}
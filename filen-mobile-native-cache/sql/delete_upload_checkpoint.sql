@@ -0,0 +1,3 @@
+-- Drops a file's upload checkpoint, for an upload that has to start over.
+DELETE FROM upload_checkpoints
+WHERE stable_uuid = ?1;
@@ -167,6 +167,31 @@ CREATE TABLE change_meta (
 
 INSERT INTO change_meta (id, db_instance) VALUES (0, randomblob(16));
 
+-- Where an edit's upload got to, so an attempt after the process died resumes
+-- from the last chunk the server confirmed instead of the file's first byte.
+-- Keyed on `stable_uuid` like `pending_upload_at`, which it only ever
+-- accompanies: the trigger below drops it together with the marker.
+-- `source_size` and `source_modified` (millis) fingerprint the cached bytes it
+-- was taken from — resuming over different bytes would upload half of each, so
+-- a mismatch discards it.
+CREATE TABLE upload_checkpoints (
+	stable_uuid BLOB PRIMARY KEY NOT NULL,
+	checkpoint TEXT NOT NULL,
+	source_size INTEGER NOT NULL,
+	source_modified INTEGER NOT NULL
+);
+
+-- An edit that reached the server, or was dropped, has no upload left to
+-- resume.
+CREATE TRIGGER drop_upload_checkpoint_with_marker
+AFTER UPDATE OF pending_upload_at ON items
+FOR EACH ROW
+WHEN new.pending_upload_at IS NULL AND new.stable_uuid IS NOT NULL
+BEGIN
+	DELETE FROM upload_checkpoints
+	WHERE stable_uuid = new.stable_uuid;
+END;
+
 CREATE TRIGGER cascade_on_update_uuid_delete_children
 AFTER UPDATE OF uuid ON items
 FOR EACH ROW
@@ -0,0 +1,5 @@
+-- The checkpoint of a file's interrupted upload, with the fingerprint of the
+-- cached bytes it was taken from.
+SELECT checkpoint, source_size, source_modified
+FROM upload_checkpoints
+WHERE stable_uuid = ?1;
@@ -0,0 +1,9 @@
+-- Saves the latest checkpoint (?2) of a file's upload, replacing the one
+-- before it, along with the size (?3) and modification millis (?4) of the
+-- cached bytes being uploaded.
+INSERT INTO upload_checkpoints (stable_uuid, checkpoint, source_size, source_modified)
+VALUES (?1, ?2, ?3, ?4)
+ON CONFLICT (stable_uuid) DO UPDATE SET
+	checkpoint = excluded.checkpoint,
+	source_size = excluded.source_size,
+	source_modified = excluded.source_modified;
@@ -3,7 +3,10 @@ use std::{
 	io::{self},
 	path::{Path, PathBuf},
 	str::FromStr,
-	sync::Arc,
+	sync::{
+		Arc,
+		atomic::{AtomicBool, Ordering},
+	},
 	time::{Duration, SystemTime},
 };
 
@@ -12,7 +15,7 @@ use crate::{
 		AUTH_CLEANUP_INTERVAL, AuthCacheState, AuthStatus, CacheState, DB_FILE_NAME,
 		FilenMobileCacheState, update_saved_db_state_cache_cleanup_time,
 	},
-	sql::{self, item::RawDBItem},
+	sql::{self, SavedUploadCheckpoint, item::RawDBItem},
 	traits::ProgressCallback,
 };
 use chrono::{DateTime, Utc};
@@ -20,13 +23,17 @@ use filen_sdk_rs::{
 	error::FilenSdkError,
 	fs::{
 		HasName, HasUUID,
-		file::{FileBuilderOptionalName, RemoteFile, traits::HasFileInfo},
+		file::{
+			FileBuilderOptionalName, RemoteFile,
+			checkpoint::{UploadCheckpoint, UploadCheckpointStore},
+			traits::HasFileInfo,
+		},
 	},
 	io::{FilenMetaExt, client_impl::IoSharedClientExt},
 };
 use filen_types::{
 	crypto::Blake3Hash,
-	fs::{Uuid, UuidStr},
+	fs::{StableUuid, Uuid, UuidStr},
 };
 use futures::{StreamExt, stream::FuturesUnordered};
 use tokio::{fs::DirEntry, sync::mpsc::UnboundedReceiver};
@@ -76,6 +83,88 @@ pub(crate) fn init(files_path: &Path) -> Result<(PathBuf, PathBuf, PathBuf), io:
 	Ok((cache_dir, tmp_dir, thumbnail_dir))
 }
 
+/// Saves the checkpoints of an edit's upload into the cache database, so the next attempt resumes
+/// where this one stopped if it never finishes. See `upload_checkpoints` in `init.sql`.
+struct DbUploadCheckpoints<'a> {
+	state: &'a AuthCacheState,
+	stable_uuid: StableUuid,
+	source_size: i64,
+	source_modified: i64,
+	/// Whether this attempt got anywhere, which a resume that failed without saving did not
+	saved: AtomicBool,
+}
+
+impl DbUploadCheckpoints<'_> {
+	/// The checkpoint to resume from, if there is one for these bytes. Best effort: anything that
+	/// can't be resumed is dropped, and the upload starts over.
+	fn load(&self) -> Option<UploadCheckpoint> {
+		let saved = match sql::select_upload_checkpoint(&self.state.conn(), self.stable_uuid) {
+			Ok(saved) => saved?,
+			Err(e) => {
+				tracing::warn!(
+					"Failed to read upload checkpoint for {}: {e}",
+					self.stable_uuid
+				);
+				return None;
+			}
+		};
+		if saved.source_size == self.source_size && saved.source_modified == self.source_modified {
+			match serde_json::from_str(&saved.checkpoint) {
+				Ok(checkpoint) => return Some(checkpoint),
+				Err(e) => {
+					tracing::warn!(
+						"Failed to parse upload checkpoint for {}: {e}",
+						self.stable_uuid
+					)
+				}
+			}
+		} else {
+			debug!(
+				"Cached bytes of {} changed since its upload checkpoint, starting over",
+				self.stable_uuid
+			);
+		}
+		self.discard();
+		None
+	}
+
+	fn discard(&self) {
+		if let Err(e) = sql::delete_upload_checkpoint(&self.state.conn(), self.stable_uuid) {
+			tracing::warn!(
+				"Failed to drop upload checkpoint for {}: {e}",
+				self.stable_uuid
+			);
+		}
+	}
+}
+
+impl UploadCheckpointStore for DbUploadCheckpoints<'_> {
+	fn save(&self, checkpoint: &UploadCheckpoint) {
+		self.saved.store(true, Ordering::Relaxed);
+		let checkpoint = match serde_json::to_string(checkpoint) {
+			Ok(checkpoint) => checkpoint,
+			Err(e) => {
+				tracing::warn!("Failed to serialize upload checkpoint: {e}");
+				return;
+			}
+		};
+		if let Err(e) = sql::upsert_upload_checkpoint(
+			&self.state.conn(),
+			self.stable_uuid,
+			&SavedUploadCheckpoint {
+				checkpoint,
+				source_size: self.source_size,
+				source_modified: self.source_modified,
+			},
+		) {
+			tracing::warn!(
+				"Failed to save upload checkpoint for {}: {e}",
+				self.stable_uuid
+			);
+		}
+	}
+}
+
 pub(crate) async fn update_task(
 	mut receiver: UnboundedReceiver<u64>,
 	file_size: u64,
@@ -259,6 +348,7 @@ impl AuthCacheState {
 		path: PathBuf,
 		builder: FileBuilderOptionalName,
 		callback: Option<Arc<dyn ProgressCallback>>,
+		checkpoints: Option<(&dyn UploadCheckpointStore, Option<UploadCheckpoint>)>,
 	) -> Result<(RemoteFile, std::fs::File), FilenSdkError> {
 		let (sender, receiver) = tokio::sync::mpsc::unbounded_channel::<u64>();
 		// redundant metadata call, we do it again in upload_file_from_path, but we need the size here
@@ -276,9 +366,18 @@ impl AuthCacheState {
 			None
 		};
 
-		self.client
-			.upload_file_from_path_with_builder(builder, path, reader_callback)
-			.await
+		match checkpoints {
+			Some((store, resume)) => {
+				self.client
+					.upload_file_from_path_resumable(builder, path, reader_callback, store, resume)
+					.await
+			}
+			None => {
+				self.client
+					.upload_file_from_path_with_builder(builder, path, reader_callback)
+					.await
+			}
+		}
 	}
 
 	/// Uploads the edited local copy and moves it under the uuid the server minted for it.
@@ -291,9 +390,14 @@ impl AuthCacheState {
 	/// It is `None` when the server handed back the uuid we already had. The caller holds that
 	/// uuid's lock for the duration of the edit, and these locks are not reentrant, so taking it
 	/// again here would hang the task forever.
+	///
+	/// The upload saves checkpoints under the file's stable id and resumes from the last one an
+	/// earlier attempt left for the same bytes, so a multi-GB edit interrupted by the process
+	/// dying doesn't start over from its first byte.
 	pub(crate) async fn io_upload_updated_file(
 		&self,
 		old_uuid: Uuid,
+		stable_uuid: StableUuid,
 		name: String,
 		parent_uuid: Uuid,
 		mime: String,
@@ -301,12 +405,34 @@ impl AuthCacheState {
 	) -> Result<(RemoteFile, Option<tokio::sync::OwnedMutexGuard<()>>), FilenSdkError> {
 		let old_path = self.get_cached_file_path_from_name(&old_uuid.to_string(), Some(&name));
 
+		let source = tokio::fs::metadata(&old_path).await?;
+		let checkpoints = DbUploadCheckpoints {
+			state: self,
+			stable_uuid,
+			source_size: FilenMetaExt::size(&source) as i64,
+			source_modified: FilenMetaExt::modified(&source).timestamp_millis(),
+			saved: AtomicBool::new(false),
+		};
+		let resume = checkpoints.load();
+		let resumed = resume.is_some();
+
 		let mut file_builder = FileBuilderOptionalName::new(parent_uuid);
 		file_builder.name(&name)?;
 		file_builder.mime(mime);
-		let (file, _) = self
-			.io_upload_file(old_path.clone(), file_builder, callback)
-			.await?;
+		let result = self
+			.io_upload_file(
+				old_path.clone(),
+				file_builder,
+				callback,
+				Some((&checkpoints, resume)),
+			)
+			.await;
+		if resumed && result.is_err() && !checkpoints.saved.load(Ordering::Relaxed) {
+			// A resume that got nowhere may be of an upload the server has since discarded, which
+			// would fail the same way on every drain. The next attempt starts over instead.
+			checkpoints.discard();
+		}
+		let (file, _) = result?;
 		let new_uuid_guard = if file.uuid() == old_uuid {
 			None
 		} else {
@@ -358,7 +484,7 @@ impl AuthCacheState {
 			.await?;
 		drop(os_file);
 		let (file, _) = self
-			.io_upload_file(target_path.clone(), builder, None)
+			.io_upload_file(target_path.clone(), builder, None, None)
 			.await?;
 		Ok((file, target_path, uuid_guard))
 	}
@@ -233,6 +233,9 @@ impl FilenMobileCacheState {
 	/// A failed upload leaves the edit marked in the cache, so it survives the extension being
 	/// torn down. Nothing drains those markers on its own — call this when the provider or the app
 	/// starts up, and after regaining connectivity. Returns how many uploads succeeded.
+	///
+	/// An upload that was cut off resumes from the last chunk the server confirmed, as long as the
+	/// cached bytes haven't changed since.
 	pub async fn retry_pending_uploads(&self) -> Result<u32, CacheError> {
 		self.async_execute_authed_owned(async move |auth_state| {
 			auth_state.retry_pending_uploads().await
@@ -633,6 +636,7 @@ impl AuthCacheState {
 		Ok(self
 			.io_upload_updated_file(
 				file.uuid,
+				file.stable_uuid,
 				meta.name.clone(),
 				file.parent.try_into().map_err(|e| {
 					CacheError::conversion(format!("Failed to convert parent UUID: {e}"))
@@ -1042,7 +1046,7 @@ impl AuthCacheState {
 		// is nothing on disk here to leave half-done either.
 		let (remote_file, _) = with_abort(
 			abort.as_ref(),
-			self.io_upload_file(os_path, builder, progress_callback),
+			self.io_upload_file(os_path, builder, progress_callback, None),
 		)
 		.await?;
 
@@ -1414,7 +1418,9 @@ impl AuthCacheState {
 	/// Retries every file still marked as having unuploaded local changes.
 	///
 	/// Best effort and independent per file: one that still fails keeps its marker for the next
-	/// drain rather than aborting the rest. Returns how many reached the server.
+	/// drain rather than aborting the rest. Each upload resumes from the checkpoint an interrupted
+	/// attempt left, see [`AuthCacheState::io_upload_updated_file`]. Returns how many reached the
+	/// server.
 	pub(crate) async fn retry_pending_uploads(&self) -> Result<u32, CacheError> {
 		let pending = sql::select_pending_uploads(&self.conn())?;
 		if pending.is_empty() {
@@ -297,6 +297,58 @@ pub(crate) fn select_pending_uploads(
 	Ok(uuids)
 }
 
+/// A file's saved upload checkpoint, as serialized JSON, and the size and modification millis of
+/// the cached bytes it was taken from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SavedUploadCheckpoint {
+	pub(crate) checkpoint: String,
+	pub(crate) source_size: i64,
+	pub(crate) source_modified: i64,
+}
+
+/// Saves the latest checkpoint of a file's upload, replacing any earlier one.
+///
+/// Dropped by a trigger once the file's pending-upload marker is cleared, so it never outlives
+/// the edit it belongs to.
+pub(crate) fn upsert_upload_checkpoint(
+	conn: &Connection,
+	stable_uuid: StableUuid,
+	saved: &SavedUploadCheckpoint,
+) -> Result<(), rusqlite::Error> {
+	let mut stmt = conn.prepare_cached(UPSERT_UPLOAD_CHECKPOINT)?;
+	stmt.execute((
+		stable_uuid,
+		&saved.checkpoint,
+		saved.source_size,
+		saved.source_modified,
+	))?;
+	Ok(())
+}
+
+pub(crate) fn select_upload_checkpoint(
+	conn: &Connection,
+	stable_uuid: StableUuid,
+) -> Result<Option<SavedUploadCheckpoint>, rusqlite::Error> {
+	let mut stmt = conn.prepare_cached(SELECT_UPLOAD_CHECKPOINT)?;
+	stmt.query_row((stable_uuid,), |row| {
+		Ok(SavedUploadCheckpoint {
+			checkpoint: row.get(0)?,
+			source_size: row.get(1)?,
+			source_modified: row.get(2)?,
+		})
+	})
+	.optional()
+}
+
+pub(crate) fn delete_upload_checkpoint(
+	conn: &Connection,
+	stable_uuid: StableUuid,
+) -> Result<(), rusqlite::Error> {
+	let mut stmt = conn.prepare_cached(DELETE_UPLOAD_CHECKPOINT)?;
+	stmt.execute((stable_uuid,))?;
+	Ok(())
+}
+
 /// Records that a file's bytes are in the local cache directory, as of `marked_at_millis`.
 ///
 /// Keyed on the file's uuid, which is what its cache slot is named after; see
@@ -1280,6 +1332,43 @@ mod pending_upload_tests {
 
 		assert!(!has_descendant_pending_upload(&conn, uuid(1)).unwrap());
 	}
+
+	/// A checkpoint only ever accompanies an outstanding edit: once the marker goes, whether the
+	/// edit landed or was dropped, there is nothing left to resume.
+	#[test]
+	fn clearing_the_marker_drops_the_upload_checkpoint() {
+		let conn = db();
+		add_file(&conn, uuid(1), stable(2), uuid(9), "big.bin");
+		add_file(&conn, uuid(3), stable(4), uuid(9), "other.bin");
+		mark_pending_upload(&conn, stable(2), MARKED_AT).unwrap();
+		mark_pending_upload(&conn, stable(4), MARKED_AT).unwrap();
+		let saved = SavedUploadCheckpoint {
+			checkpoint: "{}".to_string(),
+			source_size: 1024,
+			source_modified: MARKED_AT,
+		};
+		upsert_upload_checkpoint(&conn, stable(2), &saved).unwrap();
+		upsert_upload_checkpoint(&conn, stable(4), &saved).unwrap();
+		let newer = SavedUploadCheckpoint {
+			checkpoint: "{\"newer\":true}".to_string(),
+			..saved.clone()
+		};
+		upsert_upload_checkpoint(&conn, stable(2), &newer).unwrap();
+		assert_eq!(
+			select_upload_checkpoint(&conn, stable(2)).unwrap(),
+			Some(newer),
+			"a later save replaces the earlier one"
+		);
+
+		clear_pending_upload(&conn, stable(2)).unwrap();
+
+		assert_eq!(select_upload_checkpoint(&conn, stable(2)).unwrap(), None);
+		assert_eq!(
+			select_upload_checkpoint(&conn, stable(4)).unwrap(),
+			Some(saved),
+			"another file's edit is still outstanding"
+		);
+	}
 }
 
 #[cfg(test)]
@@ -38,6 +38,12 @@ pub(crate) const SELECT_PENDING_UPLOAD_AT: &str =
 	include_str!("../../sql/select_pending_upload_at.sql");
 pub(crate) const SELECT_DESCENDANT_PENDING_UPLOAD: &str =
 	include_str!("../../sql/select_descendant_pending_upload.sql");
+pub(crate) const UPSERT_UPLOAD_CHECKPOINT: &str =
+	include_str!("../../sql/upsert_upload_checkpoint.sql");
+pub(crate) const SELECT_UPLOAD_CHECKPOINT: &str =
+	include_str!("../../sql/select_upload_checkpoint.sql");
+pub(crate) const DELETE_UPLOAD_CHECKPOINT: &str =
+	include_str!("../../sql/delete_upload_checkpoint.sql");
 pub(crate) const MARK_MATERIALISED: &str = include_str!("../../sql/mark_materialised.sql");
 pub(crate) const CLEAR_MATERIALISED: &str = include_str!("../../sql/clear_materialised.sql");
 pub(crate) const CLEAR_MATERIALISED_NOT_IN_CACHE: &str =
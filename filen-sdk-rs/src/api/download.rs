@@ -1,12 +1,8 @@
 use filen_types::fs::Uuid;
 
 use crate::{
-	ErrorKind,
-	auth::unauth::UnauthClient,
-	consts::{CHUNK_SIZE, random_egest_url},
-	error::Error,
-	fs::file::traits::File,
-	util::MaybeSendSync,
+	ErrorKind, auth::unauth::UnauthClient, consts::CHUNK_SIZE, error::Error,
+	fs::file::traits::File, util::MaybeSendSync,
 };
 
 /// Upper bound on the encrypted body of a single downloaded file chunk, across every supported
@@ -70,15 +66,10 @@ pub(crate) async fn download_file_chunk_by_uuid<F>(
 where
 	F: Fn(u64, Option<u64>) + MaybeSendSync,
 {
-	let endpoint = random_egest_url();
+	let endpoint = client.state().endpoints().random_egest_url();
 	let url = format!("{endpoint}/{region}/{bucket}/{uuid}/{chunk_idx}");
 	client
-		.get_raw_bytes_with_callback(
-			&url,
-			endpoint.into(),
-			Some(MAX_ENCRYPTED_CHUNK_SIZE),
-			callback,
-		)
+		.get_raw_bytes_with_callback(&url, endpoint, Some(MAX_ENCRYPTED_CHUNK_SIZE), callback)
 		.await
 		.map_err(map_chunk_download_error)
 }
@@ -3,7 +3,7 @@ pub use filen_types::api::v3::upload::{ENDPOINT, Response};
 use sha2::{Digest, Sha512};
 use tracing::trace;
 
-use crate::{auth::http::AuthClient, consts::random_ingest_url, error::Error, fs::file::BaseFile};
+use crate::{auth::http::AuthClient, error::Error, fs::file::BaseFile};
 
 pub(crate) mod done;
 pub(crate) mod empty;
@@ -21,7 +21,7 @@ pub(crate) async fn upload_file_chunk(
 		chunk.len()
 	);
 
-	let ingest_url = random_ingest_url();
+	let ingest_url = client.state().endpoints().random_ingest_url();
 
 	let data_hash = Sha512::digest(&chunk);
 	let url = format!(
@@ -35,7 +35,5 @@ pub(crate) async fn upload_file_chunk(
 		hex::display(data_hash.as_slice()),
 	);
 
-	client
-		.post_raw_bytes_auth(chunk, &url, ingest_url.into())
-		.await
+	client.post_raw_bytes_auth(chunk, &url, ingest_url).await
 }
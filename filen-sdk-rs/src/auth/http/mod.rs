@@ -16,11 +16,10 @@ use reqwest::{
 use serde::{Serialize, de::DeserializeOwned};
 use tower::{ServiceBuilder, ServiceExt, limit::GlobalConcurrencyLimitLayer};
 
-use crate::consts::{CHUNK_SIZE, FILE_CHUNK_SIZE_EXTRA_USIZE};
+use crate::consts::{ApiEndpoints, CHUNK_SIZE, FILE_CHUNK_SIZE_EXTRA_USIZE};
 use crate::{
 	Error,
 	auth::{Client, http::auth::AuthLayer, unauth::UnauthClient},
-	consts::gateway_url,
 	util::{MaybeSend, MaybeSendSync},
 };
 #[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
@@ -84,6 +83,9 @@ pub struct ClientConfig {
 	connect_timeout: Option<Duration>,
 	/// Idle/time-to-first-byte read timeout (see [`DEFAULT_READ_TIMEOUT`]). `None` disables it.
 	read_timeout: Option<Duration>,
+	/// The servers requests are sent to. Carried into [`StringifiedClient`](crate::auth::StringifiedClient)
+	/// when they differ from the production defaults.
+	endpoints: ApiEndpoints,
 }
 
 impl ClientConfig {
@@ -135,6 +137,12 @@ impl ClientConfig {
 		self
 	}
 
+	/// Send requests to `endpoints` instead of the production servers, e.g. a staging or local server.
+	pub fn with_endpoints(mut self, endpoints: ApiEndpoints) -> Self {
+		self.endpoints = endpoints;
+		self
+	}
+
 	/// Build the [`reqwest::Client`] backing every request, applying the connect/read timeouts.
 	///
 	/// On wasm the timeouts are ignored — reqwest's fetch-based client exposes no
@@ -170,6 +178,7 @@ impl Default for ClientConfig {
 			log_level: None,
 			connect_timeout: Some(DEFAULT_CONNECT_TIMEOUT),
 			read_timeout: Some(DEFAULT_READ_TIMEOUT),
+			endpoints: ApiEndpoints::default(),
 			file_io_memory_budget: {
 				#[cfg(not(target_os = "ios"))]
 				{
@@ -213,11 +222,23 @@ pub struct JsClientConfig {
 	pub log_level: Option<LogLevel>,
 	#[cfg_attr(all(target_family = "wasm", target_os = "unknown"), serde(default))]
 	pub file_io_memory_budget: Option<u64>,
+	/// Overrides the API gateway URLs. All three URL lists must be set together.
+	#[cfg_attr(all(target_family = "wasm", target_os = "unknown"), serde(default))]
+	pub gateway_urls: Option<Vec<String>>,
+	#[cfg_attr(all(target_family = "wasm", target_os = "unknown"), serde(default))]
+	pub egest_urls: Option<Vec<String>>,
+	#[cfg_attr(all(target_family = "wasm", target_os = "unknown"), serde(default))]
+	pub ingest_urls: Option<Vec<String>>,
+	/// Overrides the socket server URL.
+	#[cfg_attr(all(target_family = "wasm", target_os = "unknown"), serde(default))]
+	pub socket_url: Option<String>,
 }
 
 #[cfg(any(feature = "uniffi", all(target_family = "wasm", target_os = "unknown")))]
-impl From<JsClientConfig> for ClientConfig {
-	fn from(value: JsClientConfig) -> Self {
+impl TryFrom<JsClientConfig> for ClientConfig {
+	type Error = Error;
+
+	fn try_from(value: JsClientConfig) -> Result<Self, Error> {
 		let mut config = ClientConfig::default();
 		if let Some(concurrency) = value.concurrency {
 			// A zero-permit concurrency limiter makes every request await its permit forever; treat
@@ -250,7 +271,15 @@ impl From<JsClientConfig> for ClientConfig {
 				.min(tokio::sync::Semaphore::MAX_PERMITS);
 			config = config.with_memory_budget(budget);
 		}
-		config
+		if let Some(endpoints) = ApiEndpoints::from_overrides(
+			value.gateway_urls,
+			value.egest_urls,
+			value.ingest_urls,
+			value.socket_url,
+		)? {
+			config = config.with_endpoints(endpoints);
+		}
+		Ok(config)
 	}
 }
 
@@ -270,6 +299,7 @@ pub(crate) struct SharedClientState {
 	/// stream's read-ahead at half the total budget, which `available_permits()` cannot give.
 	#[cfg(feature = "http-provider")]
 	file_io_memory_budget: usize,
+	endpoints: Arc<ApiEndpoints>,
 }
 
 impl SharedClientState {
@@ -346,9 +376,23 @@ impl SharedClientState {
 			memory_semaphore: Arc::new(tokio::sync::Semaphore::new(config.file_io_memory_budget)),
 			#[cfg(feature = "http-provider")]
 			file_io_memory_budget: config.file_io_memory_budget,
+			endpoints: Arc::new(config.endpoints),
 		})
 	}
 
+	pub(crate) fn endpoints(&self) -> &ApiEndpoints {
+		&self.endpoints
+	}
+
+	/// Returns a copy of this state that sends requests to `endpoints`,
+	/// sharing everything else (limits, budgets) with this one.
+	pub(crate) fn with_endpoints(&self, endpoints: ApiEndpoints) -> Self {
+		Self {
+			endpoints: Arc::new(endpoints),
+			..self.clone()
+		}
+	}
+
 	pub(crate) fn memory_semaphore(&self) -> &Arc<tokio::sync::Semaphore> {
 		&self.memory_semaphore
 	}
@@ -674,7 +718,7 @@ impl UnauthClient {
 		Res: DeserializeOwned + Debug,
 		Req: Serialize + Debug,
 	{
-		let url = gateway_url(&endpoint);
+		let url = self.state.endpoints.gateway_url(&endpoint);
 
 		let builder = ServiceBuilder::new()
 			.layer(logging::LogLayer::new(endpoint)) // optional logging
@@ -723,7 +767,7 @@ impl UnauthClient {
 		Req: Serialize + Debug + Sync,
 		F: Fn(u64, Option<u64>) + Send + Sync,
 	{
-		let url = gateway_url(&endpoint);
+		let url = self.state.endpoints.gateway_url(&endpoint);
 
 		let builder = ServiceBuilder::new()
 			.layer(logging::LogLayer::new(endpoint)) // optional logging
@@ -817,7 +861,7 @@ impl AuthClient {
 	where
 		Res: DeserializeOwned + Debug,
 	{
-		let url = gateway_url(&endpoint);
+		let url = self.unauthed.state.endpoints.gateway_url(&endpoint);
 
 		let builder = ServiceBuilder::new()
 			.layer(logging::LogLayer::new(endpoint)) // optional logging
@@ -863,7 +907,7 @@ impl AuthClient {
 		Res: DeserializeOwned + Debug,
 		Req: Serialize + Debug,
 	{
-		let url = gateway_url(&endpoint);
+		let url = self.unauthed.state.endpoints.gateway_url(&endpoint);
 
 		// This could be improved, all the boxes should be removable with type_alias_impl_trait
 		// and using references instead of Arcs
@@ -917,7 +961,7 @@ impl AuthClient {
 		Req: Serialize + Debug,
 		F: Fn(u64, Option<u64>) + Send + Sync,
 	{
-		let url = gateway_url(&endpoint);
+		let url = self.unauthed.state.endpoints.gateway_url(&endpoint);
 
 		// This could be improved, all the boxes should be removable with type_alias_impl_trait
 		// and using references instead of Arcs
@@ -1289,6 +1333,10 @@ mod js_client_config_tests {
 			download_bandwidth_kilobytes_per_sec: None,
 			log_level: None,
 			file_io_memory_budget: None,
+			gateway_urls: None,
+			egest_urls: None,
+			ingest_urls: None,
+			socket_url: None,
 		}
 	}
 
@@ -1296,10 +1344,11 @@ mod js_client_config_tests {
 	/// zero-permit limiter that makes every request await its permit forever.
 	#[test]
 	fn zero_concurrency_is_clamped_to_one() {
-		let config = ClientConfig::from(JsClientConfig {
+		let config = ClientConfig::try_from(JsClientConfig {
 			concurrency: Some(0),
 			..base()
-		});
+		})
+		.unwrap();
 		assert_eq!(
 			config.concurrency, 1,
 			"concurrency 0 must be clamped to 1, not left as a zero-permit (hang-forever) limiter"
@@ -1310,14 +1359,37 @@ mod js_client_config_tests {
 	/// which rejects any permit count above MAX_PERMITS (usize::MAX >> 3).
 	#[test]
 	fn huge_memory_budget_is_clamped_to_semaphore_max() {
-		let config = ClientConfig::from(JsClientConfig {
+		let config = ClientConfig::try_from(JsClientConfig {
 			file_io_memory_budget: Some(u64::MAX),
 			..base()
-		});
+		})
+		.unwrap();
 		assert_eq!(
 			config.file_io_memory_budget,
 			tokio::sync::Semaphore::MAX_PERMITS,
 			"an oversized budget must be clamped to Semaphore::MAX_PERMITS to avoid a panic"
 		);
 	}
+
+	/// Endpoint overrides only make sense as a whole, so a partial override is rejected
+	/// rather than silently mixing production and custom servers.
+	#[test]
+	fn endpoint_urls_must_be_set_together() {
+		let config = ClientConfig::try_from(JsClientConfig {
+			gateway_urls: Some(vec!["http://localhost:8080/".to_string()]),
+			egest_urls: Some(vec!["http://localhost:8081".to_string()]),
+			ingest_urls: Some(vec!["http://localhost:8082".to_string()]),
+			..base()
+		})
+		.unwrap();
+		assert_eq!(config.endpoints.gateway_urls(), ["http://localhost:8080"]);
+
+		assert!(
+			ClientConfig::try_from(JsClientConfig {
+				gateway_urls: Some(vec!["http://localhost:8080".to_string()]),
+				..base()
+			})
+			.is_err()
+		);
+	}
 }
@@ -284,7 +284,7 @@ impl UnauthClient {}
 impl UnauthJsClient {
 	#[cfg_attr(feature = "uniffi", uniffi::constructor)]
 	pub fn from_config(client_config: JsClientConfig) -> Result<Self, Error> {
-		let config: ClientConfig = client_config.into();
+		let config: ClientConfig = client_config.try_into()?;
 		Ok(Self::new(UnauthClient::from_config(config)?))
 	}
 
@@ -24,6 +24,7 @@ use typenum::U256;
 use crate::{
 	api,
 	auth::unauth::UnauthClient,
+	consts::ApiEndpoints,
 	crypto::{
 		self,
 		error::ConversionError,
@@ -324,6 +325,21 @@ pub struct StringifiedClient {
 	#[serde(default)]
 	#[cfg_attr(feature = "uniffi", uniffi(default = None))]
 	pub max_io_memory_usage: Option<u32>,
+	/// Overrides the API gateway URLs, if the client doesn't use the production servers.
+	/// All three URL lists are set together.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "uniffi", uniffi(default = None))]
+	pub gateway_urls: Option<Vec<String>>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "uniffi", uniffi(default = None))]
+	pub egest_urls: Option<Vec<String>>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "uniffi", uniffi(default = None))]
+	pub ingest_urls: Option<Vec<String>>,
+	/// Overrides the socket server URL, if the client doesn't use the production servers.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "uniffi", uniffi(default = None))]
+	pub socket_url: Option<String>,
 }
 
 impl From<FilenSDKConfig> for StringifiedClient {
@@ -338,6 +354,10 @@ impl From<FilenSDKConfig> for StringifiedClient {
 			auth_version: value.auth_version as u8,
 			max_parallel_requests: None,
 			max_io_memory_usage: None,
+			gateway_urls: None,
+			egest_urls: None,
+			ingest_urls: None,
+			socket_url: None,
 		}
 	}
 }
@@ -885,6 +905,11 @@ impl TwoFASecret {
 impl Client {
 	pub fn to_stringified(&self) -> StringifiedClient {
 		let auth_info = self.auth_info.read().unwrap_or_else(|e| e.into_inner());
+		let endpoints = self.client().state().endpoints();
+		let url_overrides = |urls: &[Cow<'static, str>]| {
+			(*endpoints != ApiEndpoints::default())
+				.then(|| urls.iter().map(|url| url.to_string()).collect())
+		};
 		StringifiedClient {
 			email: self.email.clone(),
 			user_id: self.user_id,
@@ -900,6 +925,11 @@ impl Client {
 			},
 			max_parallel_requests: None,
 			max_io_memory_usage: None,
+			gateway_urls: url_overrides(endpoints.gateway_urls()),
+			egest_urls: url_overrides(endpoints.egest_urls()),
+			ingest_urls: url_overrides(endpoints.ingest_urls()),
+			socket_url: (*endpoints != ApiEndpoints::default())
+				.then(|| endpoints.socket_url().to_string()),
 		}
 	}
 }
@@ -956,10 +986,194 @@ mod tests {
 			auth_version: 200,
 			max_parallel_requests: None,
 			max_io_memory_usage: None,
+			gateway_urls: None,
+			egest_urls: None,
+			ingest_urls: None,
+			socket_url: None,
 		};
 		assert!(unauth.from_stringified(stringified).is_err());
 	}
 
+	fn test_stringified_client(
+		urls: Option<Vec<String>>,
+		socket_url: Option<String>,
+	) -> StringifiedClient {
+		// tiny key: never used for real crypto, only to satisfy the parser
+		let private_key = RsaPrivateKey::new(&mut old_rng::thread_rng(), 512).unwrap();
+		StringifiedClient {
+			email: "test@example.com".to_string(),
+			user_id: 1,
+			root_uuid: "00000000-0000-0000-0000-000000000000".to_string(),
+			auth_info: "test-master-key".to_string(),
+			private_key: BASE64_STANDARD.encode(private_key.to_pkcs8_der().unwrap().as_bytes()),
+			api_key: "test-api-key".to_string(),
+			auth_version: 2,
+			max_parallel_requests: None,
+			max_io_memory_usage: None,
+			gateway_urls: urls.clone(),
+			egest_urls: urls.clone(),
+			ingest_urls: urls,
+			socket_url,
+		}
+	}
+
+	#[test]
+	fn stringified_client_keeps_endpoints() {
+		let endpoints = ApiEndpoints::single("http://127.0.0.1:8080/").unwrap();
+		let urls = vec!["http://127.0.0.1:8080".to_string()];
+		let default_unauth = UnauthClient::from_config(http::ClientConfig::default()).unwrap();
+
+		// a client on the production servers serializes without overrides
+		let client = default_unauth
+			.from_stringified(test_stringified_client(None, None))
+			.unwrap();
+		assert_eq!(client.to_stringified().gateway_urls, None);
+
+		// overrides in the stringified client take precedence over the unauth client's config
+		let client = default_unauth
+			.from_stringified(test_stringified_client(
+				Some(urls.clone()),
+				Some("ws://127.0.0.1:8080".to_string()),
+			))
+			.unwrap();
+		assert_eq!(*client.client().state().endpoints(), endpoints);
+
+		// a client built with custom endpoints carries them through a round trip
+		let custom_unauth = UnauthClient::from_config(
+			http::ClientConfig::default().with_endpoints(endpoints.clone()),
+		)
+		.unwrap();
+		let stringified = custom_unauth
+			.from_stringified(test_stringified_client(None, None))
+			.unwrap()
+			.to_stringified();
+		assert_eq!(stringified.gateway_urls, Some(urls.clone()));
+		assert_eq!(stringified.ingest_urls, Some(urls));
+		assert_eq!(
+			stringified.socket_url.as_deref(),
+			Some("ws://127.0.0.1:8080")
+		);
+		let restored = default_unauth.from_stringified(stringified).unwrap();
+		assert_eq!(*restored.client().state().endpoints(), endpoints);
+	}
+
+	#[test]
+	fn partial_endpoint_overrides_are_rejected() {
+		assert!(
+			ApiEndpoints::from_overrides(
+				Some(vec!["http://127.0.0.1:8080".to_string()]),
+				None,
+				None,
+				None
+			)
+			.is_err()
+		);
+		assert!(
+			ApiEndpoints::from_overrides(None, None, None, None)
+				.unwrap()
+				.is_none()
+		);
+		assert!(ApiEndpoints::new(Vec::<String>::new(), ["a"], ["b"]).is_err());
+
+		// the socket server can be overridden on its own
+		let endpoints = ApiEndpoints::from_overrides(
+			None,
+			None,
+			None,
+			Some("ws://localhost:9000/".to_string()),
+		)
+		.unwrap()
+		.unwrap();
+		assert_eq!(endpoints.socket_url(), "ws://localhost:9000");
+		assert_eq!(
+			endpoints.gateway_urls(),
+			ApiEndpoints::default().gateway_urls()
+		);
+	}
+
+	#[test]
+	fn plain_socket_urls_are_limited_to_loopback() {
+		for url in [
+			"wss://socket.example.com",
+			"ws://127.0.0.1:8080",
+			"ws://localhost",
+		] {
+			assert!(
+				ApiEndpoints::default().with_socket_url(url).is_ok(),
+				"{url}"
+			);
+		}
+		for url in [
+			"ws://socket.example.com",
+			"ws://127.0.0.1.example.com",
+			"https://a.b",
+		] {
+			assert!(
+				ApiEndpoints::default().with_socket_url(url).is_err(),
+				"{url}"
+			);
+		}
+		// `single` derives the socket URL from the server's
+		assert_eq!(
+			ApiEndpoints::single("https://example.com")
+				.unwrap()
+				.socket_url(),
+			"wss://example.com"
+		);
+		assert_eq!(
+			ApiEndpoints::single("http://localhost:8080")
+				.unwrap()
+				.socket_url(),
+			"ws://localhost:8080"
+		);
+	}
+
+	#[test]
+	fn plain_http_urls_are_limited_to_loopback() {
+		let secure = ["https://gateway.example.com"];
+		assert!(ApiEndpoints::new(secure, secure, secure).is_ok());
+		assert!(
+			ApiEndpoints::new(
+				["http://127.0.0.1:8080"],
+				["http://[::1]"],
+				["http://localhost/"]
+			)
+			.is_ok()
+		);
+		for url in [
+			"http://gateway.example.com",
+			"http://127.0.0.1.example.com",
+			"http://localhost.example.com:8080",
+			"ftp://gateway.example.com",
+			"gateway.example.com",
+			"wss://gateway.example.com",
+		] {
+			assert!(ApiEndpoints::new([url], secure, secure).is_err(), "{url}");
+			assert!(ApiEndpoints::new(secure, [url], secure).is_err(), "{url}");
+			assert!(ApiEndpoints::new(secure, secure, [url]).is_err(), "{url}");
+			assert!(ApiEndpoints::single(url).is_err(), "{url}");
+		}
+		// one insecure URL among secure ones is enough to fail
+		assert!(
+			ApiEndpoints::new(
+				["https://a.example.com", "http://b.example.com"],
+				secure,
+				secure
+			)
+			.is_err()
+		);
+		// serialized configs are checked the same way
+		assert!(
+			ApiEndpoints::from_overrides(
+				Some(vec!["http://gateway.example.com".to_string()]),
+				Some(vec!["https://egest.example.com".to_string()]),
+				Some(vec!["https://ingest.example.com".to_string()]),
+				None
+			)
+			.is_err()
+		);
+	}
+
 	#[test]
 	fn v3_auth_info_is_not_exportable() {
 		let auth_info = AuthInfo::V3(v3::AuthInfo {
@@ -25,7 +25,7 @@ use crate::{
 		http::{AuthClient, ClientConfig, SharedClientState},
 	},
 	consts::{
-		NEW_ACCOUNT_AUTH_VERSION, RSA_KEY_SIZE, V2FILE_ENCRYPTION_VERSION,
+		ApiEndpoints, NEW_ACCOUNT_AUTH_VERSION, RSA_KEY_SIZE, V2FILE_ENCRYPTION_VERSION,
 		V2META_ENCRYPTION_VERSION,
 	},
 	crypto::{
@@ -82,8 +82,17 @@ impl UnauthClient {
 		)
 		.map_err(ConversionError::from)?;
 
+		let mut unauthed = self.clone();
+		if let Some(endpoints) = ApiEndpoints::from_overrides(
+			stringified.gateway_urls,
+			stringified.egest_urls,
+			stringified.ingest_urls,
+			stringified.socket_url,
+		)? {
+			unauthed.state = unauthed.state.with_endpoints(endpoints);
+		}
 		let http_client = Arc::new(AuthClient::from_unauthed(
-			self.clone(),
+			unauthed,
 			Arc::new(RwLock::new(APIKey(Cow::Owned(stringified.api_key)))),
 		));
 
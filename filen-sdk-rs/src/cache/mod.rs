@@ -24,8 +24,9 @@ pub use {
 	error::CacheError,
 	handle::{CacheMessage, ResyncProgress, SyncRootHandle},
 	search::{
-		Search, SearchConfig, SearchHit, SearchItemType, SearchResult, SearchSnapshot,
-		SearchWindowCallback, SearchWindowHandle,
+		Search, SearchConfig, SearchHit, SearchItemType, SearchMatchMode, SearchResult,
+		SearchSnapshot, SearchSortBy, SearchSortDirection, SearchWindowCallback,
+		SearchWindowHandle,
 	},
 	state::{
 		CacheEvent, CacheEventType, DirEvent, FileEvent, GlobalEvent, RootKey, SyncRootCallback,
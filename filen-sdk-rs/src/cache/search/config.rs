@@ -1,10 +1,18 @@
+use chrono::{DateTime, Utc};
+use filen_types::api::v3::dir::color::DirColor;
 use unicode_normalization::UnicodeNormalization;
 
 /// Filter/shape configuration for a cache-backed search (see
 /// [`Client::create_search`](crate::auth::Client::create_search)). Construct via
 /// [`SearchConfig::new`] / [`Default`] plus the chainable `with_*` setters; the struct is
-/// `#[non_exhaustive]` so future filters (size, mime, date, more sort orders, …) slot in without
-/// a breaking change. v1 sorts by name ascending (directories first, ties broken by uuid).
+/// `#[non_exhaustive]` so further filters slot in without a breaking change. Results are ordered
+/// by [`sort_by`](Self::sort_by) in [`sort_direction`](Self::sort_direction) (name ascending by
+/// default), directories always ahead of files, ties broken by name and then uuid — after
+/// relevance, in the ranked [`match_mode`](Self::match_mode)s.
+///
+/// The metadata filters combine with each other and with the name needle. Filters on file
+/// metadata (size, MIME type, extension, modification time) only ever match files, and the
+/// [`color`](Self::color) filter only ever matches directories.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
 pub struct SearchConfig {
@@ -22,6 +30,35 @@ pub struct SearchConfig {
 	/// `false` (default): names match case-insensitively (Unicode simple case folding). `true`:
 	/// byte-exact substring match (after the needle's trim + NFC normalization).
 	pub case_sensitive: bool,
+	/// How [`name`](Self::name) is matched. Defaults to [`SearchMatchMode::Substring`].
+	pub match_mode: SearchMatchMode,
+	/// Only files of at least this many bytes.
+	pub min_size: Option<u64>,
+	/// Only files of at most this many bytes.
+	pub max_size: Option<u64>,
+	/// Only files whose MIME type starts with one of these prefixes (`"image/"`,
+	/// `"application/pdf"`), compared ASCII case-insensitively. Empty (default): any MIME type.
+	pub mime_prefixes: Vec<String>,
+	/// Only files whose name ends in one of these extensions, given with or without the leading
+	/// dot and compared ASCII case-insensitively. Empty (default): any name.
+	pub extensions: Vec<String>,
+	/// Only items created at or after this time. Items without a creation time never match a
+	/// creation time bound.
+	pub created_after: Option<DateTime<Utc>>,
+	/// Only items created before this time.
+	pub created_before: Option<DateTime<Utc>>,
+	/// Only files last modified at or after this time.
+	pub modified_after: Option<DateTime<Utc>>,
+	/// Only files last modified before this time.
+	pub modified_before: Option<DateTime<Utc>>,
+	/// Only favorited items.
+	pub favorites_only: bool,
+	/// Only directories of this color; [`DirColor::Default`] matches the uncolored ones.
+	pub color: Option<DirColor<'static>>,
+	/// What results are ordered by. Defaults to [`SearchSortBy::Name`].
+	pub sort_by: SearchSortBy,
+	/// Defaults to [`SearchSortDirection::Ascending`].
+	pub sort_direction: SearchSortDirection,
 }
 
 impl Default for SearchConfig {
@@ -31,6 +68,19 @@ impl Default for SearchConfig {
 			item_type: SearchItemType::All,
 			recursive: true,
 			case_sensitive: false,
+			match_mode: SearchMatchMode::Substring,
+			min_size: None,
+			max_size: None,
+			mime_prefixes: Vec::new(),
+			extensions: Vec::new(),
+			created_after: None,
+			created_before: None,
+			modified_after: None,
+			modified_before: None,
+			favorites_only: false,
+			color: None,
+			sort_by: SearchSortBy::Name,
+			sort_direction: SearchSortDirection::Ascending,
 		}
 	}
 }
@@ -59,6 +109,77 @@ impl SearchConfig {
 		self.case_sensitive = case_sensitive;
 		self
 	}
+
+	pub fn with_match_mode(mut self, match_mode: SearchMatchMode) -> Self {
+		self.match_mode = match_mode;
+		self
+	}
+
+	pub fn with_min_size(mut self, min_size: u64) -> Self {
+		self.min_size = Some(min_size);
+		self
+	}
+
+	pub fn with_max_size(mut self, max_size: u64) -> Self {
+		self.max_size = Some(max_size);
+		self
+	}
+
+	pub fn with_mime_prefixes(
+		mut self,
+		prefixes: impl IntoIterator<Item = impl Into<String>>,
+	) -> Self {
+		self.mime_prefixes = prefixes.into_iter().map(Into::into).collect();
+		self
+	}
+
+	pub fn with_extensions(
+		mut self,
+		extensions: impl IntoIterator<Item = impl Into<String>>,
+	) -> Self {
+		self.extensions = extensions.into_iter().map(Into::into).collect();
+		self
+	}
+
+	pub fn with_created_after(mut self, created_after: DateTime<Utc>) -> Self {
+		self.created_after = Some(created_after);
+		self
+	}
+
+	pub fn with_created_before(mut self, created_before: DateTime<Utc>) -> Self {
+		self.created_before = Some(created_before);
+		self
+	}
+
+	pub fn with_modified_after(mut self, modified_after: DateTime<Utc>) -> Self {
+		self.modified_after = Some(modified_after);
+		self
+	}
+
+	pub fn with_modified_before(mut self, modified_before: DateTime<Utc>) -> Self {
+		self.modified_before = Some(modified_before);
+		self
+	}
+
+	pub fn with_favorites_only(mut self, favorites_only: bool) -> Self {
+		self.favorites_only = favorites_only;
+		self
+	}
+
+	pub fn with_color(mut self, color: DirColor<'static>) -> Self {
+		self.color = Some(color);
+		self
+	}
+
+	pub fn with_sort_by(mut self, sort_by: SearchSortBy) -> Self {
+		self.sort_by = sort_by;
+		self
+	}
+
+	pub fn with_sort_direction(mut self, sort_direction: SearchSortDirection) -> Self {
+		self.sort_direction = sort_direction;
+		self
+	}
 }
 
 /// Which item kinds a search returns.
@@ -70,16 +191,71 @@ pub enum SearchItemType {
 	Dir,
 }
 
+/// How a search's name needle is matched against item names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchMatchMode {
+	/// The whole needle is a substring of the name.
+	#[default]
+	Substring,
+	/// Every whitespace-separated token of the needle is a substring of the name, in any order:
+	/// "q3 rep" finds "Report Q3 final.pdf". Results are ranked by relevance: whole-word matches
+	/// over word prefixes over infixes, then names starting with a match, holding the tokens in
+	/// needle order, and shorter names first.
+	Tokens,
+	/// Like [`Tokens`](Self::Tokens), but a token that is no substring of the name may also match
+	/// the start of one of its words with a typo: one edit (an insertion, deletion, substitution
+	/// or swap of adjacent characters) for tokens of four to seven characters, two for longer
+	/// ones. Typo matches rank below all others.
+	Fuzzy,
+}
+
+/// What a search's results are ordered by, within directories and within files.
+///
+/// Directories have no size, modification time or MIME type, so sorting by those orders them by
+/// name. Items lacking the sort key come after the ones that have it in either direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchSortBy {
+	/// Case-insensitive for ASCII; other characters order by their bytes.
+	#[default]
+	Name,
+	Size,
+	Modified,
+	Created,
+	Mime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchSortDirection {
+	#[default]
+	Ascending,
+	Descending,
+}
+
 /// `SearchConfig` compiled into the engine's query-binding form: the needle normalized once
 /// (trim + NFC — NEVER pre-case-folded: the SQL matcher's `(?i)` regex does the folding, and
 /// pre-folding would corrupt needles like "İstanbul", whose lowercase inserts a combining dot),
-/// an empty/absent needle collapsed to `None`.
+/// an empty/absent needle collapsed to `None`. The MIME prefix and extension sets are
+/// lowercased and serialized to the JSON arrays the queries read with `json_each`, an empty set
+/// collapsed to `None`, and times become the unix millis the cache stores.
 #[derive(Debug, Clone)]
 pub(super) struct CompiledFilter {
 	pub(super) needle: Option<String>,
 	pub(super) item_type: SearchItemType,
 	pub(super) recursive: bool,
 	pub(super) case_sensitive: bool,
+	pub(super) match_mode: SearchMatchMode,
+	pub(super) min_size: Option<u64>,
+	pub(super) max_size: Option<u64>,
+	pub(super) mime_prefixes: Option<String>,
+	pub(super) extensions: Option<String>,
+	pub(super) created_after: Option<i64>,
+	pub(super) created_before: Option<i64>,
+	pub(super) modified_after: Option<i64>,
+	pub(super) modified_before: Option<i64>,
+	pub(super) favorites_only: bool,
+	pub(super) color: Option<DirColor<'static>>,
+	pub(super) sort_by: SearchSortBy,
+	pub(super) sort_direction: SearchSortDirection,
 }
 
 impl CompiledFilter {
@@ -94,6 +270,32 @@ impl CompiledFilter {
 			item_type: config.item_type,
 			recursive: config.recursive,
 			case_sensitive: config.case_sensitive,
+			match_mode: config.match_mode,
+			min_size: config.min_size,
+			max_size: config.max_size,
+			mime_prefixes: json_set(&config.mime_prefixes, |prefix| prefix),
+			extensions: json_set(&config.extensions, |extension| {
+				extension.strip_prefix('.').unwrap_or(extension)
+			}),
+			created_after: config.created_after.map(|time| time.timestamp_millis()),
+			created_before: config.created_before.map(|time| time.timestamp_millis()),
+			modified_after: config.modified_after.map(|time| time.timestamp_millis()),
+			modified_before: config.modified_before.map(|time| time.timestamp_millis()),
+			favorites_only: config.favorites_only,
+			color: config.color.clone(),
+			sort_by: config.sort_by,
+			sort_direction: config.sort_direction,
 		}
 	}
 }
+
+/// `values` trimmed, passed through `normalize` and ASCII-lowercased, as a JSON array; `None`
+/// when nothing is left, which the queries read as "no filter".
+fn json_set(values: &[String], normalize: impl Fn(&str) -> &str) -> Option<String> {
+	let values = values
+		.iter()
+		.map(|value| normalize(value.trim()).to_ascii_lowercase())
+		.filter(|value| !value.is_empty())
+		.collect::<Vec<_>>();
+	(!values.is_empty()).then(|| serde_json::Value::from(values).to_string())
+}
@@ -1,5 +1,5 @@
-//! The per-search engine task: owns the read-only DB connection (with the `filen_name_matches`
-//! function registered, see [`hydrate`]) and the window subscriptions. All filtering, ordering,
+//! The per-search engine task: owns the read-only DB connection (with the `filen_name_*`
+//! functions registered, see [`hydrate`]) and the window subscriptions. All filtering, ordering,
 //! windowing, and hydration run inside SQLite — the engine keeps no result state beyond each
 //! window's last-delivered snapshot (for equality suppression and the terminal fire). Cache
 //! events reach it as bare refresh PINGS: any committed batch touching the subtree schedules a
@@ -1,9 +1,9 @@
 //! The search engine's DB side: its own READ-ONLY connection to the cache file (WAL lets it read
 //! concurrently with the worker's writer), the `filen_name_matches` scalar function that gives
 //! SQLite full-Unicode case-insensitive matching (its built-in `LIKE`/`lower()` are ASCII-only)
-//! via per-query compiled regexes (see `NameMatchers`), and the window/count queries that do ALL
-//! filtering, ordering, windowing, and hydration inside SQLite — the engine holds no result set
-//! in memory.
+//! via per-query compiled regexes (see `NameMatchers`) and its `filen_name_relevance` sibling
+//! that ranks the token and fuzzy modes, and the window/count queries that do ALL filtering,
+//! ordering, windowing, and hydration inside SQLite — the engine holds no result set in memory.
 //!
 //! NAME NORMALIZATION ASSUMPTION: cached names are assumed to already be NFC-normalized
 //! (matching compares codepoints; there is no canonical-equivalence pass). Pre-v4 drives can
@@ -16,7 +16,11 @@ use std::{borrow::Cow, ops::Range, sync::Arc, time::Duration};
 use chrono::{DateTime, Utc};
 use filen_types::{api::v3::dir::color::DirColor, auth::FileEncryptionVersion, crypto::Blake3Hash};
 use regex::bytes::Regex;
-use rusqlite::{Connection, Row, functions::FunctionFlags, params, types::ValueRef};
+use rusqlite::{
+	Connection, Row, ToSql,
+	functions::{Context, FunctionFlags},
+	types::ValueRef,
+};
 use uuid::Uuid;
 
 // Native-only: the engine opens its own read connection here (wasm routes reads through the
@@ -30,8 +34,9 @@ use crate::{
 };
 
 use super::{
-	config::CompiledFilter,
+	config::{CompiledFilter, SearchMatchMode, SearchSortDirection},
 	result::{SearchHit, SearchResult},
+	tokens::{self, TokenMatchers},
 };
 use crate::cache::sql::columns::{
 	COUNT, DIR_CREATED, DIR_FAVORITE, DIR_NAME, DIR_TIMESTAMP, DIRS_COLOR, FILE_CREATED,
@@ -131,16 +136,18 @@ fn worker_gone() -> rusqlite::Error {
 	)
 }
 
-/// The matchers behind the SQL `filen_name_matches(name, needle, case_insensitive)` function:
-/// one escaped-literal regex per case mode, compiled once per query and cached on the needle
+/// The matchers behind the SQL `filen_name_matches(name, needle, case_insensitive, mode)` and
+/// `filen_name_relevance(name, needle, case_insensitive, mode)` functions: one escaped-literal
+/// regex per case mode for the whole needle, plus the needle's tokens for the ranked modes (see
+/// [`tokens`](super::tokens)), compiled once per query and cached on the needle
 /// argument via SQLite's auxiliary-data slot (`get_or_create_aux` — kept across rows, dropped
 /// when the bound needle changes). An escaped literal compiles to a SIMD-prefiltered substring
 /// scan, so the per-row cost is allocation-free with NO haystack transform — unlike a
 /// `to_lowercase().contains()` fold, which rewrites every non-ASCII name on every row.
 ///
-/// BOTH modes are compiled together because the aux slot is keyed on the NEEDLE argument alone:
-/// the case flag is a separate binding whose changes do not invalidate this slot, so the cached
-/// object must serve either flag value.
+/// BOTH case modes and the tokens are compiled together because the aux slot is keyed on the
+/// NEEDLE argument alone: the case flag and the match mode are separate bindings whose changes
+/// do not invalidate this slot, so the cached object must serve any of their values.
 ///
 /// Case-insensitive matching is Unicode SIMPLE case folding (`(?i)`). Versus folding both sides
 /// through `to_lowercase`: Greek Σ/σ/ς now match position-independently (needle "ΟΔΟΣ" finds
@@ -151,6 +158,7 @@ fn worker_gone() -> rusqlite::Error {
 struct NameMatchers {
 	sensitive: Regex,
 	insensitive: Regex,
+	tokens: TokenMatchers,
 }
 
 impl NameMatchers {
@@ -161,19 +169,70 @@ impl NameMatchers {
 		Ok(Self {
 			sensitive: Regex::new(&literal)?,
 			insensitive: Regex::new(&format!("(?i){literal}"))?,
+			tokens: TokenMatchers::compile(needle)?,
 		})
 	}
 
-	fn matches(&self, name: &[u8], case_insensitive: bool) -> bool {
+	fn regex(&self, case_insensitive: bool) -> &Regex {
 		if case_insensitive {
-			self.insensitive.is_match(name)
+			&self.insensitive
 		} else {
-			self.sensitive.is_match(name)
+			&self.sensitive
+		}
+	}
+
+	fn matches(&self, name: &[u8], case_insensitive: bool) -> bool {
+		self.regex(case_insensitive).is_match(name)
+	}
+
+	fn matches_in(&self, name: &[u8], case_insensitive: bool, mode: SearchMatchMode) -> bool {
+		match mode {
+			SearchMatchMode::Substring => self.matches(name, case_insensitive),
+			SearchMatchMode::Tokens => self.tokens.matches(name, case_insensitive, false),
+			SearchMatchMode::Fuzzy => self.tokens.matches(name, case_insensitive, true),
+		}
+	}
+
+	/// The ranked modes' relevance of `name`; 0 for a name that doesn't match, and for every name
+	/// in substring mode, which isn't ranked.
+	fn relevance(&self, name: &[u8], case_insensitive: bool, mode: SearchMatchMode) -> i64 {
+		if mode == SearchMatchMode::Substring {
+			return 0;
+		}
+		self.tokens
+			.find(name, case_insensitive, mode == SearchMatchMode::Fuzzy)
+			.map_or(0, |matches| tokens::relevance(name, &matches))
+	}
+
+	/// The byte ranges of `name` the needle matched: its first occurrence in substring mode, or
+	/// each token's best match in the ranked modes, merged where they overlap.
+	fn highlights(
+		&self,
+		name: &str,
+		case_insensitive: bool,
+		mode: SearchMatchMode,
+	) -> Vec<Range<usize>> {
+		match mode {
+			SearchMatchMode::Substring => self
+				.regex(case_insensitive)
+				.find(name.as_bytes())
+				.map(|found| found.range())
+				.into_iter()
+				.collect(),
+			SearchMatchMode::Tokens | SearchMatchMode::Fuzzy => self
+				.tokens
+				.find(
+					name.as_bytes(),
+					case_insensitive,
+					mode == SearchMatchMode::Fuzzy,
+				)
+				.map(|matches| tokens::merge_ranges(matches.into_iter().map(|found| found.range)))
+				.unwrap_or_default(),
 		}
 	}
 }
 
-/// Open the engine's NATIVE read-only connection and register `filen_name_matches` on it (WAL
+/// Open the engine's NATIVE read-only connection and register the matcher functions on it (WAL
 /// lets it read concurrently with the worker's writer). `NO_MUTEX` is safe because the
 /// connection never leaves the engine task; the busy timeout rides out WAL checkpoints by the
 /// worker. On wasm the engine never opens this — the wasm VFS supports neither WAL nor a second
@@ -192,43 +251,74 @@ pub(super) fn open_read_connection(path: &Path) -> rusqlite::Result<Connection>
 	Ok(conn)
 }
 
-/// Register the `filen_name_matches` scalar function on `conn` (re-registering replaces, so this
-/// is idempotent). Needed on every connection search queries run against: the engine's own read
-/// connection on native, and the WORKER's single connection (which serves [`ReadConn`]
-/// queries) on wasm.
+/// Register the `filen_name_matches` and `filen_name_relevance` scalar functions on `conn`
+/// (re-registering replaces, so this is idempotent). Needed on every connection search queries
+/// run against: the engine's own read connection on native, and the WORKER's single connection
+/// (which serves [`ReadConn`] queries) on wasm.
 pub(in crate::cache) fn register_name_matches(conn: &Connection) -> rusqlite::Result<()> {
-	conn.create_scalar_function(
-		"filen_name_matches",
-		3,
-		FunctionFlags::SQLITE_UTF8
-			| FunctionFlags::SQLITE_DETERMINISTIC
-			| FunctionFlags::SQLITE_INNOCUOUS,
-		|ctx| {
-			let name = match ctx.get_raw(0) {
-				// An items row whose files/dirs companion row is missing (mid-upsert or
-				// corrupt): never a match; the explaining event converges it later.
-				ValueRef::Null => return Ok(false),
-				ValueRef::Text(bytes) => bytes,
-				other => {
-					return Err(rusqlite::Error::InvalidFunctionParameterType(
-						0,
-						other.data_type(),
-					));
-				}
-			};
-			if matches!(ctx.get_raw(1), ValueRef::Text(needle) if needle.is_empty()) {
-				return Ok(true);
-			}
-			let case_insensitive: bool = ctx.get(2)?;
-			let matchers: Arc<NameMatchers> = ctx.get_or_create_aux(
-				1,
-				|needle| -> Result<_, Box<dyn std::error::Error + Send + Sync + 'static>> {
-					Ok(NameMatchers::compile(needle.as_str()?)?)
-				},
-			)?;
-			Ok(matchers.matches(name, case_insensitive))
+	let flags = FunctionFlags::SQLITE_UTF8
+		| FunctionFlags::SQLITE_DETERMINISTIC
+		| FunctionFlags::SQLITE_INNOCUOUS;
+	conn.create_scalar_function("filen_name_matches", 4, flags, |ctx| {
+		// An items row whose files/dirs companion row is missing (mid-upsert or corrupt): never
+		// a match; the explaining event converges it later.
+		let Some(name) = name_arg(ctx)? else {
+			return Ok(false);
+		};
+		let Some(matchers) = matchers_arg(ctx)? else {
+			return Ok(true);
+		};
+		Ok(matchers.matches_in(name, ctx.get(2)?, mode_arg(ctx)?))
+	})?;
+	conn.create_scalar_function("filen_name_relevance", 4, flags, |ctx| {
+		// checked first: substring mode is unranked, and its queries shouldn't pay for ranking
+		let mode = mode_arg(ctx)?;
+		if mode == SearchMatchMode::Substring {
+			return Ok(0i64);
+		}
+		let (Some(name), Some(matchers)) = (name_arg(ctx)?, matchers_arg(ctx)?) else {
+			return Ok(0);
+		};
+		Ok(matchers.relevance(name, ctx.get(2)?, mode))
+	})
+}
+
+/// The name argument of a matcher function; `None` for a NULL name.
+fn name_arg<'a>(ctx: &'a Context<'_>) -> rusqlite::Result<Option<&'a [u8]>> {
+	match ctx.get_raw(0) {
+		ValueRef::Null => Ok(None),
+		ValueRef::Text(bytes) => Ok(Some(bytes)),
+		other => Err(rusqlite::Error::InvalidFunctionParameterType(
+			0,
+			other.data_type(),
+		)),
+	}
+}
+
+/// The matchers for the needle argument of a matcher function, cached across rows; `None` for
+/// the empty needle, which matches everything.
+fn matchers_arg(ctx: &Context<'_>) -> rusqlite::Result<Option<Arc<NameMatchers>>> {
+	if matches!(ctx.get_raw(1), ValueRef::Text(needle) if needle.is_empty()) {
+		return Ok(None);
+	}
+	ctx.get_or_create_aux(
+		1,
+		|needle| -> Result<_, Box<dyn std::error::Error + Send + Sync + 'static>> {
+			Ok(NameMatchers::compile(needle.as_str()?)?)
 		},
 	)
+	.map(Some)
+}
+
+fn mode_arg(ctx: &Context<'_>) -> rusqlite::Result<SearchMatchMode> {
+	match ctx.get::<i64>(3)? {
+		0 => Ok(SearchMatchMode::Substring),
+		1 => Ok(SearchMatchMode::Tokens),
+		2 => Ok(SearchMatchMode::Fuzzy),
+		other => Err(rusqlite::Error::UserFunctionError(
+			format!("unknown match mode {other}").into(),
+		)),
+	}
 }
 
 fn type_filter_param(filter: &CompiledFilter) -> i64 {
@@ -240,6 +330,42 @@ fn type_filter_param(filter: &CompiledFilter) -> i64 {
 	}
 }
 
+fn match_mode_param(filter: &CompiledFilter) -> i64 {
+	match filter.match_mode {
+		SearchMatchMode::Substring => 0,
+		SearchMatchMode::Tokens => 1,
+		SearchMatchMode::Fuzzy => 2,
+	}
+}
+
+fn sort_by_param(filter: &CompiledFilter) -> i64 {
+	use super::config::SearchSortBy;
+	match filter.sort_by {
+		SearchSortBy::Name => 0,
+		SearchSortBy::Size => 1,
+		SearchSortBy::Modified => 2,
+		SearchSortBy::Created => 3,
+		SearchSortBy::Mime => 4,
+	}
+}
+
+/// The metadata filter parameters, in the order every search statement numbers them right after
+/// its scope, type, needle (and window) parameters.
+fn metadata_params(filter: &CompiledFilter) -> [&dyn ToSql; 10] {
+	[
+		&filter.min_size,
+		&filter.max_size,
+		&filter.mime_prefixes,
+		&filter.extensions,
+		&filter.created_after,
+		&filter.created_before,
+		&filter.modified_after,
+		&filter.modified_before,
+		&filter.favorites_only,
+		&filter.color,
+	]
+}
+
 /// One window: scope + filter + order + LIMIT/OFFSET + full hydration, all in one statement.
 /// With a needle this scans the scope (substring matches cannot use an index); without one it
 /// is bounded by the scope and the window.
@@ -263,37 +389,50 @@ pub(super) fn window_and_count(
 	let needle = filter.needle.as_deref().unwrap_or("");
 	let type_filter = type_filter_param(filter);
 	let case_insensitive = !filter.case_sensitive;
+	let sort_by = sort_by_param(filter);
+	let descending = filter.sort_direction == SearchSortDirection::Descending;
+	let match_mode = match_mode_param(filter);
+	// the same matchers the SQL functions used, for the page's highlights
+	let matchers = filter
+		.needle
+		.as_deref()
+		.map(NameMatchers::compile)
+		.transpose()
+		.map_err(|e| rusqlite::Error::UserFunctionError(e.into()))?;
+	let (sql, anchor) = match scope {
+		Scope::Account(root) => (SEARCH_WINDOW_ACCOUNT, root),
+		Scope::Subtree(anchor) => (SEARCH_WINDOW_SUBTREE, anchor),
+		Scope::Children(parent) => (SEARCH_WINDOW_CHILDREN, parent),
+	};
+	let params = [
+		&anchor as &dyn ToSql,
+		&type_filter,
+		&needle,
+		&case_insensitive,
+		&limit,
+		&offset,
+	]
+	.into_iter()
+	.chain(metadata_params(filter))
+	.chain([&sort_by as &dyn ToSql, &descending, &match_mode])
+	.collect::<Vec<_>>();
 	let map_row = |row: &Row<'_>| {
+		let result = row_to_result(row)?;
+		let highlights = matchers
+			.as_ref()
+			.map(|matchers| matchers.highlights(result.name(), case_insensitive, filter.match_mode))
+			.unwrap_or_default();
 		let hit = SearchHit {
-			result: row_to_result(row)?,
+			result,
 			parent_path: row.get::<_, String>(SEARCH_PARENT_PATH)?.into(),
+			highlights,
 		};
 		Ok((hit, row.get::<_, i64>(SEARCH_TOTAL)?))
 	};
-	let rows: rusqlite::Result<Vec<(SearchHit, i64)>> = match scope {
-		Scope::Account(root) => conn
-			.prepare_cached(SEARCH_WINDOW_ACCOUNT)?
-			.query_map(
-				params![root, type_filter, needle, case_insensitive, limit, offset],
-				map_row,
-			)?
-			.collect(),
-		Scope::Subtree(anchor) => conn
-			.prepare_cached(SEARCH_WINDOW_SUBTREE)?
-			.query_map(
-				params![anchor, type_filter, needle, case_insensitive, limit, offset],
-				map_row,
-			)?
-			.collect(),
-		Scope::Children(parent) => conn
-			.prepare_cached(SEARCH_WINDOW_CHILDREN)?
-			.query_map(
-				params![parent, type_filter, needle, case_insensitive, limit, offset],
-				map_row,
-			)?
-			.collect(),
-	};
-	let rows = rows?;
+	let rows = conn
+		.prepare_cached(sql)?
+		.query_map(params.as_slice(), map_row)?
+		.collect::<rusqlite::Result<Vec<(SearchHit, i64)>>>()?;
 	let Some(&(_, total)) = rows.first() else {
 		return Ok((Vec::new(), count_results(conn, scope, filter)?));
 	};
@@ -321,23 +460,25 @@ pub(super) fn count_results(
 	let needle = filter.needle.as_deref().unwrap_or("");
 	let type_filter = type_filter_param(filter);
 	let case_insensitive = !filter.case_sensitive;
-	let count: i64 = match scope {
+	let match_mode = match_mode_param(filter);
+	let (sql, anchor) = match scope {
 		// The count SQL is unchanged (no path computation, no anchor param), so the account-root
 		// uuid is unused here.
-		Scope::Account(_) => conn
-			.prepare_cached(SEARCH_COUNT_ACCOUNT)?
-			.query_row(params![type_filter, needle, case_insensitive], |row| {
-				row.get(COUNT)
-			})?,
-		Scope::Subtree(anchor) => conn.prepare_cached(SEARCH_COUNT_SUBTREE)?.query_row(
-			params![anchor, type_filter, needle, case_insensitive],
-			|row| row.get(COUNT),
-		)?,
-		Scope::Children(parent) => conn.prepare_cached(SEARCH_COUNT_CHILDREN)?.query_row(
-			params![parent, type_filter, needle, case_insensitive],
-			|row| row.get(COUNT),
-		)?,
+		Scope::Account(_) => (SEARCH_COUNT_ACCOUNT, None),
+		Scope::Subtree(anchor) => (SEARCH_COUNT_SUBTREE, Some(anchor)),
+		Scope::Children(parent) => (SEARCH_COUNT_CHILDREN, Some(parent)),
 	};
+	let params = anchor
+		.as_ref()
+		.map(|anchor| anchor as &dyn ToSql)
+		.into_iter()
+		.chain([&type_filter as &dyn ToSql, &needle, &case_insensitive])
+		.chain(metadata_params(filter))
+		.chain([&match_mode as &dyn ToSql])
+		.collect::<Vec<_>>();
+	let count: i64 = conn
+		.prepare_cached(sql)?
+		.query_row(params.as_slice(), |row| row.get(COUNT))?;
 	Ok(count.max(0) as usize)
 }
 
@@ -452,7 +593,7 @@ mod tests {
 
 	use crate::cache::CacheState;
 
-	use super::super::config::{SearchConfig, SearchItemType};
+	use super::super::config::{SearchConfig, SearchItemType, SearchSortBy};
 	use super::*;
 
 	#[test]
@@ -714,9 +855,241 @@ mod tests {
 		assert_eq!(result_names(&results), vec!["sub"]);
 	}
 
+	/// account_root → { docs(dir), report.PDF, photo.png, clip.Mov }, with sizes, MIME types and
+	/// times that differ per file. The files' creation times order differently from their
+	/// modification times, and the report has none.
+	struct MetadataFixture {
+		path: std::path::PathBuf,
+		root: Uuid,
+		_state: CacheState,
+	}
+
+	fn metadata_fixture() -> MetadataFixture {
+		let path = temp_db_path();
+		let root = Uuid::new_v4();
+		let mut state = CacheState::new_on_path(&path, root);
+		let [report, photo, clip] = [
+			("report.PDF", 10, "application/pdf", 100, None, false),
+			("photo.png", 5_000, "image/png", 300, Some(200), true),
+			("clip.Mov", 90_000, "video/quicktime", 200, Some(100), false),
+		]
+		.map(|(name, size, mime, modified, created, favorited)| {
+			let mut file = test_file(Uuid::new_v4(), root, name);
+			file.size = size;
+			file.mime = Cow::Borrowed(mime);
+			file.last_modified = ms(modified);
+			file.created = created.map(ms);
+			file.favorited = favorited;
+			file
+		});
+		state
+			.upsert_dirs(std::iter::once(&test_dir(Uuid::new_v4(), root, "docs")))
+			.unwrap();
+		state
+			.upsert_files([&report, &photo, &clip].into_iter())
+			.unwrap();
+		MetadataFixture {
+			path,
+			root,
+			_state: state,
+		}
+	}
+
+	#[test]
+	fn metadata_filters_apply_in_sql() {
+		let fixture = metadata_fixture();
+		let conn = open_read_connection(&fixture.path).unwrap();
+		let scope = Scope::Account(fixture.root);
+		let names = |config: SearchConfig| {
+			let filter = filter(&config);
+			let results = window_results(&conn, scope, &filter, &(0..10)).unwrap();
+			assert_eq!(
+				count_results(&conn, scope, &filter).unwrap(),
+				results.len(),
+				"the count agrees with the window ({config:?})"
+			);
+			result_names(&results)
+		};
+
+		assert_eq!(
+			names(SearchConfig::new().with_min_size(11).with_max_size(5_000)),
+			vec!["photo.png"],
+			"size bounds are inclusive and drop dirs"
+		);
+		assert_eq!(
+			names(SearchConfig::new().with_mime_prefixes(["IMAGE/", "video/"])),
+			vec!["clip.Mov", "photo.png"]
+		);
+		assert_eq!(
+			names(SearchConfig::new().with_extensions([".pdf", "MOV", "v"])),
+			vec!["clip.Mov", "report.PDF"],
+			"extensions match whole, case-insensitively, with or without the dot"
+		);
+		assert_eq!(
+			names(SearchConfig::new().with_created_before(ms(200))),
+			vec!["clip.Mov"],
+			"the upper bound is exclusive; no creation time never matches"
+		);
+		assert_eq!(
+			names(
+				SearchConfig::new()
+					.with_modified_after(ms(200))
+					.with_modified_before(ms(300))
+			),
+			vec!["clip.Mov"]
+		);
+		assert_eq!(
+			names(SearchConfig::new().with_favorites_only(true)),
+			vec!["docs", "photo.png"]
+		);
+		assert_eq!(
+			names(SearchConfig::new().with_color(DirColor::Custom(Cow::Borrowed("#123456")))),
+			vec!["docs"]
+		);
+		assert!(
+			names(SearchConfig::new().with_color(DirColor::Default)).is_empty(),
+			"files never match a color, even the default one"
+		);
+	}
+
+	#[test]
+	fn sort_orders_apply_in_both_directions_in_every_scope() {
+		let fixture = metadata_fixture();
+		let conn = open_read_connection(&fixture.path).unwrap();
+		let cases = [
+			(SearchSortBy::Name, ["clip.Mov", "photo.png", "report.PDF"]),
+			(SearchSortBy::Size, ["report.PDF", "photo.png", "clip.Mov"]),
+			(
+				SearchSortBy::Modified,
+				["report.PDF", "clip.Mov", "photo.png"],
+			),
+			(SearchSortBy::Mime, ["report.PDF", "photo.png", "clip.Mov"]),
+		];
+		let scopes = [
+			Scope::Account(fixture.root),
+			Scope::Subtree(fixture.root),
+			Scope::Children(fixture.root),
+		];
+		for scope in scopes {
+			for (sort_by, ascending) in cases {
+				let config = SearchConfig::new().with_sort_by(sort_by);
+				let results = window_results(&conn, scope, &filter(&config), &(0..10)).unwrap();
+				assert_eq!(
+					result_names(&results),
+					["docs"].into_iter().chain(ascending).collect::<Vec<_>>(),
+					"{sort_by:?} ascending ({scope:?})"
+				);
+
+				let config = config.with_sort_direction(SearchSortDirection::Descending);
+				let results = window_results(&conn, scope, &filter(&config), &(0..10)).unwrap();
+				assert_eq!(
+					result_names(&results),
+					["docs"]
+						.into_iter()
+						.chain(ascending.into_iter().rev())
+						.collect::<Vec<_>>(),
+					"{sort_by:?} descending, dirs still first ({scope:?})"
+				);
+			}
+
+			// the report has no creation time, so it stays last either way
+			for (direction, expected) in [
+				(SearchSortDirection::Ascending, ["clip.Mov", "photo.png"]),
+				(SearchSortDirection::Descending, ["photo.png", "clip.Mov"]),
+			] {
+				let config = SearchConfig::new()
+					.with_item_type(SearchItemType::File)
+					.with_sort_by(SearchSortBy::Created)
+					.with_sort_direction(direction);
+				let results = window_results(&conn, scope, &filter(&config), &(0..10)).unwrap();
+				assert_eq!(
+					result_names(&results),
+					expected
+						.into_iter()
+						.chain(["report.PDF"])
+						.collect::<Vec<_>>(),
+					"created {direction:?} ({scope:?})"
+				);
+			}
+		}
+	}
+
 	/// `window_and_count` serves the window AND the pre-LIMIT total from one scan — and the
 	/// total must survive the pages that return no rows (offset beyond the end; an empty
 	/// requested range), where it falls back to the dedicated count.
+	#[test]
+	fn ranked_modes_order_by_relevance_and_report_highlights() {
+		let path = temp_db_path();
+		let root = Uuid::new_v4();
+		let mut state = CacheState::new_on_path(&path, root);
+		let files = [
+			"misreported.txt",
+			"reports 2024.txt",
+			"old report.txt",
+			"report.txt",
+			"Report Q3 final.pdf",
+		]
+		.map(|name| test_file(Uuid::new_v4(), root, name));
+		state.upsert_files(files.iter()).unwrap();
+		let conn = open_read_connection(&path).unwrap();
+		let search = |needle: &str, mode: SearchMatchMode| {
+			let filter = filter(&SearchConfig::new().with_name(needle).with_match_mode(mode));
+			let (results, total) =
+				window_and_count(&conn, Scope::Account(root), &filter, &(0..10)).unwrap();
+			assert_eq!(
+				count_results(&conn, Scope::Account(root), &filter).unwrap(),
+				total
+			);
+			results
+		};
+
+		let substring = search("report", SearchMatchMode::Substring);
+		assert_eq!(
+			result_names(&substring),
+			vec![
+				"misreported.txt",
+				"old report.txt",
+				"Report Q3 final.pdf",
+				"report.txt",
+				"reports 2024.txt",
+			],
+			"substring mode keeps the configured name order"
+		);
+		assert_eq!(substring[0].highlights(), &[3..9]);
+
+		let tokens = search("report", SearchMatchMode::Tokens);
+		assert_eq!(
+			result_names(&tokens),
+			vec![
+				"report.txt",
+				"Report Q3 final.pdf",
+				"old report.txt",
+				"reports 2024.txt",
+				"misreported.txt",
+			],
+			"whole words, then word prefixes, then infixes; names starting with a match and \
+			 shorter names first among equals"
+		);
+
+		let reordered = search("q3 rep", SearchMatchMode::Tokens);
+		assert_eq!(result_names(&reordered), vec!["Report Q3 final.pdf"]);
+		assert_eq!(reordered[0].highlights(), &[0..3, 7..9]);
+
+		assert!(search("reprot", SearchMatchMode::Tokens).is_empty());
+		let fuzzy = search("reprot", SearchMatchMode::Fuzzy);
+		assert_eq!(
+			result_names(&fuzzy),
+			vec![
+				"report.txt",
+				"reports 2024.txt",
+				"Report Q3 final.pdf",
+				"old report.txt",
+			],
+			"a transposition is one edit; an infix is no word start"
+		);
+		assert_eq!(fuzzy[3].highlights(), &[4..10]);
+	}
+
 	#[test]
 	fn window_and_count_reports_the_total_even_for_empty_pages() {
 		let fixture = fixture();
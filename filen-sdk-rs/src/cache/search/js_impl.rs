@@ -11,6 +11,7 @@
 #[cfg(feature = "uniffi")]
 use std::sync::Arc;
 
+use chrono::{DateTime, Utc};
 use filen_macros::js_type;
 #[cfg(all(target_family = "wasm", target_os = "unknown"))]
 use filen_types::fs::UuidStr;
@@ -19,14 +20,14 @@ use uuid::Uuid;
 use wasm_bindgen::JsValue;
 
 use super::{
-	Search, SearchConfig, SearchHit, SearchItemType, SearchResult, SearchSnapshot,
-	SearchWindowHandle,
+	Search, SearchConfig, SearchHit, SearchItemType, SearchMatchMode, SearchResult, SearchSnapshot,
+	SearchSortBy, SearchSortDirection, SearchWindowHandle,
 };
 use crate::{
 	Error, ErrorKind,
 	auth::JsClient,
 	io::{RemoteDirectory, RemoteFile},
-	js::{Dir, File},
+	js::{Dir, DirColor, File},
 	runtime::do_on_commander,
 };
 
@@ -57,6 +58,84 @@ impl From<CacheSearchItemType> for SearchItemType {
 	}
 }
 
+/// FFI mirror of [`SearchMatchMode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+#[cfg_attr(
+	all(target_family = "wasm", target_os = "unknown"),
+	derive(tsify::Tsify),
+	tsify(from_wasm_abi, into_wasm_abi)
+)]
+pub enum CacheSearchMatchMode {
+	Substring,
+	Tokens,
+	Fuzzy,
+}
+
+impl From<CacheSearchMatchMode> for SearchMatchMode {
+	fn from(mode: CacheSearchMatchMode) -> Self {
+		match mode {
+			CacheSearchMatchMode::Substring => Self::Substring,
+			CacheSearchMatchMode::Tokens => Self::Tokens,
+			CacheSearchMatchMode::Fuzzy => Self::Fuzzy,
+		}
+	}
+}
+
+/// FFI mirror of [`SearchSortBy`], with the same hand-rolled derives as
+/// [`CacheSearchItemType`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+#[cfg_attr(
+	all(target_family = "wasm", target_os = "unknown"),
+	derive(tsify::Tsify),
+	tsify(from_wasm_abi, into_wasm_abi)
+)]
+pub enum CacheSearchSortBy {
+	Name,
+	Size,
+	Modified,
+	Created,
+	Mime,
+}
+
+impl From<CacheSearchSortBy> for SearchSortBy {
+	fn from(sort_by: CacheSearchSortBy) -> Self {
+		match sort_by {
+			CacheSearchSortBy::Name => Self::Name,
+			CacheSearchSortBy::Size => Self::Size,
+			CacheSearchSortBy::Modified => Self::Modified,
+			CacheSearchSortBy::Created => Self::Created,
+			CacheSearchSortBy::Mime => Self::Mime,
+		}
+	}
+}
+
+/// FFI mirror of [`SearchSortDirection`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+#[cfg_attr(
+	all(target_family = "wasm", target_os = "unknown"),
+	derive(tsify::Tsify),
+	tsify(from_wasm_abi, into_wasm_abi)
+)]
+pub enum CacheSearchSortDirection {
+	Ascending,
+	Descending,
+}
+
+impl From<CacheSearchSortDirection> for SearchSortDirection {
+	fn from(direction: CacheSearchSortDirection) -> Self {
+		match direction {
+			CacheSearchSortDirection::Ascending => Self::Ascending,
+			CacheSearchSortDirection::Descending => Self::Descending,
+		}
+	}
+}
+
 /// FFI mirror of [`SearchConfig`].
 #[js_type(import)]
 pub struct CacheSearchConfig {
@@ -74,6 +153,82 @@ pub struct CacheSearchConfig {
 	pub recursive: bool,
 	#[cfg_attr(feature = "uniffi", uniffi(default = false))]
 	pub case_sensitive: bool,
+	/// `None` means [`CacheSearchMatchMode::Substring`]. The token and fuzzy modes rank results
+	/// by relevance.
+	#[cfg_attr(all(target_family = "wasm", target_os = "unknown"), serde(default))]
+	#[cfg_attr(feature = "uniffi", uniffi(default = None))]
+	pub match_mode: Option<CacheSearchMatchMode>,
+	/// Only files of at least this many bytes.
+	#[cfg_attr(
+		all(target_family = "wasm", target_os = "unknown"),
+		tsify(type = "bigint"),
+		serde(default)
+	)]
+	#[cfg_attr(feature = "uniffi", uniffi(default = None))]
+	pub min_size: Option<u64>,
+	/// Only files of at most this many bytes.
+	#[cfg_attr(
+		all(target_family = "wasm", target_os = "unknown"),
+		tsify(type = "bigint"),
+		serde(default)
+	)]
+	#[cfg_attr(feature = "uniffi", uniffi(default = None))]
+	pub max_size: Option<u64>,
+	/// Only files whose MIME type starts with one of these prefixes, e.g. `"image/"`. `None` or
+	/// empty matches any MIME type.
+	#[cfg_attr(feature = "uniffi", uniffi(default = None))]
+	pub mime_prefixes: Option<Vec<String>>,
+	/// Only files with one of these extensions, with or without the leading dot. `None` or empty
+	/// matches any name.
+	#[cfg_attr(feature = "uniffi", uniffi(default = None))]
+	pub extensions: Option<Vec<String>>,
+	/// Only items created at or after this time.
+	#[cfg_attr(
+		all(target_family = "wasm", target_os = "unknown"),
+		tsify(type = "bigint"),
+		serde(with = "filen_types::serde::time::optional", default)
+	)]
+	#[cfg_attr(feature = "uniffi", uniffi(default = None))]
+	pub created_after: Option<DateTime<Utc>>,
+	/// Only items created before this time.
+	#[cfg_attr(
+		all(target_family = "wasm", target_os = "unknown"),
+		tsify(type = "bigint"),
+		serde(with = "filen_types::serde::time::optional", default)
+	)]
+	#[cfg_attr(feature = "uniffi", uniffi(default = None))]
+	pub created_before: Option<DateTime<Utc>>,
+	/// Only files last modified at or after this time.
+	#[cfg_attr(
+		all(target_family = "wasm", target_os = "unknown"),
+		tsify(type = "bigint"),
+		serde(with = "filen_types::serde::time::optional", default)
+	)]
+	#[cfg_attr(feature = "uniffi", uniffi(default = None))]
+	pub modified_after: Option<DateTime<Utc>>,
+	/// Only files last modified before this time.
+	#[cfg_attr(
+		all(target_family = "wasm", target_os = "unknown"),
+		tsify(type = "bigint"),
+		serde(with = "filen_types::serde::time::optional", default)
+	)]
+	#[cfg_attr(feature = "uniffi", uniffi(default = None))]
+	pub modified_before: Option<DateTime<Utc>>,
+	#[cfg_attr(all(target_family = "wasm", target_os = "unknown"), serde(default))]
+	#[cfg_attr(feature = "uniffi", uniffi(default = false))]
+	pub favorites_only: bool,
+	/// Only directories of this color; `Default` matches the uncolored ones.
+	#[cfg_attr(all(target_family = "wasm", target_os = "unknown"), serde(default))]
+	#[cfg_attr(feature = "uniffi", uniffi(default = None))]
+	pub color: Option<DirColor>,
+	/// `None` means [`CacheSearchSortBy::Name`].
+	#[cfg_attr(all(target_family = "wasm", target_os = "unknown"), serde(default))]
+	#[cfg_attr(feature = "uniffi", uniffi(default = None))]
+	pub sort_by: Option<CacheSearchSortBy>,
+	/// `None` means [`CacheSearchSortDirection::Ascending`].
+	#[cfg_attr(all(target_family = "wasm", target_os = "unknown"), serde(default))]
+	#[cfg_attr(feature = "uniffi", uniffi(default = None))]
+	pub sort_direction: Option<CacheSearchSortDirection>,
 }
 
 impl From<CacheSearchConfig> for SearchConfig {
@@ -81,8 +236,21 @@ impl From<CacheSearchConfig> for SearchConfig {
 		let mut out = Self::new()
 			.with_item_type(config.item_type.map(Into::into).unwrap_or_default())
 			.with_recursive(config.recursive)
-			.with_case_sensitive(config.case_sensitive);
+			.with_case_sensitive(config.case_sensitive)
+			.with_match_mode(config.match_mode.map(Into::into).unwrap_or_default())
+			.with_mime_prefixes(config.mime_prefixes.unwrap_or_default())
+			.with_extensions(config.extensions.unwrap_or_default())
+			.with_favorites_only(config.favorites_only)
+			.with_sort_by(config.sort_by.map(Into::into).unwrap_or_default())
+			.with_sort_direction(config.sort_direction.map(Into::into).unwrap_or_default());
 		out.name = config.name;
+		out.min_size = config.min_size;
+		out.max_size = config.max_size;
+		out.created_after = config.created_after;
+		out.created_before = config.created_before;
+		out.modified_after = config.modified_after;
+		out.modified_before = config.modified_before;
+		out.color = config.color.map(Into::into);
 		out
 	}
 }
@@ -119,23 +287,48 @@ pub struct CacheSearchHit {
 	pub parent_path: String,
 	/// The matched item.
 	pub result: CacheSearchResult,
+	/// The parts of the item's name the needle matched, sorted and non-overlapping.
+	pub highlights: Vec<CacheSearchHighlight>,
 }
 
 impl From<SearchHit> for CacheSearchHit {
 	fn from(hit: SearchHit) -> Self {
 		Self {
+			highlights: utf16_highlights(hit.result.name(), &hit.highlights),
 			parent_path: String::from(hit.parent_path),
 			result: hit.result.into(),
 		}
 	}
 }
 
+/// FFI mirror of one of [`SearchHit::highlights`], in UTF-16 code units — the string indices of
+/// JS, Kotlin and Swift's `NSString` — instead of bytes.
+#[js_type(export, no_deser)]
+pub struct CacheSearchHighlight {
+	pub start: u32,
+	pub end: u32,
+}
+
+fn utf16_highlights(name: &str, ranges: &[std::ops::Range<usize>]) -> Vec<CacheSearchHighlight> {
+	let utf16_offset = |byte_offset: usize| {
+		name.get(..byte_offset)
+			.map_or(0, |prefix| prefix.encode_utf16().count()) as u32
+	};
+	ranges
+		.iter()
+		.map(|range| CacheSearchHighlight {
+			start: utf16_offset(range.start),
+			end: utf16_offset(range.end),
+		})
+		.collect()
+}
+
 /// FFI mirror of [`SearchSnapshot`]: one window's FULL fresh contents plus the total match
 /// count — never a delta. Treat each delivery as the window's new truth.
 #[js_type(export, no_deser)]
 pub struct CacheSearchSnapshot {
-	/// The window's current contents (name-ascending, directories first), each paired with its
-	/// `parent_path` relative to the search root.
+	/// The window's current contents (in the configured order, directories first), each paired
+	/// with its `parent_path` relative to the search root.
 	pub results: Vec<CacheSearchHit>,
 	/// Total matches across the WHOLE result set, not just this window.
 	pub total: u64,
@@ -504,6 +697,19 @@ mod tests {
 			item_type: None,
 			recursive: true,
 			case_sensitive: false,
+			match_mode: None,
+			min_size: None,
+			max_size: None,
+			mime_prefixes: None,
+			extensions: None,
+			created_after: None,
+			created_before: None,
+			modified_after: None,
+			modified_before: None,
+			favorites_only: false,
+			color: None,
+			sort_by: None,
+			sort_direction: None,
 		});
 		assert_eq!(config, SearchConfig::new(), "FFI defaults == core defaults");
 
@@ -512,11 +718,40 @@ mod tests {
 			item_type: Some(CacheSearchItemType::File),
 			recursive: false,
 			case_sensitive: true,
+			match_mode: Some(CacheSearchMatchMode::Fuzzy),
+			min_size: Some(1),
+			max_size: Some(2),
+			mime_prefixes: Some(vec!["image/".to_string()]),
+			extensions: Some(vec!["pdf".to_string()]),
+			created_after: Some(ms(3)),
+			created_before: Some(ms(4)),
+			modified_after: Some(ms(5)),
+			modified_before: Some(ms(6)),
+			favorites_only: true,
+			color: Some(crate::js::DirColor::Blue),
+			sort_by: Some(CacheSearchSortBy::Size),
+			sort_direction: Some(CacheSearchSortDirection::Descending),
 		});
 		assert_eq!(config.name.as_deref(), Some("report"));
 		assert_eq!(config.item_type, SearchItemType::File);
 		assert!(!config.recursive);
 		assert!(config.case_sensitive);
+		assert_eq!(config.match_mode, SearchMatchMode::Fuzzy);
+		assert_eq!((config.min_size, config.max_size), (Some(1), Some(2)));
+		assert_eq!(config.mime_prefixes, vec!["image/"]);
+		assert_eq!(config.extensions, vec!["pdf"]);
+		assert_eq!(
+			(config.created_after, config.created_before),
+			(Some(ms(3)), Some(ms(4)))
+		);
+		assert_eq!(
+			(config.modified_after, config.modified_before),
+			(Some(ms(5)), Some(ms(6)))
+		);
+		assert!(config.favorites_only);
+		assert_eq!(config.color, Some(DirColor::Blue));
+		assert_eq!(config.sort_by, SearchSortBy::Size);
+		assert_eq!(config.sort_direction, SearchSortDirection::Descending);
 	}
 
 	#[test]
@@ -556,10 +791,12 @@ mod tests {
 				SearchHit {
 					result: SearchResult::Dir(dir.clone()),
 					parent_path: "".into(),
+					highlights: Vec::new(),
 				},
 				SearchHit {
 					result: SearchResult::File(file.clone()),
 					parent_path: "docs".into(),
+					highlights: vec![0..5],
 				},
 			],
 			total: 9,
@@ -574,9 +811,27 @@ mod tests {
 			CacheSearchResult::Dir { dir: converted } if *converted == Dir::from(RemoteDirectory::from(dir.clone()))
 		));
 		assert_eq!(snapshot.results[1].parent_path, "docs");
+		assert_eq!(
+			snapshot.results[1].highlights,
+			vec![CacheSearchHighlight { start: 0, end: 5 }]
+		);
 		assert!(matches!(
 			&snapshot.results[1].result,
 			CacheSearchResult::File { file: converted } if *converted == File::from(RemoteFile::from(file.clone()))
 		));
 	}
+
+	#[test]
+	fn highlights_cross_as_utf16_offsets() {
+		// "Ü" is two UTF-8 bytes but one UTF-16 unit, "😀" four bytes but two units
+		let name = "Übung 😀 final";
+		let start = name.find("final").unwrap();
+		assert_eq!(
+			utf16_highlights(name, &[0.."Übung".len(), start..name.len()]),
+			vec![
+				CacheSearchHighlight { start: 0, end: 5 },
+				CacheSearchHighlight { start: 9, end: 14 },
+			]
+		);
+	}
 }
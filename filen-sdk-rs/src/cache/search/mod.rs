@@ -32,6 +32,12 @@
 //! visually-identical needles — a rare edge accepted for now and gone for good once the v4
 //! re-encode normalizes the whole drive.
 //!
+//! [`SearchMatchMode::Tokens`] and [`SearchMatchMode::Fuzzy`] split the needle into
+//! whitespace-separated tokens matched in any order (fuzzy also tolerating a typo or two per
+//! token) and rank the results by relevance, computed per row by a second registered function,
+//! `filen_name_relevance`. Every [`SearchHit`] carries the byte ranges of its name the needle
+//! matched, for highlighting.
+//!
 //! # Consistency model
 //!
 //! Results are CACHE truth, which converges to server truth: a search starts from whatever is
@@ -90,8 +96,9 @@ use crate::{
 mod config;
 mod engine;
 mod hydrate;
+mod tokens;
 // Wasm routes read queries through the worker's connection; export both the task type and the
-// matcher registration so the worker can set up the same `filen_name_matches` function.
+// matcher registration so the worker can set up the same `filen_name_*` functions.
 pub(crate) use hydrate::ReadTask;
 pub(in crate::cache) use hydrate::register_name_matches;
 // FFI bindings: UniFFI on native, wasm_bindgen on wasm — gated together because both targets
@@ -100,7 +107,9 @@ pub(in crate::cache) use hydrate::register_name_matches;
 pub mod js_impl;
 mod result;
 
-pub use config::{SearchConfig, SearchItemType};
+pub use config::{
+	SearchConfig, SearchItemType, SearchMatchMode, SearchSortBy, SearchSortDirection,
+};
 pub use engine::SearchWindowCallback;
 pub use result::{SearchHit, SearchResult, SearchSnapshot};
 
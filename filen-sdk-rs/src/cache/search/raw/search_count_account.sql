@@ -1,6 +1,8 @@
 -- Total match count for an account-root-scoped search; the WHERE clause
 -- mirrors search_window_account.sql exactly. ?1 = type filter (0/1/2),
--- ?2 = needle, ?3 = case-insensitive flag.
+-- ?2 = needle, ?3 = case-insensitive flag, ?4-?13 = the metadata filters
+-- (search_window_account.sql's ?7-?16, in the same order), ?14 = match
+-- mode.
 SELECT count(*) AS count
 FROM items AS i
 LEFT JOIN files AS f ON i.id = f.id
@@ -8,7 +10,23 @@ LEFT JOIN dirs AS d ON i.id = d.id
 WHERE
 	i.type != 0
 	AND (?1 = 0 OR i.type = ?1)
-	AND filen_name_matches(coalesce(f.name, d.name), ?2, ?3)
+	AND filen_name_matches(coalesce(f.name, d.name), ?2, ?3, ?14)
+	AND (?4 IS NULL OR f.size >= ?4)
+	AND (?5 IS NULL OR f.size <= ?5)
+	AND (?6 IS NULL OR EXISTS (
+		SELECT 1 FROM json_each(?6) AS m
+		WHERE substr(lower(f.mime), 1, length(m.value)) = m.value
+	))
+	AND (?7 IS NULL OR EXISTS (
+		SELECT 1 FROM json_each(?7) AS e
+		WHERE lower(substr(f.name, -length(e.value) - 1)) = '.' || e.value
+	))
+	AND (?8 IS NULL OR coalesce(f.created, d.created) >= ?8)
+	AND (?9 IS NULL OR coalesce(f.created, d.created) < ?9)
+	AND (?10 IS NULL OR f.modified >= ?10)
+	AND (?11 IS NULL OR f.modified < ?11)
+	AND (NOT ?12 OR coalesce(f.favorite, d.favorite))
+	AND (?13 IS NULL OR (i.type = 1 AND coalesce(d.color, 'default') = ?13))
 	-- A row mid-supersede carries the PREDECESSOR's content under the
 	-- successor's uuid, so handing it out would hand out an undownloadable
 	-- file (see files.superseded). Dirs have no such row, hence the LEFT
@@ -1,7 +1,8 @@
 -- Total match count for a non-recursive (direct children) search; the
 -- WHERE clause mirrors search_window_children.sql exactly. ?1 = parent
 -- uuid, ?2 = type filter (0/1/2), ?3 = needle, ?4 = case-insensitive
--- flag.
+-- flag, ?5-?14 = the metadata filters (search_window_account.sql's ?7-?16,
+-- in the same order), ?15 = match mode.
 SELECT count(*) AS count
 FROM items AS i
 LEFT JOIN files AS f ON i.id = f.id
@@ -9,7 +10,23 @@ LEFT JOIN dirs AS d ON i.id = d.id
 WHERE
 	i.parent = ?1
 	AND (?2 = 0 OR i.type = ?2)
-	AND filen_name_matches(coalesce(f.name, d.name), ?3, ?4)
+	AND filen_name_matches(coalesce(f.name, d.name), ?3, ?4, ?15)
+	AND (?5 IS NULL OR f.size >= ?5)
+	AND (?6 IS NULL OR f.size <= ?6)
+	AND (?7 IS NULL OR EXISTS (
+		SELECT 1 FROM json_each(?7) AS m
+		WHERE substr(lower(f.mime), 1, length(m.value)) = m.value
+	))
+	AND (?8 IS NULL OR EXISTS (
+		SELECT 1 FROM json_each(?8) AS e
+		WHERE lower(substr(f.name, -length(e.value) - 1)) = '.' || e.value
+	))
+	AND (?9 IS NULL OR coalesce(f.created, d.created) >= ?9)
+	AND (?10 IS NULL OR coalesce(f.created, d.created) < ?10)
+	AND (?11 IS NULL OR f.modified >= ?11)
+	AND (?12 IS NULL OR f.modified < ?12)
+	AND (NOT ?13 OR coalesce(f.favorite, d.favorite))
+	AND (?14 IS NULL OR (i.type = 1 AND coalesce(d.color, 'default') = ?14))
 	-- A row mid-supersede carries the PREDECESSOR's content under the
 	-- successor's uuid, so handing it out would hand out an undownloadable
 	-- file (see files.superseded). Dirs have no such row, hence the LEFT
@@ -2,9 +2,19 @@
 -- (no scope clause needed), filtered, ordered, hydrated, and tagged with its
 -- PARENT PATH in one statement. `filen_name_matches` is the
 -- engine-registered Rust matcher (Unicode case folding; SQLite LIKE/lower are
--- ASCII-only) — an empty needle matches everything. Ordering: dirs first
--- (type 1 < 2), then name (NOCASE), then uuid as the deterministic pagination
--- tiebreaker.
+-- ASCII-only) — an empty needle matches everything. Ordering: relevance
+-- (`filen_name_relevance`, 0 for every row unless a ranked match mode is
+-- on), then dirs first (type 1 < 2), then the configured sort key in the
+-- configured direction (items without one, e.g. a file with no creation
+-- time, last), then name (NOCASE), then uuid as the deterministic
+-- pagination tiebreaker.
+--
+-- The metadata filters (?7-?16) each pass everything when unset (NULL, or
+-- FALSE for the favorites flag). The size, mime, extension and modification
+-- filters compare file columns, so any of them drops every dir through the
+-- LEFT JOIN's NULLs; the colour filter likewise drops every file. Mime
+-- prefixes and extensions arrive as JSON arrays, already lowercased and
+-- without the extensions' leading dots.
 --
 -- The window is materialized ONCE as `page` (count(*) OVER () still rides
 -- every row), then `climb` walks each page row's parent chain UP to the
@@ -16,7 +26,12 @@
 -- cap) all fall through the terminal LEFT JOIN miss to an empty parent path.
 -- ?1 = account-root uuid (climb stop-anchor), ?2 = type filter (0 = all,
 -- 1 = dirs, 2 = files), ?3 = needle, ?4 = case-insensitive flag, ?5 = limit,
--- ?6 = offset.
+-- ?6 = offset, ?7/?8 = min/max size (inclusive), ?9 = mime prefixes,
+-- ?10 = extensions, ?11/?12 = created after (inclusive)/before (exclusive),
+-- ?13/?14 = modified after/before, ?15 = favorites only, ?16 = dir colour,
+-- ?17 = sort key (0 = name, 1 = size, 2 = modified, 3 = created, 4 = mime),
+-- ?18 = descending flag, ?19 = match mode (0 = substring, 1 = tokens,
+-- 2 = fuzzy). Times are unix millis, like the columns.
 WITH RECURSIVE
 page AS (
 	SELECT
@@ -44,6 +59,18 @@ page AS (
 		d.timestamp AS dir_timestamp,
 		d.name AS dir_name,
 		d.created AS dir_created,
+		-- the configured sort column (?17: 0 = name, 1 = size, 2 = modified,
+		-- 3 = created, 4 = mime); dirs have no size, modification time or mime,
+		-- but they sort ahead of every file anyway
+		CASE ?17
+			WHEN 1 THEN f.size
+			WHEN 2 THEN f.modified
+			WHEN 3 THEN coalesce(f.created, d.created)
+			WHEN 4 THEN lower(f.mime)
+			ELSE lower(coalesce(f.name, d.name))
+		END AS sort_key,
+		-- 0 unless a ranked match mode (?19) is on
+		filen_name_relevance(coalesce(f.name, d.name), ?3, ?4, ?19) AS relevance,
 		count(*) OVER () AS total
 	FROM items AS i
 	LEFT JOIN files AS f ON i.id = f.id
@@ -51,7 +78,23 @@ page AS (
 	WHERE
 		i.type != 0
 		AND (?2 = 0 OR i.type = ?2)
-		AND filen_name_matches(coalesce(f.name, d.name), ?3, ?4)
+		AND filen_name_matches(coalesce(f.name, d.name), ?3, ?4, ?19)
+		AND (?7 IS NULL OR f.size >= ?7)
+		AND (?8 IS NULL OR f.size <= ?8)
+		AND (?9 IS NULL OR EXISTS (
+			SELECT 1 FROM json_each(?9) AS m
+			WHERE substr(lower(f.mime), 1, length(m.value)) = m.value
+		))
+		AND (?10 IS NULL OR EXISTS (
+			SELECT 1 FROM json_each(?10) AS e
+			WHERE lower(substr(f.name, -length(e.value) - 1)) = '.' || e.value
+		))
+		AND (?11 IS NULL OR coalesce(f.created, d.created) >= ?11)
+		AND (?12 IS NULL OR coalesce(f.created, d.created) < ?12)
+		AND (?13 IS NULL OR f.modified >= ?13)
+		AND (?14 IS NULL OR f.modified < ?14)
+		AND (NOT ?15 OR coalesce(f.favorite, d.favorite))
+		AND (?16 IS NULL OR (i.type = 1 AND coalesce(d.color, 'default') = ?16))
 		-- A row mid-supersede carries the PREDECESSOR's content under the
 		-- successor's uuid, so handing it out would hand out an undownloadable
 		-- file (see files.superseded). Dirs have no such row, hence the LEFT
@@ -60,7 +103,14 @@ page AS (
 	-- lower() = the same ASCII case-fold ordering as COLLATE NOCASE
 	-- (which sqlfluff cannot parse here); non-ASCII names order by their
 	-- (NFC-assumed) bytes.
-	ORDER BY i.type, lower(coalesce(f.name, d.name)), i.uuid
+	ORDER BY
+		relevance DESC,
+		i.type,
+		-- ?18 = descending: the other direction's key is NULL on every row
+		CASE WHEN NOT ?18 THEN sort_key END ASC NULLS LAST,
+		CASE WHEN ?18 THEN sort_key END DESC NULLS LAST,
+		lower(coalesce(f.name, d.name)),
+		i.uuid
 	LIMIT ?5 OFFSET ?6
 ),
 
@@ -120,4 +170,11 @@ SELECT
 	coalesce(term.loc, '') AS parent_path
 FROM page AS p
 LEFT JOIN climb AS term ON p.id = term.page_id AND term.cur_parent = ?1
-ORDER BY p.type, lower(coalesce(p.file_name, p.dir_name)), p.uuid;
+ORDER BY
+	p.relevance DESC,
+	p.type,
+	-- ?18 = descending: the other direction's key is NULL on every row
+	CASE WHEN NOT ?18 THEN p.sort_key END ASC NULLS LAST,
+	CASE WHEN ?18 THEN p.sort_key END DESC NULLS LAST,
+	lower(coalesce(p.file_name, p.dir_name)),
+	p.uuid;
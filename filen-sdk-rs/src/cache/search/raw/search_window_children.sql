@@ -5,7 +5,9 @@
 -- the row shape uniform with the account/subtree windows (no climb needed).
 -- ?1 = parent uuid, ?2 = type filter
 -- (0/1/2), ?3 = needle, ?4 = case-insensitive flag, ?5 = limit,
--- ?6 = offset.
+-- ?6 = offset, ?7-?16 = the metadata filters, ?17/?18 = the sort key and
+-- direction and ?19 = the match mode, numbered as in
+-- search_window_account.sql.
 SELECT
 	i.uuid,
 	i.parent,
@@ -30,6 +32,18 @@ SELECT
 	d.timestamp AS dir_timestamp,
 	d.name AS dir_name,
 	d.created AS dir_created,
+	-- the configured sort column (?17: 0 = name, 1 = size, 2 = modified,
+	-- 3 = created, 4 = mime); dirs have no size, modification time or mime,
+	-- but they sort ahead of every file anyway
+	CASE ?17
+		WHEN 1 THEN f.size
+		WHEN 2 THEN f.modified
+		WHEN 3 THEN coalesce(f.created, d.created)
+		WHEN 4 THEN lower(f.mime)
+		ELSE lower(coalesce(f.name, d.name))
+	END AS sort_key,
+	-- 0 unless a ranked match mode (?19) is on
+	filen_name_relevance(coalesce(f.name, d.name), ?3, ?4, ?19) AS relevance,
 	-- direct children are always at the root, so the parent path is empty
 	'' AS parent_path,
 	-- the pre-LIMIT match total, piggybacked on every row so one scan
@@ -41,7 +55,23 @@ LEFT JOIN dirs AS d ON i.id = d.id
 WHERE
 	i.parent = ?1
 	AND (?2 = 0 OR i.type = ?2)
-	AND filen_name_matches(coalesce(f.name, d.name), ?3, ?4)
+	AND filen_name_matches(coalesce(f.name, d.name), ?3, ?4, ?19)
+	AND (?7 IS NULL OR f.size >= ?7)
+	AND (?8 IS NULL OR f.size <= ?8)
+	AND (?9 IS NULL OR EXISTS (
+		SELECT 1 FROM json_each(?9) AS m
+		WHERE substr(lower(f.mime), 1, length(m.value)) = m.value
+	))
+	AND (?10 IS NULL OR EXISTS (
+		SELECT 1 FROM json_each(?10) AS e
+		WHERE lower(substr(f.name, -length(e.value) - 1)) = '.' || e.value
+	))
+	AND (?11 IS NULL OR coalesce(f.created, d.created) >= ?11)
+	AND (?12 IS NULL OR coalesce(f.created, d.created) < ?12)
+	AND (?13 IS NULL OR f.modified >= ?13)
+	AND (?14 IS NULL OR f.modified < ?14)
+	AND (NOT ?15 OR coalesce(f.favorite, d.favorite))
+	AND (?16 IS NULL OR (i.type = 1 AND coalesce(d.color, 'default') = ?16))
 	-- A row mid-supersede carries the PREDECESSOR's content under the
 	-- successor's uuid, so handing it out would hand out an undownloadable
 	-- file (see files.superseded). Dirs have no such row, hence the LEFT
@@ -50,5 +80,12 @@ WHERE
 -- lower() = the same ASCII case-fold ordering as COLLATE NOCASE
 -- (which sqlfluff cannot parse here); non-ASCII names order by their
 -- (NFC-assumed) bytes.
-ORDER BY i.type, lower(coalesce(f.name, d.name)), i.uuid
+ORDER BY
+	relevance DESC,
+	i.type,
+	-- ?18 = descending: the other direction's key is NULL on every row
+	CASE WHEN NOT ?18 THEN sort_key END ASC NULLS LAST,
+	CASE WHEN ?18 THEN sort_key END DESC NULLS LAST,
+	lower(coalesce(f.name, d.name)),
+	i.uuid
 LIMIT ?5 OFFSET ?6;
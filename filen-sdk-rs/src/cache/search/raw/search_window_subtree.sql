@@ -6,7 +6,9 @@
 -- path-build (here it stops at the anchor, so the path is anchor-relative;
 -- the anchor's own direct children get '').
 -- ?1 = anchor uuid (reused as the climb stop-anchor), ?2 = type filter
--- (0/1/2), ?3 = needle, ?4 = case-insensitive flag, ?5 = limit, ?6 = offset.
+-- (0/1/2), ?3 = needle, ?4 = case-insensitive flag, ?5 = limit, ?6 = offset,
+-- ?7-?16 = the metadata filters, ?17/?18 = the sort key and direction and
+-- ?19 = the match mode, numbered as in search_window_account.sql.
 WITH RECURSIVE
 subtree (uuid) AS (
 	SELECT uuid FROM items
@@ -43,6 +45,18 @@ page AS (
 		d.timestamp AS dir_timestamp,
 		d.name AS dir_name,
 		d.created AS dir_created,
+		-- the configured sort column (?17: 0 = name, 1 = size, 2 = modified,
+		-- 3 = created, 4 = mime); dirs have no size, modification time or mime,
+		-- but they sort ahead of every file anyway
+		CASE ?17
+			WHEN 1 THEN f.size
+			WHEN 2 THEN f.modified
+			WHEN 3 THEN coalesce(f.created, d.created)
+			WHEN 4 THEN lower(f.mime)
+			ELSE lower(coalesce(f.name, d.name))
+		END AS sort_key,
+		-- 0 unless a ranked match mode (?19) is on
+		filen_name_relevance(coalesce(f.name, d.name), ?3, ?4, ?19) AS relevance,
 		count(*) OVER () AS total
 	FROM items AS i
 	INNER JOIN subtree AS s ON i.uuid = s.uuid
@@ -50,13 +64,36 @@ page AS (
 	LEFT JOIN dirs AS d ON i.id = d.id
 	WHERE
 		(?2 = 0 OR i.type = ?2)
-		AND filen_name_matches(coalesce(f.name, d.name), ?3, ?4)
+		AND filen_name_matches(coalesce(f.name, d.name), ?3, ?4, ?19)
+		AND (?7 IS NULL OR f.size >= ?7)
+		AND (?8 IS NULL OR f.size <= ?8)
+		AND (?9 IS NULL OR EXISTS (
+			SELECT 1 FROM json_each(?9) AS m
+			WHERE substr(lower(f.mime), 1, length(m.value)) = m.value
+		))
+		AND (?10 IS NULL OR EXISTS (
+			SELECT 1 FROM json_each(?10) AS e
+			WHERE lower(substr(f.name, -length(e.value) - 1)) = '.' || e.value
+		))
+		AND (?11 IS NULL OR coalesce(f.created, d.created) >= ?11)
+		AND (?12 IS NULL OR coalesce(f.created, d.created) < ?12)
+		AND (?13 IS NULL OR f.modified >= ?13)
+		AND (?14 IS NULL OR f.modified < ?14)
+		AND (NOT ?15 OR coalesce(f.favorite, d.favorite))
+		AND (?16 IS NULL OR (i.type = 1 AND coalesce(d.color, 'default') = ?16))
 		-- A row mid-supersede carries the PREDECESSOR's content under the
 		-- successor's uuid, so handing it out would hand out an undownloadable
 		-- file (see files.superseded). Dirs have no such row, hence the LEFT
 		-- JOIN's NULL passing.
 		AND coalesce(f.superseded, FALSE) = FALSE
-	ORDER BY i.type, lower(coalesce(f.name, d.name)), i.uuid
+	ORDER BY
+		relevance DESC,
+		i.type,
+		-- ?18 = descending: the other direction's key is NULL on every row
+		CASE WHEN NOT ?18 THEN sort_key END ASC NULLS LAST,
+		CASE WHEN ?18 THEN sort_key END DESC NULLS LAST,
+		lower(coalesce(f.name, d.name)),
+		i.uuid
 	LIMIT ?5 OFFSET ?6
 ),
 
@@ -110,4 +147,11 @@ SELECT
 	coalesce(term.loc, '') AS parent_path
 FROM page AS p
 LEFT JOIN climb AS term ON p.id = term.page_id AND term.cur_parent = ?1
-ORDER BY p.type, lower(coalesce(p.file_name, p.dir_name)), p.uuid;
+ORDER BY
+	p.relevance DESC,
+	p.type,
+	-- ?18 = descending: the other direction's key is NULL on every row
+	CASE WHEN NOT ?18 THEN p.sort_key END ASC NULLS LAST,
+	CASE WHEN ?18 THEN p.sort_key END DESC NULLS LAST,
+	lower(coalesce(p.file_name, p.dir_name)),
+	p.uuid;
@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use uuid::Uuid;
 
 use crate::fs::{dir::cache::CacheableDir, file::cache::CacheableFile};
@@ -46,6 +48,8 @@ impl SearchResult {
 /// appends it). The parent path is computed from cached ancestry at query time, so it tracks
 /// ancestor renames/moves.
 ///
+/// [`highlights`](Self::highlights) are the parts of the item's name the search's needle matched.
+///
 /// The joined form assumes item names do not themselves contain `/`: cache names are not
 /// validated on ingest, but a slash in a name is out of contract and would make the joined parent
 /// path ambiguous.
@@ -56,6 +60,9 @@ pub struct SearchHit {
 	/// The item's parent path relative to the search root (see the type docs). Empty for a direct
 	/// child of the search root.
 	pub parent_path: Box<str>,
+	/// The byte ranges of the item's name the needle matched, sorted and non-overlapping. Empty
+	/// without a needle.
+	pub highlights: Vec<Range<usize>>,
 }
 
 impl SearchHit {
@@ -66,6 +73,13 @@ impl SearchHit {
 		&self.parent_path
 	}
 
+	/// The byte ranges of the item's name the needle matched: its first occurrence in substring
+	/// mode, each token's best match in the ranked modes. Sorted and non-overlapping; empty
+	/// without a needle.
+	pub fn highlights(&self) -> &[Range<usize>] {
+		&self.highlights
+	}
+
 	/// The item's full path relative to the search root: [`parent_path`](Self::parent_path) plus
 	/// the item's own name (just the name for a direct child of the root).
 	pub fn full_path(&self) -> String {
@@ -83,8 +97,9 @@ impl SearchHit {
 #[derive(Debug, Clone)]
 #[non_exhaustive]
 pub struct SearchSnapshot {
-	/// The window's current contents (name-ascending, directories first, ties broken by uuid),
-	/// each paired with its [`parent_path`](SearchHit::parent_path) relative to the search root.
+	/// The window's current contents (in the configured order, directories first, ties broken
+	/// by name and then uuid), each paired with its [`parent_path`](SearchHit::parent_path)
+	/// relative to the search root.
 	pub results: Vec<SearchHit>,
 	/// Total matches across the WHOLE result set, not just this window. Any total change marks
 	/// every window dirty, so a delivered total never goes silently stale.
@@ -0,0 +1,375 @@
+//! The ranked name matching modes ([`SearchMatchMode::Tokens`] and [`SearchMatchMode::Fuzzy`]):
+//! the needle splits on whitespace into tokens that must ALL match the name, in any order. A
+//! token matches as a substring, with the same escaped-literal regexes (and so the same Unicode
+//! simple case folding) as the substring mode; in fuzzy mode a token that is no substring may
+//! instead match the start of one of the name's words within a small edit distance.
+//!
+//! Each token match gets a quality — whole word > word prefix > infix > typo — and the
+//! [`relevance`] the ranked modes order by adds those up, breaking ties toward names that start
+//! with a match, hold the tokens in needle order, and are shorter. All of this runs per row
+//! inside the SQL functions, so fuzzy mode's edit distances are the costliest part of a query;
+//! they are only computed for tokens that are no substring of the name.
+//!
+//! [`SearchMatchMode::Tokens`]: super::SearchMatchMode::Tokens
+//! [`SearchMatchMode::Fuzzy`]: super::SearchMatchMode::Fuzzy
+
+use std::ops::Range;
+
+use regex::bytes::Regex;
+
+const QUALITY_WHOLE_WORD: i64 = 4;
+const QUALITY_WORD_PREFIX: i64 = 3;
+const QUALITY_INFIX: i64 = 2;
+const QUALITY_TYPO: i64 = 1;
+
+/// One compiled needle token.
+struct Token {
+	sensitive: Regex,
+	insensitive: Regex,
+	/// The token's chars, for the case-sensitive edit distance
+	chars: Vec<char>,
+	/// The token's chars, each simply lowercased, for the case-insensitive edit distance
+	folded: Vec<char>,
+}
+
+/// Where one token matched a name, as a byte range of the name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct TokenMatch {
+	pub(super) range: Range<usize>,
+	quality: i64,
+}
+
+/// The needle's tokens, compiled once per query.
+pub(super) struct TokenMatchers(Vec<Token>);
+
+impl TokenMatchers {
+	/// Only fails on pathological tokens (the compiled-size limit).
+	pub(super) fn compile(needle: &str) -> Result<Self, regex::Error> {
+		needle
+			.split_whitespace()
+			.map(|token| {
+				let literal = regex::escape(token);
+				Ok(Token {
+					sensitive: Regex::new(&literal)?,
+					insensitive: Regex::new(&format!("(?i){literal}"))?,
+					chars: token.chars().collect(),
+					folded: token.chars().map(fold).collect(),
+				})
+			})
+			.collect::<Result<_, _>>()
+			.map(Self)
+	}
+
+	/// Whether every token matches `name`, without ranking the matches.
+	pub(super) fn matches(&self, name: &[u8], case_insensitive: bool, fuzzy: bool) -> bool {
+		let text = fuzzy.then(|| std::str::from_utf8(name).ok()).flatten();
+		self.0.iter().all(|token| {
+			token.regex(case_insensitive).is_match(name)
+				|| text.is_some_and(|text| token.fuzzy_match(text, case_insensitive).is_some())
+		})
+	}
+
+	/// The best match of every token in `name`, in needle order; `None` unless all of them
+	/// match.
+	pub(super) fn find(
+		&self,
+		name: &[u8],
+		case_insensitive: bool,
+		fuzzy: bool,
+	) -> Option<Vec<TokenMatch>> {
+		// Word boundaries and edit distances need chars; an out-of-contract non-UTF-8 name is
+		// still matched bytewise, with every match ranked as an infix.
+		let text = std::str::from_utf8(name).ok();
+		self.0
+			.iter()
+			.map(|token| {
+				token
+					.regex(case_insensitive)
+					.find_iter(name)
+					.map(|found| TokenMatch {
+						quality: text
+							.map_or(QUALITY_INFIX, |text| word_quality(text, found.range())),
+						range: found.range(),
+					})
+					// the earliest of the best matches
+					.reduce(|best, next| {
+						if next.quality > best.quality {
+							next
+						} else {
+							best
+						}
+					})
+					.or_else(|| {
+						fuzzy
+							.then(|| token.fuzzy_match(text?, case_insensitive))
+							.flatten()
+					})
+			})
+			.collect()
+	}
+}
+
+impl Token {
+	fn regex(&self, case_insensitive: bool) -> &Regex {
+		if case_insensitive {
+			&self.insensitive
+		} else {
+			&self.sensitive
+		}
+	}
+
+	/// The start of a word in `name` closest to the token by edit distance (adjacent
+	/// transpositions count as one edit), if it is close enough: tokens of four to seven chars
+	/// allow one edit, longer ones two, shorter ones none.
+	fn fuzzy_match(&self, name: &str, case_insensitive: bool) -> Option<TokenMatch> {
+		let token = if case_insensitive {
+			&self.folded
+		} else {
+			&self.chars
+		};
+		let max_edits = match token.len() {
+			0..4 => return None,
+			4..8 => 1,
+			_ => 2,
+		};
+		let mut best: Option<(usize, Range<usize>)> = None;
+		for (start, word) in words(name) {
+			let chars = word
+				.chars()
+				.map(|c| if case_insensitive { fold(c) } else { c })
+				.collect::<Vec<_>>();
+			let shortest = token.len().saturating_sub(max_edits).max(1);
+			let longest = (token.len() + max_edits).min(chars.len());
+			// longest first, so equally close prefixes highlight as much of the word as they can
+			for len in (shortest..=longest).rev() {
+				let distance = edit_distance(token, &chars[..len]);
+				if distance <= max_edits && best.as_ref().is_none_or(|(best, _)| distance < *best) {
+					let end = word
+						.char_indices()
+						.nth(len)
+						.map_or(word.len(), |(idx, _)| idx);
+					best = Some((distance, start..start + end));
+				}
+			}
+		}
+		best.map(|(_, range)| TokenMatch {
+			range,
+			quality: QUALITY_TYPO,
+		})
+	}
+}
+
+/// The relevance of a name that `matches` were found in (see the module docs); always positive.
+pub(super) fn relevance(name: &[u8], matches: &[TokenMatch]) -> i64 {
+	let quality = matches.iter().map(|found| found.quality).sum::<i64>();
+	let at_start = matches.iter().any(|found| found.range.start == 0);
+	let in_order = matches.len() > 1
+		&& matches
+			.windows(2)
+			.all(|pair| pair[0].range.start < pair[1].range.start);
+	let length = std::str::from_utf8(name).map_or(name.len(), |name| name.chars().count());
+	quality * 1000 + if at_start { 200 } else { 0 } + if in_order { 100 } else { 0 }
+		- length.min(99) as i64
+}
+
+/// `ranges` sorted, with overlapping and adjacent ones merged.
+pub(super) fn merge_ranges(ranges: impl IntoIterator<Item = Range<usize>>) -> Vec<Range<usize>> {
+	let mut ranges = ranges.into_iter().collect::<Vec<_>>();
+	ranges.sort_by_key(|range| range.start);
+	let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+	for range in ranges {
+		match merged.last_mut() {
+			Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+			_ => merged.push(range),
+		}
+	}
+	merged
+}
+
+/// Simple lowercasing, one char per char so char positions stay put.
+fn fold(c: char) -> char {
+	let mut lower = c.to_lowercase();
+	match (lower.next(), lower.next()) {
+		(Some(lower), None) => lower,
+		_ => c,
+	}
+}
+
+/// The name's words — runs of alphanumeric chars — with their byte offsets.
+fn words(name: &str) -> impl Iterator<Item = (usize, &str)> {
+	name.split(|c: char| !c.is_alphanumeric())
+		.filter(|word| !word.is_empty())
+		.map(move |word| (word.as_ptr() as usize - name.as_ptr() as usize, word))
+}
+
+/// Whether a word starts between `prev` and `next`: after a non-alphanumeric char, at a
+/// lowercase-to-uppercase step (camelCase), or where letters and digits meet ("Q3", "v2").
+fn is_boundary(prev: char, next: char) -> bool {
+	!prev.is_alphanumeric()
+		|| !next.is_alphanumeric()
+		|| (prev.is_lowercase() && next.is_uppercase())
+		|| prev.is_alphabetic() != next.is_alphabetic()
+}
+
+fn word_quality(name: &str, range: Range<usize>) -> i64 {
+	if !name.is_char_boundary(range.start) || !name.is_char_boundary(range.end) {
+		return QUALITY_INFIX;
+	}
+	let starts_word = name[..range.start]
+		.chars()
+		.next_back()
+		.zip(name[range.start..].chars().next())
+		.is_none_or(|(prev, next)| is_boundary(prev, next));
+	let ends_word = name[..range.end]
+		.chars()
+		.next_back()
+		.zip(name[range.end..].chars().next())
+		.is_none_or(|(prev, next)| is_boundary(prev, next));
+	match (starts_word, ends_word) {
+		(true, true) => QUALITY_WHOLE_WORD,
+		(true, false) => QUALITY_WORD_PREFIX,
+		_ => QUALITY_INFIX,
+	}
+}
+
+/// The optimal string alignment distance: insertions, deletions, substitutions and adjacent
+/// transpositions each cost one edit.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+	let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+	for (i, row) in rows.iter_mut().enumerate() {
+		row[0] = i;
+	}
+	for (j, cell) in rows[0].iter_mut().enumerate() {
+		*cell = j;
+	}
+	for i in 1..=a.len() {
+		for j in 1..=b.len() {
+			let substitution = usize::from(a[i - 1] != b[j - 1]);
+			let mut distance = (rows[i - 1][j] + 1)
+				.min(rows[i][j - 1] + 1)
+				.min(rows[i - 1][j - 1] + substitution);
+			if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+				distance = distance.min(rows[i - 2][j - 2] + 1);
+			}
+			rows[i][j] = distance;
+		}
+	}
+	rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn find(needle: &str, name: &str, fuzzy: bool) -> Option<Vec<TokenMatch>> {
+		TokenMatchers::compile(needle)
+			.unwrap()
+			.find(name.as_bytes(), true, fuzzy)
+	}
+
+	#[test]
+	fn match_quality_follows_word_boundaries() {
+		let quality = |needle, name| find(needle, name, false).unwrap()[0].quality;
+		assert_eq!(quality("q3", "Report Q3.pdf"), QUALITY_WHOLE_WORD);
+		assert_eq!(
+			quality("report", "report2024"),
+			QUALITY_WHOLE_WORD,
+			"letters meet digits"
+		);
+		assert_eq!(
+			quality("draft", "myDraftNotes"),
+			QUALITY_WHOLE_WORD,
+			"camelCase"
+		);
+		assert_eq!(quality("rep", "Report"), QUALITY_WORD_PREFIX);
+		assert_eq!(quality("port", "Report"), QUALITY_INFIX);
+		assert_eq!(
+			find("port", "Report port", false).unwrap()[0].range,
+			7..11,
+			"the best match wins over the earliest one"
+		);
+	}
+
+	#[test]
+	fn every_token_has_to_match_in_any_order() {
+		let matchers = TokenMatchers::compile("  final   q3 ").unwrap();
+		assert!(matchers.matches(b"Q3 report final", true, false));
+		assert!(!matchers.matches(b"Q3 report final", false, false));
+		assert!(!matchers.matches(b"Q3 report", true, false));
+		assert_eq!(
+			find("final q3", "Q3 report final", false)
+				.unwrap()
+				.into_iter()
+				.map(|found| found.range)
+				.collect::<Vec<_>>(),
+			vec![10..15, 0..2],
+			"in needle order"
+		);
+	}
+
+	#[test]
+	fn fuzzy_matches_word_starts_within_the_edit_budget() {
+		let range = |needle, name| find(needle, name, true).map(|found| found[0].range.clone());
+		assert_eq!(
+			range("reprot", "old report.txt"),
+			Some(4..10),
+			"transposition"
+		);
+		assert_eq!(
+			range("raport", "Reports"),
+			Some(0..6),
+			"substitution, word prefix"
+		);
+		assert_eq!(range("documnts", "my documents"), Some(3..12), "deletion");
+		assert_eq!(
+			range("documantz", "my documents"),
+			Some(3..12),
+			"two edits, and the longest prefix among equally close ones"
+		);
+		assert_eq!(
+			range("rpt", "report"),
+			None,
+			"short tokens need exact matches"
+		);
+		assert_eq!(range("reprot", "misreported"), None, "only word starts");
+		assert!(
+			find("reprot", "report", false).is_none(),
+			"only in fuzzy mode"
+		);
+	}
+
+	#[test]
+	fn relevance_prefers_better_matches_starts_order_and_short_names() {
+		let relevance =
+			|needle, name: &str| relevance(name.as_bytes(), &find(needle, name, true).unwrap());
+		assert!(relevance("report", "old report") > relevance("report", "reports"));
+		assert!(relevance("report", "reports") > relevance("report", "misreported"));
+		assert!(relevance("report", "report b") > relevance("report", "a report"));
+		assert!(relevance("a b", "x a b") > relevance("a b", "x b a"));
+		assert!(relevance("report", "report") > relevance("report", "report 2"));
+		assert!(relevance("reprot", "report") < relevance("port", "report"));
+	}
+
+	#[test]
+	fn merge_ranges_sorts_and_joins_overlaps() {
+		assert_eq!(
+			merge_ranges([7..9, 0..3, 2..5, 5..6, 10..12]),
+			vec![0..6, 7..9, 10..12]
+		);
+	}
+
+	#[test]
+	fn edit_distance_counts_transpositions_once() {
+		let distance = |a: &str, b: &str| {
+			edit_distance(
+				&a.chars().collect::<Vec<_>>(),
+				&b.chars().collect::<Vec<_>>(),
+			)
+		};
+		assert_eq!(distance("report", "report"), 0);
+		assert_eq!(distance("reprot", "report"), 1);
+		assert_eq!(distance("rport", "report"), 1);
+		assert_eq!(distance("raport", "report"), 1);
+		assert_eq!(distance("", "abc"), 3);
+	}
+}
@@ -415,6 +415,10 @@ impl SharedRootFile {
 		&self.file
 	}
 
+	pub fn sharing_role(&self) -> &SharingRole {
+		&self.sharing_role
+	}
+
 	pub fn get_source_id(&self) -> u64 {
 		match &self.sharing_role {
 			SharingRole::Sharer(info) | SharingRole::Receiver(info) => info.id,
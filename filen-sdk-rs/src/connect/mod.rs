@@ -136,6 +136,14 @@ impl FilePublicLink {
 		self.link_uuid
 	}
 
+	pub fn expiration(&self) -> PublicLinkExpiration {
+		self.expiration
+	}
+
+	pub fn downloadable(&self) -> bool {
+		self.downloadable
+	}
+
 	pub fn set_password(&mut self, password: String) {
 		if let PasswordState::Known(ref current) = self.password
 			&& &password == current
@@ -280,6 +288,18 @@ impl DirPublicLinkRW {
 		self.link_key.as_ref().map(|k| k.to_string())
 	}
 
+	pub fn password(&self) -> &PasswordState {
+		&self.password
+	}
+
+	pub fn expiration(&self) -> PublicLinkExpiration {
+		self.expiration
+	}
+
+	pub fn enable_download(&self) -> bool {
+		self.enable_download
+	}
+
 	pub fn set_password(&mut self, password: String) {
 		self.password = PasswordState::Known(password);
 		self.salt = crate::crypto::connect::new_random_salt()
@@ -1,8 +1,9 @@
-use std::{num::NonZeroU32, time::Duration};
+use std::{borrow::Cow, num::NonZeroU32, time::Duration};
 
 use filen_types::auth::{AuthVersion, FileEncryptionVersion, MetaEncryptionVersion};
 
-#[cfg(all(target_family = "wasm", target_os = "unknown"))]
+use crate::{Error, ErrorKind};
+
 const GATEWAY_URLS: [&str; 8] = [
 	"https://gateway.filen.io",
 	"https://gateway.filen.net",
@@ -14,7 +15,6 @@ const GATEWAY_URLS: [&str; 8] = [
 	"https://gateway.filen-6.net",
 ];
 
-#[cfg(all(target_family = "wasm", target_os = "unknown"))]
 const EGEST_URLS: [&str; 8] = [
 	"https://egest.filen.io",
 	"https://egest.filen.net",
@@ -26,7 +26,6 @@ const EGEST_URLS: [&str; 8] = [
 	"https://egest.filen-6.net",
 ];
 
-#[cfg(all(target_family = "wasm", target_os = "unknown"))]
 const INGEST_URLS: [&str; 8] = [
 	"https://ingest.filen.io",
 	"https://ingest.filen.net",
@@ -38,38 +37,226 @@ const INGEST_URLS: [&str; 8] = [
 	"https://ingest.filen-6.net",
 ];
 
+const SOCKET_URL: &str = "wss://socket.filen.io";
+
+/// How many of the default URLs of each kind are used. Native clients only use the first one.
 #[cfg(all(target_family = "wasm", target_os = "unknown"))]
-pub fn random_gateway_url() -> &'static str {
-	GATEWAY_URLS[rand::random_range(0..GATEWAY_URLS.len())]
+const DEFAULT_URL_COUNT: usize = 8;
+#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
+const DEFAULT_URL_COUNT: usize = 1;
+
+/// Base URLs of the Filen servers a client talks to: the API gateway, the egest and ingest
+/// servers that file chunks are downloaded from and uploaded to, and the socket server that
+/// pushes drive events.
+///
+/// Every request picks one URL of its kind at random. Defaults to the production servers;
+/// override it with [`ClientConfig::with_endpoints`](crate::auth::http::ClientConfig::with_endpoints)
+/// to target a staging or local server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiEndpoints {
+	gateway: Vec<Cow<'static, str>>,
+	egest: Vec<Cow<'static, str>>,
+	ingest: Vec<Cow<'static, str>>,
+	socket: Cow<'static, str>,
 }
 
-#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
-pub fn random_gateway_url() -> &'static str {
-	"https://gateway.filen.io"
+impl ApiEndpoints {
+	/// Creates endpoints from lists of base URLs. Trailing slashes are removed.
+	/// The socket server stays the production one, see [`Self::with_socket_url`].
+	///
+	/// Fails if any of the lists is empty, or any URL doesn't use `https://`. Plain `http://`
+	/// URLs are only accepted for loopback hosts.
+	pub fn new(
+		gateway: impl IntoIterator<Item = impl Into<String>>,
+		egest: impl IntoIterator<Item = impl Into<String>>,
+		ingest: impl IntoIterator<Item = impl Into<String>>,
+	) -> Result<Self, Error> {
+		fn normalize(
+			kind: &str,
+			urls: impl IntoIterator<Item = impl Into<String>>,
+		) -> Result<Vec<Cow<'static, str>>, Error> {
+			let urls = urls
+				.into_iter()
+				.map(|url| {
+					let url: String = url.into();
+					let url = url.trim_end_matches('/');
+					if !is_allowed_http_url(url) {
+						return Err(insecure_url_error(kind, url));
+					}
+					Ok(Cow::Owned(url.to_owned()))
+				})
+				.collect::<Result<Vec<_>, _>>()?;
+			if urls.is_empty() {
+				return Err(Error::custom(
+					ErrorKind::InvalidState,
+					format!("at least one {kind} URL is required"),
+				));
+			}
+			Ok(urls)
+		}
+		Ok(Self {
+			gateway: normalize("gateway", gateway)?,
+			egest: normalize("egest", egest)?,
+			ingest: normalize("ingest", ingest)?,
+			socket: Cow::Borrowed(SOCKET_URL),
+		})
+	}
+
+	/// Uses the same base URL for the gateway, egest, ingest and socket servers, e.g. a local mock
+	/// server. The socket URL is derived by swapping `http(s)://` for `ws(s)://`.
+	///
+	/// Fails like [`Self::new`] if the URL doesn't use `https://` and isn't a loopback one.
+	pub fn single(url: impl Into<String>) -> Result<Self, Error> {
+		let url: String = url.into();
+		let url = url.trim_end_matches('/');
+		let socket = if let Some(rest) = url.strip_prefix("https://") {
+			format!("wss://{rest}")
+		} else if let Some(rest) = url.strip_prefix("http://")
+			&& is_allowed_http_url(url)
+		{
+			format!("ws://{rest}")
+		} else {
+			return Err(insecure_url_error("server", url));
+		};
+		let url = Cow::Owned(url.to_owned());
+		Ok(Self {
+			gateway: vec![url.clone()],
+			egest: vec![url.clone()],
+			ingest: vec![url],
+			socket: Cow::Owned(socket),
+		})
+	}
+
+	/// Replaces the socket server URL, e.g. `wss://socket.filen.io`. A trailing slash is removed.
+	///
+	/// Plain `ws://` URLs are only accepted for loopback hosts, everything else must use `wss://`.
+	pub fn with_socket_url(mut self, url: impl Into<String>) -> Result<Self, Error> {
+		let url: String = url.into();
+		let url = url.trim_end_matches('/');
+		if !is_allowed_socket_url(url) {
+			return Err(Error::custom(
+				ErrorKind::InvalidState,
+				format!("socket URL must use wss:// unless it points to a loopback host: {url}"),
+			));
+		}
+		self.socket = Cow::Owned(url.to_owned());
+		Ok(self)
+	}
+
+	/// Builds endpoints from optional URL overrides, as found in serialized configs.
+	/// Returns `None` if none are set, and fails if only some of the URL lists are.
+	pub(crate) fn from_overrides(
+		gateway: Option<Vec<String>>,
+		egest: Option<Vec<String>>,
+		ingest: Option<Vec<String>>,
+		socket: Option<String>,
+	) -> Result<Option<Self>, Error> {
+		let endpoints = match (gateway, egest, ingest) {
+			(None, None, None) => None,
+			(Some(gateway), Some(egest), Some(ingest)) => Some(Self::new(gateway, egest, ingest)?),
+			_ => {
+				return Err(Error::custom(
+					ErrorKind::InvalidState,
+					"gateway, egest and ingest URLs must be overridden together",
+				));
+			}
+		};
+		match socket {
+			Some(socket) => endpoints
+				.unwrap_or_default()
+				.with_socket_url(socket)
+				.map(Some),
+			None => Ok(endpoints),
+		}
+	}
+
+	pub fn gateway_urls(&self) -> &[Cow<'static, str>] {
+		&self.gateway
+	}
+
+	pub fn egest_urls(&self) -> &[Cow<'static, str>] {
+		&self.egest
+	}
+
+	pub fn ingest_urls(&self) -> &[Cow<'static, str>] {
+		&self.ingest
+	}
+
+	pub fn socket_url(&self) -> &str {
+		&self.socket
+	}
+
+	fn random(urls: &[Cow<'static, str>]) -> Cow<'static, str> {
+		urls[rand::random_range(0..urls.len())].clone()
+	}
+
+	pub(crate) fn random_gateway_url(&self) -> Cow<'static, str> {
+		Self::random(&self.gateway)
+	}
+
+	pub(crate) fn gateway_url(&self, path: &str) -> String {
+		format!("{}/{}", self.random_gateway_url(), path)
+	}
+
+	pub(crate) fn random_egest_url(&self) -> Cow<'static, str> {
+		Self::random(&self.egest)
+	}
+
+	pub(crate) fn random_ingest_url(&self) -> Cow<'static, str> {
+		Self::random(&self.ingest)
+	}
 }
 
-pub fn gateway_url(path: &str) -> String {
-	format!("{}/{}", random_gateway_url(), path)
+/// Socket connections must be TLS-encrypted, except to a server on this machine.
+pub(crate) fn is_allowed_socket_url(url: &str) -> bool {
+	is_secure_or_loopback_url(url, "wss://", "ws://")
 }
 
-#[cfg(all(target_family = "wasm", target_os = "unknown"))]
-pub fn random_egest_url() -> &'static str {
-	EGEST_URLS[rand::random_range(0..EGEST_URLS.len())]
+/// Requests carry credentials, so like socket connections they must be TLS-encrypted, except
+/// to a server on this machine.
+fn is_allowed_http_url(url: &str) -> bool {
+	is_secure_or_loopback_url(url, "https://", "http://")
 }
 
-#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
-pub fn random_egest_url() -> &'static str {
-	"https://egest.filen.io"
+fn is_secure_or_loopback_url(url: &str, secure_scheme: &str, plain_scheme: &str) -> bool {
+	if url.starts_with(secure_scheme) {
+		return true;
+	}
+	let Some(authority) = url
+		.strip_prefix(plain_scheme)
+		.and_then(|rest| rest.split(['/', '?']).next())
+	else {
+		return false;
+	};
+	let host = match authority.rsplit_once(':') {
+		Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+		_ => authority,
+	};
+	matches!(host, "localhost" | "127.0.0.1" | "[::1]")
 }
 
-#[cfg(all(target_family = "wasm", target_os = "unknown"))]
-pub fn random_ingest_url() -> &'static str {
-	INGEST_URLS[rand::random_range(0..INGEST_URLS.len())]
+fn insecure_url_error(kind: &str, url: &str) -> Error {
+	Error::custom(
+		ErrorKind::InvalidState,
+		format!("{kind} URL must use https:// unless it points to a loopback host: {url}"),
+	)
 }
 
-#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
-pub fn random_ingest_url() -> &'static str {
-	"https://ingest.filen.io"
+impl Default for ApiEndpoints {
+	fn default() -> Self {
+		fn defaults(urls: &[&'static str]) -> Vec<Cow<'static, str>> {
+			urls[..DEFAULT_URL_COUNT]
+				.iter()
+				.map(|url| Cow::Borrowed(*url))
+				.collect()
+		}
+		Self {
+			gateway: defaults(&GATEWAY_URLS),
+			egest: defaults(&EGEST_URLS),
+			ingest: defaults(&INGEST_URLS),
+			socket: Cow::Borrowed(SOCKET_URL),
+		}
+	}
 }
 
 pub const V2FILE_ENCRYPTION_VERSION: FileEncryptionVersion = FileEncryptionVersion::V2;
@@ -0,0 +1,290 @@
+//! Checkpoints that let an upload continue after the process that started it is gone.
+//!
+//! The server confirms an upload's chunks out of order, so a checkpoint records the run of
+//! confirmed chunks at the start of the file together with the Blake3 state over them, and the
+//! confirmed chunks past that run, which a resumed upload reads again but doesn't send.
+//!
+//! `blake3::Hasher` can't be saved, so the file hash is built differently: every chunk is hashed
+//! as its own BLAKE3 subtree, which works because chunks are a power of two of BLAKE3's chunk
+//! length, and the subtrees are merged on a stack the same way `blake3::Hasher` merges its
+//! chunks. The result is the same hash, from a state that is a few chaining values.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use blake3::hazmat::{
+	ChainingValue, HasherExt, Mode, merge_subtrees_non_root, merge_subtrees_root,
+};
+use filen_types::{auth::FileEncryptionVersion, crypto::Blake3Hash, fs::Uuid};
+use serde::{Deserialize, Serialize};
+
+use crate::{consts::CHUNK_SIZE_U64, crypto::file::FileKey, error::Error, util::MaybeSendSync};
+
+/// Where an upload's checkpoints are kept until it's resumed.
+pub trait UploadCheckpointStore: MaybeSendSync {
+	/// Called each time the server confirmed another chunk, with a checkpoint that replaces the
+	/// ones saved before it.
+	///
+	/// The upload carries on whether or not saving worked, so failures are the store's to report.
+	fn save(&self, checkpoint: &UploadCheckpoint);
+}
+
+/// Everything needed to continue an upload, see [`FileWriter::resume_from`].
+///
+/// It contains the file's key, so it has to be stored as carefully as the user's credentials.
+///
+/// [`FileWriter::resume_from`]: super::write::FileWriter::resume_from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadCheckpoint {
+	pub(crate) uuid: Uuid,
+	pub(crate) key: String,
+	pub(crate) key_version: FileEncryptionVersion,
+	pub(crate) upload_key: String,
+	/// Where the chunks went, once the first was confirmed
+	pub(crate) location: Option<(String, String)>,
+	pub(crate) hasher: ChunkHasher,
+	/// Confirmed chunks after the first unconfirmed one
+	pub(crate) confirmed: BTreeSet<u64>,
+}
+
+impl UploadCheckpoint {
+	/// The uuid of the file being uploaded.
+	pub fn uuid(&self) -> Uuid {
+		self.uuid
+	}
+
+	/// The byte offset the resumed upload's contents have to start at.
+	pub fn resume_offset(&self) -> u64 {
+		self.hasher.chunks * CHUNK_SIZE_U64
+	}
+
+	pub(crate) fn file_key(&self) -> Result<FileKey, Error> {
+		Ok(FileKey::from_str_with_version(&self.key, self.key_version)?)
+	}
+}
+
+/// A chunk's part of the file hash.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ChunkHash {
+	/// `None` for an empty chunk, which only an empty file has
+	subtree: Option<ChainingValue>,
+	/// The chunk's hash as a whole file, only computed for the first chunk
+	root: Option<[u8; 32]>,
+}
+
+impl ChunkHash {
+	pub(crate) fn new(chunk_idx: u64, data: &[u8]) -> Self {
+		let mut hasher = blake3::Hasher::new();
+		hasher.set_input_offset(chunk_idx * CHUNK_SIZE_U64);
+		hasher.update_rayon(data);
+		Self {
+			subtree: (!data.is_empty()).then(|| hasher.finalize_non_root()),
+			root: (chunk_idx == 0).then(|| *hasher.finalize().as_bytes()),
+		}
+	}
+}
+
+/// The Blake3 state over a file's first chunks.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ChunkHasher {
+	chunks: u64,
+	stack: Vec<Blake3Hash>,
+	/// The first chunk's hash, which is the file's if there's no other chunk
+	first_chunk: Option<Blake3Hash>,
+}
+
+impl ChunkHasher {
+	fn push(&mut self, hash: ChunkHash) {
+		if let Some(root) = hash.root {
+			self.first_chunk = Some(root.into());
+		}
+		if let Some(subtree) = hash.subtree {
+			// merge the subtrees that are complete now that another one follows them, but never
+			// the last one, which may have to be merged as the root
+			while self.stack.len() > self.chunks.count_ones() as usize {
+				let right = self.stack.pop().expect("stack has more than one entry");
+				let left = self.stack.pop().expect("stack has more than one entry");
+				self.stack.push(
+					merge_subtrees_non_root(left.as_ref(), right.as_ref(), Mode::Hash).into(),
+				);
+			}
+			self.stack.push(subtree.into());
+		}
+		self.chunks += 1;
+	}
+
+	fn finalize(&self) -> Blake3Hash {
+		if self.stack.len() < 2 {
+			return self.first_chunk.unwrap_or_else(|| blake3::hash(&[]).into());
+		}
+		let mut stack = self.stack.iter().rev();
+		let mut right = *stack
+			.next()
+			.expect("stack has more than one entry")
+			.as_ref();
+		let mut left = stack.next().expect("stack has more than one entry");
+		for next in stack {
+			right = merge_subtrees_non_root(left.as_ref(), &right, Mode::Hash);
+			left = next;
+		}
+		merge_subtrees_root(left.as_ref(), &right, Mode::Hash).into()
+	}
+}
+
+/// Which of an upload's chunks were hashed and confirmed, folding both into the file hash in
+/// order.
+#[derive(Debug, Default)]
+pub(crate) struct ChunkLedger {
+	/// The state over the chunks before the first unconfirmed one
+	hasher: ChunkHasher,
+	/// Confirmed chunks after the first unconfirmed one
+	confirmed: BTreeSet<u64>,
+	/// Hashes of chunks after the first unconfirmed one
+	hashed: BTreeMap<u64, ChunkHash>,
+}
+
+impl ChunkLedger {
+	pub(crate) fn from_checkpoint(checkpoint: &UploadCheckpoint) -> Self {
+		Self {
+			hasher: checkpoint.hasher.clone(),
+			confirmed: checkpoint.confirmed.clone(),
+			hashed: BTreeMap::new(),
+		}
+	}
+
+	/// The first chunk that isn't confirmed along with all before it
+	pub(crate) fn resume_chunk(&self) -> u64 {
+		self.hasher.chunks
+	}
+
+	pub(crate) fn is_confirmed(&self, chunk_idx: u64) -> bool {
+		chunk_idx < self.hasher.chunks || self.confirmed.contains(&chunk_idx)
+	}
+
+	pub(crate) fn hashed(&mut self, chunk_idx: u64, hash: ChunkHash) {
+		self.hashed.insert(chunk_idx, hash);
+		self.advance();
+	}
+
+	pub(crate) fn confirmed(&mut self, chunk_idx: u64) {
+		if chunk_idx >= self.hasher.chunks {
+			self.confirmed.insert(chunk_idx);
+			self.advance();
+		}
+	}
+
+	fn advance(&mut self) {
+		while self.confirmed.contains(&self.hasher.chunks)
+			&& let Some(hash) = self.hashed.remove(&self.hasher.chunks)
+		{
+			self.confirmed.remove(&self.hasher.chunks);
+			self.hasher.push(hash);
+		}
+	}
+
+	/// The file hash, once every chunk was hashed and confirmed.
+	pub(crate) fn finalize(&self) -> Blake3Hash {
+		debug_assert!(
+			self.hashed.is_empty() && self.confirmed.is_empty(),
+			"finalized with unconfirmed chunks"
+		);
+		self.hasher.finalize()
+	}
+
+	pub(crate) fn checkpoint(
+		&self,
+		uuid: Uuid,
+		key: &FileKey,
+		upload_key: &str,
+		location: Option<(String, String)>,
+	) -> UploadCheckpoint {
+		UploadCheckpoint {
+			uuid,
+			key: key.to_str().as_ref().to_owned(),
+			key_version: key.version(),
+			upload_key: upload_key.to_owned(),
+			location,
+			hasher: self.hasher.clone(),
+			confirmed: self.confirmed.clone(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn data(len: u64) -> Vec<u8> {
+		(0..len).map(|i| (i % 251) as u8).collect()
+	}
+
+	fn chunk_hashes(data: &[u8]) -> Vec<ChunkHash> {
+		if data.is_empty() {
+			return vec![ChunkHash::new(0, &[])];
+		}
+		data.chunks(CHUNK_SIZE_U64 as usize)
+			.enumerate()
+			.map(|(idx, chunk)| ChunkHash::new(idx as u64, chunk))
+			.collect()
+	}
+
+	#[test]
+	fn matches_blake3_across_chunk_boundaries() {
+		for len in [
+			0,
+			1,
+			1024,
+			CHUNK_SIZE_U64 - 1,
+			CHUNK_SIZE_U64,
+			CHUNK_SIZE_U64 + 1,
+			2 * CHUNK_SIZE_U64,
+			3 * CHUNK_SIZE_U64 + 5,
+			4 * CHUNK_SIZE_U64,
+			5 * CHUNK_SIZE_U64 + 1024,
+		] {
+			let data = data(len);
+			let mut hasher = ChunkHasher::default();
+			for hash in chunk_hashes(&data) {
+				hasher.push(hash);
+			}
+			assert_eq!(
+				hasher.finalize(),
+				Blake3Hash::from(blake3::hash(&data)),
+				"len {len}"
+			);
+		}
+	}
+
+	#[test]
+	fn out_of_order_confirmations_resume_from_the_confirmed_prefix() {
+		let data = data(6 * CHUNK_SIZE_U64 + 7);
+		let hashes = chunk_hashes(&data);
+
+		let mut ledger = ChunkLedger::default();
+		for (idx, hash) in hashes.iter().enumerate().take(5) {
+			ledger.hashed(idx as u64, *hash);
+		}
+		for idx in [1, 0, 3] {
+			ledger.confirmed(idx);
+		}
+		assert_eq!(ledger.resume_chunk(), 2);
+		assert!(ledger.is_confirmed(3));
+		assert!(!ledger.is_confirmed(4));
+
+		let key = FileKey::V3(crate::crypto::v3::EncryptionKey::new([7; 32]));
+		let checkpoint = ledger.checkpoint(Uuid::new_v4(), &key, "upload", None);
+		let checkpoint: UploadCheckpoint =
+			serde_json::from_str(&serde_json::to_string(&checkpoint).unwrap()).unwrap();
+		assert_eq!(checkpoint.resume_offset(), 2 * CHUNK_SIZE_U64);
+		assert_eq!(checkpoint.file_key().unwrap(), key);
+
+		let mut resumed = ChunkLedger::from_checkpoint(&checkpoint);
+		for (idx, hash) in hashes.iter().enumerate().skip(2) {
+			let idx = idx as u64;
+			resumed.hashed(idx, *hash);
+			if !resumed.is_confirmed(idx) {
+				resumed.confirmed(idx);
+			}
+		}
+		assert_eq!(resumed.finalize(), Blake3Hash::from(blake3::hash(&data)));
+	}
+}
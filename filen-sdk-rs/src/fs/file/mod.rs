@@ -26,6 +26,7 @@ use super::{HasMeta, HasName, HasParent, HasRemoteInfo, HasType, HasUUID};
 
 #[cfg(feature = "cache")]
 pub mod cache;
+pub mod checkpoint;
 pub(crate) mod chunk;
 pub mod client_impl;
 pub mod enums;
@@ -787,6 +788,24 @@ impl FileVersion {
 		self.stable_uuid
 	}
 
+	/// This version as a file in `file`'s place, e.g. to download its contents.
+	///
+	/// Nothing changes on the server, see [`Client::restore_file_version`] for that.
+	pub fn to_remote_file(&self, file: &RemoteFile) -> RemoteFile {
+		RemoteFile {
+			uuid: self.uuid,
+			stable_uuid: self.stable_uuid,
+			meta: self.metadata.clone(),
+			parent: file.parent,
+			size: self.size,
+			favorited: file.favorited,
+			region: self.region.clone(),
+			bucket: self.bucket.clone(),
+			timestamp: self.timestamp,
+			chunks: self.chunks,
+		}
+	}
+
 	pub(crate) fn blocking_from_response(
 		crypter: &impl MetaCrypter,
 		response: filen_types::api::v3::file::versions::FileVersion<'_>,
@@ -115,7 +115,7 @@ impl<'a> FileReaderBuilder<'a> {
 /// Remote metadata violating this would drive the chunk-size math out of range, so such
 /// readers refuse to read. A chunk count of zero is only consistent with a zero size (legacy
 /// empty files); with a nonzero size it would silently read as truncated-to-empty.
-fn chunks_consistent_with_size(chunks: u64, size: u64) -> bool {
+pub(crate) fn chunks_consistent_with_size(chunks: u64, size: u64) -> bool {
 	match chunks.checked_sub(1) {
 		None => size == 0,
 		Some(last_chunk_idx) => last_chunk_idx
@@ -10,7 +10,10 @@ use chrono::{DateTime, Utc};
 use filen_types::crypto::Blake3Hash;
 use futures::{AsyncWrite, FutureExt, StreamExt, stream::FuturesUnordered};
 
-use super::exif::ExifTeeState;
+use super::{
+	checkpoint::{ChunkHash, ChunkLedger, UploadCheckpoint, UploadCheckpointStore},
+	exif::ExifTeeState,
+};
 
 use crate::{
 	api,
@@ -64,7 +67,8 @@ where
 {
 	file: Arc<BaseFile>,
 	progress: Option<MaybeArc<ThrottledProgress<'a>>>,
-	futures: FuturesUnordered<MaybeSendBoxFuture<'a, Result<Chunk<'a>, Error>>>,
+	/// Uploads of chunks, yielding their index and their buffer for reuse
+	futures: FuturesUnordered<MaybeSendBoxFuture<'a, Result<(u64, Chunk<'a>), Error>>>,
 	alloc_future: Option<MaybeSendBoxFuture<'a, Chunk<'a>>>,
 	confirm_upload_callback: Option<F>,
 	// annoying that I have to split it up like this, but Cursor doesn't implement Write
@@ -74,7 +78,8 @@ where
 	size: Option<u64>,
 	next_chunk_idx: u64,
 	written: u64,
-	hasher: blake3::Hasher,
+	chunks: ChunkLedger,
+	checkpoint_store: Option<&'a dyn UploadCheckpointStore>,
 	remote_file_info: Arc<OnceLock<RemoteFileInfo>>,
 	upload_key: Arc<String>,
 	// Overrides file.created/file.last_modified at finalization time when Some.
@@ -95,7 +100,7 @@ where
 		self,
 	) -> Result<FileWriterWaitingForDriveLockState<'a>, Error> {
 		let lock_future = self.client.lock_drive();
-		let hash = Blake3Hash::from(self.hasher.finalize());
+		let hash = self.chunks.finalize();
 
 		let remote_file_info = match Arc::try_unwrap(self.remote_file_info) {
 			Ok(lock) => lock.into_inner().unwrap_or_default(),
@@ -161,7 +166,20 @@ where
 		let file = self.file.clone();
 		let upload_key = self.upload_key.clone();
 		let progress = self.progress.clone();
-		self.hasher.update_rayon(out_data.as_ref());
+		self.chunks
+			.hashed(chunk_idx, ChunkHash::new(chunk_idx, out_data.as_ref()));
+		if self.chunks.is_confirmed(chunk_idx) {
+			// sent before the upload was resumed, only read again for the hash
+			let len = out_data.as_ref().len() as u64;
+			self.futures.push(Box::pin(async move {
+				if let Some(progress) = progress {
+					progress.report(len);
+				}
+				AsMut::<Vec<u8>>::as_mut(&mut out_data).clear();
+				Ok((chunk_idx, out_data))
+			}));
+			return;
+		}
 		let remote_file_info = self.remote_file_info.clone();
 		self.futures.push(Box::pin(async move {
 			// encrypt the data
@@ -197,10 +215,49 @@ where
 			});
 			let mut chunk_bytes: Vec<u8> = chunk_bytes.into();
 			chunk_bytes.clear();
-			Ok(Chunk::from_parts(chunk_bytes, permit))
+			Ok((chunk_idx, Chunk::from_parts(chunk_bytes, permit)))
 		}));
 	}
 
+	/// Records that the server has chunk `chunk_idx`, saving a checkpoint if there's a store.
+	fn chunk_confirmed(&mut self, chunk_idx: u64) {
+		self.chunks.confirmed(chunk_idx);
+		if let Some(store) = self.checkpoint_store {
+			let location = self
+				.remote_file_info
+				.get()
+				.map(|info| (info.region.clone(), info.bucket.clone()));
+			store.save(&self.chunks.checkpoint(
+				self.file.uuid(),
+				self.file.key(),
+				&self.upload_key,
+				location,
+			));
+		}
+	}
+
+	/// Continues the upload `checkpoint` was saved from, see [`FileWriter::resume_from`].
+	fn resume_from(&mut self, checkpoint: UploadCheckpoint) -> Result<(), Error> {
+		debug_assert!(
+			self.next_chunk_idx == 0 && self.written == 0,
+			"resumed a writer that was written to"
+		);
+		let mut file = BaseFile::clone(&self.file);
+		file.root.uuid = checkpoint.uuid;
+		file.root.key = checkpoint.file_key()?;
+		self.file = Arc::new(file);
+		self.chunks = ChunkLedger::from_checkpoint(&checkpoint);
+		self.next_chunk_idx = self.chunks.resume_chunk();
+		self.written = checkpoint.resume_offset();
+		if let Some((region, bucket)) = checkpoint.location {
+			let _ = self.remote_file_info.set(RemoteFileInfo { region, bucket });
+		}
+		self.upload_key = Arc::new(checkpoint.upload_key);
+		// EXIF data is at the start of the file, which isn't written again
+		self.exif_tee = None;
+		Ok(())
+	}
+
 	pub fn write_next_chunk(&mut self, buf: &[u8]) -> std::io::Result<usize> {
 		// A reader that yields more than the caller's declared size would otherwise stall on a
 		// zero-capacity final chunk and fail with an opaque WriteZero, orphaning the already
@@ -303,7 +360,8 @@ where
 
 			loop {
 				match self.futures.poll_next_unpin(cx) {
-					std::task::Poll::Ready(Some(Ok(chunk))) => {
+					std::task::Poll::Ready(Some(Ok((chunk_idx, chunk)))) => {
+						self.chunk_confirmed(chunk_idx);
 						if self.next_chunk_size().is_some() {
 							self.allocated_chunks.push(chunk);
 						} else {
@@ -363,7 +421,7 @@ where
 		// First, drain all in-flight chunk uploads.
 		loop {
 			match self.futures.poll_next_unpin(cx) {
-				std::task::Poll::Ready(Some(Ok(_))) => {}
+				std::task::Poll::Ready(Some(Ok((chunk_idx, _)))) => self.chunk_confirmed(chunk_idx),
 				std::task::Poll::Ready(Some(Err(e))) => {
 					return std::task::Poll::Ready(Err(std::io::Error::other(e.to_string())));
 				}
@@ -663,7 +721,8 @@ where
 			next_chunk_idx: 0,
 			written: 0,
 			confirm_upload_callback,
-			hasher: blake3::Hasher::new(),
+			chunks: ChunkLedger::default(),
+			checkpoint_store: None,
 			remote_file_info: Arc::new(OnceLock::new()),
 			upload_key: Arc::new(crypto::shared::generate_random_base64_values(
 				32,
@@ -718,6 +777,33 @@ where
 		}
 	}
 
+	/// Saves a checkpoint to `store` each time the server confirms a chunk, from which
+	/// [`resume_from`](Self::resume_from) can continue the upload, even in another process.
+	///
+	/// Has to be called before anything is written.
+	pub fn with_checkpoint_store(mut self, store: &'a dyn UploadCheckpointStore) -> Self {
+		if let FileWriterState::Uploading(uploading) = &mut self.state {
+			uploading.checkpoint_store = Some(store);
+		}
+		self
+	}
+
+	/// Continues the upload `checkpoint` was saved from instead of starting a new one.
+	///
+	/// The file's contents have to be written starting at
+	/// [`UploadCheckpoint::resume_offset`]. Chunks the server already has are read again for the
+	/// file hash, but not sent. The checkpoint's uuid and key replace the ones the writer was
+	/// built with, and EXIF data isn't parsed, since it's in the part that isn't written again.
+	///
+	/// Has to be called before anything is written. The server discards uploads that aren't
+	/// completed for a while, after which the checkpoint is useless and the upload fails.
+	pub fn resume_from(mut self, checkpoint: UploadCheckpoint) -> Result<Self, Error> {
+		if let FileWriterState::Uploading(uploading) = &mut self.state {
+			uploading.resume_from(checkpoint)?;
+		}
+		Ok(self)
+	}
+
 	pub fn into_remote_file(self) -> Option<RemoteFile> {
 		match self.state {
 			FileWriterState::Complete(complete) => Some(complete.file),
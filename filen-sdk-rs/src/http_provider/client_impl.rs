@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use crate::{Error, auth::shared_client::SharedClient};
 
-use super::HttpProviderHandle;
+use super::{HttpProviderHandle, TokenStorage};
 
 #[allow(private_bounds)]
 #[allow(async_fn_in_trait)]
@@ -11,20 +11,42 @@ pub trait HttpProviderSharedClientExt: SharedClient {
 		&self,
 		port: Option<u16>,
 	) -> Result<Arc<HttpProviderHandle>, Error> {
-		let unauth_client = self.get_unauth_client();
-		// ideally this wouldn't clone the client and we would get an Arc<Self> from the SharedClient
-		let client = Arc::new(unauth_client.clone());
-
-		let mut guard = unauth_client.http_provider.lock().await;
-		if let Some(handle) = guard.upgrade() {
-			return Ok(handle);
-		}
-
-		let handle = Arc::new(HttpProviderHandle::new(port, client).await?);
+		start(self, port, None).await
+	}
 
-		*guard = Arc::downgrade(&handle);
-		Ok(handle)
+	/// Like [`start_http_provider`](Self::start_http_provider), but keeps URL tokens in `storage`
+	/// so URLs minted before a restart keep working, and doesn't bound how many stay live.
+	///
+	/// The port is required because restored URLs only work if the provider comes back on it.
+	/// If a provider is already running it is returned as-is, with whatever port and storage it
+	/// was started with.
+	async fn start_http_provider_with_storage(
+		&self,
+		port: u16,
+		storage: TokenStorage,
+	) -> Result<Arc<HttpProviderHandle>, Error> {
+		start(self, Some(port), Some(storage)).await
 	}
 }
 
 impl<T: SharedClient> HttpProviderSharedClientExt for T {}
+
+async fn start<C: SharedClient + ?Sized>(
+	shared_client: &C,
+	port: Option<u16>,
+	storage: Option<TokenStorage>,
+) -> Result<Arc<HttpProviderHandle>, Error> {
+	let unauth_client = shared_client.get_unauth_client();
+	// ideally this wouldn't clone the client and we would get an Arc<Self> from the SharedClient
+	let client = Arc::new(unauth_client.clone());
+
+	let mut guard = unauth_client.http_provider.lock().await;
+	if let Some(handle) = guard.upgrade() {
+		return Ok(handle);
+	}
+
+	let handle = Arc::new(HttpProviderHandle::new(port, client, storage).await?);
+
+	*guard = Arc::downgrade(&handle);
+	Ok(handle)
+}
@@ -0,0 +1,359 @@
+//! Directory routes: `/dir/{token}/` lists a directory, `/dir/{token}/{path}` serves its
+//! children by relative path.
+//!
+//! The token rides in the path so that relative references in served files (HLS playlists,
+//! `.cue` sheets, sibling subtitles) resolve against the directory URL, which a query token
+//! would not survive.
+
+use std::{
+	collections::HashMap,
+	fmt::Write,
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+
+use axum::{
+	Json, Router,
+	extract::{Path, Query, State},
+	response::{Html, IntoResponse, Redirect, Response},
+	routing::get,
+};
+use axum_extra::{TypedHeader, headers::Range};
+use chrono::{DateTime, Utc};
+use filen_types::fs::{ParentUuid, Uuid};
+use http::{HeaderMap, StatusCode, header::ACCEPT};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	auth::Client,
+	fs::{
+		HasName, HasUUID, categories::normal::list_parent_uuid, dir::RemoteDirectory,
+		file::RemoteFile,
+	},
+	io::HasFileInfo,
+};
+
+use super::{ProviderState, Served, serve_file};
+
+/// How long a directory listing is reused before it is fetched again. A player walking an HLS
+/// playlist requests a segment every few seconds, and each request resolves its path through
+/// the listings, so without this every segment would cost one listing call per path segment.
+const LISTING_TTL: Duration = Duration::from_secs(10);
+/// Upper bound on cached listings; expired entries are dropped first, then the cache is cleared.
+const MAX_CACHED_LISTINGS: usize = 256;
+
+pub(super) type SharedListingCache = Arc<Mutex<HashMap<Uuid, (Instant, Arc<Listing>)>>>;
+
+pub(super) struct Listing {
+	pub(super) dirs: Vec<RemoteDirectory>,
+	pub(super) files: Vec<RemoteFile>,
+}
+
+impl Listing {
+	fn dir(&self, name: &str) -> Option<&RemoteDirectory> {
+		self.dirs.iter().find(|dir| dir.name() == Some(name))
+	}
+
+	fn file(&self, name: &str) -> Option<&RemoteFile> {
+		self.files
+			.iter()
+			.find(|file| HasName::name(*file) == Some(name))
+	}
+}
+
+pub(super) fn routes() -> Router<ProviderState> {
+	Router::new()
+		.route("/dir/{token}", get(redirect_to_root))
+		.route("/dir/{token}/", get(root_handler))
+		.route("/dir/{token}/{*path}", get(child_handler))
+}
+
+#[derive(Deserialize)]
+struct DirQuery {
+	/// `json` or `html`; without it the `Accept` header decides.
+	#[serde(default)]
+	format: Option<String>,
+	/// Read-ahead window for a served file, like `/file?buffer=`.
+	#[serde(default)]
+	buffer: Option<u64>,
+}
+
+impl DirQuery {
+	fn wants_json(&self, headers: &HeaderMap) -> bool {
+		match self.format.as_deref() {
+			Some(format) => format.eq_ignore_ascii_case("json"),
+			None => headers
+				.get(ACCEPT)
+				.and_then(|value| value.to_str().ok())
+				.is_some_and(|accept| accept.contains("application/json")),
+		}
+	}
+}
+
+/// Without the trailing slash, relative links in the listing would resolve against `/dir/`.
+async fn redirect_to_root(Path(token): Path<String>) -> Redirect {
+	Redirect::permanent(&format!("/dir/{token}/"))
+}
+
+async fn root_handler(
+	Path(token): Path<String>,
+	Query(query): Query<DirQuery>,
+	State(state): State<ProviderState>,
+	headers: HeaderMap,
+	range: Option<TypedHeader<Range>>,
+) -> Response {
+	handle(state, &token, "", query, headers, range).await
+}
+
+async fn child_handler(
+	Path((token, path)): Path<(String, String)>,
+	Query(query): Query<DirQuery>,
+	State(state): State<ProviderState>,
+	headers: HeaderMap,
+	range: Option<TypedHeader<Range>>,
+) -> Response {
+	handle(state, &token, &path, query, headers, range).await
+}
+
+async fn handle(
+	state: ProviderState,
+	token: &str,
+	path: &str,
+	query: DirQuery,
+	headers: HeaderMap,
+	range: Option<TypedHeader<Range>>,
+) -> Response {
+	let Some(Served::Dir {
+		user_id,
+		uuid,
+		name,
+	}) = state
+		.tokens
+		.lock()
+		.expect("http-provider token store lock poisoned")
+		.resolve(token)
+	else {
+		return StatusCode::NOT_FOUND.into_response();
+	};
+	let Some(client) = state
+		.clients
+		.lock()
+		.expect("http-provider client registry lock poisoned")
+		.get(&user_id)
+		.cloned()
+	else {
+		tracing::warn!("http provider has no client registered for user {user_id}");
+		return StatusCode::SERVICE_UNAVAILABLE.into_response();
+	};
+
+	// Everything but the last segment must be a directory. The last one is empty for a path
+	// ending in a slash, which lists the directory reached so far.
+	let mut segments = path.split('/').collect::<Vec<_>>();
+	let last = segments.pop().unwrap_or_default();
+	let (mut current, mut current_name) = (uuid, name);
+	for segment in segments {
+		let listing = match list(&state, &client, current).await {
+			Ok(listing) => listing,
+			Err(response) => return response,
+		};
+		let Some(child) = listing.dir(segment) else {
+			return StatusCode::NOT_FOUND.into_response();
+		};
+		current = child.uuid();
+		current_name = segment.to_owned();
+	}
+
+	let listing = match list(&state, &client, current).await {
+		Ok(listing) => listing,
+		Err(response) => return response,
+	};
+	if last.is_empty() {
+		return if query.wants_json(&headers) {
+			Json(listing_json(&current_name, &listing)).into_response()
+		} else {
+			Html(listing_html(&current_name, !path.is_empty(), &listing)).into_response()
+		};
+	}
+	if let Some(file) = listing.file(last) {
+		return serve_file(
+			&state.client,
+			file.clone().into(),
+			query.buffer,
+			&headers,
+			range,
+		);
+	}
+	if listing.dir(last).is_some() {
+		return Redirect::permanent(&format!("{}/", urlencoding::encode(last))).into_response();
+	}
+	StatusCode::NOT_FOUND.into_response()
+}
+
+/// Lists the directory `uuid`, reusing a listing fetched less than [`LISTING_TTL`] ago.
+async fn list(
+	state: &ProviderState,
+	client: &Client,
+	uuid: Uuid,
+) -> Result<Arc<Listing>, Response> {
+	if let Some((fetched, listing)) = state
+		.listings
+		.lock()
+		.expect("http-provider listing cache lock poisoned")
+		.get(&uuid)
+		&& fetched.elapsed() < LISTING_TTL
+	{
+		return Ok(listing.clone());
+	}
+
+	let (dirs, files) = list_parent_uuid(
+		client,
+		ParentUuid::Uuid(uuid),
+		None::<&fn(u64, Option<u64>)>,
+	)
+	.await
+	.map_err(|e| {
+		tracing::error!("http provider failed to list directory {uuid}: {e}");
+		StatusCode::INTERNAL_SERVER_ERROR.into_response()
+	})?;
+	let listing = Arc::new(Listing { dirs, files });
+
+	let mut listings = state
+		.listings
+		.lock()
+		.expect("http-provider listing cache lock poisoned");
+	if listings.len() >= MAX_CACHED_LISTINGS {
+		listings.retain(|_, (fetched, _)| fetched.elapsed() < LISTING_TTL);
+		if listings.len() >= MAX_CACHED_LISTINGS {
+			listings.clear();
+		}
+	}
+	listings.insert(uuid, (Instant::now(), listing.clone()));
+	Ok(listing)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ListingJson<'a> {
+	name: &'a str,
+	dirs: Vec<DirJson<'a>>,
+	files: Vec<FileJson<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DirJson<'a> {
+	name: &'a str,
+	/// Relative to the listing's URL
+	href: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileJson<'a> {
+	name: &'a str,
+	/// Relative to the listing's URL
+	href: String,
+	size: u64,
+	mime: Option<&'a str>,
+	last_modified: Option<DateTime<Utc>>,
+}
+
+/// Items whose metadata could not be decrypted have no name, so they can't be addressed by
+/// path and are left out of listings.
+fn named_entries(listing: &Listing) -> (Vec<(&str, String)>, Vec<(&RemoteFile, &str, String)>) {
+	let mut dirs = listing
+		.dirs
+		.iter()
+		.filter_map(|dir| dir.name())
+		.map(|name| (name, format!("{}/", urlencoding::encode(name))))
+		.collect::<Vec<_>>();
+	dirs.sort_unstable_by(|a, b| a.0.cmp(b.0));
+	let mut files = listing
+		.files
+		.iter()
+		.filter_map(|file| HasName::name(file).map(|name| (file, name)))
+		.map(|(file, name)| (file, name, urlencoding::encode(name).into_owned()))
+		.collect::<Vec<_>>();
+	files.sort_unstable_by(|a, b| a.1.cmp(b.1));
+	(dirs, files)
+}
+
+fn listing_json<'a>(name: &'a str, listing: &'a Listing) -> ListingJson<'a> {
+	let (dirs, files) = named_entries(listing);
+	ListingJson {
+		name,
+		dirs: dirs
+			.into_iter()
+			.map(|(name, href)| DirJson { name, href })
+			.collect(),
+		files: files
+			.into_iter()
+			.map(|(file, name, href)| FileJson {
+				name,
+				href,
+				size: file.size(),
+				mime: file.mime(),
+				last_modified: file.last_modified(),
+			})
+			.collect(),
+	}
+}
+
+pub(super) fn listing_html(name: &str, has_parent: bool, listing: &Listing) -> String {
+	let (dirs, files) = named_entries(listing);
+	let title = escape_html(name);
+	let mut html = format!(
+		"<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{title}</title></head>\n<body>\n<h1>{title}</h1>\n<ul>\n"
+	);
+	if has_parent {
+		html.push_str("<li><a href=\"../\">../</a></li>\n");
+	}
+	for (name, href) in dirs {
+		let _ = writeln!(
+			html,
+			"<li><a href=\"{}\">{}/</a></li>",
+			escape_html(&href),
+			escape_html(name)
+		);
+	}
+	for (file, name, href) in files {
+		let _ = writeln!(
+			html,
+			"<li><a href=\"{}\">{}</a> ({} bytes)</li>",
+			escape_html(&href),
+			escape_html(name),
+			file.size()
+		);
+	}
+	html.push_str("</ul>\n</body>\n</html>\n");
+	html
+}
+
+pub(super) fn escape_html(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+	for c in text.chars() {
+		match c {
+			'&' => escaped.push_str("&amp;"),
+			'<' => escaped.push_str("&lt;"),
+			'>' => escaped.push_str("&gt;"),
+			'"' => escaped.push_str("&quot;"),
+			'\'' => escaped.push_str("&#39;"),
+			c => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+#[cfg(test)]
+mod tests {
+	use super::escape_html;
+
+	#[test]
+	fn escape_html_escapes_markup_and_quotes() {
+		assert_eq!(
+			escape_html(r#"<a href="x">Tom & Jerry's</a>"#),
+			"&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+		);
+		assert_eq!(escape_html("plain name.m3u8"), "plain name.m3u8");
+	}
+}
@@ -3,7 +3,8 @@ use std::sync::Arc;
 use crate::{
 	Error,
 	auth::{JsClient, js_impls::UnauthJsClient},
-	http_provider::{HttpProviderHandle, client_impl::HttpProviderSharedClientExt},
+	crypto::v3::EncryptionKey,
+	http_provider::{HttpProviderHandle, TokenStorage, client_impl::HttpProviderSharedClientExt},
 	runtime::do_on_commander,
 };
 
@@ -22,6 +23,26 @@ impl JsClient {
 		let this = self.inner();
 		do_on_commander(move || async move { this.start_http_provider(port).await }).await
 	}
+
+	/// Like `startHttpProvider`, but keeps URL tokens in `storageDir`, encrypted with
+	/// `storageKey`, so URLs minted before a restart keep working.
+	///
+	/// The port is required because restored URLs only work if the provider comes back on it.
+	/// If a provider is already running it is returned as-is, with whatever port and storage it
+	/// was started with.
+	async fn start_http_provider_with_storage(
+		&self,
+		port: u16,
+		storage_dir: String,
+		storage_key: EncryptionKey,
+	) -> Result<Arc<HttpProviderHandle>, Error> {
+		let this = self.inner();
+		let storage = TokenStorage::new(storage_dir, storage_key);
+		do_on_commander(move || async move {
+			this.start_http_provider_with_storage(port, storage).await
+		})
+		.await
+	}
 }
 
 #[cfg_attr(feature = "uniffi", uniffi::export)]
@@ -39,4 +60,24 @@ impl UnauthJsClient {
 		let this = self.inner();
 		do_on_commander(move || async move { this.start_http_provider(port).await }).await
 	}
+
+	/// Like `startHttpProvider`, but keeps URL tokens in `storageDir`, encrypted with
+	/// `storageKey`, so URLs minted before a restart keep working.
+	///
+	/// The port is required because restored URLs only work if the provider comes back on it.
+	/// If a provider is already running it is returned as-is, with whatever port and storage it
+	/// was started with.
+	async fn start_http_provider_with_storage(
+		&self,
+		port: u16,
+		storage_dir: String,
+		storage_key: EncryptionKey,
+	) -> Result<Arc<HttpProviderHandle>, Error> {
+		let this = self.inner();
+		let storage = TokenStorage::new(storage_dir, storage_key);
+		do_on_commander(move || async move {
+			this.start_http_provider_with_storage(port, storage).await
+		})
+		.await
+	}
 }
@@ -25,13 +25,18 @@ use http::{
 use serde::Deserialize;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
-use filen_types::traits::CowHelpers;
+use chrono::{DateTime, Utc};
+use filen_types::{fs::Uuid, traits::CowHelpers};
 
 use crate::{
 	Error,
-	auth::unauth::UnauthClient,
+	auth::{Client, unauth::UnauthClient},
 	consts::{CHUNK_SIZE_U64, FILE_CHUNK_SIZE_EXTRA},
-	fs::file::{enums::RemoteFileType, read::FileReaderBuilder},
+	fs::{
+		HasName, HasUUID,
+		dir::RemoteDirectory,
+		file::{enums::RemoteFileType, read::FileReaderBuilder},
+	},
 	io::HasFileInfo,
 };
 
@@ -228,34 +233,67 @@ impl axum::serve::Listener for TimeoutListener {
 }
 
 pub mod client_impl;
+mod dir;
 #[cfg(feature = "uniffi")]
 mod js_impl;
+mod persist;
+pub mod s3;
+mod server;
+pub mod webdav;
 
-/// Upper bound on live URL tokens. With deduplication this is only reachable by
-/// browsing that many *distinct* files in one provider lifetime; at a few hundred
-/// bytes per entry the store tops out well under 1 MiB. Eviction is LRU and
+pub use persist::TokenStorage;
+pub use server::DriveServer;
+
+/// Upper bound on live URL tokens when they aren't persisted. With deduplication this is only
+/// reachable by browsing that many *distinct* files and directories in one provider lifetime;
+/// at a few hundred bytes per entry the store tops out well under 1 MiB. Eviction is LRU and
 /// [`TokenStore::resolve`] counts as use, so an actively streaming URL stays warm.
 const MAX_URL_TOKENS: usize = 1024;
 
-/// The raw random token minted per served file; rides in the URL base64url-encoded.
+/// The raw random token minted per served file or directory; rides in the URL base64url-encoded.
 type UrlToken = [u8; 32];
 
-/// Maps opaque URL tokens to the files they serve. The file metadata (including
-/// its decryption key) lives here in-process instead of in the URL, so no key
-/// material reaches media-framework/OS request logs.
+/// What a URL token serves: a single file, or a directory whose children are served by
+/// relative path (see [`dir`]).
+#[derive(Clone, PartialEq)]
+enum Served {
+	File(RemoteFileType<'static>),
+	/// Listing a directory needs an authenticated client, unlike streaming a file. The entry only
+	/// names the account, so it can be persisted; the client comes from the handle's registry.
+	Dir {
+		user_id: u64,
+		uuid: Uuid,
+		name: String,
+	},
+}
+
+#[derive(Clone)]
+struct TokenEntry {
+	served: Served,
+	/// After this the token resolves to nothing and is dropped.
+	expires_at: Option<DateTime<Utc>>,
+}
+
+impl TokenEntry {
+	fn is_expired(&self, now: DateTime<Utc>) -> bool {
+		self.expires_at.is_some_and(|at| at <= now)
+	}
+}
+
+/// Maps opaque URL tokens to the files and directories they serve. The metadata (including
+/// decryption keys) lives here instead of in the URL, so no key material reaches
+/// media-framework/OS request logs.
 ///
-/// The store is bounded: minting a URL for a file that already has a live token
-/// reuses it (the app re-derives URLs on every preview mount, so this is the hot
-/// path), and once [`MAX_URL_TOKENS`] distinct files are live the least recently
-/// used entry is evicted. Long term this should move to a file-backed store in an
-/// app-provided private directory — that removes the eviction bound entirely and
-/// lets tokens survive a provider restart — but it needs a writable-directory
-/// handoff from the host apps first, and today they treat every URL as scoped to
-/// one foreground session anyway.
+/// Minting a URL for an item that already has a live token reuses it (the app re-derives URLs
+/// on every preview mount, so this is the hot path). Without [`TokenStorage`] the store is
+/// in-memory and bounded: once [`MAX_URL_TOKENS`] distinct items are live the least recently
+/// used entry is evicted. With it, every entry is also written to disk, survives a provider
+/// restart and is only dropped when it expires or is revoked.
 struct TokenStore {
-	entries: HashMap<UrlToken, RemoteFileType<'static>>,
+	entries: HashMap<UrlToken, TokenEntry>,
 	/// LRU order over `entries`' keys; front is the eviction candidate.
 	order: VecDeque<UrlToken>,
+	storage: Option<TokenStorage>,
 }
 
 impl TokenStore {
@@ -263,45 +301,163 @@ impl TokenStore {
 		Self {
 			entries: HashMap::new(),
 			order: VecDeque::new(),
+			storage: None,
 		}
 	}
 
+	/// Restores the tokens kept in `storage` and persists every later change to it.
+	fn with_storage(storage: TokenStorage) -> Result<Self, Error> {
+		let entries = storage.load()?.into_iter().collect::<HashMap<_, _>>();
+		Ok(Self {
+			order: entries.keys().copied().collect(),
+			entries,
+			storage: Some(storage),
+		})
+	}
+
 	/// Returns the live token for `file`, minting (and if needed evicting) one if
 	/// no equal file is currently served. Takes `file` by value so an already-owned
 	/// file is stored as-is instead of cloned.
 	fn token_for(&mut self, file: RemoteFileType<'_>) -> UrlToken {
-		let existing = self
-			.entries
-			.iter()
-			.find_map(|(token, stored)| (stored == &file).then_some(*token));
-		if let Some(token) = existing {
-			self.mark_used(token);
+		if let Some(token) =
+			self.find_live(|served| matches!(served, Served::File(stored) if *stored == file))
+		{
+			return token;
+		}
+		self.insert(Served::File(file.into_owned_cow()))
+	}
+
+	/// Like [`token_for`](Self::token_for), for a directory of `user_id`'s drive.
+	fn token_for_dir(&mut self, user_id: u64, dir: &RemoteDirectory) -> UrlToken {
+		let served = Served::Dir {
+			user_id,
+			uuid: dir.uuid(),
+			name: dir.name().unwrap_or_default().to_owned(),
+		};
+		if let Some(token) = self.find_live(|stored| *stored == served) {
 			return token;
 		}
+		self.insert(served)
+	}
+
+	fn find_live(&mut self, matches: impl Fn(&Served) -> bool) -> Option<UrlToken> {
+		let now = Utc::now();
+		let token = self.entries.iter().find_map(|(token, entry)| {
+			(!entry.is_expired(now) && matches(&entry.served)).then_some(*token)
+		})?;
+		self.mark_used(token);
+		Some(token)
+	}
 
-		if self.entries.len() >= MAX_URL_TOKENS
+	fn insert(&mut self, served: Served) -> UrlToken {
+		self.remove_expired();
+		if self.storage.is_none()
+			&& self.entries.len() >= MAX_URL_TOKENS
 			&& let Some(oldest) = self.order.pop_front()
 		{
 			self.entries.remove(&oldest);
 		}
 
 		let token: UrlToken = rand::random();
-		self.entries.insert(token, file.into_owned_cow());
+		let entry = TokenEntry {
+			served,
+			expires_at: None,
+		};
+		if let Some(storage) = &self.storage {
+			storage.save(&token, &entry);
+		}
+		self.entries.insert(token, entry);
 		self.order.push_back(token);
 		token
 	}
 
-	/// Resolves a URL's `?token=` value to the file it serves, refreshing its LRU
-	/// slot. Malformed, wrong-length, and unknown tokens are all `None` (a 404).
-	fn resolve(&mut self, token_param: &str) -> Option<RemoteFileType<'static>> {
-		let token: UrlToken = BASE64_URL_SAFE_NO_PAD
+	fn decode(token_param: &str) -> Option<UrlToken> {
+		BASE64_URL_SAFE_NO_PAD
 			.decode(token_param)
 			.ok()?
 			.try_into()
-			.ok()?;
-		let file = self.entries.get(&token)?.clone();
+			.ok()
+	}
+
+	/// Resolves a URL's token to what it serves, refreshing its LRU slot. Malformed,
+	/// wrong-length, unknown and expired tokens are all `None` (a 404).
+	fn resolve(&mut self, token_param: &str) -> Option<Served> {
+		let token = Self::decode(token_param)?;
+		let entry = self.entries.get(&token)?;
+		if entry.is_expired(Utc::now()) {
+			self.remove(&token);
+			return None;
+		}
+		let served = entry.served.clone();
 		self.mark_used(token);
-		Some(file)
+		Some(served)
+	}
+
+	/// Resolves a token minted for a file; directory tokens are `None` here.
+	fn resolve_file(&mut self, token_param: &str) -> Option<RemoteFileType<'static>> {
+		match self.resolve(token_param)? {
+			Served::File(file) => Some(file),
+			Served::Dir { .. } => None,
+		}
+	}
+
+	/// Sets or clears when a live token expires. `false` if the token isn't live.
+	fn set_expiry(&mut self, token_param: &str, expires_at: Option<DateTime<Utc>>) -> bool {
+		let Some(token) = Self::decode(token_param) else {
+			return false;
+		};
+		let Some(entry) = self
+			.entries
+			.get_mut(&token)
+			.filter(|entry| !entry.is_expired(Utc::now()))
+		else {
+			return false;
+		};
+		entry.expires_at = expires_at;
+		// expired entries, this one too if it expired already, are pruned before saving
+		self.remove_expired();
+		if let (Some(storage), Some(entry)) = (&self.storage, self.entries.get(&token)) {
+			storage.save(&token, entry);
+		}
+		true
+	}
+
+	/// Drops a token. `false` if it wasn't live.
+	fn revoke(&mut self, token_param: &str) -> bool {
+		Self::decode(token_param).is_some_and(|token| self.remove(&token))
+	}
+
+	fn revoke_all(&mut self) {
+		for token in self.order.drain(..) {
+			if let Some(storage) = &self.storage {
+				storage.remove(&token);
+			}
+		}
+		self.entries.clear();
+	}
+
+	fn remove_expired(&mut self) {
+		let now = Utc::now();
+		let expired = self
+			.entries
+			.iter()
+			.filter(|(_, entry)| entry.is_expired(now))
+			.map(|(token, _)| *token)
+			.collect::<Vec<_>>();
+		for token in expired {
+			self.remove(&token);
+		}
+	}
+
+	fn remove(&mut self, token: &UrlToken) -> bool {
+		let Some(entry) = self.entries.remove(token) else {
+			return false;
+		};
+		self.order.retain(|t| t != token);
+		if let Some(storage) = &self.storage {
+			storage.remove(token);
+		}
+		!entry.is_expired(Utc::now())
 	}
 
 	fn mark_used(&mut self, token: UrlToken) {
@@ -314,12 +470,30 @@ impl TokenStore {
 
 type SharedTokenStore = Arc<Mutex<TokenStore>>;
 
+/// The authenticated clients directory URLs are listed with, by user id
+type SharedClients = Arc<Mutex<HashMap<u64, Arc<Client>>>>;
+
+/// Extracts the token from a URL minted by [`HttpProviderHandle`]: the `token` query parameter of
+/// a file URL or the path segment after `/dir/` of a directory URL.
+fn token_param(url: &str) -> Option<String> {
+	let url = url::Url::parse(url).ok()?;
+	let mut segments = url.path_segments()?;
+	match segments.next()? {
+		"file" => url
+			.query_pairs()
+			.find_map(|(key, value)| (key == "token").then(|| value.into_owned())),
+		"dir" => segments.next().map(str::to_owned),
+		_ => None,
+	}
+}
+
 #[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
 pub struct HttpProviderHandle {
 	task: Option<tokio::task::JoinHandle<()>>,
 	cancel_sender: Option<tokio::sync::oneshot::Sender<()>>,
 	port: u16,
 	tokens: SharedTokenStore,
+	clients: SharedClients,
 }
 
 impl Drop for HttpProviderHandle {
@@ -356,13 +530,33 @@ impl Drop for HttpProviderHandle {
 }
 
 impl HttpProviderHandle {
-	pub(crate) async fn new(port: Option<u16>, client: Arc<UnauthClient>) -> Result<Self, Error> {
-		let tokens: SharedTokenStore = Arc::new(Mutex::new(TokenStore::new()));
+	pub(crate) async fn new(
+		port: Option<u16>,
+		client: Arc<UnauthClient>,
+		storage: Option<TokenStorage>,
+	) -> Result<Self, Error> {
+		let tokens = match storage {
+			// loading reads and decrypts every stored entry
+			Some(storage) => tokio::task::spawn_blocking(|| TokenStore::with_storage(storage))
+				.await
+				.map_err(|e| {
+					Error::custom(
+						crate::ErrorKind::Internal,
+						format!("loading http provider tokens failed: {e}"),
+					)
+				})??,
+			None => TokenStore::new(),
+		};
+		let tokens: SharedTokenStore = Arc::new(Mutex::new(tokens));
+		let clients = SharedClients::default();
 		let router = axum::Router::new()
 			.route("/file", get(file_handler))
+			.merge(dir::routes())
 			.with_state(ProviderState {
 				client,
 				tokens: tokens.clone(),
+				clients: clients.clone(),
+				listings: Arc::default(),
 			});
 
 		let (port_sender, port_receiver) = tokio::sync::oneshot::channel();
@@ -400,8 +594,15 @@ impl HttpProviderHandle {
 			cancel_sender: Some(cancel_sender),
 			port,
 			tokens,
+			clients,
 		})
 	}
+
+	fn tokens(&self) -> std::sync::MutexGuard<'_, TokenStore> {
+		self.tokens
+			.lock()
+			.expect("http-provider token store lock poisoned")
+	}
 }
 
 #[cfg_attr(feature = "uniffi", uniffi::export)]
@@ -409,21 +610,36 @@ impl HttpProviderHandle {
 	pub fn port(&self) -> u16 {
 		self.port
 	}
+
+	/// Sets when the token behind `url` stops working, or with `None` lets it live until
+	/// revoked. Returns `false` if `url` isn't a live URL of this provider.
+	///
+	/// Minting a URL for an item that already has one returns the same URL, so the expiry
+	/// applies to every holder of it.
+	pub fn set_url_expiry(&self, url: &str, expires_at: Option<DateTime<Utc>>) -> bool {
+		token_param(url).is_some_and(|token| self.tokens().set_expiry(&token, expires_at))
+	}
+
+	/// Makes `url` stop working. Returns `false` if it wasn't a live URL of this provider.
+	pub fn revoke_url(&self, url: &str) -> bool {
+		token_param(url).is_some_and(|token| self.tokens().revoke(&token))
+	}
+
+	/// Makes every URL minted by this provider stop working, including persisted ones.
+	pub fn revoke_all_urls(&self) {
+		self.tokens().revoke_all();
+	}
 }
 
 impl HttpProviderHandle {
 	/// Returns the local HTTP URL that serves the given file through this provider.
 	///
 	/// The URL carries only an opaque random token; the file metadata (including
-	/// its decryption key) is stored in this provider's in-process store and looked
+	/// its decryption key) is stored in this provider's token store and looked
 	/// up by the handler, so no key material rides in the URL where a media
 	/// framework or the OS could log it.
 	pub fn get_file_url(&self, file: RemoteFileType<'_>) -> String {
-		let token = self
-			.tokens
-			.lock()
-			.expect("http-provider token store lock poisoned")
-			.token_for(file);
+		let token = self.tokens().token_for(file);
 		format!(
 			"http://127.0.0.1:{}/file?token={}",
 			self.port,
@@ -442,6 +658,37 @@ impl HttpProviderHandle {
 	) -> String {
 		format!("{}&buffer={}", self.get_file_url(file), buffer_bytes)
 	}
+
+	/// Returns the local HTTP URL that lists the given directory and serves its
+	/// children by relative path, e.g. `{url}season-1/episode.m3u8`.
+	///
+	/// The URL ends in a slash and carries its token in the path rather than the query,
+	/// so relative references inside served files (HLS playlists, `.cue` sheets,
+	/// sibling subtitles) resolve against it without minting a URL per file. The
+	/// listing is JSON with `?format=json` or an `Accept: application/json` header,
+	/// and HTML otherwise.
+	///
+	/// Listing needs an authenticated client, so `client` is registered as if by
+	/// [`register_client`](Self::register_client).
+	pub fn get_dir_url(&self, client: Arc<Client>, dir: RemoteDirectory) -> String {
+		let token = self.tokens().token_for_dir(client.user_id, &dir);
+		self.register_client(client);
+		format!(
+			"http://127.0.0.1:{}/dir/{}/",
+			self.port,
+			BASE64_URL_SAFE_NO_PAD.encode(token)
+		)
+	}
+
+	/// Lists directory URLs of `client`'s account with it. Directory URLs restored from
+	/// [`TokenStorage`] answer `503 Service Unavailable` until a client of their account is
+	/// registered, since the provider can't list a drive without one.
+	pub fn register_client(&self, client: Arc<Client>) {
+		self.clients
+			.lock()
+			.expect("http-provider client registry lock poisoned")
+			.insert(client.user_id, client);
+	}
 }
 
 #[cfg(feature = "uniffi")]
@@ -460,18 +707,34 @@ impl HttpProviderHandle {
 	) -> Result<String, Error> {
 		Ok(self.get_file_url_with_buffer_size(file.try_into()?, buffer_bytes))
 	}
+
+	#[uniffi::method(name = "getDirUrl")]
+	pub fn get_dir_url_uniffi(
+		&self,
+		client: Arc<crate::auth::JsClient>,
+		dir: crate::js::Dir,
+	) -> String {
+		self.get_dir_url(client.inner(), dir.into())
+	}
+
+	#[uniffi::method(name = "registerClient")]
+	pub fn register_client_uniffi(&self, client: Arc<crate::auth::JsClient>) {
+		self.register_client(client.inner());
+	}
 }
 
 #[derive(Clone)]
 struct ProviderState {
 	client: Arc<UnauthClient>,
 	tokens: SharedTokenStore,
+	clients: SharedClients,
+	listings: dir::SharedListingCache,
 }
 
 #[derive(Deserialize)]
 struct FileQuery {
 	/// Opaque token minted by [`get_file_url`](HttpProviderHandle::get_file_url);
-	/// resolves to the file (and its key) in the provider's in-process store.
+	/// resolves to the file (and its key) in the provider's token store.
 	token: String,
 	/// Optional read-ahead window in bytes (`?buffer=`); bounds how much this stream
 	/// prefetches and therefore how much of the shared memory budget it can pin.
@@ -641,8 +904,8 @@ async fn file_handler(
 	State(state): State<ProviderState>,
 	headers: HeaderMap,
 	range: Option<TypedHeader<Range>>,
-) -> impl IntoResponse {
-	// Resolve the opaque token to the file (and its key) held in-process. The entry
+) -> Response {
+	// Resolve the opaque token to the file (and its key) in the token store. The entry
 	// stays in the store (the token is reusable, not single-use) and resolving
 	// refreshes its LRU slot, so the media player's later range requests over the
 	// same playback session keep working.
@@ -650,14 +913,27 @@ async fn file_handler(
 		.tokens
 		.lock()
 		.expect("http-provider token store lock poisoned")
-		.resolve(&params.token)
+		.resolve_file(&params.token)
 	else {
-		return http::Response::builder()
-			.status(StatusCode::NOT_FOUND)
-			.body(axum::body::Body::empty())
-			.expect("should always be able to build a response");
+		return StatusCode::NOT_FOUND.into_response();
 	};
 
+	serve_file(&state.client, file, params.buffer, &headers, range)
+}
+
+/// Formats `date` for `Last-Modified` and other HTTP date headers (RFC 9110 §5.6.7).
+fn http_date(date: DateTime<Utc>) -> String {
+	date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Streams `file`, honouring the request's `Range` header and `?buffer=` read-ahead window.
+fn serve_file(
+	client: &Arc<UnauthClient>,
+	file: RemoteFileType<'static>,
+	buffer: Option<u64>,
+	headers: &HeaderMap,
+	range: Option<TypedHeader<Range>>,
+) -> Response {
 	let ranges = if let Some(TypedHeader(range)) = range {
 		let raw_range = headers
 			.get(http::header::RANGE)
@@ -667,17 +943,13 @@ async fn file_handler(
 		vec![(0, file.size())]
 	};
 
-	let read_ahead = effective_read_ahead(params.buffer, state.client.state().memory_budget());
+	let read_ahead = effective_read_ahead(buffer, client.state().memory_budget());
 	let response_builder = http::Response::builder().header(http::header::ACCEPT_RANGES, "bytes");
 
 	match ranges {
-		ranges if let [range] = *ranges => single_range_response_builder(
-			file,
-			range,
-			read_ahead,
-			state.client.clone(),
-			response_builder,
-		),
+		ranges if let [range] = *ranges => {
+			single_range_response_builder(file, range, read_ahead, client.clone(), response_builder)
+		}
 		ranges if ranges.is_empty() => response_builder
 			.status(StatusCode::RANGE_NOT_SATISFIABLE)
 			.header(
@@ -690,7 +962,7 @@ async fn file_handler(
 			file,
 			ranges,
 			read_ahead,
-			state.client.clone(),
+			client.clone(),
 			response_builder,
 		),
 	}
@@ -1058,6 +1330,155 @@ mod tests {
 		);
 	}
 
+	#[test]
+	fn token_store_drops_expired_tokens() {
+		let mut store = super::TokenStore::new();
+		let token = encode_token(store.token_for(token_store_test_file("a.txt")));
+
+		assert!(store.set_expiry(
+			&token,
+			Some(chrono::Utc::now() + chrono::Duration::hours(1))
+		));
+		assert!(store.resolve(&token).is_some(), "not expired yet");
+
+		assert!(store.set_expiry(
+			&token,
+			Some(chrono::Utc::now() - chrono::Duration::seconds(1))
+		));
+		assert!(
+			store.resolve(&token).is_none(),
+			"an expired token must not resolve"
+		);
+		assert!(
+			store.entries.is_empty(),
+			"resolving an expired token drops it"
+		);
+		assert!(
+			!store.set_expiry(&token, None),
+			"an expired token can't be revived"
+		);
+	}
+
+	#[test]
+	fn token_store_revokes_tokens() {
+		let mut store = super::TokenStore::new();
+		let a = encode_token(store.token_for(token_store_test_file("a.txt")));
+		let b = encode_token(store.token_for(token_store_test_file("b.txt")));
+
+		assert!(store.revoke(&a));
+		assert!(!store.revoke(&a), "a revoked token is no longer live");
+		assert!(store.resolve(&a).is_none());
+		assert!(store.resolve(&b).is_some());
+
+		store.revoke_all();
+		assert!(store.resolve(&b).is_none());
+	}
+
+	/// Tokens minted with storage resolve again from a fresh store over the same directory,
+	/// the way they do after the provider restarts.
+	#[test]
+	fn token_store_persists_tokens_across_restarts() {
+		let dir = tempfile::tempdir().unwrap();
+		let storage = super::TokenStorage::new(
+			dir.path(),
+			crate::crypto::v3::EncryptionKey::new(rand::random()),
+		);
+		let file = token_store_test_file("a.txt");
+		let dir_uuid = filen_types::fs::Uuid::new_v4();
+
+		let (file_token, dir_token, revoked) = {
+			let mut store = super::TokenStore::with_storage(storage.clone()).unwrap();
+			let file_token = encode_token(store.token_for(file.clone()));
+			let dir_token = encode_token(store.insert(super::Served::Dir {
+				user_id: 7,
+				uuid: dir_uuid,
+				name: "media".to_owned(),
+			}));
+			let revoked = encode_token(store.token_for(token_store_test_file("b.txt")));
+			assert!(store.revoke(&revoked));
+			(file_token, dir_token, revoked)
+		};
+
+		let mut store = super::TokenStore::with_storage(storage).unwrap();
+		assert!(store.resolve_file(&file_token) == Some(file));
+		assert!(
+			store.resolve(&dir_token)
+				== Some(super::Served::Dir {
+					user_id: 7,
+					uuid: dir_uuid,
+					name: "media".to_owned(),
+				})
+		);
+		assert!(store.resolve(&revoked).is_none(), "revocation must persist");
+		assert_eq!(store.entries.len(), 2);
+	}
+
+	#[test]
+	fn token_storage_drops_expired_and_keeps_undecryptable_entries() {
+		let dir = tempfile::tempdir().unwrap();
+		let storage = super::TokenStorage::new(
+			dir.path(),
+			crate::crypto::v3::EncryptionKey::new(rand::random()),
+		);
+		{
+			let mut store = super::TokenStore::with_storage(storage.clone()).unwrap();
+			let expired = encode_token(store.token_for(token_store_test_file("a.txt")));
+			let expiring = encode_token(store.token_for(token_store_test_file("b.txt")));
+			store.token_for(token_store_test_file("c.txt"));
+			assert!(store.set_expiry(
+				&expired,
+				Some(chrono::Utc::now() - chrono::Duration::seconds(1))
+			));
+			assert_eq!(store.entries.len(), 2, "saving prunes the expired entry");
+			assert!(store.set_expiry(
+				&expiring,
+				Some(chrono::Utc::now() + chrono::Duration::milliseconds(200))
+			));
+		}
+		storage.wait_for_writes();
+		assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+
+		std::thread::sleep(std::time::Duration::from_millis(300));
+		let store = super::TokenStore::with_storage(storage.clone()).unwrap();
+		assert_eq!(store.entries.len(), 1, "the expired entry is dropped");
+		assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+		let other_key = super::TokenStorage::new(
+			dir.path(),
+			crate::crypto::v3::EncryptionKey::new(rand::random()),
+		);
+		let store = super::TokenStore::with_storage(other_key).unwrap();
+		assert!(
+			store.entries.is_empty(),
+			"entries under another key can't be read"
+		);
+		assert_eq!(
+			std::fs::read_dir(dir.path()).unwrap().count(),
+			1,
+			"entries that can't be read are kept"
+		);
+		let store = super::TokenStore::with_storage(storage).unwrap();
+		assert_eq!(store.entries.len(), 1);
+	}
+
+	#[test]
+	fn token_param_is_parsed_from_file_and_dir_urls() {
+		assert_eq!(
+			super::token_param("http://127.0.0.1:1234/file?token=abc&buffer=10").as_deref(),
+			Some("abc")
+		);
+		assert_eq!(
+			super::token_param("http://127.0.0.1:1234/dir/abc/").as_deref(),
+			Some("abc")
+		);
+		assert_eq!(
+			super::token_param("http://127.0.0.1:1234/dir/abc/season-1/e01.mkv").as_deref(),
+			Some("abc")
+		);
+		assert_eq!(super::token_param("http://127.0.0.1:1234/file"), None);
+		assert_eq!(super::token_param("not a url"), None);
+	}
+
 	// ─── read-ahead window ────────────────────────────────────────────────────
 
 	#[test]
@@ -0,0 +1,358 @@
+//! File-backed storage for URL tokens, so minted URLs survive a provider restart.
+//!
+//! Every token is one file in an app-provided directory. Entries hold file keys, so their
+//! contents are encrypted with an app-provided key, and each file is named by the SHA-256 of its
+//! token so the directory listing doesn't reveal usable URLs.
+//!
+//! Changes are queued and written in order on a blocking thread, so neither the encryption nor
+//! the file IO happens while the token store is locked or on an async worker.
+
+use std::{
+	borrow::Cow,
+	collections::VecDeque,
+	fmt, fs, io,
+	path::{Path, PathBuf},
+	sync::{Arc, Condvar, Mutex, MutexGuard},
+};
+
+use chrono::{DateTime, Utc};
+use filen_types::{
+	auth::FileEncryptionVersion,
+	crypto::Blake3Hash,
+	fs::{ParentUuid, Uuid},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+	crypto::{file::FileKey, shared::DataCrypter, v3::EncryptionKey},
+	fs::file::{
+		AnonymousRemoteFile,
+		enums::RemoteFileType,
+		meta::{DecryptedFileMeta, FileMeta},
+	},
+};
+
+use super::{Served, TokenEntry, UrlToken};
+
+const ENTRY_EXTENSION: &str = "token";
+
+/// Where an [`HttpProviderHandle`](super::HttpProviderHandle) keeps its URL tokens.
+///
+/// `dir` should be private to the app, and `key` should come from the platform keystore: with
+/// both, a stored entry can't be read or reused by anything else. Entries that fail to decrypt,
+/// for example after the key changed, are deleted on startup.
+#[derive(Clone, Debug)]
+pub struct TokenStorage {
+	dir: PathBuf,
+	key: EncryptionKey,
+	writes: Arc<(Mutex<PendingWrites>, Condvar)>,
+}
+
+/// Changes that aren't on disk yet. At most one writer applies them at a time, so they land in
+/// the order they were made.
+#[derive(Default)]
+struct PendingWrites {
+	queue: VecDeque<Write>,
+	writing: bool,
+}
+
+impl fmt::Debug for PendingWrites {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		// entries hold file keys
+		f.debug_struct("PendingWrites")
+			.field("queued", &self.queue.len())
+			.field("writing", &self.writing)
+			.finish()
+	}
+}
+
+enum Write {
+	Save(Box<StoredEntry>),
+	Remove(UrlToken),
+}
+
+impl TokenStorage {
+	pub fn new(dir: impl Into<PathBuf>, key: EncryptionKey) -> Self {
+		Self {
+			dir: dir.into(),
+			key,
+			writes: Arc::default(),
+		}
+	}
+
+	fn entry_path(&self, token: &UrlToken) -> PathBuf {
+		self.dir
+			.join(hex::encode(Sha256::digest(token)))
+			.with_extension(ENTRY_EXTENSION)
+	}
+
+	/// Reads every stored entry, deleting the expired ones. Entries that can't be read or
+	/// decrypted, e.g. ones stored with another key, are skipped and left on disk.
+	pub(super) fn load(&self) -> io::Result<Vec<(UrlToken, TokenEntry)>> {
+		self.wait_for_writes();
+		fs::create_dir_all(&self.dir)?;
+		let now = Utc::now();
+		let mut entries = Vec::new();
+		for dir_entry in fs::read_dir(&self.dir)? {
+			let path = dir_entry?.path();
+			if path.extension().and_then(|ext| ext.to_str()) != Some(ENTRY_EXTENSION) {
+				continue;
+			}
+			match self.read_entry(&path) {
+				Some((token, entry)) if entry.expires_at.is_none_or(|at| at > now) => {
+					entries.push((token, entry));
+				}
+				Some(_) => remove_file(&path),
+				None => {
+					tracing::warn!("skipping unreadable http provider token {}", path.display());
+				}
+			}
+		}
+		Ok(entries)
+	}
+
+	fn read_entry(&self, path: &Path) -> Option<(UrlToken, TokenEntry)> {
+		let mut data = fs::read(path).ok()?;
+		self.key.blocking_decrypt_data(&mut data).ok()?;
+		let stored: StoredEntry = serde_json::from_slice(&data).ok()?;
+		// a file renamed onto another token's name must not resolve under that token
+		if self.entry_path(&stored.token) != path {
+			return None;
+		}
+		let served = match stored.served {
+			StoredServed::File(file) => Served::File(file.into_remote_file()?),
+			StoredServed::Dir {
+				user_id,
+				uuid,
+				name,
+			} => Served::Dir {
+				user_id,
+				uuid,
+				name,
+			},
+		};
+		Some((
+			stored.token,
+			TokenEntry {
+				served,
+				expires_at: stored.expires_at,
+			},
+		))
+	}
+
+	/// Queues writing `entry`. Only drive files with decrypted metadata are written: other files
+	/// can't be streamed anyway, and shared or linked files would come back as drive files, so
+	/// those entries stay in memory only. An entry that already expired is removed instead.
+	pub(super) fn save(&self, token: &UrlToken, entry: &TokenEntry) {
+		if entry.is_expired(Utc::now()) {
+			self.remove(token);
+			return;
+		}
+		let served = match &entry.served {
+			Served::File(RemoteFileType::File(file)) => match StoredFile::from_remote_file(file) {
+				Some(file) => StoredServed::File(file),
+				None => return,
+			},
+			Served::File(_) => return,
+			Served::Dir {
+				user_id,
+				uuid,
+				name,
+			} => StoredServed::Dir {
+				user_id: *user_id,
+				uuid: *uuid,
+				name: name.clone(),
+			},
+		};
+		self.queue(Write::Save(Box::new(StoredEntry {
+			token: *token,
+			served,
+			expires_at: entry.expires_at,
+		})));
+	}
+
+	/// Queues removing the entry of `token`.
+	pub(super) fn remove(&self, token: &UrlToken) {
+		self.queue(Write::Remove(*token));
+	}
+
+	fn pending(&self) -> MutexGuard<'_, PendingWrites> {
+		self.writes
+			.0
+			.lock()
+			.expect("http-provider token storage lock poisoned")
+	}
+
+	fn queue(&self, write: Write) {
+		let mut pending = self.pending();
+		pending.queue.push_back(write);
+		if pending.writing {
+			return;
+		}
+		pending.writing = true;
+		drop(pending);
+
+		let this = self.clone();
+		match tokio::runtime::Handle::try_current() {
+			Ok(runtime_handle) => {
+				runtime_handle.spawn_blocking(move || this.write_pending());
+			}
+			Err(_) => {
+				std::thread::spawn(move || this.write_pending());
+			}
+		}
+	}
+
+	/// Applies queued writes until there are none left.
+	fn write_pending(&self) {
+		loop {
+			let write = {
+				let mut pending = self.pending();
+				match pending.queue.pop_front() {
+					Some(write) => write,
+					None => {
+						pending.writing = false;
+						self.writes.1.notify_all();
+						return;
+					}
+				}
+			};
+			match write {
+				Write::Save(stored) => self.write_entry(&stored),
+				Write::Remove(token) => remove_file(&self.entry_path(&token)),
+			}
+		}
+	}
+
+	fn write_entry(&self, stored: &StoredEntry) {
+		let result = serde_json::to_vec(stored)
+			.map_err(io::Error::other)
+			.and_then(|mut data| {
+				self.key
+					.blocking_encrypt_data(&mut data)
+					.map_err(io::Error::other)?;
+				fs::write(self.entry_path(&stored.token), data)
+			});
+		if let Err(e) = result {
+			tracing::warn!(
+				"failed to persist http provider token, it will not survive a restart: {e}"
+			);
+		}
+	}
+
+	/// Blocks until every queued write is on disk.
+	pub(super) fn wait_for_writes(&self) {
+		let (pending, written) = &*self.writes;
+		let _pending = written
+			.wait_while(
+				pending
+					.lock()
+					.expect("http-provider token storage lock poisoned"),
+				|pending| pending.writing,
+			)
+			.expect("http-provider token storage lock poisoned");
+	}
+}
+
+fn remove_file(path: &Path) {
+	if let Err(e) = fs::remove_file(path)
+		&& e.kind() != io::ErrorKind::NotFound
+	{
+		tracing::warn!(
+			"failed to remove http provider token {}: {e}",
+			path.display()
+		);
+	}
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredEntry {
+	token: UrlToken,
+	served: StoredServed,
+	expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum StoredServed {
+	File(StoredFile),
+	#[serde(rename_all = "camelCase")]
+	Dir {
+		user_id: u64,
+		uuid: Uuid,
+		name: String,
+	},
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredFile {
+	uuid: Uuid,
+	parent: ParentUuid,
+	size: u64,
+	favorited: bool,
+	region: String,
+	bucket: String,
+	timestamp: DateTime<Utc>,
+	chunks: u64,
+	name: String,
+	meta_size: u64,
+	mime: String,
+	key: String,
+	key_version: FileEncryptionVersion,
+	last_modified: DateTime<Utc>,
+	created: Option<DateTime<Utc>>,
+	hash: Option<Blake3Hash>,
+}
+
+impl StoredFile {
+	fn from_remote_file(file: &AnonymousRemoteFile) -> Option<Self> {
+		let FileMeta::Decoded(meta) = &file.meta else {
+			return None;
+		};
+		Some(Self {
+			uuid: file.uuid,
+			parent: file.parent,
+			size: file.size,
+			favorited: file.favorited,
+			region: file.region.clone(),
+			bucket: file.bucket.clone(),
+			timestamp: file.timestamp,
+			chunks: file.chunks,
+			name: meta.name.to_string(),
+			meta_size: meta.size,
+			mime: meta.mime.to_string(),
+			key: meta.key.to_str().as_ref().to_owned(),
+			key_version: meta.key.version(),
+			last_modified: meta.last_modified,
+			created: meta.created,
+			hash: meta.hash,
+		})
+	}
+
+	fn into_remote_file(self) -> Option<RemoteFileType<'static>> {
+		let key = FileKey::from_str_with_version(&self.key, self.key_version).ok()?;
+		Some(RemoteFileType::File(Cow::Owned(AnonymousRemoteFile {
+			uuid: self.uuid,
+			stable_uuid: (),
+			meta: FileMeta::Decoded(DecryptedFileMeta {
+				name: Cow::Owned(self.name),
+				size: self.meta_size,
+				mime: Cow::Owned(self.mime),
+				key,
+				last_modified: self.last_modified,
+				created: self.created,
+				hash: self.hash,
+			}),
+			parent: self.parent,
+			size: self.size,
+			favorited: self.favorited,
+			region: self.region,
+			bucket: self.bucket,
+			timestamp: self.timestamp,
+			chunks: self.chunks,
+		})))
+	}
+}
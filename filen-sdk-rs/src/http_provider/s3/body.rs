@@ -0,0 +1,197 @@
+//! Upload bodies, which AWS SDKs often send `aws-chunked` encoded.
+
+use axum::body::Body;
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt, TryStreamExt, stream::BoxStream};
+use http::{HeaderMap, header::CONTENT_ENCODING};
+
+use super::S3Error;
+use crate::consts::CHUNK_SIZE;
+
+/// Longest accepted chunk header: the hex size and a `chunk-signature` extension
+const MAX_CHUNK_HEADER_LEN: usize = 256;
+/// Largest accepted chunk, well above what AWS SDKs send. Chunk data is passed on as it arrives,
+/// so this only bounds what a header can claim.
+const MAX_CHUNK_SIZE: usize = 16 * CHUNK_SIZE;
+
+fn incomplete_body() -> S3Error {
+	S3Error::new(
+		http::StatusCode::BAD_REQUEST,
+		"IncompleteBody",
+		"the request body ended early",
+	)
+}
+
+fn malformed_chunk() -> S3Error {
+	S3Error::new(
+		http::StatusCode::BAD_REQUEST,
+		"InvalidRequest",
+		"malformed aws-chunked body",
+	)
+}
+
+/// The object data of an upload request
+pub(super) fn object_data(
+	headers: &HeaderMap,
+	body: Body,
+) -> BoxStream<'static, Result<Bytes, S3Error>> {
+	let data = body.into_data_stream().map_err(|_| incomplete_body());
+	let is_chunked = headers
+		.get("x-amz-content-sha256")
+		.is_some_and(|value| value.as_bytes().starts_with(b"STREAMING-"))
+		|| headers
+			.get(CONTENT_ENCODING)
+			.and_then(|value| value.to_str().ok())
+			.is_some_and(|value| {
+				value
+					.split(',')
+					.any(|coding| coding.trim() == "aws-chunked")
+			});
+	if is_chunked {
+		decode_aws_chunked(data).boxed()
+	} else {
+		data.boxed()
+	}
+}
+
+/// Strips the framing of an `aws-chunked` body: chunks of `<hex size>[;<extensions>]\r\n<data>\r\n`,
+/// ending with a chunk of size 0 and optional trailers. Chunk signatures and trailing checksums
+/// are skipped.
+fn decode_aws_chunked(
+	data: impl Stream<Item = Result<Bytes, S3Error>> + Send + 'static,
+) -> impl Stream<Item = Result<Bytes, S3Error>> + Send + 'static {
+	async_stream::try_stream! {
+		let mut data = Box::pin(data);
+		let mut buffer = BytesMut::new();
+		loop {
+			let header_len = loop {
+				if let Some(i) = buffer.windows(2).position(|window| window == b"\r\n") {
+					break i;
+				}
+				if buffer.len() > MAX_CHUNK_HEADER_LEN {
+					Err(malformed_chunk())?;
+				}
+				match data.next().await {
+					Some(bytes) => buffer.extend_from_slice(&bytes?),
+					None => Err(incomplete_body())?,
+				}
+			};
+			let header = buffer.split_to(header_len + 2);
+			let size = std::str::from_utf8(&header[..header_len])
+				.ok()
+				.and_then(|header| header.split(';').next())
+				.and_then(|size| usize::from_str_radix(size.trim(), 16).ok())
+				.ok_or_else(malformed_chunk)?;
+			if size > MAX_CHUNK_SIZE {
+				Err(malformed_chunk())?;
+			}
+			if size == 0 {
+				break;
+			}
+
+			// the chunk's data is passed on as it arrives instead of being collected first
+			let mut remaining = size;
+			if !buffer.is_empty() {
+				let buffered = buffer.split_to(remaining.min(buffer.len())).freeze();
+				remaining -= buffered.len();
+				yield buffered;
+			}
+			while remaining > 0 {
+				let mut bytes = match data.next().await {
+					Some(bytes) => bytes?,
+					None => Err(incomplete_body())?,
+				};
+				if bytes.len() > remaining {
+					buffer.extend_from_slice(&bytes.split_off(remaining));
+				}
+				remaining -= bytes.len();
+				if !bytes.is_empty() {
+					yield bytes;
+				}
+			}
+			while buffer.len() < 2 {
+				match data.next().await {
+					Some(bytes) => buffer.extend_from_slice(&bytes?),
+					None => Err(incomplete_body())?,
+				}
+			}
+			if buffer.split_to(2).as_ref() != b"\r\n" {
+				Err(malformed_chunk())?;
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bytes::Bytes;
+	use futures::{StreamExt, TryStreamExt, executor::block_on, stream};
+
+	use super::decode_aws_chunked;
+
+	fn decode(body: &[u8], split_every: usize) -> Result<Vec<u8>, &'static str> {
+		let parts = body
+			.chunks(split_every)
+			.map(|part| Ok(Bytes::copy_from_slice(part)))
+			.collect::<Vec<_>>();
+		block_on(
+			decode_aws_chunked(stream::iter(parts))
+				.map_ok(|chunk| chunk.to_vec())
+				.try_concat(),
+		)
+		.map_err(|e| e.code)
+	}
+
+	#[test]
+	fn chunked_bodies_are_decoded() {
+		let body = b"5;chunk-signature=abc\r\nhello\r\n7;chunk-signature=def\r\n, world\r\n\
+			0;chunk-signature=0\r\nx-amz-checksum-crc32:AAAAAA==\r\n\r\n";
+		for split_every in [1, 3, body.len()] {
+			assert_eq!(decode(body, split_every).unwrap(), b"hello, world");
+		}
+		assert_eq!(decode(b"0\r\n\r\n", 2).unwrap(), b"");
+	}
+
+	#[test]
+	fn truncated_or_malformed_bodies_fail() {
+		assert_eq!(decode(b"5\r\nhel", 4), Err("IncompleteBody"));
+		assert_eq!(decode(b"zz\r\nhello\r\n", 4), Err("InvalidRequest"));
+		assert_eq!(decode(b"5\r\nhelloXX0\r\n\r\n", 4), Err("InvalidRequest"));
+		let collected = block_on(
+			decode_aws_chunked(stream::iter(Vec::<Result<Bytes, _>>::new())).collect::<Vec<_>>(),
+		);
+		assert!(collected[0].is_err());
+	}
+
+	#[test]
+	fn oversized_chunks_are_rejected() {
+		assert_eq!(
+			decode(b"ffffffffffffffff\r\nhello\r\n", 4),
+			Err("InvalidRequest")
+		);
+		assert_eq!(
+			decode(b"fffffffffffffffff\r\nhello\r\n", 4),
+			Err("InvalidRequest")
+		);
+		assert_eq!(decode(b"40000000\r\nhello\r\n", 4), Err("InvalidRequest"));
+	}
+
+	#[test]
+	fn chunk_data_is_passed_on_as_it_arrives() {
+		let parts = [b"a\r\nhel".as_slice(), b"lo"]
+			.into_iter()
+			.map(|part| Ok(Bytes::from_static(part)))
+			.collect::<Vec<_>>();
+		let collected = block_on(decode_aws_chunked(stream::iter(parts)).collect::<Vec<_>>());
+		let data = collected
+			.iter()
+			.map_while(|chunk| chunk.as_ref().ok())
+			.flat_map(|chunk| chunk.to_vec())
+			.collect::<Vec<_>>();
+		assert_eq!(data, b"hello", "data before the body ended early");
+		assert_eq!(
+			collected.last().unwrap().as_ref().map_err(|e| e.code).err(),
+			Some("IncompleteBody")
+		);
+	}
+}
@@ -0,0 +1,889 @@
+//! An S3-compatible gateway over the drive, so tools built on AWS SDKs can read and write it.
+//!
+//! Buckets are the directories directly inside the served root, and object keys are paths below
+//! them: key `photos/a.jpg` in bucket `backup` is the file `backup/photos/a.jpg`. Only
+//! path-style requests (`http://host/bucket/key`) are understood, so clients need path-style
+//! addressing turned on (`force_path_style` in most SDKs).
+//!
+//! Supported are `ListBuckets`, `HeadBucket`, `GetBucketLocation`, `ListObjects`,
+//! `ListObjectsV2`, `GetObject` with ranges, `HeadObject`, `PutObject`, `CopyObject`,
+//! `DeleteObject` and multipart uploads. Everything else answers `501 NotImplemented`.
+
+use std::{
+	borrow::Cow,
+	collections::HashMap,
+	net::SocketAddr,
+	sync::{Arc, Mutex},
+};
+
+use axum::{
+	body::Body,
+	extract::{Request, State},
+	response::{IntoResponse, Response},
+};
+use axum_extra::{
+	TypedHeader,
+	headers::{HeaderMapExt, Range},
+};
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use filen_types::{fs::Uuid, traits::CowHelpers};
+use futures::{AsyncWriteExt, StreamExt};
+use http::{
+	HeaderMap, HeaderValue, Method, StatusCode,
+	header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_TYPE, ETAG, LAST_MODIFIED},
+};
+
+use crate::{
+	Error, ErrorKind,
+	auth::{Client, shared_client::SharedClient, unauth::UnauthClient},
+	fs::{
+		HasName, HasUUID,
+		categories::{DirType, NonRootFileType, Normal, fs::CategoryFSExt},
+		file::RemoteFile,
+	},
+	io::HasFileInfo,
+};
+
+use super::{DriveServer, http_date};
+
+mod auth;
+mod body;
+mod multipart;
+mod xml;
+
+use multipart::Upload;
+
+/// The most keys one listing returns, as on S3
+const MAX_KEYS: usize = 1000;
+const COPY_SOURCE: &str = "x-amz-copy-source";
+/// Subresources that would otherwise be mistaken for plain object or bucket requests
+const UNSUPPORTED_SUBRESOURCES: [&str; 8] = [
+	"acl",
+	"attributes",
+	"cors",
+	"lifecycle",
+	"policy",
+	"tagging",
+	"versioning",
+	"versions",
+];
+
+/// How an S3 server exposes the drive
+#[derive(Clone, Debug, Default)]
+pub struct S3Options {
+	/// Drive directory whose subdirectories are the buckets, e.g. `Backups`; the drive root
+	/// when `None`.
+	pub root: Option<String>,
+	/// Keys every request must be signed with (AWS Signature Version 4); requests aren't
+	/// checked when `None`.
+	pub credentials: Option<S3Credentials>,
+	/// Rejects every request that would change the drive with `403 AccessDenied`.
+	pub read_only: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct S3Credentials {
+	pub access_key_id: String,
+	pub secret_access_key: String,
+}
+
+impl Client {
+	/// Starts an S3-compatible server for this client's drive on `address`.
+	///
+	/// Like the [WebDAV server](Self::start_webdav_server), it never deletes anything for good:
+	/// `DeleteObject` moves files to the trash, and writing over an object uploads a new
+	/// version of it.
+	pub async fn start_s3_server(
+		self: Arc<Self>,
+		address: SocketAddr,
+		options: S3Options,
+	) -> Result<DriveServer, Error> {
+		let root = options
+			.root
+			.as_deref()
+			.unwrap_or_default()
+			.trim_matches('/')
+			.to_owned();
+		match self.find_item_at_path(&root).await? {
+			Some(NonRootFileType::Root(_) | NonRootFileType::Dir(_)) => {}
+			_ => {
+				return Err(Error::custom(
+					ErrorKind::FolderNotFound,
+					format!("S3 root {root} is not a directory"),
+				));
+			}
+		}
+
+		// ideally this wouldn't clone the client, see `start_http_provider`
+		let unauth_client = Arc::new(self.get_unauth_client().clone());
+		let router = axum::Router::new().fallback(dispatch).with_state(S3State {
+			client: self,
+			unauth_client,
+			root: Arc::new(root),
+			credentials: options.credentials.map(Arc::new),
+			read_only: options.read_only,
+			uploads: Arc::default(),
+		});
+		DriveServer::serve(address, router).await
+	}
+}
+
+#[derive(Clone)]
+struct S3State {
+	client: Arc<Client>,
+	unauth_client: Arc<UnauthClient>,
+	/// Drive path of the directory holding the buckets
+	root: Arc<String>,
+	credentials: Option<Arc<S3Credentials>>,
+	read_only: bool,
+	/// Multipart uploads in progress, by upload ID
+	uploads: Arc<Mutex<HashMap<String, Arc<Upload>>>>,
+}
+
+impl S3State {
+	fn uploads(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<Upload>>> {
+		self.uploads.lock().expect("s3 uploads lock poisoned")
+	}
+
+	fn drive_path(&self, bucket: &str, key: &str) -> String {
+		[self.root.as_str(), bucket, key.trim_end_matches('/')]
+			.into_iter()
+			.filter(|part| !part.is_empty())
+			.collect::<Vec<_>>()
+			.join("/")
+	}
+
+	async fn find(&self, path: &str) -> Result<Option<NonRootFileType<'static, Normal>>, S3Error> {
+		match self.client.find_item_at_path(path).await {
+			Ok(item) => Ok(item.map(CowHelpers::into_owned_cow)),
+			// a file in the middle of the path
+			Err(e) if e.kind() == ErrorKind::InvalidType => Ok(None),
+			Err(e) => Err(e.into()),
+		}
+	}
+
+	async fn find_bucket(&self, bucket: &str) -> Result<DirType<'static, Normal>, S3Error> {
+		match self.find(&self.drive_path(bucket, "")).await? {
+			Some(NonRootFileType::Dir(dir)) => Ok(DirType::Dir(dir)),
+			_ => Err(S3Error::new(
+				StatusCode::NOT_FOUND,
+				"NoSuchBucket",
+				"the bucket does not exist",
+			)),
+		}
+	}
+
+	async fn find_object(&self, bucket: &str, key: &str) -> Result<RemoteFile, S3Error> {
+		match self.find(&self.drive_path(bucket, key)).await? {
+			Some(NonRootFileType::File(file)) if !key.ends_with('/') => Ok(file.into_owned()),
+			_ => {
+				self.find_bucket(bucket).await?;
+				Err(S3Error::new(
+					StatusCode::NOT_FOUND,
+					"NoSuchKey",
+					"the key does not exist",
+				))
+			}
+		}
+	}
+
+	/// The directory an object at `key` goes into, created with its missing parents, and the
+	/// object's name
+	async fn object_parent<'k>(
+		&self,
+		bucket: &str,
+		key: &'k str,
+	) -> Result<(DirType<'static, Normal>, &'k str), S3Error> {
+		let bucket_dir = self.find_bucket(bucket).await?;
+		match key.rsplit_once('/') {
+			Some((dirs, name)) => Ok((
+				self.client
+					.find_or_create_dir_starting_at(bucket_dir, dirs)
+					.await?,
+				name,
+			)),
+			None => Ok((bucket_dir, key)),
+		}
+	}
+
+	/// Aborts and forgets the uploads that saw no parts for [`multipart::IDLE_TIMEOUT`].
+	fn expire_idle_uploads(&self) {
+		self.uploads().retain(|_, upload| {
+			let idle = upload.is_idle();
+			if idle {
+				upload.abort();
+			}
+			!idle
+		});
+	}
+
+	fn upload(&self, bucket: &str, key: &str, upload_id: &str) -> Result<Arc<Upload>, S3Error> {
+		self.expire_idle_uploads();
+		self.uploads()
+			.get(upload_id)
+			.filter(|upload| upload.bucket == bucket && upload.key == key)
+			.cloned()
+			.ok_or_else(multipart::no_such_upload)
+	}
+}
+
+/// A failed request, answered with an S3 `Error` body
+#[derive(Debug)]
+struct S3Error {
+	status: StatusCode,
+	code: &'static str,
+	message: Cow<'static, str>,
+}
+
+impl S3Error {
+	fn new(status: StatusCode, code: &'static str, message: impl Into<Cow<'static, str>>) -> Self {
+		Self {
+			status,
+			code,
+			message: message.into(),
+		}
+	}
+
+	fn access_denied() -> Self {
+		Self::new(StatusCode::FORBIDDEN, "AccessDenied", "access denied")
+	}
+
+	fn invalid_argument(message: impl Into<Cow<'static, str>>) -> Self {
+		Self::new(StatusCode::BAD_REQUEST, "InvalidArgument", message)
+	}
+
+	fn not_implemented() -> Self {
+		Self::new(
+			StatusCode::NOT_IMPLEMENTED,
+			"NotImplemented",
+			"this operation is not supported by the Filen S3 gateway",
+		)
+	}
+}
+
+impl From<Error> for S3Error {
+	fn from(e: Error) -> Self {
+		match e.kind() {
+			ErrorKind::FileNotFound | ErrorKind::FolderNotFound => {
+				Self::new(StatusCode::NOT_FOUND, "NoSuchKey", "the key does not exist")
+			}
+			ErrorKind::InvalidName => Self::invalid_argument(e.to_string()),
+			ErrorKind::InvalidType => Self::new(
+				StatusCode::CONFLICT,
+				"InvalidRequest",
+				"a prefix of the key is an object",
+			),
+			ErrorKind::MaxStorageReached => Self::new(
+				StatusCode::INSUFFICIENT_STORAGE,
+				"QuotaExceeded",
+				"the drive is full",
+			),
+			_ => {
+				tracing::error!("S3 request failed: {e}");
+				Self::new(
+					StatusCode::INTERNAL_SERVER_ERROR,
+					"InternalError",
+					"internal error",
+				)
+			}
+		}
+	}
+}
+
+impl From<crate::fs::name::EntryNameError> for S3Error {
+	fn from(e: crate::fs::name::EntryNameError) -> Self {
+		Self::invalid_argument(e.to_string())
+	}
+}
+
+impl IntoResponse for S3Error {
+	fn into_response(self) -> Response {
+		(
+			self.status,
+			[(CONTENT_TYPE, xml::CONTENT_TYPE)],
+			xml::error_body(self.code, &self.message),
+		)
+			.into_response()
+	}
+}
+
+type S3Result = Result<Response, S3Error>;
+
+fn xml_response(body: String) -> Response {
+	(StatusCode::OK, [(CONTENT_TYPE, xml::CONTENT_TYPE)], body).into_response()
+}
+
+/// The decoded query parameters of a request, in request order
+struct QueryParams(Vec<(String, String)>);
+
+impl QueryParams {
+	fn parse(query: &str) -> Self {
+		Self(
+			query
+				.split('&')
+				.filter(|param| !param.is_empty())
+				.map(|param| {
+					let (name, value) = param.split_once('=').unwrap_or((param, ""));
+					(decode(name), decode(value))
+				})
+				.collect(),
+		)
+	}
+
+	fn get(&self, name: &str) -> Option<&str> {
+		self.iter()
+			.find(|(param, _)| *param == name)
+			.map(|(_, value)| value)
+	}
+
+	fn has(&self, name: &str) -> bool {
+		self.get(name).is_some()
+	}
+
+	fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+		self.0
+			.iter()
+			.map(|(name, value)| (name.as_str(), value.as_str()))
+	}
+}
+
+fn decode(value: &str) -> String {
+	urlencoding::decode(value)
+		.map(Cow::into_owned)
+		.unwrap_or_else(|_| value.to_owned())
+}
+
+enum Target {
+	Service,
+	Bucket(String),
+	Object { bucket: String, key: String },
+}
+
+/// Splits a request path into the bucket and the key. Keys must be drive paths: no empty, `.`
+/// or `..` segments, and only a trailing slash, which marks a folder object.
+fn parse_target(path: &str) -> Result<Target, S3Error> {
+	let path = path.strip_prefix('/').unwrap_or(path);
+	let (bucket, key) = path.split_once('/').unwrap_or((path, ""));
+	let bucket = decode(bucket);
+	if bucket.is_empty() {
+		return Ok(Target::Service);
+	}
+	if bucket == "." || bucket == ".." || bucket.contains('/') {
+		return Err(S3Error::new(
+			StatusCode::BAD_REQUEST,
+			"InvalidBucketName",
+			"the bucket name is not a directory name",
+		));
+	}
+	if key.is_empty() {
+		return Ok(Target::Bucket(bucket));
+	}
+	let key = decode(key);
+	if key
+		.strip_suffix('/')
+		.unwrap_or(&key)
+		.split('/')
+		.any(|segment| segment.is_empty() || segment == "." || segment == "..")
+	{
+		return Err(S3Error::invalid_argument(
+			"keys must be paths without empty, `.` or `..` segments",
+		));
+	}
+	Ok(Target::Object { bucket, key })
+}
+
+async fn dispatch(State(state): State<S3State>, request: Request) -> Response {
+	let (parts, body) = request.into_parts();
+	if let Some(credentials) = &state.credentials
+		&& let Err(e) = auth::verify(
+			&parts.method,
+			&parts.uri,
+			&parts.headers,
+			credentials,
+			Utc::now(),
+		) {
+		return e.into_response();
+	}
+	if state.read_only && !matches!(parts.method, Method::GET | Method::HEAD) {
+		return S3Error::access_denied().into_response();
+	}
+
+	let query = QueryParams::parse(parts.uri.query().unwrap_or_default());
+	if UNSUPPORTED_SUBRESOURCES.iter().any(|name| query.has(name)) {
+		return S3Error::not_implemented().into_response();
+	}
+	let result = match parse_target(parts.uri.path()) {
+		Err(e) => Err(e),
+		Ok(Target::Service) if parts.method == Method::GET => list_buckets(&state).await,
+		Ok(Target::Service) => Err(S3Error::not_implemented()),
+		Ok(Target::Bucket(bucket)) => match parts.method {
+			Method::HEAD => state
+				.find_bucket(&bucket)
+				.await
+				.map(|_| StatusCode::OK.into_response()),
+			Method::GET if query.has("location") => state
+				.find_bucket(&bucket)
+				.await
+				.map(|_| xml_response(xml::location_body())),
+			Method::GET if !query.has("uploads") => list_objects(&state, &bucket, &query).await,
+			_ => Err(S3Error::not_implemented()),
+		},
+		Ok(Target::Object { bucket, key }) => {
+			let headers = &parts.headers;
+			match (&parts.method, query.get("uploadId")) {
+				(&Method::GET, None) => get_object(&state, &bucket, &key, headers).await,
+				(&Method::HEAD, None) => head_object(&state, &bucket, &key).await,
+				(&Method::PUT, None) if headers.contains_key(COPY_SOURCE) => {
+					copy_object(&state, &bucket, &key, headers).await
+				}
+				(&Method::PUT, None) => put_object(&state, &bucket, &key, headers, body).await,
+				(&Method::PUT, Some(upload_id)) if !headers.contains_key(COPY_SOURCE) => {
+					let upload = state.upload(&bucket, &key, upload_id);
+					upload_part(upload, &query, headers, body).await
+				}
+				(&Method::POST, None) if query.has("uploads") => {
+					create_multipart_upload(&state, &bucket, &key).await
+				}
+				(&Method::POST, Some(upload_id)) => {
+					complete_multipart_upload(&state, &bucket, &key, upload_id, body).await
+				}
+				(&Method::DELETE, Some(upload_id)) => {
+					abort_multipart_upload(&state, &bucket, &key, upload_id)
+				}
+				(&Method::DELETE, None) => delete_object(&state, &bucket, &key).await,
+				_ => Err(S3Error::not_implemented()),
+			}
+		}
+	};
+	result.unwrap_or_else(IntoResponse::into_response)
+}
+
+fn etag(file: &RemoteFile) -> String {
+	// every upload, including a new version of the same file, gets a new uuid
+	format!("\"{}\"", file.uuid())
+}
+
+fn modified(file: &RemoteFile) -> DateTime<Utc> {
+	file.last_modified().unwrap_or(file.timestamp)
+}
+
+fn object_headers(file: &RemoteFile) -> HeaderMap {
+	let mut headers = HeaderMap::new();
+	if let Ok(etag) = HeaderValue::from_str(&etag(file)) {
+		headers.insert(ETAG, etag);
+	}
+	if let Ok(last_modified) = HeaderValue::from_str(&http_date(modified(file))) {
+		headers.insert(LAST_MODIFIED, last_modified);
+	}
+	headers
+}
+
+async fn list_buckets(state: &S3State) -> S3Result {
+	let root = match state.find(&state.root).await? {
+		Some(NonRootFileType::Root(root)) => DirType::Root(root),
+		Some(NonRootFileType::Dir(dir)) => DirType::Dir(dir),
+		_ => return Err(Error::custom(ErrorKind::FolderNotFound, "S3 root is gone").into()),
+	};
+	let (dirs, _) = state
+		.client
+		.list_dir(&root, None::<&fn(u64, Option<u64>)>)
+		.await?;
+	let buckets = dirs
+		.iter()
+		.filter_map(|dir| {
+			Some(xml::Bucket {
+				name: dir.name()?.to_owned(),
+				created: dir.created().unwrap_or(dir.timestamp),
+			})
+		})
+		.collect::<Vec<_>>();
+	Ok(xml_response(xml::list_buckets_body(&buckets)))
+}
+
+enum Entry {
+	Object(xml::Object),
+	Prefix(String),
+}
+
+impl Entry {
+	fn object(key: String, file: &RemoteFile) -> Self {
+		Self::Object(xml::Object {
+			key,
+			size: file.size(),
+			etag: etag(file),
+			modified: modified(file),
+		})
+	}
+
+	fn key(&self) -> &str {
+		match self {
+			Self::Object(object) => &object.key,
+			Self::Prefix(prefix) => prefix,
+		}
+	}
+}
+
+async fn list_objects(state: &S3State, bucket: &str, query: &QueryParams) -> S3Result {
+	let is_v2 = query.get("list-type") == Some("2");
+	let prefix = query.get("prefix").unwrap_or_default();
+	let delimiter = query
+		.get("delimiter")
+		.filter(|delimiter| !delimiter.is_empty());
+	let max_keys = match query.get("max-keys") {
+		Some(max_keys) => max_keys
+			.parse::<usize>()
+			.map_err(|_| S3Error::invalid_argument("max-keys must be a number"))?
+			.min(MAX_KEYS),
+		None => MAX_KEYS,
+	};
+	let continuation_token = query.get("continuation-token").filter(|_| is_v2);
+	let start_after = match (is_v2, continuation_token) {
+		(true, Some(token)) => BASE64_URL_SAFE_NO_PAD
+			.decode(token)
+			.ok()
+			.and_then(|key| String::from_utf8(key).ok())
+			.ok_or_else(|| S3Error::invalid_argument("invalid continuation token"))?,
+		(true, None) => query.get("start-after").unwrap_or_default().to_owned(),
+		(false, _) => query.get("marker").unwrap_or_default().to_owned(),
+	};
+
+	let bucket_dir = state.find_bucket(bucket).await?;
+	let mut page = list_entries(state, bucket_dir, bucket, prefix, delimiter)
+		.await?
+		.into_iter()
+		.filter(|entry| entry.key() > start_after.as_str())
+		.take(max_keys + 1)
+		.collect::<Vec<_>>();
+	let is_truncated = page.len() > max_keys;
+	page.truncate(max_keys);
+	let last_key = page
+		.last()
+		.filter(|_| is_truncated)
+		.map(|entry| entry.key().to_owned());
+
+	let (mut objects, mut common_prefixes) = (Vec::new(), Vec::new());
+	for entry in page {
+		match entry {
+			Entry::Object(object) => objects.push(object),
+			Entry::Prefix(prefix) => common_prefixes.push(prefix),
+		}
+	}
+	let version = if is_v2 {
+		xml::ListVersion::V2 {
+			continuation_token: continuation_token.map(str::to_owned),
+			next_continuation_token: last_key.map(|key| BASE64_URL_SAFE_NO_PAD.encode(key)),
+			start_after: query.get("start-after").map(str::to_owned),
+		}
+	} else {
+		xml::ListVersion::V1 {
+			marker: start_after,
+			next_marker: last_key,
+		}
+	};
+	Ok(xml_response(xml::list_objects_body(&xml::Listing {
+		bucket: bucket.to_owned(),
+		prefix: prefix.to_owned(),
+		delimiter: delimiter.map(str::to_owned),
+		max_keys,
+		objects,
+		common_prefixes,
+		is_truncated,
+		url_encoded: query.get("encoding-type") == Some("url"),
+		version,
+	})))
+}
+
+/// Every key and common prefix under `prefix`, sorted.
+///
+/// With the `/` delimiter, only the directory `prefix` ends in is listed; with any other (or
+/// none), everything below it is.
+async fn list_entries(
+	state: &S3State,
+	bucket_dir: DirType<'static, Normal>,
+	bucket: &str,
+	prefix: &str,
+	delimiter: Option<&str>,
+) -> Result<Vec<Entry>, S3Error> {
+	// only the directory the prefix ends in can hold matching keys
+	let dir_prefix = &prefix[..prefix.rfind('/').map_or(0, |i| i + 1)];
+	let dir = if dir_prefix.is_empty() {
+		bucket_dir
+	} else {
+		match state.find(&state.drive_path(bucket, dir_prefix)).await? {
+			Some(NonRootFileType::Dir(dir)) => DirType::Dir(dir),
+			_ => return Ok(Vec::new()),
+		}
+	};
+
+	let mut entries = Vec::new();
+	if delimiter == Some("/") {
+		let (dirs, files) = state
+			.client
+			.list_dir(&dir, None::<&fn(u64, Option<u64>)>)
+			.await?;
+		entries.extend(
+			dirs.iter()
+				.filter_map(|dir| Some(Entry::Prefix(format!("{dir_prefix}{}/", dir.name()?)))),
+		);
+		entries.extend(
+			files.iter().filter_map(|file| {
+				Some(Entry::object(format!("{dir_prefix}{}", file.name()?), file))
+			}),
+		);
+	} else {
+		let (_, files) = Normal::list_dir_recursive_with_paths(
+			state.client.clone(),
+			dir,
+			None::<&fn(u64, Option<u64>)>,
+			&mut |errors: Vec<Error>| {
+				for e in errors {
+					tracing::warn!("S3 listing skipped an item: {e}");
+				}
+			},
+			(),
+		)
+		.await?;
+		for (file, path) in &files {
+			let key = format!("{dir_prefix}{path}");
+			let prefix_end = delimiter.and_then(|delimiter| {
+				let end = key.get(prefix.len()..)?.find(delimiter)?;
+				Some(prefix.len() + end + delimiter.len())
+			});
+			entries.push(match prefix_end {
+				Some(end) => Entry::Prefix(key[..end].to_owned()),
+				None => Entry::object(key, file),
+			});
+		}
+	}
+	entries.retain(|entry| entry.key().starts_with(prefix));
+	entries.sort_by(|a, b| a.key().cmp(b.key()));
+	entries.dedup_by(|a, b| a.key() == b.key());
+	Ok(entries)
+}
+
+async fn get_object(state: &S3State, bucket: &str, key: &str, headers: &HeaderMap) -> S3Result {
+	let file = state.find_object(bucket, key).await?;
+	let range = headers.typed_get::<Range>().map(TypedHeader);
+	let object_headers = object_headers(&file);
+	let mut response = super::serve_file(&state.unauth_client, file.into(), None, headers, range);
+	response.headers_mut().extend(object_headers);
+	Ok(response)
+}
+
+async fn head_object(state: &S3State, bucket: &str, key: &str) -> S3Result {
+	let file = state.find_object(bucket, key).await?;
+	let mut headers = object_headers(&file);
+	headers.insert(CONTENT_LENGTH, file.size().into());
+	headers.insert(
+		CONTENT_TYPE,
+		file.mime()
+			.and_then(|mime| HeaderValue::from_str(mime).ok())
+			.unwrap_or(HeaderValue::from_static("application/octet-stream")),
+	);
+	headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+	Ok((StatusCode::OK, headers).into_response())
+}
+
+async fn put_object(
+	state: &S3State,
+	bucket: &str,
+	key: &str,
+	headers: &HeaderMap,
+	body: Body,
+) -> S3Result {
+	if let Some(dir) = key.strip_suffix('/') {
+		// a folder object, as S3 consoles and sync tools create them
+		let bucket_dir = state.find_bucket(bucket).await?;
+		state
+			.client
+			.find_or_create_dir_starting_at(bucket_dir, dir)
+			.await?;
+		return Ok(StatusCode::OK.into_response());
+	}
+
+	let (parent, name) = state.object_parent(bucket, key).await?;
+	let builder = state.client.make_file_builder(name, parent.uuid())?;
+	let mut writer = state.client.get_file_writer(builder);
+	let mut data = body::object_data(headers, body);
+	while let Some(chunk) = data.next().await {
+		writer.write_all(&chunk?).await.map_err(Error::from)?;
+	}
+	writer.close().await.map_err(Error::from)?;
+	let file = writer
+		.into_remote_file()
+		.expect("a closed file writer holds its remote file");
+	Ok((StatusCode::OK, object_headers(&file)).into_response())
+}
+
+async fn copy_object(state: &S3State, bucket: &str, key: &str, headers: &HeaderMap) -> S3Result {
+	let source = headers
+		.get(COPY_SOURCE)
+		.and_then(|value| value.to_str().ok())
+		.ok_or_else(|| S3Error::invalid_argument("invalid x-amz-copy-source"))?;
+	// a `?versionId=` is ignored, only current versions are served
+	let source = source.split_once('?').map_or(source, |(source, _)| source);
+	let Target::Object {
+		bucket: source_bucket,
+		key: source_key,
+	} = parse_target(source)?
+	else {
+		return Err(S3Error::invalid_argument("invalid x-amz-copy-source"));
+	};
+	let source = state.find_object(&source_bucket, &source_key).await?;
+	if key.ends_with('/') {
+		return Err(S3Error::invalid_argument(
+			"folder objects can't be copied onto",
+		));
+	}
+
+	let (parent, name) = state.object_parent(bucket, key).await?;
+	let file = state
+		.client
+		.copy_file(&source, &parent, Some(name), None)
+		.await?;
+	Ok(xml_response(xml::copy_object_body(
+		&etag(&file),
+		modified(&file),
+	)))
+}
+
+async fn delete_object(state: &S3State, bucket: &str, key: &str) -> S3Result {
+	match state.find(&state.drive_path(bucket, key)).await? {
+		Some(NonRootFileType::File(file)) if !key.ends_with('/') => {
+			state.client.trash_file(&mut file.into_owned()).await?;
+		}
+		Some(NonRootFileType::Dir(dir)) if key.ends_with('/') => {
+			// a folder object only stands for the directory while it is empty
+			let (dirs, files) = state
+				.client
+				.list_dir(
+					&DirType::Dir(Cow::Borrowed(&*dir)),
+					None::<&fn(u64, Option<u64>)>,
+				)
+				.await?;
+			if dirs.is_empty() && files.is_empty() {
+				state.client.trash_dir(&mut dir.into_owned()).await?;
+			}
+		}
+		// deleting a missing key succeeds, as on S3
+		_ => {
+			state.find_bucket(bucket).await?;
+		}
+	}
+	Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+async fn create_multipart_upload(state: &S3State, bucket: &str, key: &str) -> S3Result {
+	if key.ends_with('/') {
+		return Err(S3Error::invalid_argument(
+			"folder objects can't be uploaded in parts",
+		));
+	}
+	state.expire_idle_uploads();
+	let (parent, name) = state.object_parent(bucket, key).await?;
+	let builder = state.client.make_file_builder(name, parent.uuid())?;
+	let upload_id = Uuid::new_v4().to_string();
+	let upload = Upload::start(
+		state.client.clone(),
+		builder,
+		bucket.to_owned(),
+		key.to_owned(),
+	);
+	state.uploads().insert(upload_id.clone(), Arc::new(upload));
+	Ok(xml_response(xml::create_multipart_upload_body(
+		bucket, key, &upload_id,
+	)))
+}
+
+async fn upload_part(
+	upload: Result<Arc<Upload>, S3Error>,
+	query: &QueryParams,
+	headers: &HeaderMap,
+	body: Body,
+) -> S3Result {
+	let number = query
+		.get("partNumber")
+		.and_then(|number| number.parse().ok())
+		.ok_or_else(|| S3Error::invalid_argument("invalid partNumber"))?;
+	let etag = upload?
+		.write_part(number, body::object_data(headers, body))
+		.await?;
+	Ok((StatusCode::OK, [(ETAG, etag)]).into_response())
+}
+
+async fn complete_multipart_upload(
+	state: &S3State,
+	bucket: &str,
+	key: &str,
+	upload_id: &str,
+	body: Body,
+) -> S3Result {
+	let upload = state.upload(bucket, key, upload_id)?;
+	let body = axum::body::to_bytes(body, xml::MAX_BODY_SIZE)
+		.await
+		.map_err(|_| S3Error::invalid_argument("the part list is too large"))?;
+	let parts = xml::parse_complete_multipart_upload(&body).ok_or_else(|| {
+		S3Error::new(
+			StatusCode::BAD_REQUEST,
+			"MalformedXML",
+			"the part list is not well-formed",
+		)
+	})?;
+
+	let result = upload.complete(&parts).await;
+	// the client may send a corrected part list, anything else ends the upload
+	if !matches!(&result, Err(e) if e.code == "InvalidPart") {
+		state.uploads().remove(upload_id);
+	}
+	let file = result?;
+	Ok(xml_response(xml::complete_multipart_upload_body(
+		bucket,
+		key,
+		&etag(&file),
+	)))
+}
+
+fn abort_multipart_upload(state: &S3State, bucket: &str, key: &str, upload_id: &str) -> S3Result {
+	let upload = state.upload(bucket, key, upload_id)?;
+	state.uploads().remove(upload_id);
+	upload.abort();
+	Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{QueryParams, Target, parse_target};
+
+	#[test]
+	fn targets() {
+		assert!(matches!(parse_target("/"), Ok(Target::Service)));
+		assert!(
+			matches!(parse_target("/bucket"), Ok(Target::Bucket(bucket)) if bucket == "bucket")
+		);
+		assert!(
+			matches!(parse_target("/bucket/"), Ok(Target::Bucket(bucket)) if bucket == "bucket")
+		);
+		assert!(matches!(
+			parse_target("/bucket/a%20b/c.txt"),
+			Ok(Target::Object { bucket, key }) if bucket == "bucket" && key == "a b/c.txt"
+		));
+		assert!(matches!(
+			parse_target("/bucket/folder/"),
+			Ok(Target::Object { key, .. }) if key == "folder/"
+		));
+		assert!(parse_target("/bucket/a//b").is_err());
+		assert!(parse_target("/bucket/a/../b").is_err());
+		assert!(parse_target("/../a").is_err());
+	}
+
+	#[test]
+	fn query_params() {
+		let query = QueryParams::parse("list-type=2&prefix=a%2Fb&uploads&max-keys=");
+		assert_eq!(query.get("list-type"), Some("2"));
+		assert_eq!(query.get("prefix"), Some("a/b"));
+		assert!(query.has("uploads"));
+		assert_eq!(query.get("max-keys"), Some(""));
+		assert_eq!(query.get("delimiter"), None);
+	}
+}
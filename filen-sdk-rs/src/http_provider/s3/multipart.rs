@@ -0,0 +1,238 @@
+//! Multipart uploads, streamed into one [`FileWriter`](crate::fs::file::write::FileWriter) as
+//! their parts arrive.
+//!
+//! Parts are appended in order, so a part waits until every part before it was written. AWS
+//! SDKs upload parts in ascending order with bounded concurrency, which keeps those waits short.
+//! An appended part can't be replaced, and an upload whose part failed can only be aborted.
+//! Uploads that see no parts for [`IDLE_TIMEOUT`] are aborted.
+
+use std::{
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use futures::{AsyncWriteExt, Stream, StreamExt};
+use md5::{Digest, Md5};
+use tokio::{
+	sync::{mpsc, watch},
+	task::JoinHandle,
+};
+
+use crate::{
+	Error,
+	auth::Client,
+	fs::file::{FileBuilder, RemoteFile},
+};
+
+use super::S3Error;
+
+/// How many body chunks may wait for the writer before a part stops reading its body
+const CHANNEL_CAPACITY: usize = 8;
+/// S3's largest part number
+pub(super) const MAX_PART_NUMBER: u32 = 10_000;
+/// `next_part` of an upload that can't take more parts
+const FAILED: u32 = u32::MAX;
+/// How long an upload may go without parts before it expires
+pub(super) const IDLE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+pub(super) struct Upload {
+	pub(super) bucket: String,
+	pub(super) key: String,
+	/// The number of the part to append next, or [`FAILED`]
+	next_part: watch::Sender<u32>,
+	/// Feeds the writer task; held by the part being appended, and dropped to finish the file
+	data: tokio::sync::Mutex<Option<mpsc::Sender<Bytes>>>,
+	/// The ETags of the appended parts, in order
+	etags: Mutex<Vec<String>>,
+	task: Mutex<Option<JoinHandle<Result<RemoteFile, Error>>>>,
+	/// When a part last started or finished
+	last_active: Mutex<Instant>,
+}
+
+/// Aborts the upload when a part stops midway, including when its request is dropped
+struct AbortOnDrop<'a>(Option<&'a Upload>);
+
+impl Drop for AbortOnDrop<'_> {
+	fn drop(&mut self) {
+		if let Some(upload) = self.0 {
+			upload.abort();
+		}
+	}
+}
+
+pub(super) fn no_such_upload() -> S3Error {
+	S3Error::new(
+		http::StatusCode::NOT_FOUND,
+		"NoSuchUpload",
+		"the upload does not exist, or a part of it failed",
+	)
+}
+
+fn invalid_part(message: &'static str) -> S3Error {
+	S3Error::new(http::StatusCode::BAD_REQUEST, "InvalidPart", message)
+}
+
+/// ETags are compared without their quotes, which some clients drop
+fn same_etag(a: &str, b: &str) -> bool {
+	a.trim_matches('"') == b.trim_matches('"')
+}
+
+impl Upload {
+	/// Starts the writer of a new upload for the file `builder` describes.
+	pub(super) fn start(
+		client: Arc<Client>,
+		builder: FileBuilder,
+		bucket: String,
+		key: String,
+	) -> Self {
+		let (data, mut receiver) = mpsc::channel::<Bytes>(CHANNEL_CAPACITY);
+		let task = tokio::task::spawn(async move {
+			let mut writer = client.get_file_writer(builder);
+			while let Some(chunk) = receiver.recv().await {
+				writer.write_all(&chunk).await?;
+			}
+			writer.close().await?;
+			Ok(writer
+				.into_remote_file()
+				.expect("a closed file writer holds its remote file"))
+		});
+		Self {
+			bucket,
+			key,
+			next_part: watch::Sender::new(1),
+			data: tokio::sync::Mutex::new(Some(data)),
+			etags: Mutex::new(Vec::new()),
+			task: Mutex::new(Some(task)),
+			last_active: Mutex::new(Instant::now()),
+		}
+	}
+
+	fn touch(&self) {
+		*self
+			.last_active
+			.lock()
+			.expect("upload activity lock poisoned") = Instant::now();
+	}
+
+	/// Whether no part is being appended and none started for [`IDLE_TIMEOUT`].
+	pub(super) fn is_idle(&self) -> bool {
+		self.data.try_lock().is_ok()
+			&& self
+				.last_active
+				.lock()
+				.expect("upload activity lock poisoned")
+				.elapsed() >= IDLE_TIMEOUT
+	}
+
+	/// Stops the writer without finishing the file.
+	pub(super) fn abort(&self) {
+		self.next_part.send_replace(FAILED);
+		if let Some(task) = self.task.lock().expect("upload task lock poisoned").take() {
+			task.abort();
+		}
+	}
+
+	/// Appends part `number`, once the parts before it are appended, and returns its ETag.
+	pub(super) async fn write_part(
+		&self,
+		number: u32,
+		body: impl Stream<Item = Result<Bytes, S3Error>>,
+	) -> Result<String, S3Error> {
+		if !(1..=MAX_PART_NUMBER).contains(&number) {
+			return Err(S3Error::invalid_argument(
+				"part numbers range from 1 to 10000",
+			));
+		}
+		self.touch();
+		let next = *self
+			.next_part
+			.subscribe()
+			.wait_for(|next| *next >= number)
+			.await
+			.map_err(|_| no_such_upload())?;
+		if next == FAILED {
+			return Err(no_such_upload());
+		}
+
+		let data = self.data.lock().await;
+		// checked again in case a retry of this part got here first
+		if *self.next_part.borrow() != number {
+			return Err(invalid_part("parts can't be uploaded twice"));
+		}
+		let sender = data.as_ref().ok_or_else(no_such_upload)?;
+		let mut hasher = Md5::new();
+		let mut body = std::pin::pin!(body);
+		// the writer may have got some of this part's data, so the file can't be finished
+		let mut abort_guard = AbortOnDrop(Some(self));
+		while let Some(chunk) = body.next().await {
+			let chunk = chunk?;
+			hasher.update(&chunk);
+			sender.send(chunk).await.map_err(|_| no_such_upload())?;
+		}
+		abort_guard.0 = None;
+
+		let etag = format!("\"{}\"", hex::encode(hasher.finalize()));
+		self.etags
+			.lock()
+			.expect("upload etags lock poisoned")
+			.push(etag.clone());
+		self.next_part.send_replace(number + 1);
+		self.touch();
+		Ok(etag)
+	}
+
+	/// Finishes the file, if `parts` lists every appended part in order.
+	pub(super) async fn complete(&self, parts: &[(u32, String)]) -> Result<RemoteFile, S3Error> {
+		// waits for a part that is still being appended
+		let mut data = self.data.lock().await;
+		if *self.next_part.borrow() == FAILED {
+			return Err(no_such_upload());
+		}
+		let etags = self
+			.etags
+			.lock()
+			.expect("upload etags lock poisoned")
+			.clone();
+		let lists_every_part = parts.len() == etags.len()
+			&& parts
+				.iter()
+				.zip(&etags)
+				.enumerate()
+				.all(|(i, ((number, etag), appended))| {
+					*number as usize == i + 1 && same_etag(etag, appended)
+				});
+		if !lists_every_part {
+			return Err(invalid_part(
+				"the part list must name every uploaded part, in order, with its ETag",
+			));
+		}
+
+		// closing the channel lets the writer finish the file
+		data.take();
+		self.next_part.send_replace(FAILED);
+		let task = self
+			.task
+			.lock()
+			.expect("upload task lock poisoned")
+			.take()
+			.ok_or_else(no_such_upload)?;
+		match task.await {
+			Ok(result) => result.map_err(Into::into),
+			Err(e) => Err(Error::custom(
+				crate::ErrorKind::Internal,
+				format!("multipart upload task failed: {e}"),
+			)
+			.into()),
+		}
+	}
+}
+
+impl Drop for Upload {
+	fn drop(&mut self) {
+		// without this, dropping `data` would let the writer finish a partial file
+		if let Some(task) = self.task.get_mut().ok().and_then(Option::take) {
+			task.abort();
+		}
+	}
+}
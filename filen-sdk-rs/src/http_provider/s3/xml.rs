@@ -0,0 +1,231 @@
+//! The XML bodies of S3 responses, and the one request body the gateway reads.
+
+use std::fmt::Write;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use quick_xml::{Reader, escape::escape, events::Event};
+
+pub(super) const CONTENT_TYPE: &str = "application/xml";
+/// Request bodies are part lists, so anything bigger isn't a real client
+pub(super) const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+const DECLARATION: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+const NAMESPACE: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+
+fn timestamp(date: DateTime<Utc>) -> String {
+	date.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Appends `<name>value</name>`, escaping `value`.
+fn element(xml: &mut String, name: &str, value: &str) {
+	let _ = write!(xml, "<{name}>{}</{name}>", escape(value));
+}
+
+pub(super) fn error_body(code: &str, message: &str) -> String {
+	let mut xml = format!("{DECLARATION}<Error>");
+	element(&mut xml, "Code", code);
+	element(&mut xml, "Message", message);
+	xml.push_str("</Error>");
+	xml
+}
+
+pub(super) struct Bucket {
+	pub(super) name: String,
+	pub(super) created: DateTime<Utc>,
+}
+
+pub(super) fn list_buckets_body(buckets: &[Bucket]) -> String {
+	let mut xml = format!("{DECLARATION}<ListAllMyBucketsResult xmlns=\"{NAMESPACE}\"><Buckets>");
+	for bucket in buckets {
+		xml.push_str("<Bucket>");
+		element(&mut xml, "Name", &bucket.name);
+		element(&mut xml, "CreationDate", &timestamp(bucket.created));
+		xml.push_str("</Bucket>");
+	}
+	xml.push_str("</Buckets></ListAllMyBucketsResult>");
+	xml
+}
+
+pub(super) fn location_body() -> String {
+	// empty, which clients read as the default region
+	format!("{DECLARATION}<LocationConstraint xmlns=\"{NAMESPACE}\"/>")
+}
+
+pub(super) struct Object {
+	pub(super) key: String,
+	pub(super) size: u64,
+	pub(super) etag: String,
+	pub(super) modified: DateTime<Utc>,
+}
+
+/// The pagination fields, which differ between `ListObjects` and `ListObjectsV2`
+pub(super) enum ListVersion {
+	V1 {
+		marker: String,
+		next_marker: Option<String>,
+	},
+	V2 {
+		continuation_token: Option<String>,
+		next_continuation_token: Option<String>,
+		start_after: Option<String>,
+	},
+}
+
+pub(super) struct Listing {
+	pub(super) bucket: String,
+	pub(super) prefix: String,
+	pub(super) delimiter: Option<String>,
+	pub(super) max_keys: usize,
+	pub(super) objects: Vec<Object>,
+	pub(super) common_prefixes: Vec<String>,
+	pub(super) is_truncated: bool,
+	/// Whether keys are URL-encoded, for `encoding-type=url`
+	pub(super) url_encoded: bool,
+	pub(super) version: ListVersion,
+}
+
+pub(super) fn list_objects_body(listing: &Listing) -> String {
+	let key = |key: &str| {
+		if listing.url_encoded {
+			urlencoding::encode(key).into_owned()
+		} else {
+			key.to_owned()
+		}
+	};
+
+	let mut xml = format!("{DECLARATION}<ListBucketResult xmlns=\"{NAMESPACE}\">");
+	element(&mut xml, "Name", &listing.bucket);
+	element(&mut xml, "Prefix", &key(&listing.prefix));
+	if let Some(delimiter) = &listing.delimiter {
+		element(&mut xml, "Delimiter", &key(delimiter));
+	}
+	element(&mut xml, "MaxKeys", &listing.max_keys.to_string());
+	if listing.url_encoded {
+		element(&mut xml, "EncodingType", "url");
+	}
+	element(&mut xml, "IsTruncated", &listing.is_truncated.to_string());
+	match &listing.version {
+		ListVersion::V1 {
+			marker,
+			next_marker,
+		} => {
+			element(&mut xml, "Marker", &key(marker));
+			if let Some(next_marker) = next_marker {
+				element(&mut xml, "NextMarker", &key(next_marker));
+			}
+		}
+		ListVersion::V2 {
+			continuation_token,
+			next_continuation_token,
+			start_after,
+		} => {
+			let key_count = listing.objects.len() + listing.common_prefixes.len();
+			element(&mut xml, "KeyCount", &key_count.to_string());
+			if let Some(token) = continuation_token {
+				element(&mut xml, "ContinuationToken", token);
+			}
+			if let Some(token) = next_continuation_token {
+				element(&mut xml, "NextContinuationToken", token);
+			}
+			if let Some(start_after) = start_after {
+				element(&mut xml, "StartAfter", &key(start_after));
+			}
+		}
+	}
+	for object in &listing.objects {
+		xml.push_str("<Contents>");
+		element(&mut xml, "Key", &key(&object.key));
+		element(&mut xml, "LastModified", &timestamp(object.modified));
+		element(&mut xml, "ETag", &object.etag);
+		element(&mut xml, "Size", &object.size.to_string());
+		element(&mut xml, "StorageClass", "STANDARD");
+		xml.push_str("</Contents>");
+	}
+	for prefix in &listing.common_prefixes {
+		xml.push_str("<CommonPrefixes>");
+		element(&mut xml, "Prefix", &key(prefix));
+		xml.push_str("</CommonPrefixes>");
+	}
+	xml.push_str("</ListBucketResult>");
+	xml
+}
+
+pub(super) fn copy_object_body(etag: &str, modified: DateTime<Utc>) -> String {
+	let mut xml = format!("{DECLARATION}<CopyObjectResult xmlns=\"{NAMESPACE}\">");
+	element(&mut xml, "ETag", etag);
+	element(&mut xml, "LastModified", &timestamp(modified));
+	xml.push_str("</CopyObjectResult>");
+	xml
+}
+
+pub(super) fn create_multipart_upload_body(bucket: &str, key: &str, upload_id: &str) -> String {
+	let mut xml = format!("{DECLARATION}<InitiateMultipartUploadResult xmlns=\"{NAMESPACE}\">");
+	element(&mut xml, "Bucket", bucket);
+	element(&mut xml, "Key", key);
+	element(&mut xml, "UploadId", upload_id);
+	xml.push_str("</InitiateMultipartUploadResult>");
+	xml
+}
+
+pub(super) fn complete_multipart_upload_body(bucket: &str, key: &str, etag: &str) -> String {
+	let mut xml = format!("{DECLARATION}<CompleteMultipartUploadResult xmlns=\"{NAMESPACE}\">");
+	element(&mut xml, "Bucket", bucket);
+	element(&mut xml, "Key", key);
+	element(&mut xml, "ETag", etag);
+	xml.push_str("</CompleteMultipartUploadResult>");
+	xml
+}
+
+/// Parses the part numbers and ETags of a `CompleteMultipartUpload` body, in body order.
+/// `None` if the body isn't well-formed or a part lacks either.
+pub(super) fn parse_complete_multipart_upload(body: &[u8]) -> Option<Vec<(u32, String)>> {
+	let mut reader = Reader::from_reader(body);
+	reader.config_mut().trim_text(true);
+	let mut stack = Vec::new();
+	let (mut number, mut etag) = (None, None);
+	let mut parts = Vec::new();
+	loop {
+		match reader.read_event().ok()? {
+			Event::Start(element) => stack.push(element.local_name().as_ref().to_vec()),
+			Event::End(_) => {
+				if stack.pop().as_deref() == Some(b"Part") {
+					parts.push((number.take()?, etag.take()?));
+				}
+			}
+			Event::Text(text) => {
+				let text = text.unescape().ok()?;
+				match stack.last().map(Vec::as_slice) {
+					Some(b"PartNumber") => number = Some(text.trim().parse().ok()?),
+					Some(b"ETag") => etag = Some(text.into_owned()),
+					_ => {}
+				}
+			}
+			Event::Eof => return Some(parts),
+			_ => {}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::parse_complete_multipart_upload;
+
+	#[test]
+	fn complete_multipart_upload_bodies() {
+		let body = br#"<?xml version="1.0" encoding="UTF-8"?>
+			<CompleteMultipartUpload xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+				<Part><ETag>&quot;a&quot;</ETag><PartNumber>1</PartNumber></Part>
+				<Part><PartNumber>2</PartNumber><ChecksumCRC32>x</ChecksumCRC32><ETag>"b"</ETag></Part>
+			</CompleteMultipartUpload>"#;
+		assert_eq!(
+			parse_complete_multipart_upload(body),
+			Some(vec![(1, "\"a\"".to_owned()), (2, "\"b\"".to_owned())])
+		);
+		assert_eq!(
+			parse_complete_multipart_upload(
+				b"<CompleteMultipartUpload><Part><PartNumber>1</PartNumber></Part></CompleteMultipartUpload>"
+			),
+			None
+		);
+	}
+}
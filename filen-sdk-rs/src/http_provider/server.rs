@@ -0,0 +1,63 @@
+//! The lifecycle of the servers that expose the drive to other machines, like the
+//! [`webdav`](super::webdav) and [`s3`](super::s3) gateways.
+
+use std::net::SocketAddr;
+
+use crate::{Error, ErrorKind};
+
+/// A running gateway server, stopped when this is dropped or [`stop`](Self::stop)ped.
+pub struct DriveServer {
+	address: SocketAddr,
+	cancel_sender: Option<tokio::sync::oneshot::Sender<()>>,
+	task: Option<tokio::task::JoinHandle<std::io::Result<()>>>,
+}
+
+impl DriveServer {
+	/// Binds `address` and serves `router` on it until stopped.
+	pub(super) async fn serve(address: SocketAddr, router: axum::Router) -> Result<Self, Error> {
+		let listener = tokio::net::TcpListener::bind(address).await?;
+		let address = listener.local_addr()?;
+		let (cancel_sender, cancel_receiver) = tokio::sync::oneshot::channel();
+		let task = tokio::task::spawn(async move {
+			axum::serve(listener, router)
+				.with_graceful_shutdown(async {
+					let _ = cancel_receiver.await;
+				})
+				.await
+		});
+
+		Ok(Self {
+			address,
+			cancel_sender: Some(cancel_sender),
+			task: Some(task),
+		})
+	}
+
+	/// The address the server listens on, with the port the OS picked if it was started on
+	/// port 0
+	pub fn address(&self) -> SocketAddr {
+		self.address
+	}
+
+	/// Stops accepting requests and waits for the ones in flight to finish.
+	pub async fn stop(mut self) -> Result<(), Error> {
+		if let Some(cancel_sender) = self.cancel_sender.take() {
+			let _ = cancel_sender.send(());
+		}
+		match self.task.take() {
+			Some(task) => task.await.map_err(|e| {
+				Error::custom(ErrorKind::Internal, format!("server task failed: {e}"))
+			})?,
+			None => Ok(()),
+		}
+		.map_err(Into::into)
+	}
+}
+
+impl Drop for DriveServer {
+	fn drop(&mut self) {
+		if let Some(cancel_sender) = self.cancel_sender.take() {
+			let _ = cancel_sender.send(());
+		}
+	}
+}
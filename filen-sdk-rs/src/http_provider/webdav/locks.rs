@@ -0,0 +1,262 @@
+//! Exclusive write locks (RFC 4918 §6, §7).
+//!
+//! Each lock holds a [`ResourceLock`] on the locked item, so two servers on one account can't
+//! hand out locks on the same item. Which paths a lock covers is tracked here, since the
+//! [`ResourceLock`] only knows the item it was taken on.
+
+use std::{
+	collections::HashMap,
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+
+use filen_types::fs::Uuid;
+use http::HeaderMap;
+
+use crate::sync::lock::ResourceLock;
+
+use super::path::DavPath;
+
+/// A contended [`ResourceLock`] is retried this briefly before answering `423 Locked`; WebDAV
+/// clients retry on their own.
+pub(super) const ACQUIRE_MAX_SLEEP: Duration = Duration::from_millis(500);
+pub(super) const ACQUIRE_ATTEMPTS: usize = 2;
+/// Used when the request has no `Timeout` header
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+/// Upper bound for requested timeouts, `Infinite` included, so a client that disappears can't
+/// hold a lock forever
+const MAX_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub(super) type SharedLocks = Arc<Mutex<LockTable>>;
+
+struct HeldLock {
+	path: DavPath,
+	/// Whether the lock covers everything below `path` (`Depth: infinity`)
+	deep: bool,
+	owner: Option<String>,
+	timeout: Duration,
+	expires_at: Instant,
+	/// Released when the lock is dropped
+	resource: Arc<ResourceLock>,
+}
+
+impl HeldLock {
+	/// Whether taking a lock on `path` (and with `deep`, everything below it) conflicts with
+	/// this one
+	fn conflicts_with(&self, path: &DavPath, deep: bool) -> bool {
+		(path.starts_with(&self.path) && (self.deep || *path == self.path))
+			|| (deep && self.path.starts_with(path))
+	}
+
+	fn is_live(&self, now: Instant) -> bool {
+		self.expires_at > now && self.resource.is_valid()
+	}
+
+	fn active(&self, token: &str) -> ActiveLock {
+		ActiveLock {
+			token: token.to_owned(),
+			root: self.path.href(false),
+			deep: self.deep,
+			owner: self.owner.clone(),
+			timeout: self.timeout,
+		}
+	}
+}
+
+/// A snapshot of a held lock, for `lockdiscovery`
+#[derive(Clone, Debug)]
+pub(super) struct ActiveLock {
+	pub(super) token: String,
+	/// href of the locked resource
+	pub(super) root: String,
+	pub(super) deep: bool,
+	pub(super) owner: Option<String>,
+	pub(super) timeout: Duration,
+}
+
+/// The locks of one server, by token
+#[derive(Default)]
+pub(super) struct LockTable {
+	locks: HashMap<String, HeldLock>,
+}
+
+impl LockTable {
+	/// Drops expired locks and the ones whose [`ResourceLock`] lost its lease.
+	fn prune(&mut self) {
+		let now = Instant::now();
+		self.locks.retain(|_, lock| lock.is_live(now));
+	}
+
+	/// Whether `path` may be written (with `deep`, including everything below it) by a request
+	/// that submitted the lock tokens in `submitted`.
+	pub(super) fn is_unlocked_for(
+		&mut self,
+		path: &DavPath,
+		deep: bool,
+		submitted: &[String],
+	) -> bool {
+		self.prune();
+		self.locks.iter().all(|(token, lock)| {
+			!lock.conflicts_with(path, deep) || submitted.iter().any(|s| s == token)
+		})
+	}
+
+	/// Adds a lock on `path`, unless a conflicting one was added while its [`ResourceLock`] was
+	/// being acquired.
+	pub(super) fn insert(
+		&mut self,
+		path: DavPath,
+		deep: bool,
+		owner: Option<String>,
+		timeout: Duration,
+		resource: Arc<ResourceLock>,
+	) -> Option<ActiveLock> {
+		if !self.is_unlocked_for(&path, deep, &[]) {
+			return None;
+		}
+		let token = format!("opaquelocktoken:{}", Uuid::new_v4());
+		let lock = HeldLock {
+			path,
+			deep,
+			owner,
+			timeout,
+			expires_at: Instant::now() + timeout,
+			resource,
+		};
+		let active = lock.active(&token);
+		self.locks.insert(token, lock);
+		Some(active)
+	}
+
+	/// Restarts the timeout of the lock `token`, if it covers `path`.
+	pub(super) fn refresh(
+		&mut self,
+		token: &str,
+		path: &DavPath,
+		timeout: Duration,
+	) -> Option<ActiveLock> {
+		self.prune();
+		let lock = self
+			.locks
+			.get_mut(token)
+			.filter(|lock| lock.conflicts_with(path, false))?;
+		lock.timeout = timeout;
+		lock.expires_at = Instant::now() + timeout;
+		Some(lock.active(token))
+	}
+
+	/// Releases the lock `token`, if it covers `path`.
+	pub(super) fn remove(&mut self, token: &str, path: &DavPath) -> bool {
+		self.prune();
+		match self.locks.get(token) {
+			Some(lock) if lock.conflicts_with(path, false) => {
+				self.locks.remove(token);
+				true
+			}
+			_ => false,
+		}
+	}
+
+	/// Releases the locks on `path` and below it, after it was moved or deleted.
+	pub(super) fn remove_under(&mut self, path: &DavPath) {
+		self.locks.retain(|_, lock| !lock.path.starts_with(path));
+	}
+
+	/// The locks that cover `path`
+	pub(super) fn active_on(&mut self, path: &DavPath) -> Vec<ActiveLock> {
+		self.prune();
+		self.locks
+			.iter()
+			.filter(|(_, lock)| lock.conflicts_with(path, false))
+			.map(|(token, lock)| lock.active(token))
+			.collect()
+	}
+}
+
+/// The lock tokens named in the `If` header.
+///
+/// This doesn't evaluate the header's conditions: a token anywhere in it counts as submitted,
+/// which is what every client that sends one intends.
+pub(super) fn submitted_tokens(headers: &HeaderMap) -> Vec<String> {
+	let Some(header) = headers.get("If").and_then(|value| value.to_str().ok()) else {
+		return Vec::new();
+	};
+	header
+		.split('<')
+		.skip(1)
+		.filter_map(|rest| rest.split_once('>'))
+		.map(|(token, _)| token)
+		.filter(|token| token.starts_with("opaquelocktoken:"))
+		.map(str::to_owned)
+		.collect()
+}
+
+/// The first usable timeout of the `Timeout` header, capped at [`MAX_TIMEOUT`]
+pub(super) fn requested_timeout(headers: &HeaderMap) -> Duration {
+	headers
+		.get("Timeout")
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| {
+			value.split(',').find_map(|timeout| {
+				let timeout = timeout.trim();
+				if timeout.eq_ignore_ascii_case("Infinite") {
+					return Some(MAX_TIMEOUT);
+				}
+				timeout
+					.strip_prefix("Second-")
+					.and_then(|seconds| seconds.parse().ok())
+					.map(Duration::from_secs)
+			})
+		})
+		.unwrap_or(DEFAULT_TIMEOUT)
+		.min(MAX_TIMEOUT)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use http::{HeaderMap, HeaderValue};
+
+	use super::{DEFAULT_TIMEOUT, MAX_TIMEOUT, requested_timeout, submitted_tokens};
+
+	fn headers(name: &'static str, value: &'static str) -> HeaderMap {
+		let mut headers = HeaderMap::new();
+		headers.insert(name, HeaderValue::from_static(value));
+		headers
+	}
+
+	#[test]
+	fn if_header_tokens() {
+		let headers = headers(
+			"If",
+			"<http://host/a> (<opaquelocktoken:1> [\"etag\"]) (Not <opaquelocktoken:2>)",
+		);
+		assert_eq!(
+			submitted_tokens(&headers),
+			vec!["opaquelocktoken:1", "opaquelocktoken:2"]
+		);
+		assert!(submitted_tokens(&HeaderMap::new()).is_empty());
+	}
+
+	#[test]
+	fn timeout_header() {
+		assert_eq!(
+			requested_timeout(&headers("Timeout", "Second-120")),
+			Duration::from_secs(120)
+		);
+		assert_eq!(
+			requested_timeout(&headers("Timeout", "Infinite, Second-4100000000")),
+			MAX_TIMEOUT
+		);
+		assert_eq!(
+			requested_timeout(&headers("Timeout", "Second-4100000000")),
+			MAX_TIMEOUT
+		);
+		assert_eq!(
+			requested_timeout(&headers("Timeout", "soon")),
+			DEFAULT_TIMEOUT
+		);
+		assert_eq!(requested_timeout(&HeaderMap::new()), DEFAULT_TIMEOUT);
+	}
+}
@@ -0,0 +1,783 @@
+//! A WebDAV (class 1 and 2) server over the drive, so it can be mounted by file managers and
+//! synced by WebDAV clients without an external binary.
+//!
+//! Unlike the [`HttpProviderHandle`](super::HttpProviderHandle), which only streams files to
+//! the local device, this server is meant to be reachable by other machines: it binds to a
+//! caller-chosen address and can require HTTP basic authentication.
+
+use std::{
+	borrow::Cow,
+	net::SocketAddr,
+	sync::{Arc, Mutex},
+};
+
+use axum::{
+	body::Body,
+	extract::{Request, State},
+	response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::{
+	TypedHeader,
+	headers::{HeaderMapExt, Range},
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use filen_types::{fs::ParentUuid, traits::CowHelpers};
+use futures::{AsyncWriteExt, StreamExt};
+use http::{
+	HeaderMap, HeaderValue, StatusCode,
+	header::{ALLOW, AUTHORIZATION, CONTENT_TYPE, ETAG, LAST_MODIFIED, LOCATION, WWW_AUTHENTICATE},
+};
+use subtle::ConstantTimeEq;
+
+use crate::{
+	Error, ErrorKind,
+	auth::{Client, shared_client::SharedClient, unauth::UnauthClient},
+	fs::{
+		HasName, HasUUID,
+		categories::{DirType, NonRootFileType, NonRootItemType, Normal},
+		dir::{RemoteDirectory, meta::DirectoryMetaChanges},
+		file::{RemoteFile, meta::FileMetaChanges},
+	},
+	io::{DirCopyCallback, HasFileInfo},
+};
+
+use super::{
+	DriveServer,
+	dir::{Listing, listing_html},
+	http_date,
+};
+
+mod locks;
+mod path;
+mod xml;
+
+use locks::{LockTable, SharedLocks};
+use path::DavPath;
+
+const ALLOWED_METHODS: &str =
+	"OPTIONS, GET, HEAD, PUT, DELETE, MKCOL, COPY, MOVE, PROPFIND, PROPPATCH, LOCK, UNLOCK";
+
+/// How a WebDAV server exposes the drive
+#[derive(Clone, Debug, Default)]
+pub struct WebDavOptions {
+	/// Drive directory served as the WebDAV root, e.g. `Documents/Shared`; the whole drive when
+	/// `None`.
+	pub root: Option<String>,
+	/// Credentials clients must send with HTTP basic authentication; anyone who can reach the
+	/// server has access when `None`.
+	pub credentials: Option<WebDavCredentials>,
+	/// Rejects every method that would change the drive with `403 Forbidden`.
+	pub read_only: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct WebDavCredentials {
+	pub user: String,
+	pub password: String,
+}
+
+impl Client {
+	/// Starts a WebDAV server for this client's drive on `address`.
+	///
+	/// `DELETE` moves items to the trash rather than deleting them permanently, and `PUT` over
+	/// an existing file uploads a new version of it, so nothing a WebDAV client does is
+	/// irreversible. Dead properties aren't stored: `PROPPATCH` answers `403` for each of them.
+	pub async fn start_webdav_server(
+		self: Arc<Self>,
+		address: SocketAddr,
+		options: WebDavOptions,
+	) -> Result<DriveServer, Error> {
+		let root = DavPath::parse_drive_path(options.root.as_deref().unwrap_or_default())
+			.ok_or_else(|| Error::custom(ErrorKind::InvalidName, "invalid WebDAV root path"))?;
+		match self.find_item_at_path(&root.drive_path()).await? {
+			Some(NonRootFileType::Root(_) | NonRootFileType::Dir(_)) => {}
+			_ => {
+				return Err(Error::custom(
+					ErrorKind::FolderNotFound,
+					format!("WebDAV root {} is not a directory", root.drive_path()),
+				));
+			}
+		}
+
+		// ideally this wouldn't clone the client, see `start_http_provider`
+		let unauth_client = Arc::new(self.get_unauth_client().clone());
+		let router = axum::Router::new()
+			.fallback(dispatch)
+			.with_state(WebDavState {
+				client: self,
+				unauth_client,
+				root: Arc::new(root),
+				credentials: options.credentials.map(Arc::new),
+				read_only: options.read_only,
+				locks: Arc::new(Mutex::new(LockTable::default())),
+			});
+
+		DriveServer::serve(address, router).await
+	}
+}
+
+#[derive(Clone)]
+struct WebDavState {
+	client: Arc<Client>,
+	unauth_client: Arc<UnauthClient>,
+	/// The served directory, relative to the drive root
+	root: Arc<DavPath>,
+	credentials: Option<Arc<WebDavCredentials>>,
+	read_only: bool,
+	locks: SharedLocks,
+}
+
+impl WebDavState {
+	fn locks(&self) -> std::sync::MutexGuard<'_, LockTable> {
+		self.locks.lock().expect("webdav lock table lock poisoned")
+	}
+
+	/// Finds the item at `path`, relative to the served root
+	async fn find(&self, path: &DavPath) -> Result<Option<Item>, DavError> {
+		let drive_path = self.root.join(path).drive_path();
+		match self.client.find_item_at_path(&drive_path).await {
+			Ok(item) => Ok(item.map(|item| match item.into_owned_cow() {
+				NonRootFileType::Root(root) => Item::Dir(DirType::Root(root)),
+				NonRootFileType::Dir(dir) => Item::Dir(DirType::Dir(dir)),
+				NonRootFileType::File(file) => Item::File(file.into_owned()),
+			})),
+			// a file in the middle of the path
+			Err(e) if e.kind() == ErrorKind::InvalidType => Ok(None),
+			Err(e) => Err(e.into()),
+		}
+	}
+
+	/// Finds the directory a new item at `path` would be created in. A missing parent is a
+	/// `409 Conflict` (RFC 4918 §9.7.1).
+	async fn find_parent(&self, path: &DavPath) -> Result<DirType<'static, Normal>, DavError> {
+		let parent = path.parent().ok_or(DavError(StatusCode::FORBIDDEN))?;
+		match self.find(&parent).await? {
+			Some(Item::Dir(dir)) => Ok(dir),
+			_ => Err(DavError(StatusCode::CONFLICT)),
+		}
+	}
+
+	/// Fails with `423 Locked` unless every lock on `path` (and, with `deep`, below it) was
+	/// submitted in the `If` header.
+	fn check_locks(&self, path: &DavPath, deep: bool, headers: &HeaderMap) -> Result<(), DavError> {
+		let submitted = locks::submitted_tokens(headers);
+		if self.locks().is_unlocked_for(path, deep, &submitted) {
+			Ok(())
+		} else {
+			Err(DavError(StatusCode::LOCKED))
+		}
+	}
+}
+
+enum Item {
+	Dir(DirType<'static, Normal>),
+	File(RemoteFile),
+}
+
+/// A failed request, answered with just its status
+struct DavError(StatusCode);
+
+impl From<Error> for DavError {
+	fn from(e: Error) -> Self {
+		let status = match e.kind() {
+			ErrorKind::FileNotFound | ErrorKind::FolderNotFound => StatusCode::NOT_FOUND,
+			ErrorKind::InvalidName => StatusCode::BAD_REQUEST,
+			ErrorKind::MaxStorageReached => StatusCode::INSUFFICIENT_STORAGE,
+			_ => {
+				tracing::error!("WebDAV request failed: {e}");
+				StatusCode::INTERNAL_SERVER_ERROR
+			}
+		};
+		Self(status)
+	}
+}
+
+impl From<crate::fs::name::EntryNameError> for DavError {
+	fn from(_: crate::fs::name::EntryNameError) -> Self {
+		Self(StatusCode::BAD_REQUEST)
+	}
+}
+
+impl IntoResponse for DavError {
+	fn into_response(self) -> Response {
+		self.0.into_response()
+	}
+}
+
+type DavResult = Result<Response, DavError>;
+
+async fn dispatch(State(state): State<WebDavState>, request: Request) -> Response {
+	if let Some(credentials) = &state.credentials
+		&& !is_authorized(request.headers(), credentials)
+	{
+		return (
+			StatusCode::UNAUTHORIZED,
+			[(WWW_AUTHENTICATE, "Basic realm=\"Filen\", charset=\"UTF-8\"")],
+		)
+			.into_response();
+	}
+	let Some(path) = DavPath::parse_uri_path(request.uri().path()) else {
+		return StatusCode::BAD_REQUEST.into_response();
+	};
+
+	let method = request.method().clone();
+	let writes = !matches!(method.as_str(), "OPTIONS" | "GET" | "HEAD" | "PROPFIND");
+	if writes && state.read_only {
+		return StatusCode::FORBIDDEN.into_response();
+	}
+
+	let result = match method.as_str() {
+		"OPTIONS" => Ok(options()),
+		"GET" | "HEAD" => get(&state, &path, request).await,
+		"PUT" => put(&state, &path, request).await,
+		"DELETE" => delete(&state, &path, request.headers()).await,
+		"MKCOL" => mkcol(&state, &path, request).await,
+		"COPY" | "MOVE" => copy_or_move(&state, &path, method == "MOVE", request.headers()).await,
+		"PROPFIND" => propfind(&state, &path, request).await,
+		"PROPPATCH" => proppatch(&state, &path, request).await,
+		"LOCK" => lock(&state, &path, request).await,
+		"UNLOCK" => unlock(&state, &path, request.headers()),
+		_ => Err(DavError(StatusCode::METHOD_NOT_ALLOWED)),
+	};
+	result.unwrap_or_else(IntoResponse::into_response)
+}
+
+fn is_authorized(headers: &HeaderMap, credentials: &WebDavCredentials) -> bool {
+	let Some(decoded) = headers
+		.get(AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Basic "))
+		.and_then(|encoded| BASE64_STANDARD.decode(encoded.trim()).ok())
+		.and_then(|decoded| String::from_utf8(decoded).ok())
+	else {
+		return false;
+	};
+	let Some((user, password)) = decoded.split_once(':') else {
+		return false;
+	};
+	// constant time, so response timing doesn't tell how much of a credential matched
+	let user_matches = user.as_bytes().ct_eq(credentials.user.as_bytes());
+	let password_matches = password.as_bytes().ct_eq(credentials.password.as_bytes());
+	(user_matches & password_matches).into()
+}
+
+fn options() -> Response {
+	(
+		StatusCode::OK,
+		[
+			("DAV", "1, 2"),
+			(ALLOW.as_str(), ALLOWED_METHODS),
+			// Windows' WebDAV redirector refuses to write to servers without this
+			("MS-Author-Via", "DAV"),
+		],
+	)
+		.into_response()
+}
+
+async fn read_body(request: Request) -> Result<Vec<u8>, DavError> {
+	axum::body::to_bytes(request.into_body(), xml::MAX_BODY_SIZE)
+		.await
+		.map(|body| body.to_vec())
+		.map_err(|_| DavError(StatusCode::PAYLOAD_TOO_LARGE))
+}
+
+fn etag(file: &RemoteFile) -> String {
+	// every upload, including a new version of the same file, gets a new uuid
+	format!("\"{}\"", file.uuid())
+}
+
+async fn get(state: &WebDavState, path: &DavPath, request: Request) -> DavResult {
+	match state.find(path).await? {
+		None => Err(DavError(StatusCode::NOT_FOUND)),
+		Some(Item::Dir(_)) if !request.uri().path().ends_with('/') => {
+			// relative links in the listing need the trailing slash
+			Ok(Redirect::permanent(&path.href(true)).into_response())
+		}
+		Some(Item::Dir(dir)) => {
+			let (dirs, files) = state
+				.client
+				.list_dir(&dir, None::<&fn(u64, Option<u64>)>)
+				.await?;
+			let name = path.name().unwrap_or("/");
+			Ok(Html(listing_html(
+				name,
+				!path.is_root(),
+				&Listing { dirs, files },
+			))
+			.into_response())
+		}
+		Some(Item::File(file)) => {
+			let headers = request.headers();
+			let range = headers.typed_get::<Range>().map(TypedHeader);
+			let etag = etag(&file);
+			let last_modified = file.last_modified().map(http_date);
+			let mut response =
+				super::serve_file(&state.unauth_client, file.into(), None, headers, range);
+			let response_headers = response.headers_mut();
+			if let Ok(etag) = HeaderValue::from_str(&etag) {
+				response_headers.insert(ETAG, etag);
+			}
+			if let Some(Ok(last_modified)) = last_modified.map(|date| HeaderValue::from_str(&date))
+			{
+				response_headers.insert(LAST_MODIFIED, last_modified);
+			}
+			Ok(response)
+		}
+	}
+}
+
+async fn put(state: &WebDavState, path: &DavPath, request: Request) -> DavResult {
+	let name = path
+		.name()
+		.ok_or(DavError(StatusCode::METHOD_NOT_ALLOWED))?;
+	state.check_locks(path, false, request.headers())?;
+	let existed = match state.find(path).await? {
+		Some(Item::Dir(_)) => return Err(DavError(StatusCode::METHOD_NOT_ALLOWED)),
+		Some(Item::File(_)) => true,
+		None => false,
+	};
+	let parent = state.find_parent(path).await?;
+
+	let file = upload(state, name, &parent, request.into_body()).await?;
+	let status = if existed {
+		StatusCode::NO_CONTENT
+	} else {
+		StatusCode::CREATED
+	};
+	Ok((status, [(ETAG, etag(&file))]).into_response())
+}
+
+/// Uploads `body` as `name` into `parent`, as a new version if `parent` already has a file of
+/// that name.
+async fn upload(
+	state: &WebDavState,
+	name: &str,
+	parent: &DirType<'_, Normal>,
+	body: Body,
+) -> Result<RemoteFile, DavError> {
+	let builder = state.client.make_file_builder(name, parent.uuid())?;
+	let mut writer = state.client.get_file_writer(builder);
+	let mut body = body.into_data_stream();
+	while let Some(chunk) = body.next().await {
+		let chunk = chunk.map_err(|_| DavError(StatusCode::BAD_REQUEST))?;
+		writer.write_all(&chunk).await.map_err(Error::from)?;
+	}
+	writer.close().await.map_err(Error::from)?;
+	Ok(writer
+		.into_remote_file()
+		.expect("a closed file writer holds its remote file"))
+}
+
+async fn delete(state: &WebDavState, path: &DavPath, headers: &HeaderMap) -> DavResult {
+	if path.is_root() {
+		return Err(DavError(StatusCode::FORBIDDEN));
+	}
+	state.check_locks(path, true, headers)?;
+	match state.find(path).await? {
+		None => return Err(DavError(StatusCode::NOT_FOUND)),
+		Some(Item::File(mut file)) => state.client.trash_file(&mut file).await?,
+		Some(Item::Dir(DirType::Dir(dir))) => state.client.trash_dir(&mut dir.into_owned()).await?,
+		Some(Item::Dir(DirType::Root(_))) => return Err(DavError(StatusCode::FORBIDDEN)),
+	}
+	state.locks().remove_under(path);
+	Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+async fn mkcol(state: &WebDavState, path: &DavPath, request: Request) -> DavResult {
+	let name = path
+		.name()
+		.ok_or(DavError(StatusCode::METHOD_NOT_ALLOWED))?;
+	state.check_locks(path, false, request.headers())?;
+	if !read_body(request).await?.is_empty() {
+		return Err(DavError(StatusCode::UNSUPPORTED_MEDIA_TYPE));
+	}
+	if state.find(path).await?.is_some() {
+		return Err(DavError(StatusCode::METHOD_NOT_ALLOWED));
+	}
+	let parent = state.find_parent(path).await?;
+	state.client.create_dir(&parent, name).await?;
+	Ok(StatusCode::CREATED.into_response())
+}
+
+/// Reads the `Destination` header, which may be an absolute URL or an absolute path.
+fn destination(headers: &HeaderMap) -> Result<DavPath, DavError> {
+	let destination = headers
+		.get("Destination")
+		.and_then(|value| value.to_str().ok())
+		.ok_or(DavError(StatusCode::BAD_REQUEST))?;
+	let path = match url::Url::parse(destination) {
+		Ok(url) => Cow::Owned(url.path().to_owned()),
+		Err(_) => Cow::Borrowed(destination),
+	};
+	DavPath::parse_uri_path(&path).ok_or(DavError(StatusCode::BAD_REQUEST))
+}
+
+async fn copy_or_move(
+	state: &WebDavState,
+	path: &DavPath,
+	is_move: bool,
+	headers: &HeaderMap,
+) -> DavResult {
+	let destination = destination(headers)?;
+	let name = destination.name().ok_or(DavError(StatusCode::FORBIDDEN))?;
+	if destination.starts_with(path) || path.is_root() {
+		return Err(DavError(StatusCode::FORBIDDEN));
+	}
+	let overwrite = headers
+		.get("Overwrite")
+		.is_none_or(|value| !value.as_bytes().eq_ignore_ascii_case(b"F"));
+	// only a COPY may be shallow, and only for a collection (RFC 4918 §9.8.3)
+	let deep = is_move
+		|| headers
+			.get("Depth")
+			.is_none_or(|value| value.as_bytes() != b"0");
+
+	if is_move {
+		state.check_locks(path, true, headers)?;
+	}
+	state.check_locks(&destination, true, headers)?;
+	let source = state
+		.find(path)
+		.await?
+		.ok_or(DavError(StatusCode::NOT_FOUND))?;
+	let parent = state.find_parent(&destination).await?;
+
+	let existing = state.find(&destination).await?;
+	let existed = existing.is_some();
+	match existing {
+		Some(_) if !overwrite => return Err(DavError(StatusCode::PRECONDITION_FAILED)),
+		Some(Item::File(mut file)) => state.client.trash_file(&mut file).await?,
+		Some(Item::Dir(DirType::Dir(dir))) => state.client.trash_dir(&mut dir.into_owned()).await?,
+		Some(Item::Dir(DirType::Root(_))) => return Err(DavError(StatusCode::FORBIDDEN)),
+		None => {}
+	}
+
+	match (source, is_move) {
+		(Item::File(mut file), true) => {
+			if file.name() != Some(name) {
+				state
+					.client
+					.update_file_metadata(&mut file, FileMetaChanges::default().name(name)?)
+					.await?;
+			}
+			if file.parent != ParentUuid::Uuid(parent.uuid()) {
+				state.client.move_file(&mut file, &parent).await?;
+			}
+		}
+		(Item::Dir(DirType::Dir(dir)), true) => {
+			let mut dir = dir.into_owned();
+			if dir.name() != Some(name) {
+				state
+					.client
+					.update_dir_metadata(&mut dir, DirectoryMetaChanges::default().name(name)?)
+					.await?;
+			}
+			if dir.parent != ParentUuid::Uuid(parent.uuid()) {
+				state.client.move_dir(&mut dir, &parent).await?;
+			}
+		}
+		(Item::File(file), false) => {
+			state
+				.client
+				.copy_file(&file, &parent, Some(name), None)
+				.await?;
+		}
+		(Item::Dir(DirType::Dir(dir)), false) => {
+			copy_dir(state, &dir, name, &parent, deep).await?;
+		}
+		(Item::Dir(DirType::Root(_)), _) => return Err(DavError(StatusCode::FORBIDDEN)),
+	}
+	if is_move {
+		state.locks().remove_under(path);
+	}
+
+	let status = if existed {
+		StatusCode::NO_CONTENT
+	} else {
+		StatusCode::CREATED
+	};
+	Ok((status, [(LOCATION, destination.href(false))]).into_response())
+}
+
+/// Copies `dir` as `name` into `parent`, with its contents if `deep`.
+async fn copy_dir(
+	state: &WebDavState,
+	dir: &RemoteDirectory,
+	name: &str,
+	parent: &DirType<'_, Normal>,
+	deep: bool,
+) -> Result<RemoteDirectory, Error> {
+	if !deep {
+		return match dir.created() {
+			Some(created) => {
+				state
+					.client
+					.create_dir_with_created(parent, name, created)
+					.await
+			}
+			None => state.client.create_dir(parent, name).await,
+		};
+	}
+	let errors = CopyErrors::default();
+	let copy = state
+		.client
+		.copy_dir_recursive(dir, parent, Some(name), &errors)
+		.await?;
+	// a COPY is all or nothing for clients, so anything left out fails it
+	match errors.0.into_inner().unwrap_or_else(|e| e.into_inner()) {
+		Some(e) => Err(e),
+		None => Ok(copy),
+	}
+}
+
+/// Keeps the first failure below a directory that is being copied
+#[derive(Default)]
+struct CopyErrors(Mutex<Option<Error>>);
+
+impl DirCopyCallback for CopyErrors {
+	fn on_scan_complete(&self, _total_dirs: u64, _total_files: u64, _total_bytes: u64) {}
+
+	fn on_copy_update(
+		&self,
+		_copied_dirs: Vec<RemoteDirectory>,
+		_copied_files: Vec<RemoteFile>,
+		_copied_bytes: u64,
+	) {
+	}
+
+	fn on_copy_errors(&self, errors: Vec<(Error, NonRootItemType<'static, Normal>)>) {
+		let mut first = self.0.lock().unwrap_or_else(|e| e.into_inner());
+		if first.is_none() {
+			*first = errors.into_iter().next().map(|(e, _)| e);
+		}
+	}
+}
+
+async fn propfind(state: &WebDavState, path: &DavPath, request: Request) -> DavResult {
+	let depth = match request.headers().get("Depth").map(HeaderValue::as_bytes) {
+		Some(b"0") => 0,
+		Some(b"1") => 1,
+		// listing a whole drive in one response is what `propfind-finite-depth` exists for
+		_ => {
+			return Ok((
+				StatusCode::FORBIDDEN,
+				[(CONTENT_TYPE, xml::CONTENT_TYPE)],
+				xml::error_body("propfind-finite-depth"),
+			)
+				.into_response());
+		}
+	};
+	let request =
+		xml::parse_propfind(&read_body(request).await?).ok_or(DavError(StatusCode::BAD_REQUEST))?;
+	let item = state
+		.find(path)
+		.await?
+		.ok_or(DavError(StatusCode::NOT_FOUND))?;
+
+	let mut resources = Vec::new();
+	if let (Item::Dir(dir), 1) = (&item, depth) {
+		let (dirs, files) = state
+			.client
+			.list_dir(dir, None::<&fn(u64, Option<u64>)>)
+			.await?;
+		for dir in &dirs {
+			if let Some(name) = dir.name() {
+				let child = path.child(name);
+				resources.push(dir_resource(state, &child, Some(dir)));
+			}
+		}
+		for file in &files {
+			if let Some(name) = file.name() {
+				let child = path.child(name);
+				resources.push(file_resource(state, &child, file));
+			}
+		}
+	}
+	let resource = match &item {
+		Item::Dir(DirType::Dir(dir)) => dir_resource(state, path, Some(dir)),
+		Item::Dir(DirType::Root(_)) => dir_resource(state, path, None),
+		Item::File(file) => file_resource(state, path, file),
+	};
+	resources.insert(0, resource);
+
+	Ok(multistatus(xml::propfind_body(&request, &resources)))
+}
+
+fn dir_resource(
+	state: &WebDavState,
+	path: &DavPath,
+	dir: Option<&RemoteDirectory>,
+) -> xml::Resource {
+	xml::Resource {
+		href: path.href(true),
+		name: path.name().unwrap_or_default().to_owned(),
+		is_dir: true,
+		size: None,
+		mime: None,
+		etag: None,
+		created: dir.and_then(|dir| dir.created()),
+		modified: dir.map(|dir| dir.timestamp),
+		locks: state.locks().active_on(path),
+	}
+}
+
+fn file_resource(state: &WebDavState, path: &DavPath, file: &RemoteFile) -> xml::Resource {
+	xml::Resource {
+		href: path.href(false),
+		name: path.name().unwrap_or_default().to_owned(),
+		is_dir: false,
+		size: Some(file.size()),
+		mime: file.mime().map(str::to_owned),
+		etag: Some(etag(file)),
+		created: file.created(),
+		modified: file.last_modified(),
+		locks: state.locks().active_on(path),
+	}
+}
+
+fn multistatus(body: String) -> Response {
+	(
+		StatusCode::MULTI_STATUS,
+		[(CONTENT_TYPE, xml::CONTENT_TYPE)],
+		body,
+	)
+		.into_response()
+}
+
+async fn proppatch(state: &WebDavState, path: &DavPath, request: Request) -> DavResult {
+	state.check_locks(path, false, request.headers())?;
+	let names = xml::parse_proppatch(&read_body(request).await?)
+		.ok_or(DavError(StatusCode::BAD_REQUEST))?;
+	let item = state
+		.find(path)
+		.await?
+		.ok_or(DavError(StatusCode::NOT_FOUND))?;
+	let href = path.href(matches!(item, Item::Dir(_)));
+	Ok(multistatus(xml::proppatch_body(&href, &names)))
+}
+
+async fn lock(state: &WebDavState, path: &DavPath, request: Request) -> DavResult {
+	let timeout = locks::requested_timeout(request.headers());
+	let submitted = locks::submitted_tokens(request.headers());
+	let deep = request
+		.headers()
+		.get("Depth")
+		.is_none_or(|value| value.as_bytes() != b"0");
+	let body = read_body(request).await?;
+
+	// without a body, LOCK refreshes a lock named in the `If` header (RFC 4918 §9.10.2)
+	if body.is_empty() {
+		let lock = submitted
+			.iter()
+			.find_map(|token| state.locks().refresh(token, path, timeout))
+			.ok_or(DavError(StatusCode::PRECONDITION_FAILED))?;
+		return Ok(lock_response(StatusCode::OK, &lock));
+	}
+
+	let info = xml::parse_lockinfo(&body).ok_or(DavError(StatusCode::BAD_REQUEST))?;
+	if info.shared {
+		// every lock is backed by an exclusive `ResourceLock`
+		return Err(DavError(StatusCode::UNPROCESSABLE_ENTITY));
+	}
+	if !state.locks().is_unlocked_for(path, deep, &[]) {
+		return Err(DavError(StatusCode::LOCKED));
+	}
+
+	// locking an unmapped URL creates an empty resource there (RFC 4918 §7.3)
+	let (uuid, mut created) = match state.find(path).await? {
+		Some(Item::Dir(dir)) => (dir.uuid(), None),
+		Some(Item::File(file)) => (file.uuid(), None),
+		None => {
+			let name = path.name().ok_or(DavError(StatusCode::FORBIDDEN))?;
+			let parent = state.find_parent(path).await?;
+			let file = upload(state, name, &parent, Body::empty()).await?;
+			(file.uuid(), Some(file))
+		}
+	};
+	let result = async {
+		let resource = state
+			.client
+			.acquire_lock(
+				format!("webdav-{uuid}"),
+				locks::ACQUIRE_MAX_SLEEP,
+				locks::ACQUIRE_ATTEMPTS,
+			)
+			.await
+			.map_err(|e| match e.kind() {
+				ErrorKind::RetryFailed => DavError(StatusCode::LOCKED),
+				_ => e.into(),
+			})?;
+		state
+			.locks()
+			.insert(path.clone(), deep, info.owner, timeout, resource)
+			.ok_or(DavError(StatusCode::LOCKED))
+	}
+	.await;
+
+	match result {
+		Ok(lock) if created.is_some() => Ok(lock_response(StatusCode::CREATED, &lock)),
+		Ok(lock) => Ok(lock_response(StatusCode::OK, &lock)),
+		Err(e) => {
+			// the empty resource only exists for the lock
+			if let Some(file) = &mut created
+				&& let Err(e) = state.client.trash_file(file).await
+			{
+				tracing::warn!("failed to trash the resource of a failed WebDAV lock: {e}");
+			}
+			Err(e)
+		}
+	}
+}
+
+fn lock_response(status: StatusCode, lock: &locks::ActiveLock) -> Response {
+	(
+		status,
+		[
+			(CONTENT_TYPE, xml::CONTENT_TYPE.to_owned()),
+			("Lock-Token", format!("<{}>", lock.token)),
+		],
+		xml::lock_body(lock),
+	)
+		.into_response()
+}
+
+fn unlock(state: &WebDavState, path: &DavPath, headers: &HeaderMap) -> DavResult {
+	let token = headers
+		.get("Lock-Token")
+		.and_then(|value| value.to_str().ok())
+		.map(|value| value.trim().trim_start_matches('<').trim_end_matches('>'))
+		.ok_or(DavError(StatusCode::BAD_REQUEST))?;
+	if state.locks().remove(token, path) {
+		Ok(StatusCode::NO_CONTENT.into_response())
+	} else {
+		Err(DavError(StatusCode::CONFLICT))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use base64::{Engine, prelude::BASE64_STANDARD};
+	use http::{HeaderMap, HeaderValue, header::AUTHORIZATION};
+
+	use super::{WebDavCredentials, is_authorized};
+
+	#[test]
+	fn basic_auth_requires_matching_credentials() {
+		let credentials = WebDavCredentials {
+			user: "user".to_owned(),
+			password: "pa:ss".to_owned(),
+		};
+		let headers = |value: &str| {
+			let mut headers = HeaderMap::new();
+			headers.insert(
+				AUTHORIZATION,
+				HeaderValue::from_str(&format!("Basic {}", BASE64_STANDARD.encode(value))).unwrap(),
+			);
+			headers
+		};
+		assert!(is_authorized(&headers("user:pa:ss"), &credentials));
+		assert!(!is_authorized(&headers("user:wrong"), &credentials));
+		assert!(!is_authorized(&headers("other:pa:ss"), &credentials));
+		assert!(!is_authorized(&HeaderMap::new(), &credentials));
+	}
+}
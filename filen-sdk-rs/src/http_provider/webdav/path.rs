@@ -0,0 +1,119 @@
+//! Paths of WebDAV resources, as decoded name segments.
+
+/// A path below the served root (or, for the root itself, below the drive root). The root is
+/// the empty path, and segments are decoded names.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(super) struct DavPath {
+	segments: Vec<String>,
+}
+
+impl DavPath {
+	/// Parses the percent-encoded path of a request URI. `.` and `..` segments are rejected
+	/// rather than resolved, so a request can't reach outside the served root, and so are
+	/// encoded slashes, which no drive item can have in its name.
+	pub(super) fn parse_uri_path(path: &str) -> Option<Self> {
+		let segments = path
+			.split('/')
+			.filter(|segment| !segment.is_empty())
+			.map(|segment| urlencoding::decode(segment).ok().map(|s| s.into_owned()))
+			.collect::<Option<Vec<_>>>()?;
+		Self::from_segments(segments)
+	}
+
+	/// Parses a plain drive path like `Documents/Shared`.
+	pub(super) fn parse_drive_path(path: &str) -> Option<Self> {
+		Self::from_segments(
+			path.split('/')
+				.filter(|segment| !segment.is_empty())
+				.map(str::to_owned)
+				.collect(),
+		)
+	}
+
+	fn from_segments(segments: Vec<String>) -> Option<Self> {
+		if segments
+			.iter()
+			.any(|segment| segment == "." || segment == ".." || segment.contains('/'))
+		{
+			return None;
+		}
+		Some(Self { segments })
+	}
+
+	pub(super) fn is_root(&self) -> bool {
+		self.segments.is_empty()
+	}
+
+	pub(super) fn name(&self) -> Option<&str> {
+		self.segments.last().map(String::as_str)
+	}
+
+	pub(super) fn parent(&self) -> Option<Self> {
+		let (_, parent) = self.segments.split_last()?;
+		Some(Self {
+			segments: parent.to_vec(),
+		})
+	}
+
+	pub(super) fn child(&self, name: &str) -> Self {
+		let mut segments = self.segments.clone();
+		segments.push(name.to_owned());
+		Self { segments }
+	}
+
+	pub(super) fn join(&self, other: &Self) -> Self {
+		Self {
+			segments: [self.segments.as_slice(), other.segments.as_slice()].concat(),
+		}
+	}
+
+	/// Whether `self` is `other` or below it
+	pub(super) fn starts_with(&self, other: &Self) -> bool {
+		self.segments.starts_with(&other.segments)
+	}
+
+	/// The path in the form [`Client::find_item_at_path`](crate::auth::Client::find_item_at_path)
+	/// takes
+	pub(super) fn drive_path(&self) -> String {
+		self.segments.join("/")
+	}
+
+	/// The percent-encoded absolute URL path, with a trailing slash for collections
+	pub(super) fn href(&self, is_dir: bool) -> String {
+		let mut href = String::from("/");
+		for segment in &self.segments {
+			href.push_str(&urlencoding::encode(segment));
+			href.push('/');
+		}
+		if !is_dir && !self.is_root() {
+			href.pop();
+		}
+		href
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::DavPath;
+
+	#[test]
+	fn uri_paths_are_decoded_and_normalized() {
+		let path = DavPath::parse_uri_path("/docs//a%20b/file.txt/").unwrap();
+		assert_eq!(path.drive_path(), "docs/a b/file.txt");
+		assert_eq!(path.name(), Some("file.txt"));
+		assert_eq!(path.parent().unwrap().drive_path(), "docs/a b");
+		assert!(DavPath::parse_uri_path("/").unwrap().is_root());
+		assert!(DavPath::parse_uri_path("/docs/../secret").is_none());
+		assert!(DavPath::parse_uri_path("/docs/%2E%2E/secret").is_none());
+		assert!(DavPath::parse_uri_path("/docs/a%2Fb").is_none());
+	}
+
+	#[test]
+	fn hrefs_are_encoded() {
+		let path = DavPath::parse_uri_path("/a%20b/c%23d").unwrap();
+		assert_eq!(path.href(false), "/a%20b/c%23d");
+		assert_eq!(path.href(true), "/a%20b/c%23d/");
+		assert_eq!(DavPath::default().href(true), "/");
+		assert_eq!(DavPath::default().href(false), "/");
+	}
+}
@@ -0,0 +1,420 @@
+//! The XML bodies of `PROPFIND`, `PROPPATCH` and `LOCK` requests and their responses.
+
+use std::fmt::Write;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use quick_xml::{
+	escape::escape,
+	events::Event,
+	name::{Namespace, ResolveResult},
+	reader::NsReader,
+};
+
+use super::locks::ActiveLock;
+
+pub(super) const CONTENT_TYPE: &str = "application/xml; charset=utf-8";
+/// Request bodies are property lists, so anything bigger isn't a real client
+pub(super) const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+const DAV: &str = "DAV:";
+/// The live properties of RFC 4918 §15, in the order they are reported
+const LIVE_PROPS: [&str; 9] = [
+	"creationdate",
+	"displayname",
+	"getcontentlength",
+	"getcontenttype",
+	"getetag",
+	"getlastmodified",
+	"lockdiscovery",
+	"resourcetype",
+	"supportedlock",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct PropName {
+	namespace: String,
+	name: String,
+}
+
+impl PropName {
+	fn dav(name: &str) -> Self {
+		Self {
+			namespace: DAV.to_owned(),
+			name: name.to_owned(),
+		}
+	}
+
+	fn is_dav(&self, name: &str) -> bool {
+		self.namespace == DAV && self.name == name
+	}
+
+	/// Renders this property as an element with `content`
+	fn element(&self, content: &str) -> String {
+		let name = &self.name;
+		match (self.namespace.as_str(), content.is_empty()) {
+			(DAV, true) => format!("<D:{name}/>"),
+			(DAV, false) => format!("<D:{name}>{content}</D:{name}>"),
+			(namespace, true) => format!("<{name} xmlns=\"{}\"/>", escape(namespace)),
+			(namespace, false) => {
+				format!("<{name} xmlns=\"{}\">{content}</{name}>", escape(namespace))
+			}
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Propfind {
+	AllProp,
+	PropName,
+	Prop(Vec<PropName>),
+}
+
+/// Walks the elements of `body`, passing each one and the text inside them to the callbacks
+/// along with the elements enclosing it. `None` if `body` isn't well-formed XML.
+fn walk(
+	body: &[u8],
+	mut on_element: impl FnMut(&[PropName], PropName),
+	mut on_text: impl FnMut(&[PropName], &str),
+) -> Option<()> {
+	let mut reader = NsReader::from_reader(body);
+	reader.config_mut().trim_text(true);
+	let mut stack = Vec::new();
+	loop {
+		let (namespace, event) = reader.read_resolved_event().ok()?;
+		let namespace = match namespace {
+			ResolveResult::Bound(Namespace(namespace)) => {
+				String::from_utf8_lossy(namespace).into_owned()
+			}
+			ResolveResult::Unbound => String::new(),
+			ResolveResult::Unknown(_) => return None,
+		};
+		match event {
+			Event::Start(element) | Event::Empty(element) => {
+				let name = PropName {
+					namespace,
+					name: String::from_utf8_lossy(element.local_name().as_ref()).into_owned(),
+				};
+				on_element(&stack, name.clone());
+				if matches!(event, Event::Start(_)) {
+					stack.push(name);
+				}
+			}
+			Event::End(_) => {
+				stack.pop();
+			}
+			Event::Text(text) => on_text(&stack, &text.unescape().ok()?),
+			Event::Eof => return Some(()),
+			_ => {}
+		}
+	}
+}
+
+fn is_path(stack: &[PropName], path: &[&str]) -> bool {
+	stack.len() == path.len()
+		&& stack
+			.iter()
+			.zip(path)
+			.all(|(element, name)| element.is_dav(name))
+}
+
+/// Parses a `PROPFIND` body; an empty body asks for all properties (RFC 4918 §9.1).
+pub(super) fn parse_propfind(body: &[u8]) -> Option<Propfind> {
+	if body.iter().all(u8::is_ascii_whitespace) {
+		return Some(Propfind::AllProp);
+	}
+	let mut request = None;
+	let mut props = Vec::new();
+	walk(
+		body,
+		|stack, element| {
+			if is_path(stack, &["propfind"]) {
+				if element.is_dav("allprop") {
+					request = Some(Propfind::AllProp);
+				} else if element.is_dav("propname") {
+					request = Some(Propfind::PropName);
+				}
+			} else if is_path(stack, &["propfind", "prop"]) {
+				props.push(element);
+			}
+		},
+		|_, _| {},
+	)?;
+	request.or_else(|| (!props.is_empty()).then_some(Propfind::Prop(props)))
+}
+
+/// Parses the names of the properties a `PROPPATCH` body sets or removes.
+pub(super) fn parse_proppatch(body: &[u8]) -> Option<Vec<PropName>> {
+	let mut names = Vec::new();
+	let mut is_update = false;
+	walk(
+		body,
+		|stack, element| {
+			if stack.is_empty() {
+				is_update = element.is_dav("propertyupdate");
+			} else if is_path(stack, &["propertyupdate", "set", "prop"])
+				|| is_path(stack, &["propertyupdate", "remove", "prop"])
+			{
+				names.push(element);
+			}
+		},
+		|_, _| {},
+	)?;
+	is_update.then_some(names)
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(super) struct LockInfo {
+	pub(super) shared: bool,
+	/// The text of the `owner` element, usually a URL or an account name
+	pub(super) owner: Option<String>,
+}
+
+/// Parses a `LOCK` body that creates a lock.
+pub(super) fn parse_lockinfo(body: &[u8]) -> Option<LockInfo> {
+	let mut info = LockInfo::default();
+	let mut is_lockinfo = false;
+	walk(
+		body,
+		|stack, element| {
+			if stack.is_empty() {
+				is_lockinfo = element.is_dav("lockinfo");
+			} else if is_path(stack, &["lockinfo", "lockscope"]) {
+				info.shared = element.is_dav("shared");
+			}
+		},
+		|stack, text| {
+			if stack.len() >= 2 && is_path(&stack[..2], &["lockinfo", "owner"]) {
+				info.owner.get_or_insert_default().push_str(text);
+			}
+		},
+	)?;
+	is_lockinfo.then_some(info)
+}
+
+/// What a `PROPFIND` reports about one file or directory
+pub(super) struct Resource {
+	pub(super) href: String,
+	pub(super) name: String,
+	pub(super) is_dir: bool,
+	pub(super) size: Option<u64>,
+	pub(super) mime: Option<String>,
+	pub(super) etag: Option<String>,
+	pub(super) created: Option<DateTime<Utc>>,
+	pub(super) modified: Option<DateTime<Utc>>,
+	pub(super) locks: Vec<ActiveLock>,
+}
+
+impl Resource {
+	/// The content of the live property `name`, `None` if this resource doesn't have it
+	fn live_prop(&self, name: &str) -> Option<String> {
+		Some(match name {
+			"creationdate" => self
+				.created
+				.or(self.modified)?
+				.to_rfc3339_opts(SecondsFormat::Secs, true),
+			"displayname" => escape(&self.name).into_owned(),
+			"getcontentlength" => self.size?.to_string(),
+			"getcontenttype" => escape(self.mime.as_deref()?).into_owned(),
+			"getetag" => escape(self.etag.as_deref()?).into_owned(),
+			"getlastmodified" => super::http_date(self.modified?),
+			"lockdiscovery" => self.locks.iter().map(active_lock).collect(),
+			"resourcetype" if self.is_dir => "<D:collection/>".to_owned(),
+			"resourcetype" => String::new(),
+			"supportedlock" => "<D:lockentry><D:lockscope><D:exclusive/></D:lockscope>\
+				<D:locktype><D:write/></D:locktype></D:lockentry>"
+				.to_owned(),
+			_ => return None,
+		})
+	}
+}
+
+fn status_line(status: &str) -> String {
+	format!("<D:status>HTTP/1.1 {status}</D:status>")
+}
+
+fn propstat(props: &str, status: &str) -> String {
+	format!(
+		"<D:propstat><D:prop>{props}</D:prop>{}</D:propstat>",
+		status_line(status)
+	)
+}
+
+fn multistatus(responses: &str) -> String {
+	format!(
+		"<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+		<D:multistatus xmlns:D=\"DAV:\">{responses}</D:multistatus>\n"
+	)
+}
+
+pub(super) fn propfind_body(request: &Propfind, resources: &[Resource]) -> String {
+	let mut responses = String::new();
+	for resource in resources {
+		let (mut found, mut missing) = (String::new(), String::new());
+		match request {
+			Propfind::AllProp | Propfind::PropName => {
+				for name in LIVE_PROPS {
+					if let Some(value) = resource.live_prop(name) {
+						let value = match request {
+							Propfind::PropName => "",
+							_ => &value,
+						};
+						found.push_str(&PropName::dav(name).element(value));
+					}
+				}
+			}
+			Propfind::Prop(names) => {
+				for name in names {
+					let value = (name.namespace == DAV)
+						.then(|| resource.live_prop(&name.name))
+						.flatten();
+					match value {
+						Some(value) => found.push_str(&name.element(&value)),
+						None => missing.push_str(&name.element("")),
+					}
+				}
+			}
+		}
+
+		let _ = write!(
+			responses,
+			"<D:response><D:href>{}</D:href>",
+			escape(&resource.href)
+		);
+		if !found.is_empty() {
+			responses.push_str(&propstat(&found, "200 OK"));
+		}
+		if !missing.is_empty() {
+			responses.push_str(&propstat(&missing, "404 Not Found"));
+		}
+		responses.push_str("</D:response>");
+	}
+	multistatus(&responses)
+}
+
+/// Dead properties aren't stored, so every property a `PROPPATCH` names is refused.
+pub(super) fn proppatch_body(href: &str, names: &[PropName]) -> String {
+	let props = names
+		.iter()
+		.map(|name| name.element(""))
+		.collect::<String>();
+	multistatus(&format!(
+		"<D:response><D:href>{}</D:href>{}</D:response>",
+		escape(href),
+		propstat(&props, "403 Forbidden")
+	))
+}
+
+fn active_lock(lock: &ActiveLock) -> String {
+	let mut xml = format!(
+		"<D:activelock><D:locktype><D:write/></D:locktype>\
+		<D:lockscope><D:exclusive/></D:lockscope><D:depth>{}</D:depth>",
+		if lock.deep { "infinity" } else { "0" }
+	);
+	if let Some(owner) = &lock.owner {
+		let _ = write!(xml, "<D:owner>{}</D:owner>", escape(owner));
+	}
+	let _ = write!(
+		xml,
+		"<D:timeout>Second-{}</D:timeout>\
+		<D:locktoken><D:href>{}</D:href></D:locktoken>\
+		<D:lockroot><D:href>{}</D:href></D:lockroot></D:activelock>",
+		lock.timeout.as_secs(),
+		escape(&lock.token),
+		escape(&lock.root)
+	);
+	xml
+}
+
+/// The body of a successful `LOCK`
+pub(super) fn lock_body(lock: &ActiveLock) -> String {
+	format!(
+		"<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+		<D:prop xmlns:D=\"DAV:\"><D:lockdiscovery>{}</D:lockdiscovery></D:prop>\n",
+		active_lock(lock)
+	)
+}
+
+/// A `DAV:error` body naming the precondition a request failed
+pub(super) fn error_body(condition: &str) -> String {
+	format!(
+		"<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+		<D:error xmlns:D=\"DAV:\"><D:{condition}/></D:error>\n"
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{LockInfo, PropName, Propfind, parse_lockinfo, parse_propfind, parse_proppatch};
+
+	#[test]
+	fn propfind_bodies() {
+		assert_eq!(parse_propfind(b""), Some(Propfind::AllProp));
+		assert_eq!(
+			parse_propfind(
+				br#"<?xml version="1.0"?><propfind xmlns="DAV:"><propname/></propfind>"#
+			),
+			Some(Propfind::PropName)
+		);
+		assert_eq!(
+			parse_propfind(
+				br#"<D:propfind xmlns:D="DAV:" xmlns:z="urn:z">
+					<D:prop><D:getetag/><z:color/></D:prop>
+				</D:propfind>"#
+			),
+			Some(Propfind::Prop(vec![
+				PropName::dav("getetag"),
+				PropName {
+					namespace: "urn:z".to_owned(),
+					name: "color".to_owned(),
+				},
+			]))
+		);
+		assert_eq!(parse_propfind(b"<propfind xmlns=\"DAV:\">"), None);
+		assert_eq!(parse_propfind(b"<other xmlns=\"DAV:\"/>"), None);
+	}
+
+	#[test]
+	fn proppatch_bodies() {
+		assert_eq!(
+			parse_proppatch(
+				br#"<D:propertyupdate xmlns:D="DAV:" xmlns:Z="urn:schemas-microsoft-com:">
+					<D:set><D:prop><Z:Win32LastAccessTime>x</Z:Win32LastAccessTime></D:prop></D:set>
+					<D:remove><D:prop><D:displayname/></D:prop></D:remove>
+				</D:propertyupdate>"#
+			),
+			Some(vec![
+				PropName {
+					namespace: "urn:schemas-microsoft-com:".to_owned(),
+					name: "Win32LastAccessTime".to_owned(),
+				},
+				PropName::dav("displayname"),
+			])
+		);
+	}
+
+	#[test]
+	fn lockinfo_bodies() {
+		assert_eq!(
+			parse_lockinfo(
+				br#"<D:lockinfo xmlns:D="DAV:">
+					<D:lockscope><D:exclusive/></D:lockscope>
+					<D:locktype><D:write/></D:locktype>
+					<D:owner><D:href>mailto:a&amp;b@example.com</D:href></D:owner>
+				</D:lockinfo>"#
+			),
+			Some(LockInfo {
+				shared: false,
+				owner: Some("mailto:a&b@example.com".to_owned()),
+			})
+		);
+		assert_eq!(
+			parse_lockinfo(
+				br#"<lockinfo xmlns="DAV:"><lockscope><shared/></lockscope></lockinfo>"#
+			),
+			Some(LockInfo {
+				shared: true,
+				owner: None,
+			})
+		);
+	}
+}
@@ -8,7 +8,7 @@ use std::{
 use filen_types::crypto::Blake3Hash;
 use futures::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
 #[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
-use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
+use tokio_util::compat::TokioAsyncReadCompatExt;
 
 use crate::{
 	Error,
@@ -16,6 +16,7 @@ use crate::{
 	consts::CHUNK_SIZE_U64,
 	fs::file::{
 		BaseFile, FileBuilder, RemoteFile,
+		checkpoint::{UploadCheckpoint, UploadCheckpointStore},
 		client_impl::{FileReaderSharedClientExt, build_exif_tee_from_builder},
 		exif::ExifTeeState,
 		traits::File,
@@ -32,7 +33,7 @@ use crate::{
 		categories::{DirType, Normal},
 		file::FileBuilderOptionalName,
 	},
-	io::{CanonicalPath, FilenMetaExt, meta_ext::FileTimesExt},
+	io::{CanonicalPath, FilenMetaExt, meta_ext::FileTimesExt, partial_download},
 };
 
 const IO_BUFFER_SIZE: usize = 1024 * 64; // 64 KiB
@@ -49,6 +50,7 @@ impl Client {
 		callback: Option<MaybeSendCallback<'a, u64>>,
 		known_size: Option<u64>,
 		confirm_completion_callback: Option<F>,
+		checkpoints: Option<(&'a dyn UploadCheckpointStore, Option<UploadCheckpoint>)>,
 	) -> Result<RemoteFile, Error>
 	where
 		T: 'a + AsyncReadExt + Unpin,
@@ -66,6 +68,7 @@ impl Client {
 			known_size,
 			confirm_completion_callback,
 			exif_tee,
+			checkpoints,
 		)
 		.await
 	}
@@ -79,6 +82,7 @@ impl Client {
 		known_size: Option<u64>,
 		confirm_completion_callback: Option<F>,
 		exif_tee: Option<ExifTeeState>,
+		checkpoints: Option<(&'a dyn UploadCheckpointStore, Option<UploadCheckpoint>)>,
 	) -> Result<RemoteFile, Error>
 	where
 		T: 'a + AsyncReadExt + Unpin,
@@ -93,6 +97,12 @@ impl Client {
 			confirm_completion_callback,
 			exif_tee,
 		);
+		if let Some((store, resume)) = checkpoints {
+			writer = writer.with_checkpoint_store(store);
+			if let Some(checkpoint) = resume {
+				writer = writer.resume_from(checkpoint)?;
+			}
+		}
 		let buffer_size = known_size
 			.map(|size| std::cmp::min(size, CHUNK_SIZE_U64) as usize)
 			.unwrap_or(IO_BUFFER_SIZE);
@@ -123,7 +133,37 @@ impl Client {
 		T: 'a + AsyncReadExt + Unpin,
 	{
 		self.inner_upload_from_builder::<T, fn(Blake3Hash, u64) -> DummyFuture, DummyFuture>(
-			builder, reader, callback, known_size, None,
+			builder, reader, callback, known_size, None, None,
+		)
+		.await
+	}
+
+	/// Like [`upload_file_from_reader`](Self::upload_file_from_reader), but saves a checkpoint to
+	/// `store` each time the server confirms a chunk and continues the upload `resume` was saved
+	/// from, if given.
+	///
+	/// When resuming, `reader` has to start at [`UploadCheckpoint::resume_offset`], while
+	/// `known_size` is still the size of the whole file. See [`FileWriter::resume_from`].
+	#[tracing::instrument(level = "debug", name = "upload_file_resumable", skip_all)]
+	pub async fn upload_file_from_reader_resumable<'a, T>(
+		&'a self,
+		builder: FileBuilder,
+		reader: &mut T,
+		callback: Option<MaybeSendCallback<'a, u64>>,
+		known_size: Option<u64>,
+		store: &'a dyn UploadCheckpointStore,
+		resume: Option<UploadCheckpoint>,
+	) -> Result<RemoteFile, Error>
+	where
+		T: 'a + AsyncReadExt + Unpin,
+	{
+		self.inner_upload_from_builder::<T, fn(Blake3Hash, u64) -> DummyFuture, DummyFuture>(
+			builder,
+			reader,
+			callback,
+			known_size,
+			None,
+			Some((store, resume)),
 		)
 		.await
 	}
@@ -177,6 +217,44 @@ impl Client {
 			.await
 	}
 
+	/// Like [`Client::upload_dir_recursively`], but skips every entry for which
+	/// `filter(path, is_dir)` returns `false`. Skipped directories are not descended into.
+	#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
+	#[tracing::instrument(name = "upload_dir_recursively_filtered", skip_all, fields(dir_path = %dir_path.display()))]
+	pub async fn upload_dir_recursively_filtered<C>(
+		self: Arc<Self>,
+		dir_path: PathBuf,
+		callback: impl Deref<Target = C>,
+		target: &crate::fs::dir::RemoteDirectory,
+		filter: impl FnMut(&Path, bool) -> bool,
+	) -> Result<(), Error>
+	where
+		C: super::dir_upload::DirUploadCallback + ?Sized,
+	{
+		use crate::util::AtomicDropCanceller;
+
+		let drop_canceller = AtomicDropCanceller::default();
+		let ref_callback = callback.deref();
+
+		let (tree, stats) = super::fs_tree::build_filtered_fs_tree_from_walkdir_iterator(
+			&dir_path,
+			filter,
+			&mut |errors| {
+				ref_callback.on_scan_errors(errors);
+			},
+			&mut |dirs, files, bytes| {
+				ref_callback.on_scan_progress(dirs, files, bytes);
+			},
+			drop_canceller.cancelled(),
+		)?;
+
+		let (dirs, files, bytes) = stats.snapshot();
+		ref_callback.on_scan_complete(dirs, files, bytes);
+
+		self.upload_fs_tree_from_path_into_target(callback, dir_path, &tree, target)
+			.await
+	}
+
 	// #[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
 	// pub async fn download_dir_recursively<C>(
 	// 	self: Arc<Self>,
@@ -252,9 +330,47 @@ impl Client {
 		path: PathBuf,
 		callback: Option<MaybeSendCallback<'_, u64>>,
 	) -> Result<(RemoteFile, std::fs::File), Error> {
-		let (meta, file, path) = tokio::task::spawn_blocking(|| {
-			let file = std::fs::File::open(&path)?;
+		self.inner_upload_file_from_path(builder, path, callback, None)
+			.await
+	}
+
+	/// Like [`upload_file_from_path_with_builder`](Self::upload_file_from_path_with_builder),
+	/// with checkpoints like
+	/// [`upload_file_from_reader_resumable`](Self::upload_file_from_reader_resumable).
+	///
+	/// A resumed upload reads the file from [`UploadCheckpoint::resume_offset`]. Whether the file
+	/// is still the one the checkpoint was saved for is up to the caller.
+	#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
+	pub async fn upload_file_from_path_resumable<'a>(
+		&'a self,
+		builder: FileBuilderOptionalName,
+		path: PathBuf,
+		callback: Option<MaybeSendCallback<'a, u64>>,
+		store: &'a dyn UploadCheckpointStore,
+		resume: Option<UploadCheckpoint>,
+	) -> Result<(RemoteFile, std::fs::File), Error> {
+		self.inner_upload_file_from_path(builder, path, callback, Some((store, resume)))
+			.await
+	}
+
+	#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
+	async fn inner_upload_file_from_path<'a>(
+		&'a self,
+		builder: FileBuilderOptionalName,
+		path: PathBuf,
+		callback: Option<MaybeSendCallback<'a, u64>>,
+		checkpoints: Option<(&'a dyn UploadCheckpointStore, Option<UploadCheckpoint>)>,
+	) -> Result<(RemoteFile, std::fs::File), Error> {
+		let resume_offset = checkpoints
+			.as_ref()
+			.and_then(|(_, resume)| resume.as_ref())
+			.map_or(0, UploadCheckpoint::resume_offset);
+		let (meta, file, path) = tokio::task::spawn_blocking(move || {
+			use std::io::Seek;
+
+			let mut file = std::fs::File::open(&path)?;
 			let meta = file.metadata()?;
+			file.seek(std::io::SeekFrom::Start(resume_offset))?;
 			Ok::<_, std::io::Error>((meta, file, path))
 		})
 		.await
@@ -329,6 +445,7 @@ impl Client {
 					}
 					Ok(())
 				}),
+				checkpoints,
 			)
 			.await?;
 		Ok((uploaded, reader.into_inner().into_std().await))
@@ -341,6 +458,9 @@ impl Client {
 /// `<uuid>.filendl` inside the sync tree, where the next scan pass — which only
 /// filters the quarantine dir — would treat it as a new local file and upload
 /// the partial garbage.
+///
+/// A temp file whose sidecar recorded a chunk is kept instead, for the next
+/// download of the file to resume (see [`partial_download`](super::partial_download)).
 #[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
 struct TmpFileGuard {
 	path: Option<PathBuf>,
@@ -362,7 +482,9 @@ impl TmpFileGuard {
 #[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
 impl Drop for TmpFileGuard {
 	fn drop(&mut self) {
-		if let Some(path) = self.path.take() {
+		if let Some(path) = self.path.take()
+			&& !partial_download::sidecar_path(&path).exists()
+		{
 			let _ = std::fs::remove_file(path);
 		}
 	}
@@ -393,25 +515,16 @@ where
 	let tmp_path = parent
 		.join(remote_file.uuid().to_string())
 		.with_extension("filendl");
-	let tmp_file = tokio::fs::OpenOptions::new()
-		.write(true)
-		.create(true)
-		.truncate(true)
-		.open(&tmp_path)
-		.await?;
-	// Arm cleanup now that the temp file exists: every path out of this function
+	// Arm cleanup before the temp file is created: every path out of this function
 	// before the final rename must unlink it rather than leak it into the sync
-	// tree.
+	// tree, unless it can be resumed.
 	let mut tmp_guard = TmpFileGuard::new(tmp_path.clone());
-	let mut writer = tmp_file.compat_write();
-
-	unauth_client
-		.download_file_to_writer(remote_file, &mut writer, callback)
-		.await?;
+	let tmp_file =
+		partial_download::download_to_partial_file(unauth_client, remote_file, &tmp_path, callback)
+			.await?;
 
 	let file_times = FileTimesExt::get_file_times(remote_file);
 
-	let tmp_file = writer.into_inner().into_std().await;
 	tokio::task::spawn_blocking(move || tmp_file.set_times(file_times))
 		.await
 		.unwrap()?;
@@ -422,7 +535,7 @@ where
 	// which are not widely supported across platforms
 	// This at least covers the common case where the file is modified while we are downloading
 	if let Some(mod_time) = mod_time {
-		let current_meta = tokio::fs::metadata(&tmp_path).await?;
+		let current_meta = tokio::fs::metadata(path).await?;
 		let current_mod_time = FilenMetaExt::modified(&current_meta);
 		if current_mod_time != mod_time {
 			return Err(Error::custom(
@@ -432,6 +545,8 @@ where
 		}
 	}
 
+	// the sidecar goes first, a temp file without one is started over rather than resumed
+	partial_download::remove_if_exists(&partial_download::sidecar_path(&tmp_path))?;
 	tokio::fs::rename(&tmp_path, path).await?;
 	// Committed: the temp file no longer exists under its old name, so disarm
 	// cleanup to avoid removing the file we just placed.
@@ -529,6 +644,12 @@ pub trait IoSharedClientExt<'a>: SharedClient {
 	// and finally shrinking the buffer to file.size()
 	async fn download_file(&self, file: &dyn File) -> Result<Vec<u8>, Error>;
 
+	/// Downloads `remote_file` to `path`, unless the file there already has the same contents.
+	///
+	/// The download goes to `<uuid>.filendl` next to `path` first. If it's interrupted, that
+	/// file is kept, and the next download of `remote_file` into the same directory only
+	/// fetches the chunks it's missing. The result is checked against the file's hash before
+	/// it replaces `path`.
 	#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
 	async fn download_file_to_path<'b>(
 		&'b self,
@@ -0,0 +1,258 @@
+//! Copies of files and directory trees within the drive.
+//!
+//! The backend has no copy endpoint, so copies are streamed: every file is downloaded and
+//! uploaded again chunk by chunk, holding no more than the client's file-IO memory budget
+//! allows. Dropping a copy's future cancels it; whatever was created by then stays.
+
+#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
+use std::{
+	borrow::Cow,
+	collections::HashMap,
+	ops::Deref,
+	sync::{
+		Arc,
+		atomic::{AtomicU64, Ordering},
+	},
+};
+
+#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
+use filen_types::fs::{ParentUuid, Uuid};
+#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
+use futures::StreamExt;
+
+use crate::{
+	Error, ErrorKind,
+	auth::Client,
+	fs::{
+		HasName, HasUUID,
+		categories::{DirType, Normal},
+		file::{RemoteFile, client_impl::FileReaderSharedClientExt, traits::HasFileInfo},
+	},
+	util::MaybeSendCallback,
+};
+#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
+use crate::{
+	auth::shared_client::SharedClient,
+	consts::{CALLBACK_INTERVAL, MAX_SMALL_PARALLEL_REQUESTS},
+	fs::{categories::NonRootItemType, dir::RemoteDirectory, dir::traits::HasDirInfo},
+	util::MaybeArc,
+};
+
+/// Callback trait for directory copies
+///
+/// Directory copies list the whole source tree first, then recreate it below the target one
+/// level at a time, copying files once their directory exists.
+#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
+pub trait DirCopyCallback: Send + Sync {
+	/// Called once the source tree was listed
+	fn on_scan_complete(&self, total_dirs: u64, total_files: u64, total_bytes: u64);
+	/// Called periodically with the copies made and the bytes uploaded since the last call
+	fn on_copy_update(
+		&self,
+		copied_dirs: Vec<RemoteDirectory>,
+		copied_files: Vec<RemoteFile>,
+		copied_bytes: u64,
+	);
+	/// Called with the source items that failed to copy; nothing below a failed directory is
+	/// copied
+	fn on_copy_errors(&self, errors: Vec<(Error, NonRootItemType<'static, Normal>)>);
+}
+
+fn undecrypted_name() -> Error {
+	Error::custom(
+		ErrorKind::MetadataWasNotDecrypted,
+		"can't copy an item whose name failed to decrypt",
+	)
+}
+
+impl Client {
+	/// Copies `file` into `parent`, named `name` or like the original when `None`.
+	///
+	/// The copy keeps the original's MIME type and creation and modification times. `callback`
+	/// is called with the number of bytes uploaded as the copy progresses.
+	pub async fn copy_file(
+		&self,
+		file: &RemoteFile,
+		parent: &DirType<'_, Normal>,
+		name: Option<&str>,
+		callback: Option<MaybeSendCallback<'_, u64>>,
+	) -> Result<RemoteFile, Error> {
+		let name = match name {
+			Some(name) => name,
+			None => file.name().ok_or_else(undecrypted_name)?,
+		};
+		// the original's times are kept even if the copy's EXIF data disagrees
+		let mut builder = self
+			.make_file_builder(name, parent.uuid())?
+			.no_exif_override();
+		if let Some(mime) = file.mime() {
+			builder = builder.mime(mime.to_owned());
+		}
+		if let Some(created) = file.created() {
+			builder = builder.created(created);
+		}
+		if let Some(modified) = file.last_modified() {
+			builder = builder.modified(modified);
+		}
+		let mut reader = self.get_file_reader(file);
+		self.upload_file_from_reader(builder, &mut reader, callback, Some(file.size()))
+			.await
+	}
+
+	/// Copies `dir` and everything in it into `parent`, named `name` or like the original when
+	/// `None`, returning the new directory.
+	///
+	/// Only failing to list `dir` or to create its copy fails the whole operation; failures
+	/// below it are reported to `callback` and skipped.
+	#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
+	pub async fn copy_dir_recursive<C>(
+		&self,
+		dir: &RemoteDirectory,
+		parent: &DirType<'_, Normal>,
+		name: Option<&str>,
+		callback: impl Deref<Target = C>,
+	) -> Result<RemoteDirectory, Error>
+	where
+		C: DirCopyCallback + ?Sized,
+	{
+		let callback = callback.deref();
+		let name = match name {
+			Some(name) => name,
+			None => dir.name().ok_or_else(undecrypted_name)?,
+		};
+		let (dirs, files) = self
+			.list_dir_recursive::<Normal, fn(u64, Option<u64>)>(
+				&DirType::Dir(Cow::Borrowed(dir)),
+				None,
+				(),
+			)
+			.await?;
+		callback.on_scan_complete(
+			dirs.len() as u64,
+			files.len() as u64,
+			files.iter().map(|file| file.size()).sum(),
+		);
+
+		let root = self.copy_dir_shallow(dir, parent, name).await?;
+		// copies by the uuid of their original
+		let mut copies = HashMap::from([(dir.uuid(), root.clone())]);
+		let mut children = HashMap::<Uuid, Vec<RemoteDirectory>>::new();
+		for dir in dirs {
+			if let ParentUuid::Uuid(parent) = dir.parent {
+				children.entry(parent).or_default().push(dir);
+			}
+		}
+
+		let mut errors = Vec::new();
+		let mut level = vec![dir.uuid()];
+		while !level.is_empty() {
+			let sources = level
+				.drain(..)
+				.flat_map(|parent| children.remove(&parent).unwrap_or_default())
+				.collect::<Vec<_>>();
+			let results = futures::stream::iter(sources)
+				.map(|source| {
+					let copies = &copies;
+					async move {
+						let result = match (source.parent, source.name()) {
+							(ParentUuid::Uuid(parent), Some(name)) => {
+								let parent = DirType::Dir(Cow::Borrowed(&copies[&parent]));
+								self.copy_dir_shallow(&source, &parent, name).await
+							}
+							_ => Err(undecrypted_name()),
+						};
+						(source, result)
+					}
+				})
+				.buffer_unordered(MAX_SMALL_PARALLEL_REQUESTS)
+				.collect::<Vec<_>>()
+				.await;
+			let mut copied_dirs = Vec::new();
+			for (source, result) in results {
+				match result {
+					Ok(copy) => {
+						level.push(source.uuid());
+						copies.insert(source.uuid(), copy.clone());
+						copied_dirs.push(copy);
+					}
+					Err(e) => errors.push((e, NonRootItemType::Dir(Cow::Owned(source)))),
+				}
+			}
+			if !copied_dirs.is_empty() {
+				callback.on_copy_update(copied_dirs, Vec::new(), 0);
+			}
+		}
+
+		let copied_bytes = Arc::new(AtomicU64::new(0));
+		let mut file_copies = futures::stream::iter(files.into_iter().filter_map(|file| {
+			// files in directories that failed to copy are skipped with them
+			let ParentUuid::Uuid(parent) = file.parent else {
+				return None;
+			};
+			Some((file, copies.get(&parent)?))
+		}))
+		.map(|(file, parent)| {
+			let copied_bytes = Arc::clone(&copied_bytes);
+			async move {
+				let progress = MaybeArc::new(move |bytes| {
+					copied_bytes.fetch_add(bytes, Ordering::Relaxed);
+				}) as MaybeSendCallback<u64>;
+				let result = self
+					.copy_file(
+						&file,
+						&DirType::Dir(Cow::Borrowed(parent)),
+						None,
+						Some(progress),
+					)
+					.await;
+				(file, result)
+			}
+		})
+		.buffer_unordered(self.get_unauth_client().state().max_concurrency());
+
+		let mut copied_files = Vec::new();
+		let mut update_interval = tokio::time::interval(CALLBACK_INTERVAL);
+		update_interval.reset();
+		loop {
+			tokio::select! {
+				_ = update_interval.tick() => {
+					if !errors.is_empty() {
+						callback.on_copy_errors(std::mem::take(&mut errors));
+					}
+					let bytes = copied_bytes.swap(0, Ordering::Relaxed);
+					if !copied_files.is_empty() || bytes != 0 {
+						callback.on_copy_update(Vec::new(), std::mem::take(&mut copied_files), bytes);
+					}
+				}
+				copy = file_copies.next() => match copy {
+					Some((_, Ok(copy))) => copied_files.push(copy),
+					Some((file, Err(e))) => errors.push((e, NonRootItemType::File(Cow::Owned(file)))),
+					None => break,
+				}
+			}
+		}
+
+		if !errors.is_empty() {
+			callback.on_copy_errors(errors);
+		}
+		let bytes = copied_bytes.swap(0, Ordering::Relaxed);
+		if !copied_files.is_empty() || bytes != 0 {
+			callback.on_copy_update(Vec::new(), copied_files, bytes);
+		}
+		Ok(root)
+	}
+
+	/// Creates an empty copy of `dir` in `parent`, keeping its creation time
+	#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
+	async fn copy_dir_shallow(
+		&self,
+		dir: &RemoteDirectory,
+		parent: &DirType<'_, Normal>,
+		name: &str,
+	) -> Result<RemoteDirectory, Error> {
+		match dir.created() {
+			Some(created) => self.create_dir_with_created(parent, name, created).await,
+			None => self.create_dir(parent, name).await,
+		}
+	}
+}
@@ -1,6 +1,7 @@
 use std::{
 	borrow::Cow,
 	ops::Deref,
+	path::{Path, PathBuf},
 	sync::{Arc, atomic::AtomicU64},
 };
 
@@ -25,6 +26,9 @@ type EntryResult<Cat: Category> = (Result<(), Error>, String, NonRootItemType<'s
 /// Folder downloads are implemented using a single sweep
 /// While scanning the folder contents, files are downloaded in parallel
 /// Progress is reported during the download process.
+///
+/// Downloading the same folder again after a failure skips the files that are already there
+/// and continues the interrupted ones from the chunks they have on disk.
 pub trait DirDownloadCallback<Cat>: Send + Sync
 where
 	Cat: Category + ?Sized,
@@ -76,6 +80,7 @@ pub(crate) trait CategoryDirDownloadExt: CategoryFSExt {
 		file_download_request_sender: tokio::sync::mpsc::Sender<(Self::File, CanonicalPath)>,
 		target_folder: DirType<'static, Self>,
 		root_path: String,
+		mut filter: impl FnMut(&Path, bool) -> bool + Send + 'static,
 	) -> tokio::task::JoinHandle<Result<(), Error>> {
 		tokio::task::spawn_blocking(move || {
 			match (std::fs::create_dir_all(&root_path), &target_folder) {
@@ -96,6 +101,8 @@ pub(crate) trait CategoryDirDownloadExt: CategoryFSExt {
 			};
 
 			let iter = tree.dfs_iter_with_path(&root_path);
+			// directories the filter rejected, whose descendants are skipped too
+			let mut skipped_dirs = Vec::<PathBuf>::new();
 
 			for (entry, path) in iter.canonicalize()? {
 				// A remote name that is not a safe single path component (`..`,
@@ -113,6 +120,19 @@ pub(crate) trait CategoryDirDownloadExt: CategoryFSExt {
 						continue;
 					}
 				};
+				if skipped_dirs
+					.iter()
+					.any(|dir| path.as_ref().starts_with(dir))
+				{
+					continue;
+				}
+				let is_dir = matches!(entry, Entry::Dir(_));
+				if !filter(path.as_ref(), is_dir) {
+					if is_dir {
+						skipped_dirs.push(path.as_ref().to_path_buf());
+					}
+					continue;
+				}
 				match entry {
 					Entry::Dir(dir_entry) => {
 						let dir = dir_entry.extra_data().clone();
@@ -236,6 +256,7 @@ pub(crate) trait CategoryDirDownloadExt: CategoryFSExt {
 		path: String,
 		tree: super::fs_tree::FSTree<Self::Dir, Self::File>,
 		target_folder: DirType<'static, Self>,
+		filter: impl FnMut(&Path, bool) -> bool + Send + 'static,
 	) -> Result<(), Error> {
 		let (entry_complete_sender, mut entry_complete_receiver) =
 			tokio::sync::mpsc::channel::<EntryResult<Self>>(16);
@@ -255,6 +276,7 @@ pub(crate) trait CategoryDirDownloadExt: CategoryFSExt {
 			file_download_request_sender,
 			target_folder,
 			path,
+			filter,
 		);
 
 		let file_handle = Self::spawn_file_downloader_task(
@@ -339,6 +361,30 @@ pub trait CategoryDirDownloadExtPub: CategoryDirDownloadExt {
 		target: DirType<'static, Self>,
 		context: Self::ListDirContext<'_>,
 	) -> impl std::future::Future<Output = Result<(), Error>>
+	where
+		C: DirDownloadCallback<Self> + ?Sized,
+	{
+		Self::download_dir_recursively_filtered(
+			client,
+			dir_path,
+			callback,
+			target,
+			context,
+			|_, _| true,
+		)
+	}
+
+	/// Like [`CategoryDirDownloadExtPub::download_dir_recursively`], but skips every entry
+	/// for which `filter(path, is_dir)` returns `false`. Skipped directories are not created
+	/// and nothing below them is downloaded.
+	fn download_dir_recursively_filtered<C>(
+		client: Arc<Self::Client>,
+		dir_path: String,
+		callback: impl Deref<Target = C>,
+		target: DirType<'static, Self>,
+		context: Self::ListDirContext<'_>,
+		filter: impl FnMut(&Path, bool) -> bool + Send + 'static,
+	) -> impl std::future::Future<Output = Result<(), Error>>
 	where
 		C: DirDownloadCallback<Self> + ?Sized,
 	{
@@ -385,6 +431,7 @@ pub trait CategoryDirDownloadExtPub: CategoryDirDownloadExt {
 				dir_path,
 				tree,
 				target,
+				filter,
 			)
 			.await
 		}
@@ -131,7 +131,10 @@ mod tests {
 
 	use crate::{
 		ErrorKind,
-		io::fs_tree::{WalkError, build_fs_tree_from_walkdir_iterator},
+		io::fs_tree::{
+			WalkError, build_filtered_fs_tree_from_walkdir_iterator,
+			build_fs_tree_from_walkdir_iterator,
+		},
 	};
 
 	#[test]
@@ -168,4 +171,32 @@ mod tests {
 			Some(WalkError::UnsupportedFileType(path)) if path == &fifo_path
 		));
 	}
+
+	#[test]
+	fn filtered_walk_skips_rejected_entries() {
+		let dir = tempfile::tempdir().unwrap();
+		std::fs::write(dir.path().join("keep.txt"), b"data").unwrap();
+		std::fs::write(dir.path().join("skip.tmp"), b"data").unwrap();
+		std::fs::create_dir(dir.path().join("skipped")).unwrap();
+		std::fs::write(dir.path().join("skipped").join("inner.txt"), b"data").unwrap();
+
+		let (tree, stats) = build_filtered_fs_tree_from_walkdir_iterator(
+			dir.path(),
+			|path, _| {
+				let name = path.file_name().unwrap().to_str().unwrap();
+				name != "skipped" && !name.ends_with(".tmp")
+			},
+			&mut |errs| panic!("unexpected walk errors: {errs:?}"),
+			&mut |_, _, _| {},
+			&AtomicBool::new(false),
+		)
+		.unwrap();
+
+		assert_eq!(stats.snapshot(), (0, 1, 4));
+		let names = tree
+			.dfs_iter()
+			.map(|(entry, _)| tree.get_name(entry).to_owned())
+			.collect::<Vec<_>>();
+		assert_eq!(names, ["keep.txt"]);
+	}
 }
@@ -18,7 +18,9 @@ pub(crate) use entry::{
 pub(crate) use tree::{FSTree, remote::build_fs_tree_from_remote_iterator};
 
 #[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
-pub(crate) use tree::local::build_fs_tree_from_walkdir_iterator;
+pub(crate) use tree::local::{
+	build_filtered_fs_tree_from_walkdir_iterator, build_fs_tree_from_walkdir_iterator,
+};
 
 use crate::ErrorKind;
 
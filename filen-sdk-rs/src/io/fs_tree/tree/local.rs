@@ -12,10 +12,32 @@ pub(crate) fn build_fs_tree_from_walkdir_iterator(
 	error_callback: &mut impl FnMut(Vec<Error>),
 	progress_callback: &mut impl FnMut(u64, u64, u64),
 	should_cancel: &AtomicBool,
+) -> Result<(FSTree<ExtraLocalDirData, ExtraLocalFileData>, FSStats), Error> {
+	build_filtered_fs_tree_from_walkdir_iterator(
+		root_path,
+		|_, _| true,
+		error_callback,
+		progress_callback,
+		should_cancel,
+	)
+}
+
+/// Like [`build_fs_tree_from_walkdir_iterator`], but only keeps entries for which
+/// `filter(path, is_dir)` returns `true`. Rejected directories are not descended into.
+/// The root entry is always kept.
+pub(crate) fn build_filtered_fs_tree_from_walkdir_iterator(
+	root_path: &Path,
+	mut filter: impl FnMut(&Path, bool) -> bool,
+	error_callback: &mut impl FnMut(Vec<Error>),
+	progress_callback: &mut impl FnMut(u64, u64, u64),
+	should_cancel: &AtomicBool,
 ) -> Result<(FSTree<ExtraLocalDirData, ExtraLocalFileData>, FSStats), Error> {
 	let mut iter = walkdir::WalkDir::new(root_path)
 		.follow_links(true)
 		.into_iter()
+		.filter_entry(move |entry| {
+			entry.depth() == 0 || filter(entry.path(), entry.file_type().is_dir())
+		})
 		.filter_map(|res| match res {
 			Ok(v) => Some(Ok(v)),
 			Err(e) if let Some(path) = e.loop_ancestor().map(|p| p.to_path_buf()) => {
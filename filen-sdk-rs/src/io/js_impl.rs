@@ -5,15 +5,15 @@ use crate::{
 	auth::{JsClient, js_impls::UnauthJsClient},
 	error::FilenSdkError,
 	fs::{
-		categories::{Category, Linked, NonRootItemType, Normal, Shared, fs::CategoryFS},
+		categories::{Category, DirType, Linked, NonRootItemType, Normal, Shared, fs::CategoryFS},
 		file::enums::RemoteFileType,
 	},
 	io::{
-		DirDownloadCallback, client_impl::IoSharedClientExt,
+		DirCopyCallback, DirDownloadCallback, client_impl::IoSharedClientExt,
 		dir_download::CategoryDirDownloadExtPub,
 	},
 	js::{
-		AnyDirWithContext, AnyFile, Dir, DirByCategoryWithContext, DirWithPath, File,
+		AnyDirWithContext, AnyFile, AnyNormalDir, Dir, DirByCategoryWithContext, DirWithPath, File,
 		FileBuilderParamsOptionalName, FileWithPath, LinkedFile, NonRootDir, NonRootItem,
 	},
 	util::MaybeSendCallback,
@@ -237,6 +237,69 @@ where
 	}
 }
 
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+pub struct CopyError {
+	pub error: Arc<Error>,
+	pub item: NonRootItem,
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export(with_foreign))]
+pub trait JsDirCopyCallback: Send + Sync {
+	/// Called once the source tree was listed
+	fn on_scan_complete(&self, total_dirs: u64, total_files: u64, total_bytes: u64);
+	/// Called periodically with the copies made and the bytes uploaded since the last call
+	fn on_copy_update(&self, copied_dirs: Vec<Dir>, copied_files: Vec<File>, copied_bytes: u64);
+	/// Called with the source items that failed to copy
+	fn on_copy_errors(&self, errors: Vec<CopyError>);
+}
+
+impl DirCopyCallback for Arc<dyn JsDirCopyCallback> {
+	fn on_scan_complete(&self, total_dirs: u64, total_files: u64, total_bytes: u64) {
+		let this = self.clone();
+		tokio::task::spawn_blocking(move || {
+			JsDirCopyCallback::on_scan_complete(
+				this.as_ref(),
+				total_dirs,
+				total_files,
+				total_bytes,
+			);
+		});
+	}
+
+	fn on_copy_update(
+		&self,
+		copied_dirs: Vec<super::RemoteDirectory>,
+		copied_files: Vec<super::RemoteFile>,
+		copied_bytes: u64,
+	) {
+		let this = self.clone();
+		tokio::task::spawn_blocking(move || {
+			JsDirCopyCallback::on_copy_update(
+				this.as_ref(),
+				copied_dirs.into_iter().map(|d| d.into()).collect(),
+				copied_files.into_iter().map(|f| f.into()).collect(),
+				copied_bytes,
+			);
+		});
+	}
+
+	fn on_copy_errors(&self, errors: Vec<(Error, NonRootItemType<'static, Normal>)>) {
+		let this = self.clone();
+		tokio::task::spawn_blocking(move || {
+			JsDirCopyCallback::on_copy_errors(
+				this.as_ref(),
+				errors
+					.into_iter()
+					.map(|(e, item)| CopyError {
+						error: Arc::new(e),
+						item: item.into(),
+					})
+					.collect(),
+			);
+		});
+	}
+}
+
 #[cfg_attr(feature = "uniffi", uniffi::export(with_foreign))]
 pub trait JsFileDownloadCallback: Send + Sync {
 	/// `downloaded_bytes` is a **delta** — the number of plaintext bytes downloaded *since the
@@ -385,6 +448,64 @@ impl JsClient {
 			})
 			.await
 	}
+
+	/// Copies `file` into `parent`, named `name` or like the original when `None`
+	pub async fn copy_file(
+		&self,
+		file: File,
+		parent: AnyNormalDir,
+		name: Option<String>,
+		callback: Option<Arc<dyn JsFileUploadCallback>>,
+		managed_future: crate::js::ManagedFuture,
+	) -> Result<File, Error> {
+		let this = self.inner();
+		managed_future
+			.into_js_managed_commander_future(move || async move {
+				let callback = callback.as_ref().map(|cb| {
+					Arc::new(|uploaded_bytes| {
+						let inner_cb = Arc::clone(cb);
+						tokio::task::spawn_blocking(move || {
+							JsFileUploadCallback::on_update(inner_cb.as_ref(), uploaded_bytes);
+						});
+					}) as MaybeSendCallback<u64>
+				});
+
+				this.copy_file(
+					&file.try_into()?,
+					&DirType::<'static, Normal>::from(parent),
+					name.as_deref(),
+					callback,
+				)
+				.await
+				.map(File::from)
+			})
+			.await
+	}
+
+	/// Copies `dir` and everything in it into `parent`, named `name` or like the original
+	/// when `None`
+	pub async fn copy_dir_recursive(
+		&self,
+		dir: Dir,
+		parent: AnyNormalDir,
+		name: Option<String>,
+		callback: Arc<dyn JsDirCopyCallback>,
+		managed_future: crate::js::ManagedFuture,
+	) -> Result<Dir, Error> {
+		let this = self.inner();
+		managed_future
+			.into_js_managed_commander_future(move || async move {
+				this.copy_dir_recursive(
+					&dir.into(),
+					&DirType::<'static, Normal>::from(parent),
+					name.as_deref(),
+					&callback,
+				)
+				.await
+				.map(Dir::from)
+			})
+			.await
+	}
 }
 
 trait PathToStringExt {
@@ -1,4 +1,5 @@
 pub mod client_impl;
+mod copy;
 
 #[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
 mod dir_download;
@@ -9,6 +10,8 @@ pub(crate) mod fs_tree;
 mod js_impl;
 #[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
 mod meta_ext;
+#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
+mod partial_download;
 
 #[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
 mod canonical_path;
@@ -18,6 +21,8 @@ pub use crate::fs::{
 	file::{AnonymousRemoteFile, RemoteFile, traits::HasFileInfo},
 };
 #[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
+pub use copy::DirCopyCallback;
+#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
 pub use dir_download::{CategoryDirDownloadExtPub, DirDownloadCallback};
 #[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
 pub use dir_upload::DirUploadCallback;
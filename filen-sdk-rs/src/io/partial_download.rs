@@ -0,0 +1,368 @@
+//! Downloads to disk that continue where an interrupted attempt stopped.
+//!
+//! A file is downloaded into `<uuid>.filendl` next to its target. Chunks are written in the order
+//! they arrive, and each one is then appended to the `<uuid>.filendl.chunks` sidecar. The next
+//! download of the same file into the same directory only fetches the chunks the sidecar doesn't
+//! list. A file's contents never change under its uuid, so the chunks on disk stay valid.
+//!
+//! Once every chunk is on disk, the whole file is checked against its Blake3 hash. That also
+//! catches chunks that were recorded but lost, e.g. when the system went down before they
+//! reached the disk.
+
+use std::{
+	collections::BTreeSet,
+	fs::OpenOptions,
+	io::{Seek, SeekFrom, Write},
+	path::{Path, PathBuf},
+	sync::atomic::{AtomicU64, Ordering},
+};
+
+use filen_types::crypto::Blake3Hash;
+use futures::StreamExt;
+
+use crate::{
+	api,
+	auth::{shared_client::SharedClient, unauth::UnauthClient},
+	consts::{CHUNK_SIZE_U64, FILE_CHUNK_SIZE_EXTRA},
+	crypto::shared::DataCrypter,
+	error::{Error, ErrorKind, MetadataWasNotDecryptedError},
+	fs::file::{chunk::Chunk, read::chunks_consistent_with_size, traits::File},
+	progress::ThrottledProgress,
+	util::MaybeSendCallback,
+};
+
+/// The sidecar recording which chunks of the partial download at `tmp_path` were written.
+pub(super) fn sidecar_path(tmp_path: &Path) -> PathBuf {
+	tmp_path.with_extension("filendl.chunks")
+}
+
+/// Downloads `remote_file` into `tmp_path`, keeping the chunks an earlier attempt left there,
+/// and returns the file once it matches the remote file's hash.
+///
+/// If a resumed download doesn't match, it's downloaded again from scratch.
+pub(super) async fn download_to_partial_file<SC>(
+	unauth_client: &SC,
+	remote_file: &dyn File,
+	tmp_path: &Path,
+	callback: Option<MaybeSendCallback<'_, u64>>,
+) -> Result<std::fs::File, Error>
+where
+	SC: SharedClient,
+{
+	let (size, chunks) = (remote_file.size(), remote_file.chunks());
+	if !chunks_consistent_with_size(chunks, size) {
+		return Err(Error::custom(
+			ErrorKind::Response,
+			format!("file chunk count ({chunks}) is inconsistent with file size ({size})"),
+		));
+	}
+	let progress = ThrottledProgress::new(callback);
+
+	let mut resume = true;
+	loop {
+		let (partial, resumed) = fetch_missing_chunks(
+			unauth_client.get_unauth_client(),
+			remote_file,
+			tmp_path,
+			progress.as_deref(),
+			resume,
+		)
+		.await?;
+		let hash = remote_file.hash();
+		let (partial, matches) =
+			tokio::task::spawn_blocking(move || partial.matches_hash(hash).map(|m| (partial, m)))
+				.await
+				.unwrap()?;
+		if matches {
+			if let Some(progress) = &progress {
+				progress.flush();
+			}
+			return Ok(partial.tmp);
+		}
+
+		partial.discard();
+		if !resumed {
+			return Err(Error::custom(
+				ErrorKind::Response,
+				format!(
+					"downloaded file {} doesn't match its hash",
+					remote_file.uuid()
+				),
+			));
+		}
+		tracing::warn!(
+			"Resumed download of {} doesn't match its hash, downloading it again",
+			remote_file.uuid()
+		);
+		resume = false;
+	}
+}
+
+/// The plaintext length of chunk `chunk_idx` of a file with `size` bytes
+fn chunk_len(size: u64, chunk_idx: u64) -> u64 {
+	size.saturating_sub(chunk_idx * CHUNK_SIZE_U64)
+		.min(CHUNK_SIZE_U64)
+}
+
+/// Downloads the chunks missing from the partial file, returning it and whether any chunk was
+/// kept from an earlier attempt.
+async fn fetch_missing_chunks(
+	client: &UnauthClient,
+	file: &dyn File,
+	tmp_path: &Path,
+	progress: Option<&ThrottledProgress<'_>>,
+	resume: bool,
+) -> Result<(PartialFile, bool), Error> {
+	let (size, chunks) = (file.size(), file.chunks());
+	let path = tmp_path.to_owned();
+	let (mut partial, written) =
+		tokio::task::spawn_blocking(move || PartialFile::open(path, size, chunks, resume))
+			.await
+			.unwrap()?;
+	let resumed = !written.is_empty();
+	if let Some(progress) = progress {
+		progress.report(written.iter().map(|idx| chunk_len(size, *idx)).sum());
+	}
+
+	let key = file.key().ok_or(MetadataWasNotDecryptedError)?;
+	let mut downloads = futures::stream::iter((0..chunks).filter(|idx| !written.contains(idx)))
+		.map(|chunk_idx| async move {
+			let len = chunk_len(size, chunk_idx);
+			// chunk_len is at most CHUNK_SIZE_U64, which fits in a u32
+			let buffer = Chunk::acquire(
+				FILE_CHUNK_SIZE_EXTRA.saturating_add(len as u32),
+				client.state(),
+			)
+			.await;
+			// bytes of this chunk reported so far, see FileReader::push_fetch_next_chunk
+			let reported = AtomicU64::new(0);
+			let on_bytes = |bytes_so_far: u64, _content_length: Option<u64>| {
+				if let Some(progress) = progress {
+					let clamped = bytes_so_far.min(len);
+					let prev = reported.fetch_max(clamped, Ordering::Relaxed);
+					if clamped > prev {
+						progress.report(clamped - prev);
+					}
+				}
+			};
+			let data = api::download::download_file_chunk_by_uuid(
+				client,
+				file.region(),
+				file.bucket(),
+				file.uuid(),
+				chunk_idx,
+				Some(&on_bytes),
+			)
+			.await?;
+			let (_, permits) = buffer.into_parts();
+			let mut chunk = Chunk::from_parts(data, permits);
+			key.decrypt_data(chunk.as_mut()).await?;
+			if chunk.len() as u64 != len {
+				return Err(Error::custom(
+					ErrorKind::Response,
+					format!(
+						"chunk {chunk_idx} of file {} has {} bytes instead of {len}",
+						file.uuid(),
+						chunk.len()
+					),
+				));
+			}
+			Ok((chunk_idx, chunk))
+		})
+		.buffer_unordered(client.state().max_concurrency());
+
+	while let Some(result) = downloads.next().await {
+		let (chunk_idx, chunk) = result?;
+		let (data, permits) = chunk.into_parts();
+		partial = tokio::task::spawn_blocking(move || {
+			partial.write_chunk(chunk_idx, &data).map(|()| partial)
+		})
+		.await
+		.unwrap()?;
+		drop(permits);
+	}
+	Ok((partial, resumed))
+}
+
+/// A download in progress and its sidecar.
+///
+/// The sidecar starts with the file's size and chunk count, followed by one line per chunk
+/// written. It's only created with the first chunk, so a download that failed before that
+/// leaves nothing to resume.
+struct PartialFile {
+	tmp: std::fs::File,
+	tmp_path: PathBuf,
+	sidecar: Option<std::fs::File>,
+	header: String,
+}
+
+impl PartialFile {
+	/// Opens the partial download at `tmp_path` with the chunks already written to it, or
+	/// starts it over if `resume` is `false` or its sidecar belongs to a different file.
+	fn open(
+		tmp_path: PathBuf,
+		size: u64,
+		chunks: u64,
+		resume: bool,
+	) -> std::io::Result<(Self, BTreeSet<u64>)> {
+		let header = format!("{size} {chunks}\n");
+		let sidecar_path = sidecar_path(&tmp_path);
+		let written = match std::fs::read_to_string(&sidecar_path) {
+			Ok(contents) if resume => parse_sidecar(&contents, &header),
+			Ok(_) => None,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+			Err(e) => return Err(e),
+		};
+		let tmp = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.create(true)
+			.truncate(false)
+			.open(&tmp_path)?;
+
+		if let Some(written) = written
+			&& tmp.metadata()?.len() == size
+		{
+			let written = written
+				.into_iter()
+				.filter(|idx| *idx < chunks)
+				.collect::<BTreeSet<_>>();
+			// rewritten rather than appended to, which would continue a cut-off line
+			let mut sidecar = OpenOptions::new()
+				.write(true)
+				.truncate(true)
+				.open(&sidecar_path)?;
+			let mut contents = header.clone();
+			for idx in &written {
+				contents.push_str(&format!("{idx}\n"));
+			}
+			sidecar.write_all(contents.as_bytes())?;
+			return Ok((
+				Self {
+					tmp,
+					tmp_path,
+					sidecar: Some(sidecar),
+					header,
+				},
+				written,
+			));
+		}
+
+		remove_if_exists(&sidecar_path)?;
+		tmp.set_len(0)?;
+		tmp.set_len(size)?;
+		Ok((
+			Self {
+				tmp,
+				tmp_path,
+				sidecar: None,
+				header,
+			},
+			BTreeSet::new(),
+		))
+	}
+
+	fn write_chunk(&mut self, chunk_idx: u64, data: &[u8]) -> std::io::Result<()> {
+		self.tmp.seek(SeekFrom::Start(chunk_idx * CHUNK_SIZE_U64))?;
+		self.tmp.write_all(data)?;
+		// only recorded once the data was written, so the sidecar never lists a chunk the
+		// process didn't write
+		match &mut self.sidecar {
+			Some(sidecar) => sidecar.write_all(format!("{chunk_idx}\n").as_bytes()),
+			None => {
+				let mut sidecar = OpenOptions::new()
+					.write(true)
+					.create(true)
+					.truncate(true)
+					.open(sidecar_path(&self.tmp_path))?;
+				sidecar.write_all(format!("{}{chunk_idx}\n", self.header).as_bytes())?;
+				self.sidecar = Some(sidecar);
+				Ok(())
+			}
+		}
+	}
+
+	/// Whether the partial file's hash is `expected`, always true for files without one.
+	fn matches_hash(&self, expected: Option<Blake3Hash>) -> std::io::Result<bool> {
+		let Some(expected) = expected else {
+			return Ok(true);
+		};
+		let mut hasher = blake3::Hasher::new();
+		hasher.update_reader(std::fs::File::open(&self.tmp_path)?)?;
+		Ok(Blake3Hash::from(hasher.finalize()) == expected)
+	}
+
+	/// Removes the sidecar, so the partial file is started over the next time it's opened.
+	fn discard(self) {
+		if let Err(e) = remove_if_exists(&sidecar_path(&self.tmp_path)) {
+			tracing::warn!("Failed to remove download sidecar: {e}");
+		}
+	}
+}
+
+/// The chunks listed in a sidecar, or `None` if it was written for a different file.
+///
+/// A line without its newline was cut off while being appended, so the chunk it names is
+/// downloaded again.
+fn parse_sidecar(contents: &str, header: &str) -> Option<BTreeSet<u64>> {
+	let written = contents.strip_prefix(header)?;
+	Some(
+		written
+			.split_inclusive('\n')
+			.filter_map(|line| line.strip_suffix('\n')?.parse().ok())
+			.collect(),
+	)
+}
+
+pub(super) fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+	match std::fs::remove_file(path) {
+		Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+		_ => Ok(()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sidecar_ignores_a_cut_off_line() {
+		let header = "5 3\n";
+		assert_eq!(
+			parse_sidecar("5 3\n2\n0\n1", header),
+			Some(BTreeSet::from([0, 2]))
+		);
+		assert_eq!(parse_sidecar("5 3\n", header), Some(BTreeSet::new()));
+		assert_eq!(parse_sidecar("6 3\n0\n", header), None);
+		assert_eq!(parse_sidecar("5 3", header), None);
+	}
+
+	#[test]
+	fn reopening_keeps_written_chunks_until_started_over() {
+		let dir = tempfile::tempdir().unwrap();
+		let tmp_path = dir.path().join("abc.filendl");
+		let size = 2 * CHUNK_SIZE_U64 + 3;
+		let first = vec![1; CHUNK_SIZE_U64 as usize];
+
+		let (mut partial, written) = PartialFile::open(tmp_path.clone(), size, 3, true).unwrap();
+		assert!(written.is_empty());
+		assert!(!sidecar_path(&tmp_path).exists());
+		partial.write_chunk(2, b"end").unwrap();
+		partial.write_chunk(0, &first).unwrap();
+		drop(partial);
+
+		let (partial, written) = PartialFile::open(tmp_path.clone(), size, 3, true).unwrap();
+		assert_eq!(written, BTreeSet::from([0, 2]));
+		let contents = std::fs::read(&tmp_path).unwrap();
+		assert_eq!(contents.len() as u64, size);
+		assert_eq!(&contents[..CHUNK_SIZE_U64 as usize], first);
+		assert_eq!(&contents[2 * CHUNK_SIZE_U64 as usize..], b"end");
+		drop(partial);
+
+		// a different file under the same name, e.g. after the size changed
+		let (_, written) = PartialFile::open(tmp_path.clone(), size + 1, 3, true).unwrap();
+		assert!(written.is_empty());
+		assert!(!sidecar_path(&tmp_path).exists());
+		assert_eq!(std::fs::metadata(&tmp_path).unwrap().len(), size + 1);
+	}
+}
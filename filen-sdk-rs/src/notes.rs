@@ -220,6 +220,14 @@ impl Note {
 		&self.participants
 	}
 
+	pub fn created(&self) -> DateTime<Utc> {
+		self.created_timestamp
+	}
+
+	pub fn edited(&self) -> DateTime<Utc> {
+		self.edited_timestamp
+	}
+
 	pub fn participants_mut(&mut self) -> &mut [NoteParticipant] {
 		&mut self.participants
 	}
@@ -261,6 +269,10 @@ pub struct NoteHistory {
 }
 
 impl NoteHistory {
+	pub fn id(&self) -> u64 {
+		self.id
+	}
+
 	pub fn preview(&self) -> Option<&str> {
 		self.preview.as_deref()
 	}
@@ -272,6 +284,10 @@ impl NoteHistory {
 	pub fn note_type(&self) -> NoteType {
 		self.note_type
 	}
+
+	pub fn edited(&self) -> DateTime<Utc> {
+		self.edited_timestamp
+	}
 }
 
 impl NoteHistory {
@@ -26,8 +26,9 @@ pub(super) const PING_INTERVAL: Duration = Duration::from_secs(15);
 /// read against a dead peer parks the websocket task forever with no retry.
 pub(super) const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
 
-pub(super) const WEBSOCKET_URL_CORE: &str =
-	"wss://socket.filen.io/socket.io/?EIO=3&transport=websocket&t=";
+/// Appended to the socket server URL from [`ApiEndpoints`](crate::consts::ApiEndpoints),
+/// followed by a millisecond timestamp.
+pub(super) const WEBSOCKET_PATH: &str = "/socket.io/?EIO=3&transport=websocket&t=";
 
 pub(super) const AUTHED_TRUE: &str = r#"["authed",true]"#;
 pub(super) const ARCHIVED_EVENT_PREFIX: &str = r#"["file-archived","#;
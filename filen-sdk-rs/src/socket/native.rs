@@ -11,7 +11,7 @@ use tungstenite::{Message, Utf8Bytes};
 use crate::{Error, ErrorKind};
 
 use super::{
-	consts::{PING_MESSAGE, WEBSOCKET_URL_CORE},
+	consts::{PING_MESSAGE, WEBSOCKET_PATH},
 	traits::*,
 };
 
@@ -209,15 +209,18 @@ impl
 		NativePingTask,
 	> for NativeSocket
 {
-	async fn build_request() -> Result<String, Error> {
+	async fn build_request(socket_url: &str) -> Result<String, Error> {
 		Ok(format!(
-			"{}{}",
-			WEBSOCKET_URL_CORE,
+			"{}{}{}",
+			socket_url,
+			WEBSOCKET_PATH,
 			chrono::Utc::now().timestamp_millis()
 		))
 	}
 
 	async fn connect(request: String) -> Result<UnauthedNativeSocket, Error> {
+		// `ApiEndpoints` only lets a plain `ws://` URL through for loopback hosts
+		let require_tls = !request.starts_with("ws://");
 		let (ws_stream, _) = tokio_tungstenite::connect_async(request)
 			.await
 			.map_err(|e| {
@@ -233,6 +236,7 @@ impl
 		// wait for a message that never arrives.
 		match ws_stream.get_ref() {
 			MaybeTlsStream::Rustls(_) => {}
+			MaybeTlsStream::Plain(_) if !require_tls => {}
 			MaybeTlsStream::Plain(_) => {
 				return Err(Error::custom(
 					ErrorKind::InvalidState,
@@ -488,6 +488,7 @@ where
 			.unwrap_or_else(|poisoned| poisoned.into_inner())
 			.0
 			.to_string();
+		let socket_url = config.client.state().endpoints().socket_url().to_owned();
 
 		tokio::select! {
 			biased;
@@ -498,7 +499,7 @@ where
 				};
 				handle_request(request, listeners);
 			}
-			result = connect_and_build_socket(&api_key, listeners) => {
+			result = connect_and_build_socket(&socket_url, &api_key, listeners) => {
 				match result {
 					Ok((streams, interval)) => {
 						config.ping_interval = interval;
@@ -610,6 +611,7 @@ fn handle_request(request: SocketRequest, listeners: &mut impl ListenerManagerEx
 }
 
 async fn connect_and_build_socket<UW, US, UR, RV, W, S, R, T, PT>(
+	socket_url: &str,
 	api_key: &str,
 	listeners: &mut DisconnectedListenerManager,
 ) -> Result<(W, Duration), Error>
@@ -628,7 +630,7 @@ where
 	// are otherwise unbounded, so a dead peer would park the task forever.
 	let setup = async {
 		tracing::debug!("Connecting to WebSocket server...");
-		let request = W::build_request().await?;
+		let request = W::build_request(socket_url).await?;
 
 		tracing::debug!("WebSocket request built, connecting...");
 
@@ -1055,7 +1057,7 @@ mod tests {
 				FakeNoopPingTask,
 			> for FakeSocket
 		{
-			async fn build_request() -> Result<(), Error> {
+			async fn build_request(_socket_url: &str) -> Result<(), Error> {
 				Ok(())
 			}
 
@@ -56,7 +56,7 @@ where
 	U: UnauthedSocket<US, UR, RV>,
 	PT: PingTask<S>,
 {
-	async fn build_request() -> Result<T, Error>;
+	async fn build_request(socket_url: &str) -> Result<T, Error>;
 
 	async fn connect(request: T) -> Result<U, Error>;
 
@@ -6,7 +6,7 @@ use wasm_bindgen::JsCast;
 use crate::{Error, ErrorKind};
 
 use super::{
-	consts::{PING_MESSAGE, WEBSOCKET_URL_CORE},
+	consts::{PING_MESSAGE, WEBSOCKET_PATH},
 	traits::*,
 };
 
@@ -237,10 +237,11 @@ impl
 		WasmPingTask,
 	> for WasmSocket
 {
-	async fn build_request() -> Result<String, Error> {
+	async fn build_request(socket_url: &str) -> Result<String, Error> {
 		Ok(format!(
-			"{}{}",
-			WEBSOCKET_URL_CORE,
+			"{}{}{}",
+			socket_url,
+			WEBSOCKET_PATH,
 			chrono::Utc::now().timestamp_millis()
 		))
 	}
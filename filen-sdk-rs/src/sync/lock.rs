@@ -25,7 +25,6 @@ use wasmtimer::{
 use crate::{
 	ErrorKind, api,
 	auth::{Client, http::AuthClient},
-	consts::gateway_url,
 	error::Error,
 };
 
@@ -343,7 +342,11 @@ impl Client {
 			r#type: LockType::Acquire,
 			resource: Cow::Borrowed(&resource),
 		})?);
-		let url = gateway_url(api::v3::user::lock::ENDPOINT);
+		let url = self
+			.client()
+			.state()
+			.endpoints()
+			.gateway_url(api::v3::user::lock::ENDPOINT);
 		let endpoint = api::v3::user::lock::ENDPOINT;
 		for (i, delay) in (0..attempts).zip(fibonacci_iter(max_sleep_time)) {
 			// The server's lease clock starts when it processes this request, no
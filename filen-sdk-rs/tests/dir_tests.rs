@@ -26,7 +26,10 @@ use filen_sdk_rs::{
 		file::{RemoteFile, meta::FileMeta, traits::HasFileMeta},
 		name::{EntryNameError, EntryNameErrorKind},
 	},
-	io::{CategoryDirDownloadExtPub, DirDownloadCallback, DirUploadCallback, FilenMetaExt},
+	io::{
+		CategoryDirDownloadExtPub, DirCopyCallback, DirDownloadCallback, DirUploadCallback,
+		FilenMetaExt,
+	},
 };
 use filen_types::fs::{ParentUuid, Uuid};
 use futures::StreamExt;
@@ -1097,6 +1100,77 @@ async fn download_dir_with_long_names() {
 	let _ = tokio::fs::remove_dir_all(&download_dir).await;
 }
 
+#[shared_test_runtime]
+async fn download_dir_filtered() {
+	let resources = test_utils::RESOURCES.get_resources().await;
+	let client = resources.client.clone();
+	let test_dir = &resources.dir;
+
+	let upload_dir =
+		std::env::temp_dir().join(format!("test_filtered_upload_{}", std::process::id()));
+	let _ = tokio::fs::remove_dir_all(&upload_dir).await;
+	tokio::fs::create_dir_all(upload_dir.join("kept_dir"))
+		.await
+		.unwrap();
+	tokio::fs::create_dir_all(upload_dir.join("skipped_dir"))
+		.await
+		.unwrap();
+	tokio::fs::write(upload_dir.join("kept.txt"), b"kept")
+		.await
+		.unwrap();
+	tokio::fs::write(upload_dir.join("skipped.txt"), b"skipped")
+		.await
+		.unwrap();
+	tokio::fs::write(upload_dir.join("kept_dir/inner.txt"), b"inner")
+		.await
+		.unwrap();
+	tokio::fs::write(upload_dir.join("skipped_dir/inner.txt"), b"inner")
+		.await
+		.unwrap();
+
+	client
+		.clone()
+		.upload_dir_recursively(
+			upload_dir.clone(),
+			&DebugDirUploadCallback::default(),
+			test_dir,
+		)
+		.await
+		.unwrap();
+
+	let download_dir =
+		std::env::temp_dir().join(format!("test_filtered_download_{}", std::process::id()));
+	let _ = tokio::fs::remove_dir_all(&download_dir).await;
+	let callback = DebugDirDownloadCallback::default();
+
+	Normal::download_dir_recursively_filtered(
+		client.clone(),
+		download_dir.to_str().unwrap().to_string(),
+		&callback,
+		DirType::Dir(Cow::Owned(test_dir.clone())),
+		(),
+		|path: &Path, _is_dir| {
+			!path
+				.file_name()
+				.is_some_and(|name| name.to_string_lossy().starts_with("skipped"))
+		},
+	)
+	.await
+	.unwrap();
+
+	{
+		let errors = callback.errors.lock().unwrap();
+		assert!(errors.is_empty(), "download had errors: {:?}", *errors);
+	}
+	assert!(download_dir.join("kept.txt").exists());
+	assert!(download_dir.join("kept_dir/inner.txt").exists());
+	assert!(!download_dir.join("skipped.txt").exists());
+	assert!(!download_dir.join("skipped_dir").exists());
+
+	let _ = tokio::fs::remove_dir_all(&upload_dir).await;
+	let _ = tokio::fs::remove_dir_all(&download_dir).await;
+}
+
 // ── Name validation: DirectoryMetaChanges ───────────────────────────
 
 #[test]
@@ -1252,3 +1326,96 @@ async fn update_dir_meta_normalizes_nfc() {
 		.unwrap();
 	assert_eq!(dir.name().unwrap(), nfc_name);
 }
+
+#[derive(Default)]
+struct DebugDirCopyCallback {
+	total_files: std::sync::atomic::AtomicU64,
+	copied_dirs: std::sync::Mutex<Vec<RemoteDirectory>>,
+	copied_files: std::sync::Mutex<Vec<RemoteFile>>,
+	errors: std::sync::Mutex<Vec<String>>,
+}
+
+impl DirCopyCallback for DebugDirCopyCallback {
+	fn on_scan_complete(&self, _total_dirs: u64, total_files: u64, _total_bytes: u64) {
+		self.total_files
+			.store(total_files, std::sync::atomic::Ordering::Relaxed);
+	}
+	fn on_copy_update(
+		&self,
+		copied_dirs: Vec<RemoteDirectory>,
+		copied_files: Vec<RemoteFile>,
+		_copied_bytes: u64,
+	) {
+		self.copied_dirs.lock().unwrap().extend(copied_dirs);
+		self.copied_files.lock().unwrap().extend(copied_files);
+	}
+	fn on_copy_errors(&self, errors: Vec<(Error, NonRootItemType<'static, Normal>)>) {
+		let mut errs = self.errors.lock().unwrap();
+		for (e, item) in &errors {
+			errs.push(format!("{:?}: {e}", item.name()));
+		}
+	}
+}
+
+#[shared_test_runtime]
+async fn copy_dir_recursive() {
+	let resources = test_utils::RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let test_dir = &resources.dir;
+
+	let dir_a = client.create_dir(&test_dir.into(), "a").await.unwrap();
+	let dir_b = client.create_dir(&(&dir_a).into(), "b").await.unwrap();
+	let _dir_c = client.create_dir(&(&dir_b).into(), "c").await.unwrap();
+	let file_1 = client.make_file_builder("file1.txt", dir_a.uuid()).unwrap();
+	client.upload_file(file_1, b"file 1 content").await.unwrap();
+	let file_2 = client.make_file_builder("file2.txt", dir_b.uuid()).unwrap();
+	client.upload_file(file_2, b"file 2 content").await.unwrap();
+
+	let callback = DebugDirCopyCallback::default();
+	let copy = client
+		.copy_dir_recursive(&dir_a, &test_dir.into(), Some("a_copy"), &callback)
+		.await
+		.unwrap();
+	assert!(callback.errors.lock().unwrap().is_empty());
+	assert_ne!(copy.uuid(), dir_a.uuid());
+	assert_eq!(copy.name(), Some("a_copy"));
+	assert_eq!(copy.created(), dir_a.created());
+	assert_eq!(
+		callback
+			.total_files
+			.load(std::sync::atomic::Ordering::Relaxed),
+		2
+	);
+	assert_eq!(callback.copied_dirs.lock().unwrap().len(), 2);
+	assert_eq!(callback.copied_files.lock().unwrap().len(), 2);
+
+	let base = format!("{}/a_copy", test_dir.name().unwrap());
+	assert!(matches!(
+		client
+			.find_item_at_path(&format!("{base}/b/c"))
+			.await
+			.unwrap(),
+		Some(NonRootFileType::Dir(_))
+	));
+	for (path, contents) in [
+		("file1.txt", &b"file 1 content"[..]),
+		("b/file2.txt", &b"file 2 content"[..]),
+	] {
+		let Some(NonRootFileType::File(file)) = client
+			.find_item_at_path(&format!("{base}/{path}"))
+			.await
+			.unwrap()
+		else {
+			panic!("{path} wasn't copied");
+		};
+		assert_eq!(client.download_file(&file).await.unwrap(), contents);
+	}
+
+	// the original is untouched
+	let (dirs, files) = client
+		.list_dir_recursive::<_, fn(u64, Option<u64>)>(&(&dir_a).into(), None, ())
+		.await
+		.unwrap();
+	assert_eq!(dirs.len(), 2);
+	assert_eq!(files.len(), 2);
+}
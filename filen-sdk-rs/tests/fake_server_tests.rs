@@ -0,0 +1,163 @@
+//! Tests that always run against the in-memory fake server, so they need neither test accounts
+//! nor network access.
+
+use std::time::Duration;
+
+use filen_macros::shared_test_runtime;
+use filen_sdk_rs::{
+	fs::{HasName, HasParent, HasUUID, file::client_impl::FileReaderSharedClientExt},
+	io::client_impl::IoSharedClientExt,
+	socket::{DecryptedDriveEvent, DecryptedSocketEvent},
+};
+use filen_types::{fs::ParentUuid, traits::CowHelpersExt};
+use test_utils::await_event;
+
+#[shared_test_runtime]
+async fn dir_create_list_trash() {
+	let resources = test_utils::FAKE_RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let test_dir = &resources.dir;
+
+	let mut dir = client
+		.create_dir(&test_dir.into(), "test_dir")
+		.await
+		.unwrap();
+	assert_eq!(dir.name().unwrap(), "test_dir");
+
+	let (dirs, _) = client
+		.list_dir(&test_dir.into(), None::<&fn(u64, Option<u64>)>)
+		.await
+		.unwrap();
+	assert_eq!(dirs, vec![dir.clone()]);
+
+	client.trash_dir(&mut dir).await.unwrap();
+	let (trashed_dirs, _) = client
+		.list_trash(None::<&fn(u64, Option<u64>)>)
+		.await
+		.unwrap();
+	let found = trashed_dirs
+		.into_iter()
+		.find(|d| d.uuid() == dir.uuid())
+		.unwrap();
+	assert_eq!(*found.parent(), ParentUuid::Trash(test_dir.uuid()));
+	assert_eq!(client.get_dir(dir.uuid()).await.unwrap(), dir);
+}
+
+#[shared_test_runtime]
+async fn file_upload_download() {
+	let resources = test_utils::FAKE_RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let test_dir = &resources.dir;
+
+	// spans several chunks, so chunk ordering is covered too
+	let contents = (0..3 * 1024 * 1024 + 17)
+		.map(|i| (i % 251) as u8)
+		.collect::<Vec<_>>();
+	let file = client
+		.make_file_builder("big.bin", test_dir.uuid())
+		.unwrap();
+	let file = client.upload_file(file, &contents).await.unwrap();
+
+	let found = client.get_file(file.uuid()).await.unwrap();
+	assert_eq!(found, file);
+	assert_eq!(client.download_file(&found).await.unwrap(), contents);
+
+	let (_, files) = client
+		.list_dir(&test_dir.into(), None::<&fn(u64, Option<u64>)>)
+		.await
+		.unwrap();
+	assert_eq!(files, vec![file]);
+}
+
+#[shared_test_runtime]
+async fn file_reupload_creates_versions() {
+	let resources = test_utils::FAKE_RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let test_dir = &resources.dir;
+
+	let mut uploaded = Vec::new();
+	for content in ["first", "second", "third"] {
+		let file = client
+			.make_file_builder("notes.txt", test_dir.uuid())
+			.unwrap();
+		uploaded.push(client.upload_file(file, content.as_bytes()).await.unwrap());
+	}
+	let current = uploaded.last().unwrap();
+	assert!(
+		uploaded
+			.iter()
+			.all(|file| file.stable_uuid() == current.stable_uuid())
+	);
+
+	let versions = client.list_file_versions(current).await.unwrap();
+	assert_eq!(versions.len(), 3);
+	assert!(versions.iter().any(|v| v.uuid() == current.uuid()));
+
+	let (_, files) = client
+		.list_dir(&test_dir.into(), None::<&fn(u64, Option<u64>)>)
+		.await
+		.unwrap();
+	assert_eq!(files.len(), 1);
+	assert_eq!(client.download_file(&files[0]).await.unwrap(), b"third");
+}
+
+#[shared_test_runtime]
+async fn move_and_delete_permanently() {
+	let resources = test_utils::FAKE_RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let test_dir = &resources.dir;
+
+	let target = client.create_dir(&test_dir.into(), "target").await.unwrap();
+	let file = client
+		.make_file_builder("moved.txt", test_dir.uuid())
+		.unwrap();
+	let mut file = client.upload_file(file, b"moved").await.unwrap();
+
+	client
+		.move_file(&mut file, &(&target).into())
+		.await
+		.unwrap();
+	assert_eq!(*file.parent(), ParentUuid::Uuid(target.uuid()));
+	let (_, files) = client
+		.list_dir(&(&target).into(), None::<&fn(u64, Option<u64>)>)
+		.await
+		.unwrap();
+	assert_eq!(files, vec![file.clone()]);
+
+	let uuid = file.uuid();
+	client.delete_file_permanently(file).await.unwrap();
+	assert!(client.get_file(uuid).await.is_err());
+}
+
+#[shared_test_runtime]
+async fn socket_drive_events() {
+	let resources = test_utils::FAKE_RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let test_dir = &resources.dir;
+
+	let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+	let _handle = client
+		.add_event_listener(
+			Box::new(move |event| {
+				let _ = sender.send(event.to_owned_cow());
+			}),
+			None,
+		)
+		.await
+		.unwrap();
+
+	let dir = client.create_dir(&test_dir.into(), "events").await.unwrap();
+	await_event(
+		&mut receiver,
+		|event| match event {
+			DecryptedSocketEvent::Drive {
+				inner: DecryptedDriveEvent::FolderSubCreated(data),
+				..
+			} => data.0.uuid() == dir.uuid(),
+			_ => false,
+		},
+		Duration::from_secs(10),
+		"folderSubCreated",
+	)
+	.await;
+}
@@ -12,6 +12,7 @@ use filen_sdk_rs::{
 		categories::NonRootFileType,
 		dir::RemoteDirectory,
 		file::{
+			checkpoint::{UploadCheckpoint, UploadCheckpointStore},
 			client_impl::FileReaderSharedClientExt,
 			meta::{FileMeta, FileMetaChanges},
 			traits::{HasFileInfo, HasFileMeta},
@@ -631,6 +632,125 @@ async fn file_read_range() {
 	assert_eq!(str::from_utf8(&buf).unwrap(), "Hello, Filen");
 }
 
+#[shared_test_runtime]
+async fn file_copy() {
+	let resources = test_utils::RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let test_dir = &resources.dir;
+
+	let mut contents = vec![0u8; 1024 * 1024 * 2 + 17];
+	rand::rng().try_fill_bytes(&mut contents).unwrap();
+	let created = Utc::now() - chrono::Duration::days(2);
+	let modified = Utc::now() - chrono::Duration::days(1);
+	let file = client
+		.make_file_builder("original.bin", test_dir.uuid())
+		.unwrap()
+		.created(created)
+		.modified(modified)
+		.mime("application/x-test".to_owned());
+	let file = client.upload_file(file, &contents).await.unwrap();
+
+	let (sender, receiver) = std::sync::mpsc::channel::<u64>();
+	let copy = client
+		.copy_file(
+			&file,
+			&test_dir.into(),
+			Some("copy.bin"),
+			Some(Arc::new(move |bytes: u64| {
+				sender.send(bytes).unwrap();
+			}) as MaybeSendCallback<u64>),
+		)
+		.await
+		.unwrap();
+	assert_eq!(receiver.iter().sum::<u64>(), contents.len() as u64);
+
+	assert_ne!(copy.uuid(), file.uuid());
+	assert_eq!(copy.name(), Some("copy.bin"));
+	assert_eq!(copy.mime(), Some("application/x-test"));
+	assert_eq!(copy.created(), Some(created.round_subsecs(3)));
+	assert_eq!(copy.last_modified(), Some(modified.round_subsecs(3)));
+	assert_eq!(client.download_file(&copy).await.unwrap(), contents);
+	// the original is untouched
+	assert_eq!(client.get_file(file.uuid()).await.unwrap(), file);
+}
+
+#[derive(Default)]
+struct LastCheckpoint(std::sync::Mutex<Option<UploadCheckpoint>>);
+
+impl UploadCheckpointStore for LastCheckpoint {
+	fn save(&self, checkpoint: &UploadCheckpoint) {
+		*self.0.lock().unwrap() = Some(checkpoint.clone());
+	}
+}
+
+/// Fails every read, like a process that died mid-upload
+struct FailingReader;
+
+impl futures::AsyncRead for FailingReader {
+	fn poll_read(
+		self: std::pin::Pin<&mut Self>,
+		_cx: &mut std::task::Context<'_>,
+		_buf: &mut [u8],
+	) -> std::task::Poll<std::io::Result<usize>> {
+		std::task::Poll::Ready(Err(std::io::Error::other("interrupted")))
+	}
+}
+
+#[shared_test_runtime]
+async fn resume_interrupted_upload() {
+	let resources = test_utils::RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let test_dir = &resources.dir;
+
+	let mut contents = vec![0u8; 1024 * 1024 * 6 + 17];
+	rand::rng().try_fill_bytes(&mut contents).unwrap();
+	let size = contents.len() as u64;
+
+	let store = LastCheckpoint::default();
+	let builder = client
+		.make_file_builder("resumed.bin", test_dir.uuid())
+		.unwrap();
+	let mut reader = (&contents[..1024 * 1024 * 4 + 5]).chain(FailingReader);
+	client
+		.upload_file_from_reader_resumable(builder, &mut reader, None, Some(size), &store, None)
+		.await
+		.unwrap_err();
+
+	// which chunks were confirmed before the failure depends on timing
+	let checkpoint = store.0.lock().unwrap().take();
+	let offset = checkpoint
+		.as_ref()
+		.map_or(0, |checkpoint| checkpoint.resume_offset());
+	assert!(offset < size);
+	let builder = client
+		.make_file_builder("resumed.bin", test_dir.uuid())
+		.unwrap();
+	let mut reader = &contents[offset as usize..];
+	let file = client
+		.upload_file_from_reader_resumable(
+			builder,
+			&mut reader,
+			None,
+			Some(size),
+			&store,
+			checkpoint.clone(),
+		)
+		.await
+		.unwrap();
+
+	if let Some(checkpoint) = checkpoint {
+		assert_eq!(file.uuid(), checkpoint.uuid());
+	}
+	assert_eq!(file.size(), size);
+	assert_eq!(
+		file.hash(),
+		Some(filen_types::crypto::Blake3Hash::from(blake3::hash(
+			&contents
+		)))
+	);
+	assert_eq!(client.download_file(&file).await.unwrap(), contents);
+}
+
 #[shared_test_runtime]
 async fn file_versions() {
 	let resources = test_utils::RESOURCES.get_resources().await;
@@ -1405,6 +1525,109 @@ mod http_provider_tests {
 		assert_eq!(body.len(), end - start);
 		assert_eq!(&body[..], &content[start..end]);
 	}
+
+	// ─── directory routes ─────────────────────────────────────────────────────
+
+	/// Children of a directory URL are served by relative path, so a playlist's relative
+	/// segment references resolve without minting a URL per file.
+	#[shared_test_runtime]
+	async fn http_provider_dir_serves_children_by_relative_path() {
+		let resources = test_utils::RESOURCES.get_resources().await;
+		let client = &resources.client;
+		let test_dir = &resources.dir;
+
+		let hls = client.create_dir(&test_dir.into(), "hls").await.unwrap();
+		let segment: Vec<u8> = (0..4096).map(|i| (i % 251) as u8).collect();
+		upload_test_file(client, &hls, "segment 0.ts", &segment).await;
+		upload_test_file(
+			client,
+			&hls,
+			"playlist.m3u8",
+			b"#EXTM3U\n#EXTINF:4.0,\nsegment%200.ts\n#EXT-X-ENDLIST\n",
+		)
+		.await;
+
+		let handle = client.start_http_provider(None).await.unwrap();
+		let dir_url =
+			reqwest::Url::parse(&handle.get_dir_url(client.clone(), test_dir.clone())).unwrap();
+
+		let playlist_url = dir_url.join("hls/playlist.m3u8").unwrap();
+		let playlist = reqwest::get(playlist_url.clone())
+			.await
+			.unwrap()
+			.text()
+			.await
+			.unwrap();
+		let segment_ref = playlist
+			.lines()
+			.find(|line| !line.starts_with('#'))
+			.unwrap();
+
+		let segment_url = playlist_url.join(segment_ref).unwrap();
+		let response = reqwest::get(segment_url.clone()).await.unwrap();
+		assert_eq!(response.status(), 200);
+		assert_eq!(&response.bytes().await.unwrap()[..], &segment[..]);
+
+		let response = reqwest::Client::new()
+			.get(segment_url)
+			.header(http::header::RANGE, "bytes=10-19")
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(
+			response.status(),
+			206,
+			"children must honour Range requests"
+		);
+		assert_eq!(&response.bytes().await.unwrap()[..], &segment[10..20]);
+
+		let missing = reqwest::get(dir_url.join("hls/missing.ts").unwrap())
+			.await
+			.unwrap();
+		assert_eq!(missing.status(), 404);
+	}
+
+	/// A directory URL lists its children as JSON or HTML with relative links, and a
+	/// subdirectory requested without its trailing slash redirects to the slashed URL.
+	#[shared_test_runtime]
+	async fn http_provider_dir_listing() {
+		let resources = test_utils::RESOURCES.get_resources().await;
+		let client = &resources.client;
+		let test_dir = &resources.dir;
+
+		let subs = client.create_dir(&test_dir.into(), "subs").await.unwrap();
+		upload_test_file(
+			client,
+			&subs,
+			"movie.en.srt",
+			b"1\n00:00:01,000 --> 00:00:02,000\nHi\n",
+		)
+		.await;
+		upload_test_file(client, test_dir, "movie.mp4", b"not really a movie").await;
+
+		let handle = client.start_http_provider(None).await.unwrap();
+		let dir_url =
+			reqwest::Url::parse(&handle.get_dir_url(client.clone(), test_dir.clone())).unwrap();
+
+		let listing: serde_json::Value = reqwest::get(dir_url.join("?format=json").unwrap())
+			.await
+			.unwrap()
+			.json()
+			.await
+			.unwrap();
+		assert_eq!(listing["dirs"][0]["name"], "subs");
+		assert_eq!(listing["dirs"][0]["href"], "subs/");
+		assert_eq!(listing["files"][0]["name"], "movie.mp4");
+		assert_eq!(listing["files"][0]["size"], 18);
+
+		// reqwest follows the redirect to `subs/`, so the listing's relative links work
+		let response = reqwest::get(dir_url.join("subs").unwrap()).await.unwrap();
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.url().path(), format!("{}subs/", dir_url.path()));
+		let html = response.text().await.unwrap();
+		assert!(html.contains("<a href=\"../\">"), "{html}");
+		assert!(html.contains("<a href=\"movie.en.srt\">"), "{html}");
+	}
 }
 
 #[cfg(feature = "malformed")]
@@ -1726,6 +1949,40 @@ async fn download_file_to_path_creates_nonexistent_file() {
 	cleanup().await;
 }
 
+// The check for a file that changed during the download compares the target with itself, not
+// with the downloaded temp file, which already has the remote file's times.
+#[shared_test_runtime]
+async fn download_file_to_path_overwrites_existing_file() {
+	let resources = test_utils::RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let test_dir = &resources.dir;
+
+	let modified = chrono::DateTime::from_timestamp_millis(1_600_000_000_000).unwrap();
+	let file = client
+		.make_file_builder("download_overwrite.txt", test_dir.uuid())
+		.unwrap()
+		.modified(modified);
+	let file = client.upload_file(file, b"remote contents").await.unwrap();
+
+	let download_dir = tempfile::tempdir().unwrap();
+	let download_path = download_dir.path().join("downloaded.txt");
+	std::fs::write(&download_path, b"older local contents").unwrap();
+
+	client
+		.download_file_to_path(&file, &download_path, None)
+		.await
+		.unwrap();
+
+	assert_eq!(std::fs::read(&download_path).unwrap(), b"remote contents");
+	let local_modified = chrono::DateTime::<Utc>::from(
+		std::fs::metadata(&download_path)
+			.unwrap()
+			.modified()
+			.unwrap(),
+	);
+	assert_eq!(local_modified, modified, "the remote mtime should be kept");
+}
+
 #[shared_test_runtime]
 async fn download_file_to_path_fails_when_parent_missing() {
 	let resources = test_utils::RESOURCES.get_resources().await;
@@ -1765,6 +2022,62 @@ async fn download_file_to_path_fails_when_parent_missing() {
 	);
 }
 
+#[shared_test_runtime]
+async fn download_file_to_path_resumes_after_cancellation() {
+	let resources = test_utils::RESOURCES.get_resources().await;
+	let client = &resources.client;
+	let test_dir = &resources.dir;
+
+	let mut contents = vec![0u8; 1024 * 1024 * 6 + 17];
+	rand::rng().try_fill_bytes(&mut contents).unwrap();
+	let file = client
+		.make_file_builder("download_resume.bin", test_dir.uuid())
+		.unwrap();
+	let file = client.upload_file(file, &contents).await.unwrap();
+
+	let download_dir = tempfile::tempdir().unwrap();
+	let download_path = download_dir.path().join("downloaded.bin");
+
+	// drop the download once the first bytes arrived, or start over if it finished before that
+	let (started, on_started) = tokio::sync::oneshot::channel::<()>();
+	let started = std::sync::Mutex::new(Some(started));
+	let callback: MaybeSendCallback<u64> = Arc::new(move |_| {
+		if let Some(started) = started.lock().unwrap().take() {
+			let _ = started.send(());
+		}
+	});
+	tokio::select! {
+		res = client.download_file_to_path(&file, &download_path, Some(callback)) => {
+			res.unwrap();
+			std::fs::remove_file(&download_path).unwrap();
+		}
+		_ = on_started => {}
+	}
+
+	let downloaded = Arc::new(std::sync::atomic::AtomicU64::new(0));
+	let counter = downloaded.clone();
+	let callback: MaybeSendCallback<u64> = Arc::new(move |bytes| {
+		counter.fetch_add(bytes, std::sync::atomic::Ordering::Relaxed);
+	});
+	client
+		.download_file_to_path(&file, &download_path, Some(callback))
+		.await
+		.unwrap();
+
+	assert_eq!(std::fs::read(&download_path).unwrap(), contents);
+	assert_eq!(
+		downloaded.load(std::sync::atomic::Ordering::Relaxed),
+		contents.len() as u64,
+		"resumed chunks should count towards the progress"
+	);
+	let leftovers = std::fs::read_dir(download_dir.path())
+		.unwrap()
+		.map(|entry| entry.unwrap().file_name())
+		.filter(|name| name != "downloaded.bin")
+		.collect::<Vec<_>>();
+	assert!(leftovers.is_empty(), "leftover files: {leftovers:?}");
+}
+
 // A restore whose `current` lost a race must come back as StaleState, so the caller knows to
 // refresh and try again rather than treating it as a permanent failure. The server rejects a stale
 // `current` with invalid_params, which is also its generic validation bucket — the mapping is only
@@ -0,0 +1,296 @@
+//! S3 gateway tests, run against the in-memory fake server.
+//!
+//! Run with: `cargo test -p filen-sdk-rs --features http-provider --test s3_tests`
+
+use filen_macros::shared_test_runtime;
+use filen_sdk_rs::{
+	fs::HasName,
+	http_provider::{
+		DriveServer,
+		s3::{S3Credentials, S3Options},
+	},
+};
+use reqwest::StatusCode;
+
+/// Starts a server whose buckets are the drive's top-level directories, and returns the URL of
+/// this test's bucket.
+async fn start_server(options: S3Options) -> (DriveServer, String) {
+	let resources = test_utils::FAKE_RESOURCES.get_resources().await;
+	let server = resources
+		.client
+		.clone()
+		.start_s3_server("127.0.0.1:0".parse().unwrap(), options)
+		.await
+		.unwrap();
+	let bucket = format!(
+		"http://{}/{}",
+		server.address(),
+		resources.dir.name().unwrap()
+	);
+	(server, bucket)
+}
+
+/// The text of every `<name>` element in `body`
+fn elements<'a>(body: &'a str, name: &str) -> Vec<&'a str> {
+	let (open, close) = (format!("<{name}>"), format!("</{name}>"));
+	body.split(open.as_str())
+		.skip(1)
+		.filter_map(|rest| rest.split_once(close.as_str()).map(|(text, _)| text))
+		.collect()
+}
+
+#[shared_test_runtime]
+async fn put_get_list_delete() {
+	let (server, bucket) = start_server(S3Options::default()).await;
+	let http = reqwest::Client::new();
+
+	for (key, body) in [
+		("a.txt", "hello s3"),
+		("photos/2024/b.jpg", "jpeg"),
+		("photos/c.jpg", "jpeg too"),
+	] {
+		let response = http
+			.put(format!("{bucket}/{key}"))
+			.body(body)
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(response.status(), StatusCode::OK);
+		assert!(response.headers().contains_key("etag"));
+	}
+
+	let response = http
+		.get(format!("{bucket}/a.txt"))
+		.header("Range", "bytes=6-")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+	assert!(response.headers().contains_key("last-modified"));
+	assert_eq!(response.text().await.unwrap(), "s3");
+
+	let response = http
+		.head(format!("{bucket}/photos/c.jpg"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.headers()["content-length"], "8");
+
+	let response = http
+		.get(format!("{bucket}?list-type=2&delimiter=/"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::OK);
+	let body = response.text().await.unwrap();
+	assert_eq!(elements(&body, "Key"), ["a.txt"], "{body}");
+	assert_eq!(elements(&body, "Prefix")[1..], ["photos/"], "{body}");
+
+	let response = http
+		.get(format!("{bucket}?list-type=2&prefix=photos/"))
+		.send()
+		.await
+		.unwrap();
+	let body = response.text().await.unwrap();
+	assert_eq!(
+		elements(&body, "Key"),
+		["photos/2024/b.jpg", "photos/c.jpg"],
+		"{body}"
+	);
+
+	// paging through every key, one per page
+	let mut keys = Vec::new();
+	let mut token = None;
+	loop {
+		let mut url = format!("{bucket}?list-type=2&max-keys=1");
+		if let Some(token) = &token {
+			url.push_str(&format!("&continuation-token={token}"));
+		}
+		let body = http.get(url).send().await.unwrap().text().await.unwrap();
+		keys.extend(elements(&body, "Key").into_iter().map(str::to_owned));
+		match elements(&body, "NextContinuationToken").first() {
+			Some(next) => token = Some(next.to_string()),
+			None => break,
+		}
+	}
+	assert_eq!(keys, ["a.txt", "photos/2024/b.jpg", "photos/c.jpg"]);
+
+	let response = http.delete(format!("{bucket}/a.txt")).send().await.unwrap();
+	assert_eq!(response.status(), StatusCode::NO_CONTENT);
+	let response = http.get(format!("{bucket}/a.txt")).send().await.unwrap();
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+	assert!(
+		response
+			.text()
+			.await
+			.unwrap()
+			.contains("<Code>NoSuchKey</Code>")
+	);
+
+	// deleting a missing key succeeds, a missing bucket doesn't
+	let response = http.delete(format!("{bucket}/a.txt")).send().await.unwrap();
+	assert_eq!(response.status(), StatusCode::NO_CONTENT);
+	let response = http
+		.delete(format!("{bucket}-missing/a.txt"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	server.stop().await.unwrap();
+}
+
+#[shared_test_runtime]
+async fn copy_and_multipart_upload() {
+	let (server, bucket) = start_server(S3Options::default()).await;
+	let http = reqwest::Client::new();
+	let bucket_name = bucket.rsplit('/').next().unwrap();
+
+	let response = http
+		.put(format!("{bucket}/source.txt"))
+		.body("copied")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::OK);
+	let response = http
+		.put(format!("{bucket}/copies/target.txt"))
+		.header("x-amz-copy-source", format!("/{bucket_name}/source.txt"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.text().await.unwrap().contains("<CopyObjectResult"));
+	let response = http
+		.get(format!("{bucket}/copies/target.txt"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.text().await.unwrap(), "copied");
+
+	let response = http
+		.post(format!("{bucket}/big.bin?uploads"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::OK);
+	let body = response.text().await.unwrap();
+	let upload_id = elements(&body, "UploadId")[0].to_owned();
+
+	let mut etags = Vec::new();
+	for (number, part) in [(1, "first part, "), (2, "second part")] {
+		let response = http
+			.put(format!(
+				"{bucket}/big.bin?partNumber={number}&uploadId={upload_id}"
+			))
+			.body(part)
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(response.status(), StatusCode::OK);
+		etags.push(response.headers()["etag"].to_str().unwrap().to_owned());
+	}
+
+	// a part list missing a part is rejected, and can be sent again
+	let response = http
+		.post(format!("{bucket}/big.bin?uploadId={upload_id}"))
+		.body(format!(
+			"<CompleteMultipartUpload><Part><PartNumber>1</PartNumber><ETag>{}</ETag></Part></CompleteMultipartUpload>",
+			etags[0]
+		))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+	let parts = etags
+		.iter()
+		.enumerate()
+		.map(|(i, etag)| {
+			format!(
+				"<Part><PartNumber>{}</PartNumber><ETag>{etag}</ETag></Part>",
+				i + 1
+			)
+		})
+		.collect::<String>();
+	let response = http
+		.post(format!("{bucket}/big.bin?uploadId={upload_id}"))
+		.body(format!(
+			"<CompleteMultipartUpload>{parts}</CompleteMultipartUpload>"
+		))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::OK);
+	let response = http.get(format!("{bucket}/big.bin")).send().await.unwrap();
+	assert_eq!(response.text().await.unwrap(), "first part, second part");
+
+	// an aborted upload leaves nothing behind
+	let body = http
+		.post(format!("{bucket}/aborted.bin?uploads"))
+		.send()
+		.await
+		.unwrap()
+		.text()
+		.await
+		.unwrap();
+	let upload_id = elements(&body, "UploadId")[0].to_owned();
+	let response = http
+		.delete(format!("{bucket}/aborted.bin?uploadId={upload_id}"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::NO_CONTENT);
+	let response = http
+		.head(format!("{bucket}/aborted.bin"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	server.stop().await.unwrap();
+}
+
+#[shared_test_runtime]
+async fn credentials_and_read_only() {
+	let (server, bucket) = start_server(S3Options {
+		credentials: Some(S3Credentials {
+			access_key_id: "key".to_owned(),
+			secret_access_key: "secret".to_owned(),
+		}),
+		..Default::default()
+	})
+	.await;
+	let response = reqwest::get(format!("{bucket}?list-type=2")).await.unwrap();
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+	assert!(
+		response
+			.text()
+			.await
+			.unwrap()
+			.contains("<Code>AccessDenied</Code>")
+	);
+	server.stop().await.unwrap();
+
+	let (server, bucket) = start_server(S3Options {
+		read_only: true,
+		..Default::default()
+	})
+	.await;
+	let http = reqwest::Client::new();
+	let response = http
+		.get(format!("{bucket}?list-type=2"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::OK);
+	let response = http
+		.put(format!("{bucket}/file.txt"))
+		.body("contents")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+	server.stop().await.unwrap();
+}
@@ -0,0 +1,267 @@
+//! WebDAV server tests, run against the in-memory fake server.
+//!
+//! Run with: `cargo test -p filen-sdk-rs --features http-provider --test webdav_tests`
+
+use filen_macros::shared_test_runtime;
+use filen_sdk_rs::{
+	fs::HasName,
+	http_provider::{
+		DriveServer,
+		webdav::{WebDavCredentials, WebDavOptions},
+	},
+};
+use reqwest::{Method, StatusCode};
+
+async fn start_server(options: WebDavOptions) -> (DriveServer, String) {
+	let resources = test_utils::FAKE_RESOURCES.get_resources().await;
+	let options = WebDavOptions {
+		root: Some(resources.dir.name().unwrap().to_owned()),
+		..options
+	};
+	let server = resources
+		.client
+		.clone()
+		.start_webdav_server("127.0.0.1:0".parse().unwrap(), options)
+		.await
+		.unwrap();
+	let base = format!("http://{}", server.address());
+	(server, base)
+}
+
+fn method(name: &str) -> Method {
+	Method::from_bytes(name.as_bytes()).unwrap()
+}
+
+#[shared_test_runtime]
+async fn put_get_propfind_delete() {
+	let (server, base) = start_server(WebDavOptions::default()).await;
+	let http = reqwest::Client::new();
+
+	let response = http
+		.put(format!("{base}/a%20file.txt"))
+		.body("hello webdav")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::CREATED);
+
+	let response = http
+		.get(format!("{base}/a%20file.txt"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.headers().contains_key("etag"));
+	assert_eq!(response.text().await.unwrap(), "hello webdav");
+
+	let response = http
+		.get(format!("{base}/a%20file.txt"))
+		.header("Range", "bytes=6-")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+	assert_eq!(response.text().await.unwrap(), "webdav");
+
+	let response = http
+		.request(method("PROPFIND"), format!("{base}/"))
+		.header("Depth", "1")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+	let body = response.text().await.unwrap();
+	assert!(body.contains("<D:href>/a%20file.txt</D:href>"), "{body}");
+	assert!(
+		body.contains("<D:getcontentlength>12</D:getcontentlength>"),
+		"{body}"
+	);
+	assert!(body.contains("<D:collection/>"), "{body}");
+
+	let response = http
+		.request(method("PROPFIND"), format!("{base}/"))
+		.header("Depth", "infinity")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+	let response = http
+		.delete(format!("{base}/a%20file.txt"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::NO_CONTENT);
+	let response = http
+		.get(format!("{base}/a%20file.txt"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	server.stop().await.unwrap();
+}
+
+#[shared_test_runtime]
+async fn mkcol_copy_move() {
+	let (server, base) = start_server(WebDavOptions::default()).await;
+	let http = reqwest::Client::new();
+
+	let response = http
+		.request(method("MKCOL"), format!("{base}/src"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::CREATED);
+	let response = http
+		.request(method("MKCOL"), format!("{base}/missing/dir"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::CONFLICT);
+
+	http.put(format!("{base}/src/file.txt"))
+		.body("contents")
+		.send()
+		.await
+		.unwrap();
+
+	let response = http
+		.request(method("COPY"), format!("{base}/src"))
+		.header("Destination", format!("{base}/copy"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::CREATED);
+	let response = http
+		.get(format!("{base}/copy/file.txt"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.text().await.unwrap(), "contents");
+
+	let response = http
+		.request(method("MOVE"), format!("{base}/src/file.txt"))
+		.header("Destination", format!("{base}/copy/file.txt"))
+		.header("Overwrite", "F")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+	let response = http
+		.request(method("MOVE"), format!("{base}/src/file.txt"))
+		.header("Destination", "/moved.txt")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::CREATED);
+	let response = http.get(format!("{base}/moved.txt")).send().await.unwrap();
+	assert_eq!(response.text().await.unwrap(), "contents");
+	let response = http
+		.get(format!("{base}/src/file.txt"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	server.stop().await.unwrap();
+}
+
+#[shared_test_runtime]
+async fn locks_block_writes_without_their_token() {
+	let (server, base) = start_server(WebDavOptions::default()).await;
+	let http = reqwest::Client::new();
+
+	let response = http
+		.request(method("LOCK"), format!("{base}/locked.txt"))
+		.header("Timeout", "Second-60")
+		.body(
+			r#"<?xml version="1.0"?><D:lockinfo xmlns:D="DAV:">
+				<D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype>
+			</D:lockinfo>"#,
+		)
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::CREATED);
+	let token = response.headers()["Lock-Token"]
+		.to_str()
+		.unwrap()
+		.to_owned();
+	assert!(response.text().await.unwrap().contains("Second-60"));
+
+	let response = http
+		.put(format!("{base}/locked.txt"))
+		.body("blocked")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::LOCKED);
+	let response = http
+		.put(format!("{base}/locked.txt"))
+		.header("If", format!("({token})"))
+		.body("allowed")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+	let response = http
+		.request(method("UNLOCK"), format!("{base}/locked.txt"))
+		.header("Lock-Token", &token)
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::NO_CONTENT);
+	let response = http
+		.delete(format!("{base}/locked.txt"))
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+	server.stop().await.unwrap();
+}
+
+#[shared_test_runtime]
+async fn credentials_and_read_only() {
+	let (server, base) = start_server(WebDavOptions {
+		credentials: Some(WebDavCredentials {
+			user: "user".to_owned(),
+			password: "password".to_owned(),
+		}),
+		read_only: true,
+		..Default::default()
+	})
+	.await;
+	let http = reqwest::Client::new();
+
+	let response = http
+		.request(method("PROPFIND"), format!("{base}/"))
+		.header("Depth", "0")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+	let response = http
+		.request(method("PROPFIND"), format!("{base}/"))
+		.basic_auth("user", Some("password"))
+		.header("Depth", "0")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+
+	let response = http
+		.put(format!("{base}/file.txt"))
+		.basic_auth("user", Some("password"))
+		.body("contents")
+		.send()
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+	server.stop().await.unwrap();
+}
@@ -0,0 +1,186 @@
+use chrono::{DateTime, Utc};
+use filen_sdk_rs::fs::name::{EntryNameError, ValidatedName};
+use serde::Serialize;
+
+// ── Conflict model ──
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Side {
+	Local,
+	Remote,
+}
+
+/// How both sides changed the same path since the last sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ConflictKind {
+	/// Both sides edited (or created) a file, with different content.
+	BothEdited,
+	/// One side edited the entry (or something below it), the other deleted it.
+	EditedAndDeleted { deleted: Side },
+	/// Both sides edited the entry, and it's a file on one side and a directory on the other.
+	TypeChanged,
+}
+
+/// What to do when both sides changed the same path.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictPolicy {
+	/// Keep both versions: the local one is renamed to a conflicted copy and
+	/// uploaded, the remote one takes the original name.
+	/// Edits always win over deletes, since there's nothing to keep of a delete.
+	#[default]
+	KeepBoth,
+	/// The local version wins, including local deletes.
+	PreferLocal,
+	/// The remote version wins, including remote deletes.
+	PreferRemote,
+	/// The version with the newer modification time wins.
+	/// Edits win over deletes, whose time is unknown.
+	NewestWins,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "resolution", rename_all = "camelCase")]
+pub enum Resolution {
+	KeptLocal,
+	KeptRemote,
+	/// The local version was kept as `copy`, next to the remote version.
+	KeptBoth {
+		copy: String,
+	},
+	/// Nothing was done; the conflict comes up again on the next run.
+	Unresolved {
+		reason: String,
+	},
+}
+
+/// A conflict found while planning, and how the plan resolves it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conflict {
+	pub path: String,
+	#[serde(flatten)]
+	pub kind: ConflictKind,
+	#[serde(flatten)]
+	pub resolution: Resolution,
+}
+
+/// Receives every conflict a sync run resolves, e.g. to surface it in a UI.
+pub trait ConflictCallback: Send + Sync {
+	fn on_conflict(&self, conflict: &Conflict);
+}
+
+impl<F> ConflictCallback for F
+where
+	F: Fn(&Conflict) + Send + Sync,
+{
+	fn on_conflict(&self, conflict: &Conflict) {
+		self(conflict)
+	}
+}
+
+// ── Conflicted copy names ──
+
+const MAX_NAME_BYTES: usize = 255;
+
+/// Builds `name (conflicted copy <device> <date>)`, keeping a file's
+/// extension at the end. `attempt`s after the first are numbered to avoid
+/// collisions with existing copies.
+///
+/// The stem is shortened to fit the name length limit; any other way the
+/// result fails `fs::name` validation (e.g. forbidden characters in
+/// `device`) is returned as an error.
+pub fn conflicted_copy_name(
+	name: &str,
+	is_dir: bool,
+	device: &str,
+	date: DateTime<Utc>,
+	attempt: u32,
+) -> Result<String, EntryNameError> {
+	let (stem, ext) = match name.rsplit_once('.') {
+		Some((stem, ext)) if !is_dir && !stem.is_empty() => (stem, Some(ext)),
+		_ => (name, None),
+	};
+	let date = date.format("%Y-%m-%d");
+	let suffix = if attempt > 1 {
+		format!(" (conflicted copy {device} {date} {attempt})")
+	} else {
+		format!(" (conflicted copy {device} {date})")
+	};
+	let ext_len = ext.map_or(0, |ext| ext.len() + 1);
+	let budget = MAX_NAME_BYTES.saturating_sub(suffix.len() + ext_len);
+	let stem = truncate_at_char_boundary(stem, budget).trim_end();
+
+	let mut copy = format!("{stem}{suffix}");
+	if let Some(ext) = ext {
+		copy.push('.');
+		copy.push_str(ext);
+	}
+	ValidatedName::try_from(copy.as_str()).map(String::from)
+}
+
+fn truncate_at_char_boundary(s: &str, max_bytes: usize) -> &str {
+	if s.len() <= max_bytes {
+		return s;
+	}
+	let mut end = max_bytes;
+	while !s.is_char_boundary(end) {
+		end -= 1;
+	}
+	&s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::TimeZone;
+	use filen_sdk_rs::fs::name::EntryNameErrorKind;
+
+	use super::*;
+
+	fn date() -> DateTime<Utc> {
+		Utc.with_ymd_and_hms(2026, 3, 14, 15, 9, 26).unwrap()
+	}
+
+	#[test]
+	fn copy_name_keeps_extension() {
+		assert_eq!(
+			conflicted_copy_name("report.final.pdf", false, "laptop", date(), 1).unwrap(),
+			"report.final (conflicted copy laptop 2026-03-14).pdf"
+		);
+		assert_eq!(
+			conflicted_copy_name(".bashrc", false, "laptop", date(), 1).unwrap(),
+			".bashrc (conflicted copy laptop 2026-03-14)"
+		);
+		assert_eq!(
+			conflicted_copy_name("photos.2025", true, "laptop", date(), 1).unwrap(),
+			"photos.2025 (conflicted copy laptop 2026-03-14)"
+		);
+	}
+
+	#[test]
+	fn later_attempts_are_numbered() {
+		assert_eq!(
+			conflicted_copy_name("a.txt", false, "pc", date(), 3).unwrap(),
+			"a (conflicted copy pc 2026-03-14 3).txt"
+		);
+	}
+
+	#[test]
+	fn long_names_are_shortened_to_fit() {
+		let name = format!("{}.txt", "é".repeat(126));
+		let copy = conflicted_copy_name(&name, false, "laptop", date(), 1).unwrap();
+		assert!(copy.len() <= MAX_NAME_BYTES);
+		assert!(copy.ends_with(" (conflicted copy laptop 2026-03-14).txt"));
+	}
+
+	#[test]
+	fn invalid_device_name_is_rejected() {
+		let err = conflicted_copy_name("a.txt", false, "my:pc", date(), 1).unwrap_err();
+		assert!(matches!(
+			err.kind,
+			EntryNameErrorKind::ForbiddenChar { ch: ':', .. }
+		));
+	}
+}
@@ -0,0 +1,1088 @@
+use std::{
+	borrow::Cow,
+	collections::BTreeMap,
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex, Weak},
+	time::Duration,
+};
+
+use filen_sdk_rs::{
+	auth::Client,
+	fs::{
+		HasUUID,
+		categories::{DirType, NonRootItemType, Normal, fs::CategoryFSExt},
+		dir::{RemoteDirectory, meta::DirectoryMetaChanges},
+		file::{RemoteFile, meta::FileMetaChanges, traits::HasFileInfo},
+	},
+	io::{
+		CategoryDirDownloadExtPub, DirDownloadCallback, DirUploadCallback,
+		client_impl::IoSharedClientExt,
+	},
+};
+use filen_types::crypto::Blake3Hash;
+
+use crate::{
+	Error,
+	conflict::{Conflict, ConflictCallback, ConflictPolicy, Resolution},
+	ignore::{IgnoreStack, IgnoreStackBuilder, is_ignore_file},
+	plan::{Direction, PlanOptions, SyncMode, SyncOp, SyncPlan, plan},
+	remote_watch::{RemoteChangeSet, RemotePaths, RemoteWatcher},
+	scan::{
+		LocalScan, RemoteIndex, RemoteScan, local_state, remote_dir_state, remote_file_state,
+		scan_local, scan_remote, update_local, update_remote,
+	},
+	snapshot::{BaseEntry, BaseSnapshot},
+	state::{PairId, SyncPairRecord, SyncStateDb},
+	tree::{
+		LocalState, LocalTree, RemoteTree, file_name, is_same_or_descendant, join_path,
+		parent_path, rebase_subtree,
+	},
+	watch::{LocalChangeSet, LocalWatcher},
+};
+
+// ── Configuration ──
+
+/// A local directory kept in sync with a remote directory.
+#[derive(Debug, Clone)]
+pub struct SyncPairConfig {
+	pub local_root: PathBuf,
+	pub remote_root: RemoteDirectory,
+	/// The state database holding the pair's base snapshot between runs.
+	/// One database can be shared by any number of pairs.
+	pub state_db: PathBuf,
+	/// The user's global ignore file, applied to every sync pair.
+	pub global_ignore_file: Option<PathBuf>,
+	/// The ignore file for this sync pair only.
+	pub sync_ignore_file: Option<PathBuf>,
+	pub mode: SyncMode,
+	pub conflict_policy: ConflictPolicy,
+	/// Names this device in conflicted copies, so it must be a valid file name.
+	pub device_name: String,
+}
+
+// ── Results ──
+
+/// The outcome of executing a [`SyncPlan`].
+#[derive(Debug, Default)]
+pub struct SyncReport {
+	/// Operations that were carried out.
+	pub applied: Vec<SyncOp>,
+	/// Operations that failed or were skipped. They're retried on the next run.
+	pub failed: Vec<(SyncOp, Error)>,
+	/// Entries that couldn't be scanned on either side and were left alone.
+	pub scan_errors: Vec<Error>,
+	/// Paths that changed on both sides, and how they were resolved.
+	pub conflicts: Vec<Conflict>,
+}
+
+/// A plan together with the scan results needed to execute it.
+pub struct PreparedSync {
+	pub plan: SyncPlan,
+	pub scan_errors: Vec<Error>,
+	local: BTreeMap<String, LocalState>,
+	index: RemoteIndex,
+	ignore: IgnoreStack,
+}
+
+// ── SyncEngine ──
+
+/// Sync between a local directory and a remote directory, in the
+/// [`SyncMode`] of the pair's config.
+///
+/// Each run scans both sides, diffs them against the base snapshot from the
+/// last run and executes the resulting [`SyncPlan`]. The snapshot is only
+/// advanced for operations that succeeded, so anything that failed is
+/// picked up again next time.
+pub struct SyncEngine {
+	client: Arc<Client>,
+	config: SyncPairConfig,
+	ignore_builder: IgnoreStackBuilder,
+	state: SyncStateDb,
+	pair: PairId,
+	/// The latest local scan, updated in place from change sets.
+	local_scan: Option<LocalScan>,
+	/// The latest remote scan, updated in place from remote change sets.
+	remote_scan: Option<RemoteScan>,
+	/// Remote paths that executed operations changed since `remote_scan`.
+	remote_dirty: RemoteChangeSet,
+	/// Shared with the live [`RemoteWatcher`], if there is one.
+	remote_paths: Weak<Mutex<RemotePaths>>,
+	conflict_callback: Option<Arc<dyn ConflictCallback>>,
+	/// Set for [`SyncEngine::new_dry_run`], which treats a missing local root as empty.
+	dry_run: bool,
+}
+
+impl SyncEngine {
+	/// Opens the state database and registers the pair in it.
+	pub fn new(client: Arc<Client>, config: SyncPairConfig) -> Result<Self, Error> {
+		let record = pair_record(&config);
+		let mut state = SyncStateDb::open(&config.state_db)?;
+		let pair = state.register_pair(&record)?;
+		Ok(Self::with_state(
+			client, config, &record, state, pair, false,
+		))
+	}
+
+	/// Like [`SyncEngine::new`], but for dry runs, which mustn't change anything:
+	/// the state database is only read, and the pair's base snapshot is copied
+	/// into a throwaway one, and a local root that doesn't exist yet is
+	/// treated as empty. Plans made with it are only for showing.
+	pub fn new_dry_run(client: Arc<Client>, config: SyncPairConfig) -> Result<Self, Error> {
+		let record = pair_record(&config);
+		let mut base = BaseSnapshot::default();
+		for (path, entry) in SyncStateDb::read_base(&config.state_db, &record)?.entries() {
+			base.insert(path.clone(), *entry);
+		}
+		let mut state = SyncStateDb::open_in_memory()?;
+		let pair = state.register_pair(&record)?;
+		state.commit(pair, &mut base)?;
+		Ok(Self::with_state(client, config, &record, state, pair, true))
+	}
+
+	fn with_state(
+		client: Arc<Client>,
+		config: SyncPairConfig,
+		record: &SyncPairRecord,
+		state: SyncStateDb,
+		pair: PairId,
+		dry_run: bool,
+	) -> Self {
+		Self {
+			client,
+			config,
+			ignore_builder: record.ignore_builder(),
+			state,
+			pair,
+			local_scan: None,
+			remote_scan: None,
+			remote_dirty: RemoteChangeSet::default(),
+			remote_paths: Weak::new(),
+			conflict_callback: None,
+			dry_run,
+		}
+	}
+
+	pub fn config(&self) -> &SyncPairConfig {
+		&self.config
+	}
+
+	/// Sets the callback told about every conflict once its resolution was carried out.
+	pub fn set_conflict_callback(&mut self, callback: Arc<dyn ConflictCallback>) {
+		self.conflict_callback = Some(callback);
+	}
+
+	/// Starts watching the local root, for [`SyncEngine::prepare_changed`].
+	pub fn watch(&self, debounce: Duration) -> Result<LocalWatcher, Error> {
+		LocalWatcher::new(&self.config.local_root, debounce)
+	}
+
+	/// Starts following remote drive events, for
+	/// [`SyncEngine::prepare_remote_changed`].
+	///
+	/// While the watcher is alive, [`SyncEngine::prepare_changed`] relies on
+	/// it for remote changes too and stops re-listing the whole remote root,
+	/// so its change sets must all be passed on.
+	pub async fn watch_remote(&mut self, debounce: Duration) -> Result<RemoteWatcher, Error> {
+		let root = self.config.remote_root.uuid();
+		let paths = match &self.remote_scan {
+			Some(scan) => RemotePaths::from_tree(root, &scan.tree),
+			None => RemotePaths::new(root),
+		};
+		let paths = Arc::new(Mutex::new(paths));
+		self.remote_paths = Arc::downgrade(&paths);
+		RemoteWatcher::new(self.client.clone(), paths, debounce).await
+	}
+
+	/// Scans both sides and plans the sync without changing anything.
+	pub async fn prepare(&mut self) -> Result<PreparedSync, Error> {
+		let local_scan = self.full_local_scan().await?;
+		self.prepare_with(local_scan, None).await
+	}
+
+	/// Like [`SyncEngine::prepare`], but only re-reads the local paths in
+	/// `changes` instead of scanning the whole local tree. Falls back to a
+	/// full scan if there's no earlier scan to update or `changes` asks for one.
+	pub async fn prepare_changed(
+		&mut self,
+		changes: LocalChangeSet,
+	) -> Result<PreparedSync, Error> {
+		let Some(mut local_scan) = self.local_scan.take().filter(|_| !changes.rescan) else {
+			return self.prepare().await;
+		};
+		// new ignore rules apply to the remote side as well
+		let ignore_changed = changes
+			.paths
+			.iter()
+			.any(|path| is_ignore_file(file_name(path)));
+		let remote_changes = (self.remote_paths.strong_count() > 0 && !ignore_changed)
+			.then(RemoteChangeSet::default);
+		local_scan.errors.clear();
+		let local_scan = self
+			.with_ignore_builder(move |root, ignore_builder| {
+				update_local(root, ignore_builder, &mut local_scan, &changes).map(|()| local_scan)
+			})
+			.await?;
+		self.prepare_with(local_scan, remote_changes).await
+	}
+
+	/// Like [`SyncEngine::prepare`], but only re-lists the remote directories
+	/// holding paths in `changes`, and reuses the kept local scan. Falls back
+	/// to full scans if there are no earlier scans to update or `changes`
+	/// asks for one.
+	pub async fn prepare_remote_changed(
+		&mut self,
+		changes: RemoteChangeSet,
+	) -> Result<PreparedSync, Error> {
+		let local_scan = match self.local_scan.take() {
+			Some(local_scan) => local_scan,
+			None => self.full_local_scan().await?,
+		};
+		self.prepare_with(local_scan, (!changes.rescan).then_some(changes))
+			.await
+	}
+
+	/// Scans the whole local tree. Dry runs see a missing local root as empty.
+	async fn full_local_scan(&mut self) -> Result<LocalScan, Error> {
+		let dry_run = self.dry_run;
+		self.with_ignore_builder(move |root, ignore_builder| {
+			if dry_run && !root.exists() {
+				empty_local_scan(ignore_builder)
+			} else {
+				scan_local(root, ignore_builder)
+			}
+		})
+		.await
+	}
+
+	/// Runs `f` with the pair's ignore builder on a blocking thread.
+	async fn with_ignore_builder<T: Send + 'static>(
+		&mut self,
+		f: impl FnOnce(&Path, &mut IgnoreStackBuilder) -> T + Send + 'static,
+	) -> T {
+		let local_root = self.config.local_root.clone();
+		let mut ignore_builder = std::mem::replace(
+			&mut self.ignore_builder,
+			IgnoreStackBuilder::new(&local_root),
+		);
+		let (result, ignore_builder) = tokio::task::spawn_blocking(move || {
+			let result = f(&local_root, &mut ignore_builder);
+			(result, ignore_builder)
+		})
+		.await
+		.expect("local scan panicked");
+		self.ignore_builder = ignore_builder;
+		result
+	}
+
+	/// Plans against `local_scan` and the remote side, which is re-listed in
+	/// full unless `remote_changes` says what to update the kept remote scan with.
+	async fn prepare_with(
+		&mut self,
+		local_scan: LocalScan,
+		remote_changes: Option<RemoteChangeSet>,
+	) -> Result<PreparedSync, Error> {
+		self.local_scan = Some(LocalScan {
+			tree: local_scan.tree.clone(),
+			ignore: local_scan.ignore.clone(),
+			errors: Vec::new(),
+		});
+		let base = self.state.load_base(self.pair)?;
+
+		let kept = self.remote_scan.take();
+		let remote_scan = match (remote_changes, kept) {
+			(Some(mut changes), Some(mut remote_scan)) => {
+				changes.merge(std::mem::take(&mut self.remote_dirty));
+				match update_remote(
+					self.client.clone(),
+					&self.config.local_root,
+					&local_scan.ignore,
+					&mut remote_scan,
+					&changes,
+				)
+				.await
+				{
+					Ok(()) => remote_scan,
+					// a half-updated scan can't be trusted, so start over
+					Err(_) => self.scan_remote(&local_scan.ignore).await?,
+				}
+			}
+			_ => self.scan_remote(&local_scan.ignore).await?,
+		};
+		self.remote_dirty = RemoteChangeSet::default();
+		self.remote_scan = Some(RemoteScan {
+			tree: remote_scan.tree.clone(),
+			index: remote_scan.index.clone(),
+			errors: Vec::new(),
+		});
+		if let Some(paths) = self.remote_paths.upgrade() {
+			*paths.lock().unwrap_or_else(|e| e.into_inner()) =
+				RemotePaths::from_tree(self.config.remote_root.uuid(), &remote_scan.tree);
+		}
+
+		let local_root = self.config.local_root.clone();
+		let local_tree = local_scan.tree;
+		let remote_tree = remote_scan.tree;
+		let options = PlanOptions {
+			mode: self.config.mode,
+			conflict_policy: self.config.conflict_policy,
+			device_name: self.config.device_name.clone(),
+			now: chrono::Utc::now(),
+		};
+		let (plan, local_tree) = tokio::task::spawn_blocking(move || {
+			let plan = plan(&base, &local_tree, &remote_tree, &options, &mut |path| {
+				hash_local_file(&local_root.join(path)).ok()
+			});
+			(plan, local_tree)
+		})
+		.await
+		.expect("sync planning panicked");
+
+		let mut scan_errors = local_scan.errors;
+		scan_errors.extend(remote_scan.errors);
+		Ok(PreparedSync {
+			plan,
+			scan_errors,
+			local: local_tree.entries,
+			index: remote_scan.index,
+			ignore: local_scan.ignore,
+		})
+	}
+
+	async fn scan_remote(&self, ignore: &IgnoreStack) -> Result<RemoteScan, Error> {
+		scan_remote(
+			self.client.clone(),
+			&self.config.remote_root,
+			&self.config.local_root,
+			ignore,
+		)
+		.await
+	}
+
+	/// Executes a prepared plan, committing the base snapshot as it goes.
+	///
+	/// The planned moves are committed together once they're all done, since
+	/// the plan's base already assumes every one of them. After that, each
+	/// operation's result is committed on its own, so a crash loses at most
+	/// the operation in flight, which the next run reconciles.
+	pub async fn execute(&mut self, prepared: PreparedSync) -> Result<SyncReport, Error> {
+		let PreparedSync {
+			plan,
+			scan_errors,
+			local,
+			index,
+			ignore,
+		} = prepared;
+
+		let mut executor = Executor {
+			client: &self.client,
+			local_root: &self.config.local_root,
+			ignore: &ignore,
+			local,
+			index,
+			base: plan.base,
+			blocked: Vec::new(),
+		};
+		for path in plan.forget {
+			executor.base.remove_subtree(&path);
+		}
+		for (path, entry) in plan.adopt {
+			executor.base.insert(path, entry);
+		}
+
+		let mut report = SyncReport {
+			scan_errors,
+			..Default::default()
+		};
+		let mut moves_committed = false;
+		for op in plan.ops {
+			let is_move = matches!(op, SyncOp::MoveRemote { .. } | SyncOp::MoveLocal { .. });
+			if !is_move && !moves_committed {
+				self.state.commit(self.pair, &mut executor.base)?;
+				moves_committed = true;
+			}
+
+			if let Some(blocked) = executor.blocked_by(&op) {
+				let reason = format!("an operation on {blocked} failed");
+				report.failed.push((
+					op.clone(),
+					Error::Skipped {
+						path: op.path().to_owned(),
+						reason,
+					},
+				));
+				continue;
+			}
+			match executor.apply(&op).await {
+				Ok(()) => report.applied.push(op),
+				Err(e) => {
+					executor.on_failure(&op);
+					report.failed.push((op, e));
+				}
+			}
+			if moves_committed {
+				self.state.commit(self.pair, &mut executor.base)?;
+			}
+		}
+		self.state.commit(self.pair, &mut executor.base)?;
+		self.refresh_local_scan(&report).await;
+		self.mark_remote_dirty(&report);
+
+		for mut conflict in plan.conflicts {
+			if let Some((_, e)) = report
+				.failed
+				.iter()
+				.find(|(op, _)| resolves_conflict(&conflict, op))
+			{
+				conflict.resolution = Resolution::Unresolved {
+					reason: e.to_string(),
+				};
+			}
+			if let Some(callback) = &self.conflict_callback {
+				callback.on_conflict(&conflict);
+			}
+			report.conflicts.push(conflict);
+		}
+		Ok(report)
+	}
+
+	/// Re-reads what the executed ops changed locally, so the kept scan is
+	/// current even before the watcher reports those changes.
+	async fn refresh_local_scan(&mut self, report: &SyncReport) {
+		let Some(mut local_scan) = self.local_scan.take() else {
+			return;
+		};
+		let mut changes = LocalChangeSet::default();
+		let ops = report
+			.applied
+			.iter()
+			.chain(report.failed.iter().map(|(op, _)| op));
+		for op in ops.filter(|op| op.direction() == Direction::Down) {
+			changes.paths.insert(op.path().to_owned());
+			match op {
+				SyncOp::MoveLocal { from, .. } => {
+					changes.paths.insert(from.clone());
+				}
+				SyncOp::ConflictCopy { copy, .. } => {
+					changes.paths.insert(copy.clone());
+				}
+				_ => {}
+			}
+		}
+		if changes.is_empty() {
+			self.local_scan = Some(local_scan);
+			return;
+		}
+		// on failure, the next run just scans everything again
+		self.local_scan = self
+			.with_ignore_builder(move |root, ignore_builder| {
+				update_local(root, ignore_builder, &mut local_scan, &changes)
+					.ok()
+					.map(|()| local_scan)
+			})
+			.await;
+	}
+
+	/// Notes what the executed ops changed remotely, so the kept remote scan
+	/// is updated with it even before the remote watcher reports those changes.
+	fn mark_remote_dirty(&mut self, report: &SyncReport) {
+		let Some(remote_scan) = &self.remote_scan else {
+			return;
+		};
+		let ops = report
+			.applied
+			.iter()
+			.chain(report.failed.iter().map(|(op, _)| op));
+		for op in ops.filter(|op| op.direction() == Direction::Up) {
+			let mut paths = vec![op.path()];
+			if let SyncOp::MoveRemote { from, .. } = op {
+				paths.push(from);
+			}
+			for mut path in paths {
+				self.remote_dirty.paths.insert(path.to_owned());
+				// parents created along the way are new to the kept scan too
+				while let Some(parent) =
+					parent_path(path).filter(|parent| remote_scan.index.dir(parent).is_none())
+				{
+					self.remote_dirty.paths.insert(parent.to_owned());
+					path = parent;
+				}
+			}
+		}
+	}
+
+	/// Scans, plans and executes a single sync run.
+	pub async fn sync_once(&mut self) -> Result<SyncReport, Error> {
+		let prepared = self.prepare().await?;
+		self.execute(prepared).await
+	}
+}
+
+/// Plans the first sync of `local_root` into a remote root that doesn't exist
+/// yet, as if it were empty, for dry runs that mustn't create it.
+///
+/// Returns the plan and the local entries that couldn't be scanned.
+pub async fn prepare_missing_remote(
+	local_root: PathBuf,
+	mut ignore_builder: IgnoreStackBuilder,
+	options: PlanOptions,
+) -> Result<(SyncPlan, Vec<Error>), Error> {
+	tokio::task::spawn_blocking(move || {
+		let local_scan = if local_root.exists() {
+			scan_local(&local_root, &mut ignore_builder)?
+		} else {
+			empty_local_scan(&mut ignore_builder)?
+		};
+		let plan = plan(
+			&BaseSnapshot::default(),
+			&local_scan.tree,
+			&RemoteTree::default(),
+			&options,
+			&mut |path| hash_local_file(&local_root.join(path)).ok(),
+		);
+		Ok((plan, local_scan.errors))
+	})
+	.await
+	.expect("sync planning panicked")
+}
+
+fn empty_local_scan(ignore_builder: &mut IgnoreStackBuilder) -> Result<LocalScan, Error> {
+	Ok(LocalScan {
+		tree: LocalTree::default(),
+		ignore: ignore_builder.build()?,
+		errors: Vec::new(),
+	})
+}
+
+fn pair_record(config: &SyncPairConfig) -> SyncPairRecord {
+	SyncPairRecord {
+		local_root: config.local_root.clone(),
+		remote_root: config.remote_root.uuid(),
+		global_ignore_file: config.global_ignore_file.clone(),
+		sync_ignore_file: config.sync_ignore_file.clone(),
+		mode: config.mode,
+	}
+}
+
+/// Whether `op` is part of resolving `conflict`.
+fn resolves_conflict(conflict: &Conflict, op: &SyncOp) -> bool {
+	let copy = match &conflict.resolution {
+		Resolution::KeptBoth { copy } => Some(copy),
+		_ => None,
+	};
+	is_same_or_descendant(op.path(), &conflict.path)
+		|| copy.is_some_and(|copy| is_same_or_descendant(op.path(), copy))
+}
+
+fn hash_local_file(path: &Path) -> std::io::Result<Blake3Hash> {
+	let file = std::fs::File::open(path)?;
+	let mut hasher = blake3::Hasher::new();
+	hasher.update_reader(file)?;
+	Ok(hasher.finalize().into())
+}
+
+// ── Executor ──
+
+struct Executor<'a> {
+	client: &'a Arc<Client>,
+	local_root: &'a Path,
+	ignore: &'a IgnoreStack,
+	/// The local tree as scanned, with local moves applied as they happen.
+	local: BTreeMap<String, LocalState>,
+	index: RemoteIndex,
+	base: BaseSnapshot,
+	/// Paths whose move or creation failed. Later ops below them are skipped.
+	blocked: Vec<String>,
+}
+
+impl Executor<'_> {
+	fn abs_path(&self, path: &str) -> PathBuf {
+		self.local_root.join(path)
+	}
+
+	fn blocked_by(&self, op: &SyncOp) -> Option<&str> {
+		let mut paths = vec![op.path()];
+		if let SyncOp::MoveRemote { from, .. } | SyncOp::MoveLocal { from, .. } = op {
+			paths.push(from);
+		}
+		self.blocked
+			.iter()
+			.find(|blocked| paths.iter().any(|p| is_same_or_descendant(p, blocked)))
+			.map(String::as_str)
+	}
+
+	fn on_failure(&mut self, op: &SyncOp) {
+		match op {
+			SyncOp::MoveRemote { from, to } | SyncOp::MoveLocal { from, to } => {
+				// the plan's base already has the move applied; undo it so the
+				// move is detected again next run
+				self.base.rebase_subtree(to, from);
+				self.blocked.push(to.clone());
+			}
+			SyncOp::CreateRemoteDir { path }
+			| SyncOp::CreateLocalDir { path }
+			| SyncOp::UploadDir { path }
+			| SyncOp::DownloadDir { path } => self.blocked.push(path.clone()),
+			SyncOp::ConflictCopy { path, copy } => {
+				self.blocked.push(path.clone());
+				self.blocked.push(copy.clone());
+			}
+			_ => {}
+		}
+	}
+
+	async fn apply(&mut self, op: &SyncOp) -> Result<(), Error> {
+		match op {
+			SyncOp::CreateRemoteDir { path } => self.ensure_remote_dir(path).await.map(|_| ()),
+			SyncOp::CreateLocalDir { path } => self.create_local_dir(path).await,
+			SyncOp::Upload { path } => self.upload(path).await,
+			SyncOp::Download { path } => self.download(path).await,
+			SyncOp::UploadDir { path } => self.upload_dir(path).await,
+			SyncOp::DownloadDir { path } => self.download_dir(path).await,
+			SyncOp::MoveRemote { from, to } => self.move_remote(from, to).await,
+			SyncOp::MoveLocal { from, to } => self.move_local(from, to).await,
+			SyncOp::DeleteRemote { path } => self.delete_remote(path).await,
+			SyncOp::DeleteLocal { path } => self.delete_local(path).await,
+			SyncOp::ConflictCopy { path, copy } => self.conflict_copy(path, copy).await,
+		}
+	}
+
+	fn record_dir(&mut self, path: &str, dir: &RemoteDirectory) {
+		if let Ok(meta) = std::fs::metadata(self.abs_path(path))
+			&& meta.is_dir()
+		{
+			self.base.insert(
+				path.to_owned(),
+				BaseEntry::new(local_state(&meta), remote_dir_state(dir)),
+			);
+		}
+	}
+
+	fn record_file(&mut self, path: &str, file: &RemoteFile, meta: &std::fs::Metadata) {
+		self.base.insert(
+			path.to_owned(),
+			BaseEntry::new(local_state(meta), remote_file_state(file)),
+		);
+	}
+
+	// ── Remote side ──
+
+	/// Returns the remote directory at `path`, creating it and any missing parents.
+	async fn ensure_remote_dir(&mut self, path: &str) -> Result<RemoteDirectory, Error> {
+		let mut missing = Vec::new();
+		let mut current = path;
+		let mut parent = loop {
+			if let Some(dir) = self.index.dir(current) {
+				break dir.clone();
+			}
+			missing.push(current);
+			// SAFETY: the root is always in the index, so we stop before running out of parents
+			current = parent_path(current).unwrap();
+		};
+		for path in missing.into_iter().rev() {
+			let dir = self
+				.client
+				.create_dir(&DirType::Dir(Cow::Borrowed(&parent)), file_name(path))
+				.await?;
+			self.index.dirs.insert(path.to_owned(), dir.clone());
+			self.record_dir(path, &dir);
+			parent = dir;
+		}
+		Ok(parent)
+	}
+
+	async fn upload(&mut self, path: &str) -> Result<(), Error> {
+		let parent = self
+			.ensure_remote_dir(parent_path(path).unwrap_or(""))
+			.await?;
+		let (file, local_file) = self
+			.client
+			.upload_file_from_path(
+				&DirType::Dir(Cow::Borrowed(&parent)),
+				self.abs_path(path),
+				None,
+			)
+			.await?;
+		let meta = local_file
+			.metadata()
+			.map_err(|e| Error::io(self.abs_path(path), e))?;
+		self.record_file(path, &file, &meta);
+		self.index.files.insert(path.to_owned(), file);
+		Ok(())
+	}
+
+	async fn upload_dir(&mut self, path: &str) -> Result<(), Error> {
+		let parent = self
+			.ensure_remote_dir(parent_path(path).unwrap_or(""))
+			.await?;
+		let dir = self
+			.client
+			.create_dir(&DirType::Dir(Cow::Borrowed(&parent)), file_name(path))
+			.await?;
+		self.index.dirs.insert(path.to_owned(), dir.clone());
+		self.record_dir(path, &dir);
+
+		let callback = ErrorCollector::default();
+		let ignore = self.ignore;
+		self.client
+			.clone()
+			.upload_dir_recursively_filtered(
+				self.abs_path(path),
+				&callback,
+				&dir,
+				|path, is_dir| {
+					!ignore.is_ignored(path, is_dir)
+						&& !std::fs::symlink_metadata(path)
+							.is_ok_and(|m| m.file_type().is_symlink())
+				},
+			)
+			.await?;
+
+		// Record what actually made it to the remote, so anything that failed is
+		// retried as a regular upload next run
+		let (dirs, files) = Normal::list_dir_recursive_with_paths(
+			self.client.clone(),
+			DirType::Dir(Cow::Borrowed(&dir)),
+			None::<&fn(u64, Option<u64>)>,
+			&mut |_| {},
+			(),
+		)
+		.await?;
+		for (dir, rel_path) in dirs {
+			let path = join_path(path, &rel_path);
+			self.record_dir(&path, &dir);
+			self.index.dirs.insert(path, dir);
+		}
+		for (file, rel_path) in files {
+			let path = join_path(path, &rel_path);
+			if let Ok(meta) = std::fs::metadata(self.abs_path(&path))
+				&& meta.is_file()
+			{
+				self.record_file(&path, &file, &meta);
+			}
+			self.index.files.insert(path, file);
+		}
+		callback.into_result()
+	}
+
+	async fn move_remote(&mut self, from: &str, to: &str) -> Result<(), Error> {
+		let new_parent = if parent_path(from) != parent_path(to) {
+			Some(
+				self.ensure_remote_dir(parent_path(to).unwrap_or(""))
+					.await?,
+			)
+		} else {
+			None
+		};
+		let new_name = (file_name(from) != file_name(to)).then(|| file_name(to));
+
+		if let Some(mut dir) = self.index.dirs.get(from).cloned() {
+			if let Some(new_parent) = &new_parent {
+				self.client
+					.move_dir(&mut dir, &DirType::Dir(Cow::Borrowed(new_parent)))
+					.await?;
+			}
+			if let Some(name) = new_name {
+				let changes = DirectoryMetaChanges::default()
+					.name(name)
+					.map_err(filen_sdk_rs::Error::from)?;
+				self.client.update_dir_metadata(&mut dir, changes).await?;
+			}
+			self.index.rebase_subtree(from, to);
+			self.index.dirs.insert(to.to_owned(), dir);
+		} else if let Some(mut file) = self.index.files.get(from).cloned() {
+			if let Some(new_parent) = &new_parent {
+				self.client
+					.move_file(&mut file, &DirType::Dir(Cow::Borrowed(new_parent)))
+					.await?;
+			}
+			if let Some(name) = new_name {
+				let changes = FileMetaChanges::default()
+					.name(name)
+					.map_err(filen_sdk_rs::Error::from)?;
+				self.client.update_file_metadata(&mut file, changes).await?;
+			}
+			self.index.rebase_subtree(from, to);
+			self.index.files.insert(to.to_owned(), file);
+		} else {
+			return Err(Error::ChangedSinceScan {
+				path: from.to_owned(),
+			});
+		}
+		Ok(())
+	}
+
+	async fn delete_remote(&mut self, path: &str) -> Result<(), Error> {
+		if let Some(mut dir) = self.index.dirs.get(path).cloned() {
+			self.client.trash_dir(&mut dir).await?;
+		} else if let Some(mut file) = self.index.files.get(path).cloned() {
+			self.client.trash_file(&mut file).await?;
+		}
+		self.index.remove_subtree(path);
+		self.base.remove_subtree(path);
+		Ok(())
+	}
+
+	// ── Local side ──
+
+	async fn create_local_dir(&mut self, path: &str) -> Result<(), Error> {
+		let abs_path = self.abs_path(path);
+		tokio::fs::create_dir_all(&abs_path)
+			.await
+			.map_err(|e| Error::io(&abs_path, e))?;
+		if let Some(dir) = self.index.dir(path).cloned() {
+			self.record_dir(path, &dir);
+		}
+		Ok(())
+	}
+
+	async fn download(&mut self, path: &str) -> Result<(), Error> {
+		let file = self
+			.index
+			.files
+			.get(path)
+			.cloned()
+			.ok_or_else(|| Error::ChangedSinceScan {
+				path: path.to_owned(),
+			})?;
+		let abs_path = self.abs_path(path);
+		if let Some(parent) = abs_path.parent() {
+			tokio::fs::create_dir_all(parent)
+				.await
+				.map_err(|e| Error::io(parent, e))?;
+		}
+		self.client
+			.download_file_to_path(&file, &abs_path, None)
+			.await?;
+		let meta = tokio::fs::metadata(&abs_path)
+			.await
+			.map_err(|e| Error::io(&abs_path, e))?;
+		self.record_file(path, &file, &meta);
+		Ok(())
+	}
+
+	async fn download_dir(&mut self, path: &str) -> Result<(), Error> {
+		let dir = self
+			.index
+			.dirs
+			.get(path)
+			.cloned()
+			.ok_or_else(|| Error::ChangedSinceScan {
+				path: path.to_owned(),
+			})?;
+		let abs_path = self.abs_path(path);
+		let Some(abs_path_str) = abs_path.to_str().map(str::to_owned) else {
+			return Err(Error::InvalidPath(abs_path));
+		};
+		let callback = ErrorCollector::default();
+		Normal::download_dir_recursively(
+			self.client.clone(),
+			abs_path_str,
+			&callback,
+			DirType::Dir(Cow::Owned(dir.clone())),
+			(),
+		)
+		.await?;
+
+		// Record what actually made it to disk, so anything that failed is
+		// retried as a regular download next run
+		self.record_dir(path, &dir);
+		let dirs = self
+			.index
+			.dirs
+			.iter()
+			.filter(|(p, _)| is_same_or_descendant(p, path) && p.as_str() != path)
+			.map(|(p, d)| (p.clone(), d.clone()))
+			.collect::<Vec<_>>();
+		for (path, dir) in dirs {
+			self.record_dir(&path, &dir);
+		}
+		let files = self
+			.index
+			.files
+			.iter()
+			.filter(|(p, _)| is_same_or_descendant(p, path))
+			.map(|(p, f)| (p.clone(), f.clone()))
+			.collect::<Vec<_>>();
+		for (path, file) in files {
+			if let Ok(meta) = std::fs::metadata(self.abs_path(&path))
+				&& meta.is_file()
+				&& meta.len() == file.size()
+			{
+				self.record_file(&path, &file, &meta);
+			}
+		}
+		callback.into_result()
+	}
+
+	async fn move_local(&mut self, from: &str, to: &str) -> Result<(), Error> {
+		let (abs_from, abs_to) = (self.abs_path(from), self.abs_path(to));
+		if tokio::fs::symlink_metadata(&abs_to).await.is_ok() {
+			return Err(Error::ChangedSinceScan {
+				path: to.to_owned(),
+			});
+		}
+		if let Some(parent) = abs_to.parent() {
+			tokio::fs::create_dir_all(parent)
+				.await
+				.map_err(|e| Error::io(parent, e))?;
+		}
+		tokio::fs::rename(&abs_from, &abs_to)
+			.await
+			.map_err(|e| Error::io(&abs_from, e))?;
+		rebase_subtree(&mut self.local, from, to);
+		Ok(())
+	}
+
+	/// Renames the local entry at `path` to `copy`, which is then uploaded as a
+	/// new entry. The base entry is dropped, since it described the copy.
+	async fn conflict_copy(&mut self, path: &str, copy: &str) -> Result<(), Error> {
+		let (abs_path, abs_copy) = (self.abs_path(path), self.abs_path(copy));
+		if tokio::fs::symlink_metadata(&abs_copy).await.is_ok() {
+			return Err(Error::ChangedSinceScan {
+				path: copy.to_owned(),
+			});
+		}
+		tokio::fs::rename(&abs_path, &abs_copy)
+			.await
+			.map_err(|e| Error::io(&abs_path, e))?;
+		rebase_subtree(&mut self.local, path, copy);
+		self.base.remove_subtree(path);
+		Ok(())
+	}
+
+	/// Deletes a local entry, unless something in it changed since it was scanned.
+	///
+	/// The plan decided the entry should go as it was then; anything newer
+	/// is left for the next run to look at.
+	async fn delete_local(&mut self, path: &str) -> Result<(), Error> {
+		let abs_path = self.abs_path(path);
+		let is_dir = match std::fs::symlink_metadata(&abs_path) {
+			Ok(meta) => meta.is_dir(),
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+				self.base.remove_subtree(path);
+				return Ok(());
+			}
+			Err(e) => return Err(Error::io(&abs_path, e)),
+		};
+		let ignore = self.ignore;
+		let is_ignored = |p: &Path, is_dir: bool| ignore.is_ignored(p, is_dir);
+		if let Some(changed) = find_changed_local(&abs_path, path, &self.local, &is_ignored)? {
+			return Err(Error::ChangedSinceScan { path: changed });
+		}
+		let res = if is_dir {
+			tokio::fs::remove_dir_all(&abs_path).await
+		} else {
+			tokio::fs::remove_file(&abs_path).await
+		};
+		res.map_err(|e| Error::io(&abs_path, e))?;
+		self.base.remove_subtree(path);
+		Ok(())
+	}
+}
+
+/// Returns the first entry at or below `path` that isn't in `scanned` as it
+/// is on disk now. Ignored entries don't count.
+fn find_changed_local(
+	abs_path: &Path,
+	path: &str,
+	scanned: &BTreeMap<String, LocalState>,
+	is_ignored: &impl Fn(&Path, bool) -> bool,
+) -> Result<Option<String>, Error> {
+	let meta = std::fs::symlink_metadata(abs_path).map_err(|e| Error::io(abs_path, e))?;
+	let is_dir = meta.is_dir();
+	if is_ignored(abs_path, is_dir) {
+		return Ok(None);
+	}
+	let unchanged = scanned
+		.get(path)
+		.is_some_and(|state| !local_state(&meta).content_differs(state));
+	if !unchanged {
+		return Ok(Some(path.to_owned()));
+	}
+	if !is_dir {
+		return Ok(None);
+	}
+	let read_dir = std::fs::read_dir(abs_path).map_err(|e| Error::io(abs_path, e))?;
+	for entry in read_dir {
+		let entry = entry.map_err(|e| Error::io(abs_path, e))?;
+		let Some(name) = entry.file_name().to_str().map(str::to_owned) else {
+			return Ok(Some(join_path(path, &entry.file_name().to_string_lossy())));
+		};
+		if let Some(changed) =
+			find_changed_local(&entry.path(), &join_path(path, &name), scanned, is_ignored)?
+		{
+			return Ok(Some(changed));
+		}
+	}
+	Ok(None)
+}
+
+// ── SDK callbacks ──
+
+/// Collects the errors of a directory transfer; progress is ignored.
+#[derive(Default)]
+struct ErrorCollector {
+	errors: Mutex<Vec<Error>>,
+}
+
+impl ErrorCollector {
+	fn push(&self, error: Error) {
+		self.errors
+			.lock()
+			.unwrap_or_else(|e| e.into_inner())
+			.push(error);
+	}
+
+	/// Fails with the first collected error, if any.
+	fn into_result(self) -> Result<(), Error> {
+		let errors = self.errors.into_inner().unwrap_or_else(|e| e.into_inner());
+		match errors.into_iter().next() {
+			Some(e) => Err(e),
+			None => Ok(()),
+		}
+	}
+}
+
+impl DirUploadCallback for ErrorCollector {
+	fn on_scan_progress(&self, _known_dirs: u64, _known_files: u64, _known_bytes: u64) {}
+	fn on_scan_errors(&self, errors: Vec<filen_sdk_rs::Error>) {
+		errors.into_iter().for_each(|e| self.push(e.into()));
+	}
+	fn on_scan_complete(&self, _total_dirs: u64, _total_files: u64, _total_bytes: u64) {}
+	fn on_upload_update(
+		&self,
+		_uploaded_dirs: Vec<RemoteDirectory>,
+		_uploaded_files: Vec<RemoteFile>,
+		_uploaded_bytes: u64,
+	) {
+	}
+	fn on_upload_errors(&self, errors: Vec<(PathBuf, filen_sdk_rs::Error)>) {
+		errors.into_iter().for_each(|(_, e)| self.push(e.into()));
+	}
+}
+
+impl DirDownloadCallback<Normal> for ErrorCollector {
+	fn on_query_download_progress(&self, _known_bytes: u64, _total_bytes: Option<u64>) {}
+	fn on_scan_progress(&self, _known_dirs: u64, _known_files: u64, _known_bytes: u64) {}
+	fn on_scan_errors(&self, errors: Vec<filen_sdk_rs::Error>) {
+		errors.into_iter().for_each(|e| self.push(e.into()));
+	}
+	fn on_scan_complete(&self, _total_dirs: u64, _total_files: u64, _total_bytes: u64) {}
+	fn on_download_update(
+		&self,
+		_downloaded_dirs: Vec<(RemoteDirectory, String)>,
+		_downloaded_files: Vec<(RemoteFile, String)>,
+		_downloaded_bytes: u64,
+	) {
+	}
+	fn on_download_errors(
+		&self,
+		errors: Vec<(
+			filen_sdk_rs::Error,
+			String,
+			NonRootItemType<'static, Normal>,
+		)>,
+	) {
+		errors.into_iter().for_each(|(e, _, _)| self.push(e.into()));
+	}
+}
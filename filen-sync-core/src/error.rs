@@ -0,0 +1,36 @@
+use std::path::{Path, PathBuf};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error("I/O error at {path}: {source}")]
+	Io {
+		path: PathBuf,
+		#[source]
+		source: std::io::Error,
+	},
+	#[error("SDK error: {0}")]
+	Sdk(#[from] filen_sdk_rs::Error),
+	#[error("Ignore rules error: {0}")]
+	Ignore(#[from] ignore::Error),
+	#[error("File watcher error: {0}")]
+	Watch(#[from] notify_debouncer_full::notify::Error),
+	#[error("Sync state database error: {0}")]
+	Db(#[from] rusqlite::Error),
+	#[error("Sync state database has schema version {found}, but only {supported} is supported")]
+	UnsupportedStateVersion { found: i64, supported: i64 },
+	#[error("Invalid sync path: {0}")]
+	InvalidPath(PathBuf),
+	#[error("{path} changed since it was scanned")]
+	ChangedSinceScan { path: String },
+	#[error("Skipped {path}: {reason}")]
+	Skipped { path: String, reason: String },
+}
+
+impl Error {
+	pub(crate) fn io(path: impl AsRef<Path>, source: std::io::Error) -> Self {
+		Self::Io {
+			path: path.as_ref().to_path_buf(),
+			source,
+		}
+	}
+}
@@ -38,6 +38,8 @@ pub const DEFAULT_PATTERNS: &[&str] = &[
 	"*.crdownload",
 	"*.part",
 	"*.partial",
+	"*.filendl",
+	"*.filendl.chunks",
 ];
 
 // ── IgnoreStack ──
@@ -49,7 +51,7 @@ pub const DEFAULT_PATTERNS: &[&str] = &[
 /// `Send + Sync` for concurrent use. The integration crate manages access
 /// (e.g. behind `ArcSwap` or `RwLock`) if concurrent reads during rebuilds
 /// are needed.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct IgnoreStack(Gitignore);
 
 impl IgnoreStack {
@@ -220,13 +222,21 @@ impl IgnoreStackBuilder {
 		self
 	}
 
+	/// Adds a folder `.filenignore`. Adding one that's already known is a no-op.
 	pub fn add_folder_ignore_file(&mut self, path: impl Into<PathBuf>) -> &mut Self {
-		self.folder_files.push(path.into());
+		let path = path.into();
+		if !self.folder_files.contains(&path) {
+			self.folder_files.push(path);
+		}
 		self
 	}
 
+	/// Adds a folder `.local.filenignore`. Adding one that's already known is a no-op.
 	pub fn add_local_folder_ignore_file(&mut self, path: impl Into<PathBuf>) -> &mut Self {
-		self.local_folder_files.push(path.into());
+		let path = path.into();
+		if !self.local_folder_files.contains(&path) {
+			self.local_folder_files.push(path);
+		}
 		self
 	}
 
@@ -242,6 +252,15 @@ impl IgnoreStackBuilder {
 		self.local_folder_files.len() < before
 	}
 
+	/// Removes every folder ignore file (of both kinds) inside `dir`, e.g.
+	/// because it was deleted or moved away.
+	pub fn remove_ignore_files_under(&mut self, dir: &Path) -> bool {
+		let before = self.folder_files.len() + self.local_folder_files.len();
+		self.folder_files.retain(|p| !p.starts_with(dir));
+		self.local_folder_files.retain(|p| !p.starts_with(dir));
+		self.folder_files.len() + self.local_folder_files.len() < before
+	}
+
 	pub fn clear_folder_ignore_files(&mut self) -> &mut Self {
 		self.folder_files.clear();
 		self
@@ -538,6 +557,25 @@ mod tests {
 		assert!(!builder.remove_folder_ignore_file(path)); // already removed
 	}
 
+	#[test]
+	fn add_is_idempotent_and_remove_under_clears_subtree() {
+		let mut builder = IgnoreStackBuilder::new("/root");
+		builder
+			.add_folder_ignore_file("/root/sub/.filenignore")
+			.add_folder_ignore_file("/root/sub/.filenignore")
+			.add_local_folder_ignore_file("/root/sub/deep/.local.filenignore")
+			.add_folder_ignore_file("/root/subway/.filenignore");
+		assert_eq!(builder.folder_files.len(), 2);
+
+		assert!(builder.remove_ignore_files_under(Path::new("/root/sub")));
+		assert_eq!(
+			builder.folder_files,
+			[PathBuf::from("/root/subway/.filenignore")]
+		);
+		assert!(builder.local_folder_files.is_empty());
+		assert!(!builder.remove_ignore_files_under(Path::new("/root/sub")));
+	}
+
 	#[test]
 	fn subdirectory_negation_scoped() {
 		// Root ignores *.log, src/ whitelists *.log
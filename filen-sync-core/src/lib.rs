@@ -1 +1,13 @@
+pub mod conflict;
+pub mod engine;
+mod error;
 pub mod ignore;
+pub mod plan;
+pub mod remote_watch;
+pub mod scan;
+pub mod snapshot;
+pub mod state;
+pub mod tree;
+pub mod watch;
+
+pub use error::Error;
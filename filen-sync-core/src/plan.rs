@@ -0,0 +1,1674 @@
+use std::{
+	collections::{BTreeMap, BTreeSet, HashMap},
+	hash::Hash,
+};
+
+use chrono::{DateTime, Utc};
+use filen_types::crypto::Blake3Hash;
+use serde::Serialize;
+
+use crate::{
+	conflict::{Conflict, ConflictKind, ConflictPolicy, Resolution, Side, conflicted_copy_name},
+	snapshot::{BaseEntry, BaseSnapshot},
+	tree::{
+		EntryKind, LocalState, LocalTree, RemoteState, RemoteTree, descendants, file_name,
+		is_same_or_descendant, join_path, parent_path, rebase_path, rebase_subtree,
+	},
+};
+
+// ── Operations ──
+
+/// Which side an operation modifies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Direction {
+	/// Local → remote: the operation changes the remote side.
+	Up,
+	/// Remote → local: the operation changes the local side.
+	Down,
+}
+
+/// A single step of a [`SyncPlan`]. Paths are sync paths.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum SyncOp {
+	CreateRemoteDir {
+		path: String,
+	},
+	CreateLocalDir {
+		path: String,
+	},
+	/// Uploads a local file, as a new file or a new version of the remote one.
+	Upload {
+		path: String,
+	},
+	/// Downloads a remote file, replacing the local one.
+	Download {
+		path: String,
+	},
+	/// Uploads a directory that only exists locally, including everything below it.
+	UploadDir {
+		path: String,
+	},
+	/// Downloads a directory that only exists remotely, including everything below it.
+	DownloadDir {
+		path: String,
+	},
+	/// Moves and/or renames a remote entry.
+	MoveRemote {
+		from: String,
+		to: String,
+	},
+	/// Moves and/or renames a local entry.
+	MoveLocal {
+		from: String,
+		to: String,
+	},
+	/// Trashes a remote entry.
+	DeleteRemote {
+		path: String,
+	},
+	DeleteLocal {
+		path: String,
+	},
+	/// Renames a conflicting local entry out of the way, to `copy`, so the
+	/// remote one can take its place.
+	ConflictCopy {
+		path: String,
+		copy: String,
+	},
+}
+
+impl SyncOp {
+	/// The path the operation results in (the destination for moves).
+	pub fn path(&self) -> &str {
+		match self {
+			SyncOp::CreateRemoteDir { path }
+			| SyncOp::CreateLocalDir { path }
+			| SyncOp::Upload { path }
+			| SyncOp::Download { path }
+			| SyncOp::UploadDir { path }
+			| SyncOp::DownloadDir { path }
+			| SyncOp::DeleteRemote { path }
+			| SyncOp::DeleteLocal { path }
+			| SyncOp::ConflictCopy { path, .. } => path,
+			SyncOp::MoveRemote { to, .. } | SyncOp::MoveLocal { to, .. } => to,
+		}
+	}
+
+	pub fn direction(&self) -> Direction {
+		match self {
+			SyncOp::CreateRemoteDir { .. }
+			| SyncOp::Upload { .. }
+			| SyncOp::UploadDir { .. }
+			| SyncOp::MoveRemote { .. }
+			| SyncOp::DeleteRemote { .. } => Direction::Up,
+			SyncOp::CreateLocalDir { .. }
+			| SyncOp::Download { .. }
+			| SyncOp::DownloadDir { .. }
+			| SyncOp::MoveLocal { .. }
+			| SyncOp::DeleteLocal { .. }
+			| SyncOp::ConflictCopy { .. } => Direction::Down,
+		}
+	}
+}
+
+/// Everything needed to bring both sides in sync.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncPlan {
+	/// Operations in execution order: moves and conflicted copies first, then
+	/// everything else ordered by path so parents are handled before their children.
+	pub ops: Vec<SyncOp>,
+	/// The base snapshot with every planned move already applied, journaled
+	/// so committing it records the moves. The executor starts from this and
+	/// folds in the results of `ops`.
+	pub base: BaseSnapshot,
+	/// Paths that changed identically on both sides and only need recording.
+	pub adopt: Vec<(String, BaseEntry)>,
+	/// Paths that no longer need tracking, e.g. because they were deleted on
+	/// both sides.
+	pub forget: Vec<String>,
+	/// Paths that changed on both sides, and how `ops` resolves them.
+	pub conflicts: Vec<Conflict>,
+}
+
+impl SyncPlan {
+	pub fn is_empty(&self) -> bool {
+		self.ops.is_empty() && self.adopt.is_empty() && self.forget.is_empty()
+	}
+}
+
+/// Which way changes flow between the two sides of a sync pair.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncMode {
+	/// Changes on either side are propagated to the other.
+	#[default]
+	TwoWay,
+	/// The remote side mirrors the local one: local changes are uploaded and
+	/// remote changes are undone. Only the remote side is ever modified.
+	MirrorUp,
+	/// The local side mirrors the remote one: remote changes are downloaded
+	/// and local changes are undone. Only the local side is ever modified.
+	MirrorDown,
+	/// Like [`SyncMode::MirrorUp`], except nothing is taken away from the
+	/// remote side: local deletes aren't propagated, remote-only entries are
+	/// kept, and local moves upload a new copy instead of moving the old one.
+	/// Only an entry replaced locally by one of the other type is replaced
+	/// remotely too.
+	BackupOnly,
+}
+
+/// Settings that affect how a plan is made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanOptions {
+	pub mode: SyncMode,
+	/// Only used in [`SyncMode::TwoWay`]; the one-way modes always side with their source.
+	pub conflict_policy: ConflictPolicy,
+	/// Names this device in conflicted copies.
+	pub device_name: String,
+	/// Dates conflicted copies.
+	pub now: DateTime<Utc>,
+}
+
+// ── Deltas ──
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+	/// Not present now and not in the base.
+	Absent,
+	Unchanged,
+	Created,
+	Modified,
+	Deleted,
+}
+
+impl Change {
+	fn is_edit(self) -> bool {
+		matches!(self, Change::Created | Change::Modified)
+	}
+}
+
+fn local_change(base: Option<&BaseEntry>, current: Option<&LocalState>) -> Change {
+	match (base, current) {
+		(None, None) => Change::Absent,
+		(None, Some(_)) => Change::Created,
+		(Some(_), None) => Change::Deleted,
+		(Some(base), Some(current)) if current.content_differs(&base.local) => Change::Modified,
+		(Some(_), Some(_)) => Change::Unchanged,
+	}
+}
+
+fn remote_change(base: Option<&BaseEntry>, current: Option<&RemoteState>) -> Change {
+	match (base, current) {
+		(None, None) => Change::Absent,
+		(None, Some(_)) => Change::Created,
+		(Some(_), None) => Change::Deleted,
+		(Some(base), Some(current)) if current.content_differs(&base.remote) => Change::Modified,
+		(Some(_), Some(_)) => Change::Unchanged,
+	}
+}
+
+/// Matches entries that left their base path with entries that appeared
+/// elsewhere, by identity key. Returns `from → to` for top-level moves only:
+/// children that moved along with a moved parent are implied.
+fn detect_moves<S, K>(
+	base: &BTreeMap<String, BaseEntry>,
+	current: &BTreeMap<String, S>,
+	base_key: impl Fn(&BaseEntry) -> Option<K>,
+	current_key: impl Fn(&S) -> Option<K>,
+) -> BTreeMap<String, String>
+where
+	K: Eq + Hash,
+{
+	// `None` marks keys seen at several new paths (e.g. hard links), which are ambiguous
+	let mut arrived: HashMap<K, Option<&str>> = HashMap::new();
+	for (path, state) in current {
+		let Some(key) = current_key(state) else {
+			continue;
+		};
+		if base.get(path).and_then(&base_key).as_ref() == Some(&key) {
+			continue;
+		}
+		arrived
+			.entry(key)
+			.and_modify(|p| *p = None)
+			.or_insert(Some(path));
+	}
+
+	let mut top_level = BTreeMap::<String, String>::new();
+	for (from, entry) in base {
+		let Some(key) = base_key(entry) else {
+			continue;
+		};
+		if current.get(from).and_then(&current_key).as_ref() == Some(&key) {
+			continue;
+		}
+		let Some(Some(to)) = arrived.get(&key) else {
+			continue;
+		};
+		let implied = std::iter::successors(parent_path(from), |p| parent_path(p))
+			.find_map(|ancestor| top_level.get(ancestor).map(|dest| (ancestor, dest)))
+			.is_some_and(|(ancestor, dest)| {
+				rebase_path(from, ancestor, dest).as_deref() == Some(*to)
+			});
+		if !implied {
+			top_level.insert(from.clone(), (*to).to_owned());
+		}
+	}
+	top_level
+}
+
+fn local_key(state: &LocalState) -> Option<(EntryKind, u64, u64, i64)> {
+	// files must also keep their content, so a reused inode isn't mistaken for a move
+	let inode = state.inode?;
+	Some(match state.kind {
+		EntryKind::Dir => (EntryKind::Dir, inode, 0, 0),
+		EntryKind::File => (EntryKind::File, inode, state.size, state.mtime),
+	})
+}
+
+fn remote_key(state: &RemoteState) -> Option<(EntryKind, uuid::Uuid)> {
+	Some((state.kind, state.id))
+}
+
+/// Replays `shifts` (applied in order) on a path.
+fn translate(path: &str, shifts: &[(String, String)]) -> String {
+	shifts.iter().fold(path.to_owned(), |path, (from, to)| {
+		rebase_path(&path, from, to).unwrap_or(path)
+	})
+}
+
+// ── Planner ──
+
+/// Computes the operations that bring `local` and `remote` in sync, given
+/// the `base` both sides were in sync at.
+///
+/// `local_hash` is asked for the Blake3 hash of a local file when a path was
+/// edited on both sides and can only be told apart by content.
+///
+/// Resolution rules:
+/// - a change on one side is propagated to the other
+/// - a move on one side is replayed on the other; if both sides moved the
+///   same entry to different places, the local move wins
+/// - edits on both sides, edits against deletes (including edits below a
+///   deleted directory) and file/directory swaps are conflicts, resolved by
+///   `options.conflict_policy` and listed in [`SyncPlan::conflicts`]
+///
+/// These apply to [`SyncMode::TwoWay`]. The one-way modes instead make the
+/// target side match the source side wherever either of them changed.
+pub fn plan(
+	base: &BaseSnapshot,
+	local: &LocalTree,
+	remote: &RemoteTree,
+	options: &PlanOptions,
+	local_hash: &mut dyn FnMut(&str) -> Option<Blake3Hash>,
+) -> SyncPlan {
+	let mut planner = Planner {
+		options,
+		base: base.clone(),
+		local: local.entries.clone(),
+		remote: remote.entries.clone(),
+		remote_ignored: &remote.ignored,
+		renames: Vec::new(),
+		ops: Vec::new(),
+		adopt: Vec::new(),
+		forget: Vec::new(),
+		deleted_remote_dirs: Vec::new(),
+		deleted_local_dirs: Vec::new(),
+		kept_remote_dirs: Vec::new(),
+		conflicts: Vec::new(),
+		conflict_dirs: Vec::new(),
+	};
+	planner.plan_moves();
+	planner.plan_paths(local_hash);
+	planner.finish()
+}
+
+struct Planner<'a> {
+	options: &'a PlanOptions,
+	base: BaseSnapshot,
+	local: BTreeMap<String, LocalState>,
+	remote: BTreeMap<String, RemoteState>,
+	remote_ignored: &'a BTreeSet<String>,
+	/// Moves and conflicted copies, which run before everything else.
+	renames: Vec<SyncOp>,
+	ops: Vec<SyncOp>,
+	adopt: Vec<(String, BaseEntry)>,
+	forget: Vec<String>,
+	/// Directories planned for deletion; nothing below them needs planning.
+	deleted_remote_dirs: Vec<String>,
+	deleted_local_dirs: Vec<String>,
+	/// Remote directories kept despite a local delete, in
+	/// [`SyncMode::BackupOnly`]; nothing below them needs planning either.
+	kept_remote_dirs: Vec<String>,
+	conflicts: Vec<Conflict>,
+	/// Directories with a reported conflict. Conflicts below them follow
+	/// from the same resolution and aren't reported again.
+	conflict_dirs: Vec<String>,
+}
+
+impl Planner<'_> {
+	fn plan_moves(&mut self) {
+		if self.options.mode == SyncMode::BackupOnly {
+			// a replayed move would take the old path away from the backup
+			return;
+		}
+		let local_moves = detect_moves(
+			self.base.entries(),
+			&self.local,
+			|b| local_key(&b.local),
+			local_key,
+		);
+		let remote_moves = detect_moves(
+			self.base.entries(),
+			&self.remote,
+			|b| remote_key(&b.remote),
+			remote_key,
+		);
+
+		let sources = local_moves
+			.keys()
+			.chain(remote_moves.keys())
+			.cloned()
+			.collect::<BTreeSet<_>>();
+
+		// Moves already applied to each virtual tree, in order
+		let mut base_shifts = Vec::<(String, String)>::new();
+		let mut local_shifts = Vec::<(String, String)>::new();
+		let mut remote_shifts = Vec::<(String, String)>::new();
+
+		for source in sources {
+			let pos = translate(&source, &base_shifts);
+			let Some(base_entry) = self.base.get(&pos).copied() else {
+				continue;
+			};
+			let local_to = local_moves
+				.get(&source)
+				.map(|to| translate(to, &local_shifts));
+			let remote_to = remote_moves
+				.get(&source)
+				.map(|to| translate(to, &remote_shifts));
+
+			if let Some(to) = &local_to
+				&& local_to == remote_to
+			{
+				self.base.rebase_subtree(&pos, to);
+				base_shifts.push((pos, to.clone()));
+				continue;
+			}
+			// the side whose move wins; the other side is moved to match it,
+			// which in the one-way modes may mean moving it back
+			let local_wins = match self.options.mode {
+				SyncMode::TwoWay => local_to.is_some(),
+				SyncMode::MirrorUp | SyncMode::BackupOnly => true,
+				SyncMode::MirrorDown => false,
+			};
+			let local_at = local_to.unwrap_or_else(|| pos.clone());
+			let remote_at = remote_to.unwrap_or_else(|| pos.clone());
+
+			let to = if local_wins {
+				let remote_in_place = self
+					.remote
+					.get(&remote_at)
+					.is_some_and(|r| r.id == base_entry.remote.id);
+				if !remote_in_place || self.remote.contains_key(&local_at) {
+					// falls back to delete + create in the path pass
+					continue;
+				}
+				self.renames.push(SyncOp::MoveRemote {
+					from: remote_at.clone(),
+					to: local_at.clone(),
+				});
+				rebase_subtree(&mut self.remote, &remote_at, &local_at);
+				remote_shifts.push((remote_at, local_at.clone()));
+				local_at
+			} else {
+				let local_in_place = self.local.get(&local_at).is_some_and(|l| {
+					l.kind == base_entry.local.kind && l.inode == base_entry.local.inode
+				});
+				if !local_in_place || self.local.contains_key(&remote_at) {
+					continue;
+				}
+				self.renames.push(SyncOp::MoveLocal {
+					from: local_at.clone(),
+					to: remote_at.clone(),
+				});
+				rebase_subtree(&mut self.local, &local_at, &remote_at);
+				local_shifts.push((local_at, remote_at.clone()));
+				remote_at
+			};
+			if to != pos {
+				self.base.rebase_subtree(&pos, &to);
+				base_shifts.push((pos, to));
+			}
+		}
+	}
+
+	fn plan_paths(&mut self, local_hash: &mut dyn FnMut(&str) -> Option<Blake3Hash>) {
+		let paths = self
+			.base
+			.entries()
+			.keys()
+			.chain(self.local.keys())
+			.chain(self.remote.keys())
+			.cloned()
+			.collect::<BTreeSet<_>>();
+
+		for path in paths {
+			if self
+				.deleted_remote_dirs
+				.iter()
+				.chain(&self.deleted_local_dirs)
+				.chain(&self.kept_remote_dirs)
+				.any(|dir| path.len() > dir.len() && is_same_or_descendant(&path, dir))
+			{
+				continue;
+			}
+			let base = self.base.get(&path);
+			let lc = local_change(base, self.local.get(&path));
+			let rc = remote_change(base, self.remote.get(&path));
+
+			match (lc, rc) {
+				(Change::Absent | Change::Unchanged, Change::Absent | Change::Unchanged) => {}
+				(Change::Deleted, Change::Deleted) => self.forget.push(path),
+				_ if self.options.mode != SyncMode::TwoWay => self.mirror(path, lc, rc, local_hash),
+				(Change::Deleted, Change::Unchanged) => self.delete_remote(path),
+				(Change::Unchanged, Change::Deleted) => self.delete_local(path),
+				(Change::Deleted, _) => self.conflict(
+					path,
+					ConflictKind::EditedAndDeleted {
+						deleted: Side::Local,
+					},
+				),
+				(_, Change::Deleted) => self.conflict(
+					path,
+					ConflictKind::EditedAndDeleted {
+						deleted: Side::Remote,
+					},
+				),
+				(lc, Change::Absent | Change::Unchanged) if lc.is_edit() => self.push_up(path),
+				(Change::Absent | Change::Unchanged, rc) if rc.is_edit() => self.push_down(path),
+				(_, _) => self.both_edited(path, local_hash),
+			}
+		}
+	}
+
+	fn remote_edited_below(&self, path: &str) -> bool {
+		descendants(&self.remote, path)
+			.any(|(p, r)| remote_change(self.base.get(p), Some(r)).is_edit())
+	}
+
+	fn local_edited_below(&self, path: &str) -> bool {
+		descendants(&self.local, path)
+			.any(|(p, l)| local_change(self.base.get(p), Some(l)).is_edit())
+	}
+
+	fn both_edited(
+		&mut self,
+		path: String,
+		local_hash: &mut dyn FnMut(&str) -> Option<Blake3Hash>,
+	) {
+		// SAFETY: both sides were edited, so both exist
+		let local = self.local[&path];
+		let remote = self.remote[&path];
+		if local.kind == remote.kind
+			&& (local.kind == EntryKind::Dir || same_content(&path, &local, &remote, local_hash))
+		{
+			self.adopt.push((path, BaseEntry::new(local, remote)));
+		} else if local.kind == remote.kind {
+			self.conflict(path, ConflictKind::BothEdited);
+		} else {
+			self.conflict(path, ConflictKind::TypeChanged);
+		}
+	}
+
+	/// Plans `path` in one of the one-way modes, where the source side wins
+	/// whichever side changed.
+	fn mirror(
+		&mut self,
+		path: String,
+		lc: Change,
+		rc: Change,
+		local_hash: &mut dyn FnMut(&str) -> Option<Blake3Hash>,
+	) {
+		let local = self.local.get(&path).copied();
+		let remote = self.remote.get(&path).copied();
+		// both sides are there and the target changed to match the source anyway
+		let converged = |target_changed: bool,
+		                 local_hash: &mut dyn FnMut(&str) -> Option<Blake3Hash>| {
+			let (Some(local), Some(remote)) = (local, remote) else {
+				return false;
+			};
+			target_changed
+				&& local.kind == EntryKind::File
+				&& remote.kind == EntryKind::File
+				&& same_content(&path, &local, &remote, local_hash)
+		};
+
+		match self.options.mode {
+			SyncMode::TwoWay => unreachable!("two-way paths aren't mirrored"),
+			SyncMode::MirrorUp | SyncMode::BackupOnly => {
+				if converged(rc.is_edit(), local_hash) {
+					// SAFETY: both exist if converged
+					self.adopt
+						.push((path, BaseEntry::new(local.unwrap(), remote.unwrap())));
+				} else if local.is_some() {
+					self.overwrite_remote(path);
+				} else if self.options.mode == SyncMode::MirrorUp {
+					self.remove_remote(path);
+				} else if lc == Change::Deleted {
+					// the remote entry stays, but is no longer in sync with anything
+					if remote.is_some_and(|r| r.kind == EntryKind::Dir) {
+						self.kept_remote_dirs.push(path.clone());
+					}
+					self.forget.push(path);
+				}
+			}
+			SyncMode::MirrorDown => {
+				if converged(lc.is_edit(), local_hash) {
+					self.adopt
+						.push((path, BaseEntry::new(local.unwrap(), remote.unwrap())));
+				} else if remote.is_some() {
+					self.overwrite_local(path);
+				} else {
+					self.remove_local(path);
+				}
+			}
+		}
+	}
+
+	/// The local side changed `path`; remote is unchanged there.
+	fn push_up(&mut self, path: String) {
+		let local = self.local[&path];
+		let replaces_edited_dir = self
+			.remote
+			.get(&path)
+			.is_some_and(|r| r.kind == EntryKind::Dir && local.kind != EntryKind::Dir)
+			&& self.remote_edited_below(&path);
+		if replaces_edited_dir {
+			self.conflict(path, ConflictKind::TypeChanged);
+		} else {
+			self.overwrite_remote(path);
+		}
+	}
+
+	/// The remote side changed `path`; local is unchanged there.
+	fn push_down(&mut self, path: String) {
+		let remote = self.remote[&path];
+		let replaces_edited_dir = self
+			.local
+			.get(&path)
+			.is_some_and(|l| l.kind == EntryKind::Dir && remote.kind != EntryKind::Dir)
+			&& self.local_edited_below(&path);
+		if replaces_edited_dir {
+			self.conflict(path, ConflictKind::TypeChanged);
+		} else {
+			self.overwrite_local(path);
+		}
+	}
+
+	/// The local side deleted `path`; remote is unchanged there.
+	fn delete_remote(&mut self, path: String) {
+		if self.remote[&path].kind == EntryKind::Dir && self.remote_edited_below(&path) {
+			self.conflict(
+				path,
+				ConflictKind::EditedAndDeleted {
+					deleted: Side::Local,
+				},
+			);
+		} else {
+			self.remove_remote(path);
+		}
+	}
+
+	/// The remote side deleted `path`; local is unchanged there.
+	fn delete_local(&mut self, path: String) {
+		if self.local[&path].kind == EntryKind::Dir && self.local_edited_below(&path) {
+			self.conflict(
+				path,
+				ConflictKind::EditedAndDeleted {
+					deleted: Side::Remote,
+				},
+			);
+		} else {
+			self.remove_local(path);
+		}
+	}
+
+	// ── Conflicts ──
+
+	fn conflict(&mut self, path: String, kind: ConflictKind) {
+		let winner = match (kind, self.options.conflict_policy) {
+			(_, ConflictPolicy::PreferLocal) => Side::Local,
+			(_, ConflictPolicy::PreferRemote) => Side::Remote,
+			// there's nothing to keep (or date) of a delete, so the edit wins
+			(
+				ConflictKind::EditedAndDeleted {
+					deleted: Side::Local,
+				},
+				_,
+			) => Side::Remote,
+			(
+				ConflictKind::EditedAndDeleted {
+					deleted: Side::Remote,
+				},
+				_,
+			) => Side::Local,
+			(_, ConflictPolicy::NewestWins) => {
+				let local = self.local[&path];
+				let remote = self.remote[&path];
+				if local.mtime >= remote.mtime.unwrap_or(i64::MIN) {
+					Side::Local
+				} else {
+					Side::Remote
+				}
+			}
+			(_, ConflictPolicy::KeepBoth) => return self.keep_both(path, kind),
+		};
+
+		match winner {
+			Side::Local => {
+				self.report(&path, kind, Resolution::KeptLocal);
+				if self.local.contains_key(&path) {
+					self.overwrite_remote(path);
+				} else {
+					self.remove_remote(path);
+				}
+			}
+			Side::Remote => {
+				self.report(&path, kind, Resolution::KeptRemote);
+				if self.remote.contains_key(&path) {
+					self.overwrite_local(path);
+				} else {
+					self.remove_local(path);
+				}
+			}
+		}
+	}
+
+	/// Moves the local entry at `path` to a conflicted copy and uploads it
+	/// from there, so the remote entry can be downloaded in its place.
+	fn keep_both(&mut self, path: String, kind: ConflictKind) {
+		// SAFETY: only edited-on-both-sides conflicts get here, so both exist
+		let local = self.local[&path];
+		let parent = parent_path(&path).unwrap_or("");
+		let copy = (1..)
+			.map(|attempt| {
+				conflicted_copy_name(
+					file_name(&path),
+					local.kind == EntryKind::Dir,
+					&self.options.device_name,
+					self.options.now,
+					attempt,
+				)
+				.map(|name| join_path(parent, &name))
+			})
+			.find(|copy| copy.as_ref().map_or(true, |copy| !self.is_taken(copy)))
+			.expect("attempts are unbounded");
+		let copy = match copy {
+			Ok(copy) => copy,
+			Err(e) => {
+				self.report(
+					&path,
+					kind,
+					Resolution::Unresolved {
+						reason: e.to_string(),
+					},
+				);
+				return;
+			}
+		};
+
+		self.report(&path, kind, Resolution::KeptBoth { copy: copy.clone() });
+		self.renames.push(SyncOp::ConflictCopy {
+			path: path.clone(),
+			copy: copy.clone(),
+		});
+		rebase_subtree(&mut self.local, &path, &copy);
+		// the base described the entry that's now the copy
+		self.base.remove_subtree(&path);
+		self.ops.push(match local.kind {
+			EntryKind::File => SyncOp::Upload { path: copy },
+			EntryKind::Dir => SyncOp::UploadDir { path: copy },
+		});
+		self.overwrite_local(path);
+	}
+
+	fn is_taken(&self, path: &str) -> bool {
+		self.local.contains_key(path)
+			|| self.remote.contains_key(path)
+			|| self.remote_ignored.contains(path)
+			|| self.base.get(path).is_some()
+	}
+
+	fn report(&mut self, path: &str, kind: ConflictKind, resolution: Resolution) {
+		let is_dir = [
+			self.local.get(path).map(|l| l.kind),
+			self.remote.get(path).map(|r| r.kind),
+		]
+		.contains(&Some(EntryKind::Dir));
+		if self
+			.conflict_dirs
+			.iter()
+			.any(|dir| is_same_or_descendant(path, dir))
+		{
+			return;
+		}
+		if is_dir {
+			self.conflict_dirs.push(path.to_owned());
+		}
+		self.conflicts.push(Conflict {
+			path: path.to_owned(),
+			kind,
+			resolution,
+		});
+	}
+
+	// ── Resolved changes ──
+
+	/// Makes the remote side at `path` match the local one.
+	fn overwrite_remote(&mut self, path: String) {
+		let local = self.local[&path];
+		let remote = self.remote.get(&path).copied();
+		if let Some(remote) = remote
+			&& remote.kind != local.kind
+		{
+			self.remove_remote(path.clone());
+			// whatever the base had at and below `path` is gone now
+			self.base.remove_subtree(&path);
+		}
+		match local.kind {
+			EntryKind::File => self.ops.push(SyncOp::Upload { path }),
+			EntryKind::Dir if remote.is_some_and(|r| r.kind == EntryKind::Dir) => self
+				.adopt
+				.push((path, BaseEntry::new(local, remote.unwrap()))),
+			EntryKind::Dir => self.ops.push(SyncOp::CreateRemoteDir { path }),
+		}
+	}
+
+	/// Makes the local side at `path` match the remote one.
+	fn overwrite_local(&mut self, path: String) {
+		let remote = self.remote[&path];
+		let local = self.local.get(&path).copied();
+		if let Some(local) = local
+			&& local.kind != remote.kind
+		{
+			self.remove_local(path.clone());
+			self.base.remove_subtree(&path);
+		}
+		match remote.kind {
+			EntryKind::File => self.ops.push(SyncOp::Download { path }),
+			EntryKind::Dir if local.is_some_and(|l| l.kind == EntryKind::Dir) => self
+				.adopt
+				.push((path, BaseEntry::new(local.unwrap(), remote))),
+			EntryKind::Dir => self.ops.push(SyncOp::CreateLocalDir { path }),
+		}
+	}
+
+	fn remove_remote(&mut self, path: String) {
+		if self.remote[&path].kind == EntryKind::Dir {
+			self.deleted_remote_dirs.push(path.clone());
+		}
+		self.ops.push(SyncOp::DeleteRemote { path });
+	}
+
+	fn remove_local(&mut self, path: String) {
+		if self.local[&path].kind == EntryKind::Dir {
+			self.deleted_local_dirs.push(path.clone());
+		}
+		self.ops.push(SyncOp::DeleteLocal { path });
+	}
+
+	fn finish(mut self) -> SyncPlan {
+		// uploads of conflicted copies are planned out of path order; the sort
+		// is stable, so ops on the same path keep their relative order
+		self.ops.sort_by(|a, b| a.path().cmp(b.path()));
+		let ops = collapse_dir_transfers(self.ops, &self.local, &self.remote, self.remote_ignored);
+		let mut all_ops = self.renames;
+		all_ops.extend(ops);
+		SyncPlan {
+			ops: all_ops,
+			base: self.base,
+			adopt: self.adopt,
+			forget: self.forget,
+			conflicts: self.conflicts,
+		}
+	}
+}
+
+/// Whether two files hold the same content, going by their remote hash.
+fn same_content(
+	path: &str,
+	local: &LocalState,
+	remote: &RemoteState,
+	local_hash: &mut dyn FnMut(&str) -> Option<Blake3Hash>,
+) -> bool {
+	local.size == remote.size && remote.hash.is_some() && local_hash(path) == remote.hash
+}
+
+/// Folds directories that only exist on one side, together with everything
+/// below them, into a single whole-directory transfer.
+///
+/// `ops` must be ordered by path, which keeps every directory's descendants
+/// in one contiguous run.
+fn collapse_dir_transfers(
+	ops: Vec<SyncOp>,
+	local: &BTreeMap<String, LocalState>,
+	remote: &BTreeMap<String, RemoteState>,
+	remote_ignored: &BTreeSet<String>,
+) -> Vec<SyncOp> {
+	let mut skip = vec![false; ops.len()];
+	let mut collapsed = Vec::with_capacity(ops.len());
+	for (i, op) in ops.iter().enumerate() {
+		if skip[i] {
+			continue;
+		}
+		let replacement = match op {
+			SyncOp::CreateRemoteDir { path }
+				if !remote.contains_key(path) && descendants(remote, path).next().is_none() =>
+			{
+				let run = descendant_run(&ops, i, path);
+				run.clone()
+					.all(|j| {
+						matches!(
+							ops[j],
+							SyncOp::Upload { .. } | SyncOp::CreateRemoteDir { .. }
+						)
+					})
+					.then(|| {
+						run.for_each(|j| skip[j] = true);
+						SyncOp::UploadDir { path: path.clone() }
+					})
+			}
+			SyncOp::CreateLocalDir { path }
+				if !local.contains_key(path)
+					&& descendants(local, path).next().is_none()
+					&& !remote_ignored
+						.iter()
+						.any(|ignored| is_same_or_descendant(ignored, path)) =>
+			{
+				let run = descendant_run(&ops, i, path);
+				run.clone()
+					.all(|j| {
+						matches!(
+							ops[j],
+							SyncOp::Download { .. } | SyncOp::CreateLocalDir { .. }
+						)
+					})
+					.then(|| {
+						run.for_each(|j| skip[j] = true);
+						SyncOp::DownloadDir { path: path.clone() }
+					})
+			}
+			_ => None,
+		};
+		collapsed.push(replacement.unwrap_or_else(|| op.clone()));
+	}
+	collapsed
+}
+
+/// Indices of the ops after `start` that target paths strictly below `dir`.
+fn descendant_run(ops: &[SyncOp], start: usize, dir: &str) -> std::ops::Range<usize> {
+	let prefix = format!("{dir}/");
+	let begin = start + 1 + ops[start + 1..].partition_point(|op| op.path() < prefix.as_str());
+	let end = begin + ops[begin..].partition_point(|op| op.path().starts_with(&prefix));
+	begin..end
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::TimeZone;
+	use uuid::Uuid;
+
+	use super::*;
+
+	// ── Fixtures ──
+
+	fn local_file(inode: u64, size: u64, mtime: i64) -> LocalState {
+		LocalState {
+			kind: EntryKind::File,
+			size,
+			mtime,
+			inode: Some(inode),
+		}
+	}
+
+	fn local_dir(inode: u64) -> LocalState {
+		LocalState {
+			kind: EntryKind::Dir,
+			size: 0,
+			mtime: 0,
+			inode: Some(inode),
+		}
+	}
+
+	fn remote_file(id: u128, version: u128, mtime: i64) -> RemoteState {
+		RemoteState {
+			kind: EntryKind::File,
+			id: Uuid::from_u128(id),
+			version: Uuid::from_u128(version),
+			size: 1,
+			mtime: Some(mtime),
+			hash: None,
+		}
+	}
+
+	fn remote_dir(id: u128) -> RemoteState {
+		RemoteState {
+			kind: EntryKind::Dir,
+			id: Uuid::from_u128(id),
+			version: Uuid::from_u128(id),
+			size: 0,
+			mtime: None,
+			hash: None,
+		}
+	}
+
+	#[derive(Default)]
+	struct Fixture {
+		base: BaseSnapshot,
+		local: LocalTree,
+		remote: RemoteTree,
+		mode: SyncMode,
+		policy: ConflictPolicy,
+	}
+
+	impl Fixture {
+		/// Adds an in-sync file to all three trees.
+		fn synced_file(mut self, path: &str, n: u64) -> Self {
+			let local = local_file(n, 1, 100);
+			let remote = remote_file(n as u128, n as u128, 100);
+			self.base.insert(path.into(), BaseEntry::new(local, remote));
+			self.local.entries.insert(path.into(), local);
+			self.remote.entries.insert(path.into(), remote);
+			self
+		}
+
+		/// Adds an in-sync directory to all three trees.
+		fn synced_dir(mut self, path: &str, n: u64) -> Self {
+			let local = local_dir(n);
+			let remote = remote_dir(n as u128);
+			self.base.insert(path.into(), BaseEntry::new(local, remote));
+			self.local.entries.insert(path.into(), local);
+			self.remote.entries.insert(path.into(), remote);
+			self
+		}
+
+		fn options(&self) -> PlanOptions {
+			PlanOptions {
+				mode: self.mode,
+				conflict_policy: self.policy,
+				device_name: "test".into(),
+				now: Utc.with_ymd_and_hms(2026, 3, 14, 12, 0, 0).unwrap(),
+			}
+		}
+
+		fn plan(&self) -> SyncPlan {
+			plan(
+				&self.base,
+				&self.local,
+				&self.remote,
+				&self.options(),
+				&mut |_| None,
+			)
+		}
+	}
+
+	fn ops(plan: &SyncPlan) -> Vec<SyncOp> {
+		plan.ops.clone()
+	}
+
+	fn upload(path: &str) -> SyncOp {
+		SyncOp::Upload { path: path.into() }
+	}
+
+	fn download(path: &str) -> SyncOp {
+		SyncOp::Download { path: path.into() }
+	}
+
+	// ── One-sided change tests ──
+
+	#[test]
+	fn in_sync_plans_nothing() {
+		let f = Fixture::default().synced_dir("a", 1).synced_file("a/x", 2);
+		assert!(f.plan().is_empty());
+	}
+
+	#[test]
+	fn local_new_file_uploads() {
+		let mut f = Fixture::default().synced_dir("a", 1);
+		f.local
+			.entries
+			.insert("a/new".into(), local_file(9, 3, 100));
+		assert_eq!(ops(&f.plan()), [upload("a/new")]);
+	}
+
+	#[test]
+	fn remote_new_file_downloads() {
+		let mut f = Fixture::default();
+		f.remote
+			.entries
+			.insert("new".into(), remote_file(9, 9, 100));
+		assert_eq!(ops(&f.plan()), [download("new")]);
+	}
+
+	#[test]
+	fn local_edit_uploads() {
+		let mut f = Fixture::default().synced_file("x", 1);
+		f.local.entries.insert("x".into(), local_file(1, 5, 200));
+		assert_eq!(ops(&f.plan()), [upload("x")]);
+	}
+
+	#[test]
+	fn remote_edit_downloads() {
+		let mut f = Fixture::default().synced_file("x", 1);
+		f.remote.entries.insert("x".into(), remote_file(1, 2, 200));
+		assert_eq!(ops(&f.plan()), [download("x")]);
+	}
+
+	#[test]
+	fn local_delete_trashes_remote() {
+		let mut f = Fixture::default().synced_file("x", 1);
+		f.local.entries.remove("x");
+		assert_eq!(ops(&f.plan()), [SyncOp::DeleteRemote { path: "x".into() }]);
+	}
+
+	#[test]
+	fn remote_delete_removes_local() {
+		let mut f = Fixture::default().synced_file("x", 1);
+		f.remote.entries.remove("x");
+		assert_eq!(ops(&f.plan()), [SyncOp::DeleteLocal { path: "x".into() }]);
+	}
+
+	#[test]
+	fn deleted_on_both_sides_is_forgotten() {
+		let mut f = Fixture::default().synced_file("x", 1);
+		f.local.entries.remove("x");
+		f.remote.entries.remove("x");
+		let plan = f.plan();
+		assert!(plan.ops.is_empty());
+		assert_eq!(plan.forget, ["x"]);
+	}
+
+	#[test]
+	fn deleted_dir_is_trashed_once() {
+		let mut f = Fixture::default()
+			.synced_dir("a", 1)
+			.synced_file("a/x", 2)
+			.synced_file("a/y", 3);
+		f.local.entries.clear();
+		assert_eq!(ops(&f.plan()), [SyncOp::DeleteRemote { path: "a".into() }]);
+	}
+
+	// ── Move tests ──
+
+	#[test]
+	fn local_rename_is_replayed_remotely() {
+		let mut f = Fixture::default().synced_file("x", 1);
+		let state = f.local.entries.remove("x").unwrap();
+		f.local.entries.insert("y".into(), state);
+		let plan = f.plan();
+		assert_eq!(
+			ops(&plan),
+			[SyncOp::MoveRemote {
+				from: "x".into(),
+				to: "y".into()
+			}]
+		);
+		assert!(plan.base.get("y").is_some());
+		assert!(plan.base.get("x").is_none());
+	}
+
+	#[test]
+	fn remote_dir_move_carries_children() {
+		let mut f = Fixture::default()
+			.synced_dir("a", 1)
+			.synced_file("a/x", 2)
+			.synced_dir("b", 3);
+		rebase_subtree(&mut f.remote.entries, "a", "b/a");
+		assert_eq!(
+			ops(&f.plan()),
+			[SyncOp::MoveLocal {
+				from: "a".into(),
+				to: "b/a".into()
+			}]
+		);
+	}
+
+	#[test]
+	fn child_moved_out_of_moved_dir_uses_new_source() {
+		let mut f = Fixture::default().synced_dir("a", 1).synced_file("a/x", 2);
+		// remote: a -> b; local: a/x -> x
+		rebase_subtree(&mut f.remote.entries, "a", "b");
+		let x = f.local.entries.remove("a/x").unwrap();
+		f.local.entries.insert("x".into(), x);
+		assert_eq!(
+			ops(&f.plan()),
+			[
+				SyncOp::MoveLocal {
+					from: "a".into(),
+					to: "b".into()
+				},
+				SyncOp::MoveRemote {
+					from: "b/x".into(),
+					to: "x".into()
+				},
+			]
+		);
+	}
+
+	#[test]
+	fn same_move_on_both_sides_needs_no_op() {
+		let mut f = Fixture::default().synced_file("x", 1);
+		rebase_subtree(&mut f.local.entries, "x", "y");
+		rebase_subtree(&mut f.remote.entries, "x", "y");
+		let plan = f.plan();
+		assert!(plan.ops.is_empty());
+		assert!(plan.base.get("y").is_some());
+	}
+
+	#[test]
+	fn moved_and_edited_moves_then_uploads() {
+		let mut f = Fixture::default().synced_dir("a", 1).synced_file("a/x", 2);
+		rebase_subtree(&mut f.local.entries, "a", "b");
+		f.local.entries.insert("b/x".into(), local_file(2, 7, 300));
+		assert_eq!(
+			ops(&f.plan()),
+			[
+				SyncOp::MoveRemote {
+					from: "a".into(),
+					to: "b".into()
+				},
+				upload("b/x"),
+			]
+		);
+	}
+
+	#[test]
+	fn move_of_remotely_deleted_entry_reuploads() {
+		let mut f = Fixture::default().synced_file("x", 1);
+		rebase_subtree(&mut f.local.entries, "x", "y");
+		f.remote.entries.remove("x");
+		let plan = f.plan();
+		assert_eq!(ops(&plan), [upload("y")]);
+		assert_eq!(plan.forget, ["x"]);
+	}
+
+	// ── Both-sides tests ──
+
+	#[test]
+	fn type_change_replaces_other_side() {
+		let mut f = Fixture::default().synced_file("x", 1);
+		f.local.entries.insert("x".into(), local_dir(5));
+		assert_eq!(
+			ops(&f.plan()),
+			[
+				SyncOp::DeleteRemote { path: "x".into() },
+				SyncOp::CreateRemoteDir { path: "x".into() },
+			]
+		);
+	}
+
+	#[test]
+	fn identical_content_is_adopted() {
+		let mut f = Fixture::default();
+		let hash = Blake3Hash::from([7u8; 32]);
+		f.local.entries.insert("x".into(), local_file(1, 1, 100));
+		f.remote.entries.insert(
+			"x".into(),
+			RemoteState {
+				hash: Some(hash),
+				..remote_file(1, 1, 50)
+			},
+		);
+		let plan = plan(&f.base, &f.local, &f.remote, &f.options(), &mut |_| {
+			Some(hash)
+		});
+		assert!(plan.ops.is_empty());
+		assert_eq!(plan.adopt.len(), 1);
+	}
+
+	// ── Conflict tests ──
+
+	const COPY: &str = "x (conflicted copy test 2026-03-14).txt";
+
+	/// `x.txt` edited on both sides; local is newer.
+	fn both_edited() -> Fixture {
+		let mut f = Fixture::default().synced_file("x.txt", 1);
+		f.local
+			.entries
+			.insert("x.txt".into(), local_file(1, 2, 300));
+		f.remote
+			.entries
+			.insert("x.txt".into(), remote_file(1, 2, 200));
+		f
+	}
+
+	fn resolutions(plan: &SyncPlan) -> Vec<(&str, ConflictKind, Resolution)> {
+		plan.conflicts
+			.iter()
+			.map(|c| (c.path.as_str(), c.kind, c.resolution.clone()))
+			.collect()
+	}
+
+	#[test]
+	fn both_edited_keeps_both() {
+		let plan = both_edited().plan();
+		assert_eq!(
+			ops(&plan),
+			[
+				SyncOp::ConflictCopy {
+					path: "x.txt".into(),
+					copy: COPY.into()
+				},
+				upload(COPY),
+				download("x.txt"),
+			]
+		);
+		assert_eq!(
+			resolutions(&plan),
+			[(
+				"x.txt",
+				ConflictKind::BothEdited,
+				Resolution::KeptBoth { copy: COPY.into() }
+			)]
+		);
+		assert!(plan.base.get("x.txt").is_none());
+	}
+
+	#[test]
+	fn both_edited_follows_policy() {
+		let mut f = both_edited();
+		for (policy, op, resolution) in [
+			(
+				ConflictPolicy::PreferLocal,
+				upload("x.txt"),
+				Resolution::KeptLocal,
+			),
+			(
+				ConflictPolicy::PreferRemote,
+				download("x.txt"),
+				Resolution::KeptRemote,
+			),
+			(
+				ConflictPolicy::NewestWins,
+				upload("x.txt"),
+				Resolution::KeptLocal,
+			),
+		] {
+			f.policy = policy;
+			let plan = f.plan();
+			assert_eq!(ops(&plan), [op], "{policy:?}");
+			assert_eq!(
+				resolutions(&plan),
+				[("x.txt", ConflictKind::BothEdited, resolution)]
+			);
+		}
+
+		f.remote
+			.entries
+			.insert("x.txt".into(), remote_file(1, 2, 400));
+		assert_eq!(ops(&f.plan()), [download("x.txt")]);
+	}
+
+	#[test]
+	fn conflicted_copy_name_is_unique() {
+		let mut f = both_edited();
+		f.remote.entries.insert(COPY.into(), remote_file(9, 9, 100));
+		let plan = f.plan();
+		assert_eq!(
+			plan.conflicts[0].resolution,
+			Resolution::KeptBoth {
+				copy: "x (conflicted copy test 2026-03-14 2).txt".into()
+			}
+		);
+	}
+
+	#[test]
+	fn invalid_copy_name_leaves_conflict_unresolved() {
+		let f = both_edited();
+		let options = PlanOptions {
+			device_name: "my:pc".into(),
+			..f.options()
+		};
+		let plan = plan(&f.base, &f.local, &f.remote, &options, &mut |_| None);
+		assert!(plan.ops.is_empty());
+		assert!(matches!(
+			plan.conflicts[0].resolution,
+			Resolution::Unresolved { .. }
+		));
+	}
+
+	#[test]
+	fn edit_beats_delete() {
+		let mut f = Fixture::default().synced_file("x", 1);
+		f.local.entries.remove("x");
+		f.remote.entries.insert("x".into(), remote_file(1, 2, 200));
+		for policy in [ConflictPolicy::KeepBoth, ConflictPolicy::NewestWins] {
+			f.policy = policy;
+			let plan = f.plan();
+			assert_eq!(ops(&plan), [download("x")]);
+			assert_eq!(
+				resolutions(&plan),
+				[(
+					"x",
+					ConflictKind::EditedAndDeleted {
+						deleted: Side::Local
+					},
+					Resolution::KeptRemote
+				)]
+			);
+		}
+	}
+
+	#[test]
+	fn preferred_delete_beats_edit() {
+		let mut f = Fixture::default().synced_file("x", 1);
+		f.local.entries.remove("x");
+		f.remote.entries.insert("x".into(), remote_file(1, 2, 200));
+		f.policy = ConflictPolicy::PreferLocal;
+		assert_eq!(ops(&f.plan()), [SyncOp::DeleteRemote { path: "x".into() }]);
+	}
+
+	#[test]
+	fn dir_delete_yields_to_edit_below() {
+		let mut f = Fixture::default()
+			.synced_dir("a", 1)
+			.synced_file("a/x", 2)
+			.synced_file("a/y", 3);
+		f.local.entries.clear();
+		f.remote
+			.entries
+			.insert("a/x".into(), remote_file(2, 20, 200));
+		let plan = f.plan();
+		assert_eq!(
+			ops(&plan),
+			[
+				SyncOp::CreateLocalDir { path: "a".into() },
+				download("a/x"),
+				SyncOp::DeleteRemote { path: "a/y".into() },
+			]
+		);
+		// reported once, for the directory
+		assert_eq!(
+			resolutions(&plan),
+			[(
+				"a",
+				ConflictKind::EditedAndDeleted {
+					deleted: Side::Local
+				},
+				Resolution::KeptRemote
+			)]
+		);
+	}
+
+	#[test]
+	fn preferred_dir_delete_discards_edit_below() {
+		let mut f = Fixture::default()
+			.synced_dir("a", 1)
+			.synced_file("a/x", 2)
+			.synced_file("a/y", 3);
+		f.remote.entries.clear();
+		f.local.entries.insert("a/x".into(), local_file(2, 5, 200));
+		f.policy = ConflictPolicy::PreferRemote;
+		let plan = f.plan();
+		assert_eq!(ops(&plan), [SyncOp::DeleteLocal { path: "a".into() }]);
+		assert_eq!(plan.conflicts.len(), 1);
+	}
+
+	#[test]
+	fn file_replacing_edited_dir_keeps_both() {
+		let mut f = Fixture::default().synced_dir("a", 1).synced_file("a/x", 2);
+		f.local.entries.clear();
+		f.local.entries.insert("a".into(), local_file(7, 1, 300));
+		f.remote
+			.entries
+			.insert("a/x".into(), remote_file(2, 20, 200));
+		let copy = "a (conflicted copy test 2026-03-14)";
+		let plan = f.plan();
+		assert_eq!(
+			ops(&plan),
+			[
+				SyncOp::ConflictCopy {
+					path: "a".into(),
+					copy: copy.into()
+				},
+				SyncOp::DownloadDir { path: "a".into() },
+				upload(copy),
+			]
+		);
+		assert_eq!(
+			resolutions(&plan),
+			[(
+				"a",
+				ConflictKind::TypeChanged,
+				Resolution::KeptBoth { copy: copy.into() }
+			)]
+		);
+	}
+
+	#[test]
+	fn dir_replacing_file_keeps_local_children() {
+		let mut f = Fixture::default().synced_dir("a", 1).synced_file("a/x", 2);
+		f.local.entries.insert("a/x".into(), local_file(2, 5, 200));
+		f.remote.entries.remove("a/x");
+		f.remote.entries.insert("a".into(), remote_file(1, 1, 200));
+		f.policy = ConflictPolicy::PreferLocal;
+		let plan = f.plan();
+		assert_eq!(
+			ops(&plan),
+			[
+				SyncOp::DeleteRemote { path: "a".into() },
+				SyncOp::CreateRemoteDir { path: "a".into() },
+				upload("a/x"),
+			]
+		);
+		assert_eq!(
+			resolutions(&plan),
+			[("a", ConflictKind::TypeChanged, Resolution::KeptLocal)]
+		);
+	}
+
+	// ── Sync mode tests ──
+
+	/// One of every kind of change on each side.
+	fn changed_on_both_sides(mode: SyncMode) -> Fixture {
+		let mut f = Fixture {
+			mode,
+			..Fixture::default()
+		}
+		.synced_file("a", 1)
+		.synced_file("c", 3)
+		.synced_file("d", 4)
+		.synced_file("f", 6)
+		.synced_file("m", 7)
+		.synced_file("r", 8);
+		// local: edit a, create b, delete c, move m -> m2
+		f.local.entries.insert("a".into(), local_file(1, 5, 200));
+		f.local.entries.insert("b".into(), local_file(2, 1, 100));
+		f.local.entries.remove("c");
+		rebase_subtree(&mut f.local.entries, "m", "m2");
+		// remote: edit d, create e, delete f, move r -> r2
+		f.remote.entries.insert("d".into(), remote_file(4, 40, 200));
+		f.remote.entries.insert("e".into(), remote_file(5, 5, 100));
+		f.remote.entries.remove("f");
+		rebase_subtree(&mut f.remote.entries, "r", "r2");
+		f
+	}
+
+	fn delete_remote(path: &str) -> SyncOp {
+		SyncOp::DeleteRemote { path: path.into() }
+	}
+
+	fn delete_local(path: &str) -> SyncOp {
+		SyncOp::DeleteLocal { path: path.into() }
+	}
+
+	fn move_remote(from: &str, to: &str) -> SyncOp {
+		SyncOp::MoveRemote {
+			from: from.into(),
+			to: to.into(),
+		}
+	}
+
+	fn move_local(from: &str, to: &str) -> SyncOp {
+		SyncOp::MoveLocal {
+			from: from.into(),
+			to: to.into(),
+		}
+	}
+
+	#[test]
+	fn two_way_propagates_both_directions() {
+		let plan = changed_on_both_sides(SyncMode::TwoWay).plan();
+		assert_eq!(
+			ops(&plan),
+			[
+				move_remote("m", "m2"),
+				move_local("r", "r2"),
+				upload("a"),
+				upload("b"),
+				delete_remote("c"),
+				download("d"),
+				download("e"),
+				delete_local("f"),
+			]
+		);
+	}
+
+	#[test]
+	fn mirror_up_suppresses_downward_ops() {
+		let plan = changed_on_both_sides(SyncMode::MirrorUp).plan();
+		// remote changes are undone instead of downloaded
+		assert_eq!(
+			ops(&plan),
+			[
+				move_remote("m", "m2"),
+				move_remote("r2", "r"),
+				upload("a"),
+				upload("b"),
+				delete_remote("c"),
+				upload("d"),
+				delete_remote("e"),
+				upload("f"),
+			]
+		);
+		assert!(plan.ops.iter().all(|op| op.direction() == Direction::Up));
+		assert!(plan.conflicts.is_empty());
+	}
+
+	#[test]
+	fn mirror_down_suppresses_upward_ops() {
+		let plan = changed_on_both_sides(SyncMode::MirrorDown).plan();
+		// local changes are undone instead of uploaded
+		assert_eq!(
+			ops(&plan),
+			[
+				move_local("m2", "m"),
+				move_local("r", "r2"),
+				download("a"),
+				delete_local("b"),
+				download("c"),
+				download("d"),
+				download("e"),
+				delete_local("f"),
+			]
+		);
+		assert!(plan.ops.iter().all(|op| op.direction() == Direction::Down));
+		assert!(plan.conflicts.is_empty());
+	}
+
+	#[test]
+	fn backup_only_never_removes_remote_entries() {
+		let plan = changed_on_both_sides(SyncMode::BackupOnly).plan();
+		// no deletes or moves; the remote keeps c, e, m and r2
+		assert_eq!(
+			ops(&plan),
+			[
+				upload("a"),
+				upload("b"),
+				upload("d"),
+				upload("f"),
+				upload("m2"),
+				upload("r"),
+			]
+		);
+		assert_eq!(plan.forget, ["c", "m"]);
+	}
+
+	#[test]
+	fn backup_only_keeps_locally_deleted_dir() {
+		let mut f = Fixture {
+			mode: SyncMode::BackupOnly,
+			..Fixture::default()
+		}
+		.synced_dir("a", 1)
+		.synced_file("a/x", 2);
+		f.local.entries.clear();
+		f.remote
+			.entries
+			.insert("a/y".into(), remote_file(3, 3, 100));
+		let plan = f.plan();
+		assert!(plan.ops.is_empty());
+		assert_eq!(plan.forget, ["a"]);
+	}
+
+	#[test]
+	fn mirror_up_restores_remotely_deleted_dir() {
+		let mut f = Fixture {
+			mode: SyncMode::MirrorUp,
+			..Fixture::default()
+		}
+		.synced_dir("a", 1)
+		.synced_file("a/x", 2);
+		f.remote.entries.clear();
+		assert_eq!(ops(&f.plan()), [SyncOp::UploadDir { path: "a".into() }]);
+	}
+
+	#[test]
+	fn mirror_down_overrides_local_conflict() {
+		let mut f = both_edited();
+		f.mode = SyncMode::MirrorDown;
+		let plan = f.plan();
+		assert_eq!(ops(&plan), [download("x.txt")]);
+		assert!(plan.conflicts.is_empty());
+	}
+
+	// ── Whole-directory transfer tests ──
+
+	#[test]
+	fn new_local_tree_uploads_as_dir() {
+		let mut f = Fixture::default();
+		f.local.entries.insert("a".into(), local_dir(1));
+		f.local.entries.insert("a/b".into(), local_dir(2));
+		f.local.entries.insert("a/b/x".into(), local_file(3, 1, 1));
+		f.local
+			.entries
+			.insert("a-sibling".into(), local_file(4, 1, 1));
+		assert_eq!(
+			ops(&f.plan()),
+			[SyncOp::UploadDir { path: "a".into() }, upload("a-sibling"),]
+		);
+	}
+
+	#[test]
+	fn new_remote_tree_downloads_as_dir() {
+		let mut f = Fixture::default();
+		f.remote.entries.insert("a".into(), remote_dir(1));
+		f.remote.entries.insert("a/x".into(), remote_file(2, 2, 1));
+		assert_eq!(ops(&f.plan()), [SyncOp::DownloadDir { path: "a".into() }]);
+	}
+
+	#[test]
+	fn remote_tree_with_ignored_entries_downloads_per_file() {
+		let mut f = Fixture::default();
+		f.remote.entries.insert("a".into(), remote_dir(1));
+		f.remote.entries.insert("a/x".into(), remote_file(2, 2, 1));
+		f.remote.ignored.insert("a/x.tmp".into());
+		assert_eq!(
+			ops(&f.plan()),
+			[SyncOp::CreateLocalDir { path: "a".into() }, download("a/x"),]
+		);
+	}
+}
@@ -0,0 +1,503 @@
+use std::{
+	collections::{BTreeSet, HashMap},
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+use filen_sdk_rs::{
+	auth::Client,
+	fs::{HasName, HasParent, HasUUID},
+	socket::{
+		DecryptedDriveEvent, DecryptedSocketEvent, FileArchiveRestored, FileMove, FileNew,
+		FileRestore, FolderMove, FolderRestore, FolderSubCreated, ListenerHandle,
+	},
+};
+use filen_types::{fs::ParentUuid, traits::CowHelpers};
+use tokio::{sync::mpsc, time::Instant};
+use uuid::Uuid;
+
+use crate::{
+	Error,
+	tree::{EntryKind, RemoteTree, is_same_or_descendant, join_path, parent_path, rebase_path},
+};
+
+// ── Change sets ──
+
+/// Remote paths that changed since the last change set, for
+/// [`SyncEngine::prepare_remote_changed`](crate::engine::SyncEngine::prepare_remote_changed).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemoteChangeSet {
+	/// Sync paths of every entry that was created, modified, removed, or
+	/// moved (both ends). Ancestors of a changed entry aren't included.
+	pub paths: BTreeSet<String>,
+	/// Drive events were missed or couldn't be placed, so only a full
+	/// listing is reliable.
+	pub rescan: bool,
+}
+
+impl RemoteChangeSet {
+	pub fn is_empty(&self) -> bool {
+		self.paths.is_empty() && !self.rescan
+	}
+
+	pub fn merge(&mut self, other: RemoteChangeSet) {
+		self.paths.extend(other.paths);
+		self.rescan |= other.rescan;
+	}
+}
+
+// ── RemotePaths ──
+
+/// The sync path of every remote entry by id, so drive events, which only
+/// carry uuids, can be placed in the sync root.
+///
+/// Files are keyed by their stable uuid, directories by their uuid, like
+/// [`RemoteState::id`](crate::tree::RemoteState::id).
+#[derive(Debug, Clone)]
+pub struct RemotePaths {
+	root: Uuid,
+	paths: HashMap<Uuid, String>,
+}
+
+impl RemotePaths {
+	pub fn new(root: Uuid) -> Self {
+		Self {
+			root,
+			paths: HashMap::new(),
+		}
+	}
+
+	pub fn from_tree(root: Uuid, tree: &RemoteTree) -> Self {
+		Self {
+			root,
+			paths: tree
+				.entries
+				.iter()
+				.map(|(path, state)| (state.id, path.clone()))
+				.collect(),
+		}
+	}
+
+	/// Returns the sync path of `id`, `""` being the sync root.
+	pub fn path(&self, id: Uuid) -> Option<&str> {
+		if id == self.root {
+			Some("")
+		} else {
+			self.paths.get(&id).map(String::as_str)
+		}
+	}
+
+	/// Adds the sync paths `event` touches to `changes`, and keeps the
+	/// paths of moved and removed entries current.
+	pub(crate) fn apply(&mut self, event: &DecryptedDriveEvent<'_>, changes: &mut RemoteChangeSet) {
+		match event {
+			DecryptedDriveEvent::FileNew(FileNew(file))
+			| DecryptedDriveEvent::FileRestore(FileRestore(file))
+			| DecryptedDriveEvent::FileMove(FileMove(file))
+			| DecryptedDriveEvent::FileArchiveRestored(FileArchiveRestored { file, .. }) => self.moved(
+				Uuid::from(file.stable_uuid()),
+				EntryKind::File,
+				file.parent(),
+				file.name(),
+				changes,
+			),
+			DecryptedDriveEvent::FolderSubCreated(FolderSubCreated(dir))
+			| DecryptedDriveEvent::FolderRestore(FolderRestore(dir))
+			| DecryptedDriveEvent::FolderMove(FolderMove(dir)) => self.moved(
+				dir.uuid(),
+				EntryKind::Dir,
+				dir.parent(),
+				dir.name(),
+				changes,
+			),
+			DecryptedDriveEvent::FileMetadataChanged(e) => self.renamed(
+				Uuid::from(e.stable_uuid),
+				EntryKind::File,
+				e.metadata.name(),
+				changes,
+			),
+			DecryptedDriveEvent::FolderMetadataChanged(e) => {
+				self.renamed(e.uuid, EntryKind::Dir, e.meta.name(), changes)
+			}
+			// a new version replaced the trashed one, at the same path
+			DecryptedDriveEvent::FileTrash(e) if e.new_uuid.is_some() => {
+				self.changed(Uuid::from(e.stable_uuid), changes)
+			}
+			DecryptedDriveEvent::FileTrash(e) => {
+				self.removed(Uuid::from(e.stable_uuid), EntryKind::File, changes)
+			}
+			DecryptedDriveEvent::FileArchived(e) => {
+				self.changed(Uuid::from(e.stable_uuid), changes)
+			}
+			DecryptedDriveEvent::FileDeletedPermanent(e) => {
+				// without a stable uuid, only an old version was deleted
+				if let Some(stable_uuid) = e.stable_uuid {
+					self.removed(Uuid::from(stable_uuid), EntryKind::File, changes);
+				}
+			}
+			DecryptedDriveEvent::FolderTrash(e) => self.removed(e.uuid, EntryKind::Dir, changes),
+			DecryptedDriveEvent::FolderDeletedPermanent(e) => {
+				self.removed(e.uuid, EntryKind::Dir, changes)
+			}
+			DecryptedDriveEvent::DeleteAll => changes.rescan = true,
+			// nothing a sync cares about
+			DecryptedDriveEvent::FolderColorChanged(_)
+			| DecryptedDriveEvent::ItemFavorite(_)
+			| DecryptedDriveEvent::TrashEmpty
+			| DecryptedDriveEvent::DeleteVersioned => {}
+		}
+	}
+
+	fn changed(&self, id: Uuid, changes: &mut RemoteChangeSet) {
+		if let Some(path) = self.path(id).filter(|path| !path.is_empty()) {
+			changes.paths.insert(path.to_owned());
+		}
+	}
+
+	/// An entry now lives in `parent` as `name`, which may be outside the
+	/// sync root. Either end inside the root is a change.
+	fn moved(
+		&mut self,
+		id: Uuid,
+		kind: EntryKind,
+		parent: &ParentUuid,
+		name: Option<&str>,
+		changes: &mut RemoteChangeSet,
+	) {
+		if id == self.root {
+			// the root is synced by uuid, wherever it is
+			return;
+		}
+		let old = self.paths.get(&id).cloned();
+		let new_parent = match parent {
+			ParentUuid::Uuid(parent) => self.path(*parent),
+			_ => None,
+		};
+		let new = match (new_parent, name) {
+			(Some(parent), Some(name)) => Some(join_path(parent, name)),
+			(Some(_), None) => {
+				// it's in the root, but there's no telling where
+				changes.rescan = true;
+				None
+			}
+			(None, _) => None,
+		};
+
+		if let Some(old) = &old {
+			changes.paths.insert(old.clone());
+		}
+		if let Some(new) = &new {
+			changes.paths.insert(new.clone());
+		}
+		match (old, new) {
+			(Some(old), Some(new)) if kind == EntryKind::Dir => self.rebase_subtree(&old, &new),
+			(_, Some(new)) => {
+				self.paths.insert(id, new);
+			}
+			(Some(old), None) => self.remove(id, kind, &old),
+			(None, None) => {}
+		}
+	}
+
+	fn renamed(
+		&mut self,
+		id: Uuid,
+		kind: EntryKind,
+		name: Option<&str>,
+		changes: &mut RemoteChangeSet,
+	) {
+		let Some(old) = self.paths.get(&id).cloned() else {
+			return;
+		};
+		changes.paths.insert(old.clone());
+		let Some(name) = name else {
+			changes.rescan = true;
+			return;
+		};
+		let new = join_path(parent_path(&old).unwrap_or(""), name);
+		changes.paths.insert(new.clone());
+		match kind {
+			EntryKind::Dir => self.rebase_subtree(&old, &new),
+			EntryKind::File => {
+				self.paths.insert(id, new);
+			}
+		}
+	}
+
+	fn removed(&mut self, id: Uuid, kind: EntryKind, changes: &mut RemoteChangeSet) {
+		if id == self.root {
+			changes.rescan = true;
+			return;
+		}
+		let Some(path) = self.paths.get(&id).cloned() else {
+			return;
+		};
+		changes.paths.insert(path.clone());
+		self.remove(id, kind, &path);
+	}
+
+	fn remove(&mut self, id: Uuid, kind: EntryKind, path: &str) {
+		match kind {
+			EntryKind::Dir => self.paths.retain(|_, p| !is_same_or_descendant(p, path)),
+			// another file may have taken the path already
+			EntryKind::File => {
+				self.paths.remove(&id);
+			}
+		}
+	}
+
+	fn rebase_subtree(&mut self, from: &str, to: &str) {
+		for path in self.paths.values_mut() {
+			if let Some(rebased) = rebase_path(path, from, to) {
+				*path = rebased;
+			}
+		}
+	}
+}
+
+// ── RemoteWatcher ──
+
+/// How long a change set keeps collecting events after the first one, so a
+/// steady stream of events can't hold it back indefinitely.
+const MAX_BATCH_WAIT: Duration = Duration::from_secs(30);
+
+/// What the socket listener forwards to the watcher.
+enum Signal {
+	Drive {
+		id: u64,
+		/// `None` for events that couldn't be decrypted.
+		event: Option<DecryptedDriveEvent<'static>>,
+	},
+	Reconnecting,
+	Connected,
+}
+
+impl Signal {
+	fn from_event(event: &DecryptedSocketEvent<'_>) -> Option<Self> {
+		Some(match event {
+			DecryptedSocketEvent::Drive {
+				inner,
+				drive_message_id,
+			} => Signal::Drive {
+				id: *drive_message_id,
+				event: Some(inner.clone().into_owned_cow()),
+			},
+			DecryptedSocketEvent::DriveMalformed { drive_message_id } => Signal::Drive {
+				id: *drive_message_id,
+				event: None,
+			},
+			DecryptedSocketEvent::Reconnecting => Signal::Reconnecting,
+			DecryptedSocketEvent::AuthSuccess => Signal::Connected,
+			_ => return None,
+		})
+	}
+}
+
+/// Returns `true` if `id` doesn't directly follow `last`, i.e. events in
+/// between were lost. Replayed ids don't count.
+fn is_gap(last: u64, id: u64) -> bool {
+	id > last.saturating_add(1)
+}
+
+/// Follows the socket's drive events and turns them into
+/// [`RemoteChangeSet`]s for one sync root, in batches.
+///
+/// Drive event ids are consecutive, so a skipped id, or the server's id
+/// having moved on while the socket was reconnecting, means events were
+/// lost; the next change set then asks for a full listing instead.
+pub struct RemoteWatcher {
+	client: Arc<Client>,
+	paths: Arc<Mutex<RemotePaths>>,
+	rx: mpsc::UnboundedReceiver<Signal>,
+	debounce: Duration,
+	/// The id of the latest drive event seen.
+	last_id: u64,
+	reconnecting: bool,
+	// dropping it unregisters the listener
+	_listener: ListenerHandle,
+}
+
+impl RemoteWatcher {
+	/// Starts following drive events, placing them with `paths`. Changes are
+	/// reported once no new event arrived for `debounce`.
+	pub(crate) async fn new(
+		client: Arc<Client>,
+		paths: Arc<Mutex<RemotePaths>>,
+		debounce: Duration,
+	) -> Result<Self, Error> {
+		let (tx, rx) = mpsc::unbounded_channel();
+		// Listen before reading the latest id: events in between are then
+		// seen twice rather than not at all
+		let listener = client
+			.add_event_listener_sync(
+				Box::new(move |event: &DecryptedSocketEvent<'_>| {
+					if let Some(signal) = Signal::from_event(event) {
+						// a closed receiver means the watcher is being dropped
+						let _ = tx.send(signal);
+					}
+				}),
+				None,
+			)
+			.await?;
+		let last_id = client.get_last_event_ids().await?.drive;
+		Ok(Self {
+			client,
+			paths,
+			rx,
+			debounce,
+			last_id,
+			reconnecting: false,
+			_listener: listener,
+		})
+	}
+
+	/// Waits for the next non-empty change set, collecting events until
+	/// none arrived for the debounce period, or for at most
+	/// [`MAX_BATCH_WAIT`] (or one debounce period, if longer) after the first
+	/// one.
+	pub async fn next(&mut self) -> Option<RemoteChangeSet> {
+		loop {
+			let signal = self.rx.recv().await?;
+			let deadline = Instant::now() + MAX_BATCH_WAIT.max(self.debounce);
+			let mut changes = RemoteChangeSet::default();
+			self.handle(signal, &mut changes).await;
+			loop {
+				let quiet_until = deadline.min(Instant::now() + self.debounce);
+				match tokio::time::timeout_at(quiet_until, self.rx.recv()).await {
+					Ok(Some(signal)) => self.handle(signal, &mut changes).await,
+					_ => break,
+				}
+			}
+			if !changes.is_empty() {
+				return Some(changes);
+			}
+		}
+	}
+
+	async fn handle(&mut self, signal: Signal, changes: &mut RemoteChangeSet) {
+		match signal {
+			Signal::Drive { id, event } => {
+				if is_gap(self.last_id, id) {
+					changes.rescan = true;
+				}
+				self.last_id = self.last_id.max(id);
+				if let Some(event) = event {
+					self.paths
+						.lock()
+						.unwrap_or_else(|e| e.into_inner())
+						.apply(&event, changes);
+				}
+			}
+			Signal::Reconnecting => self.reconnecting = true,
+			Signal::Connected if self.reconnecting => {
+				self.reconnecting = false;
+				// the socket doesn't replay what happened while it was down
+				match self.client.get_last_event_ids().await {
+					Ok(ids) if ids.drive <= self.last_id => {}
+					Ok(ids) => {
+						changes.rescan = true;
+						self.last_id = ids.drive;
+					}
+					Err(_) => changes.rescan = true,
+				}
+			}
+			Signal::Connected => {}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::Utc;
+	use filen_sdk_rs::{
+		fs::dir::{DecryptedDirectoryMeta, RemoteDirectory, meta::DirectoryMeta},
+		socket::{FolderMetadataChanged, FolderTrash},
+	};
+
+	use super::*;
+
+	fn dir(uuid: Uuid, parent: Uuid, name: &str) -> RemoteDirectory {
+		RemoteDirectory::new_from_parts(
+			uuid,
+			DecryptedDirectoryMeta {
+				name: name.to_owned().into(),
+				created: None,
+			},
+			ParentUuid::Uuid(parent),
+			Utc::now(),
+		)
+	}
+
+	fn paths(changes: &RemoteChangeSet) -> Vec<&str> {
+		changes.paths.iter().map(String::as_str).collect()
+	}
+
+	#[test]
+	fn events_are_placed_in_the_root() {
+		let (root, a, b, outside) = (
+			Uuid::new_v4(),
+			Uuid::new_v4(),
+			Uuid::new_v4(),
+			Uuid::new_v4(),
+		);
+		let mut remote = RemotePaths::new(root);
+		let mut changes = RemoteChangeSet::default();
+
+		let created = DecryptedDriveEvent::FolderSubCreated(FolderSubCreated(dir(a, root, "a")));
+		remote.apply(&created, &mut changes);
+		let created = DecryptedDriveEvent::FolderSubCreated(FolderSubCreated(dir(b, a, "b")));
+		remote.apply(&created, &mut changes);
+		assert_eq!(paths(&changes), ["a", "a/b"]);
+
+		// renaming a directory moves everything below it
+		let mut changes = RemoteChangeSet::default();
+		let renamed = DecryptedDriveEvent::FolderMetadataChanged(FolderMetadataChanged {
+			uuid: a,
+			meta: DirectoryMeta::Decoded(DecryptedDirectoryMeta {
+				name: "c".into(),
+				created: None,
+			}),
+		});
+		remote.apply(&renamed, &mut changes);
+		assert_eq!(paths(&changes), ["a", "c"]);
+		assert_eq!(remote.path(b), Some("c/b"));
+
+		// moving out of the root is a removal, events outside are dropped
+		let mut changes = RemoteChangeSet::default();
+		let moved = DecryptedDriveEvent::FolderMove(FolderMove(dir(b, outside, "b")));
+		remote.apply(&moved, &mut changes);
+		let elsewhere =
+			DecryptedDriveEvent::FolderSubCreated(FolderSubCreated(dir(Uuid::new_v4(), b, "x")));
+		remote.apply(&elsewhere, &mut changes);
+		assert_eq!(paths(&changes), ["c/b"]);
+		assert_eq!(remote.path(b), None);
+
+		let mut changes = RemoteChangeSet::default();
+		let trashed = DecryptedDriveEvent::FolderTrash(FolderTrash {
+			parent: root,
+			uuid: a,
+		});
+		remote.apply(&trashed, &mut changes);
+		assert_eq!(paths(&changes), ["c"]);
+		assert!(!changes.rescan);
+		assert_eq!(remote.path(a), None);
+	}
+
+	#[test]
+	fn lost_events_ask_for_rescan() {
+		let root = Uuid::new_v4();
+		let mut remote = RemotePaths::new(root);
+
+		let mut changes = RemoteChangeSet::default();
+		let trashed = DecryptedDriveEvent::FolderTrash(FolderTrash {
+			parent: Uuid::new_v4(),
+			uuid: root,
+		});
+		remote.apply(&trashed, &mut changes);
+		assert!(changes.rescan);
+
+		assert!(!is_gap(4, 5));
+		assert!(!is_gap(4, 3), "replayed events aren't a gap");
+		assert!(is_gap(4, 6));
+	}
+}
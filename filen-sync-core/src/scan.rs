@@ -0,0 +1,661 @@
+use std::{
+	borrow::Cow,
+	collections::{BTreeSet, HashMap, HashSet},
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+use filen_sdk_rs::{
+	auth::Client,
+	fs::{
+		HasName, HasUUID,
+		categories::{
+			DirType, Normal,
+			fs::{CategoryFS, CategoryFSExt},
+		},
+		dir::RemoteDirectory,
+		file::{
+			RemoteFile,
+			traits::{HasFileInfo, HasRemoteFileInfo},
+		},
+	},
+	io::FilenMetaExt,
+};
+use uuid::Uuid;
+
+use crate::{
+	Error,
+	ignore::{
+		FILENIGNORE, IgnoreStack, IgnoreStackBuilder, LOCAL_FILENIGNORE, is_ignore_file,
+		is_local_only_ignore_file,
+	},
+	remote_watch::RemoteChangeSet,
+	tree::{
+		EntryKind, LocalState, LocalTree, RemoteState, RemoteTree, descendants, file_name,
+		is_same_or_descendant, join_path, parent_path, rebase_path, take_subtree,
+	},
+	watch::LocalChangeSet,
+};
+
+// ── State conversion ──
+
+pub(crate) fn local_state(meta: &std::fs::Metadata) -> LocalState {
+	let kind = if meta.is_dir() {
+		EntryKind::Dir
+	} else {
+		EntryKind::File
+	};
+	LocalState {
+		kind,
+		size: match kind {
+			EntryKind::File => FilenMetaExt::size(meta),
+			EntryKind::Dir => 0,
+		},
+		mtime: FilenMetaExt::modified(meta).timestamp_millis(),
+		#[cfg(unix)]
+		inode: Some(std::os::unix::fs::MetadataExt::ino(meta)),
+		#[cfg(not(unix))]
+		inode: None,
+	}
+}
+
+pub(crate) fn remote_file_state(file: &RemoteFile) -> RemoteState {
+	RemoteState {
+		kind: EntryKind::File,
+		id: Uuid::from(file.stable_uuid()),
+		version: file.uuid(),
+		size: file.size(),
+		mtime: file.last_modified().map(|t| t.timestamp_millis()),
+		hash: file.hash(),
+	}
+}
+
+pub(crate) fn remote_dir_state(dir: &RemoteDirectory) -> RemoteState {
+	RemoteState {
+		kind: EntryKind::Dir,
+		id: dir.uuid(),
+		version: dir.uuid(),
+		size: 0,
+		mtime: None,
+		hash: None,
+	}
+}
+
+/// Returns `true` for names that can't be mapped onto a local path component.
+fn is_unsafe_name(name: &str) -> bool {
+	name.is_empty()
+		|| name == "."
+		|| name == ".."
+		|| name.contains(['/', '\0'])
+		|| (cfg!(windows) && name.contains('\\'))
+}
+
+// ── Local scan ──
+
+pub struct LocalScan {
+	pub tree: LocalTree,
+	/// The ignore stack rebuilt from every ignore file found during the scan.
+	pub ignore: IgnoreStack,
+	/// Entries that couldn't be read. They are left out of `tree`.
+	pub errors: Vec<Error>,
+}
+
+/// Walks the local sync root without following symlinks.
+///
+/// Folder ignore files are (re-)registered on `ignore_builder` as they're
+/// found, so a `.filenignore` applies to its own directory's children within
+/// the same scan. Symlinks are skipped.
+pub fn scan_local(
+	root: &Path,
+	ignore_builder: &mut IgnoreStackBuilder,
+) -> Result<LocalScan, Error> {
+	let root_meta = std::fs::metadata(root).map_err(|e| Error::io(root, e))?;
+	if !root_meta.is_dir() {
+		return Err(Error::InvalidPath(root.to_path_buf()));
+	}
+
+	ignore_builder
+		.clear_folder_ignore_files()
+		.clear_local_folder_ignore_files();
+	let mut ignore = ignore_builder.build()?;
+
+	let mut tree = LocalTree::default();
+	let mut errors = Vec::new();
+	walk_local(
+		root.to_path_buf(),
+		String::new(),
+		ignore_builder,
+		&mut ignore,
+		&mut tree,
+		&mut errors,
+	)?;
+	Ok(LocalScan {
+		tree,
+		ignore,
+		errors,
+	})
+}
+
+/// Adds everything below `abs_dir` (at sync path `rel_dir`) to `tree`,
+/// registering folder ignore files and rebuilding `ignore` as they're found.
+fn walk_local(
+	abs_dir: PathBuf,
+	rel_dir: String,
+	ignore_builder: &mut IgnoreStackBuilder,
+	ignore: &mut IgnoreStack,
+	tree: &mut LocalTree,
+	errors: &mut Vec<Error>,
+) -> Result<(), Error> {
+	let mut pending: Vec<(PathBuf, String)> = vec![(abs_dir, rel_dir)];
+
+	while let Some((abs_dir, rel_dir)) = pending.pop() {
+		let read_dir = match std::fs::read_dir(&abs_dir) {
+			Ok(read_dir) => read_dir,
+			Err(e) => {
+				errors.push(Error::io(&abs_dir, e));
+				continue;
+			}
+		};
+		let mut children = Vec::new();
+		for entry in read_dir {
+			match entry {
+				Ok(entry) => children.push(entry),
+				Err(e) => errors.push(Error::io(&abs_dir, e)),
+			}
+		}
+
+		let mut found_ignore_file = false;
+		for child in &children {
+			let name = child.file_name();
+			if name == FILENIGNORE {
+				ignore_builder.add_folder_ignore_file(child.path());
+				found_ignore_file = true;
+			} else if name == LOCAL_FILENIGNORE {
+				ignore_builder.add_local_folder_ignore_file(child.path());
+				found_ignore_file = true;
+			}
+		}
+		if found_ignore_file {
+			*ignore = ignore_builder.build()?;
+		}
+
+		for child in children {
+			let abs_path = child.path();
+			let Some(name) = child.file_name().to_str().map(str::to_owned) else {
+				errors.push(Error::Skipped {
+					path: abs_path.display().to_string(),
+					reason: "name is not valid UTF-8".into(),
+				});
+				continue;
+			};
+			let meta = match std::fs::symlink_metadata(&abs_path) {
+				Ok(meta) => meta,
+				Err(e) => {
+					errors.push(Error::io(&abs_path, e));
+					continue;
+				}
+			};
+			if meta.file_type().is_symlink() {
+				continue;
+			}
+			if ignore.is_ignored(&abs_path, meta.is_dir()) {
+				continue;
+			}
+			let rel_path = join_path(&rel_dir, &name);
+			let state = local_state(&meta);
+			tree.entries.insert(rel_path.clone(), state);
+			if state.kind == EntryKind::Dir {
+				pending.push((abs_path, rel_path));
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Brings a [`LocalScan`] up to date with `changes`, re-reading only the
+/// changed paths. Directories that are new to the tree are walked in full.
+///
+/// A changed ignore file is re-registered (or dropped, if it's gone) and
+/// its directory re-walked, since its rules may hide or reveal anything
+/// below it. Changes below an ignored directory are dropped like a full
+/// scan would.
+///
+/// `changes.rescan` isn't looked at; a full [`scan_local`] is the caller's call.
+pub fn update_local(
+	root: &Path,
+	ignore_builder: &mut IgnoreStackBuilder,
+	scan: &mut LocalScan,
+	changes: &LocalChangeSet,
+) -> Result<(), Error> {
+	let mut dirty = changes.paths.clone();
+	// directories whose whole subtree must be re-read
+	let mut rewalk = BTreeSet::new();
+	let mut rebuild = false;
+	for path in &changes.paths {
+		if !is_ignore_file(file_name(path)) {
+			continue;
+		}
+		let abs_path = root.join(path);
+		ignore_builder.remove_folder_ignore_file(&abs_path);
+		ignore_builder.remove_local_folder_ignore_file(&abs_path);
+		if abs_path.is_file() {
+			if is_local_only_ignore_file(file_name(path)) {
+				ignore_builder.add_local_folder_ignore_file(abs_path);
+			} else {
+				ignore_builder.add_folder_ignore_file(abs_path);
+			}
+		}
+		rebuild = true;
+		let dir = parent_path(path).unwrap_or("").to_owned();
+		dirty.insert(dir.clone());
+		rewalk.insert(dir);
+	}
+	if rebuild {
+		scan.ignore = ignore_builder.build()?;
+	}
+
+	let mut walked = Vec::<String>::new();
+	for path in dirty {
+		if walked.iter().any(|dir| is_same_or_descendant(&path, dir)) {
+			continue;
+		}
+		if path.is_empty() {
+			// the root itself: only its subtree can have changed
+			if rewalk.contains(&path) {
+				scan.tree.entries.clear();
+				ignore_builder.remove_ignore_files_under(root);
+				scan.ignore = ignore_builder.build()?;
+				walk_local(
+					root.to_path_buf(),
+					path.clone(),
+					ignore_builder,
+					&mut scan.ignore,
+					&mut scan.tree,
+					&mut scan.errors,
+				)?;
+				walked.push(path);
+			}
+			continue;
+		}
+
+		let abs_path = root.join(&path);
+		let parent_known = parent_path(&path).is_none_or(|parent| {
+			parent.is_empty()
+				|| scan
+					.tree
+					.entries
+					.get(parent)
+					.is_some_and(|p| p.kind == EntryKind::Dir)
+		});
+		let meta = match std::fs::symlink_metadata(&abs_path) {
+			Ok(meta) if parent_known => Some(meta),
+			Ok(_) => None,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+			Err(e) => {
+				scan.errors.push(Error::io(&abs_path, e));
+				continue;
+			}
+		};
+		let meta = meta.filter(|meta| {
+			!meta.file_type().is_symlink() && !scan.ignore.is_ignored(&abs_path, meta.is_dir())
+		});
+
+		let previous = scan.tree.entries.get(&path).map(|state| state.kind);
+		let Some(meta) = meta else {
+			take_subtree(&mut scan.tree.entries, &path);
+			if previous == Some(EntryKind::Dir)
+				&& ignore_builder.remove_ignore_files_under(&abs_path)
+			{
+				scan.ignore = ignore_builder.build()?;
+			}
+			continue;
+		};
+		let state = local_state(&meta);
+		let walk = state.kind == EntryKind::Dir
+			&& (previous != Some(EntryKind::Dir) || rewalk.contains(&path));
+		if walk || (previous == Some(EntryKind::Dir) && state.kind != EntryKind::Dir) {
+			take_subtree(&mut scan.tree.entries, &path);
+			if ignore_builder.remove_ignore_files_under(&abs_path) {
+				scan.ignore = ignore_builder.build()?;
+			}
+		}
+		scan.tree.entries.insert(path.clone(), state);
+		if walk {
+			walk_local(
+				abs_path,
+				path.clone(),
+				ignore_builder,
+				&mut scan.ignore,
+				&mut scan.tree,
+				&mut scan.errors,
+			)?;
+			walked.push(path);
+		}
+	}
+	Ok(())
+}
+
+// ── Remote scan ──
+
+/// SDK objects backing a [`RemoteTree`], needed to act on remote entries.
+#[derive(Debug, Clone)]
+pub struct RemoteIndex {
+	pub root: RemoteDirectory,
+	pub dirs: HashMap<String, RemoteDirectory>,
+	pub files: HashMap<String, RemoteFile>,
+}
+
+impl RemoteIndex {
+	pub fn new(root: RemoteDirectory) -> Self {
+		Self {
+			root,
+			dirs: HashMap::new(),
+			files: HashMap::new(),
+		}
+	}
+
+	/// Returns the directory at `path`, `""` being the sync root.
+	pub fn dir(&self, path: &str) -> Option<&RemoteDirectory> {
+		if path.is_empty() {
+			Some(&self.root)
+		} else {
+			self.dirs.get(path)
+		}
+	}
+
+	pub fn remove_subtree(&mut self, path: &str) {
+		self.dirs.retain(|k, _| !is_same_or_descendant(k, path));
+		self.files.retain(|k, _| !is_same_or_descendant(k, path));
+	}
+
+	pub fn rebase_subtree(&mut self, from: &str, to: &str) {
+		rebase_map(&mut self.dirs, from, to);
+		rebase_map(&mut self.files, from, to);
+	}
+}
+
+fn rebase_map<V>(map: &mut HashMap<String, V>, from: &str, to: &str) {
+	let moved = map
+		.extract_if(|k, _| is_same_or_descendant(k, from))
+		.collect::<Vec<_>>();
+	for (path, value) in moved {
+		// SAFETY: every extracted key is inside `from`
+		map.insert(rebase_path(&path, from, to).unwrap(), value);
+	}
+}
+
+pub struct RemoteScan {
+	pub tree: RemoteTree,
+	pub index: RemoteIndex,
+	/// Entries that were listed but can't be synced. They are left out of `tree`.
+	pub errors: Vec<Error>,
+}
+
+/// Lists the remote sync root recursively, applying `ignore` as if every
+/// remote path were located below `local_root`.
+pub async fn scan_remote(
+	client: Arc<Client>,
+	root: &RemoteDirectory,
+	local_root: &Path,
+	ignore: &IgnoreStack,
+) -> Result<RemoteScan, Error> {
+	let mut errors = Vec::new();
+	let (dirs, files) = Normal::list_dir_recursive_with_paths(
+		client,
+		DirType::Dir(Cow::Borrowed(root)),
+		None::<&fn(u64, Option<u64>)>,
+		&mut |scan_errors| errors.extend(scan_errors.into_iter().map(Error::Sdk)),
+		(),
+	)
+	.await?;
+
+	let mut scan = RemoteScan {
+		tree: RemoteTree::default(),
+		index: RemoteIndex::new(root.clone()),
+		errors,
+	};
+	insert_listed(&mut scan, "", dirs, files, local_root, ignore);
+	Ok(scan)
+}
+
+/// Brings a [`RemoteScan`] up to date with `changes`, re-listing only the
+/// directories that hold a changed path. Directories that are new to the
+/// tree are listed in full.
+///
+/// `ignore` must be the stack `scan` was filtered with. `changes.rescan`
+/// isn't looked at; a full [`scan_remote`] is the caller's call.
+pub async fn update_remote(
+	client: Arc<Client>,
+	local_root: &Path,
+	ignore: &IgnoreStack,
+	scan: &mut RemoteScan,
+	changes: &RemoteChangeSet,
+) -> Result<(), Error> {
+	let parents = changes
+		.paths
+		.iter()
+		.filter_map(|path| parent_path(path))
+		.map(str::to_owned)
+		.collect::<BTreeSet<_>>();
+	// directories whose whole subtree was re-listed
+	let mut listed = Vec::<String>::new();
+	for parent in parents {
+		if listed.iter().any(|dir| is_same_or_descendant(&parent, dir)) {
+			continue;
+		}
+		// gone by now, or below an ignored directory
+		let Some(dir) = scan.index.dir(&parent).cloned() else {
+			continue;
+		};
+		let (dirs, files) = Normal::list_dir(
+			&client,
+			&DirType::Dir(Cow::Borrowed(&dir)),
+			None::<&fn(u64, Option<u64>)>,
+			(),
+		)
+		.await?;
+
+		// directories that are still where they were keep their subtree
+		let mut kept = HashSet::new();
+		let mut new_dirs = Vec::new();
+		for dir in &dirs {
+			let path = join_path(&parent, dir.name().unwrap_or_default());
+			if scan
+				.index
+				.dirs
+				.get(&path)
+				.is_some_and(|old| old.uuid() == dir.uuid())
+			{
+				kept.insert(path);
+			} else {
+				new_dirs.push(path);
+			}
+		}
+		let children = descendants(&scan.tree.entries, &parent)
+			.map(|(path, _)| path)
+			.chain(&scan.tree.ignored)
+			.filter(|path| parent_path(path) == Some(parent.as_str()) && !kept.contains(*path))
+			.cloned()
+			.collect::<Vec<_>>();
+		for child in children {
+			scan.tree
+				.ignored
+				.retain(|path| !is_same_or_descendant(path, &child));
+			take_subtree(&mut scan.tree.entries, &child);
+			scan.index.remove_subtree(&child);
+		}
+
+		insert_listed(
+			scan,
+			&parent,
+			with_names(dirs),
+			with_names(files),
+			local_root,
+			ignore,
+		);
+
+		for path in new_dirs {
+			let Some(dir) = scan.index.dirs.get(&path).cloned() else {
+				continue;
+			};
+			let mut errors = Vec::new();
+			let (dirs, files) = Normal::list_dir_recursive_with_paths(
+				client.clone(),
+				DirType::Dir(Cow::Borrowed(&dir)),
+				None::<&fn(u64, Option<u64>)>,
+				&mut |scan_errors| errors.extend(scan_errors.into_iter().map(Error::Sdk)),
+				(),
+			)
+			.await?;
+			scan.errors.extend(errors);
+			insert_listed(scan, &path, dirs, files, local_root, ignore);
+			listed.push(path);
+		}
+	}
+	Ok(())
+}
+
+/// Pairs listed items with their names, which stay empty (and so unsafe)
+/// if they couldn't be decrypted.
+fn with_names<T: HasName>(items: Vec<T>) -> Vec<(T, String)> {
+	items
+		.into_iter()
+		.map(|item| {
+			let name = item.name().unwrap_or_default().to_owned();
+			(item, name)
+		})
+		.collect()
+}
+
+/// Adds listed entries to `scan`, their paths relative to the directory at
+/// sync path `prefix`. Entries that can't be synced end up in `scan.errors`
+/// instead, ignored ones in `scan.tree.ignored`.
+fn insert_listed(
+	scan: &mut RemoteScan,
+	prefix: &str,
+	dirs: Vec<(RemoteDirectory, String)>,
+	files: Vec<(RemoteFile, String)>,
+	local_root: &Path,
+	ignore: &IgnoreStack,
+) {
+	let mut unsafe_paths = Vec::new();
+	for (dir, path) in dirs {
+		let is_unsafe = path.split('/').any(is_unsafe_name);
+		let path = join_path(prefix, &path);
+		if is_unsafe {
+			unsafe_paths.push(path);
+			continue;
+		}
+		if ignore.is_ignored(&local_root.join(&path), true) {
+			scan.tree.ignored.insert(path);
+			continue;
+		}
+		scan.tree
+			.entries
+			.insert(path.clone(), remote_dir_state(&dir));
+		scan.index.dirs.insert(path, dir);
+	}
+	for (file, path) in files {
+		let is_unsafe = path.split('/').any(is_unsafe_name);
+		let path = join_path(prefix, &path);
+		if is_unsafe {
+			unsafe_paths.push(path);
+			continue;
+		}
+		if ignore.is_ignored(&local_root.join(&path), false) {
+			scan.tree.ignored.insert(path);
+			continue;
+		}
+		if scan.tree.entries.contains_key(&path) {
+			scan.errors.push(Error::Skipped {
+				path,
+				reason: "a remote directory with the same name exists".into(),
+			});
+			continue;
+		}
+		scan.tree
+			.entries
+			.insert(path.clone(), remote_file_state(&file));
+		scan.index.files.insert(path, file);
+	}
+	for path in unsafe_paths {
+		scan.errors.push(Error::Skipped {
+			reason: "name can't be used as a local path".into(),
+			path,
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use super::*;
+
+	fn changes(paths: &[&str]) -> LocalChangeSet {
+		LocalChangeSet {
+			paths: paths.iter().map(|path| (*path).to_owned()).collect(),
+			rescan: false,
+		}
+	}
+
+	fn paths(tree: &LocalTree) -> Vec<&str> {
+		tree.entries.keys().map(String::as_str).collect()
+	}
+
+	#[test]
+	fn update_matches_full_scan() {
+		let dir = tempfile::tempdir().unwrap();
+		let root = dir.path();
+		fs::create_dir_all(root.join("a/b")).unwrap();
+		fs::write(root.join("a/b/x.log"), b"x").unwrap();
+		fs::write(root.join("a/y"), b"y").unwrap();
+		fs::write(root.join("gone"), b"gone").unwrap();
+
+		let mut builder = IgnoreStackBuilder::new(root);
+		let mut scan = scan_local(root, &mut builder).unwrap();
+		assert_eq!(paths(&scan.tree), ["a", "a/b", "a/b/x.log", "a/y", "gone"]);
+
+		// a new ignore file hides existing entries, a new dir is walked in full
+		fs::write(root.join("a/.filenignore"), b"*.log\n").unwrap();
+		fs::remove_file(root.join("gone")).unwrap();
+		fs::create_dir_all(root.join("new/deep")).unwrap();
+		fs::write(root.join("new/deep/z"), b"z").unwrap();
+		update_local(
+			root,
+			&mut builder,
+			&mut scan,
+			&changes(&["a/.filenignore", "gone", "new"]),
+		)
+		.unwrap();
+		let full = scan_local(root, &mut IgnoreStackBuilder::new(root)).unwrap();
+		assert_eq!(scan.tree, full.tree);
+		assert_eq!(
+			paths(&scan.tree),
+			[
+				"a",
+				"a/.filenignore",
+				"a/b",
+				"a/y",
+				"new",
+				"new/deep",
+				"new/deep/z"
+			]
+		);
+
+		// removing the ignore file's directory drops its rules
+		fs::remove_dir_all(root.join("a")).unwrap();
+		fs::create_dir_all(root.join("new/deep")).unwrap();
+		fs::write(root.join("new/deep/w.log"), b"w").unwrap();
+		update_local(
+			root,
+			&mut builder,
+			&mut scan,
+			&changes(&["a", "new/deep", "new/deep/w.log"]),
+		)
+		.unwrap();
+		let full = scan_local(root, &mut IgnoreStackBuilder::new(root)).unwrap();
+		assert_eq!(scan.tree, full.tree);
+		assert!(scan.tree.entries.contains_key("new/deep/w.log"));
+	}
+}
@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+use filen_types::crypto::Blake3Hash;
+
+use crate::tree::{LocalState, RemoteState, rebase_subtree, take_subtree};
+
+// ── BaseSnapshot ──
+
+/// Both sides of a single path as they looked after the last successful sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BaseEntry {
+	pub local: LocalState,
+	pub remote: RemoteState,
+	/// Blake3 hash of the content both sides agreed on, `None` for directories
+	/// and for files whose remote hash is unknown.
+	pub synced_hash: Option<Blake3Hash>,
+}
+
+impl BaseEntry {
+	/// Records `local` and `remote` as in sync, holding the remote's content.
+	pub fn new(local: LocalState, remote: RemoteState) -> Self {
+		Self {
+			local,
+			remote,
+			synced_hash: remote.hash,
+		}
+	}
+}
+
+/// A single mutation of a [`BaseSnapshot`], replayed onto the state database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum BaseChange {
+	Upsert(String, BaseEntry),
+	RemoveSubtree(String),
+	RebaseSubtree { from: String, to: String },
+}
+
+/// The common ancestor both deltas are computed against.
+///
+/// A path is only present if it was in sync on both sides at the end of the
+/// last run; everything else is a change on at least one side.
+///
+/// Every mutation is journaled so it can be committed to the state database
+/// with [`SyncStateDb::commit`](crate::state::SyncStateDb::commit).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BaseSnapshot {
+	entries: BTreeMap<String, BaseEntry>,
+	changes: Vec<BaseChange>,
+}
+
+impl BaseSnapshot {
+	/// Wraps entries loaded from storage, with an empty journal.
+	pub(crate) fn from_entries(entries: BTreeMap<String, BaseEntry>) -> Self {
+		Self {
+			entries,
+			changes: Vec::new(),
+		}
+	}
+
+	pub fn entries(&self) -> &BTreeMap<String, BaseEntry> {
+		&self.entries
+	}
+
+	pub fn get(&self, path: &str) -> Option<&BaseEntry> {
+		self.entries.get(path)
+	}
+
+	pub fn insert(&mut self, path: String, entry: BaseEntry) {
+		self.entries.insert(path.clone(), entry);
+		self.changes.push(BaseChange::Upsert(path, entry));
+	}
+
+	/// Forgets `path` and everything below it.
+	pub fn remove_subtree(&mut self, path: &str) {
+		take_subtree(&mut self.entries, path);
+		self.changes
+			.push(BaseChange::RemoveSubtree(path.to_owned()));
+	}
+
+	/// Re-keys `from` and everything below it to `to`.
+	pub fn rebase_subtree(&mut self, from: &str, to: &str) {
+		rebase_subtree(&mut self.entries, from, to);
+		self.changes.push(BaseChange::RebaseSubtree {
+			from: from.to_owned(),
+			to: to.to_owned(),
+		});
+	}
+
+	/// Returns `true` if there are mutations that haven't been committed yet.
+	pub fn has_changes(&self) -> bool {
+		!self.changes.is_empty()
+	}
+
+	pub(crate) fn take_changes(&mut self) -> Vec<BaseChange> {
+		std::mem::take(&mut self.changes)
+	}
+}
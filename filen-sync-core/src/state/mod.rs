@@ -0,0 +1,373 @@
+use std::{
+	collections::BTreeMap,
+	path::{Path, PathBuf},
+};
+
+use filen_types::crypto::Blake3Hash;
+use rusqlite::{Connection, OpenFlags, OptionalExtension, Row, TransactionBehavior, params};
+use uuid::Uuid;
+
+use crate::{
+	Error,
+	ignore::IgnoreStackBuilder,
+	plan::SyncMode,
+	snapshot::{BaseChange, BaseEntry, BaseSnapshot},
+	tree::{EntryKind, LocalState, RemoteState},
+};
+
+mod statements;
+#[cfg(test)]
+mod tests;
+
+// ── Sync pairs ──
+
+/// Identifies a sync pair within a [`SyncStateDb`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PairId(i64);
+
+/// The persisted configuration of a sync pair: its roots, ignore files and mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncPairRecord {
+	pub local_root: PathBuf,
+	pub remote_root: Uuid,
+	/// The user's global ignore file, applied to every sync pair.
+	pub global_ignore_file: Option<PathBuf>,
+	/// The ignore file for this sync pair only.
+	pub sync_ignore_file: Option<PathBuf>,
+	pub mode: SyncMode,
+}
+
+impl SyncPairRecord {
+	/// Returns a builder for the pair's ignore stack, with the configured
+	/// files set. Folder ignore files are found while scanning.
+	pub fn ignore_builder(&self) -> IgnoreStackBuilder {
+		let mut builder = IgnoreStackBuilder::new(&self.local_root);
+		if let Some(path) = &self.global_ignore_file {
+			builder.set_global_user_file(path);
+		}
+		if let Some(path) = &self.sync_ignore_file {
+			builder.set_sync_specific_file(path);
+		}
+		builder
+	}
+}
+
+fn path_to_sql(path: &Path) -> Result<&str, Error> {
+	path.to_str()
+		.ok_or_else(|| Error::InvalidPath(path.to_path_buf()))
+}
+
+fn mode_to_sql(mode: SyncMode) -> u8 {
+	match mode {
+		SyncMode::TwoWay => 0,
+		SyncMode::MirrorUp => 1,
+		SyncMode::MirrorDown => 2,
+		SyncMode::BackupOnly => 3,
+	}
+}
+
+fn pair_from_row(row: &Row<'_>) -> rusqlite::Result<(PairId, SyncPairRecord)> {
+	let mode = match row.get::<_, u8>(5)? {
+		0 => SyncMode::TwoWay,
+		1 => SyncMode::MirrorUp,
+		2 => SyncMode::MirrorDown,
+		3 => SyncMode::BackupOnly,
+		other => {
+			return Err(rusqlite::Error::IntegralValueOutOfRange(5, other.into()));
+		}
+	};
+	Ok((
+		PairId(row.get(0)?),
+		SyncPairRecord {
+			local_root: PathBuf::from(row.get::<_, String>(1)?),
+			remote_root: row.get(2)?,
+			global_ignore_file: row.get::<_, Option<String>>(3)?.map(PathBuf::from),
+			sync_ignore_file: row.get::<_, Option<String>>(4)?.map(PathBuf::from),
+			mode,
+		},
+	))
+}
+
+// ── Entries ──
+
+fn kind_to_sql(kind: EntryKind) -> u8 {
+	match kind {
+		EntryKind::File => 0,
+		EntryKind::Dir => 1,
+	}
+}
+
+fn entry_from_row(row: &Row<'_>) -> rusqlite::Result<(String, BaseEntry)> {
+	let kind = match row.get::<_, u8>(1)? {
+		0 => EntryKind::File,
+		1 => EntryKind::Dir,
+		other => {
+			return Err(rusqlite::Error::IntegralValueOutOfRange(1, other.into()));
+		}
+	};
+	let local = LocalState {
+		kind,
+		size: row.get(2)?,
+		mtime: row.get(3)?,
+		inode: row.get::<_, Option<i64>>(4)?.map(|inode| inode as u64),
+	};
+	let remote = RemoteState {
+		kind,
+		id: row.get(5)?,
+		version: row.get(6)?,
+		size: row.get(7)?,
+		mtime: row.get(8)?,
+		hash: row.get::<_, Option<[u8; 32]>>(9)?.map(Blake3Hash::from),
+	};
+	Ok((
+		row.get(0)?,
+		BaseEntry {
+			local,
+			remote,
+			synced_hash: row.get::<_, Option<[u8; 32]>>(10)?.map(Blake3Hash::from),
+		},
+	))
+}
+
+fn load_base(db: &Connection, id: PairId) -> Result<BaseSnapshot, Error> {
+	let mut stmt = db.prepare(statements::ENTRY_SELECT_ALL)?;
+	let entries = stmt
+		.query_map([id.0], entry_from_row)?
+		.collect::<rusqlite::Result<BTreeMap<_, _>>>()?;
+	Ok(BaseSnapshot::from_entries(entries))
+}
+
+// ── SyncStateDb ──
+
+const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(5000);
+
+/// SQLite store for every sync pair's configuration and base snapshot.
+///
+/// Unlike the SDK cache, this holds state that can't be rebuilt from the
+/// server (it's what tells a local delete apart from a remote create), so
+/// schema changes are applied as migrations rather than a wipe and rebuild.
+pub struct SyncStateDb {
+	db: Connection,
+}
+
+impl SyncStateDb {
+	/// Opens (or creates) the state database at `path`, migrating it to the current schema.
+	pub fn open(path: &Path) -> Result<Self, Error> {
+		Self::init(Connection::open(path)?)
+	}
+
+	/// Opens a throwaway database, e.g. for dry runs.
+	pub fn open_in_memory() -> Result<Self, Error> {
+		Self::init(Connection::open_in_memory()?)
+	}
+
+	/// Reads the base snapshot of the pair `record` describes without writing to the
+	/// database at `path`, or creating it, e.g. for dry runs.
+	///
+	/// The database isn't migrated, so it can have any schema version up to the current one.
+	/// A pair that isn't registered, or is registered with a different remote root, has an
+	/// empty base, just like after [`SyncStateDb::register_pair`].
+	pub fn read_base(path: &Path, record: &SyncPairRecord) -> Result<BaseSnapshot, Error> {
+		if !path.exists() {
+			return Ok(BaseSnapshot::default());
+		}
+		let db = Connection::open_with_flags(
+			path,
+			OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+		)?;
+		db.busy_timeout(BUSY_TIMEOUT)?;
+		let version: i64 = db.query_row(statements::GET_USER_VERSION, [], |row| row.get(0))?;
+		let supported = statements::MIGRATIONS.len() as i64;
+		if version > supported {
+			return Err(Error::UnsupportedStateVersion {
+				found: version,
+				supported,
+			});
+		}
+		// no tables yet
+		if version == 0 {
+			return Ok(BaseSnapshot::default());
+		}
+		let pair = db
+			.query_row(
+				statements::PAIR_SELECT_REMOTE_ROOT,
+				[path_to_sql(&record.local_root)?],
+				|row| Ok((PairId(row.get(0)?), row.get::<_, Uuid>(1)?)),
+			)
+			.optional()?;
+		match pair {
+			Some((id, remote_root)) if remote_root == record.remote_root => load_base(&db, id),
+			_ => Ok(BaseSnapshot::default()),
+		}
+	}
+
+	fn init(db: Connection) -> Result<Self, Error> {
+		// Per-connection settings: they revert on every open, so they're not part of a migration.
+		db.busy_timeout(BUSY_TIMEOUT)?;
+		db.execute_batch("PRAGMA foreign_keys = ON; PRAGMA synchronous = NORMAL;")?;
+		let mut state = Self { db };
+		state.migrate()?;
+		Ok(state)
+	}
+
+	/// Applies every migration newer than the database's `user_version`, all
+	/// in one transaction, so a crash mid-migration leaves the old schema intact.
+	fn migrate(&mut self) -> Result<(), Error> {
+		let version: i64 = self
+			.db
+			.query_row(statements::GET_USER_VERSION, [], |row| row.get(0))?;
+		let supported = statements::MIGRATIONS.len() as i64;
+		if version > supported {
+			return Err(Error::UnsupportedStateVersion {
+				found: version,
+				supported,
+			});
+		}
+
+		// `journal_mode` can't change inside a transaction; it's a no-op once set
+		if version == 0 {
+			self.db.pragma_update(None, "journal_mode", "WAL")?;
+		}
+		let tx = self
+			.db
+			.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+		for migration in &statements::MIGRATIONS[version as usize..] {
+			tx.execute_batch(migration)?;
+		}
+		tx.execute_batch(&statements::set_user_version(statements::MIGRATIONS.len()))?;
+		tx.commit()?;
+		Ok(())
+	}
+
+	/// Registers `record`, or updates the pair with the same local root.
+	///
+	/// If the pair already existed with a different remote root, its base
+	/// snapshot no longer describes anything and is cleared. A different mode
+	/// keeps it: the base is the same whichever way changes flow.
+	pub fn register_pair(&mut self, record: &SyncPairRecord) -> Result<PairId, Error> {
+		let local_root = path_to_sql(&record.local_root)?;
+		let global_ignore_file = record
+			.global_ignore_file
+			.as_deref()
+			.map(path_to_sql)
+			.transpose()?;
+		let sync_ignore_file = record
+			.sync_ignore_file
+			.as_deref()
+			.map(path_to_sql)
+			.transpose()?;
+
+		let tx = self.db.transaction()?;
+		let existing = tx
+			.query_row(statements::PAIR_SELECT_BY_ROOT, [local_root], pair_from_row)
+			.optional()?;
+		let id = match existing {
+			Some((id, existing)) => {
+				if existing.remote_root != record.remote_root {
+					tx.execute(statements::ENTRY_DELETE_ALL, [id.0])?;
+				}
+				tx.execute(
+					statements::PAIR_UPDATE,
+					params![
+						id.0,
+						record.remote_root,
+						global_ignore_file,
+						sync_ignore_file,
+						mode_to_sql(record.mode)
+					],
+				)?;
+				id
+			}
+			None => PairId(tx.query_row(
+				statements::PAIR_INSERT,
+				params![
+					local_root,
+					record.remote_root,
+					global_ignore_file,
+					sync_ignore_file,
+					mode_to_sql(record.mode)
+				],
+				|row| row.get(0),
+			)?),
+		};
+		tx.commit()?;
+		Ok(id)
+	}
+
+	/// Looks up the pair whose local root is `local_root`.
+	pub fn pair(&self, local_root: &Path) -> Result<Option<(PairId, SyncPairRecord)>, Error> {
+		Ok(self
+			.db
+			.query_row(
+				statements::PAIR_SELECT_BY_ROOT,
+				[path_to_sql(local_root)?],
+				pair_from_row,
+			)
+			.optional()?)
+	}
+
+	pub fn pairs(&self) -> Result<Vec<(PairId, SyncPairRecord)>, Error> {
+		let mut stmt = self.db.prepare(statements::PAIR_SELECT_ALL)?;
+		let pairs = stmt
+			.query_map([], pair_from_row)?
+			.collect::<rusqlite::Result<_>>()?;
+		Ok(pairs)
+	}
+
+	/// Removes a pair together with its base snapshot.
+	pub fn remove_pair(&mut self, id: PairId) -> Result<(), Error> {
+		self.db.execute(statements::PAIR_DELETE, [id.0])?;
+		Ok(())
+	}
+
+	/// Loads the base snapshot of a pair. A pair that never synced has an empty one.
+	pub fn load_base(&self, id: PairId) -> Result<BaseSnapshot, Error> {
+		load_base(&self.db, id)
+	}
+
+	/// Writes every journaled change of `base` in a single transaction.
+	///
+	/// Either all of them land or none do, so the stored snapshot always
+	/// matches a state `base` was in.
+	pub fn commit(&mut self, id: PairId, base: &mut BaseSnapshot) -> Result<(), Error> {
+		let changes = base.take_changes();
+		if changes.is_empty() {
+			return Ok(());
+		}
+		let tx = self.db.transaction()?;
+		{
+			let mut upsert = tx.prepare_cached(statements::ENTRY_UPSERT)?;
+			for change in changes {
+				match change {
+					BaseChange::Upsert(path, entry) => {
+						upsert.execute(params![
+							id.0,
+							path,
+							kind_to_sql(entry.local.kind),
+							entry.local.size,
+							entry.local.mtime,
+							entry.local.inode.map(|inode| inode as i64),
+							entry.remote.id,
+							entry.remote.version,
+							entry.remote.size,
+							entry.remote.mtime,
+							entry.remote.hash.as_ref().map(AsRef::<[u8; 32]>::as_ref),
+							entry.synced_hash.as_ref().map(AsRef::<[u8; 32]>::as_ref),
+						])?;
+					}
+					BaseChange::RemoveSubtree(path) if path.is_empty() => {
+						tx.execute(statements::ENTRY_DELETE_ALL, [id.0])?;
+					}
+					BaseChange::RemoveSubtree(path) => {
+						tx.execute(statements::ENTRY_DELETE_SUBTREE, params![id.0, path])?;
+					}
+					BaseChange::RebaseSubtree { from, to } => {
+						tx.execute(statements::ENTRY_REBASE_SUBTREE, params![id.0, from, to])?;
+					}
+				}
+			}
+		}
+		tx.commit()?;
+		Ok(())
+	}
+}
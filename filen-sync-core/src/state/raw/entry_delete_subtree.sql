@@ -0,0 +1,5 @@
+-- ?2 is a non-empty sync path; the root (`''`) is cleared with ENTRY_DELETE_ALL.
+DELETE FROM entries
+WHERE
+	pair_id = ?1
+	AND (path = ?2 OR substr(path, 1, length(?2) + 1) = ?2 || '/');
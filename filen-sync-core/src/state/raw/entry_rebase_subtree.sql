@@ -0,0 +1,7 @@
+-- Re-keys ?2 and everything below it to ?3. `OR REPLACE` drops rows already
+-- at a destination path, matching the in-memory `BTreeMap` overwrite.
+UPDATE OR REPLACE entries
+SET path = ?3 || substr(path, length(?2) + 1)
+WHERE
+	pair_id = ?1
+	AND (path = ?2 OR substr(path, 1, length(?2) + 1) = ?2 || '/');
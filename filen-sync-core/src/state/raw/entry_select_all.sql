@@ -0,0 +1,14 @@
+SELECT
+	path,
+	kind,
+	local_size,
+	local_mtime,
+	local_inode,
+	remote_id,
+	remote_version,
+	remote_size,
+	remote_mtime,
+	remote_hash,
+	synced_hash
+FROM entries
+WHERE pair_id = ?1;
@@ -0,0 +1,14 @@
+INSERT OR REPLACE INTO entries (
+	pair_id,
+	path,
+	kind,
+	local_size,
+	local_mtime,
+	local_inode,
+	remote_id,
+	remote_version,
+	remote_size,
+	remote_mtime,
+	remote_hash,
+	synced_hash
+) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12);
@@ -0,0 +1,34 @@
+-- Runs inside the migration transaction, so `journal_mode = WAL` (which
+-- can't change mid-transaction) is set by `SyncStateDb::migrate` instead.
+
+-- One row per sync pair. The local root identifies the pair; the remote
+-- root and ignore files are the rest of its configuration, kept together so
+-- the ignore stack can be rebuilt from the database alone.
+CREATE TABLE sync_pairs (
+	id INTEGER PRIMARY KEY NOT NULL,
+	local_root TEXT NOT NULL UNIQUE,
+	remote_root BLOB NOT NULL,
+	global_ignore_file TEXT,
+	sync_ignore_file TEXT
+);
+
+-- The base snapshot: every path that was in sync on both sides at the end
+-- of the last run, with what each side looked like at the time.
+CREATE TABLE entries (
+	pair_id INTEGER NOT NULL,
+	path TEXT NOT NULL,
+	-- 0 = file, 1 = dir
+	kind SMALLINT NOT NULL CHECK (kind IN (0, 1)),
+	local_size BIGINT NOT NULL,
+	local_mtime BIGINT NOT NULL,
+	-- u64 inode stored bit-for-bit as i64
+	local_inode BIGINT,
+	remote_id BLOB NOT NULL,
+	remote_version BLOB NOT NULL,
+	remote_size BIGINT NOT NULL,
+	remote_mtime BIGINT,
+	remote_hash BLOB,
+	synced_hash BLOB,
+	PRIMARY KEY (pair_id, path),
+	FOREIGN KEY (pair_id) REFERENCES sync_pairs (id) ON DELETE CASCADE
+) WITHOUT ROWID;
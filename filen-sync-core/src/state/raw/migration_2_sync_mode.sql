@@ -0,0 +1,3 @@
+-- 0 = two-way, 1 = mirror up, 2 = mirror down, 3 = backup only.
+-- Pairs registered before modes existed were two-way.
+ALTER TABLE sync_pairs ADD COLUMN mode SMALLINT NOT NULL DEFAULT 0 CHECK (mode IN (0, 1, 2, 3));
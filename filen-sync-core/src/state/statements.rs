@@ -0,0 +1,27 @@
+// Schema migrations, in order. `PRAGMA user_version` records how many have been applied,
+// so a migration must never be edited or removed once released; append a new one instead.
+pub(super) const MIGRATIONS: &[&str] = &[
+	include_str!("raw/migration_1_init.sql"),
+	include_str!("raw/migration_2_sync_mode.sql"),
+];
+
+pub(super) const GET_USER_VERSION: &str = "PRAGMA user_version;";
+// `PRAGMA` doesn't take bound parameters, so the version is formatted in.
+pub(super) fn set_user_version(version: usize) -> String {
+	format!("PRAGMA user_version = {version};")
+}
+
+pub(super) const PAIR_SELECT_BY_ROOT: &str = "SELECT id, local_root, remote_root, global_ignore_file, sync_ignore_file, mode FROM sync_pairs WHERE local_root = ?1";
+// Only columns every schema version has, for reading databases without migrating them.
+pub(super) const PAIR_SELECT_REMOTE_ROOT: &str =
+	"SELECT id, remote_root FROM sync_pairs WHERE local_root = ?1";
+pub(super) const PAIR_SELECT_ALL: &str = "SELECT id, local_root, remote_root, global_ignore_file, sync_ignore_file, mode FROM sync_pairs ORDER BY id";
+pub(super) const PAIR_INSERT: &str = "INSERT INTO sync_pairs (local_root, remote_root, global_ignore_file, sync_ignore_file, mode) VALUES (?1, ?2, ?3, ?4, ?5) RETURNING id";
+pub(super) const PAIR_UPDATE: &str = "UPDATE sync_pairs SET remote_root = ?2, global_ignore_file = ?3, sync_ignore_file = ?4, mode = ?5 WHERE id = ?1";
+pub(super) const PAIR_DELETE: &str = "DELETE FROM sync_pairs WHERE id = ?1";
+
+pub(super) const ENTRY_SELECT_ALL: &str = include_str!("raw/entry_select_all.sql");
+pub(super) const ENTRY_UPSERT: &str = include_str!("raw/entry_upsert.sql");
+pub(super) const ENTRY_DELETE_SUBTREE: &str = include_str!("raw/entry_delete_subtree.sql");
+pub(super) const ENTRY_DELETE_ALL: &str = "DELETE FROM entries WHERE pair_id = ?1";
+pub(super) const ENTRY_REBASE_SUBTREE: &str = include_str!("raw/entry_rebase_subtree.sql");
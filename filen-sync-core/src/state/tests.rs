@@ -0,0 +1,275 @@
+use super::*;
+
+// ── Fixtures ──
+
+fn record(local_root: &str, remote_root: u128) -> SyncPairRecord {
+	SyncPairRecord {
+		local_root: PathBuf::from(local_root),
+		remote_root: Uuid::from_u128(remote_root),
+		global_ignore_file: None,
+		sync_ignore_file: Some(PathBuf::from("/config/sync.filenignore")),
+		mode: SyncMode::MirrorUp,
+	}
+}
+
+fn file_entry(n: u64) -> BaseEntry {
+	BaseEntry::new(
+		LocalState {
+			kind: EntryKind::File,
+			size: n,
+			mtime: n as i64 * 1000,
+			// exercises the u64 -> i64 bit cast
+			inode: Some(u64::MAX - n),
+		},
+		RemoteState {
+			kind: EntryKind::File,
+			id: Uuid::from_u128(n as u128),
+			version: Uuid::from_u128(n as u128 + 1000),
+			size: n,
+			mtime: Some(n as i64 * 1000),
+			hash: Some(Blake3Hash::from([n as u8; 32])),
+		},
+	)
+}
+
+fn dir_entry(n: u64) -> BaseEntry {
+	BaseEntry::new(
+		LocalState {
+			kind: EntryKind::Dir,
+			size: 0,
+			mtime: 0,
+			inode: None,
+		},
+		RemoteState {
+			kind: EntryKind::Dir,
+			id: Uuid::from_u128(n as u128),
+			version: Uuid::from_u128(n as u128),
+			size: 0,
+			mtime: None,
+			hash: None,
+		},
+	)
+}
+
+fn keys(base: &BaseSnapshot) -> Vec<&str> {
+	base.entries().keys().map(String::as_str).collect()
+}
+
+// ── Schema tests ──
+
+#[test]
+fn fresh_db_is_fully_migrated() {
+	let state = SyncStateDb::open_in_memory().unwrap();
+	let version: i64 = state
+		.db
+		.query_row(statements::GET_USER_VERSION, [], |row| row.get(0))
+		.unwrap();
+	assert_eq!(version, statements::MIGRATIONS.len() as i64);
+}
+
+#[test]
+fn newer_schema_is_rejected() {
+	let dir = tempfile::tempdir().unwrap();
+	let path = dir.path().join("state.db");
+	drop(SyncStateDb::open(&path).unwrap());
+	Connection::open(&path)
+		.unwrap()
+		.execute_batch(&statements::set_user_version(
+			statements::MIGRATIONS.len() + 1,
+		))
+		.unwrap();
+
+	assert!(matches!(
+		SyncStateDb::open(&path),
+		Err(Error::UnsupportedStateVersion { .. })
+	));
+}
+
+#[test]
+fn reopen_keeps_pairs_and_entries() {
+	let dir = tempfile::tempdir().unwrap();
+	let path = dir.path().join("state.db");
+	let id = {
+		let mut state = SyncStateDb::open(&path).unwrap();
+		let id = state.register_pair(&record("/sync", 1)).unwrap();
+		let mut base = BaseSnapshot::default();
+		base.insert("a".into(), file_entry(1));
+		state.commit(id, &mut base).unwrap();
+		id
+	};
+
+	let state = SyncStateDb::open(&path).unwrap();
+	assert_eq!(
+		state.pair(Path::new("/sync")).unwrap(),
+		Some((id, record("/sync", 1)))
+	);
+	assert_eq!(state.load_base(id).unwrap().get("a"), Some(&file_entry(1)));
+}
+
+#[test]
+fn read_base_doesnt_write() {
+	let dir = tempfile::tempdir().unwrap();
+	let path = dir.path().join("state.db");
+	assert!(
+		SyncStateDb::read_base(&path, &record("/sync", 1))
+			.unwrap()
+			.entries()
+			.is_empty()
+	);
+	assert!(!path.exists());
+
+	{
+		let mut state = SyncStateDb::open(&path).unwrap();
+		let id = state.register_pair(&record("/sync", 1)).unwrap();
+		let mut base = BaseSnapshot::default();
+		base.insert("a".into(), file_entry(1));
+		state.commit(id, &mut base).unwrap();
+	}
+	let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+	assert_eq!(
+		keys(&SyncStateDb::read_base(&path, &record("/sync", 1)).unwrap()),
+		["a"]
+	);
+	// another remote root or local root has no base yet
+	assert!(
+		SyncStateDb::read_base(&path, &record("/sync", 2))
+			.unwrap()
+			.entries()
+			.is_empty()
+	);
+	assert!(
+		SyncStateDb::read_base(&path, &record("/other", 1))
+			.unwrap()
+			.entries()
+			.is_empty()
+	);
+	assert_eq!(
+		std::fs::metadata(&path).unwrap().modified().unwrap(),
+		modified
+	);
+	let state = SyncStateDb::open(&path).unwrap();
+	assert!(state.pair(Path::new("/other")).unwrap().is_none());
+}
+
+#[test]
+fn read_base_rejects_newer_schema() {
+	let dir = tempfile::tempdir().unwrap();
+	let path = dir.path().join("state.db");
+	drop(SyncStateDb::open(&path).unwrap());
+	Connection::open(&path)
+		.unwrap()
+		.execute_batch(&statements::set_user_version(
+			statements::MIGRATIONS.len() + 1,
+		))
+		.unwrap();
+
+	assert!(matches!(
+		SyncStateDb::read_base(&path, &record("/sync", 1)),
+		Err(Error::UnsupportedStateVersion { .. })
+	));
+}
+
+#[test]
+fn pairs_from_before_modes_are_two_way() {
+	let dir = tempfile::tempdir().unwrap();
+	let path = dir.path().join("state.db");
+	{
+		let db = Connection::open(&path).unwrap();
+		db.execute_batch(statements::MIGRATIONS[0]).unwrap();
+		db.execute_batch(&statements::set_user_version(1)).unwrap();
+		db.execute(
+			"INSERT INTO sync_pairs (local_root, remote_root) VALUES (?1, ?2)",
+			params!["/sync", Uuid::from_u128(1)],
+		)
+		.unwrap();
+	}
+
+	let state = SyncStateDb::open(&path).unwrap();
+	let (_, record) = state.pair(Path::new("/sync")).unwrap().unwrap();
+	assert_eq!(record.mode, SyncMode::TwoWay);
+}
+
+// ── Pair tests ──
+
+#[test]
+fn register_pair_updates_in_place() {
+	let mut state = SyncStateDb::open_in_memory().unwrap();
+	let id = state.register_pair(&record("/sync", 1)).unwrap();
+	let mut base = BaseSnapshot::default();
+	base.insert("a".into(), file_entry(1));
+	state.commit(id, &mut base).unwrap();
+
+	let mut updated = record("/sync", 1);
+	updated.global_ignore_file = Some(PathBuf::from("/config/global.filenignore"));
+	updated.mode = SyncMode::BackupOnly;
+	assert_eq!(state.register_pair(&updated).unwrap(), id);
+	assert_eq!(state.pairs().unwrap(), [(id, updated)]);
+	assert_eq!(keys(&state.load_base(id).unwrap()), ["a"]);
+}
+
+#[test]
+fn changing_remote_root_clears_base() {
+	let mut state = SyncStateDb::open_in_memory().unwrap();
+	let id = state.register_pair(&record("/sync", 1)).unwrap();
+	let mut base = BaseSnapshot::default();
+	base.insert("a".into(), file_entry(1));
+	state.commit(id, &mut base).unwrap();
+
+	assert_eq!(state.register_pair(&record("/sync", 2)).unwrap(), id);
+	assert!(state.load_base(id).unwrap().entries().is_empty());
+}
+
+#[test]
+fn pairs_are_isolated() {
+	let mut state = SyncStateDb::open_in_memory().unwrap();
+	let first = state.register_pair(&record("/one", 1)).unwrap();
+	let second = state.register_pair(&record("/two", 2)).unwrap();
+	let mut base = BaseSnapshot::default();
+	base.insert("a".into(), file_entry(1));
+	state.commit(first, &mut base).unwrap();
+
+	assert!(state.load_base(second).unwrap().entries().is_empty());
+	state.remove_pair(first).unwrap();
+	assert!(state.load_base(first).unwrap().entries().is_empty());
+	assert_eq!(state.pairs().unwrap().len(), 1);
+}
+
+// ── Journal tests ──
+
+#[test]
+fn committed_journal_matches_memory() {
+	let mut state = SyncStateDb::open_in_memory().unwrap();
+	let id = state.register_pair(&record("/sync", 1)).unwrap();
+
+	let mut base = BaseSnapshot::default();
+	base.insert("a".into(), dir_entry(1));
+	base.insert("a/x".into(), file_entry(2));
+	base.insert("a/y".into(), file_entry(3));
+	base.insert("a-b".into(), file_entry(4));
+	base.insert("b".into(), dir_entry(5));
+	base.insert("c".into(), file_entry(6));
+	state.commit(id, &mut base).unwrap();
+	assert!(!base.has_changes());
+
+	base.rebase_subtree("a", "b/a");
+	base.remove_subtree("b/a/y");
+	// overwrites the destination, like the in-memory map does
+	base.rebase_subtree("c", "a-b");
+	state.commit(id, &mut base).unwrap();
+
+	let loaded = state.load_base(id).unwrap();
+	assert_eq!(keys(&loaded), ["a-b", "b", "b/a", "b/a/x"]);
+	assert_eq!(loaded.entries(), base.entries());
+}
+
+#[test]
+fn removing_root_clears_everything() {
+	let mut state = SyncStateDb::open_in_memory().unwrap();
+	let id = state.register_pair(&record("/sync", 1)).unwrap();
+	let mut base = BaseSnapshot::default();
+	base.insert("a".into(), dir_entry(1));
+	base.insert("a/x".into(), file_entry(2));
+	base.remove_subtree("");
+	state.commit(id, &mut base).unwrap();
+	assert!(state.load_base(id).unwrap().entries().is_empty());
+}
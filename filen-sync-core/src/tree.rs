@@ -0,0 +1,217 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use filen_types::crypto::Blake3Hash;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// ── Entry state ──
+//
+// Sync paths are relative to the sync root, `/`-separated on every OS and
+// never start or end with a separator. The sync root itself is `""`.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EntryKind {
+	File,
+	Dir,
+}
+
+/// What a local entry looked like when it was scanned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalState {
+	pub kind: EntryKind,
+	/// Size in bytes, `0` for directories.
+	pub size: u64,
+	/// Last modification time in milliseconds since the Unix epoch.
+	pub mtime: i64,
+	/// Inode (or platform equivalent), used to detect local moves.
+	/// `None` where the platform doesn't expose a stable file id.
+	pub inode: Option<u64>,
+}
+
+impl LocalState {
+	/// Returns `true` if the entry's content may differ from `other`.
+	///
+	/// Directories never change content; their children are compared on their own.
+	pub fn content_differs(&self, other: &LocalState) -> bool {
+		self.kind != other.kind
+			|| (self.kind == EntryKind::File
+				&& (self.size != other.size || self.mtime != other.mtime))
+	}
+}
+
+/// What a remote entry looked like when it was listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteState {
+	pub kind: EntryKind,
+	/// Lifetime identity of the entry, used to detect remote moves.
+	/// The directory uuid, or the file's stable uuid.
+	pub id: Uuid,
+	/// The current version: the file uuid, which the server re-mints on every
+	/// content edit. Equal to `id` for directories.
+	pub version: Uuid,
+	/// Size in bytes, `0` for directories.
+	pub size: u64,
+	/// Last modification time in milliseconds since the Unix epoch, if known.
+	pub mtime: Option<i64>,
+	pub hash: Option<Blake3Hash>,
+}
+
+impl RemoteState {
+	/// Returns `true` if the entry's content may differ from `other`.
+	pub fn content_differs(&self, other: &RemoteState) -> bool {
+		self.kind != other.kind
+			|| (self.kind == EntryKind::File
+				&& (self.version != other.version || self.hash != other.hash))
+	}
+}
+
+// ── Trees ──
+
+/// Flat view of the local side of a sync pair, keyed by sync path.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LocalTree {
+	pub entries: BTreeMap<String, LocalState>,
+}
+
+/// Flat view of the remote side of a sync pair, keyed by sync path.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemoteTree {
+	pub entries: BTreeMap<String, RemoteState>,
+	/// Remote paths that exist but were excluded by the ignore stack.
+	/// Whole-directory downloads are only planned for subtrees without any.
+	pub ignored: BTreeSet<String>,
+}
+
+// ── Path helpers ──
+
+/// Returns the parent sync path, `""` for top-level entries and `None` for the root.
+pub fn parent_path(path: &str) -> Option<&str> {
+	if path.is_empty() {
+		return None;
+	}
+	Some(path.rsplit_once('/').map_or("", |(parent, _)| parent))
+}
+
+/// Returns the last component of a sync path.
+pub fn file_name(path: &str) -> &str {
+	path.rsplit_once('/').map_or(path, |(_, name)| name)
+}
+
+/// Joins a child name onto a sync path.
+pub fn join_path(parent: &str, name: &str) -> String {
+	if parent.is_empty() {
+		name.to_owned()
+	} else {
+		format!("{parent}/{name}")
+	}
+}
+
+/// Returns `true` if `path` is `ancestor` or lies below it.
+pub fn is_same_or_descendant(path: &str, ancestor: &str) -> bool {
+	ancestor.is_empty()
+		|| path == ancestor
+		|| (path.starts_with(ancestor) && path.as_bytes().get(ancestor.len()) == Some(&b'/'))
+}
+
+/// Re-roots `path` from `from` to `to`, or returns `None` if it isn't inside `from`.
+pub fn rebase_path(path: &str, from: &str, to: &str) -> Option<String> {
+	if !is_same_or_descendant(path, from) {
+		return None;
+	}
+	let rest = &path[from.len()..];
+	let rest = rest.strip_prefix('/').unwrap_or(rest);
+	Some(if rest.is_empty() {
+		to.to_owned()
+	} else {
+		join_path(to, rest)
+	})
+}
+
+/// Moves every key at or below `from` so it sits below `to` instead.
+pub fn rebase_subtree<V>(map: &mut BTreeMap<String, V>, from: &str, to: &str) {
+	let moved = take_subtree(map, from);
+	for (path, value) in moved {
+		// SAFETY: every taken key is inside `from`
+		map.insert(rebase_path(&path, from, to).unwrap(), value);
+	}
+}
+
+/// Removes and returns every entry at or below `path`.
+pub fn take_subtree<V>(map: &mut BTreeMap<String, V>, path: &str) -> Vec<(String, V)> {
+	let keys = subtree_keys(map, path);
+	keys.into_iter()
+		.filter_map(|k| map.remove_entry(&k))
+		.collect()
+}
+
+/// Returns the keys at or below `path`, in order.
+pub fn subtree_keys<V>(map: &BTreeMap<String, V>, path: &str) -> Vec<String> {
+	let mut keys = Vec::new();
+	if map.contains_key(path) {
+		keys.push(path.to_owned());
+	}
+	keys.extend(descendants(map, path).map(|(k, _)| k.clone()));
+	keys
+}
+
+/// Iterates the entries strictly below `path`.
+pub fn descendants<'a, V>(
+	map: &'a BTreeMap<String, V>,
+	path: &str,
+) -> impl Iterator<Item = (&'a String, &'a V)> + 'a {
+	let prefix = if path.is_empty() {
+		String::new()
+	} else {
+		format!("{path}/")
+	};
+	map.range(prefix.clone()..)
+		.take_while(move |(k, _)| k.starts_with(&prefix))
+		.filter(|(k, _)| !k.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parent_and_name() {
+		assert_eq!(parent_path("a/b/c"), Some("a/b"));
+		assert_eq!(parent_path("a"), Some(""));
+		assert_eq!(parent_path(""), None);
+		assert_eq!(file_name("a/b/c"), "c");
+		assert_eq!(file_name("a"), "a");
+	}
+
+	#[test]
+	fn descendant_check_respects_component_boundaries() {
+		assert!(is_same_or_descendant("a/b", "a"));
+		assert!(is_same_or_descendant("a", "a"));
+		assert!(!is_same_or_descendant("ab", "a"));
+		assert!(is_same_or_descendant("anything", ""));
+	}
+
+	#[test]
+	fn rebase() {
+		assert_eq!(rebase_path("a/b/c", "a/b", "x"), Some("x/c".into()));
+		assert_eq!(rebase_path("a/b", "a/b", "x/y"), Some("x/y".into()));
+		assert_eq!(rebase_path("a/bc", "a/b", "x"), None);
+	}
+
+	#[test]
+	fn rebase_subtree_moves_children_only() {
+		let mut map = BTreeMap::from([
+			("a".to_owned(), 1),
+			("a/x".to_owned(), 2),
+			("a-b".to_owned(), 3),
+			("ab".to_owned(), 4),
+		]);
+		rebase_subtree(&mut map, "a", "z");
+		assert_eq!(
+			map.keys().map(String::as_str).collect::<Vec<_>>(),
+			["a-b", "ab", "z", "z/x"]
+		);
+	}
+}
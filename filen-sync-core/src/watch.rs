@@ -0,0 +1,225 @@
+use std::{
+	collections::BTreeSet,
+	path::{Component, Path, PathBuf},
+	time::Duration,
+};
+
+use notify_debouncer_full::{
+	DebounceEventResult, DebouncedEvent, Debouncer, RecommendedCache, new_debouncer,
+	notify::{EventKind, RecommendedWatcher, RecursiveMode},
+};
+use tokio::sync::mpsc;
+
+use crate::Error;
+
+// ── Change sets ──
+
+/// Local paths that changed since the last change set, for
+/// [`SyncEngine::prepare_changed`](crate::engine::SyncEngine::prepare_changed).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LocalChangeSet {
+	/// Sync paths of every entry that was created, modified, removed, or
+	/// renamed (both ends). Ancestors of a changed entry aren't included.
+	pub paths: BTreeSet<String>,
+	/// The watcher lost track of events, so only a full scan is reliable.
+	pub rescan: bool,
+}
+
+impl LocalChangeSet {
+	pub fn is_empty(&self) -> bool {
+		self.paths.is_empty() && !self.rescan
+	}
+
+	pub fn merge(&mut self, other: LocalChangeSet) {
+		self.paths.extend(other.paths);
+		self.rescan |= other.rescan;
+	}
+}
+
+/// Returns the sync path of `path` below `root`, `None` if it's elsewhere or
+/// not valid UTF-8.
+fn sync_path(root: &Path, path: &Path) -> Option<String> {
+	let rel = path.strip_prefix(root).ok()?;
+	let mut components = Vec::new();
+	for component in rel.components() {
+		match component {
+			Component::Normal(name) => components.push(name.to_str()?),
+			_ => return None,
+		}
+	}
+	Some(components.join("/"))
+}
+
+/// Turns a batch of debounced events into a change set.
+pub(crate) fn change_set(root: &Path, events: &[DebouncedEvent]) -> LocalChangeSet {
+	let mut changes = LocalChangeSet::default();
+	for event in events {
+		if event.need_rescan() {
+			changes.rescan = true;
+		}
+		// reads and opens don't change anything
+		if matches!(event.kind, EventKind::Access(_)) {
+			continue;
+		}
+		for path in &event.paths {
+			match sync_path(root, path) {
+				// the root's own metadata doesn't matter, but losing it does
+				Some(path) if path.is_empty() => {
+					changes.rescan |= matches!(event.kind, EventKind::Remove(_));
+				}
+				Some(path) => {
+					changes.paths.insert(path);
+				}
+				None => {}
+			}
+		}
+	}
+	changes
+}
+
+// ── LocalWatcher ──
+
+/// Watches a sync root for changes, in batches.
+///
+/// Bursts of events are debounced into one [`LocalChangeSet`]. Renames
+/// within the root arrive as one event with both ends, so both land in the
+/// same change set and the planner sees a move rather than a delete and an
+/// unrelated create.
+pub struct LocalWatcher {
+	root: PathBuf,
+	rx: mpsc::UnboundedReceiver<DebounceEventResult>,
+	// dropping it stops the watcher thread
+	_debouncer: Debouncer<RecommendedWatcher, RecommendedCache>,
+}
+
+impl LocalWatcher {
+	/// Starts watching `root` recursively. Changes are reported once no new
+	/// event arrived for `debounce`.
+	pub fn new(root: &Path, debounce: Duration) -> Result<Self, Error> {
+		let (tx, rx) = mpsc::unbounded_channel();
+		let mut debouncer = new_debouncer(debounce, None, move |result| {
+			// a closed receiver means the watcher is being dropped
+			let _ = tx.send(result);
+		})?;
+		debouncer.watch(root, RecursiveMode::Recursive)?;
+		Ok(Self {
+			root: root.to_path_buf(),
+			rx,
+			_debouncer: debouncer,
+		})
+	}
+
+	pub fn root(&self) -> &Path {
+		&self.root
+	}
+
+	/// Waits for the next non-empty change set, merged with any other batches
+	/// that are already waiting.
+	///
+	/// Watcher errors can mean missed events, so they're reported as a change
+	/// set that asks for a full rescan.
+	pub async fn next(&mut self) -> Option<LocalChangeSet> {
+		loop {
+			let result = self.rx.recv().await?;
+			let mut changes = self.convert(result);
+			while let Ok(result) = self.rx.try_recv() {
+				changes.merge(self.convert(result));
+			}
+			if !changes.is_empty() {
+				return Some(changes);
+			}
+		}
+	}
+
+	fn convert(&self, result: DebounceEventResult) -> LocalChangeSet {
+		match result {
+			Ok(events) => change_set(&self.root, &events),
+			Err(_) => LocalChangeSet {
+				rescan: true,
+				..Default::default()
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Instant;
+
+	use notify_debouncer_full::notify::{
+		Event,
+		event::{AccessKind, CreateKind, Flag, ModifyKind, RemoveKind, RenameMode},
+	};
+
+	use super::*;
+
+	fn event(kind: EventKind, paths: &[&str]) -> DebouncedEvent {
+		let mut event = Event::new(kind);
+		for path in paths {
+			event = event.add_path(PathBuf::from(path));
+		}
+		DebouncedEvent::new(event, Instant::now())
+	}
+
+	fn paths(changes: &LocalChangeSet) -> Vec<&str> {
+		changes.paths.iter().map(String::as_str).collect()
+	}
+
+	#[test]
+	fn events_become_sync_paths() {
+		let changes = change_set(
+			Path::new("/sync"),
+			&[
+				event(EventKind::Create(CreateKind::File), &["/sync/a/new"]),
+				event(
+					EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+					&["/sync/old", "/sync/b/renamed"],
+				),
+				event(EventKind::Access(AccessKind::Read), &["/sync/read"]),
+				event(EventKind::Remove(RemoveKind::File), &["/elsewhere/x"]),
+			],
+		);
+		assert_eq!(paths(&changes), ["a/new", "b/renamed", "old"]);
+		assert!(!changes.rescan);
+	}
+
+	#[test]
+	fn lost_events_or_root_ask_for_rescan() {
+		let root = Path::new("/sync");
+		let overflow = event(EventKind::Other, &[]);
+		let overflow = DebouncedEvent::new(overflow.event.set_flag(Flag::Rescan), Instant::now());
+		assert!(change_set(root, &[overflow]).rescan);
+
+		let root_removed = event(EventKind::Remove(RemoveKind::Folder), &["/sync"]);
+		assert!(change_set(root, &[root_removed]).rescan);
+
+		let root_touched = event(EventKind::Modify(ModifyKind::Any), &["/sync"]);
+		assert!(change_set(root, &[root_touched]).is_empty());
+	}
+
+	#[tokio::test(flavor = "current_thread")]
+	async fn watcher_reports_changes() {
+		let dir = tempfile::tempdir().unwrap();
+		let root = dir.path().canonicalize().unwrap();
+		std::fs::create_dir(root.join("a")).unwrap();
+		let mut watcher = LocalWatcher::new(&root, Duration::from_millis(50)).unwrap();
+
+		// files written into a brand new directory can be missed, which is why
+		// new directories are always scanned in full
+		std::fs::write(root.join("a/x"), b"x").unwrap();
+		std::fs::create_dir(root.join("b")).unwrap();
+
+		let mut seen = LocalChangeSet::default();
+		let deadline = Instant::now() + Duration::from_secs(10);
+		while !(seen.paths.contains("a/x") && seen.paths.contains("b")) && Instant::now() < deadline
+		{
+			match tokio::time::timeout(Duration::from_secs(1), watcher.next()).await {
+				Ok(Some(changes)) => seen.merge(changes),
+				Ok(None) => break,
+				Err(_) => {}
+			}
+		}
+		assert!(seen.paths.contains("a/x"), "{seen:?}");
+		assert!(seen.paths.contains("b"), "{seen:?}");
+	}
+}
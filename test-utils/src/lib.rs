@@ -7,8 +7,10 @@ use std::{
 
 use anyhow::{Context, Result};
 use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use filen_fake_server::FakeServer;
 use filen_sdk_rs::{
 	auth::{Client, http::ClientConfig, unauth::UnauthClient},
+	consts::ApiEndpoints,
 	fs::{
 		HasName, HasUUID,
 		categories::{RootItemType, Shared},
@@ -23,6 +25,8 @@ use tokio::sync::OnceCell;
 pub struct Resources {
 	client: OnceCell<Arc<Client>>,
 	account_prefix: &'static str,
+	/// Always use the fake server, whatever `FILEN_FAKE_SERVER` says
+	hermetic: bool,
 }
 
 pub struct TestResources {
@@ -76,6 +80,9 @@ impl Resources {
 	pub async fn client(&self) -> Arc<Client> {
 		self.client
 			.get_or_init(|| async {
+				if self.hermetic || use_fake_server() {
+					return Arc::new(self.fake_server_client().await);
+				}
 				let (email, password, two_factor_code) = self.get_credentials();
 				let client = UnauthClient::from_config(ClientConfig::default())
 					.unwrap()
@@ -91,6 +98,25 @@ impl Resources {
 			.clone()
 	}
 
+	/// Registers an account for this prefix on the fake server and logs into it
+	async fn fake_server_client(&self) -> Client {
+		let email = format!(
+			"{}@fake.filen.test",
+			self.account_prefix.to_ascii_lowercase()
+		);
+		let password = "fake-server-password";
+		let unauth = UnauthClient::from_config(
+			ClientConfig::default()
+				.with_endpoints(ApiEndpoints::single(fake_server().await.url()).unwrap()),
+		)
+		.unwrap();
+		unauth
+			.register(email.clone(), password, None, None)
+			.await
+			.unwrap();
+		unauth.login(email, password, "XXXXXX").await.unwrap()
+	}
+
 	pub async fn get_resources(&self) -> TestResources {
 		let name = format!(
 			"rs-{}",
@@ -129,6 +155,27 @@ impl Resources {
 }
 
 static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+static FAKE_SERVER: OnceCell<FakeServer> = OnceCell::const_new();
+
+/// Whether tests run against the in-memory fake server instead of the real backend. Set
+/// `FILEN_FAKE_SERVER=1` to run without test accounts or network access.
+pub fn use_fake_server() -> bool {
+	dotenv::dotenv().ok();
+	env::var("FILEN_FAKE_SERVER").as_deref() == Ok("1")
+}
+
+/// The fake server shared by all tests of this process. It runs on [`rt`], so it outlives the
+/// runtimes of single tests.
+pub async fn fake_server() -> &'static FakeServer {
+	FAKE_SERVER
+		.get_or_init(|| async {
+			rt().spawn(FakeServer::start())
+				.await
+				.expect("fake server task panicked")
+				.expect("Failed to start fake server")
+		})
+		.await
+}
 
 pub fn rt() -> &'static tokio::runtime::Runtime {
 	RUNTIME.get_or_init(|| {
@@ -154,11 +201,20 @@ pub fn rt() -> &'static tokio::runtime::Runtime {
 pub static RESOURCES: Resources = Resources {
 	client: OnceCell::const_new(),
 	account_prefix: "TEST",
+	hermetic: false,
 };
 
 pub static SHARE_RESOURCES: Resources = Resources {
 	client: OnceCell::const_new(),
 	account_prefix: "TEST_SHARE",
+	hermetic: false,
+};
+
+/// An account on the fake server, for tests that must run without network access
+pub static FAKE_RESOURCES: Resources = Resources {
+	client: OnceCell::const_new(),
+	account_prefix: "FAKE",
+	hermetic: true,
 };
 
 pub async fn set_up_contact_no_add<'a>(